@@ -0,0 +1,245 @@
+//! `spicy_cli run`: every analysis of a set of netlists, summarized for CI.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use clap::Args;
+use spicy_parser::{Compatibility, ParseOptions, parse};
+use spicy_simulate::{LinearSolver, SimulationConfig, TimestepConfig, simulate_steps};
+
+use crate::{is_schematic, read_input};
+
+#[derive(Args, Debug)]
+pub struct BatchArgs {
+    /// Netlists to run, or glob patterns of them (quote them: 'benches/*.spicy')
+    #[arg(value_name = "NETLIST", required = true)]
+    inputs: Vec<String>,
+
+    /// Write a JUnit XML summary to FILE, one test case per netlist
+    #[arg(long, value_name = "FILE")]
+    junit: Option<PathBuf>,
+
+    /// Linear solver: klu, blas, or auto to pick by the size and density of the matrix
+    #[arg(long, value_name = "SOLVER")]
+    solver: Option<LinearSolver>,
+
+    /// Choose the transient timestep from the truncation error instead of using tstep
+    #[arg(long)]
+    adaptive_step: bool,
+
+    /// Read the netlists in the ngspice dialect
+    #[arg(long)]
+    ngspice: bool,
+}
+
+/// How the run of one netlist went.
+#[derive(Debug)]
+enum Outcome {
+    Passed,
+    /// The netlist could not be read or parsed.
+    Error(String),
+    /// An analysis failed.
+    Failed(String),
+}
+
+#[derive(Debug)]
+struct NetlistRun {
+    path: PathBuf,
+    outcome: Outcome,
+    /// Parse and simulation time.
+    duration: Duration,
+    analyses: usize,
+    warnings: usize,
+    /// Newton iterations of the hardest point of any analysis.
+    max_newton_iterations: Option<usize>,
+}
+
+/// The netlists `inputs` name: each pattern's matches in order, a plain path as is.
+fn expand_inputs(inputs: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut paths = Vec::new();
+    for input in inputs {
+        if !input.contains(['*', '?', '[']) {
+            paths.push(PathBuf::from(input));
+            continue;
+        }
+        let matches = glob::glob(input).map_err(|e| format!("Bad pattern {input}: {e}"))?;
+        let before = paths.len();
+        for entry in matches {
+            paths.push(entry.map_err(|e| format!("Failed to read {input}: {e}"))?);
+        }
+        if paths.len() == before {
+            return Err(format!("No netlist matches {input}"));
+        }
+    }
+    Ok(paths)
+}
+
+fn run_netlist(path: &Path, args: &BatchArgs) -> NetlistRun {
+    let start = Instant::now();
+    let mut run = NetlistRun {
+        path: path.to_path_buf(),
+        outcome: Outcome::Passed,
+        duration: Duration::ZERO,
+        analyses: 0,
+        warnings: 0,
+        max_newton_iterations: None,
+    };
+    run.outcome = match read_input(path) {
+        Err(e) => Outcome::Error(e),
+        Ok(input) => {
+            let mut options = ParseOptions::new_with_source(path, input);
+            if args.ngspice || is_schematic(path) {
+                options.compatibility = Compatibility::Ngspice;
+            }
+            match parse(&mut options) {
+                Err(e) => Outcome::Error(format!("Parse error: {e}")),
+                Ok(deck) => {
+                    let config = SimulationConfig {
+                        solver: args
+                            .solver
+                            .clone()
+                            .unwrap_or_else(|| SimulationConfig::default().solver),
+                        timestep: TimestepConfig {
+                            adaptive: args.adaptive_step,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    };
+                    match simulate_steps(&mut options, deck, config) {
+                        Err(e) => Outcome::Failed(format!("Simulation error: {e}")),
+                        Ok(report) => {
+                            run.analyses = report.analyses.len();
+                            run.warnings = report.warnings().len();
+                            run.max_newton_iterations = report
+                                .analyses
+                                .iter()
+                                .filter_map(|a| a.result.max_newton_iterations())
+                                .max();
+                            Outcome::Passed
+                        }
+                    }
+                }
+            }
+        }
+    };
+    run.duration = start.elapsed();
+    run
+}
+
+/// Text with the XML special characters escaped, for an attribute or element.
+fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// The runs as a JUnit XML report: a netlist that does not parse is an error, one whose
+/// analysis fails a failure.
+fn junit_report(runs: &[NetlistRun]) -> String {
+    let count = |pick: fn(&Outcome) -> bool| runs.iter().filter(|r| pick(&r.outcome)).count();
+    let failures = count(|o| matches!(o, Outcome::Failed(_)));
+    let errors = count(|o| matches!(o, Outcome::Error(_)));
+    let time: f64 = runs.iter().map(|r| r.duration.as_secs_f64()).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let totals = format!(
+        "tests=\"{}\" failures=\"{failures}\" errors=\"{errors}\" time=\"{time:.6}\"",
+        runs.len()
+    );
+    let _ = writeln!(xml, "<testsuites name=\"spicy\" {totals}>");
+    let _ = writeln!(xml, "  <testsuite name=\"spicy\" {totals}>");
+    for run in runs {
+        let name = escape_xml(&run.path.display().to_string());
+        let _ = writeln!(
+            xml,
+            "    <testcase name=\"{name}\" classname=\"spicy\" time=\"{:.6}\">",
+            run.duration.as_secs_f64()
+        );
+        let _ = writeln!(xml, "      <properties>");
+        let mut properties = vec![
+            ("analyses", run.analyses.to_string()),
+            ("warnings", run.warnings.to_string()),
+        ];
+        if let Some(iterations) = run.max_newton_iterations {
+            properties.push(("max_newton_iterations", iterations.to_string()));
+        }
+        for (name, value) in properties {
+            let _ = writeln!(xml, "        <property name=\"{name}\" value=\"{value}\"/>");
+        }
+        let _ = writeln!(xml, "      </properties>");
+        let problem = match &run.outcome {
+            Outcome::Passed => None,
+            Outcome::Error(message) => Some(("error", message)),
+            Outcome::Failed(message) => Some(("failure", message)),
+        };
+        if let Some((tag, message)) = problem {
+            let message = escape_xml(message);
+            let _ = writeln!(xml, "      <{tag} message=\"{message}\">{message}</{tag}>");
+        }
+        let _ = writeln!(xml, "    </testcase>");
+    }
+    let _ = writeln!(xml, "  </testsuite>");
+    let _ = writeln!(xml, "</testsuites>");
+    xml
+}
+
+/// Run every netlist of `args`, print a line per netlist and write the JUnit summary.
+/// Returns the exit code: 0 when every netlist passed, 1 otherwise.
+pub fn run_batch(args: &BatchArgs) -> i32 {
+    let paths = match expand_inputs(&args.inputs) {
+        Ok(paths) => paths,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+
+    let mut runs = Vec::with_capacity(paths.len());
+    for path in &paths {
+        let run = run_netlist(path, args);
+        let iterations = run
+            .max_newton_iterations
+            .map_or("-".to_string(), |i| i.to_string());
+        let (status, message) = match &run.outcome {
+            Outcome::Passed => ("PASS", None),
+            Outcome::Error(message) => ("ERROR", Some(message)),
+            Outcome::Failed(message) => ("FAIL", Some(message)),
+        };
+        println!(
+            "{status:<5} {}  {:.3} ms  analyses {}  max newton {iterations}  warnings {}",
+            run.path.display(),
+            run.duration.as_secs_f64() * 1e3,
+            run.analyses,
+            run.warnings,
+        );
+        if let Some(message) = message {
+            println!("      {}", message.replace('\n', "\n      "));
+        }
+        runs.push(run);
+    }
+
+    let passed = runs
+        .iter()
+        .filter(|r| matches!(r.outcome, Outcome::Passed))
+        .count();
+    println!("{passed} of {} netlists passed", runs.len());
+
+    if let Some(junit) = &args.junit
+        && let Err(e) = fs::write(junit, junit_report(&runs))
+    {
+        eprintln!("Failed to write {}: {}", junit.display(), e);
+        return 1;
+    }
+    if passed == runs.len() { 0 } else { 1 }
+}
@@ -0,0 +1,141 @@
+//! `spicy_cli check`: parse and lint a netlist without simulating it.
+
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use spicy_parser::diagnostic::{Diagnostic, Severity};
+use spicy_parser::lint::{LintWarning, lint_deck};
+use spicy_parser::{Compatibility, ParseOptions, SourceMap, Span, parse_recovering};
+
+use crate::diagnostics::{self, DiagnosticsFormat};
+use crate::tui::ui::{format_error_snippet, span_location};
+use crate::{is_schematic, parse_define, path_variables, read_input};
+
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+    /// Netlist to check, or an LTspice .asc schematic
+    #[arg(value_name = "NETLIST")]
+    netlist: PathBuf,
+
+    /// Read the netlist in the ngspice dialect
+    #[arg(long)]
+    ngspice: bool,
+
+    /// Set the `${NAME}` variable of .include and .lib paths, over the environment variable
+    #[arg(long = "define", value_name = "NAME=VALUE", value_parser = parse_define)]
+    defines: Vec<(String, String)>,
+
+    /// Exit nonzero on warnings too
+    #[arg(long)]
+    deny_warnings: bool,
+
+    /// Report errors and warnings as text or as one JSON object per line on stderr
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t)]
+    diagnostics: DiagnosticsFormat,
+}
+
+fn report(path: &Path, source_map: &SourceMap, diagnostic: &Diagnostic, span: Option<Span>) {
+    let message = &diagnostic.message;
+    let label = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+    match span {
+        Some(span) => {
+            eprintln!("{}: {label}: {message}", span_location(source_map, span));
+            let src = source_map.get_content(span.source_index);
+            if let Some(snippet) = format_error_snippet(src, span) {
+                eprint!("{snippet}");
+            }
+        }
+        None => eprintln!("{}: {label}: {message}", path.display()),
+    }
+}
+
+fn count(n: usize, what: &str) -> String {
+    if n == 1 {
+        format!("1 {what}")
+    } else {
+        format!("{n} {what}s")
+    }
+}
+
+/// A lint that makes the analyses needing an operating point fail, rather than a suspicion.
+fn is_error(warning: &LintWarning) -> bool {
+    match warning {
+        LintWarning::Topology(_) => true,
+        LintWarning::Parameter(error) => error.is_error(),
+        LintWarning::DanglingNode { .. } => false,
+    }
+}
+
+/// Parse and lint the netlist of `args`, printing every diagnostic with its snippet.
+/// Returns the exit code: 2 for a parse error and 3 for a topology or parameter error, as a
+/// simulation exits with, 1 for a warning with `--deny-warnings` and 0 otherwise.
+pub fn run_check(args: &CheckArgs) -> i32 {
+    let path = args.netlist.as_path();
+    let input = match read_input(path) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+    let mut options = ParseOptions::new_with_source(path, input);
+    options.variables = path_variables(&args.defines);
+    if args.ngspice || is_schematic(path) {
+        options.compatibility = Compatibility::Ngspice;
+    }
+
+    let json = args.diagnostics == DiagnosticsFormat::Json;
+
+    let (deck, errors) = parse_recovering(&mut options);
+    let deck = match deck {
+        Some(deck) if errors.is_empty() => deck,
+        _ => {
+            for e in &errors {
+                let diagnostic = Diagnostic::from_error(e, &options.source_map);
+                if json {
+                    diagnostics::emit(&diagnostic);
+                } else {
+                    report(path, &options.source_map, &diagnostic, e.error_span());
+                }
+            }
+            if !json {
+                eprintln!("{}: {}", path.display(), count(errors.len(), "error"));
+            }
+            return 2;
+        }
+    };
+
+    let lints = lint_deck(&deck);
+    let errors = lints.iter().filter(|w| is_error(w)).count();
+    let warnings = lints.len() - errors;
+    for lint in &lints {
+        let mut diagnostic = Diagnostic::from_lint(lint, &options.source_map);
+        if is_error(lint) {
+            diagnostic.severity = Severity::Error;
+        }
+        if json {
+            diagnostics::emit(&diagnostic);
+        } else {
+            report(path, &options.source_map, &diagnostic, lint.span());
+        }
+    }
+    if !json {
+        eprintln!(
+            "{}: {}, {}",
+            path.display(),
+            count(errors, "error"),
+            count(warnings, "warning")
+        );
+    }
+
+    if errors > 0 {
+        3
+    } else if warnings > 0 && args.deny_warnings {
+        1
+    } else {
+        0
+    }
+}
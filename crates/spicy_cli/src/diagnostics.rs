@@ -0,0 +1,71 @@
+//! `--diagnostics json`: parse errors, lints and simulation errors as one JSON object per line
+//! on stderr, for editors and CI tools.
+
+use clap::ValueEnum;
+use spicy_parser::SourceMap;
+use spicy_parser::diagnostic::{Diagnostic, Severity};
+use spicy_simulate::{SimulationError, SimulationWarning};
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiagnosticsFormat {
+    /// Messages with a snippet of the netlist
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+pub fn emit(diagnostic: &Diagnostic) {
+    match serde_json::to_string(diagnostic) {
+        Ok(json) => eprintln!("{json}"),
+        Err(e) => eprintln!("Failed to serialize a diagnostic: {e}"),
+    }
+}
+
+pub fn simulation_warning(warning: &SimulationWarning, source_map: &SourceMap) -> Diagnostic {
+    Diagnostic::new(
+        warning.code(),
+        Severity::Warning,
+        warning.to_string(),
+        None,
+        source_map,
+    )
+}
+
+/// A diagnostic per topology or parameter error, with its span, or one for any other error.
+pub fn simulation_error(error: &SimulationError, source_map: &SourceMap) -> Vec<Diagnostic> {
+    match error {
+        SimulationError::Topology(errors) => errors
+            .iter()
+            .map(|e| {
+                Diagnostic::new(
+                    e.code(),
+                    Severity::Error,
+                    e.to_string(),
+                    e.error_span(),
+                    source_map,
+                )
+            })
+            .collect(),
+        SimulationError::Parameters(errors) => errors
+            .iter()
+            .map(|e| {
+                Diagnostic::new(
+                    e.code(),
+                    Severity::Error,
+                    e.to_string(),
+                    e.error_span(),
+                    source_map,
+                )
+            })
+            .collect(),
+        SimulationError::Parse(e) => vec![Diagnostic::from_error(e, source_map)],
+        e => vec![Diagnostic::new(
+            e.code(),
+            Severity::Error,
+            e.to_string(),
+            None,
+            source_map,
+        )],
+    }
+}
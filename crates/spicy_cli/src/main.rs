@@ -1,33 +1,160 @@
+use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
+use std::sync::Arc;
 
-use clap::Parser;
-use spicy_parser::{ParseOptions, parse};
-use spicy_simulate::{SimulationConfig, simulate};
+use clap::{Parser, Subcommand};
+use spicy_parser::{
+    Compatibility, ParseOptions, SourceMap, Span,
+    asc::{asc_to_netlist, decode_asc},
+    diagnostic::Diagnostic,
+    lint::lint_deck,
+    parse,
+};
+use spicy_simulate::{
+    BackendSelection, Checkpoint, ExportFormat, LinearSolver, MatrixDump, RawFormat,
+    SimulateObserver, SimulationConfig, SimulationError, SolverBackend, TimestepConfig,
+    ipc::IpcEndpoint, simulate_steps,
+};
 
-use crate::tui::ui::format_error_snippet; // kept for non-TUI mode
+use crate::diagnostics::DiagnosticsFormat;
+use crate::tui::ui::render_error_snippet; // kept for non-TUI mode
 
+mod batch;
+mod check;
+mod diagnostics;
+mod progress;
 mod tui;
+mod watch;
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run every analysis of several netlists and summarize them, for CI
+    Run(batch::BatchArgs),
+    /// Parse and lint a netlist without simulating it
+    Check(check::CheckArgs),
+}
 
 #[derive(Parser, Debug)]
-#[command(name = "spicy_cli", about = "Spicy circuit simulator", version)]
+#[command(
+    name = "spicy_cli",
+    about = "Spicy circuit simulator",
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Run interactive TUI
     #[arg(long)]
     tui: bool,
 
+    /// Rerun the analyses whenever the netlist or a file it includes changes, printing the
+    /// measurements and operating point values that moved
+    #[arg(long, conflicts_with = "tui")]
+    watch: bool,
+
     /// Write LTSpice .raw output alongside input name
     #[arg(long)]
     raw: bool,
 
-    /// Input netlist file
+    /// Write the .raw values as text instead of binary
+    #[arg(long, requires = "raw")]
+    ascii: bool,
+
+    /// Write the output files to DIR instead of the current directory
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<std::path::PathBuf>,
+
+    /// Name of the output files of each analysis: {deck} is the netlist name, {analysis} the
+    /// kind of analysis and {index} counts the analyses of that kind from 1
+    #[arg(long, value_name = "TEMPLATE")]
+    output_name: Option<String>,
+
+    /// Also write every analysis as a table next to the input (csv or ndjson)
+    #[arg(long, value_name = "FORMAT")]
+    format: Option<ExportFormat>,
+
+    /// Print every operating point with the voltages, currents and power of each device
+    #[arg(long)]
+    op_report: bool,
+
+    /// Choose the transient timestep from the truncation error instead of using tstep
+    #[arg(long)]
+    adaptive_step: bool,
+
+    /// Merge the nodes of the inductors of .op and .dc analyses instead of solving for their
+    /// currents, which are recovered afterwards
+    #[arg(long)]
+    collapse_inductors: bool,
+
+    /// Linear solver: klu, blas, or auto to pick by the size and density of the matrix
+    #[arg(long, value_name = "SOLVER")]
+    solver: Option<LinearSolver>,
+
+    /// Solve every matrix with this backend (klu, blas or dense) instead of picking one by
+    /// its size and density; implies --solver auto
+    #[arg(long, value_name = "BACKEND")]
+    backend: Option<SolverBackend>,
+
+    /// Write the MNA matrix and right-hand side of the first operating point to <BASE>.mtx
+    /// and <BASE>_rhs.mtx (Matrix Market)
+    #[arg(long, value_name = "BASE")]
+    dump_matrix: Option<String>,
+
+    /// Dump the first transient point at or after this time (seconds) instead
+    #[arg(long, value_name = "TIME", requires = "dump_matrix")]
+    dump_at: Option<f64>,
+
+    /// Save the state of the transient analysis to FILE, and its points to FILE.points, every
+    /// --checkpoint-every time points
+    #[arg(long, value_name = "FILE")]
+    checkpoint: Option<std::path::PathBuf>,
+
+    /// Accepted time points between two checkpoints
+    #[arg(long, value_name = "POINTS", default_value_t = 1000)]
+    checkpoint_every: usize,
+
+    /// Carry on the transient analysis from the --checkpoint file of an earlier run
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+
+    /// Read the netlist in the ngspice dialect: `;` and `$` comments, `MEG` in any case, ...
+    #[arg(long)]
+    ngspice: bool,
+
+    /// Set the `${NAME}` variable of .include and .lib paths, over the environment variable
+    #[arg(long = "define", value_name = "NAME=VALUE", value_parser = parse_define)]
+    defines: Vec<(String, String)>,
+
+    /// Stream matrices and waveforms to a viewer (tcp://host:port or unix:///path)
+    #[arg(long, value_name = "ENDPOINT")]
+    ipc: Option<IpcEndpoint>,
+
+    /// Do not draw a progress bar of the DC sweeps and transients on stderr
+    #[arg(long)]
+    no_progress: bool,
+
+    /// Report errors and warnings as text or as one JSON object per line on stderr
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t)]
+    diagnostics: DiagnosticsFormat,
+
+    /// Input netlist file, or an LTspice .asc schematic
     #[arg(value_name = "NETLIST", required_unless_present = "tui")]
     netlist: Option<String>,
 }
 
 fn main() {
     let args = Args::parse();
+    match &args.command {
+        Some(Command::Run(batch)) => std::process::exit(batch::run_batch(batch)),
+        Some(Command::Check(check)) => std::process::exit(check::run_check(check)),
+        None => {}
+    }
 
-    let path = args.netlist.unwrap_or_else(|| {
+    let path = args.netlist.clone().unwrap_or_else(|| {
         let message = if args.tui {
             "--tui requires a <netlist.spicy> argument"
         } else {
@@ -48,42 +175,183 @@ fn main() {
         return;
     }
 
-    let input = fs::read_to_string(&path).unwrap_or_else(|e| {
-        eprintln!("Failed to read {}: {}", path, e);
+    let netlist_path = std::path::Path::new(&path);
+    let schematic = is_schematic(netlist_path);
+    if args.watch {
+        let compatibility = if args.ngspice || schematic {
+            Compatibility::Ngspice
+        } else {
+            Compatibility::default()
+        };
+        let variables = path_variables(&args.defines);
+        watch::run_watch(netlist_path, compatibility, &variables, || {
+            simulation_config(&args, netlist_path)
+        });
+    }
+    let input = read_input(netlist_path).unwrap_or_else(|e| {
+        eprintln!("{e}");
         std::process::exit(1);
     });
-    let mut parser_options = ParseOptions::new_with_source(std::path::Path::new(&path), input);
+    let mut parser_options = ParseOptions::new_with_source(netlist_path, input);
+    parser_options.variables = path_variables(&args.defines);
+    if args.ngspice || schematic {
+        parser_options.compatibility = Compatibility::Ngspice;
+    }
+    let json = args.diagnostics == DiagnosticsFormat::Json;
 
     match parse(&mut parser_options) {
         Ok(deck) => {
-            let base = std::path::Path::new(&path)
-                .file_stem()
-                .map(|s| s.to_string_lossy().to_string())
-                .unwrap_or_else(|| "spicy".to_string());
-            let sim_config = SimulationConfig {
-                write_raw: args.raw,
-                output_base: Some(base),
-                ..Default::default()
-            };
-            if let Err(e) = simulate(deck, sim_config) {
-                eprintln!("Simulation error: {}", e);
-                std::process::exit(3);
+            for warning in lint_deck(&deck) {
+                if json {
+                    diagnostics::emit(&Diagnostic::from_lint(&warning, &parser_options.source_map));
+                    continue;
+                }
+                eprintln!("Warning: {}", warning);
+                if let Some(span) = warning.span() {
+                    print_snippet(&parser_options.source_map, span);
+                }
             }
+            let sim_config = simulation_config(&args, netlist_path);
+            match simulate_steps(&mut parser_options, deck, sim_config) {
+                Ok(report) => {
+                    for warning in report.warnings() {
+                        if json {
+                            diagnostics::emit(&diagnostics::simulation_warning(
+                                &warning,
+                                &parser_options.source_map,
+                            ));
+                        } else {
+                            eprintln!("Warning: {}", warning);
+                        }
+                    }
+                }
+                Err(e) if json => {
+                    for diagnostic in diagnostics::simulation_error(&e, &parser_options.source_map)
+                    {
+                        diagnostics::emit(&diagnostic);
+                    }
+                    std::process::exit(3);
+                }
+                Err(SimulationError::Topology(errors)) => {
+                    // the lint warnings above already pointed at each of them
+                    for error in errors {
+                        eprintln!("Topology error: {}", error);
+                    }
+                    std::process::exit(3);
+                }
+                Err(SimulationError::Parameters(errors)) => {
+                    for error in errors {
+                        eprintln!("Parameter error: {}", error);
+                    }
+                    std::process::exit(3);
+                }
+                Err(e) => {
+                    eprintln!("Simulation error: {}", e);
+                    std::process::exit(3);
+                }
+            }
+        }
+        Err(e) if json => {
+            diagnostics::emit(&Diagnostic::from_error(&e, &parser_options.source_map));
+            std::process::exit(2);
         }
         Err(e) => {
             eprintln!("Parse error: {}", e);
             if let Some(span) = e.error_span() {
-                let input_path = parser_options.source_map.get_path(span.source_index);
-                eprintln!();
-                let input = fs::read_to_string(input_path).unwrap_or_else(|e| {
-                    eprintln!("Failed to read {}: {}", input_path.display(), e);
-                    std::process::exit(1);
-                });
-                if let Some(snippet) = format_error_snippet(&input, span) {
-                    eprint!("{snippet}");
-                }
+                print_snippet(&parser_options.source_map, span);
             }
             std::process::exit(2);
         }
     }
 }
+
+/// A `--define NAME=VALUE`.
+fn parse_define(define: &str) -> Result<(String, String), String> {
+    match define.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, got '{define}'")),
+    }
+}
+
+/// The variables of include paths: the environment, with the `--define`s over it.
+fn path_variables(defines: &[(String, String)]) -> HashMap<String, String> {
+    std::env::vars().chain(defines.iter().cloned()).collect()
+}
+
+/// The simulation the flags of `args` ask for, writing its outputs next to `path`.
+fn simulation_config(args: &Args, path: &Path) -> SimulationConfig {
+    let base = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "spicy".to_string());
+    SimulationConfig {
+        solver: match (&args.solver, args.backend) {
+            (Some(solver), None) => solver.clone(),
+            (_, Some(_)) => LinearSolver::Auto {
+                config: Default::default(),
+            },
+            (None, None) => SimulationConfig::default().solver,
+        },
+        backend: BackendSelection {
+            force: args.backend,
+            ..Default::default()
+        },
+        write_raw: args.raw,
+        raw_format: if args.ascii {
+            RawFormat::Ascii
+        } else {
+            RawFormat::Binary
+        },
+        export: args.format,
+        op_report: args.op_report,
+        collapse_dc_inductors: args.collapse_inductors,
+        output_base: Some(base),
+        output_dir: args.output_dir.clone(),
+        output_template: args.output_name.clone(),
+        ipc: args.ipc.clone(),
+        dump_matrix: args
+            .dump_matrix
+            .clone()
+            .map(|base| MatrixDump::new(base, args.dump_at)),
+        checkpoint: args.checkpoint.clone().map(|path| Checkpoint {
+            path,
+            every: args.checkpoint_every,
+            resume: args.resume,
+        }),
+        timestep: TimestepConfig {
+            adaptive: args.adaptive_step,
+            ..Default::default()
+        },
+        // JSON diagnostics share stderr with the bar
+        observer: if args.no_progress || args.diagnostics == DiagnosticsFormat::Json {
+            None
+        } else {
+            progress::ProgressBar::for_stderr()
+                .map(|bar| Arc::new(bar) as Arc<dyn SimulateObserver>)
+        },
+        ..Default::default()
+    }
+}
+
+/// An LTspice schematic, imported rather than read as a netlist.
+fn is_schematic(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("asc"))
+}
+
+/// The netlist at `path`, imported from a schematic for an `.asc` file.
+fn read_input(path: &Path) -> Result<String, String> {
+    let input = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if is_schematic(path) {
+        let title = path.file_stem().unwrap_or_default().to_string_lossy();
+        asc_to_netlist(&title, &decode_asc(&input))
+            .map_err(|e| format!("Failed to import {}: {}", path.display(), e))
+    } else {
+        String::from_utf8(input).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    }
+}
+
+/// The snippet of `span`, from the netlist or the included file it points into.
+fn print_snippet(source_map: &SourceMap, span: Span) {
+    eprint!("{}", render_error_snippet(source_map, span));
+}
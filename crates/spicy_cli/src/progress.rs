@@ -0,0 +1,89 @@
+//! A progress bar of the running DC sweep or transient on stderr, when stderr is a terminal.
+
+use std::io::{IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use spicy_simulate::{AnalysisReport, Progress, SimulateObserver};
+
+/// Least time between two redraws of the bar.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+const BAR_WIDTH: usize = 30;
+
+/// Draws the [`Progress`] of the analyses on one line of stderr, cleared when an analysis ends.
+#[derive(Default)]
+pub struct ProgressBar {
+    /// When the bar was last drawn, `None` while it is not on screen.
+    drawn: Mutex<Option<Instant>>,
+}
+
+impl ProgressBar {
+    /// A bar, unless stderr is redirected where the redraws would pile up.
+    pub fn for_stderr() -> Option<ProgressBar> {
+        std::io::stderr().is_terminal().then(ProgressBar::default)
+    }
+
+    /// Take the bar off the screen.
+    fn clear(&self) {
+        if self.drawn.lock().unwrap().take().is_some() {
+            let mut stderr = std::io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[2K");
+            let _ = stderr.flush();
+        }
+    }
+}
+
+/// `1.2s`, `3m04s` or `1h02m`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..60 => format!("{:.1}s", duration.as_secs_f64()),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+fn render(progress: &Progress) -> String {
+    let fraction = progress.fraction().clamp(0.0, 1.0);
+    let filled = (fraction * BAR_WIDTH as f64).round() as usize;
+    let eta = progress
+        .eta()
+        .map_or_else(|| "--".to_string(), format_duration);
+    format!(
+        "{:<4} [{}{}] {:>3.0}% {}/{} elapsed {} eta {}",
+        progress.analysis,
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        fraction * 100.0,
+        progress.step,
+        progress.total_steps,
+        format_duration(progress.elapsed),
+        eta
+    )
+}
+
+impl SimulateObserver for ProgressBar {
+    fn on_progress(&self, progress: &Progress) {
+        let mut drawn = self.drawn.lock().unwrap();
+        let done = progress.step == progress.total_steps;
+        if drawn.is_some_and(|at| at.elapsed() < REDRAW_INTERVAL) && !done {
+            return;
+        }
+        *drawn = Some(Instant::now());
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K{}", render(progress));
+        let _ = stderr.flush();
+    }
+
+    fn on_analysis_end(&self, _report: &AnalysisReport) {
+        self.clear();
+    }
+}
+
+/// A failed analysis does not end, so its bar goes with the config of the simulation.
+impl Drop for ProgressBar {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
@@ -1,16 +1,67 @@
+use spicy_parser::SourceMap;
 use spicy_parser::error::SpicyError;
-use spicy_simulate::{DcSweepResult, OperatingPointResult, SimulationConfig, TransientResult};
+use spicy_parser::lint::LintWarning;
+use spicy_simulate::{
+    CancellationToken, DcSweepResult, OperatingPointResult, Progress, SimulationConfig,
+    TransientResult,
+};
 
+use crate::tui::graph::PlotView;
 use crate::tui::nvim::NvimState;
+use crate::tui::topology::Topology;
+use crate::tui::tweak::{TWEAK_STEP, apply_tweaks, tweak_targets};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Tab {
     Op,
     DC,
+    Ac,
     Trans,
 }
 
+/// Finished jobs kept for browsing; older ones are dropped.
+pub(crate) const MAX_FINISHED_JOBS: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Cancelled,
+    Failed(String),
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, JobStatus::Queued | JobStatus::Running)
+    }
+}
+
+/// One run of the netlist, with the results of its analyses.
+#[derive(Debug)]
+pub struct Job {
+    pub id: usize,
+    pub status: JobStatus,
+    pub cancel: CancellationToken,
+    pub op: Option<OperatingPointResult>,
+    pub dc: Option<DcSweepResult>,
+    pub ac: Option<AcResult>,
+    pub trans: Option<TransientResult>,
+    /// How far the running DC sweep or transient got.
+    pub progress: Option<Progress>,
+    /// Run of a tweak with `f32` factors, replaced by a full run when tweak mode is left.
+    pub preview: bool,
+}
+
+/// The node magnitudes of an AC sweep, as the TUI plots them.
+#[derive(Debug, Clone)]
+pub struct AcResult {
+    pub frequencies: Vec<f64>,
+    pub node_names: Vec<String>,
+    /// `|V|` of every node at every frequency, one vector per node.
+    pub magnitudes: Vec<Vec<f64>>,
+}
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ConfigField {
@@ -44,19 +95,13 @@ pub(crate) const CONFIG_FIELDS: [ConfigField; 5] = [
 
 impl ConfigField {
     pub fn next(self) -> ConfigField {
-        let idx = CONFIG_FIELDS
-            .iter()
-            .position(|f| *f == self)
-            .unwrap_or(0);
+        let idx = CONFIG_FIELDS.iter().position(|f| *f == self).unwrap_or(0);
         let next_idx = (idx + 1) % CONFIG_FIELDS.len();
         CONFIG_FIELDS[next_idx]
     }
 
     pub fn prev(self) -> ConfigField {
-        let idx = CONFIG_FIELDS
-            .iter()
-            .position(|f| *f == self)
-            .unwrap_or(0);
+        let idx = CONFIG_FIELDS.iter().position(|f| *f == self).unwrap_or(0);
         let prev_idx = (idx + CONFIG_FIELDS.len() - 1) % CONFIG_FIELDS.len();
         CONFIG_FIELDS[prev_idx]
     }
@@ -69,17 +114,37 @@ pub struct App {
     pub raw_netlist: String,
     pub scroll: usize,
     pub diags: Vec<SpicyError>,
+    pub lints: Vec<LintWarning>,
+    /// The sources of the last parse, to place its diagnostics in the files they point into.
+    pub source_map: Option<SourceMap>,
     pub nvim: Option<NvimState>,
     pub nvim_warning: Option<String>,
+    /// Line (1-based) of the cursor in the netlist: nvim's, or the top line while scrolling.
+    pub cursor_line: usize,
+    /// The circuit of the last netlist that parsed.
+    pub topology: Option<Topology>,
 
     // Right pane
     pub tab: Tab,
-    pub op: Option<OperatingPointResult>,
-    pub dc: Option<DcSweepResult>,
-    pub trans: Option<TransientResult>,
-    // Transient UI state
-    pub trans_selected_nodes: Vec<usize>,
-    pub trans_list_index: usize,
+    /// Every run, oldest first.
+    pub jobs: Vec<Job>,
+    /// The job whose results are shown; `None` follows the newest finished job.
+    pub selected_job: Option<usize>,
+    pub next_job_id: usize,
+    /// Overlay the traces of the run before the shown one.
+    pub overlay_previous: bool,
+    /// Show the topology instead of the results.
+    pub show_topology: bool,
+    pub topology_scroll: usize,
+    /// Tweak mode is on: the keys of the right pane pick a device and scale its value.
+    pub tweaking: bool,
+    pub tweak_index: usize,
+    /// Factor on the value of each tweaked device, by name.
+    pub tweaks: Vec<(String, f64)>,
+    // Plot state, per tab
+    pub dc_plot: PlotView,
+    pub ac_plot: PlotView,
+    pub trans_plot: PlotView,
 
     // Infra
     pub focus_right: bool,
@@ -96,14 +161,28 @@ impl App {
             raw_netlist: netlist_text,
             scroll: 0,
             diags: Vec::new(),
+            lints: Vec::new(),
+            source_map: None,
             nvim: None,
             nvim_warning: None,
+            cursor_line: 1,
+            topology: None,
             tab: Tab::Op,
-            op: None,
-            dc: None,
-            trans: None,
-            trans_selected_nodes: Vec::new(),
-            trans_list_index: 0,
+            jobs: Vec::new(),
+            selected_job: None,
+            next_job_id: 1,
+            overlay_previous: false,
+            show_topology: false,
+            topology_scroll: 0,
+            tweaking: false,
+            tweak_index: 0,
+            tweaks: Vec::new(),
+            dc_plot: PlotView::default(),
+            ac_plot: PlotView {
+                log_y: true,
+                ..PlotView::default()
+            },
+            trans_plot: PlotView::default(),
             focus_right: false,
             modal: Modal::None,
             config: SimulationConfig::default(),
@@ -125,11 +204,197 @@ impl App {
         self.raw_netlist.lines().count()
     }
 
+    /// Move the netlist cursor to `line`, and the topology to the first node with a device on
+    /// that line.
+    pub fn set_cursor_line(&mut self, line: usize) {
+        self.cursor_line = line;
+        if let Some(row) = self.topology.as_ref().and_then(|t| t.row_of_line(line)) {
+            self.topology_scroll = row;
+        }
+    }
+
+    /// The netlist to run: the text with the tweaks applied.
+    pub fn run_netlist(&self) -> String {
+        if self.tweaks.is_empty() {
+            self.raw_netlist.clone()
+        } else {
+            apply_tweaks(&self.raw_netlist, &self.tweaks)
+        }
+    }
+
+    /// Factor of the tweak on `device`, 1 without one.
+    pub fn tweak_factor(&self, device: &str) -> f64 {
+        self.tweaks
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(device))
+            .map_or(1.0, |(_, factor)| *factor)
+    }
+
+    /// Scale the value of the device picked in tweak mode by `steps` steps (none to reset it).
+    /// Returns whether the netlist to run changed.
+    pub fn tweak_selected(&mut self, steps: Option<i32>) -> bool {
+        let targets = tweak_targets(&self.raw_netlist);
+        let Some(target) = targets.get(self.tweak_index.min(targets.len().saturating_sub(1)))
+        else {
+            return false;
+        };
+        let factor = match steps {
+            Some(steps) => self.tweak_factor(&target.name) * TWEAK_STEP.powi(steps),
+            None => 1.0,
+        };
+        self.tweaks
+            .retain(|(name, _)| !name.eq_ignore_ascii_case(&target.name));
+        // snap back to exactly 1 after as many steps down as up
+        if (factor - 1.0).abs() > 1e-9 {
+            self.tweaks.push((target.name.clone(), factor));
+        }
+        true
+    }
+
+    /// Queue a new job and return its id and cancellation token.
+    pub fn push_job(&mut self, preview: bool) -> (usize, CancellationToken) {
+        let id = self.next_job_id;
+        self.next_job_id += 1;
+        let cancel = CancellationToken::new();
+        self.jobs.push(Job {
+            id,
+            status: JobStatus::Queued,
+            cancel: cancel.clone(),
+            op: None,
+            dc: None,
+            ac: None,
+            trans: None,
+            progress: None,
+            preview,
+        });
+        (id, cancel)
+    }
+
+    pub fn job_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    /// Drop the oldest finished jobs beyond [`MAX_FINISHED_JOBS`], except the selected one.
+    pub fn prune_jobs(&mut self) {
+        let finished = self.jobs.iter().filter(|j| j.status.is_finished()).count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+        let selected = self.selected_job;
+        self.jobs.retain(|job| {
+            let drop = excess > 0 && job.status.is_finished() && Some(job.id) != selected;
+            if drop {
+                excess -= 1;
+            }
+            !drop
+        });
+    }
+
+    /// The job whose results are shown.
+    pub fn shown_job(&self) -> Option<&Job> {
+        match self.selected_job {
+            Some(id) => self.jobs.iter().find(|job| job.id == id),
+            None => self.jobs.iter().rev().find(|job| job.status.is_finished()),
+        }
+    }
+
+    /// The run to compare the shown one with: the newest finished run before it, while
+    /// [`App::overlay_previous`] is on.
+    pub fn compared_job(&self) -> Option<&Job> {
+        if !self.overlay_previous {
+            return None;
+        }
+        let shown = self.shown_job()?;
+        let index = self.jobs.iter().position(|job| job.id == shown.id)?;
+        self.jobs[..index]
+            .iter()
+            .rev()
+            .find(|job| job.status.is_finished())
+    }
+
+    pub fn op(&self) -> Option<&OperatingPointResult> {
+        self.shown_job().and_then(|job| job.op.as_ref())
+    }
+
+    pub fn dc(&self) -> Option<&DcSweepResult> {
+        self.shown_job().and_then(|job| job.dc.as_ref())
+    }
+
+    pub fn ac(&self) -> Option<&AcResult> {
+        self.shown_job().and_then(|job| job.ac.as_ref())
+    }
+
+    pub fn trans(&self) -> Option<&TransientResult> {
+        self.shown_job().and_then(|job| job.trans.as_ref())
+    }
+
+    /// The plot of `tab`, none for a tab without one.
+    pub fn plot_mut(&mut self, tab: Tab) -> Option<&mut PlotView> {
+        match tab {
+            Tab::Op => None,
+            Tab::DC => Some(&mut self.dc_plot),
+            Tab::Ac => Some(&mut self.ac_plot),
+            Tab::Trans => Some(&mut self.trans_plot),
+        }
+    }
+
+    /// Number of traces the plot of `tab` can show.
+    pub fn trace_count(&self, tab: Tab) -> usize {
+        match tab {
+            Tab::Op => 0,
+            Tab::DC => self.dc().map_or(0, |dc| {
+                dc.results.first().map_or(0, |(op, _)| op.voltages.len())
+            }),
+            Tab::Ac => self.ac().map_or(0, |ac| ac.node_names.len()),
+            Tab::Trans => self.trans().map_or(0, |tr| tr.node_names.len()),
+        }
+    }
+
+    /// Show an older (`-1`) or newer (`1`) job; past the newest one, follow the newest
+    /// finished job again.
+    pub fn select_job(&mut self, delta: isize) {
+        if self.jobs.is_empty() {
+            return;
+        }
+        let current = match self.shown_job() {
+            Some(job) => self.jobs.iter().position(|j| j.id == job.id),
+            None => None,
+        };
+        let last = self.jobs.len() - 1;
+        self.selected_job = match (current, delta < 0) {
+            (None, true) => Some(self.jobs[last].id),
+            (None, false) => None,
+            (Some(index), true) => Some(self.jobs[index.saturating_sub(1)].id),
+            (Some(index), false) if index >= last => None,
+            (Some(index), false) => Some(self.jobs[index + 1].id),
+        };
+        self.ensure_visible_tab();
+    }
+
+    /// Cancel every job that has not finished, for a run that makes them stale.
+    pub fn cancel_unfinished_jobs(&mut self) {
+        for job in self.jobs.iter().filter(|job| !job.status.is_finished()) {
+            job.cancel.cancel();
+        }
+    }
+
+    /// Cancel the shown job if it has not finished, or else the newest one that has not.
+    pub fn cancel_job(&mut self) {
+        let unfinished = |job: &&Job| !job.status.is_finished();
+        let job = self
+            .selected_job
+            .and_then(|id| self.jobs.iter().find(|job| job.id == id))
+            .filter(unfinished)
+            .or_else(|| self.jobs.iter().rev().find(unfinished));
+        if let Some(job) = job {
+            job.cancel.cancel();
+        }
+    }
+
     pub fn available_tabs(&self) -> Vec<Tab> {
         [
-            (Tab::Op, self.op.is_some()),
-            (Tab::DC, self.dc.is_some()),
-            (Tab::Trans, self.trans.is_some()),
+            (Tab::Op, self.op().is_some()),
+            (Tab::DC, self.dc().is_some()),
+            (Tab::Ac, self.ac().is_some()),
+            (Tab::Trans, self.trans().is_some()),
         ]
         .into_iter()
         .filter_map(|(tab, has_results)| has_results.then_some(tab))
@@ -1,8 +1,8 @@
 use ratatui::Frame;
-use ratatui::layout::Rect;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Style};
 use ratatui::symbols::Marker;
-use ratatui::widgets::{Axis, Block, Borders, Chart, Dataset, GraphType};
+use ratatui::widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph};
 
 pub struct Series {
     pub name: String,
@@ -18,6 +18,9 @@ pub struct Graph<'a> {
     pub y_bounds: [f64; 2],
     pub series: Vec<Series>,
     pub x_is_time: bool,
+    /// The axis holds log10 of the values: label every tick with its power of ten.
+    pub x_log: bool,
+    pub y_log: bool,
     pub x_label_count: usize,
     pub y_label_count: usize,
 }
@@ -48,7 +51,9 @@ impl<'a> Graph<'a> {
         let x_ticks = compute_ticks(self.x_bounds[0], self.x_bounds[1], x_count.max(2));
         let x_labels = make_labels(
             x_ticks,
-            if self.x_is_time {
+            if self.x_log {
+                LabelKind::Log
+            } else if self.x_is_time {
                 LabelKind::Time
             } else {
                 LabelKind::Number
@@ -61,7 +66,14 @@ impl<'a> Graph<'a> {
             self.y_label_count
         };
         let y_ticks = compute_ticks(self.y_bounds[0], self.y_bounds[1], y_count.max(2));
-        let y_labels = make_labels(y_ticks, LabelKind::Number);
+        let y_labels = make_labels(
+            y_ticks,
+            if self.y_log {
+                LabelKind::Log
+            } else {
+                LabelKind::Number
+            },
+        );
 
         let chart = Chart::new(datasets)
             .x_axis(
@@ -90,6 +102,7 @@ use ratatui::prelude::Span as UiSpan;
 enum LabelKind {
     Time,
     Number,
+    Log,
 }
 
 fn make_labels(ticks: Vec<f64>, kind: LabelKind) -> Vec<UiSpan<'static>> {
@@ -98,6 +111,7 @@ fn make_labels(ticks: Vec<f64>, kind: LabelKind) -> Vec<UiSpan<'static>> {
         .map(|t| match kind {
             LabelKind::Time => UiSpan::raw(format_time(t)),
             LabelKind::Number => UiSpan::raw(format_si(t)),
+            LabelKind::Log => UiSpan::raw(format_si(10f64.powf(t))),
         })
         .collect()
 }
@@ -107,7 +121,7 @@ fn compute_ticks(min: f64, max: f64, desired: usize) -> Vec<f64> {
     (0..desired).map(|i| min + step * (i as f64)).collect()
 }
 
-fn format_time(t: f64) -> String {
+pub(crate) fn format_time(t: f64) -> String {
     // Choose unit based on magnitude
     let at = t.abs();
     if at >= 1.0 {
@@ -121,7 +135,7 @@ fn format_time(t: f64) -> String {
     }
 }
 
-fn format_si(x: f64) -> String {
+pub(crate) fn format_si(x: f64) -> String {
     let ax = x.abs();
     if ax == 0.0 {
         return "0".to_string();
@@ -146,22 +160,6 @@ fn format_si(x: f64) -> String {
     format!("{:.3}{}", x * scale, suffix)
 }
 
-impl Series {
-    pub fn from_times_and_values(
-        name: String,
-        color: Color,
-        times: &[f64],
-        values: &[f64],
-    ) -> Self {
-        let points: Vec<(f64, f64)> = times.iter().copied().zip(values.iter().copied()).collect();
-        Self {
-            name,
-            color,
-            points,
-        }
-    }
-}
-
 pub fn compute_y_bounds(series: &[Series]) -> [f64; 2] {
     let mut min_v = f64::INFINITY;
     let mut max_v = f64::NEG_INFINITY;
@@ -183,3 +181,353 @@ pub fn compute_y_bounds(series: &[Series]) -> [f64; 2] {
     }
     [min_v, max_v]
 }
+
+/// A result vector that can be plotted: `y` over the time, sweep value or frequency `x`.
+pub struct Trace {
+    pub name: String,
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+}
+
+impl Trace {
+    /// The value at `at`, linearly interpolated between points; `None` outside the trace.
+    pub fn at(&self, at: f64) -> Option<f64> {
+        let (&first, &last) = (self.x.first()?, self.x.last()?);
+        if at < first || at > last {
+            return None;
+        }
+        // the first point at or after `at`
+        let upper = self.x.partition_point(|&x| x < at);
+        if upper == 0 {
+            return self.y.first().copied();
+        }
+        let (x0, x1) = (self.x[upper - 1], self.x[upper]);
+        let frac = if x1 == x0 { 0.0 } else { (at - x0) / (x1 - x0) };
+        Some(self.y[upper - 1] + (self.y[upper] - self.y[upper - 1]) * frac)
+    }
+}
+
+/// Step of a pan and of a cursor move, as a fraction of the visible window.
+const PAN_STEP: f64 = 0.1;
+const CURSOR_STEP: f64 = 0.02;
+/// Factor of one zoom step.
+const ZOOM_STEP: f64 = 1.5;
+
+/// Interactive state of a plot: the traces shown, the window on them, the measurement cursors
+/// and the scale of the y axis.
+///
+/// The window and the cursors are fractions of the full extent of the shown traces, so they
+/// stay put when a job with slightly different data is shown.
+#[derive(Debug, Clone)]
+pub struct PlotView {
+    /// Indices of the shown traces; the first trace when empty.
+    pub selected: Vec<usize>,
+    /// Highlighted row of the trace list.
+    pub list_index: usize,
+    /// Visible fraction of the full x and y extent, 1 for all of it.
+    pub span: [f64; 2],
+    /// Centre of the window on each axis.
+    pub center: [f64; 2],
+    /// Cursors A and B along the x axis.
+    pub cursors: [Option<f64>; 2],
+    pub active_cursor: usize,
+    /// Plot log10 of the magnitudes on the y axis.
+    pub log_y: bool,
+}
+
+impl Default for PlotView {
+    fn default() -> Self {
+        Self {
+            selected: Vec::new(),
+            list_index: 0,
+            span: [1.0, 1.0],
+            center: [0.5, 0.5],
+            cursors: [None, None],
+            active_cursor: 0,
+            log_y: false,
+        }
+    }
+}
+
+impl PlotView {
+    pub fn toggle_trace(&mut self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let index = self.list_index.min(count - 1);
+        if let Some(pos) = self.selected.iter().position(|&i| i == index) {
+            self.selected.remove(pos);
+        } else {
+            self.selected.push(index);
+        }
+    }
+
+    /// Zoom the window of `axis` (0 for x, 1 for y) in (`zoom_in`) or out around its centre.
+    pub fn zoom(&mut self, axis: usize, zoom_in: bool) {
+        let factor = if zoom_in { 1.0 / ZOOM_STEP } else { ZOOM_STEP };
+        self.span[axis] = (self.span[axis] * factor).clamp(1e-6, 1.0);
+        self.clamp_center(axis);
+    }
+
+    /// Move the window of `axis` by a step towards larger (`forward`) or smaller values.
+    pub fn pan(&mut self, axis: usize, forward: bool) {
+        let step = PAN_STEP * self.span[axis];
+        self.center[axis] += if forward { step } else { -step };
+        self.clamp_center(axis);
+    }
+
+    fn clamp_center(&mut self, axis: usize) {
+        let half = self.span[axis] / 2.0;
+        self.center[axis] = self.center[axis].clamp(half, 1.0 - half);
+    }
+
+    /// Show all of the traces again.
+    pub fn reset(&mut self) {
+        self.span = [1.0, 1.0];
+        self.center = [0.5, 0.5];
+    }
+
+    /// Make cursor `index` the one that moves, placing it in the middle of the window if it
+    /// is not on the plot yet.
+    pub fn select_cursor(&mut self, index: usize) {
+        self.active_cursor = index;
+        self.cursors[index].get_or_insert(self.center[0]);
+    }
+
+    pub fn move_cursor(&mut self, forward: bool) {
+        let step = CURSOR_STEP * self.span[0];
+        if let Some(cursor) = self.cursors[self.active_cursor].as_mut() {
+            *cursor = (*cursor + if forward { step } else { -step }).clamp(0.0, 1.0);
+        }
+    }
+
+    pub fn clear_cursors(&mut self) {
+        self.cursors = [None, None];
+    }
+
+    /// The window of `axis` within the full extent `[lo, hi]`.
+    fn window(&self, axis: usize, [lo, hi]: [f64; 2]) -> [f64; 2] {
+        let width = hi - lo;
+        let half = self.span[axis] / 2.0;
+        [
+            lo + width * (self.center[axis] - half),
+            lo + width * (self.center[axis] + half),
+        ]
+    }
+}
+
+/// What a plot is of, for its titles and axes.
+pub struct PlotSpec<'a> {
+    pub title: &'a str,
+    pub x_label: &'a str,
+    pub y_label: &'a str,
+    pub x_is_time: bool,
+    /// Space the x axis logarithmically, for a frequency sweep.
+    pub x_log: bool,
+}
+
+/// The traces of an earlier run, overlaid on the plot for comparison.
+pub struct Reference<'a> {
+    /// Id of the run, for the legend.
+    pub job: usize,
+    pub traces: &'a [Trace],
+}
+
+impl Reference<'_> {
+    /// The trace of this run with the name of `trace`.
+    fn matching(&self, trace: &Trace) -> Option<&Trace> {
+        self.traces.iter().find(|t| t.name == trace.name)
+    }
+}
+
+/// Draw the selected `traces` in the window of `view`, with its cursors and, under the plot, the
+/// values at the cursors. The same traces of `reference` are drawn alongside in colors of
+/// their own, and the readout compares the two at the cursors.
+pub fn render_plot(
+    f: &mut Frame,
+    area: Rect,
+    spec: &PlotSpec,
+    traces: &[Trace],
+    reference: Option<&Reference>,
+    view: &PlotView,
+) {
+    let shown: Vec<&Trace> = if view.selected.is_empty() {
+        traces.first().into_iter().collect()
+    } else {
+        view.selected
+            .iter()
+            .filter_map(|&i| traces.get(i))
+            .collect()
+    };
+    let axis = |value: f64, log: bool| if log { value.abs().log10() } else { value };
+
+    let to_series = |trace: &Trace, name: String, color: Color| Series {
+        name,
+        color,
+        points: trace
+            .x
+            .iter()
+            .zip(&trace.y)
+            .map(|(&x, &y)| (axis(x, spec.x_log), axis(y, view.log_y)))
+            .filter(|(x, y)| x.is_finite() && y.is_finite())
+            .collect(),
+    };
+
+    let mut series: Vec<Series> = shown
+        .iter()
+        .enumerate()
+        .map(|(index, trace)| to_series(trace, trace.name.clone(), palette_color(index)))
+        .collect();
+    if let Some(reference) = reference {
+        // the colors after those of the current run, so every curve has its own
+        for (index, trace) in shown.iter().enumerate() {
+            if let Some(old) = reference.matching(trace) {
+                let name = format!("{} #{}", old.name, reference.job);
+                series.push(to_series(old, name, palette_color(shown.len() + index)));
+            }
+        }
+    }
+
+    let x_full = x_extent(&series);
+    let y_full = compute_y_bounds(&series);
+    let x_bounds = view.window(0, x_full);
+    let y_bounds = view.window(1, y_full);
+
+    let cursor_x = |c: f64| x_full[0] + (x_full[1] - x_full[0]) * c;
+    for (name, cursor) in ["A", "B"].iter().zip(view.cursors) {
+        if let Some(c) = cursor {
+            series.push(Series {
+                name: name.to_string(),
+                color: Color::White,
+                points: vec![(cursor_x(c), y_bounds[0]), (cursor_x(c), y_bounds[1])],
+            });
+        }
+    }
+
+    let first = shown.first().copied();
+    let compared = reference.and_then(|reference| {
+        let old = reference.matching(first?)?;
+        Some((reference.job, old))
+    });
+    let readout = cursor_readout(spec, first, compared, view, |c| {
+        let x = cursor_x(c);
+        if spec.x_log { 10f64.powf(x) } else { x }
+    });
+    let chart_area = match &readout {
+        Some(_) => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Length(3)])
+                .split(area);
+            if let Some(line) = &readout {
+                f.render_widget(
+                    Paragraph::new(line.as_str())
+                        .block(Block::default().borders(Borders::ALL).title("cursors")),
+                    chunks[1],
+                );
+            }
+            chunks[0]
+        }
+        None => area,
+    };
+
+    let graph = Graph {
+        title: spec.title,
+        x_label: spec.x_label,
+        y_label: spec.y_label,
+        x_bounds,
+        y_bounds,
+        series,
+        x_is_time: spec.x_is_time,
+        x_log: spec.x_log,
+        y_log: view.log_y,
+        x_label_count: 0,
+        y_label_count: 0,
+    };
+    graph.render(f, chart_area);
+}
+
+/// The values of `trace` at the cursors of `view`, and their differences when both are set.
+/// With `compared`, each cursor also shows the value of the earlier run and how far the trace
+/// moved from it.
+fn cursor_readout(
+    spec: &PlotSpec,
+    trace: Option<&Trace>,
+    compared: Option<(usize, &Trace)>,
+    view: &PlotView,
+    to_x: impl Fn(f64) -> f64,
+) -> Option<String> {
+    let format_x = |x: f64| {
+        if spec.x_is_time {
+            format_time(x)
+        } else {
+            format_si(x)
+        }
+    };
+    let mut parts = Vec::new();
+    let mut points = Vec::new();
+    for (index, (name, cursor)) in ["A", "B"].iter().zip(view.cursors).enumerate() {
+        let Some(cursor) = cursor else {
+            continue;
+        };
+        let x = to_x(cursor);
+        let y = trace.and_then(|trace| trace.at(x));
+        let active = if index == view.active_cursor { "*" } else { "" };
+        let value = y.map_or("-".to_string(), format_si);
+        let mut part = format!("{name}{active}: {} {value}", format_x(x));
+        if let Some((job, old)) = compared {
+            let old = old.at(x);
+            part.push_str(&format!(
+                " #{job} {}",
+                old.map_or("-".to_string(), format_si)
+            ));
+            if let (Some(y), Some(old)) = (y, old) {
+                part.push_str(&format!(" diff {}", format_si(y - old)));
+            }
+        }
+        parts.push(part);
+        points.push((x, y));
+    }
+    if points.is_empty() {
+        return None;
+    }
+    if let [(x0, y0), (x1, y1)] = points[..] {
+        parts.push(format!("dx {}", format_x(x1 - x0)));
+        if spec.x_is_time && x1 != x0 {
+            parts.push(format!("1/dx {}Hz", format_si(1.0 / (x1 - x0).abs())));
+        }
+        if let (Some(y0), Some(y1)) = (y0, y1) {
+            parts.push(format!("dy {}", format_si(y1 - y0)));
+        }
+    }
+    Some(parts.join("  "))
+}
+
+fn x_extent(series: &[Series]) -> [f64; 2] {
+    let xs = series.iter().flat_map(|s| s.points.iter().map(|(x, _)| *x));
+    let (lo, hi) = xs.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), x| {
+        (lo.min(x), hi.max(x))
+    });
+    if lo > hi {
+        [0.0, 1.0]
+    } else if hi == lo {
+        [lo - 0.5, hi + 0.5]
+    } else {
+        [lo, hi]
+    }
+}
+
+pub(crate) fn palette_color(index: usize) -> Color {
+    // Stable color mapping for series
+    const COLORS: [Color; 8] = [
+        Color::Yellow,
+        Color::Cyan,
+        Color::LightMagenta,
+        Color::Green,
+        Color::Blue,
+        Color::LightRed,
+        Color::LightCyan,
+        Color::Magenta,
+    ];
+    COLORS[index % COLORS.len()]
+}
@@ -3,13 +3,17 @@ use crossbeam_channel::Sender;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 use crate::tui::app::{App, ConfigEditState, ConfigField, Tab};
-use crate::tui::worker::SimCmd;
+use crate::tui::tweak::tweak_targets;
+use crate::tui::worker::{SimCmd, enqueue_preview, enqueue_run};
 use spicy_simulate::{LinearSolver, TransientIntegrator, solver::klu::KluConfig};
 
 fn toggle_solver(app: &mut App) {
     app.config.solver = match app.config.solver {
         LinearSolver::Klu { .. } => LinearSolver::Blas,
-        LinearSolver::Blas => LinearSolver::Klu {
+        LinearSolver::Blas => LinearSolver::Auto {
+            config: KluConfig::default(),
+        },
+        LinearSolver::Auto { .. } => LinearSolver::Klu {
             config: KluConfig::default(),
         },
     };
@@ -140,6 +144,81 @@ fn panel_switch_from_key(code: KeyCode, modifiers: KeyModifiers) -> Option<bool>
     }
 }
 
+/// Keys of tweak mode; `false` if `k` is not one of them. A tweak previews the netlist again,
+/// the runs it makes stale cancelled.
+fn handle_tweak_key(k: KeyEvent, app: &mut App, tx: &Sender<SimCmd>) -> Result<bool> {
+    let steps = match k.code {
+        KeyCode::Up => {
+            app.tweak_index = app.tweak_index.saturating_sub(1);
+            return Ok(true);
+        }
+        KeyCode::Down => {
+            let targets = tweak_targets(&app.raw_netlist).len();
+            app.tweak_index = (app.tweak_index + 1).min(targets.saturating_sub(1));
+            return Ok(true);
+        }
+        KeyCode::Esc => {
+            leave_tweak_mode(app, tx)?;
+            return Ok(true);
+        }
+        KeyCode::Char('+') | KeyCode::Char('=') => Some(1),
+        KeyCode::Char('-') => Some(-1),
+        KeyCode::Char('0') => None,
+        _ => return Ok(false),
+    };
+    if app.tweak_selected(steps) {
+        app.cancel_unfinished_jobs();
+        enqueue_preview(app, tx)?;
+    }
+    Ok(true)
+}
+
+/// Leave tweak mode, running the last previewed tweak in full precision.
+fn leave_tweak_mode(app: &mut App, tx: &Sender<SimCmd>) -> Result<()> {
+    app.tweaking = false;
+    if app.jobs.last().is_some_and(|job| job.preview) {
+        app.cancel_unfinished_jobs();
+        enqueue_run(app, tx)?;
+    }
+    Ok(())
+}
+
+/// Keys of the plot on the shown tab; `false` if `k` is not one of them.
+fn handle_plot_key(k: KeyEvent, app: &mut App) -> bool {
+    let tabs = app.available_tabs();
+    let Some(tab) = app.selected_tab(&tabs) else {
+        return false;
+    };
+    let traces = app.trace_count(tab);
+    let Some(plot) = app.plot_mut(tab) else {
+        return false;
+    };
+    let shift = k.modifiers.contains(KeyModifiers::SHIFT);
+    match k.code {
+        KeyCode::Left if shift => plot.pan(0, false),
+        KeyCode::Right if shift => plot.pan(0, true),
+        KeyCode::Up if shift => plot.pan(1, true),
+        KeyCode::Down if shift => plot.pan(1, false),
+        KeyCode::Down => plot.list_index = (plot.list_index + 1).min(traces.saturating_sub(1)),
+        KeyCode::Up => plot.list_index = plot.list_index.saturating_sub(1),
+        KeyCode::Enter => plot.toggle_trace(traces),
+        KeyCode::Char('+') | KeyCode::Char('=') => plot.zoom(0, true),
+        KeyCode::Char('-') => plot.zoom(0, false),
+        KeyCode::Char('*') => plot.zoom(1, true),
+        KeyCode::Char('/') => plot.zoom(1, false),
+        KeyCode::Char('0') => plot.reset(),
+        KeyCode::Char('a') => plot.select_cursor(0),
+        KeyCode::Char('b') => plot.select_cursor(1),
+        KeyCode::Char(',') => plot.move_cursor(false),
+        KeyCode::Char('.') => plot.move_cursor(true),
+        KeyCode::Backspace => plot.clear_cursors(),
+        KeyCode::Char('l') if tab == Tab::Ac => plot.log_y = !plot.log_y,
+        KeyCode::Char('o') => app.overlay_previous = !app.overlay_previous,
+        _ => return false,
+    }
+    true
+}
+
 pub fn handle_key(k: KeyEvent, app: &mut App, tx: &Sender<SimCmd>) -> Result<bool> {
     if let Some(focus_right) = panel_switch_from_key(k.code, k.modifiers) {
         app.focus_right = focus_right;
@@ -166,6 +245,26 @@ pub fn handle_key(k: KeyEvent, app: &mut App, tx: &Sender<SimCmd>) -> Result<boo
         return Ok(false);
     }
 
+    if app.tweaking && handle_tweak_key(k, app, tx)? {
+        return Ok(false);
+    }
+    if app.focus_right && app.show_topology {
+        let rows = app.topology.as_ref().map_or(0, |t| t.row_count());
+        match k.code {
+            KeyCode::Down => {
+                app.topology_scroll = (app.topology_scroll + 1).min(rows.saturating_sub(1));
+                return Ok(false);
+            }
+            KeyCode::Up => {
+                app.topology_scroll = app.topology_scroll.saturating_sub(1);
+                return Ok(false);
+            }
+            _ => {}
+        }
+    } else if app.focus_right && handle_plot_key(k, app) {
+        return Ok(false);
+    }
+
     match k.code {
         KeyCode::Char('h') | KeyCode::Char('?') => {
             app.toggle_help();
@@ -178,17 +277,22 @@ pub fn handle_key(k: KeyEvent, app: &mut App, tx: &Sender<SimCmd>) -> Result<boo
         // movement and navigation (tui-only)
         KeyCode::Char('j') if app.left_pane_active() => {
             app.scroll = app.scroll.saturating_add(1);
+            app.set_cursor_line(app.scroll + 1);
         }
         KeyCode::Char('k') if app.left_pane_active() => {
             app.scroll = app.scroll.saturating_sub(1);
+            app.set_cursor_line(app.scroll + 1);
         }
         KeyCode::Char('g')
-            if app.left_pane_active()
-                && k.modifiers.contains(KeyModifiers::SHIFT) =>
+            if app.left_pane_active() && k.modifiers.contains(KeyModifiers::SHIFT) =>
         {
-            app.scroll = app.netlist_line_count().saturating_sub(1)
+            app.scroll = app.netlist_line_count().saturating_sub(1);
+            app.set_cursor_line(app.scroll + 1);
+        }
+        KeyCode::Char('g') if app.left_pane_active() => {
+            app.scroll = 0;
+            app.set_cursor_line(1);
         }
-        KeyCode::Char('g') if app.left_pane_active() => app.scroll = 0,
         KeyCode::Left => {
             let tabs = app.available_tabs();
             if !tabs.is_empty() {
@@ -209,27 +313,6 @@ pub fn handle_key(k: KeyEvent, app: &mut App, tx: &Sender<SimCmd>) -> Result<boo
                 }
             }
         }
-        // transient tab node selection
-        KeyCode::Down if app.focus_right && matches!(app.tab, Tab::Trans) => {
-            app.trans_list_index = app.trans_list_index.saturating_add(1);
-        }
-        KeyCode::Up if app.focus_right && matches!(app.tab, Tab::Trans) => {
-            app.trans_list_index = app.trans_list_index.saturating_sub(1);
-        }
-        KeyCode::Enter if app.focus_right && matches!(app.tab, Tab::Trans) => {
-            if let Some(tr) = &app.trans
-                && !tr.node_names.is_empty()
-            {
-                let idx = app
-                    .trans_list_index
-                    .min(tr.node_names.len().saturating_sub(1));
-                if let Some(pos) = app.trans_selected_nodes.iter().position(|&i| i == idx) {
-                    app.trans_selected_nodes.remove(pos);
-                } else {
-                    app.trans_selected_nodes.push(idx);
-                }
-            }
-        }
         KeyCode::Char(c) if ('1'..='9').contains(&c) => {
             let idx = (c as u8 - b'1') as usize;
             let tabs = app.available_tabs();
@@ -237,11 +320,13 @@ pub fn handle_key(k: KeyEvent, app: &mut App, tx: &Sender<SimCmd>) -> Result<boo
                 app.tab = tab;
             }
         }
-        KeyCode::Char('r') => {
-            tx.send(SimCmd::RunCurrentTab {
-                config: app.config.clone(),
-            })?;
-        }
+        KeyCode::Char('t') => app.show_topology = !app.show_topology,
+        KeyCode::Char('v') if app.tweaking => leave_tweak_mode(app, tx)?,
+        KeyCode::Char('v') => app.tweaking = true,
+        KeyCode::Char('r') => enqueue_run(app, tx)?,
+        KeyCode::Char('x') => app.cancel_job(),
+        KeyCode::Char('[') => app.select_job(-1),
+        KeyCode::Char(']') => app.select_job(1),
         _ => {}
     }
     Ok(false)
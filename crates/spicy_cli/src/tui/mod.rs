@@ -4,6 +4,8 @@ pub mod input;
 pub mod nvim;
 pub mod run;
 pub mod term;
+pub mod topology;
+pub mod tweak;
 #[path = "ui/mod.rs"]
 pub mod ui;
 pub mod worker;
@@ -14,6 +14,8 @@ const MAIN_GRID_ID: i64 = 1;
 #[derive(Debug)]
 pub enum NvimEvent {
     Saved(Option<String>),
+    /// The cursor moved to this line (1-based).
+    Cursor(usize),
     Help,
     Config,
     Quit,
@@ -65,6 +67,7 @@ end
 vim.keymap.set('n', '<leader>h', function() notify('spicy_help') end, { noremap = true, silent = true })
 vim.keymap.set('n', '<leader>c', function() notify('spicy_config') end, { noremap = true, silent = true })
 vim.keymap.set('n', '<leader>q', function() notify('spicy_quit') end, { noremap = true, silent = true })
+vim.api.nvim_create_autocmd({ 'CursorMoved', 'CursorMovedI' }, { buffer = 0, callback = function() vim.rpcnotify(0, 'spicy_cursor', vim.fn.line('.')) end })
 vim.api.nvim_create_autocmd('BufWritePost', { buffer = 0, callback = function() vim.rpcnotify(0, 'spicy_save', vim.api.nvim_buf_get_name(0)) end })
 "#;
         nvim.execute_lua(lua, Vec::new())
@@ -124,6 +127,11 @@ vim.api.nvim_create_autocmd('BufWritePost', { buffer = 0, callback = function()
                             .map(|val| val.to_string());
                         out.push(NvimEvent::Saved(path));
                     }
+                    "spicy_cursor" => {
+                        if let Some(line) = args.first().and_then(|val| val.as_u64()) {
+                            out.push(NvimEvent::Cursor(line as usize));
+                        }
+                    }
                     "spicy_help" => out.push(NvimEvent::Help),
                     "spicy_config" => out.push(NvimEvent::Config),
                     "spicy_quit" => out.push(NvimEvent::Quit),
@@ -361,11 +369,7 @@ impl NvimGrid {
                 continue;
             }
             let text = value_to_string(&parts[0]).unwrap_or_else(|| " ".to_string());
-            let repeat = parts
-                .get(2)
-                .and_then(Value::as_i64)
-                .unwrap_or(1)
-                .max(1) as usize;
+            let repeat = parts.get(2).and_then(Value::as_i64).unwrap_or(1).max(1) as usize;
             for _ in 0..repeat {
                 for ch in text.chars() {
                     if x >= self.width as usize {
@@ -385,15 +389,7 @@ impl NvimGrid {
         }
     }
 
-    fn scroll(
-        &mut self,
-        top: usize,
-        bot: usize,
-        left: usize,
-        right: usize,
-        rows: i64,
-        _cols: i64,
-    ) {
+    fn scroll(&mut self, top: usize, bot: usize, left: usize, right: usize, rows: i64, _cols: i64) {
         if top >= bot || left >= right {
             return;
         }
@@ -11,9 +11,10 @@ use crate::tui::app::App;
 use crate::tui::input::handle_key;
 use crate::tui::nvim::{NvimEvent, NvimState};
 use crate::tui::term::setup_terminal;
+use crate::tui::topology::Topology;
 use crate::tui::ui::{main_layout, netlist_layout, ui};
-use crate::tui::worker::{SimCmd, SimMsg, apply_sim_update, worker_loop};
-use spicy_parser::{ParseOptions, parse};
+use crate::tui::worker::{SimCmd, apply_sim_update, enqueue_run, worker_count, worker_loop};
+use spicy_parser::{ParseOptions, lint::lint_deck, parse_recovering};
 
 fn refresh_netlist(app: &mut App, path: &Path) {
     let input = match fs::read_to_string(path) {
@@ -28,10 +29,17 @@ fn refresh_netlist(app: &mut App, path: &Path) {
     app.raw_netlist = parse_options.source_map.get_main_content().to_string();
     let line_count = app.netlist_line_count();
     app.scroll = app.scroll.min(line_count.saturating_sub(1));
-    match parse(&mut parse_options) {
-        Ok(_) => app.diags.clear(),
-        Err(err) => app.diags = vec![err],
+    // every error of the netlist is marked at once; the deck left without them is not linted
+    let (deck, errors) = parse_recovering(&mut parse_options);
+    app.diags = errors;
+    match deck {
+        Some(deck) if app.diags.is_empty() => {
+            app.lints = lint_deck(&deck);
+            app.topology = Some(Topology::from_deck(&deck, &parse_options.source_map));
+        }
+        _ => app.lints.clear(),
     }
+    app.source_map = Some(parse_options.source_map);
 }
 
 fn netlist_grid_size(term_size: Rect) -> (u16, u16) {
@@ -49,11 +57,15 @@ pub fn run_tui(path: &str) -> Result<()> {
     let (tx_cmd, rx_cmd) = unbounded::<SimCmd>();
     let (tx_msg, rx_msg) = unbounded();
 
-    // Spawn worker thread
-    let netlist_path = Path::new(path).to_path_buf();
-    std::thread::spawn(move || worker_loop(netlist_path, rx_cmd, tx_msg));
+    // Spawn the worker pool: every worker takes the next queued job
+    for _ in 0..worker_count() {
+        let netlist_path = Path::new(path).to_path_buf();
+        let (rx_cmd, tx_msg) = (rx_cmd.clone(), tx_msg.clone());
+        std::thread::spawn(move || worker_loop(netlist_path, rx_cmd, tx_msg));
+    }
 
     let mut app = App::new(path.to_string(), input);
+    refresh_netlist(&mut app, Path::new(path));
     let term_size: Rect = terminal
         .size()
         .context("Failed to read terminal size")?
@@ -68,7 +80,6 @@ pub fn run_tui(path: &str) -> Result<()> {
             app.nvim_warning = Some(format!("nvim unavailable: {err}"));
         }
     }
-    let mut fatal_error: Option<String> = None;
     let mut quit_requested = false;
 
     loop {
@@ -84,13 +95,11 @@ pub fn run_tui(path: &str) -> Result<()> {
             .as_mut()
             .map(|nvim| nvim.poll_events())
             .unwrap_or_default();
-        let nvim_dead = app
-            .nvim
-            .as_ref()
-            .is_some_and(|nvim| !nvim.is_alive());
+        let nvim_dead = app.nvim.as_ref().is_some_and(|nvim| !nvim.is_alive());
         for event in events {
             match event {
                 NvimEvent::Saved(path) => saved_paths.push(path),
+                NvimEvent::Cursor(line) => app.set_cursor_line(line),
                 NvimEvent::Help => app.toggle_help(),
                 NvimEvent::Config => app.toggle_config(),
                 NvimEvent::Quit => quit_requested = true,
@@ -100,6 +109,10 @@ pub fn run_tui(path: &str) -> Result<()> {
         for saved_path in saved_paths {
             let path = saved_path.unwrap_or_else(|| app.path.clone());
             refresh_netlist(&mut app, Path::new(&path));
+            // every edit that parses is run, while the older results stay browsable
+            if app.diags.is_empty() {
+                enqueue_run(&mut app, &tx_cmd)?;
+            }
         }
 
         if nvim_dead {
@@ -130,23 +143,14 @@ pub fn run_tui(path: &str) -> Result<()> {
 
         // handle simulator messages
         while let Ok(msg) = rx_msg.try_recv() {
-            match msg {
-                SimMsg::FatalError(err) => {
-                    fatal_error = Some(err);
-                }
-                other => apply_sim_update(&mut app, other),
-            }
-        }
-
-        if fatal_error.is_some() {
-            break;
+            apply_sim_update(&mut app, msg);
         }
     }
+    for job in &app.jobs {
+        job.cancel.cancel();
+    }
     if let Some(nvim) = app.nvim.as_mut() {
         nvim.quit();
     }
-    if let Some(err) = fatal_error {
-        return Err(anyhow::Error::msg(err));
-    }
     Ok(())
 }
@@ -0,0 +1,106 @@
+//! The parsed circuit as a list of nodes with the devices on them, to check connectivity
+//! without a schematic.
+
+use spicy_parser::instance_parser::Deck;
+use spicy_parser::lint::terminals;
+use spicy_parser::{SourceMap, Span};
+
+/// A device card, as it reads in the netlist.
+#[derive(Debug, Clone)]
+pub struct TopologyDevice {
+    pub name: String,
+    /// Line (1-based) of the card in the netlist, none for a card of an included file.
+    pub line: Option<usize>,
+    /// The first line of the card, or just the device name for a device of a subcircuit.
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TopologyNode {
+    pub name: String,
+    /// Indices into [`Topology::devices`] of the devices with a terminal on this node.
+    pub devices: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Topology {
+    /// Ground first, then the other nodes by name.
+    pub nodes: Vec<TopologyNode>,
+    pub devices: Vec<TopologyDevice>,
+}
+
+impl Topology {
+    pub fn from_deck(deck: &Deck, source_map: &SourceMap) -> Self {
+        let node_names = deck.node_mapping.node_names_mna_order();
+        let mut nodes: Vec<TopologyNode> = std::iter::once("0".to_string())
+            .chain(node_names)
+            .map(|name| TopologyNode {
+                name,
+                devices: Vec::new(),
+            })
+            .collect();
+
+        let mut devices: Vec<TopologyDevice> = Vec::new();
+        for (node, name, span) in terminals(deck) {
+            let index = match devices.iter().position(|d| d.name == name) {
+                Some(index) => index,
+                None => {
+                    devices.push(device(name, span, source_map));
+                    devices.len() - 1
+                }
+            };
+            let on_node = &mut nodes[node.0].devices;
+            if !on_node.contains(&index) {
+                on_node.push(index);
+            }
+        }
+        nodes[1..].sort_by(|a, b| a.name.cmp(&b.name));
+        Self { nodes, devices }
+    }
+
+    /// Whether a device on `node` has its card on `line`.
+    pub fn node_on_line(&self, node: &TopologyNode, line: usize) -> bool {
+        node.devices
+            .iter()
+            .any(|&d| self.devices[d].line == Some(line))
+    }
+
+    /// The row of the first node with a device card on `line`, counting a row per node and
+    /// one per device under it, as the topology view draws them.
+    pub fn row_of_line(&self, line: usize) -> Option<usize> {
+        let mut row = 0;
+        for node in &self.nodes {
+            if self.node_on_line(node, line) {
+                return Some(row);
+            }
+            row += 1 + node.devices.len();
+        }
+        None
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.nodes.iter().map(|node| 1 + node.devices.len()).sum()
+    }
+}
+
+fn device(name: &str, span: Span, source_map: &SourceMap) -> TopologyDevice {
+    let content = source_map.get_content(span.source_index);
+    let start = content[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let end = content[span.start..]
+        .find('\n')
+        .map_or(content.len(), |i| span.start + i);
+    let card = content[start..end].trim();
+    // the card of a device expanded from a subcircuit is not named after it
+    let text = match card.get(..name.len()) {
+        Some(start) if start.eq_ignore_ascii_case(name) => card.to_string(),
+        _ => name.to_string(),
+    };
+    TopologyDevice {
+        name: name.to_string(),
+        line: span
+            .source_index
+            .is_main()
+            .then(|| content[..start].matches('\n').count() + 1),
+        text,
+    }
+}
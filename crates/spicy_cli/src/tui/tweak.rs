@@ -0,0 +1,80 @@
+//! Tweak mode: scale the value of a device card in log steps and run the netlist again with
+//! it, without touching the file.
+//!
+//! A tweak wraps the value token of the card in an expression, `R1 a b 1k` running as
+//! `R1 a b {(1k)*1.21}`, so any value the parser reads can be scaled.
+
+/// Factor of one step: twelve steps to a decade, the spacing of the E12 series.
+pub(crate) const TWEAK_STEP: f64 = 1.211_527_658_628_589;
+
+/// A device whose value can be tweaked, as found in the netlist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TweakTarget {
+    pub name: String,
+    /// The value token as written.
+    pub value: String,
+}
+
+/// The name and the byte range of the value token of a card with a plain value: a resistor,
+/// capacitor or inductor, or a source with a (`DC`) value first.
+fn value_token(line: &str) -> Option<(&str, std::ops::Range<usize>)> {
+    let mut tokens = line.split_whitespace().map(|token| {
+        let start = token.as_ptr() as usize - line.as_ptr() as usize;
+        (token, start..start + token.len())
+    });
+    let (name, _) = tokens.next()?;
+    let kind = name.chars().next()?.to_ascii_uppercase();
+    if !matches!(kind, 'R' | 'C' | 'L' | 'V' | 'I') {
+        return None;
+    }
+    // past the two nodes
+    let (mut value, mut range) = tokens.nth(2)?;
+    if matches!(kind, 'V' | 'I') && value.eq_ignore_ascii_case("dc") {
+        (value, range) = tokens.next()?;
+    }
+    let numeric = value.starts_with('{')
+        || value
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+'));
+    numeric.then_some((name, range))
+}
+
+/// Every device card of `netlist` with a value to tweak, in netlist order.
+pub fn tweak_targets(netlist: &str) -> Vec<TweakTarget> {
+    netlist
+        .lines()
+        .filter_map(|line| {
+            let (name, range) = value_token(line)?;
+            Some(TweakTarget {
+                name: name.to_string(),
+                value: line[range].to_string(),
+            })
+        })
+        .collect()
+}
+
+/// `netlist` with the value of each device of `tweaks` scaled by its factor.
+pub fn apply_tweaks(netlist: &str, tweaks: &[(String, f64)]) -> String {
+    let mut out = String::with_capacity(netlist.len());
+    for line in netlist.split_inclusive('\n') {
+        let tweak = value_token(line).and_then(|(name, range)| {
+            let (_, factor) = tweaks.iter().find(|(n, _)| n.eq_ignore_ascii_case(name))?;
+            Some((range, *factor))
+        });
+        match tweak {
+            Some((range, factor)) => {
+                let value = &line[range.clone()];
+                let inner = value
+                    .strip_prefix('{')
+                    .and_then(|v| v.strip_suffix('}'))
+                    .unwrap_or(value);
+                out.push_str(&line[..range.start]);
+                out.push_str(&format!("{{({inner})*{factor}}}"));
+                out.push_str(&line[range.end..]);
+            }
+            None => out.push_str(line),
+        }
+    }
+    out
+}
@@ -46,11 +46,35 @@ fn help_text() -> Text<'static> {
         help_line("1..N", "select visible results tab"),
         Line::from(""),
         help_section("run"),
-        help_line("r", "run all simulations"),
+        help_line("r", "queue a run of all simulations"),
+        help_line("x", "cancel the shown run, or the newest unfinished one"),
+        help_line("[ / ]", "show an older/newer run, or follow the newest"),
         Line::from(""),
-        help_section("transient (right)"),
+        help_section("tweak"),
+        help_line("v", "tweak device values, previewing each change in f32"),
+        help_line("Up / Down", "pick the device"),
+        help_line("+ / -", "scale its value by an E12 step"),
+        help_line(
+            "0 / Esc",
+            "reset its value / leave tweak mode and run in f64",
+        ),
+        Line::from(""),
+        help_section("topology"),
+        help_line("t", "show nodes and their devices instead of the results"),
+        help_line("Up / Down", "scroll (follows the netlist cursor)"),
+        Line::from(""),
+        help_section("plots: dc / ac / tran (right)"),
         help_line("Up / Down", "select node"),
         help_line("Enter", "toggle node"),
+        help_line("+ / -", "zoom in/out on time or sweep"),
+        help_line("* / /", "zoom in/out on the values"),
+        help_line("Shift-arrows", "pan"),
+        help_line("0", "show everything"),
+        help_line("a / b", "place or pick cursor A/B"),
+        help_line(", / .", "move the picked cursor"),
+        help_line("Backspace", "remove the cursors"),
+        help_line("l", "log/linear magnitude (ac)"),
+        help_line("o", "overlay the run before the shown one"),
     ]);
     Text::from(lines)
 }
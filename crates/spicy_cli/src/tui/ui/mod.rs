@@ -8,9 +8,10 @@ mod config;
 mod help;
 mod netlist;
 mod output;
+mod topology;
 mod utils;
 
-pub use utils::format_error_snippet;
+pub use utils::{format_error_snippet, render_error_snippet, span_location};
 
 #[derive(Clone, Copy, Debug)]
 pub struct NetlistLayout {
@@ -30,7 +31,11 @@ pub fn main_layout(area: Rect) -> [Rect; 2] {
 pub fn netlist_layout(left: Rect) -> NetlistLayout {
     let [header, body] = utils::split_v(left, 3);
     let inner = Block::default().borders(Borders::ALL).inner(body);
-    NetlistLayout { header, body, inner }
+    NetlistLayout {
+        header,
+        body,
+        inner,
+    }
 }
 
 pub fn ui(f: &mut Frame, app: &App) {
@@ -48,9 +48,11 @@ pub(super) fn draw_netlist(f: &mut Frame, area: Rect, app: &App) {
     } else {
         let view = render_netlist_lines(
             &app.raw_netlist,
+            app.source_map.as_ref(),
             app.scroll,
             inner.height as usize,
             &app.diags,
+            &app.lints,
         );
         let wrap = Wrap { trim: false };
         f.render_widget(Paragraph::new(view).wrap(wrap), inner);
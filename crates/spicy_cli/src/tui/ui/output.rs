@@ -3,29 +3,18 @@ use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::prelude::Span as UiSpan;
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Text};
-use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, Tabs};
-use spicy_simulate::{DcSweepResult, OperatingPointResult};
+use ratatui::widgets::{Block, Borders, Cell, Gauge, Paragraph, Row, Table, Tabs};
+use spicy_simulate::{
+    DcSweepResult, OperatingPointResult, Progress, SimulationWarning, TransientResult,
+};
 
-use crate::tui::app::{App, Tab};
-use crate::tui::graph::{Graph, Series, compute_y_bounds};
+use crate::progress::format_duration;
+use crate::tui::app::{AcResult, App, Job, JobStatus, Tab};
+use crate::tui::graph::{PlotSpec, PlotView, Reference, Trace, render_plot};
+use crate::tui::tweak::tweak_targets;
 
 use super::utils::split_v;
 
-fn palette_color(index: usize) -> Color {
-    // Stable color mapping for series
-    const COLORS: [Color; 8] = [
-        Color::Yellow,
-        Color::Cyan,
-        Color::LightMagenta,
-        Color::Green,
-        Color::Blue,
-        Color::LightRed,
-        Color::LightCyan,
-        Color::Magenta,
-    ];
-    COLORS[index % COLORS.len()]
-}
-
 fn tab_title(tab: Tab) -> Line<'static> {
     match tab {
         Tab::Op => Line::from(vec![
@@ -36,6 +25,10 @@ fn tab_title(tab: Tab) -> Line<'static> {
             UiSpan::styled("↯ ", Style::default().fg(Color::Cyan)),
             UiSpan::raw("dc"),
         ]),
+        Tab::Ac => Line::from(vec![
+            UiSpan::styled("∿ ", Style::default().fg(Color::Green)),
+            UiSpan::raw("ac"),
+        ]),
         Tab::Trans => Line::from(vec![
             UiSpan::styled("⏱ ", Style::default().fg(Color::LightMagenta)),
             UiSpan::raw("tran"),
@@ -43,15 +36,88 @@ fn tab_title(tab: Tab) -> Line<'static> {
     }
 }
 
-fn draw_empty_results(f: &mut Frame, tabs_area: Rect, body: Rect) {
+fn job_title(job: &Job) -> Line<'static> {
+    let (mark, color) = match job.status {
+        JobStatus::Queued => ("…", Color::DarkGray),
+        JobStatus::Running => ("▶", Color::Cyan),
+        JobStatus::Done => ("✓", Color::Green),
+        JobStatus::Cancelled => ("⊘", Color::Yellow),
+        JobStatus::Failed(_) => ("✗", Color::LightRed),
+    };
+    let mut title = vec![
+        UiSpan::styled(format!("{mark} "), Style::default().fg(color)),
+        UiSpan::raw(format!("#{}", job.id)),
+    ];
+    if job.preview {
+        title.push(UiSpan::styled(
+            " preview",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    Line::from(title)
+}
+
+/// The history of runs, the shown one selected.
+fn draw_jobs(f: &mut Frame, area: Rect, app: &App) {
+    let title = match app.compared_job() {
+        Some(job) => format!("runs (overlaying #{})", job.id),
+        None => "runs".to_string(),
+    };
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let shown = app.shown_job().map(|job| job.id);
+    let selected = app.jobs.iter().position(|job| Some(job.id) == shown);
+    let titles = app.jobs.iter().map(job_title).collect::<Vec<_>>();
+    let highlight = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD | Modifier::REVERSED);
+    f.render_widget(
+        Tabs::new(titles)
+            .select(selected)
+            .highlight_style(highlight)
+            .block(block),
+        area,
+    );
+}
+
+/// The progress of the running analysis of a job, with the time left at its pace so far.
+fn draw_progress(f: &mut Frame, area: Rect, job: &Job, progress: &Progress) {
+    let eta = progress
+        .eta()
+        .map_or_else(|| "--".to_string(), format_duration);
+    let label = format!(
+        "{}/{}  elapsed {}  eta {}",
+        progress.step,
+        progress.total_steps,
+        format_duration(progress.elapsed),
+        eta
+    );
+    let title = format!("run #{} · {}", job.id, progress.analysis);
+    f.render_widget(
+        Gauge::default()
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .gauge_style(Style::default().fg(Color::Cyan))
+            .ratio(progress.fraction().clamp(0.0, 1.0))
+            .label(label),
+        area,
+    );
+}
+
+fn draw_empty_results(f: &mut Frame, tabs_area: Rect, body: Rect, app: &App) {
     let tabs_block = Block::default().borders(Borders::ALL).title("results");
     f.render_widget(tabs_block, tabs_area);
 
+    let status = match app.shown_job() {
+        Some(job) if job.status == JobStatus::Queued => format!("run #{} is queued", job.id),
+        Some(job) if job.status == JobStatus::Running => format!("run #{} is running", job.id),
+        Some(job) => format!("run #{} has no results", job.id),
+        None if app.jobs.is_empty() => "no results yet".to_string(),
+        None => "simulating…".to_string(),
+    };
     let helper = Text::from(vec![
-        Line::from("no results yet"),
+        Line::from(status),
         Line::from(""),
         Line::from("Press 'r' to run all simulations"),
-        Line::from("Ensure your netlist includes .op/.dc/.tran commands"),
+        Line::from("Ensure your netlist includes .op/.dc/.ac/.tran commands"),
     ]);
     f.render_widget(
         Paragraph::new(helper).block(Block::default().borders(Borders::ALL)),
@@ -59,17 +125,70 @@ fn draw_empty_results(f: &mut Frame, tabs_area: Rect, body: Rect) {
     );
 }
 
+fn draw_failure(f: &mut Frame, tabs_area: Rect, body: Rect, job: &Job, err: &str) {
+    let tabs_block = Block::default().borders(Borders::ALL).title("results");
+    f.render_widget(tabs_block, tabs_area);
+    let lines: Vec<Line> = err.lines().map(Line::from).collect();
+    f.render_widget(
+        Paragraph::new(lines)
+            .style(Style::default().fg(Color::LightRed))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("run #{} failed", job.id)),
+            ),
+        body,
+    );
+}
+
 pub(super) fn draw_outputs(f: &mut Frame, area: Rect, app: &App) {
+    let [jobs_area, area] = split_v(area, 3);
+    draw_jobs(f, jobs_area, app);
+    let running = app
+        .shown_job()
+        .filter(|job| job.status == JobStatus::Running)
+        .and_then(|job| Some((job, job.progress.as_ref()?)));
+    let area = match running {
+        Some((job, progress)) => {
+            let [progress_area, area] = split_v(area, 3);
+            draw_progress(f, progress_area, job, progress);
+            area
+        }
+        None => area,
+    };
+    let area = if app.tweaking {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),
+                Constraint::Length(TWEAK_ROWS as u16 + 2),
+            ])
+            .split(area);
+        draw_tweaks(f, chunks[1], app);
+        chunks[0]
+    } else {
+        area
+    };
+    if app.show_topology {
+        super::topology::draw_topology(f, area, app);
+        return;
+    }
     let [tabs_area, body] = split_v(area, 3);
 
     let available_tabs = app.available_tabs();
+    let failure = app.shown_job().and_then(|job| match &job.status {
+        JobStatus::Failed(err) => Some((job, err.as_str())),
+        _ => None,
+    });
     if available_tabs.is_empty() {
-        draw_empty_results(f, tabs_area, body);
+        match failure {
+            Some((job, err)) => draw_failure(f, tabs_area, body, job, err),
+            None => draw_empty_results(f, tabs_area, body, app),
+        }
         return;
     }
 
-    let titles: Vec<Line<'static>> =
-        available_tabs.iter().copied().map(tab_title).collect();
+    let titles: Vec<Line<'static>> = available_tabs.iter().copied().map(tab_title).collect();
 
     let tabs = Tabs::new(titles)
         .select(app.selected_tab_index(&available_tabs))
@@ -84,19 +203,53 @@ pub(super) fn draw_outputs(f: &mut Frame, area: Rect, app: &App) {
     };
     f.render_widget(tabs.style(tabs_style), tabs_area);
 
-    match app.selected_tab(&available_tabs) {
+    let selected = app.selected_tab(&available_tabs);
+    let simulation: Vec<&SimulationWarning> = match selected {
+        Some(Tab::Op) => app.op().iter().flat_map(|op| &op.warnings).collect(),
+        Some(Tab::DC) => app.dc().iter().flat_map(|dc| dc.warnings()).collect(),
+        Some(Tab::Ac) => Vec::new(),
+        Some(Tab::Trans) => app.trans().iter().flat_map(|tr| &tr.warnings).collect(),
+        None => Vec::new(),
+    };
+    // the netlist pane marks the lint warnings too, but not while nvim draws it; a run that
+    // failed after some of its analyses keeps their results
+    let warnings: Vec<String> = failure
+        .map(|(_, err)| err.to_string())
+        .into_iter()
+        .chain(app.lints.iter().map(|w| w.to_string()))
+        .chain(simulation.iter().map(|w| w.to_string()))
+        .collect();
+    let body = if warnings.is_empty() {
+        body
+    } else {
+        // borders + up to 4 lines of warnings
+        let height = warnings.len().min(4) as u16 + 2;
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(height)])
+            .split(body);
+        draw_warnings(f, chunks[1], &warnings);
+        chunks[0]
+    };
+
+    match selected {
         Some(Tab::Op) => {
-            if let Some(op) = &app.op {
+            if let Some(op) = app.op() {
                 draw_op(f, body, op);
             }
         }
         Some(Tab::DC) => {
-            if let Some(dc) = &app.dc {
-                draw_dc(f, body, dc);
+            if let Some(dc) = app.dc() {
+                draw_dc(f, body, app, dc);
+            }
+        }
+        Some(Tab::Ac) => {
+            if let Some(ac) = app.ac() {
+                draw_ac(f, body, app, ac);
             }
         }
         Some(Tab::Trans) => {
-            if let Some(tr) = &app.trans {
+            if let Some(tr) = app.trans() {
                 draw_tran(f, body, app, tr);
             }
         }
@@ -109,8 +262,65 @@ pub(super) fn draw_outputs(f: &mut Frame, area: Rect, app: &App) {
     }
 }
 
+/// Devices listed at once in tweak mode.
+const TWEAK_ROWS: usize = 5;
+
+/// The devices to tweak, around the picked one, with their factors.
+fn draw_tweaks(f: &mut Frame, area: Rect, app: &App) {
+    let targets = tweak_targets(&app.raw_netlist);
+    let current = app.tweak_index.min(targets.len().saturating_sub(1));
+    let first = (current + 1).saturating_sub(TWEAK_ROWS);
+    let lines: Vec<Line> = targets
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(TWEAK_ROWS)
+        .map(|(i, target)| {
+            let factor = app.tweak_factor(&target.name);
+            let text = format!(
+                "{} {:<8} {:<12} x{factor:.3}",
+                if i == current { ">" } else { " " },
+                target.name,
+                target.value,
+            );
+            let style = match (i == current, factor != 1.0) {
+                (true, _) => Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+                (false, true) => Style::default().fg(Color::Yellow),
+                (false, false) => Style::default(),
+            };
+            Line::styled(text, style)
+        })
+        .collect();
+    let title = "tweak: Up/Down device, +/- value, 0 reset, Esc leave";
+    let body = if lines.is_empty() {
+        Paragraph::new("no device with a plain value to tweak")
+    } else {
+        Paragraph::new(lines)
+    };
+    f.render_widget(
+        body.block(Block::default().borders(Borders::ALL).title(title)),
+        area,
+    );
+}
+
+fn draw_warnings(f: &mut Frame, area: Rect, warnings: &[String]) {
+    let lines: Vec<Line> = warnings.iter().map(|w| Line::from(w.as_str())).collect();
+    f.render_widget(
+        Paragraph::new(lines)
+            .style(Style::default().fg(Color::Yellow))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("warnings ({})", warnings.len())),
+            ),
+        area,
+    );
+}
+
 fn draw_op(f: &mut Frame, area: Rect, op: &OperatingPointResult) {
-    use std::collections::{BTreeSet, HashMap};
+    use std::collections::BTreeSet;
 
     let mut names: BTreeSet<String> = BTreeSet::new();
     for (n, _) in &op.voltages {
@@ -120,9 +330,6 @@ fn draw_op(f: &mut Frame, area: Rect, op: &OperatingPointResult) {
         names.insert(n.clone());
     }
 
-    let vmap: HashMap<&str, f64> = op.voltages.iter().map(|(n, v)| (n.as_str(), *v)).collect();
-    let imap: HashMap<&str, f64> = op.currents.iter().map(|(n, i)| (n.as_str(), *i)).collect();
-
     let header = Row::new(vec![
         Cell::from("node"),
         Cell::from("voltage (V)"),
@@ -131,11 +338,11 @@ fn draw_op(f: &mut Frame, area: Rect, op: &OperatingPointResult) {
     .style(Style::default().add_modifier(Modifier::BOLD));
 
     let rows = names.into_iter().map(|name| {
-        let v_str = match vmap.get(name.as_str()) {
+        let v_str = match op.voltage(&name) {
             Some(v) => format!("{:.6}", v),
             None => "-".to_string(),
         };
-        let i_str = match imap.get(name.as_str()) {
+        let i_str = match op.current(&name) {
             Some(i) => format!("{:.6}", i),
             None => "-".to_string(),
         };
@@ -160,93 +367,133 @@ fn draw_op(f: &mut Frame, area: Rect, op: &OperatingPointResult) {
     f.render_widget(table, area);
 }
 
-fn draw_dc(f: &mut Frame, area: Rect, _dc: &DcSweepResult) {
-    f.render_widget(
-        Paragraph::new(" DC sweep (rendering TBD) ").block(Block::default().borders(Borders::ALL)),
-        area,
-    );
+fn dc_traces(dc: &DcSweepResult) -> Vec<Trace> {
+    let x: Vec<f64> = dc.results.iter().map(|(_, value)| *value).collect();
+    let names: Vec<String> = dc
+        .results
+        .first()
+        .map(|(op, _)| op.voltages.iter().map(|(name, _)| name.clone()).collect())
+        .unwrap_or_default();
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| Trace {
+            name,
+            x: x.clone(),
+            y: dc
+                .results
+                .iter()
+                .map(|(op, _)| op.voltages.get(index).map_or(0.0, |(_, v)| *v))
+                .collect(),
+        })
+        .collect()
 }
 
-fn draw_tran(
-    f: &mut Frame,
-    area: Rect,
-    app: &crate::tui::app::App,
-    tr: &spicy_simulate::trans::TransientResult,
-) {
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
-        .split(area);
+fn ac_traces(ac: &AcResult) -> Vec<Trace> {
+    ac.node_names
+        .iter()
+        .zip(&ac.magnitudes)
+        .map(|(name, magnitude)| Trace {
+            name: name.clone(),
+            x: ac.frequencies.clone(),
+            y: magnitude.clone(),
+        })
+        .collect()
+}
 
-    // Left: Chart with GraphType::Line
-    let show_indices = if app.trans_selected_nodes.is_empty() {
-        vec![0usize]
-    } else {
-        app.trans_selected_nodes.clone()
-    };
+fn tran_traces(tr: &TransientResult) -> Vec<Trace> {
+    tr.node_names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| Trace {
+            name: name.clone(),
+            x: tr.times.clone(),
+            y: tr
+                .samples
+                .iter()
+                .map(|s| s.get(index).copied().unwrap_or(0.0))
+                .collect(),
+        })
+        .collect()
+}
 
-    let mut datasets: Vec<Series> = Vec::new();
-
-    for (index, output_index) in show_indices.iter().enumerate() {
-        let values: Vec<f64> = tr
-            .samples
-            .iter()
-            .map(|s| {
-                if *output_index < s.len() {
-                    s[*output_index]
-                } else {
-                    0.0
-                }
-            })
-            .collect();
-
-        let name = tr
-            .node_names
-            .get(*output_index)
-            .cloned()
-            .unwrap_or_else(|| format!("n{}", output_index));
-
-        datasets.push(Series::from_times_and_values(
-            name,
-            palette_color(index),
-            &tr.times,
-            &values,
-        ));
-    }
+fn draw_dc(f: &mut Frame, area: Rect, app: &App, dc: &DcSweepResult) {
+    let spec = PlotSpec {
+        title: "DC sweep",
+        x_label: "sweep",
+        y_label: "V",
+        x_is_time: false,
+        x_log: false,
+    };
+    let compared = app
+        .compared_job()
+        .and_then(|job| Some((job.id, dc_traces(job.dc.as_ref()?))));
+    draw_plot(f, area, app, &spec, &dc_traces(dc), compared, &app.dc_plot);
+}
 
-    let [y_min, y_max] = compute_y_bounds(&datasets);
+fn draw_ac(f: &mut Frame, area: Rect, app: &App, ac: &AcResult) {
+    let spec = PlotSpec {
+        title: "AC magnitude",
+        x_label: "frequency",
+        y_label: "|V|",
+        x_is_time: false,
+        x_log: true,
+    };
+    let compared = app
+        .compared_job()
+        .and_then(|job| Some((job.id, ac_traces(job.ac.as_ref()?))));
+    draw_plot(f, area, app, &spec, &ac_traces(ac), compared, &app.ac_plot);
+}
 
-    let g = Graph {
+fn draw_tran(f: &mut Frame, area: Rect, app: &App, tr: &TransientResult) {
+    let spec = PlotSpec {
         title: "transient",
         x_label: "time",
         y_label: "V",
-        x_bounds: [
-            *tr.times.first().unwrap_or(&0.0),
-            *tr.times.last().unwrap_or(&1.0),
-        ],
-        y_bounds: [y_min, y_max],
-        series: datasets,
         x_is_time: true,
-        x_label_count: 0,
-        y_label_count: 0,
+        x_log: false,
     };
-    g.render(f, chunks[0]);
-
-    draw_tran_node_list(f, chunks[1], app, tr);
+    let compared = app
+        .compared_job()
+        .and_then(|job| Some((job.id, tran_traces(job.trans.as_ref()?))));
+    draw_plot(
+        f,
+        area,
+        app,
+        &spec,
+        &tran_traces(tr),
+        compared,
+        &app.trans_plot,
+    );
 }
 
-fn draw_tran_node_list(
+/// The plot of `traces` on the left and the list to pick them from on the right; `compared`
+/// holds the same traces of an earlier run, by its id.
+fn draw_plot(
     f: &mut Frame,
     area: Rect,
-    app: &crate::tui::app::App,
-    tr: &spicy_simulate::trans::TransientResult,
+    app: &App,
+    spec: &PlotSpec,
+    traces: &[Trace],
+    compared: Option<(usize, Vec<Trace>)>,
+    view: &PlotView,
 ) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+        .split(area);
+    let reference = compared
+        .as_ref()
+        .map(|(job, traces)| Reference { job: *job, traces });
+    render_plot(f, chunks[0], spec, traces, reference.as_ref(), view);
+    draw_trace_list(f, chunks[1], app, traces, view);
+}
+
+fn draw_trace_list(f: &mut Frame, area: Rect, app: &App, traces: &[Trace], view: &PlotView) {
     let mut rows: Vec<Row> = Vec::new();
-    let current = app
-        .trans_list_index
-        .min(tr.node_names.len().saturating_sub(1));
-    for (i, name) in tr.node_names.iter().enumerate() {
-        let selected = app.trans_selected_nodes.contains(&i);
+    let current = view.list_index.min(traces.len().saturating_sub(1));
+    for (i, trace) in traces.iter().enumerate() {
+        let selected = view.selected.contains(&i);
         let is_current = i == current;
         let marker = if selected { "[x]" } else { "[ ]" };
         let sel_cell = if is_current {
@@ -254,7 +501,7 @@ fn draw_tran_node_list(
         } else {
             format!(" {}", marker)
         };
-        let mut row = Row::new(vec![Cell::from(sel_cell), Cell::from(name.clone())]);
+        let mut row = Row::new(vec![Cell::from(sel_cell), Cell::from(trace.name.clone())]);
         if is_current {
             let style = if app.right_pane_focused() {
                 Style::default()
@@ -0,0 +1,62 @@
+use ratatui::Frame;
+use ratatui::layout::Rect;
+use ratatui::prelude::Span as UiSpan;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph};
+
+use crate::tui::app::App;
+
+/// Every node with the device cards on it, the ones on the netlist cursor's line highlighted.
+pub(super) fn draw_topology(f: &mut Frame, area: Rect, app: &App) {
+    let title = format!("topology (line {})", app.cursor_line);
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let Some(topology) = app.topology.as_ref() else {
+        f.render_widget(
+            Paragraph::new("the netlist does not parse yet").block(block),
+            area,
+        );
+        return;
+    };
+
+    let highlight = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let mut lines: Vec<Line> = Vec::with_capacity(topology.row_count());
+    for node in &topology.nodes {
+        let style = if topology.node_on_line(node, app.cursor_line) {
+            highlight
+        } else {
+            Style::default().add_modifier(Modifier::BOLD)
+        };
+        lines.push(Line::from(vec![
+            UiSpan::styled(format!("● {}", node.name), style),
+            UiSpan::styled(
+                format!(" ({})", node.devices.len()),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]));
+        for (i, &index) in node.devices.iter().enumerate() {
+            let device = &topology.devices[index];
+            let branch = if i + 1 == node.devices.len() {
+                "  └ "
+            } else {
+                "  ├ "
+            };
+            let style = if device.line == Some(app.cursor_line) {
+                highlight.add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            let line = device.line.map_or("inc".to_string(), |l| l.to_string());
+            lines.push(Line::from(vec![
+                UiSpan::styled(branch, Style::default().fg(Color::DarkGray)),
+                UiSpan::styled(device.text.clone(), style),
+                UiSpan::styled(format!("  :{line}"), Style::default().fg(Color::DarkGray)),
+            ]));
+        }
+    }
+
+    let scroll = app.topology_scroll.min(lines.len().saturating_sub(1)) as u16;
+    f.render_widget(Paragraph::new(lines).block(block).scroll((scroll, 0)), area);
+}
@@ -4,8 +4,9 @@ use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::prelude::Span as UiSpan;
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Text};
-use spicy_parser::Span;
 use spicy_parser::error::SpicyError;
+use spicy_parser::lint::LintWarning;
+use spicy_parser::{SourceMap, Span};
 
 pub(crate) fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let vertical = Layout::default()
@@ -101,11 +102,36 @@ pub fn format_error_snippet(src: &str, span: Span) -> Option<String> {
     ))
 }
 
+/// `path:line:col` of `span` in the file it points into, 1-based.
+pub fn span_location(source_map: &SourceMap, span: Span) -> String {
+    let src = source_map.get_content(span.source_index);
+    let start = span.start.min(src.len());
+    let before = &src[..start];
+    let line = before.matches('\n').count() + 1;
+    let col = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    let path = source_map.get_path(span.source_index);
+    format!("{}:{line}:{col}", path.display())
+}
+
+/// The snippet of `span` under a `--> path:line:col` header, taken from the file the span
+/// points into: an error in an included file shows that file and its line numbers.
+pub fn render_error_snippet(source_map: &SourceMap, span: Span) -> String {
+    let mut out = format!("  --> {}\n", span_location(source_map, span));
+    if let Some(snippet) = format_error_snippet(source_map.get_content(span.source_index), span) {
+        out.push_str(&snippet);
+    }
+    out
+}
+
+/// The netlist with its diagnostics under their lines. A diagnostic in an included file, or
+/// without a span, is listed at the top, with the place in its file when `source_map` has it.
 pub(crate) fn render_netlist_lines(
     raw_netlist: &str,
+    source_map: Option<&SourceMap>,
     scroll: usize,
     height: usize,
     diags: &[SpicyError],
+    lints: &[LintWarning],
 ) -> Text<'static> {
     let mut lines: Vec<Line<'static>> = Vec::new();
     let netlist: Vec<&str> = raw_netlist.lines().collect();
@@ -115,19 +141,35 @@ pub(crate) fn render_netlist_lines(
         .len()
         .max(2);
 
+    // errors in red, lint warnings in yellow; an error wins a line over a warning
+    let all_diags = lints
+        .iter()
+        .map(|lint| (lint.span(), lint.to_string(), Color::Yellow))
+        .chain(
+            diags
+                .iter()
+                .map(|diag| (diag.error_span(), diag.to_string(), Color::Red)),
+        );
+
     // for each diagnostic, find the line number
-    let mut diags_by_line: HashMap<usize, (LineDiagnostic, &SpicyError)> =
+    let mut diags_by_line: HashMap<usize, (LineDiagnostic, String, Color)> =
         std::collections::HashMap::new();
-    for diag in diags {
-        if let Some(span) = diag.error_span()
-            && let Some(ld) = LineDiagnostic::new(raw_netlist, span)
-        {
-            diags_by_line.insert(ld.line_index, (ld, diag));
+    for (span, message, color) in all_diags {
+        let included = |span: &Span| source_map.is_some() && !span.source_index.is_main();
+        let in_netlist = span.filter(|span| !included(span));
+        if let Some(ld) = in_netlist.and_then(|span| LineDiagnostic::new(raw_netlist, span)) {
+            diags_by_line.insert(ld.line_index, (ld, message, color));
         } else {
-            // simply display it at the top
+            // simply display it at the top, with its place when it is in an included file
+            let message = match (span, source_map) {
+                (Some(span), Some(source_map)) if included(&span) => {
+                    format!("{}: {message}", span_location(source_map, span))
+                }
+                _ => message,
+            };
             let spans = vec![
-                UiSpan::styled("! ".to_string(), Style::default().fg(Color::Red)),
-                UiSpan::styled(diag.to_string(), Style::default().fg(Color::Red)),
+                UiSpan::styled("! ".to_string(), Style::default().fg(color)),
+                UiSpan::styled(message, Style::default().fg(color)),
             ];
             lines.push(Line::from(spans));
         }
@@ -142,8 +184,8 @@ pub(crate) fn render_netlist_lines(
         spans.push(UiSpan::styled(gutter, Style::default().fg(Color::DarkGray)));
 
         match diags_by_line.get(&ln) {
-            Some((ld, diag)) => {
-                let err_style = Style::default().fg(Color::Red);
+            Some((ld, diag, color)) => {
+                let err_style = Style::default().fg(*color);
                 let pre = &raw[..ld.span_start_in_line];
                 let mid = &raw[ld.span_start_in_line..ld.span_end_in_line];
                 let post = &raw[ld.span_end_in_line..];
@@ -1,132 +1,241 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use anyhow::Result;
 use crossbeam_channel::{Receiver, Sender};
 use spicy_simulate::{
-    DcSweepResult, OperatingPointResult, SimulationConfig, TransientResult,
+    DcSweepResult, OperatingPointResult, Precision, Progress, SimulateObserver, SimulationConfig,
+    TransientResult,
+    ac::{AcSweep, simulate_ac},
     dc::{simulate_dc, simulate_op},
+    step::temperatures,
     trans::simulate_trans,
 };
 
-use crate::tui::app::App;
-use crate::tui::ui::format_error_snippet;
-use spicy_parser::{ParseOptions, SourceMap, error::SpicyError, netlist_types::Command, parse};
+use crate::tui::app::{AcResult, App, JobStatus};
+use crate::tui::ui::render_error_snippet;
+use spicy_parser::{
+    ParseCache, ParseOptions, SourceMap, error::SpicyError, instance_parser::Deck,
+    netlist_types::Command,
+};
+
+/// Worker threads running jobs side by side.
+pub(crate) fn worker_count() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get().clamp(1, 4))
+}
 
 #[derive(Clone, Debug)]
 pub enum SimCmd {
-    RunCurrentTab { config: SimulationConfig },
+    /// Run every analysis of `netlist`, the text of the netlist when the job was queued.
+    Run {
+        job: usize,
+        netlist: String,
+        config: SimulationConfig,
+    },
 }
 
 #[derive(Debug)]
 pub enum SimMsg {
-    SimulationStarted,
-    Op(OperatingPointResult),
-    Dc(DcSweepResult),
-    Transient(TransientResult),
-    FatalError(String),
-    Done,
+    Started(usize),
+    Op(usize, OperatingPointResult),
+    Dc(usize, DcSweepResult),
+    Ac(usize, AcResult),
+    Transient(usize, TransientResult),
+    Progress(usize, Progress),
+    Failed(usize, String),
+    Done(usize),
+}
+
+/// Queue a run of the netlist as it is now, with its tweaks, and with the current config.
+pub fn enqueue_run(app: &mut App, tx: &Sender<SimCmd>) -> Result<()> {
+    enqueue(app, tx, Precision::Double)
+}
+
+/// Queue a quick run of a tweak, solved in single precision.
+pub fn enqueue_preview(app: &mut App, tx: &Sender<SimCmd>) -> Result<()> {
+    enqueue(app, tx, Precision::Single)
+}
+
+fn enqueue(app: &mut App, tx: &Sender<SimCmd>, precision: Precision) -> Result<()> {
+    let (job, cancel) = app.push_job(precision == Precision::Single);
+    tx.send(SimCmd::Run {
+        job,
+        netlist: app.run_netlist(),
+        config: SimulationConfig {
+            cancel,
+            precision,
+            ..app.config.clone()
+        },
+    })?;
+    Ok(())
 }
 
 pub fn apply_sim_update(app: &mut App, msg: SimMsg) {
+    let id = match &msg {
+        SimMsg::Started(id)
+        | SimMsg::Op(id, _)
+        | SimMsg::Dc(id, _)
+        | SimMsg::Ac(id, _)
+        | SimMsg::Transient(id, _)
+        | SimMsg::Progress(id, _)
+        | SimMsg::Failed(id, _)
+        | SimMsg::Done(id) => *id,
+    };
+    let Some(job) = app.job_mut(id) else {
+        return;
+    };
     match msg {
-        SimMsg::SimulationStarted => {
-            app.op = None;
-            app.dc = None;
-            app.trans = None;
-            app.trans_selected_nodes.clear();
-            app.trans_list_index = 0;
+        SimMsg::Started(_) => job.status = JobStatus::Running,
+        SimMsg::Op(_, op) => job.op = Some(op),
+        SimMsg::Dc(_, dc) => {
+            job.dc = Some(dc);
+            job.progress = None;
+        }
+        SimMsg::Ac(_, ac) => job.ac = Some(ac),
+        SimMsg::Transient(_, tr) => {
+            job.trans = Some(tr);
+            job.progress = None;
+        }
+        SimMsg::Progress(_, progress) => job.progress = Some(progress),
+        SimMsg::Failed(_, err) => job.status = JobStatus::Failed(err),
+        SimMsg::Done(_) => {
+            job.progress = None;
+            if !job.status.is_finished() {
+                job.status = if job.cancel.is_cancelled() {
+                    JobStatus::Cancelled
+                } else {
+                    JobStatus::Done
+                };
+            }
+            app.prune_jobs();
         }
-        SimMsg::Op(op) => app.op = Some(op),
-        SimMsg::Dc(dc) => app.dc = Some(dc),
-        SimMsg::Transient(tr) => app.trans = Some(tr),
-        _ => {}
     }
     app.ensure_visible_tab();
 }
 
+/// Least time between two progress messages of a job, a few per frame at most.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Forwards the progress of a job's analyses to the UI.
+struct JobProgress {
+    job: usize,
+    tx: Sender<SimMsg>,
+    sent: Mutex<Option<Instant>>,
+}
+
+impl SimulateObserver for JobProgress {
+    fn on_progress(&self, progress: &Progress) {
+        let mut sent = self.sent.lock().unwrap();
+        let done = progress.step == progress.total_steps;
+        if sent.is_some_and(|at| at.elapsed() < PROGRESS_INTERVAL) && !done {
+            return;
+        }
+        *sent = Some(Instant::now());
+        let _ = self.tx.send(SimMsg::Progress(self.job, *progress));
+    }
+}
+
+/// The magnitude of every node voltage of an AC sweep.
+fn ac_magnitudes(deck: &Deck, ac: &AcSweep) -> AcResult {
+    let node_names = deck.node_mapping.node_names_mna_order();
+    let magnitudes = (0..node_names.len())
+        .map(|node| {
+            ac.iter()
+                .map(|(_, re, im)| re[node].hypot(im[node]))
+                .collect()
+        })
+        .collect();
+    AcResult {
+        frequencies: ac.iter().map(|(f, _, _)| *f).collect(),
+        node_names,
+        magnitudes,
+    }
+}
+
 fn format_parse_error(error: &SpicyError, source_map: &SourceMap) -> String {
     let mut out = format!("Parse error: {error}");
     if let Some(span) = error.error_span() {
-        let path = source_map.get_path(span.source_index);
-        out.push_str(&format!("\n--> {}", path.display()));
-        if let Some(snippet) = format_error_snippet(source_map.get_content(span.source_index), span) {
-            out.push('\n');
-            out.push_str(&snippet);
-        }
+        out.push('\n');
+        out.push_str(&render_error_snippet(source_map, span));
     }
     out
 }
 
 pub fn worker_loop(netlist_path: PathBuf, rx: Receiver<SimCmd>, tx: Sender<SimMsg>) {
+    // a rerun of an unchanged netlist skips the parse
+    let mut cache = ParseCache::new();
     while let Ok(cmd) = rx.recv() {
         match cmd {
-            SimCmd::RunCurrentTab { config } => {
-                let sim_config = config;
-                let input = match std::fs::read_to_string(&netlist_path) {
-                    Ok(input) => input,
-                    Err(err) => {
-                        let _ = tx.send(SimMsg::FatalError(format!(
-                            "Failed to read netlist: {}",
-                            err
-                        )));
-                        continue;
-                    }
-                };
-                let mut parse_options = ParseOptions::new_with_source(&netlist_path, input);
-
-                let deck = match parse(&mut parse_options) {
-                    Ok(deck) => deck,
-                    Err(e) => {
-                        let _ = tx.send(SimMsg::FatalError(format_parse_error(
-                            &e,
-                            &parse_options.source_map,
-                        )));
-                        continue;
-                    }
-                };
-
-                let _ = tx.send(SimMsg::SimulationStarted);
-
-                for command in &deck.commands {
-                    match command {
-                        Command::Op(_) => {
-                            match simulate_op(&deck, &sim_config) {
-                                Ok(op) => {
-                                    let _ = tx.send(SimMsg::Op(op));
-                                }
-                                Err(e) => {
-                                    let _ = tx.send(SimMsg::FatalError(format!(
-                                        "Simulation error: {}",
-                                        e
-                                    )));
-                                }
-                            }
-                            continue;
-                        }
-                        Command::Dc(command_params) => {
-                            let dc = simulate_dc(&deck, command_params, &sim_config);
-                            let _ = tx.send(SimMsg::Dc(dc));
-                            continue;
-                        }
-                        Command::Tran(command_params) => {
-                            match simulate_trans(&deck, command_params, &sim_config) {
-                                Ok(tr) => {
-                                    let _ = tx.send(SimMsg::Transient(tr));
-                                }
-                                Err(e) => {
-                                    let _ = tx.send(SimMsg::FatalError(format!(
-                                        "Simulation error: {}",
-                                        e
-                                    )));
-                                }
-                            }
-                            continue;
-                        }
-                        _ => {}
-                    }
+            SimCmd::Run {
+                job,
+                netlist,
+                config,
+            } => {
+                if !config.cancel.is_cancelled() {
+                    run_job(&netlist_path, &mut cache, job, netlist, config, &tx);
                 }
+                let _ = tx.send(SimMsg::Done(job));
+            }
+        }
+    }
+}
+
+fn run_job(
+    netlist_path: &Path,
+    cache: &mut ParseCache,
+    job: usize,
+    netlist: String,
+    config: SimulationConfig,
+    tx: &Sender<SimMsg>,
+) {
+    let _ = tx.send(SimMsg::Started(job));
+    let mut parse_options = ParseOptions::new_with_source(netlist_path, netlist);
+    let deck = match cache.parse(&mut parse_options) {
+        Ok(deck) => deck,
+        Err(e) => {
+            let err = format_parse_error(&e, &parse_options.source_map);
+            let _ = tx.send(SimMsg::Failed(job, err));
+            return;
+        }
+    };
 
-                let _ = tx.send(SimMsg::Done);
+    // one result per analysis, at the first `.temp` temperature
+    let sim_config = SimulationConfig {
+        temperature: temperatures(&deck, &config)[0],
+        observer: Some(Arc::new(JobProgress {
+            job,
+            tx: tx.clone(),
+            sent: Mutex::new(None),
+        })),
+        ..config
+    };
+
+    for command in &deck.commands {
+        let result = match command {
+            Command::Op(_) => simulate_op(&deck, &sim_config).map(|op| SimMsg::Op(job, op)),
+            Command::Dc(command_params) => {
+                simulate_dc(&deck, command_params, &sim_config).map(|dc| SimMsg::Dc(job, dc))
+            }
+            Command::Ac(command_params) => simulate_ac(&deck, command_params, &sim_config)
+                .map(|ac| SimMsg::Ac(job, ac_magnitudes(&deck, &ac))),
+            Command::Tran(command_params) => simulate_trans(&deck, command_params, &sim_config)
+                .map(|tr| SimMsg::Transient(job, tr)),
+            _ => continue,
+        };
+        match result {
+            Ok(msg) => {
+                let _ = tx.send(msg);
             }
+            // a cancelled job keeps what it has
+            Err(_) if sim_config.cancel.is_cancelled() => return,
+            Err(e) => {
+                let _ = tx.send(SimMsg::Failed(job, format!("Simulation error: {e}")));
+                return;
+            }
+        }
+        if sim_config.cancel.is_cancelled() {
+            return;
         }
     }
 }
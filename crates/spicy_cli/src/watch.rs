@@ -0,0 +1,204 @@
+//! `--watch`: rerun the analyses whenever the netlist or a file it includes changes, and print
+//! the measurements and operating point voltages that moved since the previous run.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use spicy_parser::{Compatibility, ParseOptions, parse};
+use spicy_simulate::{SimulationConfig, SimulationError, simulate_steps};
+
+use crate::{print_snippet, read_input};
+
+/// How often the watched files are looked at.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Modification time of every watched file, `None` for one that cannot be read.
+type Snapshot = Vec<(PathBuf, Option<SystemTime>)>;
+
+fn snapshot(paths: &[PathBuf]) -> Snapshot {
+    paths
+        .iter()
+        .map(|path| {
+            let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+            (path.clone(), modified)
+        })
+        .collect()
+}
+
+/// The values a run is compared on: the measurements, then the node voltages of the first
+/// operating point. Failed measurements are `None`.
+type KeyValues = Vec<(String, Option<f64>)>;
+
+/// One parse and simulation of `path`. Returns the files it read, so a failed parse still
+/// watches the includes it got to, and the key values when it simulated.
+fn run_once(
+    path: &Path,
+    compatibility: Compatibility,
+    variables: &HashMap<String, String>,
+    sim_config: SimulationConfig,
+) -> (Vec<PathBuf>, Option<KeyValues>) {
+    let input = match read_input(path) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("{e}");
+            return (vec![path.to_path_buf()], None);
+        }
+    };
+    let mut options = ParseOptions::new_with_source(path, input);
+    options.compatibility = compatibility;
+    options.variables = variables.clone();
+    let result = parse(&mut options);
+    // the source map holds the canonical main path, so watch the path as given too
+    let mut paths = vec![path.to_path_buf()];
+    paths.extend(options.source_map.paths().skip(1).map(Path::to_path_buf));
+
+    let deck = match result {
+        Ok(deck) => deck,
+        Err(e) => {
+            eprintln!("Parse error: {}", e);
+            if let Some(span) = e.error_span() {
+                print_snippet(&options.source_map, span);
+            }
+            return (paths, None);
+        }
+    };
+    let report = match simulate_steps(&mut options, deck, sim_config) {
+        Ok(report) => report,
+        Err(SimulationError::Topology(errors)) => {
+            for error in errors {
+                eprintln!("Topology error: {}", error);
+            }
+            return (paths, None);
+        }
+        Err(SimulationError::Parameters(errors)) => {
+            for error in errors {
+                eprintln!("Parameter error: {}", error);
+            }
+            return (paths, None);
+        }
+        Err(e) => {
+            eprintln!("Simulation error: {}", e);
+            return (paths, None);
+        }
+    };
+    for warning in report.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+
+    let mut values: KeyValues = report
+        .measurements()
+        .map(|m| (m.name.clone(), m.value))
+        .collect();
+    if let Some(op) = report.operating_points().next() {
+        values.extend(
+            op.voltages
+                .iter()
+                .map(|(node, v)| (format!("V({node})"), Some(*v))),
+        );
+    }
+    (paths, Some(values))
+}
+
+fn format_value(value: Option<f64>) -> String {
+    match value {
+        Some(value) => format!("{value:.6e}"),
+        None => "failed".to_string(),
+    }
+}
+
+fn print_values(values: &KeyValues) {
+    let width = values.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, value) in values {
+        println!("  {name:<width$}  {}", format_value(*value));
+    }
+}
+
+/// The lines of the values that differ from the previous run, with the relative change when
+/// both runs have a value.
+fn diff_lines(previous: &KeyValues, current: &KeyValues) -> Vec<String> {
+    let width = current
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    let mut lines = Vec::new();
+    for (name, value) in current {
+        let old = previous.iter().find(|(n, _)| n == name).map(|(_, v)| *v);
+        match old {
+            None => lines.push(format!("  {name:<width$}  new {}", format_value(*value))),
+            Some(old) if old == *value => {}
+            Some(old) => {
+                let change = match (old, value) {
+                    (Some(old), Some(new)) if old != 0.0 => {
+                        format!(" ({:+.3}%)", (new - old) / old.abs() * 100.0)
+                    }
+                    _ => String::new(),
+                };
+                lines.push(format!(
+                    "  {name:<width$}  {} -> {}{change}",
+                    format_value(old),
+                    format_value(*value)
+                ));
+            }
+        }
+    }
+    for (name, _) in previous {
+        if !current.iter().any(|(n, _)| n == name) {
+            lines.push(format!("  {name:<width$}  removed"));
+        }
+    }
+    lines
+}
+
+/// Simulate `path` and again every time one of its files changes, until interrupted.
+pub fn run_watch(
+    path: &Path,
+    compatibility: Compatibility,
+    variables: &HashMap<String, String>,
+    sim_config: impl Fn() -> SimulationConfig,
+) -> ! {
+    let mut previous: Option<KeyValues> = None;
+    loop {
+        let (paths, values) = run_once(path, compatibility, variables, sim_config());
+        match (&previous, &values) {
+            (_, None) => println!("[watch] run failed, keeping the last results"),
+            (None, Some(values)) => {
+                println!("[watch] {} values", values.len());
+                print_values(values);
+            }
+            (Some(previous), Some(values)) => {
+                let lines = diff_lines(previous, values);
+                if lines.is_empty() {
+                    println!("[watch] no values changed");
+                } else {
+                    println!("[watch] {} of {} values changed", lines.len(), values.len());
+                    for line in lines {
+                        println!("{line}");
+                    }
+                }
+            }
+        }
+        if values.is_some() {
+            previous = values;
+        }
+
+        println!("[watch] watching {} files, Ctrl-C to stop", paths.len());
+        let before = snapshot(&paths);
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let now = snapshot(&paths);
+            if now != before {
+                let changed: Vec<_> = before
+                    .iter()
+                    .zip(&now)
+                    .filter(|(a, b)| a != b)
+                    .map(|((path, _), _)| path.display().to_string())
+                    .collect();
+                println!("[watch] changed: {}", changed.join(", "));
+                break;
+            }
+        }
+    }
+}
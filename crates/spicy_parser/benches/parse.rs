@@ -0,0 +1,41 @@
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use spicy_parser::{ParseOptions, parse};
+
+/// A ladder of `instances` parametrized RC sections, each one a subcircuit instance.
+fn rc_ladder(instances: usize) -> String {
+    let mut netlist = String::from("rc ladder\n");
+    netlist.push_str(".subckt section in out r=1k c=1n gain=2\n");
+    netlist.push_str("R1 in mid {r*gain}\nR2 mid out {r/gain + r}\n");
+    netlist.push_str("C1 mid 0 {c}\nC2 out 0 {c*gain - c/2}\n.ends\n");
+    netlist.push_str("V1 n0 0 DC 1\n");
+    for i in 0..instances {
+        netlist.push_str(&format!("X{i} n{i} n{} section r={}\n", i + 1, 1000 + i));
+    }
+    netlist.push_str(".op\n.end\n");
+    netlist
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse/rc_ladder");
+    for instances in [100, 1_000, 5_000] {
+        let netlist = rc_ladder(instances);
+        group.throughput(Throughput::Elements(instances as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(instances),
+            &netlist,
+            |b, netlist| {
+                b.iter(|| {
+                    let mut options =
+                        ParseOptions::new_with_source("ladder.spicy", netlist.clone());
+                    black_box(parse(&mut options).expect("ladder should parse"))
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
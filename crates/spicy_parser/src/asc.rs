@@ -0,0 +1,512 @@
+//! Import of LTspice `.asc` schematics: the symbols, wires and net labels of a schematic are
+//! turned into a netlist, followed by its `!` text directives, and the netlist is parsed like
+//! any other deck.
+//!
+//! Only the symbols of LTspice's built-in library are known, by the pin positions of their
+//! `.asy` files; a schematic using any other symbol is rejected.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{
+    ParseOptions,
+    compat::Compatibility,
+    error::{AscError, SpicyError},
+    instance_parser::Deck,
+    parse,
+};
+
+type Point = (i64, i64);
+
+/// A built-in symbol: the letter of its instance names and its pins, as offsets from the
+/// symbol origin.
+struct SymbolKind {
+    name: &'static str,
+    prefix: char,
+    pins: &'static [Point],
+    /// The pins on the instance line, in netlist order.
+    nodes: &'static [usize],
+}
+
+const TWO_PINS: &[usize] = &[0, 1];
+const THREE_PINS: &[usize] = &[0, 1, 2];
+
+const SYMBOLS: &[SymbolKind] = &[
+    SymbolKind {
+        name: "res",
+        prefix: 'R',
+        pins: &[(16, 16), (16, 96)],
+        nodes: TWO_PINS,
+    },
+    SymbolKind {
+        name: "cap",
+        prefix: 'C',
+        pins: &[(16, 0), (16, 64)],
+        nodes: TWO_PINS,
+    },
+    SymbolKind {
+        name: "polcap",
+        prefix: 'C',
+        pins: &[(16, 0), (16, 64)],
+        nodes: TWO_PINS,
+    },
+    SymbolKind {
+        name: "ind",
+        prefix: 'L',
+        pins: &[(16, 16), (16, 96)],
+        nodes: TWO_PINS,
+    },
+    SymbolKind {
+        name: "ind2",
+        prefix: 'L',
+        pins: &[(16, 16), (16, 96)],
+        nodes: TWO_PINS,
+    },
+    SymbolKind {
+        name: "diode",
+        prefix: 'D',
+        pins: &[(16, 0), (16, 64)],
+        nodes: TWO_PINS,
+    },
+    SymbolKind {
+        name: "schottky",
+        prefix: 'D',
+        pins: &[(16, 0), (16, 64)],
+        nodes: TWO_PINS,
+    },
+    SymbolKind {
+        name: "zener",
+        prefix: 'D',
+        pins: &[(16, 0), (16, 64)],
+        nodes: TWO_PINS,
+    },
+    SymbolKind {
+        name: "led",
+        prefix: 'D',
+        pins: &[(16, 0), (16, 64)],
+        nodes: TWO_PINS,
+    },
+    SymbolKind {
+        name: "voltage",
+        prefix: 'V',
+        pins: &[(0, 16), (0, 96)],
+        nodes: TWO_PINS,
+    },
+    SymbolKind {
+        name: "current",
+        prefix: 'I',
+        pins: &[(0, 0), (0, 80)],
+        nodes: TWO_PINS,
+    },
+    SymbolKind {
+        name: "bv",
+        prefix: 'B',
+        pins: &[(16, 16), (16, 96)],
+        nodes: TWO_PINS,
+    },
+    SymbolKind {
+        name: "bi",
+        prefix: 'B',
+        pins: &[(16, 16), (16, 96)],
+        nodes: TWO_PINS,
+    },
+    SymbolKind {
+        name: "npn",
+        prefix: 'Q',
+        pins: &[(64, 0), (0, 48), (64, 96)],
+        nodes: THREE_PINS,
+    },
+    SymbolKind {
+        name: "pnp",
+        prefix: 'Q',
+        pins: &[(64, 0), (0, 48), (64, 96)],
+        nodes: THREE_PINS,
+    },
+    SymbolKind {
+        name: "njf",
+        prefix: 'J',
+        pins: &[(48, 0), (0, 64), (48, 96)],
+        nodes: THREE_PINS,
+    },
+    SymbolKind {
+        name: "pjf",
+        prefix: 'J',
+        pins: &[(48, 0), (0, 64), (48, 96)],
+        nodes: THREE_PINS,
+    },
+    // the bulk of a three-terminal MOSFET is its source
+    SymbolKind {
+        name: "nmos",
+        prefix: 'M',
+        pins: &[(48, 0), (0, 80), (48, 96)],
+        nodes: &[0, 1, 2, 2],
+    },
+    SymbolKind {
+        name: "pmos",
+        prefix: 'M',
+        pins: &[(48, 0), (0, 80), (48, 96)],
+        nodes: &[0, 1, 2, 2],
+    },
+    SymbolKind {
+        name: "nmos4",
+        prefix: 'M',
+        pins: &[(48, 0), (0, 80), (48, 96), (48, 48)],
+        nodes: &[0, 1, 2, 3],
+    },
+    SymbolKind {
+        name: "pmos4",
+        prefix: 'M',
+        pins: &[(48, 0), (0, 80), (48, 96), (48, 48)],
+        nodes: &[0, 1, 2, 3],
+    },
+];
+
+/// The attributes that follow the nodes of an instance line, in order.
+const VALUE_ATTRIBUTES: [&str; 4] = ["Value", "Value2", "SpiceLine", "SpiceLine2"];
+
+struct Symbol {
+    kind: &'static SymbolKind,
+    pins: Vec<Point>,
+    attributes: HashMap<String, String>,
+    line: usize,
+}
+
+/// Where the pin at `offset` of a symbol placed with `orientation` (`R0`..`R270`, mirrored
+/// `M0`..`M270`) ends up, relative to the symbol origin. LTspice's y axis points down, so `R90`
+/// turns the symbol clockwise.
+fn orient(orientation: &str, (x, y): Point) -> Option<Point> {
+    let (x, y) = match orientation.get(..1)? {
+        "R" => (x, y),
+        "M" => (-x, y),
+        _ => return None,
+    };
+    match &orientation[1..] {
+        "0" => Some((x, y)),
+        "90" => Some((-y, x)),
+        "180" => Some((-x, -y)),
+        "270" => Some((y, -x)),
+        _ => None,
+    }
+}
+
+/// Whether `p` lies on the wire from `a` to `b`.
+fn on_wire(p: Point, (a, b): (Point, Point)) -> bool {
+    let cross = (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0);
+    cross == 0
+        && (a.0.min(b.0)..=a.0.max(b.0)).contains(&p.0)
+        && (a.1.min(b.1)..=a.1.max(b.1)).contains(&p.1)
+}
+
+/// Connected points of the schematic, as a union-find over the points.
+#[derive(Default)]
+struct Nets {
+    points: HashMap<Point, usize>,
+    parent: Vec<usize>,
+}
+
+impl Nets {
+    fn id(&mut self, point: Point) -> usize {
+        *self.points.entry(point).or_insert_with(|| {
+            self.parent.push(self.parent.len());
+            self.parent.len() - 1
+        })
+    }
+
+    fn find(&mut self, mut id: usize) -> usize {
+        while self.parent[id] != id {
+            self.parent[id] = self.parent[self.parent[id]];
+            id = self.parent[id];
+        }
+        id
+    }
+
+    fn connect(&mut self, a: Point, b: Point) {
+        let (a, b) = (self.id(a), self.id(b));
+        let (a, b) = (self.find(a), self.find(b));
+        self.parent[a] = b;
+    }
+
+    fn net(&mut self, point: Point) -> usize {
+        let id = self.id(point);
+        self.find(id)
+    }
+}
+
+fn numbers<const N: usize>(
+    fields: &mut std::str::SplitWhitespace,
+    record: &str,
+    line: usize,
+) -> Result<[i64; N], AscError> {
+    let mut values = [0; N];
+    for value in &mut values {
+        *value = fields
+            .next()
+            .and_then(|field| field.parse().ok())
+            .ok_or_else(|| AscError::Malformed {
+                record: record.to_string(),
+                line,
+            })?;
+    }
+    Ok(values)
+}
+
+/// A symbol value the way spicy reads it: LTspice writes `µ` for micro and `SINE` for `SIN`.
+fn spice_value(value: &str) -> String {
+    let value = value.replace(['µ', 'μ'], "u");
+    match value.get(..4) {
+        Some(sine) if sine.eq_ignore_ascii_case("sine") => format!("SIN{}", &value[4..]),
+        _ => value,
+    }
+}
+
+/// The text of an `.asc` file: LTspice writes UTF-16LE, older versions Latin-1.
+pub fn decode_asc(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xFF, 0xFE]) || bytes.get(1) == Some(&0) {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        let text = String::from_utf16_lossy(&units);
+        return text.trim_start_matches('\u{feff}').to_string();
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// The netlist of the LTspice schematic `asc`, titled `title`. Nets take the name of their
+/// `FLAG` label, `0` for ground, or are numbered `N001`, `N002`, ... like LTspice does.
+pub fn asc_to_netlist(title: &str, asc: &str) -> Result<String, AscError> {
+    let mut wires = Vec::new();
+    let mut flags = Vec::new();
+    let mut symbols: Vec<Symbol> = Vec::new();
+    let mut directives = Vec::new();
+
+    for (index, text) in asc.lines().enumerate() {
+        let line = index + 1;
+        let mut fields = text.split_whitespace();
+        let Some(record) = fields.next() else {
+            continue;
+        };
+        let malformed = || AscError::Malformed {
+            record: record.to_string(),
+            line,
+        };
+        match record {
+            "WIRE" => {
+                let [x1, y1, x2, y2] = numbers(&mut fields, record, line)?;
+                wires.push(((x1, y1), (x2, y2)));
+            }
+            "FLAG" => {
+                let [x, y] = numbers(&mut fields, record, line)?;
+                let name = fields.next().ok_or_else(malformed)?;
+                flags.push(((x, y), name.to_string()));
+            }
+            "SYMBOL" => {
+                let name = fields.next().ok_or_else(malformed)?;
+                let [x, y] = numbers(&mut fields, record, line)?;
+                let orientation = fields.next().ok_or_else(malformed)?;
+                let kind = SYMBOLS
+                    .iter()
+                    .find(|kind| kind.name.eq_ignore_ascii_case(name))
+                    .ok_or_else(|| AscError::UnknownSymbol {
+                        symbol: name.to_string(),
+                        line,
+                    })?;
+                let pins = kind
+                    .pins
+                    .iter()
+                    .map(|&pin| orient(orientation, pin).map(|(dx, dy)| (x + dx, y + dy)))
+                    .collect::<Option<_>>()
+                    .ok_or_else(malformed)?;
+                symbols.push(Symbol {
+                    kind,
+                    pins,
+                    attributes: HashMap::new(),
+                    line,
+                });
+            }
+            "SYMATTR" => {
+                let symbol = symbols.last_mut().ok_or_else(malformed)?;
+                let rest = text.trim_start()[record.len()..].trim_start();
+                let (name, value) = rest.split_once(' ').unwrap_or((rest, ""));
+                symbol
+                    .attributes
+                    .insert(name.to_string(), value.trim().to_string());
+            }
+            "TEXT" => {
+                // TEXT x y alignment size !directive (or ;comment)
+                let body = text.splitn(6, ' ').nth(5).ok_or_else(malformed)?;
+                if let Some(directive) = body.strip_prefix('!') {
+                    directives.extend(directive.split("\\n").map(str::trim).filter(|d| {
+                        !d.is_empty()
+                            && !d.eq_ignore_ascii_case(".end")
+                            && !d.eq_ignore_ascii_case(".backanno")
+                    }));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut nets = Nets::default();
+    let points: Vec<Point> = wires
+        .iter()
+        .flat_map(|&(a, b)| [a, b])
+        .chain(flags.iter().map(|(point, _)| *point))
+        .chain(symbols.iter().flat_map(|symbol| symbol.pins.clone()))
+        .collect();
+    for &(a, b) in &wires {
+        nets.connect(a, b);
+        for &point in points.iter().filter(|&&point| on_wire(point, (a, b))) {
+            nets.connect(point, a);
+        }
+    }
+
+    let mut names: HashMap<usize, String> = HashMap::new();
+    for (point, name) in &flags {
+        let net = nets.net(*point);
+        if name == "0" || !names.contains_key(&net) {
+            names.insert(net, name.clone());
+        }
+    }
+
+    let mut netlist = format!("{title}\n");
+    let mut unnamed = 0;
+    for symbol in &symbols {
+        let inst_name = symbol
+            .attributes
+            .get("InstName")
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| AscError::MissingInstName {
+                symbol: symbol.kind.name.to_string(),
+                line: symbol.line,
+            })?;
+        let mut fields = Vec::new();
+        if !inst_name
+            .to_ascii_uppercase()
+            .starts_with(symbol.kind.prefix)
+        {
+            fields.push(format!("{}{inst_name}", symbol.kind.prefix));
+        } else {
+            fields.push(inst_name.clone());
+        }
+        for &pin in symbol.kind.nodes {
+            let net = nets.net(symbol.pins[pin]);
+            let name = names.entry(net).or_insert_with(|| {
+                unnamed += 1;
+                format!("N{unnamed:03}")
+            });
+            fields.push(name.clone());
+        }
+        fields.extend(
+            VALUE_ATTRIBUTES
+                .iter()
+                .filter_map(|attribute| symbol.attributes.get(*attribute))
+                .filter(|value| !value.is_empty())
+                .map(|value| spice_value(value)),
+        );
+        netlist.push_str(&fields.join(" "));
+        netlist.push('\n');
+    }
+    for directive in directives {
+        netlist.push_str(directive);
+        netlist.push('\n');
+    }
+    netlist.push_str(".end\n");
+    Ok(netlist)
+}
+
+/// Parse the LTspice schematic `asc`, read from `path`, into a deck. The directives of a
+/// schematic are written for LTspice and are read in the [`Compatibility::Ngspice`] dialect.
+pub fn parse_asc(path: impl AsRef<Path>, asc: &str) -> Result<Deck, SpicyError> {
+    let path = path.as_ref();
+    let title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let netlist = asc_to_netlist(&title, asc)?;
+    let mut options = ParseOptions::new_with_source(path, netlist);
+    options.compatibility = Compatibility::Ngspice;
+    parse(&mut options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netlist_types::Command;
+
+    // V1 drives the low-pass R1, C1; R1 is turned a quarter and touches C1 without a wire,
+    // and the ground flag sits in the middle of the bottom wire.
+    const RC: &str = "Version 4
+SHEET 1 880 680
+WIRE 0 16 32 16
+WIRE 112 80 112 96
+WIRE 0 96 112 96
+FLAG 112 16 out
+FLAG 48 96 0
+SYMBOL voltage 0 0 R0
+WINDOW 123 0 0 Left 0
+SYMATTR InstName V1
+SYMATTR Value SINE(0 1 1k)
+SYMBOL res 128 0 R90
+SYMATTR InstName R1
+SYMATTR Value 1k
+SYMBOL cap 96 16 R0
+SYMATTR InstName C1
+SYMATTR Value 1µ
+TEXT 0 136 Left 2 !.tran 10u 3m\\n.param x=1
+TEXT 0 160 Left 2 ;a low-pass filter
+";
+
+    #[test]
+    fn schematic_becomes_a_netlist() {
+        let netlist = asc_to_netlist("rc", RC).expect("netlist");
+        assert_eq!(
+            netlist,
+            "rc
+V1 N001 0 SIN(0 1 1k)
+R1 out N001 1k
+C1 out 0 1u
+.tran 10u 3m
+.param x=1
+.end
+"
+        );
+    }
+
+    #[test]
+    fn schematic_parses_into_a_deck() {
+        let deck = parse_asc("rc.asc", RC).expect("parse");
+        assert_eq!(deck.title, "rc");
+        assert_eq!(deck.devices.resistors.len(), 1);
+        assert_eq!(deck.devices.capacitors.len(), 1);
+        assert_eq!(deck.devices.voltage_sources.len(), 1);
+        assert!(matches!(deck.commands.as_slice(), [Command::Tran(_)]));
+    }
+
+    #[test]
+    fn unknown_symbols_are_rejected() {
+        let asc = "Version 4\nSYMBOL Opamps\\\\UniversalOpamp2 0 0 R0\n";
+        let err = asc_to_netlist("opamp", asc).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2: unknown symbol Opamps\\\\UniversalOpamp2"
+        );
+        let err = asc_to_netlist("wire", "WIRE 0 0 16\n").unwrap_err();
+        assert!(matches!(err, AscError::Malformed { line: 1, .. }), "{err}");
+    }
+
+    #[test]
+    fn utf16_files_are_decoded() {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(
+            "SYMATTR Value 1µ\n"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        );
+        assert_eq!(decode_asc(&bytes), "SYMATTR Value 1µ\n");
+        assert_eq!(decode_asc(b"Value 1\xb5"), "Value 1µ");
+    }
+}
@@ -0,0 +1,298 @@
+//! Netlist dialects: the ngspice-isms the strict parser rejects are rewritten into the syntax
+//! it accepts before a source is lexed.
+//!
+//! Every rewrite keeps each line at its byte length, so spans into the rewritten source still
+//! point at the line and, past the rewritten text, the column the user wrote.
+
+/// The netlist dialect a source is read in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compatibility {
+    /// Only the syntax spicy documents.
+    #[default]
+    Strict,
+    /// Also accept the ngspice dialect:
+    /// - inline comments starting at `;`, or at a `$` that follows whitespace
+    /// - any text on `*` comment lines, `.control` ... `.endc` blocks and lines after `.end`
+    /// - `'expr'` for `{expr}` and whitespace around the `=` of `name = value`
+    /// - unit suffixes in any case, `MEG` (mega) as opposed to `M` (milli) included
+    /// - instance and model parameter names, and the `DC`/`AC` of sources, in any case
+    Ngspice,
+}
+
+impl Compatibility {
+    /// Rewrite `source` into the strict syntax. The first line of a main source is its title
+    /// and is kept as is.
+    pub(crate) fn translate(self, source: String, title: bool) -> String {
+        match self {
+            Compatibility::Strict => source,
+            Compatibility::Ngspice => ngspice(&source, title),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Block {
+    Netlist,
+    Control,
+    AfterEnd,
+}
+
+fn ngspice(source: &str, title: bool) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut lines = source.split_inclusive('\n');
+    if title {
+        out.extend(lines.next());
+    }
+    let mut block = Block::Netlist;
+    for line in lines {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let directive = text
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let keep = match (block, directive.as_str()) {
+            (Block::Netlist, ".control") => {
+                block = Block::Control;
+                false
+            }
+            (Block::Netlist, ".end") => {
+                block = Block::AfterEnd;
+                true
+            }
+            (Block::Netlist, _) => true,
+            (Block::Control, ".endc") => {
+                block = Block::Netlist;
+                false
+            }
+            (Block::Control | Block::AfterEnd, _) => false,
+        };
+
+        if !keep || text.trim().is_empty() {
+            out.push_str(&comment_out(text));
+        } else if let Some(star) = text.trim_start().strip_prefix('*') {
+            out.push_str(&text[..text.len() - star.len()]);
+            out.push_str(&blank(star));
+        } else {
+            out.push_str(&translate_line(text));
+        }
+        out.push_str(&line[text.len()..]);
+    }
+    out
+}
+
+fn blank(text: &str) -> String {
+    " ".repeat(text.len())
+}
+
+/// A comment line as long as `text`: the strict parser rejects lines of only whitespace.
+fn comment_out(text: &str) -> String {
+    match text.len() {
+        0 => String::new(),
+        len => format!("*{}", " ".repeat(len - 1)),
+    }
+}
+
+/// Start of the inline comment of `text`, if any. A `${NAME}` path variable is not one.
+fn comment_start(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    bytes.iter().enumerate().position(|(i, &b)| {
+        b == b';'
+            || (b == b'$'
+                && (i == 0 || bytes[i - 1].is_ascii_whitespace())
+                && bytes.get(i + 1) != Some(&b'{'))
+    })
+}
+
+fn translate_line(text: &str) -> String {
+    let (body, comment) = text.split_at(comment_start(text).unwrap_or(text.len()));
+    let mut bytes = tight_equals(&quotes_to_braces(body.as_bytes()));
+    let first = bytes.iter().copied().find(|b| !b.is_ascii_whitespace());
+    let is_model = body
+        .split_whitespace()
+        .next()
+        .is_some_and(|word| word.eq_ignore_ascii_case(".model"));
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_alphanumeric() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && bytes[i].is_ascii_alphanumeric() {
+            i += 1;
+        }
+        let next = bytes.get(i).copied();
+        let word = &mut bytes[start..i];
+
+        if word[0].is_ascii_digit() {
+            normalize_suffix(word, next);
+        } else if next == Some(b'=') && start > 0 {
+            // `name=value`: instance parameters are lower case, except a B source's V and I
+            match first.map(|b| b.to_ascii_uppercase()) {
+                Some(b'B') => word.make_ascii_uppercase(),
+                Some(b'.') if is_model => word.make_ascii_lowercase(),
+                Some(b'.' | b'X') | None => {}
+                Some(_) => word.make_ascii_lowercase(),
+            }
+        } else if matches!(first, Some(b'V' | b'v' | b'I' | b'i'))
+            && (word.eq_ignore_ascii_case(b"dc") || word.eq_ignore_ascii_case(b"ac"))
+        {
+            word.make_ascii_uppercase();
+        }
+    }
+
+    bytes.resize(body.len(), b' ');
+    let mut line = String::from_utf8(bytes).expect("only ASCII bytes are rewritten");
+    line.push_str(&blank(comment));
+    line
+}
+
+/// `'expr'` is the ngspice spelling of `{expr}`.
+fn quotes_to_braces(body: &[u8]) -> Vec<u8> {
+    let mut open = false;
+    body.iter()
+        .map(|&b| match b {
+            b'\'' => {
+                open = !open;
+                if open { b'{' } else { b'}' }
+            }
+            b => b,
+        })
+        .collect()
+}
+
+/// Drop the whitespace around every `=`; the caller pads the line back to its length.
+fn tight_equals(body: &[u8]) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::with_capacity(body.len());
+    let mut i = 0;
+    while i < body.len() {
+        if body[i].is_ascii_whitespace() {
+            let run = body[i..]
+                .iter()
+                .take_while(|b| b.is_ascii_whitespace())
+                .count();
+            let next = body.get(i + run);
+            if out.last() != Some(&b'=') && next != Some(&b'=') {
+                out.extend_from_slice(&body[i..i + run]);
+            }
+            i += run;
+        } else {
+            out.push(body[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Spell the unit suffix of the number `word` the way [`ValueSuffix`] reads it: ngspice
+/// suffixes ignore case, so `MEG` is mega and `N` nano. `next` is the byte after the word, to
+/// tell the exponent of `1e-3` from a suffix.
+///
+/// [`ValueSuffix`]: crate::netlist_types::ValueSuffix
+fn normalize_suffix(word: &mut [u8], next: Option<u8>) {
+    let digits = word.iter().take_while(|b| b.is_ascii_digit()).count();
+    let suffix = &mut word[digits..];
+    let exponent = suffix
+        .first()
+        .is_some_and(|b| b.eq_ignore_ascii_case(&b'e'))
+        && (suffix[1..].first().is_some_and(u8::is_ascii_digit)
+            || (suffix.len() == 1 && matches!(next, Some(b'+' | b'-'))));
+    if exponent || suffix.is_empty() || !suffix.iter().all(u8::is_ascii_alphabetic) {
+        return;
+    }
+
+    if suffix.len() >= 3 && suffix[..3].eq_ignore_ascii_case(b"meg") {
+        suffix[..3].copy_from_slice(b"Meg");
+    } else {
+        match suffix[0].to_ascii_lowercase() {
+            b't' | b'g' => suffix[0].make_ascii_uppercase(),
+            b'n' | b'p' | b'f' | b'a' => suffix[0].make_ascii_lowercase(),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParseOptions, Value, error::SpicyError, netlist_types::Command, parse};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("R1 a b 2MEG", "R1 a b 2Meg")]
+    #[case("R1 a b 1.5meg ; load", "R1 a b 1.5Meg       ")]
+    #[case("C1 a 0 10N $ tank", "C1 a 0 10n       ")]
+    #[case("C1 a 0 1u IC = 1", "C1 a 0 1u ic=1  ")]
+    #[case("V1 in 0 dc 1 ac 1", "V1 in 0 DC 1 AC 1")]
+    #[case("R1 a b '2*r'", "R1 a b {2*r}")]
+    #[case(".model dm D(IS=1e-14 N=1)", ".model dm D(is=1e-14 n=1)")]
+    #[case("B1 a 0 v={v(b)*2}", "B1 a 0 V={v(b)*2}")]
+    #[case("X1 a b sub R=1", "X1 a b sub R=1")]
+    #[case(".param Big = 1e+3", ".param Big=1e+3  ")]
+    #[case("Q1 c b e 2N3904", "Q1 c b e 2N3904")]
+    #[case("V$1 a 0 1", "V$1 a 0 1")]
+    #[case(
+        ".lib ${PDK}/models.lib tt $ corner",
+        ".lib ${PDK}/models.lib tt         "
+    )]
+    #[case(".if (corner == 1)", ".if (corner==1)  ")]
+    #[case("* it's a comment", "*               ")]
+    fn rewrites_a_line(#[case] line: &str, #[case] expected: &str) {
+        let translated = Compatibility::Ngspice.translate(line.to_string(), false);
+        assert_eq!(translated, expected);
+        assert_eq!(translated.len(), line.len());
+    }
+
+    #[test]
+    fn control_blocks_and_lines_after_end_are_dropped() {
+        let source = "it's; a title\r\n.control\nrun\n.endc\nR1 a 0 1\n.END\nplot v(a)\n";
+        let translated = Compatibility::Ngspice.translate(source.to_string(), true);
+        assert_eq!(
+            translated,
+            "it's; a title\r\n*       \n*  \n*    \nR1 a 0 1\n.END\n*        \n"
+        );
+    }
+
+    #[test]
+    fn strict_keeps_the_source() {
+        let source = "R1 a b 2MEG ; load\n".to_string();
+        assert_eq!(
+            Compatibility::Strict.translate(source.clone(), false),
+            source
+        );
+    }
+
+    #[test]
+    fn ngspice_netlist_parses() {
+        let netlist = "ngspice dialect
+* it's a divider
+.param r = 2
+V1 in 0 dc 1 ac 1 ; source
+R1 in out {r*1MEG}
+R2 out 0 '2*r*1meg' $ bottom
+C1 out 0 1N IC = 0.5
+.tran 1n 10n
+.end
+plot v(out)
+";
+        let mut options = ParseOptions::new_with_source("compat.spicy", netlist.to_string());
+        assert!(matches!(
+            parse(&mut options),
+            Err(SpicyError::Lexer(_) | SpicyError::Parser(_))
+        ));
+
+        let mut options = ParseOptions::new_with_source("compat.spicy", netlist.to_string());
+        options.compatibility = Compatibility::Ngspice;
+        let deck = parse(&mut options).expect("parse");
+        let resistors = &deck.devices.resistors;
+        let value = |value: &Option<Value>| value.as_ref().map(Value::get_value);
+        assert_eq!(value(&resistors[0].resistance), Some(2e6));
+        assert_eq!(value(&resistors[1].resistance), Some(4e6));
+        let capacitor = &deck.devices.capacitors[0];
+        assert_eq!(value(&capacitor.capacitance), Some(1e-9));
+        assert_eq!(value(&capacitor.ic), Some(0.5));
+        assert!(matches!(deck.commands.as_slice(), [Command::Tran(_)]));
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+use crate::error::{ExpressionError, ParserError, Recovered, SpicyError};
+use crate::expr::{Expr, ExpressionParser, Params, PlaceholderMap, Scope};
+use crate::expression_phase::substitute_more_expressions;
+use crate::lexer::{Span, TokenKind};
+use crate::libs_phase::DeferredIncludes;
+use crate::netlist_types::CommandType;
+use crate::parser_utils::parse_dot_param;
+use crate::statement_phase::{Statement, Statements};
+use crate::{ParseOptions, Value};
+
+/// An `.if` whose `.endif` has not been reached yet.
+struct Conditional {
+    /// The statements around the `.if` are kept.
+    outer_active: bool,
+    /// One of the branches so far was taken, so the ones after it are not.
+    taken: bool,
+    /// The statements of the current branch are kept.
+    active: bool,
+    /// The `.else` was seen, so no other branch may follow.
+    in_else: bool,
+    span: Span,
+}
+
+/// Keep only the statements of the taken branches of `.if`/`.elseif`/`.else`/`.endif`.
+///
+/// A condition is evaluated when it is reached, against the top-level `.param`s before it in
+/// the kept statements and the `variables` of `options` that are numbers, which win over a
+/// `.param` of the same name. `overrides` replace `.param`s as they do for the deck.
+///
+/// The `deferred` `.include`s and `.lib`s of a taken branch are expanded where they are, and
+/// the statements they read resolved next; those of the other branches are never read.
+pub(crate) fn resolve_conditionals(
+    statements: &mut Statements,
+    options: &mut ParseOptions,
+    deferred: &mut DeferredIncludes,
+    placeholders: &mut PlaceholderMap,
+    overrides: &[(String, f64)],
+    mut recovered: Recovered,
+) -> Result<(), SpicyError> {
+    let mut params = Params::new();
+    let mut stack: Vec<Conditional> = Vec::new();
+    let mut kept = Vec::with_capacity(statements.statements.len());
+    let mut in_subckt = false;
+
+    let mut pending = VecDeque::from(std::mem::take(&mut statements.statements));
+    while let Some(statement) = pending.pop_front() {
+        let active = stack.last().is_none_or(|c| c.active);
+        if deferred.contains(&statement) {
+            if active {
+                let mut included = deferred.expand(statement, options)?;
+                substitute_more_expressions(
+                    &mut included,
+                    options,
+                    placeholders,
+                    recovered.as_deref_mut(),
+                )?;
+                for included in included.statements.into_iter().rev() {
+                    pending.push_front(included);
+                }
+            }
+            continue;
+        }
+        let input = options.source_map.get_content(statement.span.source_index);
+        let mut cursor = statement.as_cursor();
+        let command = cursor.consume_if_commands(
+            input,
+            &[
+                CommandType::If,
+                CommandType::ElseIf,
+                CommandType::Else,
+                CommandType::EndIf,
+            ],
+        );
+        let Some(command) = command else {
+            if !active {
+                continue;
+            }
+            // a .param of a subcircuit is not visible to the conditions
+            if cursor.consume_if_command(input, CommandType::Subcircuit) {
+                in_subckt = true;
+            } else if cursor.consume_if_command(input, CommandType::Ends) {
+                in_subckt = false;
+            } else if !in_subckt && cursor.consume_if_command(input, CommandType::Param) {
+                parse_dot_param(&mut cursor, input, placeholders, &mut params)?;
+            }
+            kept.push(statement);
+            continue;
+        };
+
+        let unmatched = |directive: &str| ParserError::UnmatchedConditional {
+            directive: directive.to_string(),
+            span: statement.span,
+        };
+        match command {
+            CommandType::If => {
+                let taken = active
+                    && evaluate_condition(
+                        &statement,
+                        input,
+                        options,
+                        placeholders,
+                        &params,
+                        overrides,
+                    )?;
+                stack.push(Conditional {
+                    outer_active: active,
+                    taken,
+                    active: taken,
+                    in_else: false,
+                    span: statement.span,
+                });
+            }
+            CommandType::ElseIf => {
+                let Some(conditional) = stack.last_mut().filter(|c| !c.in_else) else {
+                    return Err(unmatched("elseif").into());
+                };
+                conditional.active = conditional.outer_active
+                    && !conditional.taken
+                    && evaluate_condition(
+                        &statement,
+                        input,
+                        options,
+                        placeholders,
+                        &params,
+                        overrides,
+                    )?;
+                conditional.taken |= conditional.active;
+            }
+            CommandType::Else => {
+                let Some(conditional) = stack.last_mut().filter(|c| !c.in_else) else {
+                    return Err(unmatched("else").into());
+                };
+                conditional.active = conditional.outer_active && !conditional.taken;
+                conditional.in_else = true;
+            }
+            CommandType::EndIf => {
+                if stack.pop().is_none() {
+                    return Err(unmatched("endif").into());
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if let Some(conditional) = stack.first() {
+        return Err(ParserError::UnterminatedIf {
+            span: conditional.span,
+        }
+        .into());
+    }
+    statements.statements = kept;
+    Ok(())
+}
+
+/// Whether the condition of an `.if` or `.elseif` holds: a `{…}` expression, or an expression
+/// written as is, usually in parentheses.
+fn evaluate_condition(
+    statement: &Statement,
+    input: &str,
+    options: &ParseOptions,
+    placeholders: &PlaceholderMap,
+    params: &Params,
+    overrides: &[(String, f64)],
+) -> Result<bool, SpicyError> {
+    let mut cursor = statement.as_cursor();
+    cursor.consume_if_commands(input, &[CommandType::If, CommandType::ElseIf]);
+    let tokens = cursor.rest();
+    let start = tokens.iter().position(|t| t.kind != TokenKind::WhiteSpace);
+    let end = tokens.iter().rposition(|t| t.kind != TokenKind::WhiteSpace);
+    let (Some(start), Some(end)) = (start, end) else {
+        return Err(ExpressionError::MissingToken {
+            message: "condition",
+            span: statement.span,
+        }
+        .into());
+    };
+    let tokens = &tokens[start..=end];
+    let condition = match tokens {
+        [token] if token.kind == TokenKind::Placeholder => placeholders
+            .get(token.id.expect("must have a placeholder id"))
+            .clone(),
+        _ => ExpressionParser::new(input, tokens, statement.span).parse()?,
+    };
+
+    let mut params = params.clone();
+    for (name, value) in overrides {
+        if let Some(expr) = params.get_param(name) {
+            let value = Expr::value(Value::new(*value, None, None), expr.span);
+            params.set_param(name.clone(), value);
+        }
+    }
+    for (name, value) in &options.variables {
+        if let Ok(value) = value.trim().parse::<f64>() {
+            let value = Expr::value(Value::new(value, None, None), condition.span);
+            params.set_param(name.clone(), value);
+        }
+    }
+    let scope = Scope::new(None, Rc::new(params), Default::default(), HashMap::new());
+    Ok(condition.evaluate(&scope)?.get_value() != 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::SpicyError;
+    use crate::parse;
+    use rstest::rstest;
+
+    fn resistances(netlist: &str, variables: &[(&str, &str)]) -> Result<Vec<f64>, SpicyError> {
+        resistances_at("conditional.spicy", netlist, variables)
+    }
+
+    fn resistances_at(
+        path: impl AsRef<std::path::Path>,
+        netlist: &str,
+        variables: &[(&str, &str)],
+    ) -> Result<Vec<f64>, SpicyError> {
+        let mut options = ParseOptions::new_with_source(path, netlist.to_string());
+        options.variables = variables
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let deck = parse(&mut options)?;
+        Ok(deck
+            .devices
+            .resistors
+            .iter()
+            .map(|r| r.resistance.as_ref().unwrap().get_value())
+            .collect())
+    }
+
+    const CORNERS: &str = "corners
+.param corner=1
+.if (corner == 0)
+R1 a 0 1k
+.elseif (corner == 1)
+R1 a 0 2k
+.if {corner > 5}
+R2 a 0 1
+.endif
+.else
+R1 a 0 3k
+.endif
+V1 a 0 1
+.op
+.end
+";
+
+    #[rstest]
+    #[case(&[], vec![2e3])]
+    #[case(&[("corner", "0")], vec![1e3])]
+    #[case(&[("corner", "7")], vec![3e3])]
+    fn only_the_taken_branch_is_kept(
+        #[case] variables: &[(&str, &str)],
+        #[case] expected: Vec<f64>,
+    ) {
+        assert_eq!(resistances(CORNERS, variables).unwrap(), expected);
+    }
+
+    #[test]
+    fn a_param_in_a_skipped_branch_is_not_set() {
+        let netlist = "params
+.param a=1
+.if (a > 1)
+.param b=1
+.else
+.param b=2
+.endif
+.if (b == 2)
+R1 x 0 {b}
+.endif
+V1 x 0 1
+.end
+";
+        assert_eq!(resistances(netlist, &[]).unwrap(), vec![2.0]);
+    }
+
+    const LIBRARY_CORNERS: &str = "library corners
+.if (1)
+.include corner_params.spicy
+.endif
+.if (corner == 1)
+.lib lib_corners.spicy ff
+.elseif (corner == 2)
+.lib lib_corners.spicy ss
+.else
+.include no_such_corner.spicy
+.endif
+V1 a 0 1
+.end
+";
+
+    #[rstest]
+    #[case(&[], Ok(vec![5.0, 900.0]))]
+    #[case(&[("corner", "2")], Err("library section 'ss' not found"))]
+    #[case(&[("corner", "0")], Err("no_such_corner.spicy"))]
+    fn only_the_includes_of_the_taken_branch_are_read(
+        #[case] variables: &[(&str, &str)],
+        #[case] expected: Result<Vec<f64>, &str>,
+    ) {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/include_inputs/library_corners.spicy");
+        let resistances = resistances_at(path, LIBRARY_CORNERS, variables);
+        match (resistances, expected) {
+            (Ok(resistances), Ok(expected)) => assert_eq!(resistances, expected),
+            (Err(error), Err(message)) => {
+                assert!(error.to_string().contains(message), "{error}")
+            }
+            (resistances, _) => panic!("{variables:?}: {resistances:?}"),
+        }
+    }
+
+    #[rstest]
+    #[case(
+        "title\n.if (1)\nR1 a 0 1\n.end\n",
+        "'.if' without a matching '.endif'"
+    )]
+    #[case("title\nR1 a 0 1\n.endif\n.end\n", "'.endif' without a matching '.if'")]
+    #[case(
+        "title\n.if (1)\n.else\n.elseif (1)\n.endif\n",
+        "'.elseif' without a matching '.if'"
+    )]
+    fn unbalanced_conditionals_are_errors(#[case] netlist: &str, #[case] message: &str) {
+        let error = resistances(netlist, &[]).unwrap_err();
+        assert_eq!(error.to_string(), message);
+    }
+}
@@ -0,0 +1,85 @@
+use serde::Serialize;
+
+use crate::{
+    ExprFunction, Span,
+    netlist_types::{CurrentBranchIndex, NodeIndex},
+};
+
+/// What the expression of a B source sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BehavioralKind {
+    /// `V=expr`: the voltage across the source, which has a branch current.
+    Voltage,
+    /// `I=expr`: the current flowing from the positive node through the source.
+    Current,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BehavioralOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    /// 1 if left < right, else 0
+    Less,
+    /// 1 if left > right, else 0
+    Greater,
+}
+
+/// A B source expression, with its params evaluated and its node voltages and branch
+/// currents resolved.
+#[derive(Debug, Clone, Serialize)]
+pub enum BehavioralExpr {
+    Constant(f64),
+    /// `V(node)`, always 0 for ground.
+    Voltage(NodeIndex),
+    /// `I(device)`: the branch current of a voltage source, inductor or `V=` B source.
+    Current {
+        name: String,
+        branch: CurrentBranchIndex,
+    },
+    /// `time`: the transient time, 0 outside of a transient analysis.
+    Time,
+    Negate(Box<BehavioralExpr>),
+    Binary {
+        op: BehavioralOp,
+        left: Box<BehavioralExpr>,
+        right: Box<BehavioralExpr>,
+    },
+    Call {
+        function: ExprFunction,
+        args: Vec<BehavioralExpr>,
+    },
+}
+
+impl BehavioralExpr {
+    /// Visit every `I(device)` of the expression.
+    pub(crate) fn currents_mut(&mut self, f: &mut impl FnMut(&str, &mut CurrentBranchIndex)) {
+        match self {
+            BehavioralExpr::Constant(_) | BehavioralExpr::Voltage(_) | BehavioralExpr::Time => {}
+            BehavioralExpr::Current { name, branch } => f(name, branch),
+            BehavioralExpr::Negate(operand) => operand.currents_mut(f),
+            BehavioralExpr::Binary { left, right, .. } => {
+                left.currents_mut(f);
+                right.currents_mut(f);
+            }
+            BehavioralExpr::Call { args, .. } => {
+                for arg in args {
+                    arg.currents_mut(f);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BehavioralSourceSpec {
+    pub name: String,
+    pub span: Span,
+    pub positive: NodeIndex,
+    pub negative: NodeIndex,
+    pub kind: BehavioralKind,
+    /// Branch current of a `V=` source, `CurrentBranchIndex(0)` for an `I=` source.
+    pub current_branch: CurrentBranchIndex,
+    pub expr: BehavioralExpr,
+}
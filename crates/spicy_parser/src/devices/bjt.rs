@@ -1,13 +1,21 @@
+use serde::Serialize;
+
 use crate::netlist_models::BjtModel;
 use crate::{Span, Value, netlist_types::NodeIndex};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct BjtSpec {
     pub name: String,
     pub span: Span,
     pub collector: NodeIndex,
     pub base: NodeIndex,
     pub emitter: NodeIndex,
+    /// The collector behind the collector resistance, `collector` without one.
+    pub collector_prime: NodeIndex,
+    /// The base behind the base resistance, `base` without one.
+    pub base_prime: NodeIndex,
+    /// The emitter behind the emitter resistance, `emitter` without one.
+    pub emitter_prime: NodeIndex,
     pub model: BjtModel,
     pub area: Option<Value>,
     pub m: Option<Value>,
@@ -31,6 +39,9 @@ impl BjtSpec {
             collector,
             base,
             emitter,
+            collector_prime: collector,
+            base_prime: base,
+            emitter_prime: emitter,
             model,
             area: None,
             m: None,
@@ -40,6 +51,17 @@ impl BjtSpec {
         }
     }
 
+    pub fn set_internal_nodes(
+        &mut self,
+        collector: NodeIndex,
+        base: NodeIndex,
+        emitter: NodeIndex,
+    ) {
+        self.collector_prime = collector;
+        self.base_prime = base;
+        self.emitter_prime = emitter;
+    }
+
     pub fn set_area(&mut self, value: Value) {
         self.area = Some(value);
     }
@@ -1,7 +1,9 @@
+use serde::Serialize;
+
 use crate::netlist_models::CapacitorModel;
 use crate::{Span, expr::Value, netlist_types::NodeIndex};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CapacitorSpec {
     pub name: String,
     pub span: Span,
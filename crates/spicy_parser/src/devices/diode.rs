@@ -1,7 +1,9 @@
+use serde::Serialize;
+
 use crate::netlist_models::DiodeModel;
 use crate::{Span, Value, netlist_types::NodeIndex};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DiodeSpec {
     pub name: String,
     pub span: Span,
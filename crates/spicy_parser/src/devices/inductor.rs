@@ -1,3 +1,5 @@
+use serde::Serialize;
+
 use crate::netlist_models::InductorModel;
 use crate::{
     Span,
@@ -5,7 +7,7 @@ use crate::{
     netlist_types::{CurrentBranchIndex, NodeIndex},
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct InductorSpec {
     pub name: String,
     pub span: Span,
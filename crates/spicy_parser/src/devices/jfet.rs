@@ -0,0 +1,66 @@
+use serde::Serialize;
+
+use crate::netlist_models::JfetModel;
+use crate::{Span, Value, netlist_types::NodeIndex};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JfetSpec {
+    pub name: String,
+    pub span: Span,
+    pub drain: NodeIndex,
+    pub gate: NodeIndex,
+    pub source: NodeIndex,
+    /// The drain behind the drain resistance, `drain` without one.
+    pub drain_prime: NodeIndex,
+    /// The source behind the source resistance, `source` without one.
+    pub source_prime: NodeIndex,
+    pub model: JfetModel,
+    pub area: Option<Value>,
+    pub off: Option<bool>,
+    pub ic_vds: Option<Value>,
+    pub ic_vgs: Option<Value>,
+}
+
+impl JfetSpec {
+    pub fn new(
+        name: String,
+        span: Span,
+        drain: NodeIndex,
+        gate: NodeIndex,
+        source: NodeIndex,
+        model: JfetModel,
+    ) -> Self {
+        Self {
+            name,
+            span,
+            drain,
+            gate,
+            source,
+            drain_prime: drain,
+            source_prime: source,
+            model,
+            area: None,
+            off: None,
+            ic_vds: None,
+            ic_vgs: None,
+        }
+    }
+
+    pub fn set_internal_nodes(&mut self, drain: NodeIndex, source: NodeIndex) {
+        self.drain_prime = drain;
+        self.source_prime = source;
+    }
+
+    pub fn set_area(&mut self, value: Value) {
+        self.area = Some(value);
+    }
+
+    pub fn set_off(&mut self, value: bool) {
+        self.off = Some(value);
+    }
+
+    pub fn set_ic(&mut self, vds: Value, vgs: Option<Value>) {
+        self.ic_vds = Some(vds);
+        self.ic_vgs = vgs;
+    }
+}
@@ -0,0 +1,103 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::{Span, netlist_types::NodeIndex};
+
+/// A two-terminal element whose current is interpolated from a measured I-V table.
+#[derive(Debug, Clone, Serialize)]
+pub struct LookupTableSpec {
+    pub name: String,
+    pub span: Span,
+    pub positive: NodeIndex,
+    pub negative: NodeIndex,
+    /// The CSV file the table was read from.
+    pub path: PathBuf,
+    /// `(voltage, current)` points, by strictly increasing voltage. The current flows from the
+    /// positive node through the element.
+    pub points: Vec<(f64, f64)>,
+}
+
+/// Read the `(voltage, current)` points of a CSV table: one `voltage,current` pair per line,
+/// after an optional header line. Blank lines and lines starting with `#` are skipped.
+pub(crate) fn read_iv_table(path: &Path) -> Result<Vec<(f64, f64)>, String> {
+    let content = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let mut points = Vec::new();
+    let rows = content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+    for (position, (line_number, line)) in rows.enumerate() {
+        let mut fields = line.split(',').map(str::trim);
+        let point = match (fields.next(), fields.next(), fields.next()) {
+            (Some(v), Some(i), None) => v.parse::<f64>().ok().zip(i.parse::<f64>().ok()),
+            _ => None,
+        };
+        match point {
+            Some(point) => points.push(point),
+            // the first row may name the columns
+            None if position == 0 => {}
+            None => return Err(format!("line {line_number}: expected `voltage,current`")),
+        }
+    }
+
+    if points.len() < 2 {
+        return Err("needs at least two points".to_string());
+    }
+    if points.windows(2).any(|w| w[1].0 <= w[0].0) {
+        return Err("voltages must be strictly increasing".to_string());
+    }
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{
+        ParseOptions,
+        error::{ParserError, SpicyError},
+        parse,
+    };
+
+    fn table_opts(netlist: &str) -> ParseOptions {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/table_inputs");
+        ParseOptions::new_with_source(dir.join("main.spicy"), netlist.to_string())
+    }
+
+    #[test]
+    fn table_is_read_relative_to_the_netlist() {
+        let mut options = table_opts("table\nV1 a 0 1\nA1 a 0 diode_iv.csv\n.op\n.end\n");
+        let deck = parse(&mut options).expect("parse");
+        let table = &deck.devices.lookup_tables[0];
+        assert_eq!(table.name, "A1");
+        assert!(table.path.ends_with("tests/table_inputs/diode_iv.csv"));
+        assert_eq!(
+            table.points,
+            vec![
+                (-1.0, -1e-12),
+                (0.0, 0.0),
+                (0.5, 1e-6),
+                (0.6, 1e-4),
+                (0.7, 5e-3)
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_tables_are_rejected() {
+        let mut options = table_opts("table\nV1 a 0 1\nA1 a 0 unsorted.csv\n.op\n.end\n");
+        match parse(&mut options) {
+            Err(SpicyError::Parser(ParserError::InvalidTable { reason, .. })) => {
+                assert_eq!(reason, "voltages must be strictly increasing")
+            }
+            other => panic!("expected InvalidTable, got {other:?}"),
+        }
+
+        let mut options = table_opts("table\nV1 a 0 1\nA1 a 0 missing.csv\n.op\n.end\n");
+        assert!(matches!(
+            parse(&mut options),
+            Err(SpicyError::Parser(ParserError::InvalidTable { .. }))
+        ));
+    }
+}
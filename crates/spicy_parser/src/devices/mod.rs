@@ -1,24 +1,51 @@
+use serde::Serialize;
+
 pub use crate::devices::{
-    bjt::BjtSpec, capacitor::CapacitorSpec, diode::DiodeSpec, inductor::InductorSpec,
-    resistor::ResistorSpec, sources::IndependentSourceSpec,
+    behavioral::{BehavioralExpr, BehavioralKind, BehavioralOp, BehavioralSourceSpec},
+    bjt::BjtSpec,
+    capacitor::CapacitorSpec,
+    diode::DiodeSpec,
+    inductor::InductorSpec,
+    jfet::JfetSpec,
+    lookup_table::LookupTableSpec,
+    mosfet::MosfetSpec,
+    mutual_inductance::MutualInductanceSpec,
+    resistor::ResistorSpec,
+    sources::IndependentSourceSpec,
+    switch::{SwitchControl, SwitchSpec},
+    transmission_line::TransmissionLineSpec,
 };
 
+mod behavioral;
 mod bjt;
 mod capacitor;
 mod diode;
 mod inductor;
+mod jfet;
+pub(crate) mod lookup_table;
+mod mosfet;
+mod mutual_inductance;
 mod resistor;
 mod sources;
+mod switch;
+mod transmission_line;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Devices {
     pub resistors: Vec<ResistorSpec>,
     pub capacitors: Vec<CapacitorSpec>,
     pub inductors: Vec<InductorSpec>,
+    pub mutual_inductances: Vec<MutualInductanceSpec>,
     pub diodes: Vec<DiodeSpec>,
     pub voltage_sources: Vec<IndependentSourceSpec>,
     pub current_sources: Vec<IndependentSourceSpec>,
     pub bjts: Vec<BjtSpec>,
+    pub mosfets: Vec<MosfetSpec>,
+    pub jfets: Vec<JfetSpec>,
+    pub behavioral_sources: Vec<BehavioralSourceSpec>,
+    pub transmission_lines: Vec<TransmissionLineSpec>,
+    pub switches: Vec<SwitchSpec>,
+    pub lookup_tables: Vec<LookupTableSpec>,
 }
 
 impl Devices {
@@ -27,10 +54,17 @@ impl Devices {
             resistors: Vec::new(),
             capacitors: Vec::new(),
             inductors: Vec::new(),
+            mutual_inductances: Vec::new(),
             diodes: Vec::new(),
             voltage_sources: Vec::new(),
             current_sources: Vec::new(),
             bjts: Vec::new(),
+            mosfets: Vec::new(),
+            jfets: Vec::new(),
+            behavioral_sources: Vec::new(),
+            transmission_lines: Vec::new(),
+            switches: Vec::new(),
+            lookup_tables: Vec::new(),
         }
     }
 }
@@ -0,0 +1,73 @@
+use serde::Serialize;
+
+use crate::netlist_models::MosfetModel;
+use crate::{Span, Value, netlist_types::NodeIndex};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MosfetSpec {
+    pub name: String,
+    pub span: Span,
+    pub drain: NodeIndex,
+    pub gate: NodeIndex,
+    pub source: NodeIndex,
+    pub bulk: NodeIndex,
+    pub model: MosfetModel,
+    pub m: Option<Value>,
+    pub l: Option<Value>,
+    pub w: Option<Value>,
+    pub off: Option<bool>,
+    pub ic_vds: Option<Value>,
+    pub ic_vgs: Option<Value>,
+    pub ic_vbs: Option<Value>,
+}
+
+impl MosfetSpec {
+    pub fn new(
+        name: String,
+        span: Span,
+        drain: NodeIndex,
+        gate: NodeIndex,
+        source: NodeIndex,
+        bulk: NodeIndex,
+        model: MosfetModel,
+    ) -> Self {
+        Self {
+            name,
+            span,
+            drain,
+            gate,
+            source,
+            bulk,
+            model,
+            m: None,
+            l: None,
+            w: None,
+            off: None,
+            ic_vds: None,
+            ic_vgs: None,
+            ic_vbs: None,
+        }
+    }
+
+    pub fn set_m(&mut self, value: Value) {
+        self.m = Some(value);
+    }
+
+    pub fn set_l(&mut self, value: Value) {
+        self.l = Some(value);
+    }
+
+    pub fn set_w(&mut self, value: Value) {
+        self.w = Some(value);
+    }
+
+    pub fn set_off(&mut self, value: bool) {
+        self.off = Some(value);
+    }
+
+    pub fn set_ic(&mut self, vds: Value, vgs: Option<Value>, vbs: Option<Value>) {
+        self.ic_vds = Some(vds);
+        self.ic_vgs = vgs;
+        self.ic_vbs = vbs;
+    }
+}
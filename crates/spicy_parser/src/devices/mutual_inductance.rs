@@ -0,0 +1,15 @@
+use serde::Serialize;
+
+use crate::{Span, expr::Value};
+
+/// A `K` statement coupling two inductors.
+#[derive(Debug, Clone, Serialize)]
+pub struct MutualInductanceSpec {
+    pub name: String,
+    pub span: Span,
+    /// Names of the coupled inductors, resolved once the whole deck is parsed.
+    pub inductor1: String,
+    pub inductor2: String,
+    /// Coupling coefficient k, the mutual inductance being k * sqrt(L1 * L2).
+    pub coupling: Value,
+}
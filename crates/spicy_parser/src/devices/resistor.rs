@@ -1,6 +1,8 @@
+use serde::Serialize;
+
 use crate::{Span, Value, netlist_models::ResistorModel, netlist_types::NodeIndex};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ResistorSpec {
     pub name: String,
     pub span: Span,
@@ -25,6 +27,10 @@ pub struct ResistorSpec {
     pub tc2: Option<Value>,
     /// Enable/disable including this resistor in noise analysis (if supported).
     pub noisy: Option<bool>,
+    /// Thermal resistance (K/W) to the ambient of a self-heating resistor.
+    pub rth: Option<Value>,
+    /// Thermal capacitance (J/K) of a self-heating resistor.
+    pub cth: Option<Value>,
 }
 
 impl ResistorSpec {
@@ -44,6 +50,8 @@ impl ResistorSpec {
             tc1: None,
             tc2: None,
             noisy: None,
+            rth: None,
+            cth: None,
         }
     }
 
@@ -81,4 +89,11 @@ impl ResistorSpec {
     pub fn set_noisy(&mut self, value: bool) {
         self.noisy = Some(value);
     }
+
+    pub fn set_rth(&mut self, value: Value) {
+        self.rth = Some(value);
+    }
+    pub fn set_cth(&mut self, value: Value) {
+        self.cth = Some(value);
+    }
 }
@@ -1,13 +1,16 @@
+use serde::Serialize;
+
 use crate::{
+    Span,
     netlist_types::Phasor,
     netlist_types::{CurrentBranchIndex, NodeIndex},
     netlist_waveform::WaveForm,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct IndependentSourceSpec {
     pub name: String,
-    // TODO: where span?
+    pub span: Span,
     pub positive: NodeIndex,
     pub negative: NodeIndex,
     pub current_branch: CurrentBranchIndex,
@@ -18,12 +21,14 @@ pub struct IndependentSourceSpec {
 impl IndependentSourceSpec {
     pub fn new(
         name: String,
+        span: Span,
         positive: NodeIndex,
         negative: NodeIndex,
         current_branch: CurrentBranchIndex,
     ) -> Self {
         Self {
             name,
+            span,
             positive,
             negative,
             current_branch,
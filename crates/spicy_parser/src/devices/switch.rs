@@ -0,0 +1,35 @@
+use serde::Serialize;
+
+use crate::netlist_models::{CurrentSwitchModel, SwitchModel};
+use crate::{
+    Span,
+    netlist_types::{CurrentBranchIndex, NodeIndex},
+};
+
+/// What opens and closes a switch.
+#[derive(Debug, Clone, Serialize)]
+pub enum SwitchControl {
+    /// `S`: the voltage between two nodes.
+    Voltage {
+        positive: NodeIndex,
+        negative: NodeIndex,
+        model: SwitchModel,
+    },
+    /// `W`: the branch current of a voltage source, resolved once the whole deck is parsed.
+    Current {
+        name: String,
+        branch: CurrentBranchIndex,
+        model: CurrentSwitchModel,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SwitchSpec {
+    pub name: String,
+    pub span: Span,
+    pub positive: NodeIndex,
+    pub negative: NodeIndex,
+    pub control: SwitchControl,
+    /// The state the switch starts in, before the first operating point; `OFF` by default.
+    pub on: bool,
+}
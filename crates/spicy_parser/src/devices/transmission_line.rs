@@ -0,0 +1,25 @@
+use serde::Serialize;
+
+use crate::{
+    Span,
+    expr::Value,
+    netlist_types::{CurrentBranchIndex, NodeIndex},
+};
+
+/// An ideal lossless transmission line between two ports.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransmissionLineSpec {
+    pub name: String,
+    pub span: Span,
+    pub positive1: NodeIndex,
+    pub negative1: NodeIndex,
+    pub positive2: NodeIndex,
+    pub negative2: NodeIndex,
+    /// Current flowing into the line at the positive node of each port.
+    pub branch1: CurrentBranchIndex,
+    pub branch2: CurrentBranchIndex,
+    /// Characteristic impedance (ohms).
+    pub z0: Value,
+    /// Delay from one port to the other (seconds).
+    pub td: Value,
+}
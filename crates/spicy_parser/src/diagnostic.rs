@@ -0,0 +1,124 @@
+//! Errors and lints as plain data, for editors and CI tools to read instead of the messages.
+//!
+//! A [`Diagnostic`] serializes to a flat object with a stable `code` (see
+//! [`SpicyError::code`]) and the location of its span: 1-based line and column, both counted
+//! in characters, and the length of the span.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::{SourceMap, Span, error::SpicyError, lint::LintWarning};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    /// The file of the span; none for a problem with no place in the netlist.
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub length: Option<usize>,
+}
+
+impl Diagnostic {
+    pub fn new(
+        code: &'static str,
+        severity: Severity,
+        message: String,
+        span: Option<Span>,
+        source_map: &SourceMap,
+    ) -> Self {
+        let mut diagnostic = Self {
+            code,
+            severity,
+            message,
+            file: None,
+            line: None,
+            column: None,
+            length: None,
+        };
+        if let Some(span) = span {
+            let src = source_map.get_content(span.source_index);
+            let start = span.start.min(src.len());
+            // spans end on their last byte
+            let end = (span.end + 1).clamp(start, src.len());
+            let before = &src[..start];
+            diagnostic.file = Some(source_map.get_path(span.source_index).to_path_buf());
+            diagnostic.line = Some(before.matches('\n').count() + 1);
+            diagnostic.column = Some(before.rsplit('\n').next().unwrap_or("").chars().count() + 1);
+            diagnostic.length = Some(src.get(start..end).map_or(0, |s| s.chars().count()));
+        }
+        diagnostic
+    }
+
+    pub fn from_error(error: &SpicyError, source_map: &SourceMap) -> Self {
+        Self::new(
+            error.code(),
+            Severity::Error,
+            error.to_string(),
+            error.error_span(),
+            source_map,
+        )
+    }
+
+    pub fn from_lint(lint: &LintWarning, source_map: &SourceMap) -> Self {
+        Self::new(
+            lint.code(),
+            Severity::Warning,
+            lint.to_string(),
+            lint.span(),
+            source_map,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParseOptions, lint::lint_deck, parse};
+
+    #[test]
+    fn parse_error_points_at_its_token() {
+        let mut options =
+            ParseOptions::new_with_source("bad.spicy", "bad\nR1 a 0 1k\nQ1 a b c nomodel\n".into());
+        let error = parse(&mut options).expect_err("missing model");
+        let diagnostic = Diagnostic::from_error(&error, &options.source_map);
+        assert_eq!(diagnostic.code, "E0218");
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.file, Some(PathBuf::from("bad.spicy")));
+        assert_eq!(diagnostic.line, Some(3));
+        assert_eq!(diagnostic.column, Some(10));
+        assert_eq!(diagnostic.length, Some(7));
+    }
+
+    #[test]
+    fn lint_serializes_flat() {
+        let mut options =
+            ParseOptions::new_with_source("lint.spicy", "lint\nV1 in 0 1\nR1 in out 1k\n".into());
+        let deck = parse(&mut options).expect("parse");
+        let lints = lint_deck(&deck);
+        let diagnostic = Diagnostic::from_lint(&lints[0], &options.source_map);
+        let json = serde_json::to_value(&diagnostic).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "code": "W0001",
+                "severity": "warning",
+                "message": "node out has a single connection (to R1)",
+                "file": "lint.spicy",
+                "line": 3,
+                "column": 1,
+                "length": 12,
+            })
+        );
+    }
+}
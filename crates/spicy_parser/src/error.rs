@@ -3,6 +3,7 @@ use std::path::PathBuf;
 use thiserror::Error;
 
 use crate::Span;
+use crate::netlist_types::{Unit, ValueSuffix};
 
 #[derive(Debug, Error)]
 pub enum SpicyError {
@@ -16,6 +17,8 @@ pub enum SpicyError {
     Subcircuit(#[from] SubcircuitError),
     #[error(transparent)]
     Include(#[from] IncludeError),
+    #[error(transparent)]
+    Asc(#[from] AscError),
 }
 
 impl SpicyError {
@@ -42,22 +45,32 @@ impl SpicyError {
                 | ParserError::UnmatchedBrace { span }
                 | ParserError::EmptyExpressionInsideBraces { span }
                 | ParserError::MissingModel { span, .. }
+                | ParserError::InvalidTable { span, .. }
                 | ParserError::InvalidModel { span, .. }
+                | ParserError::UnknownOutputVector { span, .. }
+                | ParserError::UnknownNode { span, .. }
+                | ParserError::UnknownBranch { span, .. }
+                | ParserError::UnknownInductor { span, .. }
+                | ParserError::UnmatchedConditional { span, .. }
+                | ParserError::UnterminatedIf { span }
                 | ParserError::TooManyParameters { span, .. } => Some(*span),
-                ParserError::MissingToken { .. }
-                | ParserError::InvalidDeviceType { .. }
+                ParserError::InvalidDeviceType { .. }
                 | ParserError::EmptyStatement
-                | ParserError::MissingTitle => None,
-                ParserError::InvalidNumericLiteral { span, .. } => *span,
+                | ParserError::MissingTitle
+                | ParserError::UnknownParam { .. } => None,
+                ParserError::MissingToken { span, .. }
+                | ParserError::InvalidNumericLiteral { span, .. } => *span,
             },
             SpicyError::Expression(ee) => match ee {
                 ExpressionError::UnexpectedToken { span, .. }
                 | ExpressionError::BadPrefixOperator { span, .. }
-                | ExpressionError::UnevaluatablePlaceholder { span, .. }
                 | ExpressionError::UnknownIdentifier { span, .. }
                 | ExpressionError::UnsupportedUnaryOperator { span, .. }
-                | ExpressionError::UnsupportedBinaryOperator { span, .. } => Some(*span),
-                ExpressionError::MissingToken { .. } => None,
+                | ExpressionError::UnsupportedBinaryOperator { span, .. }
+                | ExpressionError::CyclicParams { span, .. }
+                | ExpressionError::UnknownFunction { span, .. }
+                | ExpressionError::WrongArgumentCount { span, .. }
+                | ExpressionError::MissingToken { span, .. } => Some(*span),
             },
             SpicyError::Subcircuit(se) => match se {
                 SubcircuitError::MissingSubcircuitName { span } => *span,
@@ -72,12 +85,43 @@ impl SpicyError {
                 | IncludeError::IOError { span, .. }
                 | IncludeError::MaxDepthExceeded { span, .. }
                 | IncludeError::CycleDetected { span, .. }
-                | IncludeError::LibSectionNotFound { span, .. } => Some(*span),
+                | IncludeError::LibSectionNotFound { span, .. }
+                | IncludeError::UndefinedVariable { span, .. }
+                | IncludeError::UnterminatedVariable { span } => Some(*span),
             },
+            // the error points into the schematic, not into the netlist made from it
+            SpicyError::Asc(_) => None,
+        }
+    }
+
+    /// Stable code of the kind of error, for tools to match on instead of the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SpicyError::Lexer(e) => e.code(),
+            SpicyError::Parser(e) => e.code(),
+            SpicyError::Expression(e) => e.code(),
+            SpicyError::Subcircuit(e) => e.code(),
+            SpicyError::Include(e) => e.code(),
+            SpicyError::Asc(e) => e.code(),
         }
     }
 }
 
+/// Where a phase records the errors it carries on past when the parse recovers from errors
+/// (see [`crate::parse_recovering`]). Without one, the first error fails the parse.
+pub(crate) type Recovered<'e> = Option<&'e mut Vec<SpicyError>>;
+
+/// Record `error` and carry on when recovering, or fail with it.
+pub(crate) fn recover(recovered: &mut Recovered, error: SpicyError) -> Result<(), SpicyError> {
+    match recovered {
+        Some(errors) => {
+            errors.push(error);
+            Ok(())
+        }
+        None => Err(error),
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum LexerError {
     #[error("unexpected character '{ch}'")]
@@ -87,6 +131,15 @@ pub enum LexerError {
     InvalidIdentifierStart { span: Span },
 }
 
+impl LexerError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexerError::UnexpectedCharacter { .. } => "E0101",
+            LexerError::InvalidIdentifierStart { .. } => "E0102",
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum ParserError {
     #[error("empty statement")]
@@ -144,12 +197,24 @@ pub enum ParserError {
     #[error("invalid device type: {s}")]
     InvalidDeviceType { s: String },
 
-    #[error("invalid model: {model}")]
-    InvalidModel { model: String, span: Span },
+    #[error("model {model} is a {found} model, expected a {expected} model")]
+    InvalidModel {
+        model: String,
+        expected: &'static str,
+        found: &'static str,
+        span: Span,
+    },
 
     #[error("missing model: {model}")]
     MissingModel { model: String, span: Span },
 
+    #[error("invalid table {}: {reason}", path.display())]
+    InvalidTable {
+        path: PathBuf,
+        reason: String,
+        span: Span,
+    },
+
     #[error("missing scope")]
     MissingScope { span: Span },
 
@@ -164,6 +229,65 @@ pub enum ParserError {
 
     #[error("too many parameters provided (parameter {index} exceeds expected count)")]
     TooManyParameters { index: usize, span: Span },
+
+    #[error("no top-level .param named '{name}'")]
+    UnknownParam { name: String },
+
+    #[error("output vector '{name}' is not a node or a device with a branch current")]
+    UnknownOutputVector { name: String, span: Span },
+
+    #[error("unknown node '{name}'")]
+    UnknownNode { name: String, span: Span },
+
+    #[error("'{name}' is not a device with a branch current")]
+    UnknownBranch { name: String, span: Span },
+
+    #[error("'{name}' is not an inductor")]
+    UnknownInductor { name: String, span: Span },
+
+    #[error("'.{directive}' without a matching '.if'")]
+    UnmatchedConditional { directive: String, span: Span },
+
+    #[error("'.if' without a matching '.endif'")]
+    UnterminatedIf { span: Span },
+}
+
+impl ParserError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParserError::EmptyStatement => "E0201",
+            ParserError::ContinuationWithoutPrevious { .. } => "E0202",
+            ParserError::UnexpectedToken { .. } => "E0203",
+            ParserError::MissingToken { .. } => "E0204",
+            ParserError::InvalidStartNumeric { .. } => "E0205",
+            ParserError::ExpectedDigitsAfterDot { .. } => "E0206",
+            ParserError::InvalidExponentDigits { .. } => "E0207",
+            ParserError::InvalidNumericLiteral { .. } => "E0208",
+            ParserError::ExpectedBoolZeroOrOne { .. } => "E0209",
+            ParserError::ExpectedIdent { .. } => "E0210",
+            ParserError::MissingPlaceholderId { .. } => "E0211",
+            ParserError::InvalidParam { .. } => "E0212",
+            ParserError::InvalidOperation { .. } => "E0213",
+            ParserError::InvalidCommandType { .. } => "E0214",
+            ParserError::UnexpectedCommandType { .. } => "E0215",
+            ParserError::InvalidDeviceType { .. } => "E0216",
+            ParserError::InvalidModel { .. } => "E0217",
+            ParserError::MissingModel { .. } => "E0218",
+            ParserError::InvalidTable { .. } => "E0219",
+            ParserError::MissingScope { .. } => "E0220",
+            ParserError::MissingTitle => "E0221",
+            ParserError::UnmatchedBrace { .. } => "E0222",
+            ParserError::EmptyExpressionInsideBraces { .. } => "E0223",
+            ParserError::TooManyParameters { .. } => "E0224",
+            ParserError::UnknownParam { .. } => "E0225",
+            ParserError::UnknownOutputVector { .. } => "E0226",
+            ParserError::UnknownNode { .. } => "E0227",
+            ParserError::UnknownBranch { .. } => "E0228",
+            ParserError::UnknownInductor { .. } => "E0229",
+            ParserError::UnmatchedConditional { .. } => "E0230",
+            ParserError::UnterminatedIf { .. } => "E0231",
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -175,7 +299,7 @@ pub enum ExpressionError {
     },
 
     #[error("missing token: {message}")]
-    MissingToken { message: &'static str },
+    MissingToken { message: &'static str, span: Span },
 
     #[error("bad prefix operator {op:?}")]
     BadPrefixOperator {
@@ -183,12 +307,6 @@ pub enum ExpressionError {
         span: Span,
     },
 
-    #[error("placeholder not evaluatable: {id:?}")]
-    UnevaluatablePlaceholder {
-        id: crate::expr::PlaceholderId,
-        span: Span,
-    },
-
     #[error("unknown identifier '{name}'")]
     UnknownIdentifier { name: String, span: Span },
 
@@ -203,6 +321,36 @@ pub enum ExpressionError {
         op: crate::lexer::TokenKind,
         span: Span,
     },
+
+    #[error("params depend on each other: {cycle}")]
+    CyclicParams { cycle: String, span: Span },
+
+    #[error("unknown function '{name}'")]
+    UnknownFunction { name: String, span: Span },
+
+    #[error("function '{name}' takes {expected} arguments, found {found}")]
+    WrongArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+}
+
+impl ExpressionError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ExpressionError::UnexpectedToken { .. } => "E0301",
+            ExpressionError::MissingToken { .. } => "E0302",
+            ExpressionError::BadPrefixOperator { .. } => "E0303",
+            ExpressionError::UnknownIdentifier { .. } => "E0304",
+            ExpressionError::UnsupportedUnaryOperator { .. } => "E0305",
+            ExpressionError::UnsupportedBinaryOperator { .. } => "E0306",
+            ExpressionError::CyclicParams { .. } => "E0307",
+            ExpressionError::UnknownFunction { .. } => "E0308",
+            ExpressionError::WrongArgumentCount { .. } => "E0309",
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -230,6 +378,178 @@ pub enum SubcircuitError {
     ModelAlreadyExists { name: String, span: Span },
 }
 
+impl SubcircuitError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            SubcircuitError::MissingSubcircuitName { .. } => "E0401",
+            SubcircuitError::NotFound { .. } => "E0402",
+            SubcircuitError::ArityMismatch { .. } => "E0403",
+            SubcircuitError::NoNodes { .. } => "E0404",
+            SubcircuitError::InvalidDeviceModelType { .. } => "E0405",
+            SubcircuitError::ModelAlreadyExists { .. } => "E0406",
+        }
+    }
+}
+
+/// A circuit structure that makes the MNA system singular (see [`crate::topology`]).
+#[derive(Debug, Clone, Error)]
+pub enum TopologyError {
+    #[error("voltage sources form a loop: {}", devices.join(", "))]
+    VoltageSourceLoop { devices: Vec<String>, span: Span },
+
+    #[error("inductors form a loop (with voltage sources) at DC: {}", devices.join(", "))]
+    InductorLoop { devices: Vec<String>, span: Span },
+
+    #[error(
+        "current sources {} form a cutset: node(s) {} have no other DC path to ground",
+        devices.join(", "),
+        nodes.join(", ")
+    )]
+    CurrentSourceCutset {
+        devices: Vec<String>,
+        nodes: Vec<String>,
+        span: Span,
+    },
+
+    #[error(
+        "node(s) {} are only connected to the rest of the circuit through capacitors {}",
+        nodes.join(", "),
+        devices.join(", ")
+    )]
+    CapacitorOnlyNodes {
+        devices: Vec<String>,
+        nodes: Vec<String>,
+        span: Span,
+    },
+
+    #[error("node(s) {} have no DC path to ground", nodes.join(", "))]
+    FloatingNodes {
+        nodes: Vec<String>,
+        span: Option<Span>,
+    },
+}
+
+impl TopologyError {
+    pub fn error_span(&self) -> Option<Span> {
+        match self {
+            TopologyError::VoltageSourceLoop { span, .. }
+            | TopologyError::InductorLoop { span, .. }
+            | TopologyError::CurrentSourceCutset { span, .. }
+            | TopologyError::CapacitorOnlyNodes { span, .. } => Some(*span),
+            TopologyError::FloatingNodes { span, .. } => *span,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            TopologyError::VoltageSourceLoop { .. } => "E0701",
+            TopologyError::InductorLoop { .. } => "E0702",
+            TopologyError::CurrentSourceCutset { .. } => "E0703",
+            TopologyError::CapacitorOnlyNodes { .. } => "E0704",
+            TopologyError::FloatingNodes { .. } => "E0705",
+        }
+    }
+}
+
+/// A device or model parameter outside its physical range, or in the wrong unit (see
+/// [`crate::parameters`]).
+#[derive(Debug, Clone, Error)]
+pub enum ParameterError {
+    /// A value the simulator cannot work with, which fails the analyses.
+    #[error("{owner}: {param} = {value} must be {expected}")]
+    Invalid {
+        owner: String,
+        param: &'static str,
+        value: f64,
+        expected: &'static str,
+        span: Option<Span>,
+    },
+
+    /// A value that simulates, but is far from anything a real device has.
+    #[error("{owner}: {param} = {value} is outside the usual range {expected}")]
+    Unusual {
+        owner: String,
+        param: &'static str,
+        value: f64,
+        expected: &'static str,
+        span: Option<Span>,
+    },
+
+    /// A value written in a unit the parameter is not in, such as henries for a capacitance.
+    #[error("{owner}: {param} is in {expected}, not {found}")]
+    WrongUnit {
+        owner: String,
+        param: &'static str,
+        found: Unit,
+        expected: Unit,
+        span: Option<Span>,
+    },
+
+    /// A unit letter written on its own that ngspice reads as a suffix: `10F` is 10 farads
+    /// here, but 10 femto there.
+    #[error(
+        "{owner}: {param} is read as {value} {}, but ngspice reads a lone {unit} as {}",
+        unit.name(),
+        format!("{suffix:?}").to_lowercase()
+    )]
+    AmbiguousUnit {
+        owner: String,
+        param: &'static str,
+        value: f64,
+        unit: Unit,
+        suffix: ValueSuffix,
+        span: Option<Span>,
+    },
+}
+
+impl ParameterError {
+    pub fn error_span(&self) -> Option<Span> {
+        match self {
+            ParameterError::Invalid { span, .. }
+            | ParameterError::Unusual { span, .. }
+            | ParameterError::WrongUnit { span, .. }
+            | ParameterError::AmbiguousUnit { span, .. } => *span,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParameterError::Invalid { .. } => "E0901",
+            ParameterError::Unusual { .. } => "W0002",
+            ParameterError::WrongUnit { .. } => "W0003",
+            ParameterError::AmbiguousUnit { .. } => "W0004",
+        }
+    }
+
+    /// Whether the analyses fail on it, rather than it being a warning.
+    pub fn is_error(&self) -> bool {
+        matches!(self, ParameterError::Invalid { .. })
+    }
+}
+
+/// An LTspice schematic that can't be turned into a netlist (see [`crate::asc`]).
+#[derive(Debug, Error)]
+pub enum AscError {
+    #[error("line {line}: malformed {record} record")]
+    Malformed { record: String, line: usize },
+
+    #[error("line {line}: unknown symbol {symbol}")]
+    UnknownSymbol { symbol: String, line: usize },
+
+    #[error("line {line}: symbol {symbol} has no InstName")]
+    MissingInstName { symbol: String, line: usize },
+}
+
+impl AscError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AscError::Malformed { .. } => "E0601",
+            AscError::UnknownSymbol { .. } => "E0602",
+            AscError::MissingInstName { .. } => "E0603",
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum IncludeError {
     #[error("expected path")]
@@ -261,4 +581,25 @@ pub enum IncludeError {
         lib: String,
         path: PathBuf,
     },
+
+    #[error("undefined variable '{name}' in include path")]
+    UndefinedVariable { span: Span, name: String },
+
+    #[error("unterminated '${{' in include path")]
+    UnterminatedVariable { span: Span },
+}
+
+impl IncludeError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            IncludeError::ExpectedPath { .. } => "E0501",
+            IncludeError::FileNotFound { .. } => "E0502",
+            IncludeError::IOError { .. } => "E0503",
+            IncludeError::MaxDepthExceeded { .. } => "E0504",
+            IncludeError::CycleDetected { .. } => "E0505",
+            IncludeError::LibSectionNotFound { .. } => "E0506",
+            IncludeError::UndefinedVariable { .. } => "E0507",
+            IncludeError::UnterminatedVariable { .. } => "E0508",
+        }
+    }
 }
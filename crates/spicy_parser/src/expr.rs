@@ -2,13 +2,16 @@ use crate::error::{ExpressionError, SpicyError};
 use crate::{
     lexer::{Span, Token, TokenKind, token_text},
     netlist_types::NodeName,
-    netlist_types::ValueSuffix,
-    parser_utils::parse_value,
+    netlist_types::{Unit, ValueSuffix},
+    node_mapping::hierarchical_name,
+    parser_utils::{consume_hierarchy, parse_value},
     statement_phase::StmtCursor,
 };
 use serde::Serialize;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::f64::consts::PI;
+use std::rc::Rc;
 
 #[cfg(test)]
 use crate::test_utils::serialize_sorted_map;
@@ -18,6 +21,9 @@ pub struct Value {
     pub value: f64,
     pub exponent: Option<f64>,
     pub suffix: Option<ValueSuffix>,
+    /// The unit written after the suffix, which does not scale the value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<Unit>,
 }
 
 impl Value {
@@ -26,9 +32,15 @@ impl Value {
             value,
             exponent,
             suffix,
+            unit: None,
         }
     }
 
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = Some(unit);
+        self
+    }
+
     pub fn zero() -> Self {
         Self::new(0.0, None, None)
     }
@@ -44,6 +56,11 @@ impl Value {
         value
     }
 
+    /// 1 for true and 0 for false, as comparisons evaluate for `if()`.
+    pub fn from_bool(value: bool) -> Self {
+        Self::new(if value { 1.0 } else { 0.0 }, None, None)
+    }
+
     pub fn angle_radians(&self, default_degrees: bool) -> f64 {
         let value = self.get_value();
         match self.suffix {
@@ -95,7 +112,6 @@ impl Div for Value {
 #[derive(Debug, Clone, Serialize)]
 pub enum ExprType {
     Value(Value),
-    Placeholder(PlaceholderId),
     Ident(String),
     Unary {
         op: TokenKind,
@@ -105,8 +121,11 @@ pub enum ExprType {
         op: TokenKind,
         left: Box<Expr>,
         right: Box<Expr>,
-    }, // + - * /
-       // Add Call { fun, args } if you want sin(), etc.
+    }, // + - * / < >
+    Call {
+        function: String,
+        args: Vec<Expr>,
+    }, // sin(x), max(a, b), if(c, a, b)
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -130,16 +149,9 @@ impl Expr {
         }
     }
 
-    pub fn placeholder(id: PlaceholderId, span: Span) -> Expr {
-        Expr {
-            span,
-            r#type: ExprType::Placeholder(id),
-        }
-    }
-
     fn unary(op: Token, operand: Expr) -> Expr {
         Expr {
-            span: op.span,
+            span: Span::new(op.span.start, operand.span.end, op.span.source_index),
             r#type: ExprType::Unary {
                 op: op.kind,
                 operand: Box::new(operand),
@@ -158,44 +170,30 @@ impl Expr {
             },
         }
     }
-    pub fn expand(self) -> Expr {
+
+    fn call(function: String, args: Vec<Expr>, span: Span) -> Expr {
         Expr {
-            span: self.span.expand(),
-            r#type: self.r#type.clone(),
+            span,
+            r#type: ExprType::Call { function, args },
         }
     }
 
-    pub fn evaluate(self, scope: &Scope) -> Result<Value, SpicyError> {
-        match self.r#type {
-            ExprType::Value(value) => Ok(value),
-            // TODO: support layered expressions with no loops
-            ExprType::Placeholder(id) => Err(ExpressionError::UnevaluatablePlaceholder {
-                id,
-                span: self.span,
-            }
-            .into()),
-            ExprType::Ident(name) => {
-                let Some(expr) = scope.param_map.get_param(&name).cloned() else {
-                    return Err(ExpressionError::UnknownIdentifier {
-                        name,
-                        span: self.span,
-                    }
-                    .into());
-                };
-                expr.evaluate(scope)
-            }
-            ExprType::Unary { op, operand } => match op {
+    pub fn evaluate(&self, scope: &Scope) -> Result<Value, SpicyError> {
+        match &self.r#type {
+            ExprType::Value(value) => Ok(value.clone()),
+            ExprType::Ident(name) => scope.param_value(name, self.span),
+            ExprType::Unary { op, operand } => match *op {
                 TokenKind::Minus => {
                     let value = operand.evaluate(scope)?;
                     Ok(Value::new(-value.get_value(), None, None))
                 }
                 _ => Err(ExpressionError::UnsupportedUnaryOperator {
-                    op,
+                    op: *op,
                     span: self.span,
                 }
                 .into()),
             },
-            ExprType::Binary { op, left, right } => match op {
+            ExprType::Binary { op, left, right } => match *op {
                 TokenKind::Plus => {
                     let left_value = left.evaluate(scope)?;
                     let right_value = right.evaluate(scope)?;
@@ -216,12 +214,193 @@ impl Expr {
                     let right_value = right.evaluate(scope)?;
                     Ok(left_value / right_value)
                 }
+                TokenKind::LessThan => {
+                    let left_value = left.evaluate(scope)?;
+                    let right_value = right.evaluate(scope)?;
+                    Ok(Value::from_bool(
+                        left_value.get_value() < right_value.get_value(),
+                    ))
+                }
+                TokenKind::GreaterThan => {
+                    let left_value = left.evaluate(scope)?;
+                    let right_value = right.evaluate(scope)?;
+                    Ok(Value::from_bool(
+                        left_value.get_value() > right_value.get_value(),
+                    ))
+                }
+                // `==`
+                TokenKind::Equal => {
+                    let left_value = left.evaluate(scope)?;
+                    let right_value = right.evaluate(scope)?;
+                    Ok(Value::from_bool(
+                        left_value.get_value() == right_value.get_value(),
+                    ))
+                }
                 _ => Err(ExpressionError::UnsupportedBinaryOperator {
-                    op,
+                    op: *op,
                     span: self.span,
                 }
                 .into()),
             },
+            ExprType::Call { function, args } => self.evaluate_call(function, args, scope),
+        }
+    }
+
+    fn evaluate_call(
+        &self,
+        function: &str,
+        args: &[Expr],
+        scope: &Scope,
+    ) -> Result<Value, SpicyError> {
+        let function = ExprFunction::resolve(function, args.len(), self.span)?;
+
+        // only the taken branch of if() is evaluated, so the other may be undefined
+        if function == ExprFunction::If {
+            let condition = args[0].evaluate(scope)?;
+            let branch = if condition.get_value() != 0.0 {
+                &args[1]
+            } else {
+                &args[2]
+            };
+            return branch.evaluate(scope);
+        }
+
+        let values = args
+            .iter()
+            .map(|arg| arg.evaluate(scope).map(|value| value.get_value()))
+            .collect::<Result<Vec<f64>, SpicyError>>()?;
+        Ok(Value::new(function.apply(&values), None, None))
+    }
+
+    /// Add the identifiers this expression refers to, in the order they are written.
+    fn collect_identifiers<'e>(&'e self, out: &mut Vec<&'e str>) {
+        match &self.r#type {
+            ExprType::Value(_) => {}
+            ExprType::Ident(name) => out.push(name),
+            ExprType::Unary { operand, .. } => operand.collect_identifiers(out),
+            ExprType::Binary { left, right, .. } => {
+                left.collect_identifiers(out);
+                right.collect_identifiers(out);
+            }
+            ExprType::Call { args, .. } => {
+                for arg in args {
+                    arg.collect_identifiers(out);
+                }
+            }
+        }
+    }
+}
+
+/// A built-in function of the expressions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ExprFunction {
+    Sin,
+    Cos,
+    Exp,
+    /// natural logarithm
+    Log,
+    Log10,
+    Sqrt,
+    Abs,
+    Min,
+    Max,
+    Pow,
+    /// if(condition, then, else), taking `then` for a non-zero condition
+    If,
+}
+
+impl ExprFunction {
+    /// Look up a function by its case-insensitive name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let function = match name.to_lowercase().as_str() {
+            "sin" => ExprFunction::Sin,
+            "cos" => ExprFunction::Cos,
+            "exp" => ExprFunction::Exp,
+            "log" => ExprFunction::Log,
+            "log10" => ExprFunction::Log10,
+            "sqrt" => ExprFunction::Sqrt,
+            "abs" => ExprFunction::Abs,
+            "min" => ExprFunction::Min,
+            "max" => ExprFunction::Max,
+            "pow" => ExprFunction::Pow,
+            "if" => ExprFunction::If,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    /// The name the function is called by.
+    pub fn name(self) -> &'static str {
+        match self {
+            ExprFunction::Sin => "sin",
+            ExprFunction::Cos => "cos",
+            ExprFunction::Exp => "exp",
+            ExprFunction::Log => "log",
+            ExprFunction::Log10 => "log10",
+            ExprFunction::Sqrt => "sqrt",
+            ExprFunction::Abs => "abs",
+            ExprFunction::Min => "min",
+            ExprFunction::Max => "max",
+            ExprFunction::Pow => "pow",
+            ExprFunction::If => "if",
+        }
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            ExprFunction::Sin
+            | ExprFunction::Cos
+            | ExprFunction::Exp
+            | ExprFunction::Log
+            | ExprFunction::Log10
+            | ExprFunction::Sqrt
+            | ExprFunction::Abs => 1,
+            ExprFunction::Min | ExprFunction::Max | ExprFunction::Pow => 2,
+            ExprFunction::If => 3,
+        }
+    }
+
+    /// The function `name` called with `args` arguments, or an error if there is none.
+    pub(crate) fn resolve(name: &str, args: usize, span: Span) -> Result<Self, SpicyError> {
+        let Some(function) = Self::from_name(name) else {
+            return Err(ExpressionError::UnknownFunction {
+                name: name.to_string(),
+                span,
+            }
+            .into());
+        };
+        if args != function.arity() {
+            return Err(ExpressionError::WrongArgumentCount {
+                name: name.to_string(),
+                expected: function.arity(),
+                found: args,
+                span,
+            }
+            .into());
+        }
+        Ok(function)
+    }
+
+    /// Apply the function to `args`, which has `arity()` values.
+    pub fn apply(self, args: &[f64]) -> f64 {
+        match self {
+            ExprFunction::Sin => args[0].sin(),
+            ExprFunction::Cos => args[0].cos(),
+            ExprFunction::Exp => args[0].exp(),
+            ExprFunction::Log => args[0].ln(),
+            ExprFunction::Log10 => args[0].log10(),
+            ExprFunction::Sqrt => args[0].sqrt(),
+            ExprFunction::Abs => args[0].abs(),
+            ExprFunction::Min => args[0].min(args[1]),
+            ExprFunction::Max => args[0].max(args[1]),
+            ExprFunction::Pow => args[0].powf(args[1]),
+            ExprFunction::If => {
+                if args[0] != 0.0 {
+                    args[1]
+                } else {
+                    args[2]
+                }
+            }
         }
     }
 }
@@ -233,13 +412,17 @@ pub struct PlaceholderId(u64);
 pub struct PlaceholderMap {
     pub(crate) next: u64,
     pub(crate) map: Vec<Expr>,
+    /// The `{...}` each placeholder replaced, braces included. The spans of the expression
+    /// and its sub-expressions point into the same source.
+    pub(crate) spans: Vec<Span>,
 }
 
 impl PlaceholderMap {
-    pub fn fresh(&mut self, expr: Expr) -> PlaceholderId {
+    pub fn fresh(&mut self, expr: Expr, span: Span) -> PlaceholderId {
         let id = PlaceholderId(self.next);
         self.next += 1;
         self.map.push(expr);
+        self.spans.push(span);
         id
     }
 
@@ -247,6 +430,15 @@ impl PlaceholderMap {
         // techinically you can unwrap here
         self.map.get(id.0 as usize).expect("id should be in map")
     }
+
+    /// Where the expression of `id` was written, braces included.
+    pub fn span(&self, id: PlaceholderId) -> Span {
+        self.spans[id.0 as usize]
+    }
+
+    pub fn evaluate(&self, id: PlaceholderId, scope: &Scope) -> Result<Value, SpicyError> {
+        self.get(id).evaluate(scope)
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize)]
@@ -267,6 +459,87 @@ impl Params {
     pub fn merge(&mut self, other: Params) {
         self.0.extend(other.0);
     }
+
+    pub(crate) fn into_iter(self) -> impl Iterator<Item = (String, Expr)> {
+        self.0.into_iter()
+    }
+
+    pub(crate) fn contains(&self, k: &str) -> bool {
+        self.0.contains_key(k)
+    }
+
+    /// The params of the other params `name` refers to.
+    fn dependencies(&self, name: &str) -> Vec<&str> {
+        let mut identifiers = Vec::new();
+        if let Some(expr) = self.0.get(name) {
+            expr.collect_identifiers(&mut identifiers);
+        }
+        identifiers.retain(|ident| self.0.contains_key(*ident));
+        identifiers
+    }
+
+    /// Every param after the params it refers to, or an error naming a cycle of them.
+    pub(crate) fn evaluation_order(&self) -> Result<Vec<&str>, SpicyError> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            Visiting,
+            Done,
+        }
+
+        fn visit<'p>(
+            params: &'p Params,
+            name: &'p str,
+            marks: &mut HashMap<&'p str, Mark>,
+            path: &mut Vec<&'p str>,
+            order: &mut Vec<&'p str>,
+        ) -> Result<(), SpicyError> {
+            match marks.get(name) {
+                Some(Mark::Done) => return Ok(()),
+                Some(Mark::Visiting) => {
+                    let start = path.iter().position(|n| *n == name).unwrap_or(0);
+                    let mut cycle: Vec<&str> = path[start..].to_vec();
+                    cycle.push(name);
+                    return Err(ExpressionError::CyclicParams {
+                        cycle: cycle.join(" -> "),
+                        span: params.0[name].span,
+                    }
+                    .into());
+                }
+                None => {}
+            }
+            marks.insert(name, Mark::Visiting);
+            path.push(name);
+            for dependency in params.dependencies(name) {
+                visit(params, dependency, marks, path, order)?;
+            }
+            path.pop();
+            marks.insert(name, Mark::Done);
+            order.push(name);
+            Ok(())
+        }
+
+        // sorted so the order, and the cycle reported, don't depend on the hash map
+        let mut names: Vec<&str> = self.0.keys().map(String::as_str).collect();
+        names.sort_unstable();
+
+        let mut marks = HashMap::new();
+        let mut order = Vec::with_capacity(names.len());
+        for name in names {
+            visit(self, name, &mut marks, &mut Vec::new(), &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// Key identifying the expressions of these params, ignoring where they were written.
+    pub(crate) fn fingerprint(&self) -> String {
+        let mut entries: Vec<_> = self
+            .0
+            .iter()
+            .map(|(name, expr)| format!("{name}={:?}", expr.r#type))
+            .collect();
+        entries.sort();
+        entries.join(";")
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
@@ -275,16 +548,26 @@ pub struct ScopeId(usize);
 #[derive(Debug, Clone, Serialize)]
 pub struct Scope {
     pub parent: Option<ScopeId>,
+    /// The path of the subcircuit instance, e.g. `X1`; `None` at the top level.
     pub instance_name: Option<String>,
-    pub param_map: Params, // store Expr; evaluation is later
+    /// shared by every subcircuit instance with the same parameters
+    pub param_map: Rc<Params>, // store Expr; evaluation is later
     #[cfg_attr(test, serde(serialize_with = "crate::test_utils::serialize_node_map"))]
     pub node_mapping: HashMap<NodeName, NodeName>,
+    /// evaluated params, filled lazily: a param is usually referenced by many statements
+    #[serde(skip)]
+    values: ParamValues,
 }
 
+/// Evaluated params, shared along with the `Params` they were evaluated from.
+pub(crate) type ParamValues = Rc<RefCell<HashMap<String, Value>>>;
+
 impl Scope {
-    pub fn new(
+    /// A scope evaluating `param_map` into `values`, both possibly shared with other scopes.
+    pub(crate) fn new(
         instance_name: Option<String>,
-        param_map: Params,
+        param_map: Rc<Params>,
+        values: ParamValues,
         node_mapping: HashMap<NodeName, NodeName>,
     ) -> Self {
         Self {
@@ -292,18 +575,60 @@ impl Scope {
             instance_name,
             param_map,
             node_mapping,
+            values,
         }
     }
 
+    /// Evaluate the param `name` in this scope, reusing the value if it was already evaluated.
+    pub(crate) fn param_value(&self, name: &str, span: Span) -> Result<Value, SpicyError> {
+        if let Some(value) = self.values.borrow().get(name) {
+            return Ok(value.clone());
+        }
+        let Some(expr) = self.param_map.get_param(name) else {
+            return Err(ExpressionError::UnknownIdentifier {
+                name: name.to_string(),
+                span,
+            }
+            .into());
+        };
+        let value = expr.evaluate(self)?;
+        self.values
+            .borrow_mut()
+            .insert(name.to_string(), value.clone());
+        Ok(value)
+    }
+
+    /// Evaluate every param in dependency order, so that later lookups only read values.
+    pub(crate) fn evaluate_params(&self) -> Result<(), SpicyError> {
+        for name in self.param_map.evaluation_order()? {
+            let span = self.param_map.0[name].span;
+            self.param_value(name, span)?;
+        }
+        Ok(())
+    }
+
+    /// The values evaluated so far, e.g. to make the global params visible in a subcircuit.
+    pub(crate) fn evaluated_values(&self) -> HashMap<String, Value> {
+        self.values.borrow().clone()
+    }
+
     pub(crate) fn set_parent(&mut self, parent: ScopeId) {
         self.parent = Some(parent);
     }
 
     pub(crate) fn get_device_name(&self, name: &str) -> String {
-        if let Some(instance_name) = &self.instance_name {
-            return format!("{}_{}", instance_name, name);
+        match &self.instance_name {
+            Some(instance_name) => hierarchical_name(&[instance_name], name),
+            None => name.to_string(),
+        }
+    }
+
+    /// The deck-wide name of a node local to this scope, named like its devices.
+    pub(crate) fn get_node_name(&self, node: NodeName) -> NodeName {
+        match &self.instance_name {
+            Some(instance_name) => NodeName(hierarchical_name(&[instance_name], &node.0)),
+            None => node,
         }
-        name.to_string()
     }
 }
 
@@ -324,6 +649,7 @@ impl ScopeArena {
             instance_name: None,
             param_map: Default::default(),
             node_mapping: Default::default(),
+            values: Default::default(),
         });
         (self.get_mut(id), id)
     }
@@ -373,6 +699,7 @@ fn prefix_binding_power(op: &Token) -> ((), u8) {
 
 fn infix_binding_power(op: &TokenKind) -> Option<(u8, u8)> {
     match op {
+        TokenKind::LessThan | TokenKind::GreaterThan | TokenKind::Equal => Some((1, 2)),
         TokenKind::Plus | TokenKind::Minus => Some((3, 4)),
         // multiplication and division
         TokenKind::Asterisk | TokenKind::Slash => Some((5, 6)),
@@ -383,10 +710,12 @@ fn infix_binding_power(op: &TokenKind) -> Option<(u8, u8)> {
 pub(crate) struct ExpressionParser<'s> {
     input: &'s str,
     expression_cursor: StmtCursor<'s>,
+    /// Where a token missing at the end of the expression is reported, e.g. its closing brace.
+    end: Span,
 }
 
 impl<'s> ExpressionParser<'s> {
-    pub(crate) fn new(input: &'s str, tokens: &'s [Token]) -> Self {
+    pub(crate) fn new(input: &'s str, tokens: &'s [Token], end: Span) -> Self {
         // todo: can we assume all tokens are from the source index?
         let source_index = tokens[0].span.source_index;
         let span = Span::new(
@@ -397,11 +726,50 @@ impl<'s> ExpressionParser<'s> {
         ExpressionParser {
             input,
             expression_cursor: StmtCursor::new(tokens, span),
+            end,
         }
     }
 
     pub(crate) fn parse(&mut self) -> Result<Expr, SpicyError> {
-        self.parse_expr(0)
+        let expr = self.parse_expr(0)?;
+        // a stray ')' or ',' ends parse_expr, so make sure nothing is left over
+        if let Some(t) = self.expression_cursor.peek_non_whitespace() {
+            return Err(ExpressionError::UnexpectedToken {
+                found: t.kind,
+                span: t.span,
+            }
+            .into());
+        }
+        Ok(expr)
+    }
+
+    /// Parse the arguments of a call to `name`, after its opening parenthesis.
+    fn parse_call(&mut self, name: String, name_span: Span) -> Result<Expr, SpicyError> {
+        let mut args = Vec::new();
+        let close = loop {
+            args.push(self.parse_expr(0)?);
+            let token = self.expression_cursor.next_non_whitespace();
+            match token {
+                Some(t) if t.kind == TokenKind::Comma => continue,
+                Some(t) if t.kind == TokenKind::RightParen => break t,
+                Some(t) => {
+                    return Err(ExpressionError::UnexpectedToken {
+                        found: t.kind,
+                        span: t.span,
+                    }
+                    .into());
+                }
+                None => {
+                    return Err(ExpressionError::MissingToken {
+                        message: "closing parenthesis of function call",
+                        span: self.end,
+                    }
+                    .into());
+                }
+            }
+        };
+        let span = Span::new(name_span.start, close.span.end, name_span.source_index);
+        Ok(Expr::call(name, args, span))
     }
 
     fn parse_expr(&mut self, min_bp: u8) -> Result<Expr, SpicyError> {
@@ -410,8 +778,20 @@ impl<'s> ExpressionParser<'s> {
 
         let mut lhs = match token {
             Some(t) if t.kind == TokenKind::Ident => {
-                let name = token_text(self.input, t).to_string();
-                Expr::identifier(name, t.span)
+                let mut name = token_text(self.input, t).to_string();
+                let is_call = self
+                    .expression_cursor
+                    .peek_non_whitespace()
+                    .is_some_and(|next| next.kind == TokenKind::LeftParen);
+                if is_call {
+                    self.expression_cursor
+                        .next_non_whitespace()
+                        .expect("already peeked");
+                    self.parse_call(name, t.span)?
+                } else {
+                    consume_hierarchy(&mut self.expression_cursor, self.input, &mut name);
+                    Expr::identifier(name, t.span)
+                }
             }
             Some(t) if t.kind == TokenKind::Number => {
                 // kinda weird but, rewind to before we parsed the number then give it to parse_value
@@ -420,10 +800,23 @@ impl<'s> ExpressionParser<'s> {
                 Expr::value(value, t.span)
             }
             Some(t) if t.kind == TokenKind::LeftParen => {
-                let lhs = self.parse_expr(0)?;
-                self.expression_cursor.expect(TokenKind::RightParen)?;
-                // expand to include the parentheses
-                lhs.expand()
+                let inner = self.parse_expr(0)?;
+                let Some(close) = self
+                    .expression_cursor
+                    .next_non_whitespace()
+                    .filter(|close| close.kind == TokenKind::RightParen)
+                else {
+                    return Err(ExpressionError::MissingToken {
+                        message: "closing parenthesis",
+                        span: self.end,
+                    }
+                    .into());
+                };
+                // include the parentheses
+                Expr {
+                    span: Span::new(t.span.start, close.span.end, t.span.source_index),
+                    r#type: inner.r#type,
+                }
             }
             Some(t) if t.kind == TokenKind::Minus => {
                 let ((), r_bp) = prefix_binding_power(t);
@@ -439,7 +832,8 @@ impl<'s> ExpressionParser<'s> {
             }
             None => {
                 return Err(ExpressionError::MissingToken {
-                    message: "no token",
+                    message: "operand",
+                    span: self.end,
                 }
                 .into());
             }
@@ -450,12 +844,20 @@ impl<'s> ExpressionParser<'s> {
                 Some(t)
                     if matches!(
                         t.kind,
-                        TokenKind::Asterisk | TokenKind::Plus | TokenKind::Minus | TokenKind::Slash
+                        TokenKind::Asterisk
+                            | TokenKind::Plus
+                            | TokenKind::Minus
+                            | TokenKind::Slash
+                            | TokenKind::LessThan
+                            | TokenKind::GreaterThan
+                            | TokenKind::Equal
                     ) =>
                 {
                     t
                 }
                 Some(t) if t.kind.ident_or_numeric() => t,
+                // closes a parenthesized expression or a call argument
+                Some(t) if matches!(t.kind, TokenKind::RightParen | TokenKind::Comma) => break,
                 Some(t) => {
                     return Err(ExpressionError::UnexpectedToken {
                         found: t.kind,
@@ -486,6 +888,15 @@ impl<'s> ExpressionParser<'s> {
                 self.expression_cursor
                     .next_non_whitespace()
                     .expect("already peeked");
+                // equality is written `==`
+                if op.kind == TokenKind::Equal {
+                    self.expression_cursor.consume(TokenKind::Equal).ok_or(
+                        ExpressionError::UnexpectedToken {
+                            found: op.kind,
+                            span: op.span,
+                        },
+                    )?;
+                }
 
                 let rhs = self.parse_expr(r_bp)?;
                 lhs = Expr::binary(op.kind, lhs, rhs);
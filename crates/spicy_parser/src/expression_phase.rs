@@ -1,22 +1,44 @@
-use crate::error::{ParserError, SpicyError};
+use crate::error::{ParserError, Recovered, SpicyError, recover};
 use crate::expr::{ExpressionParser, PlaceholderMap};
 use crate::lexer::{Token, TokenKind};
 use crate::statement_phase::{Statement, Statements};
 use crate::{ParseOptions, Span};
 
+/// Replace every `{…}` by a placeholder token, leaving out a statement with a bad expression
+/// when recovering.
 pub fn substitute_expressions(
     statements: &mut Statements,
     input: &ParseOptions,
+    recovered: Recovered,
 ) -> Result<PlaceholderMap, SpicyError> {
     let mut placeholders = PlaceholderMap::default();
+    substitute_more_expressions(statements, input, &mut placeholders, recovered)?;
+    Ok(placeholders)
+}
 
-    let iterator = statements.statements.iter_mut();
-    for stmt in iterator {
+/// [`substitute_expressions`] for statements read later, with placeholders that follow those
+/// already in `placeholders`.
+pub(crate) fn substitute_more_expressions(
+    statements: &mut Statements,
+    input: &ParseOptions,
+    placeholders: &mut PlaceholderMap,
+    mut recovered: Recovered,
+) -> Result<(), SpicyError> {
+    let mut failed = Vec::new();
+    statements.statements.retain_mut(|stmt| {
         // Replace { … } with placeholders in this statement
-        brace_to_placeholders(stmt, input, &mut placeholders)?;
+        match brace_to_placeholders(stmt, input, placeholders) {
+            Ok(()) => true,
+            Err(error) => {
+                failed.push(error);
+                false
+            }
+        }
+    });
+    for error in failed {
+        recover(&mut recovered, error)?;
     }
-
-    Ok(placeholders)
+    Ok(())
 }
 
 /// Walk tokens, when seeing '{', collect until matching '}', parse inside to Expr,
@@ -46,6 +68,7 @@ fn brace_to_placeholders(
             let Some(right_brace) = right_brace else {
                 return Err(ParserError::UnmatchedBrace { span: tok.span })?;
             };
+            let braces = Span::new(tok.span.start, right_brace.span.end, tok.span.source_index);
 
             if expression_tokens.is_empty()
                 || expression_tokens
@@ -53,19 +76,17 @@ fn brace_to_placeholders(
                     .all(|t| t.kind == TokenKind::WhiteSpace)
             {
                 // we found a {} with nothing inside
-                Err(ParserError::EmptyExpressionInsideBraces {
-                    span: Span::new(tok.span.start, right_brace.span.end, tok.span.source_index),
-                })?;
+                Err(ParserError::EmptyExpressionInsideBraces { span: braces })?;
             }
 
             let end_pos = cursor.pos() - 1;
             let src = &input.source_map.get_content(tok.span.source_index);
             let parsed_expression =
-                ExpressionParser::new(src, expression_tokens.as_slice()).parse()?;
+                ExpressionParser::new(src, expression_tokens.as_slice(), right_brace.span)
+                    .parse()?;
 
-            let expanded_span = parsed_expression.span.expand();
-            let id = pm.fresh(parsed_expression);
-            replacements.push((start_pos, end_pos, Token::placeholder(id, expanded_span)));
+            let id = pm.fresh(parsed_expression, braces);
+            replacements.push((start_pos, end_pos, Token::placeholder(id, braces)));
         }
     }
 
@@ -81,6 +102,7 @@ mod tests {
 
     use super::*;
     use crate::SourceMap;
+    use crate::expr::ExprType;
     use crate::libs_phase::SourceFileId;
     use std::path::PathBuf;
 
@@ -93,11 +115,15 @@ mod tests {
             work_dir: PathBuf::from("."),
             source_path: PathBuf::from("."),
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let mut statements =
             Statements::new(&input_content, SourceFileId::new(0)).expect("statements");
 
-        let output = substitute_expressions(&mut statements, &input_options).expect("expressions");
+        let output =
+            substitute_expressions(&mut statements, &input_options, None).expect("expressions");
 
         let name = format!(
             "expression-{}",
@@ -119,10 +145,13 @@ mod tests {
             work_dir: PathBuf::from("."),
             source_path: PathBuf::from("."),
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let mut statements = Statements::new(input, SourceFileId::new(0)).expect("statements");
 
-        let err = substitute_expressions(&mut statements, &input_options).unwrap_err();
+        let err = substitute_expressions(&mut statements, &input_options, None).unwrap_err();
         let err = match err {
             SpicyError::Parser(e) => e,
             _ => panic!("expected parser error"),
@@ -137,4 +166,52 @@ mod tests {
             _ => panic!("expected EmptyExpressionInsideBraces"),
         }
     }
+    fn placeholders(input: &str) -> Result<PlaceholderMap, SpicyError> {
+        let source_map = SourceMap::new(".".into(), input.to_string());
+        let input_options = ParseOptions {
+            source_map,
+            work_dir: PathBuf::from("."),
+            source_path: PathBuf::from("."),
+            max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
+        };
+        let mut statements = Statements::new(input, SourceFileId::new(0)).expect("statements");
+        substitute_expressions(&mut statements, &input_options, None)
+    }
+
+    #[test]
+    fn test_placeholder_keeps_the_source_spans() {
+        let input = "R1 a b { -x * ( y + 2 ) } 1k";
+        let pm = placeholders(input).expect("expressions");
+        let text = |span: Span| &input[span.start..=span.end];
+        assert_eq!(text(pm.spans[0]), "{ -x * ( y + 2 ) }");
+
+        let expr = &pm.map[0];
+        assert_eq!(text(expr.span), "-x * ( y + 2 )");
+        let ExprType::Binary { left, right, .. } = &expr.r#type else {
+            panic!("expected a product, got {expr:?}");
+        };
+        assert_eq!(text(left.span), "-x");
+        assert_eq!(text(right.span), "( y + 2 )");
+    }
+
+    #[test]
+    fn test_missing_operand_points_at_the_closing_brace() {
+        let input = "R1 a b {1 +} 1k";
+        let err = placeholders(input).unwrap_err();
+        let Some(span) = err.error_span() else {
+            panic!("expected a span for {err:?}");
+        };
+        assert!(matches!(err, SpicyError::Expression(_)));
+        assert_eq!((span.start, span.end), (11, 11));
+
+        let err = placeholders("R1 a b {max(1, 2} 1k").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "missing token: closing parenthesis of function call"
+        );
+        assert_eq!(err.error_span().map(|span| span.start), Some(16));
+    }
 }
@@ -1,30 +1,78 @@
+use serde::Serialize;
+use std::path::Path;
+
 use crate::SourceMap;
+use crate::devices::lookup_table::read_iv_table;
 use crate::devices::{
-    BjtSpec, CapacitorSpec, Devices, DiodeSpec, IndependentSourceSpec, InductorSpec, ResistorSpec,
+    BehavioralExpr, BehavioralKind, BehavioralOp, BehavioralSourceSpec, BjtSpec, CapacitorSpec,
+    Devices, DiodeSpec, IndependentSourceSpec, InductorSpec, JfetSpec, LookupTableSpec, MosfetSpec,
+    MutualInductanceSpec, ResistorSpec, SwitchControl, SwitchSpec, TransmissionLineSpec,
+};
+use crate::error::{ExpressionError, ParserError, Recovered, SpicyError, recover};
+use crate::expr::{Expr, ExprFunction, ExprType, ExpressionParser, PlaceholderMap, Scope, Value};
+use crate::lexer::{Span, Token, TokenKind, token_text};
+use crate::netlist_models::{
+    BjtModel, CapacitorModel, CurrentSwitchModel, DiodeModel, InductorModel, JfetModel, ModelTable,
+    MosfetModel, ResistorModel, SwitchModel,
 };
-use crate::error::{ParserError, SpicyError};
-use crate::expr::{PlaceholderMap, Scope, Value};
-use crate::lexer::{Token, TokenKind, token_text};
-use crate::netlist_models::DeviceModel;
 use crate::netlist_types::{
-    AcCommand, AcSweepType, Command, CommandType, CurrentBranchIndex, DcCommand, DeviceType,
-    NodeName, OpCommand, Phasor, TranCommand,
+    AcCommand, AcSweepType, AnalysisType, Command, CommandType, CurrentBranchIndex, DcCommand,
+    DcSweep, DeviceType, FourierCommand, MeasureCommand, MeasureEdge, MeasureEvent,
+    MeasureFunction, MeasureKind, NodeIndex, NodeName, NodeValue, NoiseCommand, OpCommand,
+    OutputKind, OutputSpec, OutputVector, Phasor, ResponseMetric, SaveCommand, SimulatorOptions,
+    SpCommand, StepCommand, StepSweep, TranCommand,
 };
 use crate::netlist_waveform::WaveForm;
 use crate::parser_utils::{
-    parse_bool, parse_expr_into_value, parse_ident, parse_node, parse_usize,
+    Ident, consume_hierarchy, parse_bool, parse_device_name, parse_expr_into_value, parse_ident,
+    parse_node, parse_usize,
 };
 use crate::statement_phase::StmtCursor;
-use crate::subcircuit_phase::{ExpandedDeck, ScopedStmt};
+use crate::subcircuit_phase::{ExpandedDeck, ExpansionStats, ScopedStmt};
 
-use crate::node_mapping::NodeMapping;
+use crate::node_mapping::{NameCase, NodeMapping};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Deck {
     pub title: String,
     pub node_mapping: NodeMapping,
     pub commands: Vec<Command>,
+    /// `.print`/`.plot` requests; when an analysis has none, all of its vectors are output.
+    pub outputs: Vec<OutputSpec>,
+    /// `.ic` node voltages at t=0 of a transient analysis.
+    pub initial_conditions: Vec<NodeValue>,
+    /// `.nodeset` starting guesses of the operating point.
+    pub nodesets: Vec<NodeValue>,
+    /// `.step` sweeps, the first one outermost.
+    pub steps: Vec<StepCommand>,
+    /// `.temp` circuit temperatures (°C); every analysis runs at each of them.
+    pub temperatures: Vec<Value>,
+    /// `.meas` measurements, evaluated after every analysis of their kind.
+    pub measures: Vec<MeasureCommand>,
+    /// `.four` Fourier analyses of the transient results.
+    pub fourier: Vec<FourierCommand>,
+    /// `.options` settings.
+    pub options: SimulatorOptions,
+    /// `.save`/`.probe` lines; without any, a transient analysis keeps every vector.
+    pub saves: Vec<SaveCommand>,
     pub devices: Devices,
+    /// The `.MODEL` cards, resolved against the top-level params.
+    pub models: ModelTable,
+    pub expansion_stats: ExpansionStats,
+}
+
+/// Control lines that configure the analyses rather than being one.
+#[derive(Debug, Default)]
+struct ControlCards {
+    outputs: Vec<OutputSpec>,
+    initial_conditions: Vec<NodeValue>,
+    nodesets: Vec<NodeValue>,
+    steps: Vec<StepCommand>,
+    temperatures: Vec<Value>,
+    measures: Vec<MeasureCommand>,
+    fourier: Vec<FourierCommand>,
+    options: SimulatorOptions,
+    saves: Vec<SaveCommand>,
 }
 
 #[derive(Debug)]
@@ -163,23 +211,17 @@ impl<'s> Iterator for ParamParser<'s> {
                         .map(|t| t.kind == TokenKind::Ident)
                         .unwrap_or(false);
 
-                    match self.params_order.get(self.current_param) {
-                        Some(p) => {
-                            if is_ident != p.is_ident {
-                                self.current_param += 1;
-                                match self.params_order.get(self.current_param) {
-                                    Some(p) => {
-                                        Some(Ok(ParsedParam { name: p.canonical, cursor }))
-                                    }
-                                    None => Some(Err(ParserError::TooManyParameters {
-                                        index: self.current_param,
-                                        span: cursor.span,
-                                    }
-                                    .into())),
-                                }
-                            } else {
-                                Some(Ok(ParsedParam { name: p.canonical, cursor }))
-                            }
+                    // skip the optional params of the other kind, e.g. to a flag after a model
+                    let slot = self
+                        .params_order
+                        .iter()
+                        .skip(self.current_param)
+                        .position(|p| p.is_ident == is_ident);
+                    match slot {
+                        Some(skipped) => {
+                            self.current_param += skipped;
+                            let name = self.params_order[self.current_param].canonical;
+                            Some(Ok(ParsedParam { name, cursor }))
                         }
                         None => Some(Err(ParserError::TooManyParameters {
                             index: self.current_param,
@@ -205,6 +247,7 @@ pub(crate) struct InstanceParser<'s> {
     expanded_deck: ExpandedDeck,
     placeholder_map: PlaceholderMap,
     source_map: &'s SourceMap,
+    name_case: NameCase,
 }
 
 impl<'s> InstanceParser<'s> {
@@ -212,11 +255,13 @@ impl<'s> InstanceParser<'s> {
         expanded_deck: ExpandedDeck,
         placeholder_map: PlaceholderMap,
         source_map: &'s SourceMap,
+        name_case: NameCase,
     ) -> Self {
         InstanceParser {
             expanded_deck,
             placeholder_map,
             source_map,
+            name_case,
         }
     }
 
@@ -335,6 +380,84 @@ impl<'s> InstanceParser<'s> {
                         number_of_pulses: values.get(7).cloned().map(|v| v.get_value() as u64),
                     }
                 }
+                "PWL" => {
+                    let values = self.parse_in_parentheses(cursor, scope)?;
+                    if values.len() < 2 || values.len() % 2 != 0 {
+                        return Err(ParserError::InvalidParam {
+                            param: "PWL expects pairs of time and value".to_string(),
+                            span: ident_token.span,
+                        }
+                        .into());
+                    }
+                    let points: Vec<(Value, Value)> = values
+                        .chunks(2)
+                        .map(|pair| (pair[0].clone(), pair[1].clone()))
+                        .collect();
+                    if points
+                        .windows(2)
+                        .any(|w| w[1].0.get_value() < w[0].0.get_value())
+                    {
+                        return Err(ParserError::InvalidParam {
+                            param: "PWL times must not decrease".to_string(),
+                            span: ident_token.span,
+                        }
+                        .into());
+                    }
+
+                    // PWL(...) <R=value> <TD=value>
+                    let mut repeat = None;
+                    let mut delay = None;
+                    loop {
+                        let mark = cursor.checkpoint();
+                        cursor.skip_ws();
+                        let Some(name) = cursor.consume(TokenKind::Ident) else {
+                            cursor.rewind(mark);
+                            break;
+                        };
+                        if cursor.consume(TokenKind::Equal).is_none() {
+                            cursor.rewind(mark);
+                            break;
+                        }
+                        let name_text = token_text(input, name);
+                        match name_text.to_lowercase().as_str() {
+                            "r" => repeat = Some(self.parse_value(cursor, scope)?),
+                            "td" => delay = Some(self.parse_value(cursor, scope)?),
+                            _ => {
+                                return Err(ParserError::InvalidParam {
+                                    param: name_text.to_string(),
+                                    span: name.span,
+                                }
+                                .into());
+                            }
+                        }
+                    }
+
+                    WaveForm::PiecewiseLinear {
+                        points,
+                        repeat,
+                        delay,
+                    }
+                }
+                "SFFM" => {
+                    let values = self.parse_in_parentheses(cursor, scope)?;
+                    WaveForm::SingleFrequencyFm {
+                        offset: values.first().cloned().ok_or_else(|| {
+                            ParserError::MissingToken {
+                                message: "expected offset value for SFFM waveform",
+                                span: cursor.peek_span(),
+                            }
+                        })?,
+                        amplitude: values.get(1).cloned().ok_or_else(|| {
+                            ParserError::MissingToken {
+                                message: "expected amplitude value for SFFM waveform",
+                                span: cursor.peek_span(),
+                            }
+                        })?,
+                        carrier_frequency: values.get(2).cloned(),
+                        modulation_index: values.get(3).cloned(),
+                        signal_frequency: values.get(4).cloned(),
+                    }
+                }
                 _ => {
                     return Err(ParserError::InvalidOperation {
                         operation: ident.to_string(),
@@ -350,20 +473,37 @@ impl<'s> InstanceParser<'s> {
     fn parse_node(&self, cursor: &mut StmtCursor, scope: &Scope) -> Result<NodeName, SpicyError> {
         let input = self.source_map.get_content(cursor.span.source_index);
         let node = parse_node(cursor, input)?;
+        Ok(self.resolve_node(node, scope))
+    }
 
-        if let Some(node) = scope.node_mapping.get(&node) {
-            Ok(node.clone())
+    /// The deck-wide name of `node` as written in `scope`: a subcircuit port becomes the node
+    /// it is connected to, ground and `.global` nodes stay as they are, and any other node is
+    /// local to the instance.
+    fn resolve_node(&self, node: NodeName, scope: &Scope) -> NodeName {
+        let port = scope
+            .node_mapping
+            .iter()
+            .find(|(formal, _)| self.name_case.matches(&formal.0, &node.0));
+        if let Some((_, actual)) = port {
+            return actual.clone();
+        }
+        let global = node.0 == "0"
+            || self
+                .expanded_deck
+                .global_nodes
+                .iter()
+                .any(|g| self.name_case.matches(&g.0, &node.0));
+        if global {
+            node
         } else {
-            Ok(node)
+            scope.get_node_name(node)
         }
     }
 
     fn parse_bool(&self, cursor: &mut StmtCursor, scope: &Scope) -> Result<bool, SpicyError> {
         if let Some(token) = cursor.consume(TokenKind::Placeholder) {
             let id = token.id.expect("must have a placeholder id");
-            // TOOD: maybe we can change the expresion to only evalute once
-            let expr = self.placeholder_map.get(id).clone();
-            let evaluated = expr.evaluate(scope)?;
+            let evaluated = self.placeholder_map.evaluate(id, scope)?;
             // TODO: kinda ugly
             if evaluated.get_value() == 0.0 {
                 return Ok(false);
@@ -371,7 +511,10 @@ impl<'s> InstanceParser<'s> {
             if evaluated.get_value() == 1.0 {
                 return Ok(true);
             }
-            return Err(ParserError::ExpectedBoolZeroOrOne { span: token.span }.into());
+            return Err(ParserError::ExpectedBoolZeroOrOne {
+                span: self.placeholder_map.span(id),
+            }
+            .into());
         }
         let input = self.source_map.get_content(cursor.span.source_index);
         parse_bool(cursor, input)
@@ -380,9 +523,7 @@ impl<'s> InstanceParser<'s> {
     fn parse_usize(&self, cursor: &mut StmtCursor, scope: &Scope) -> Result<usize, SpicyError> {
         if let Some(token) = cursor.consume(TokenKind::Placeholder) {
             let id = token.id.expect("must have a placeholder id");
-            // TOOD: maybe we can change the expresion to only evalute once
-            let expr = self.placeholder_map.get(id).clone();
-            let evaluated = expr.evaluate(scope)?;
+            let evaluated = self.placeholder_map.evaluate(id, scope)?;
             let value = evaluated.get_value();
             // TODO: baba
             // Check if value is an integer (no fractional part)
@@ -392,14 +533,14 @@ impl<'s> InstanceParser<'s> {
                     return Ok(value as usize);
                 } else {
                     return Err(ParserError::InvalidNumericLiteral {
-                        span: Some(token.span),
+                        span: Some(self.placeholder_map.span(id)),
                         lexeme: format!("{:?}", evaluated),
                     }
                     .into());
                 }
             } else {
                 return Err(ParserError::InvalidNumericLiteral {
-                    span: Some(token.span),
+                    span: Some(self.placeholder_map.span(id)),
                     lexeme: format!("{:?}", evaluated),
                 }
                 .into());
@@ -411,7 +552,7 @@ impl<'s> InstanceParser<'s> {
 
     // RXXXXXXX n+ n- <resistance|r=>value <ac=val> <m=val>
     // + <scale=val> <temp=val> <dtemp=val> <tc1=val> <tc2=val>
-    // + <noisy=0|1>
+    // + <noisy=0|1> <rth=val> <cth=val>
     fn parse_resistor(
         &self,
         name: String,
@@ -438,6 +579,8 @@ impl<'s> InstanceParser<'s> {
             ParamSlot::other("tc1"),
             ParamSlot::other("tc2"),
             ParamSlot::other("noisy"),
+            ParamSlot::other("rth"),
+            ParamSlot::other("cth"),
         ];
         let input = self.source_map.get_content(cursor.span.source_index);
         let params = ParamParser::new(input, params_order, cursor);
@@ -456,20 +599,8 @@ impl<'s> InstanceParser<'s> {
                     let model = self
                         .expanded_deck
                         .model_table
-                        .get(model_name.text)
-                        .ok_or_else(|| ParserError::MissingModel {
-                            model: model_name.text.to_string(),
-                            span: model_name.span,
-                        })?;
-                    if let DeviceModel::Resistor(model) = model {
-                        resistor.set_model(model.clone());
-                    } else {
-                        return Err(ParserError::InvalidModel {
-                            model: model_name.text.to_string(),
-                            span: model_name.span,
-                        }
-                        .into());
-                    }
+                        .resolve::<ResistorModel>(&model_name)?;
+                    resistor.set_model(model.clone());
                 }
                 "ac" => {
                     let value = self.parse_value(&mut cursor, scope)?;
@@ -503,6 +634,14 @@ impl<'s> InstanceParser<'s> {
                     let value = self.parse_bool(&mut cursor, scope)?;
                     resistor.set_noisy(value);
                 }
+                "rth" => {
+                    let value = self.parse_value(&mut cursor, scope)?;
+                    resistor.set_rth(value);
+                }
+                "cth" => {
+                    let value = self.parse_value(&mut cursor, scope)?;
+                    resistor.set_cth(value);
+                }
                 _ => {
                     return Err(ParserError::InvalidParam {
                         param: ident.to_string(),
@@ -561,20 +700,9 @@ impl<'s> InstanceParser<'s> {
                     let model = self
                         .expanded_deck
                         .model_table
-                        .get(model_name.text)
-                        .ok_or_else(|| ParserError::MissingModel {
-                            model: model_name.text.to_string(),
-                            span: model_name.span,
-                        })?;
-                    if let DeviceModel::Capacitor(model) = model {
-                        capacitor.set_model(model.clone());
-                    } else {
-                        return Err(ParserError::InvalidModel {
-                            model: model_name.text.to_string(),
-                            span: model_name.span,
-                        }
-                        .into());
-                    }
+                        .resolve::<CapacitorModel>(&model_name)?;
+                    capacitor.set_model(model.clone());
+                    capacitor.mname = Some(model_name.text.to_string());
                 }
                 "m" => {
                     let value = self.parse_value(&mut cursor, scope)?;
@@ -672,20 +800,8 @@ impl<'s> InstanceParser<'s> {
                     let model = self
                         .expanded_deck
                         .model_table
-                        .get(model_name.text)
-                        .ok_or_else(|| ParserError::MissingModel {
-                            model: model_name.text.to_string(),
-                            span: model_name.span,
-                        })?;
-                    if let DeviceModel::Inductor(model) = model {
-                        inductor.set_model(model.clone());
-                    } else {
-                        return Err(ParserError::InvalidModel {
-                            model: model_name.text.to_string(),
-                            span: model_name.span,
-                        }
-                        .into());
-                    }
+                        .resolve::<InductorModel>(&model_name)?;
+                    inductor.set_model(model.clone());
                 }
                 "nt" => {
                     let value = self.parse_value(&mut cursor, scope)?;
@@ -732,6 +848,228 @@ impl<'s> InstanceParser<'s> {
         Ok(inductor)
     }
 
+    // KXXXXXXX LYYYYYYY LZZZZZZZ value
+    fn parse_mutual_inductance(
+        &self,
+        name: String,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+    ) -> Result<MutualInductanceSpec, SpicyError> {
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let inductor1 = scope.get_device_name(&parse_device_name(cursor, input)?);
+        let inductor2 = scope.get_device_name(&parse_device_name(cursor, input)?);
+
+        let coupling = self.parse_value(cursor, scope)?;
+        let k = coupling.get_value();
+        if !(k > 0.0 && k <= 1.0) {
+            return Err(ParserError::InvalidParam {
+                param: format!("coupling {k} (must be in (0, 1])"),
+                span: cursor.span,
+            }
+            .into());
+        }
+        if let Some(token) = cursor.peek_non_whitespace() {
+            return Err(ParserError::UnexpectedToken {
+                expected: "end of coupling".to_string(),
+                found: token.kind,
+                span: token.span,
+            }
+            .into());
+        }
+
+        Ok(MutualInductanceSpec {
+            name,
+            span: cursor.span,
+            inductor1,
+            inductor2,
+            coupling,
+        })
+    }
+
+    // TXXXXXXX N1 N2 N3 N4 Z0=VALUE TD=VALUE
+    fn parse_transmission_line(
+        &self,
+        name: String,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+        node_mapping: &mut NodeMapping,
+    ) -> Result<TransmissionLineSpec, SpicyError> {
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let positive1 = self.parse_node(cursor, scope)?;
+        let negative1 = self.parse_node(cursor, scope)?;
+        let positive2 = self.parse_node(cursor, scope)?;
+        let negative2 = self.parse_node(cursor, scope)?;
+
+        let mut z0 = None;
+        let mut td = None;
+        while cursor.peek_non_whitespace().is_some() {
+            let param = parse_ident(cursor, input)?;
+            let slot = match param.text.to_ascii_uppercase().as_str() {
+                "Z0" | "ZO" => &mut z0,
+                "TD" => &mut td,
+                _ => {
+                    return Err(ParserError::InvalidParam {
+                        param: param.text.to_string(),
+                        span: param.span,
+                    }
+                    .into());
+                }
+            };
+            cursor.expect_non_whitespace(TokenKind::Equal)?;
+            let value = self.parse_value(cursor, scope)?;
+            if value.get_value() <= 0.0 {
+                return Err(ParserError::InvalidParam {
+                    param: format!("{} {} (must be positive)", param.text, value.get_value()),
+                    span: param.span,
+                }
+                .into());
+            }
+            *slot = Some(value);
+        }
+        let (Some(z0), Some(td)) = (z0, td) else {
+            return Err(ParserError::MissingToken {
+                message: "transmission line needs Z0= and TD=",
+                span: Some(cursor.span),
+            }
+            .into());
+        };
+
+        Ok(TransmissionLineSpec {
+            span: cursor.span,
+            positive1: node_mapping.insert_node(positive1),
+            negative1: node_mapping.insert_node(negative1),
+            positive2: node_mapping.insert_node(positive2),
+            negative2: node_mapping.insert_node(negative2),
+            branch1: node_mapping.insert_branch(format!("{name}#1")),
+            branch2: node_mapping.insert_branch(format!("{name}#2")),
+            name,
+            z0,
+            td,
+        })
+    }
+
+    // AXXXXXXX N+ N- FILE
+    fn parse_lookup_table(
+        &self,
+        name: String,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+        node_mapping: &mut NodeMapping,
+    ) -> Result<LookupTableSpec, SpicyError> {
+        let positive = self.parse_node(cursor, scope)?;
+        let negative = self.parse_node(cursor, scope)?;
+
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let fields = cursor.split_on_whitespace();
+        let [file] = fields.as_slice() else {
+            return Err(ParserError::MissingToken {
+                message: "lookup table needs a single CSV file",
+                span: Some(cursor.span),
+            }
+            .into());
+        };
+        // relative to the netlist the element is written in
+        let path = self
+            .source_map
+            .get_path(file.span.source_index)
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(&input[file.span.start..=file.span.end]);
+        let points = read_iv_table(&path).map_err(|reason| ParserError::InvalidTable {
+            path: path.clone(),
+            reason,
+            span: file.span,
+        })?;
+
+        Ok(LookupTableSpec {
+            name,
+            span: cursor.span,
+            positive: node_mapping.insert_node(positive),
+            negative: node_mapping.insert_node(negative),
+            path,
+            points,
+        })
+    }
+
+    // SXXXXXXX N+ N- NC+ NC- MODEL <ON|OFF>
+    // WYYYYYYY N+ N- VNAM MODEL <ON|OFF>
+    fn parse_switch(
+        &self,
+        name: String,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+        node_mapping: &mut NodeMapping,
+        current_controlled: bool,
+    ) -> Result<SwitchSpec, SpicyError> {
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let positive = self.parse_node(cursor, scope)?;
+        let negative = self.parse_node(cursor, scope)?;
+        let positive = node_mapping.insert_node(positive);
+        let negative = node_mapping.insert_node(negative);
+
+        let control = if current_controlled {
+            let source = scope.get_device_name(&parse_device_name(cursor, input)?);
+            let model_name = parse_ident(cursor, input)?;
+            let model = self
+                .expanded_deck
+                .model_table
+                .resolve::<CurrentSwitchModel>(&model_name)?;
+            SwitchControl::Current {
+                name: source,
+                branch: CurrentBranchIndex(0),
+                model: model.clone(),
+            }
+        } else {
+            let control_positive = self.parse_node(cursor, scope)?;
+            let control_negative = self.parse_node(cursor, scope)?;
+            let model_name = parse_ident(cursor, input)?;
+            let model = self
+                .expanded_deck
+                .model_table
+                .resolve::<SwitchModel>(&model_name)?;
+            SwitchControl::Voltage {
+                positive: node_mapping.insert_node(control_positive),
+                negative: node_mapping.insert_node(control_negative),
+                model: model.clone(),
+            }
+        };
+
+        let on = match cursor.peek_non_whitespace() {
+            None => false,
+            Some(_) => {
+                let state = parse_ident(cursor, input)?;
+                match state.text.to_ascii_uppercase().as_str() {
+                    "ON" => true,
+                    "OFF" => false,
+                    _ => {
+                        return Err(ParserError::InvalidParam {
+                            param: state.text.to_string(),
+                            span: state.span,
+                        }
+                        .into());
+                    }
+                }
+            }
+        };
+        if let Some(token) = cursor.peek_non_whitespace() {
+            return Err(ParserError::UnexpectedToken {
+                expected: "end of switch".to_string(),
+                found: token.kind,
+                span: token.span,
+            }
+            .into());
+        }
+
+        Ok(SwitchSpec {
+            name,
+            span: cursor.span,
+            positive,
+            negative,
+            control,
+            on,
+        })
+    }
+
     // DXXXXXXX n+ n- mname <area=val> <m=val> <pj=val> <off>
     // + <ic=vd> <temp=val> <dtemp=val>
     // + <lm=val> <wm=val> <lp=val> <wp=val>
@@ -753,19 +1091,7 @@ impl<'s> InstanceParser<'s> {
         let model = self
             .expanded_deck
             .model_table
-            .get(model_name.text)
-            .ok_or_else(|| ParserError::MissingModel {
-                model: model_name.text.to_string(),
-                span: model_name.span,
-            })?;
-
-        let DeviceModel::Diode(model) = model else {
-            return Err(ParserError::InvalidModel {
-                model: model_name.text.to_string(),
-                span: model_name.span,
-            }
-            .into());
-        };
+            .resolve::<DiodeModel>(&model_name)?;
 
         let mut diode = DiodeSpec::new(
             name,
@@ -841,19 +1167,20 @@ impl<'s> InstanceParser<'s> {
         let model = self
             .expanded_deck
             .model_table
-            .get(model_name.text)
-            .ok_or_else(|| ParserError::MissingModel {
-                model: model_name.text.to_string(),
-                span: model_name.span,
-            })?;
-
-        let DeviceModel::Bjt(model) = model else {
-            return Err(ParserError::InvalidModel {
-                model: model_name.text.to_string(),
-                span: model_name.span,
-            }
-            .into());
-        };
+            .resolve::<BjtModel>(&model_name)?;
+
+        // the series resistances of the model sit between the terminals and internal nodes,
+        // named like ngspice's (`Q1#base`)
+        let mut internal =
+            |terminal: NodeIndex, resistance: &Option<Value>, suffix: &str| match resistance {
+                Some(r) if r.get_value() > 0.0 => {
+                    node_mapping.insert_node(NodeName(format!("{name}#{suffix}")))
+                }
+                _ => terminal,
+            };
+        let collector_prime = internal(collector_node, &model.rc, "collector");
+        let base_prime = internal(base_node, &model.rb, "base");
+        let emitter_prime = internal(emitter_node, &model.re, "emitter");
 
         let mut bjt = BjtSpec::new(
             name,
@@ -863,6 +1190,7 @@ impl<'s> InstanceParser<'s> {
             emitter_node,
             model.clone(),
         );
+        bjt.set_internal_nodes(collector_prime, base_prime, emitter_prime);
 
         let params_order = vec![
             ParamSlot::other("area"),
@@ -902,79 +1230,244 @@ impl<'s> InstanceParser<'s> {
         Ok(bjt)
     }
 
-    fn parse_source_value(
+    // MXXXXXXX nd ng ns nb mname <m=val> <l=val> <w=val>
+    // + <off> <ic=vds,vgs,vbs>
+    fn parse_mosfet(
         &self,
+        name: String,
         cursor: &mut StmtCursor,
         scope: &Scope,
-        independent_source: &mut IndependentSourceSpec,
-    ) -> Result<(), SpicyError> {
-        cursor.skip_ws();
-        if let Some(token) = cursor.consume(TokenKind::Ident) {
-            let input = self.source_map.get_content(token.span.source_index);
+        node_mapping: &mut NodeMapping,
+    ) -> Result<MosfetSpec, SpicyError> {
+        let drain = self.parse_node(cursor, scope)?;
+        let gate = self.parse_node(cursor, scope)?;
+        let source = self.parse_node(cursor, scope)?;
+        let bulk = self.parse_node(cursor, scope)?;
 
-            let operation = token_text(input, token);
+        let drain_node = node_mapping.insert_node(drain);
+        let gate_node = node_mapping.insert_node(gate);
+        let source_node = node_mapping.insert_node(source);
+        let bulk_node = node_mapping.insert_node(bulk);
 
-            match operation {
-                "DC" => {
-                    independent_source.set_dc(WaveForm::Constant(self.parse_value(cursor, scope)?))
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let model_name = parse_ident(cursor, input)?;
+        let model = self
+            .expanded_deck
+            .model_table
+            .resolve::<MosfetModel>(&model_name)?;
+
+        let mut mosfet = MosfetSpec::new(
+            name,
+            cursor.span,
+            drain_node,
+            gate_node,
+            source_node,
+            bulk_node,
+            model.clone(),
+        );
+
+        let params_order = vec![
+            ParamSlot::other("m"),
+            ParamSlot::other("l"),
+            ParamSlot::other("w"),
+            ParamSlot::flag("off"),
+            ParamSlot::other("ic"),
+        ];
+        let params = ParamParser::new(input, params_order, cursor);
+        for item in params {
+            let ParsedParam {
+                name: ident,
+                mut cursor,
+            } = item?;
+            match ident {
+                "m" => mosfet.set_m(self.parse_value(&mut cursor, scope)?),
+                "l" => mosfet.set_l(self.parse_value(&mut cursor, scope)?),
+                "w" => mosfet.set_w(self.parse_value(&mut cursor, scope)?),
+                "off" => mosfet.set_off(true),
+                "ic" => {
+                    let vds = self.parse_value(&mut cursor, scope)?;
+                    let mut next = || -> Result<Option<Value>, SpicyError> {
+                        if cursor.consume(TokenKind::Comma).is_some() {
+                            Ok(Some(self.parse_value(&mut cursor, scope)?))
+                        } else {
+                            Ok(None)
+                        }
+                    };
+                    let vgs = next()?;
+                    let vbs = next()?;
+                    mosfet.set_ic(vds, vgs, vbs);
                 }
-                "AC" => {
-                    let mag = self.parse_value(cursor, scope)?;
-                    let mut phasor = Phasor::new(mag);
-                    if cursor.peek_non_whitespace().is_some() {
-                        let phase = self.parse_value(cursor, scope)?;
-                        phasor.set_phase(phase);
+                _ => {
+                    return Err(ParserError::InvalidParam {
+                        param: ident.to_string(),
+                        span: cursor.span,
                     }
-
-                    independent_source.set_ac(phasor);
+                    .into());
                 }
-                _ => independent_source.set_dc(self.parse_waveform(token, cursor, scope)?),
-            };
-        } else {
-            independent_source.set_dc(WaveForm::Constant(self.parse_value(cursor, scope)?))
+            }
         }
 
-        Ok(())
+        Ok(mosfet)
     }
 
-    // VXXXXXXX N+ N- <<DC> DC/TRAN VALUE> <AC <ACMAG <ACPHASE>>>
-    // + <DISTOF1 <F1MAG <F1PHASE>>> <DISTOF2 <F2MAG <F2PHASE>>>
-    // IYYYYYYY N+ N- <<DC> DC/TRAN VALUE> <AC <ACMAG <ACPHASE>>>
-    // + <DISTOF1 <F1MAG <F1PHASE>>> <DISTOF2 <F2MAG <F2PHASE>>>
-    fn parse_independent_source(
+    // JXXXXXXX nd ng ns mname <area> <off> <ic=vds,vgs>
+    fn parse_jfet(
         &self,
         name: String,
         cursor: &mut StmtCursor,
         scope: &Scope,
         node_mapping: &mut NodeMapping,
-        alloc_branch: bool,
-    ) -> Result<IndependentSourceSpec, SpicyError> {
-        let positive = self.parse_node(cursor, scope)?;
-        let negative = self.parse_node(cursor, scope)?;
-
-        let positive_node = node_mapping.insert_node(positive);
-        let negative_node = node_mapping.insert_node(negative);
-        let current_branch = if alloc_branch {
-            node_mapping.insert_branch(name.clone())
-        } else {
-            CurrentBranchIndex(0)
-        };
+    ) -> Result<JfetSpec, SpicyError> {
+        let drain = self.parse_node(cursor, scope)?;
+        let gate = self.parse_node(cursor, scope)?;
+        let source = self.parse_node(cursor, scope)?;
 
-        let mut independent_source =
-            IndependentSourceSpec::new(name, positive_node, negative_node, current_branch);
+        let drain_node = node_mapping.insert_node(drain);
+        let gate_node = node_mapping.insert_node(gate);
+        let source_node = node_mapping.insert_node(source);
 
-        self.parse_source_value(cursor, scope, &mut independent_source)?;
-        let next_token = cursor.peek_non_whitespace();
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let model_name = parse_ident(cursor, input)?;
+        let model = self
+            .expanded_deck
+            .model_table
+            .resolve::<JfetModel>(&model_name)?;
 
-        match next_token {
-            Some(token) if token.kind == TokenKind::Ident => {
-                self.parse_source_value(cursor, scope, &mut independent_source)?;
-            }
-            Some(token) => {
-                return Err(ParserError::UnexpectedToken {
-                    expected: "ident".to_string(),
-                    found: token.kind,
-                    span: token.span,
+        // like the BJT's, the series resistances get internal nodes (`J1#drain`)
+        let mut internal =
+            |terminal: NodeIndex, resistance: &Option<Value>, suffix: &str| match resistance {
+                Some(r) if r.get_value() > 0.0 => {
+                    node_mapping.insert_node(NodeName(format!("{name}#{suffix}")))
+                }
+                _ => terminal,
+            };
+        let drain_prime = internal(drain_node, &model.rd, "drain");
+        let source_prime = internal(source_node, &model.rs, "source");
+
+        let mut jfet = JfetSpec::new(
+            name,
+            cursor.span,
+            drain_node,
+            gate_node,
+            source_node,
+            model.clone(),
+        );
+        jfet.set_internal_nodes(drain_prime, source_prime);
+
+        let params_order = vec![
+            ParamSlot::other("area"),
+            ParamSlot::flag("off"),
+            ParamSlot::other("ic"),
+        ];
+        let params = ParamParser::new(input, params_order, cursor);
+        for item in params {
+            let ParsedParam {
+                name: ident,
+                mut cursor,
+            } = item?;
+            match ident {
+                "area" => jfet.set_area(self.parse_value(&mut cursor, scope)?),
+                "off" => jfet.set_off(true),
+                "ic" => {
+                    let vds = self.parse_value(&mut cursor, scope)?;
+                    let vgs = if cursor.consume(TokenKind::Comma).is_some() {
+                        Some(self.parse_value(&mut cursor, scope)?)
+                    } else {
+                        None
+                    };
+                    jfet.set_ic(vds, vgs);
+                }
+                _ => {
+                    return Err(ParserError::InvalidParam {
+                        param: ident.to_string(),
+                        span: cursor.span,
+                    }
+                    .into());
+                }
+            }
+        }
+
+        Ok(jfet)
+    }
+
+    fn parse_source_value(
+        &self,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+        independent_source: &mut IndependentSourceSpec,
+    ) -> Result<(), SpicyError> {
+        cursor.skip_ws();
+        if let Some(token) = cursor.consume(TokenKind::Ident) {
+            let input = self.source_map.get_content(token.span.source_index);
+
+            let operation = token_text(input, token);
+
+            match operation {
+                "DC" => {
+                    independent_source.set_dc(WaveForm::Constant(self.parse_value(cursor, scope)?))
+                }
+                "AC" => {
+                    let mag = self.parse_value(cursor, scope)?;
+                    let mut phasor = Phasor::new(mag);
+                    if cursor.peek_non_whitespace().is_some() {
+                        let phase = self.parse_value(cursor, scope)?;
+                        phasor.set_phase(phase);
+                    }
+
+                    independent_source.set_ac(phasor);
+                }
+                _ => independent_source.set_dc(self.parse_waveform(token, cursor, scope)?),
+            };
+        } else {
+            independent_source.set_dc(WaveForm::Constant(self.parse_value(cursor, scope)?))
+        }
+
+        Ok(())
+    }
+
+    // VXXXXXXX N+ N- <<DC> DC/TRAN VALUE> <AC <ACMAG <ACPHASE>>>
+    // + <DISTOF1 <F1MAG <F1PHASE>>> <DISTOF2 <F2MAG <F2PHASE>>>
+    // IYYYYYYY N+ N- <<DC> DC/TRAN VALUE> <AC <ACMAG <ACPHASE>>>
+    // + <DISTOF1 <F1MAG <F1PHASE>>> <DISTOF2 <F2MAG <F2PHASE>>>
+    fn parse_independent_source(
+        &self,
+        name: String,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+        node_mapping: &mut NodeMapping,
+        alloc_branch: bool,
+    ) -> Result<IndependentSourceSpec, SpicyError> {
+        let positive = self.parse_node(cursor, scope)?;
+        let negative = self.parse_node(cursor, scope)?;
+
+        let positive_node = node_mapping.insert_node(positive);
+        let negative_node = node_mapping.insert_node(negative);
+        let current_branch = if alloc_branch {
+            node_mapping.insert_branch(name.clone())
+        } else {
+            CurrentBranchIndex(0)
+        };
+
+        let mut independent_source = IndependentSourceSpec::new(
+            name,
+            cursor.span,
+            positive_node,
+            negative_node,
+            current_branch,
+        );
+
+        self.parse_source_value(cursor, scope, &mut independent_source)?;
+        let next_token = cursor.peek_non_whitespace();
+
+        match next_token {
+            Some(token) if token.kind == TokenKind::Ident => {
+                self.parse_source_value(cursor, scope, &mut independent_source)?;
+            }
+            Some(token) => {
+                return Err(ParserError::UnexpectedToken {
+                    expected: "ident".to_string(),
+                    found: token.kind,
+                    span: token.span,
                 }
                 .into());
             }
@@ -984,6 +1477,187 @@ impl<'s> InstanceParser<'s> {
         Ok(independent_source)
     }
 
+    // BXXXXXXX N+ N- <I=EXPR> <V=EXPR>
+    fn parse_behavioral(
+        &self,
+        name: String,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+        node_mapping: &mut NodeMapping,
+    ) -> Result<BehavioralSourceSpec, SpicyError> {
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let positive = self.parse_node(cursor, scope)?;
+        let negative = self.parse_node(cursor, scope)?;
+        let positive_node = node_mapping.insert_node(positive);
+        let negative_node = node_mapping.insert_node(negative);
+
+        let kind_token = cursor.expect_non_whitespace(TokenKind::Ident)?;
+        let (kind, current_branch) = match token_text(input, kind_token).to_uppercase().as_str() {
+            "V" => (
+                BehavioralKind::Voltage,
+                node_mapping.insert_branch(name.clone()),
+            ),
+            "I" => (BehavioralKind::Current, CurrentBranchIndex(0)),
+            other => {
+                return Err(ParserError::InvalidParam {
+                    param: other.to_string(),
+                    span: kind_token.span,
+                }
+                .into());
+            }
+        };
+        cursor.expect_non_whitespace(TokenKind::Equal)?;
+
+        cursor.skip_ws();
+        let expr = match cursor.consume(TokenKind::Placeholder) {
+            Some(token) => {
+                let id = token.id.expect("must have a placeholder id");
+                self.placeholder_map.get(id).clone()
+            }
+            None => {
+                let rest = cursor.rest();
+                if rest.is_empty() {
+                    return Err(ParserError::MissingToken {
+                        message: "behavioral source expression",
+                        span: Some(cursor.span),
+                    }
+                    .into());
+                }
+                let end = Span::new(cursor.span.end, cursor.span.end, cursor.span.source_index);
+                ExpressionParser::new(input, rest, end).parse()?
+            }
+        };
+        if let Some(token) = cursor.peek_non_whitespace() {
+            return Err(ParserError::UnexpectedToken {
+                expected: "end of behavioral source".to_string(),
+                found: token.kind,
+                span: token.span,
+            }
+            .into());
+        }
+
+        Ok(BehavioralSourceSpec {
+            name,
+            span: cursor.span,
+            positive: positive_node,
+            negative: negative_node,
+            kind,
+            current_branch,
+            expr: self.behavioral_expr(&expr, input, scope, node_mapping)?,
+        })
+    }
+
+    /// Evaluate the params of a B source expression and resolve its `V(..)` nodes. `I(..)`
+    /// branches are resolved once the whole deck is parsed.
+    fn behavioral_expr(
+        &self,
+        expr: &Expr,
+        input: &str,
+        scope: &Scope,
+        node_mapping: &mut NodeMapping,
+    ) -> Result<BehavioralExpr, SpicyError> {
+        let behavioral = match &expr.r#type {
+            ExprType::Value(value) => BehavioralExpr::Constant(value.get_value()),
+            ExprType::Ident(name) if name.eq_ignore_ascii_case("time") => BehavioralExpr::Time,
+            ExprType::Ident(name) => {
+                BehavioralExpr::Constant(scope.param_value(name, expr.span)?.get_value())
+            }
+            ExprType::Unary { op, operand } => match op {
+                TokenKind::Minus => BehavioralExpr::Negate(Box::new(self.behavioral_expr(
+                    operand,
+                    input,
+                    scope,
+                    node_mapping,
+                )?)),
+                _ => {
+                    return Err(ExpressionError::UnsupportedUnaryOperator {
+                        op: *op,
+                        span: expr.span,
+                    }
+                    .into());
+                }
+            },
+            ExprType::Binary { op, left, right } => {
+                let op = match op {
+                    TokenKind::Plus => BehavioralOp::Add,
+                    TokenKind::Minus => BehavioralOp::Sub,
+                    TokenKind::Asterisk => BehavioralOp::Mul,
+                    TokenKind::Slash => BehavioralOp::Div,
+                    TokenKind::LessThan => BehavioralOp::Less,
+                    TokenKind::GreaterThan => BehavioralOp::Greater,
+                    _ => {
+                        return Err(ExpressionError::UnsupportedBinaryOperator {
+                            op: *op,
+                            span: expr.span,
+                        }
+                        .into());
+                    }
+                };
+                BehavioralExpr::Binary {
+                    op,
+                    left: Box::new(self.behavioral_expr(left, input, scope, node_mapping)?),
+                    right: Box::new(self.behavioral_expr(right, input, scope, node_mapping)?),
+                }
+            }
+            ExprType::Call { function, args } if function.eq_ignore_ascii_case("v") => {
+                let mut nodes = Vec::with_capacity(args.len());
+                for arg in args {
+                    let (ExprType::Ident(_) | ExprType::Value(_)) = arg.r#type else {
+                        return Err(ParserError::ExpectedIdent { span: arg.span }.into());
+                    };
+                    let node = NodeName(input[arg.span.start..=arg.span.end].to_string());
+                    let node = self.resolve_node(node, scope);
+                    nodes.push(BehavioralExpr::Voltage(node_mapping.insert_node(node)));
+                }
+                match <[BehavioralExpr; 1]>::try_from(nodes) {
+                    Ok([node]) => node,
+                    Err(nodes) => match <[BehavioralExpr; 2]>::try_from(nodes) {
+                        Ok([positive, negative]) => BehavioralExpr::Binary {
+                            op: BehavioralOp::Sub,
+                            left: Box::new(positive),
+                            right: Box::new(negative),
+                        },
+                        Err(nodes) => {
+                            return Err(ExpressionError::WrongArgumentCount {
+                                name: function.clone(),
+                                expected: 2,
+                                found: nodes.len(),
+                                span: expr.span,
+                            }
+                            .into());
+                        }
+                    },
+                }
+            }
+            ExprType::Call { function, args } if function.eq_ignore_ascii_case("i") => {
+                let [arg] = args.as_slice() else {
+                    return Err(ExpressionError::WrongArgumentCount {
+                        name: function.clone(),
+                        expected: 1,
+                        found: args.len(),
+                        span: expr.span,
+                    }
+                    .into());
+                };
+                let ExprType::Ident(device) = &arg.r#type else {
+                    return Err(ParserError::ExpectedIdent { span: arg.span }.into());
+                };
+                BehavioralExpr::Current {
+                    name: scope.get_device_name(device),
+                    branch: CurrentBranchIndex(0),
+                }
+            }
+            ExprType::Call { function, args } => BehavioralExpr::Call {
+                function: ExprFunction::resolve(function, args.len(), expr.span)?,
+                args: args
+                    .iter()
+                    .map(|arg| self.behavioral_expr(arg, input, scope, node_mapping))
+                    .collect::<Result<_, _>>()?,
+            },
+        };
+        Ok(behavioral)
+    }
+
     fn parse_device(
         &self,
         statement: &ScopedStmt,
@@ -994,7 +1668,8 @@ impl<'s> InstanceParser<'s> {
         let ident = cursor.expect(TokenKind::Ident)?;
 
         let input = self.source_map.get_content(ident.span.source_index);
-        let ident_string = token_text(input, ident).to_string();
+        let mut ident_string = token_text(input, ident).to_string();
+        consume_hierarchy(&mut cursor, input, &mut ident_string);
         // Identifiers can be UTF-8; don't use byte offsets.
         let first = ident_string
             .chars()
@@ -1024,6 +1699,9 @@ impl<'s> InstanceParser<'s> {
                 scope,
                 node_mapping,
             )?),
+            DeviceType::MutualInductance => devices
+                .mutual_inductances
+                .push(self.parse_mutual_inductance(name, &mut cursor, scope)?),
             DeviceType::Diode => {
                 devices
                     .diodes
@@ -1032,12 +1710,44 @@ impl<'s> InstanceParser<'s> {
             DeviceType::Bjt => devices
                 .bjts
                 .push(self.parse_bjt(name, &mut cursor, scope, node_mapping)?),
+            DeviceType::Mosfet => devices
+                .mosfets
+                .push(self.parse_mosfet(name, &mut cursor, scope, node_mapping)?),
+            DeviceType::Jfet => devices
+                .jfets
+                .push(self.parse_jfet(name, &mut cursor, scope, node_mapping)?),
             DeviceType::VoltageSource => devices.voltage_sources.push(
                 self.parse_independent_source(name, &mut cursor, scope, node_mapping, true)?,
             ),
             DeviceType::CurrentSource => devices.current_sources.push(
                 self.parse_independent_source(name, &mut cursor, scope, node_mapping, false)?,
             ),
+            DeviceType::BehavioralSource => devices
+                .behavioral_sources
+                .push(self.parse_behavioral(name, &mut cursor, scope, node_mapping)?),
+            DeviceType::TransmissionLine => devices
+                .transmission_lines
+                .push(self.parse_transmission_line(name, &mut cursor, scope, node_mapping)?),
+            DeviceType::Switch => devices.switches.push(self.parse_switch(
+                name,
+                &mut cursor,
+                scope,
+                node_mapping,
+                false,
+            )?),
+            DeviceType::CurrentSwitch => devices.switches.push(self.parse_switch(
+                name,
+                &mut cursor,
+                scope,
+                node_mapping,
+                true,
+            )?),
+            DeviceType::LookupTable => devices.lookup_tables.push(self.parse_lookup_table(
+                name,
+                &mut cursor,
+                scope,
+                node_mapping,
+            )?),
             _ => {
                 return Err(ParserError::InvalidDeviceType {
                     s: element_type.to_char().to_string(),
@@ -1061,12 +1771,26 @@ impl<'s> InstanceParser<'s> {
         let vstop = self.parse_value(cursor, scope)?;
         let vincr = self.parse_value(cursor, scope)?;
 
+        let src2 = match cursor.peek_non_whitespace() {
+            None => None,
+            Some(_) => {
+                let srcnam = parse_ident(cursor, input)?;
+                Some(DcSweep {
+                    srcnam: srcnam.text.to_string(),
+                    vstart: self.parse_value(cursor, scope)?,
+                    vstop: self.parse_value(cursor, scope)?,
+                    vincr: self.parse_value(cursor, scope)?,
+                })
+            }
+        };
+
         Ok(DcCommand {
             span: cursor.span,
             srcnam: srcnam.text.to_string(),
             vstart,
             vstop,
             vincr,
+            src2,
         })
     }
 
@@ -1077,6 +1801,9 @@ impl<'s> InstanceParser<'s> {
     ) -> Result<AcCommand, SpicyError> {
         let input = self.source_map.get_content(cursor.span.source_index);
         let ac_sweep_type = parse_ident(cursor, input)?;
+        if ac_sweep_type.text.eq_ignore_ascii_case("list") {
+            return self.parse_ac_list(cursor, scope);
+        }
         let points_per_sweep = self.parse_usize(cursor, scope)?;
         let ac_sweep_type = match ac_sweep_type.text {
             "DEC" | "dec" => AcSweepType::Dec(points_per_sweep),
@@ -1088,61 +1815,843 @@ impl<'s> InstanceParser<'s> {
                     span: cursor.span,
                 }
                 .into());
-            }
-        };
-        let fstart = self.parse_value(cursor, scope)?;
-        let fstop = self.parse_value(cursor, scope)?;
+            }
+        };
+        let fstart = self.parse_value(cursor, scope)?;
+        let fstop = self.parse_value(cursor, scope)?;
+        if fstop.get_value() < fstart.get_value() {
+            return Err(ParserError::InvalidParam {
+                param: format!("fstop {} (must not be below fstart)", fstop.get_value()),
+                span: cursor.span,
+            }
+            .into());
+        }
+
+        Ok(AcCommand {
+            span: cursor.span,
+            ac_sweep_type,
+            fstart,
+            fstop,
+        })
+    }
+
+    // list f1 f2 ...
+    fn parse_ac_list(
+        &self,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+    ) -> Result<AcCommand, SpicyError> {
+        let mut frequencies: Vec<Value> = Vec::new();
+        while let Some(t) = cursor.peek_non_whitespace() {
+            let frequency = self.parse_value(cursor, scope)?;
+            if let Some(last) = frequencies.last()
+                && frequency.get_value() <= last.get_value()
+            {
+                return Err(ParserError::InvalidParam {
+                    param: "AC list frequencies must increase".to_string(),
+                    span: t.span,
+                }
+                .into());
+            }
+            frequencies.push(frequency);
+        }
+        let (Some(fstart), Some(fstop)) = (frequencies.first(), frequencies.last()) else {
+            return Err(ParserError::MissingToken {
+                message: "expected a frequency for AC list",
+                span: Some(cursor.span),
+            }
+            .into());
+        };
+
+        Ok(AcCommand {
+            span: cursor.span,
+            fstart: fstart.clone(),
+            fstop: fstop.clone(),
+            ac_sweep_type: AcSweepType::List(frequencies),
+        })
+    }
+
+    fn parse_trans_command(
+        &self,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+    ) -> Result<TranCommand, SpicyError> {
+        let tstep = self.parse_value(cursor, scope)?;
+        let tstop = self.parse_value(cursor, scope)?;
+
+        // .tran tstep tstop [tstart [tmax]] [uic]
+        let mut tstart = None;
+        let mut tmax = None;
+        let mut uic = false;
+        while let Some(t) = cursor.peek_non_whitespace() {
+            if t.kind == TokenKind::Ident && !uic {
+                let input = self.source_map.get_content(t.span.source_index);
+                let ident = parse_ident(cursor, input)?;
+                if ident.text.to_uppercase() != "UIC" {
+                    return Err(ParserError::UnexpectedToken {
+                        expected: "UIC".to_string(),
+                        found: t.kind,
+                        span: t.span,
+                    }
+                    .into());
+                }
+                uic = true;
+            } else if uic || tmax.is_some() {
+                return Err(ParserError::TooManyParameters {
+                    index: 5,
+                    span: t.span,
+                }
+                .into());
+            } else if tstart.is_none() {
+                tstart = Some((self.parse_value(cursor, scope)?, t.span));
+            } else {
+                tmax = Some((self.parse_value(cursor, scope)?, t.span));
+            }
+        }
+
+        if let Some((value, span)) = &tstart {
+            let tstart = value.get_value();
+            if !(0.0..tstop.get_value()).contains(&tstart) {
+                return Err(ParserError::InvalidParam {
+                    param: format!("tstart {tstart} (must be in [0, tstop))"),
+                    span: *span,
+                }
+                .into());
+            }
+        }
+        if let Some((value, span)) = &tmax
+            && value.get_value() <= 0.0
+        {
+            return Err(ParserError::InvalidParam {
+                param: format!("tmax {} (must be positive)", value.get_value()),
+                span: *span,
+            }
+            .into());
+        }
+
+        Ok(TranCommand {
+            span: cursor.span,
+            tstep,
+            tstop,
+            tstart: tstart.map(|(value, _)| value),
+            tmax: tmax.map(|(value, _)| value),
+            uic,
+        })
+    }
+
+    // .print/.plot analysis v(node) i(device) ...
+    fn parse_output_command(
+        &self,
+        cursor: &mut StmtCursor,
+        kind: OutputKind,
+        scope: &Scope,
+    ) -> Result<OutputSpec, SpicyError> {
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let analysis = parse_ident(cursor, input)?;
+        let analysis = analysis
+            .text
+            .parse()
+            .map_err(|_| ParserError::InvalidOperation {
+                operation: analysis.text.to_string(),
+                span: analysis.span,
+            })?;
+
+        let mut vectors = Vec::new();
+        while cursor.peek_non_whitespace().is_some() {
+            vectors.push(self.parse_output_vector(cursor, scope)?);
+        }
+
+        Ok(OutputSpec {
+            span: cursor.span,
+            kind,
+            analysis,
+            vectors,
+        })
+    }
+
+    // .save v(node) i(device) ... or .save all
+    fn parse_save_command(
+        &self,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+    ) -> Result<SaveCommand, SpicyError> {
+        let mut all = false;
+        let mut vectors = Vec::new();
+        while cursor.peek_non_whitespace().is_some() {
+            if self.consume_keyword(cursor, &["all"])?.is_some() {
+                all = true;
+            } else {
+                vectors.push(self.parse_output_vector(cursor, scope)?);
+            }
+        }
+        Ok(SaveCommand {
+            span: cursor.span,
+            all,
+            vectors,
+        })
+    }
+
+    // v(node) or i(device)
+    fn parse_output_vector(
+        &self,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+    ) -> Result<OutputVector, SpicyError> {
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let function = parse_ident(cursor, input)?;
+        cursor.expect(TokenKind::LeftParen)?;
+        let vector = match function.text {
+            "V" | "v" => OutputVector::Voltage(self.parse_node(cursor, scope)?.0),
+            "I" | "i" => OutputVector::Current(parse_device_name(cursor, input)?),
+            _ => {
+                return Err(ParserError::InvalidOperation {
+                    operation: function.text.to_string(),
+                    span: function.span,
+                }
+                .into());
+            }
+        };
+        cursor.skip_ws();
+        cursor.expect(TokenKind::RightParen)?;
+        Ok(vector)
+    }
+
+    /// Consume the next word if it is one of `keywords` (in any case) and return it in upper
+    /// case.
+    fn consume_keyword(
+        &self,
+        cursor: &mut StmtCursor,
+        keywords: &[&str],
+    ) -> Result<Option<String>, SpicyError> {
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let found = cursor.peek_non_whitespace().and_then(|token| {
+            let text = token_text(input, token);
+            let is_keyword = token.kind == TokenKind::Ident
+                && keywords.iter().any(|k| k.eq_ignore_ascii_case(text));
+            is_keyword.then(|| text.to_ascii_uppercase())
+        });
+        if found.is_some() {
+            parse_ident(cursor, input)?;
+        }
+        Ok(found)
+    }
+
+    // KEYWORD=value
+    fn parse_keyword_value(
+        &self,
+        cursor: &mut StmtCursor,
+        keyword: &'static str,
+        scope: &Scope,
+    ) -> Result<Value, SpicyError> {
+        if self.consume_keyword(cursor, &[keyword])?.is_none() {
+            return Err(ParserError::MissingToken {
+                message: keyword,
+                span: Some(cursor.span),
+            }
+            .into());
+        }
+        cursor.expect_non_whitespace(TokenKind::Equal)?;
+        self.parse_value(cursor, scope)
+    }
+
+    // [RISE=n | FALL=n | CROSS=n]
+    fn parse_measure_edge(
+        &self,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+    ) -> Result<MeasureEdge, SpicyError> {
+        let Some(keyword) = self.consume_keyword(cursor, &["RISE", "FALL", "CROSS"])? else {
+            return Ok(MeasureEdge::Cross(1));
+        };
+        cursor.expect_non_whitespace(TokenKind::Equal)?;
+        cursor.skip_ws();
+        let n = self.parse_usize(cursor, scope)?;
+        Ok(match keyword.as_str() {
+            "RISE" => MeasureEdge::Rise(n),
+            "FALL" => MeasureEdge::Fall(n),
+            _ => MeasureEdge::Cross(n),
+        })
+    }
+
+    // AT=x, or v(out) VAL=level [edge] in a TRIG/TARG and v(out)=level [edge] in a WHEN
+    fn parse_measure_event(
+        &self,
+        cursor: &mut StmtCursor,
+        when: bool,
+        scope: &Scope,
+    ) -> Result<MeasureEvent, SpicyError> {
+        if self.consume_keyword(cursor, &["AT"])?.is_some() {
+            cursor.expect_non_whitespace(TokenKind::Equal)?;
+            return Ok(MeasureEvent::At(self.parse_value(cursor, scope)?));
+        }
+        let vector = self.parse_output_vector(cursor, scope)?;
+        let level = if when {
+            cursor.expect_non_whitespace(TokenKind::Equal)?;
+            self.parse_value(cursor, scope)?
+        } else {
+            self.parse_keyword_value(cursor, "VAL", scope)?
+        };
+        let edge = self.parse_measure_edge(cursor, scope)?;
+        Ok(MeasureEvent::Crossing {
+            vector,
+            level,
+            edge,
+        })
+    }
+
+    // .meas analysis name TRIG <event> TARG <event>
+    // .meas analysis name WHEN v(out)=level [RISE|FALL|CROSS=n]
+    // .meas analysis name FIND v(out) AT=x
+    // .meas analysis name AVG|RMS|MIN|MAX|PP|INTEG v(out) [FROM=x] [TO=x]
+    // .meas ac name UGF|PM|GM|BW v(out) [v(in)]
+    fn parse_measure_command(
+        &self,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+    ) -> Result<MeasureCommand, SpicyError> {
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let invalid = |ident: Ident| -> SpicyError {
+            ParserError::InvalidOperation {
+                operation: ident.text.to_string(),
+                span: ident.span,
+            }
+            .into()
+        };
+
+        let analysis_ident = parse_ident(cursor, input)?;
+        let analysis = match analysis_ident.text.parse() {
+            Ok(analysis) if analysis != AnalysisType::Op => analysis,
+            _ => return Err(invalid(analysis_ident)),
+        };
+        let name = parse_ident(cursor, input)?.text.to_string();
+
+        let keyword = parse_ident(cursor, input)?;
+        let kind = match keyword.text.to_ascii_uppercase().as_str() {
+            "TRIG" => {
+                let trig = self.parse_measure_event(cursor, false, scope)?;
+                let targ_keyword = parse_ident(cursor, input)?;
+                if !targ_keyword.text.eq_ignore_ascii_case("TARG") {
+                    return Err(invalid(targ_keyword));
+                }
+                let targ = self.parse_measure_event(cursor, false, scope)?;
+                MeasureKind::TrigTarg { trig, targ }
+            }
+            "WHEN" => MeasureKind::When(self.parse_measure_event(cursor, true, scope)?),
+            "FIND" => {
+                let vector = self.parse_output_vector(cursor, scope)?;
+                let at = self.parse_keyword_value(cursor, "AT", scope)?;
+                MeasureKind::FindAt { vector, at }
+            }
+            "UGF" | "PM" | "GM" | "BW" => {
+                if analysis != AnalysisType::Ac {
+                    return Err(invalid(keyword));
+                }
+                let metric = match keyword.text.to_ascii_uppercase().as_str() {
+                    "UGF" => ResponseMetric::UnityGainFrequency,
+                    "PM" => ResponseMetric::PhaseMargin,
+                    "GM" => ResponseMetric::GainMargin,
+                    _ => ResponseMetric::Bandwidth,
+                };
+                let output = self.parse_output_vector(cursor, scope)?;
+                let input = match cursor.peek_non_whitespace() {
+                    Some(_) => Some(self.parse_output_vector(cursor, scope)?),
+                    None => None,
+                };
+                MeasureKind::Response {
+                    metric,
+                    output,
+                    input,
+                }
+            }
+            function => {
+                let function = match function {
+                    "AVG" => MeasureFunction::Avg,
+                    "RMS" => MeasureFunction::Rms,
+                    "MIN" => MeasureFunction::Min,
+                    "MAX" => MeasureFunction::Max,
+                    "PP" => MeasureFunction::Pp,
+                    "INTEG" | "INTEGRAL" => MeasureFunction::Integ,
+                    _ => return Err(invalid(keyword)),
+                };
+                let vector = self.parse_output_vector(cursor, scope)?;
+                let (mut from, mut to) = (None, None);
+                while let Some(bound) = self.consume_keyword(cursor, &["FROM", "TO"])? {
+                    cursor.expect_non_whitespace(TokenKind::Equal)?;
+                    let value = Some(self.parse_value(cursor, scope)?);
+                    match bound.as_str() {
+                        "FROM" => from = value,
+                        _ => to = value,
+                    }
+                }
+                MeasureKind::Function {
+                    function,
+                    vector,
+                    from,
+                    to,
+                }
+            }
+        };
+        if let Some(token) = cursor.peek_non_whitespace() {
+            return Err(ParserError::UnexpectedToken {
+                expected: "end of .meas".to_string(),
+                found: token.kind,
+                span: token.span,
+            }
+            .into());
+        }
+
+        Ok(MeasureCommand {
+            span: cursor.span,
+            analysis,
+            name,
+            kind,
+        })
+    }
+
+    // .four freq v(out) i(device) ...
+    fn parse_fourier_command(
+        &self,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+    ) -> Result<FourierCommand, SpicyError> {
+        let fundamental = self.parse_value(cursor, scope)?;
+        let mut vectors = Vec::new();
+        while cursor.peek_non_whitespace().is_some() {
+            vectors.push(self.parse_output_vector(cursor, scope)?);
+        }
+        if vectors.is_empty() {
+            return Err(ParserError::MissingToken {
+                message: "output vector",
+                span: Some(cursor.span),
+            }
+            .into());
+        }
+
+        Ok(FourierCommand {
+            span: cursor.span,
+            fundamental,
+            vectors,
+        })
+    }
+
+    // .noise v(out[,ref]) src dec|oct|lin n fstart fstop
+    fn parse_noise_command(
+        &self,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+    ) -> Result<NoiseCommand, SpicyError> {
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let function = parse_ident(cursor, input)?;
+        if !function.text.eq_ignore_ascii_case("v") {
+            return Err(ParserError::InvalidOperation {
+                operation: function.text.to_string(),
+                span: function.span,
+            }
+            .into());
+        }
+        cursor.expect(TokenKind::LeftParen)?;
+        let output = self.parse_node(cursor, scope)?.0;
+        cursor.skip_ws();
+        let reference = match cursor.consume(TokenKind::Comma) {
+            Some(_) => {
+                cursor.skip_ws();
+                let reference = self.parse_node(cursor, scope)?.0;
+                cursor.skip_ws();
+                Some(reference)
+            }
+            None => None,
+        };
+        cursor.expect(TokenKind::RightParen)?;
+        let input_source = parse_ident(cursor, input)?.text.to_string();
+        let sweep = self.parse_ac_command(cursor, scope)?;
+
+        Ok(NoiseCommand {
+            span: cursor.span,
+            output,
+            reference,
+            input_source,
+            sweep,
+        })
+    }
+
+    // .sp <ac sweep> port1 [port2 ...] [z0=value]
+    fn parse_sp_command(
+        &self,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+    ) -> Result<SpCommand, SpicyError> {
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let sweep = self.parse_ac_command(cursor, scope)?;
+        let mut ports = Vec::new();
+        let mut z0 = None;
+        while cursor.peek_non_whitespace().is_some() {
+            let mark = cursor.checkpoint();
+            if let Ok(param) = parse_ident(cursor, input)
+                && cursor.peek_non_whitespace().map(|t| t.kind) == Some(TokenKind::Equal)
+            {
+                if !param.text.eq_ignore_ascii_case("z0") {
+                    return Err(ParserError::InvalidParam {
+                        param: param.text.to_string(),
+                        span: param.span,
+                    }
+                    .into());
+                }
+                cursor.expect_non_whitespace(TokenKind::Equal)?;
+                let value = self.parse_value(cursor, scope)?;
+                if value.get_value() <= 0.0 {
+                    return Err(ParserError::InvalidParam {
+                        param: format!("{} {} (must be positive)", param.text, value.get_value()),
+                        span: param.span,
+                    }
+                    .into());
+                }
+                z0 = Some(value);
+                continue;
+            }
+            cursor.rewind(mark);
+            ports.push(self.parse_node(cursor, scope)?.0);
+        }
+        if ports.is_empty() {
+            return Err(ParserError::MissingToken {
+                message: ".sp needs at least one port",
+                span: Some(cursor.span),
+            }
+            .into());
+        }
+
+        Ok(SpCommand {
+            span: cursor.span,
+            sweep,
+            ports,
+            z0: z0.unwrap_or_else(|| Value::new(50.0, None, None)),
+        })
+    }
+
+    // .step [lin|dec|oct] param name start stop incr|points
+    // .step param name list value ...
+    fn parse_step_command(
+        &self,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+    ) -> Result<StepCommand, SpicyError> {
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let invalid = |ident: Ident| -> SpicyError {
+            ParserError::InvalidOperation {
+                operation: ident.text.to_string(),
+                span: ident.span,
+            }
+            .into()
+        };
+
+        let mut keyword = parse_ident(cursor, input)?;
+        let sweep_type = match keyword.text.to_ascii_lowercase().as_str() {
+            "param" => None,
+            "lin" | "dec" | "oct" => {
+                let sweep_type = keyword.text.to_ascii_lowercase();
+                keyword = parse_ident(cursor, input)?;
+                Some(sweep_type)
+            }
+            _ => return Err(invalid(keyword)),
+        };
+        if !keyword.text.eq_ignore_ascii_case("param") {
+            return Err(invalid(keyword));
+        }
+        let param = parse_ident(cursor, input)?.text.to_string();
+        let global_params = &self
+            .expanded_deck
+            .scope_arena
+            .get(self.expanded_deck.global_params)
+            .param_map;
+        if global_params.get_param(&param).is_none() {
+            return Err(ParserError::UnknownParam { name: param }.into());
+        }
+
+        let is_list = cursor.peek_non_whitespace().is_some_and(|token| {
+            token.kind == TokenKind::Ident && token_text(input, token).eq_ignore_ascii_case("list")
+        });
+        let sweep = match sweep_type.as_deref() {
+            None if is_list => {
+                parse_ident(cursor, input)?;
+                let mut values = Vec::new();
+                while cursor.peek_non_whitespace().is_some() {
+                    values.push(self.parse_value(cursor, scope)?);
+                }
+                StepSweep::List(values)
+            }
+            None | Some("lin") => StepSweep::Linear {
+                start: self.parse_value(cursor, scope)?,
+                stop: self.parse_value(cursor, scope)?,
+                incr: self.parse_value(cursor, scope)?,
+            },
+            Some(sweep_type) => {
+                let fstart = self.parse_value(cursor, scope)?;
+                let fstop = self.parse_value(cursor, scope)?;
+                let points = self.parse_usize(cursor, scope)?;
+                StepSweep::Logarithmic(AcCommand {
+                    span: cursor.span,
+                    ac_sweep_type: if sweep_type == "dec" {
+                        AcSweepType::Dec(points)
+                    } else {
+                        AcSweepType::Oct(points)
+                    },
+                    fstart,
+                    fstop,
+                })
+            }
+        };
+
+        Ok(StepCommand {
+            span: cursor.span,
+            param,
+            sweep,
+        })
+    }
+
+    // .ic/.nodeset v(node)=value ...
+    fn parse_node_values(
+        &self,
+        cursor: &mut StmtCursor,
+        scope: &Scope,
+    ) -> Result<Vec<NodeValue>, SpicyError> {
+        let input = self.source_map.get_content(cursor.span.source_index);
+        let mut values = Vec::new();
+        while cursor.peek_non_whitespace().is_some() {
+            let function = parse_ident(cursor, input)?;
+            if !function.text.eq_ignore_ascii_case("v") {
+                return Err(ParserError::InvalidOperation {
+                    operation: function.text.to_string(),
+                    span: function.span,
+                }
+                .into());
+            }
+            cursor.expect(TokenKind::LeftParen)?;
+            let node = self.parse_node(cursor, scope)?.0;
+            cursor.skip_ws();
+            cursor.expect(TokenKind::RightParen)?;
+            cursor.skip_ws();
+            cursor.expect(TokenKind::Equal)?;
+            values.push(NodeValue {
+                span: cursor.span,
+                node,
+                value: self.parse_value(cursor, scope)?,
+            });
+        }
+        Ok(values)
+    }
+
+    /// Replace the node of every value by the deck's spelling, failing on unknown nodes.
+    fn resolve_node_values(
+        values: &mut [NodeValue],
+        node_mapping: &NodeMapping,
+    ) -> Result<(), SpicyError> {
+        let nodes = node_mapping.node_names_mna_order();
+        for value in values {
+            let Some(resolved) = nodes
+                .iter()
+                .find(|n| node_mapping.name_case().matches(n, &value.node))
+            else {
+                return Err(ParserError::UnknownNode {
+                    name: value.node.clone(),
+                    span: value.span,
+                }
+                .into());
+            };
+            value.node = resolved.clone();
+        }
+        Ok(())
+    }
+
+    /// Replace the output nodes of `noise` by the deck's spelling, failing on unknown nodes.
+    /// A ground reference is the same as none.
+    fn resolve_noise_nodes(
+        noise: &mut NoiseCommand,
+        node_mapping: &NodeMapping,
+    ) -> Result<(), SpicyError> {
+        if noise.reference.as_deref() == Some("0") {
+            noise.reference = None;
+        }
+        let nodes = node_mapping.node_names_mna_order();
+        for name in std::iter::once(&mut noise.output).chain(noise.reference.as_mut()) {
+            let Some(resolved) = nodes
+                .iter()
+                .find(|n| node_mapping.name_case().matches(n, name))
+            else {
+                return Err(ParserError::UnknownNode {
+                    name: name.clone(),
+                    span: noise.span,
+                }
+                .into());
+            };
+            *name = resolved.clone();
+        }
+        Ok(())
+    }
+
+    /// Replace the ports of `sp` by their spelling in the deck. Ground is not a port: every
+    /// port is referenced to it.
+    fn resolve_sp_ports(sp: &mut SpCommand, node_mapping: &NodeMapping) -> Result<(), SpicyError> {
+        let nodes = node_mapping.node_names_mna_order();
+        for port in &mut sp.ports {
+            let Some(resolved) = nodes
+                .iter()
+                .find(|n| node_mapping.name_case().matches(n, port))
+            else {
+                return Err(ParserError::UnknownNode {
+                    name: port.clone(),
+                    span: sp.span,
+                }
+                .into());
+            };
+            *port = resolved.clone();
+        }
+        Ok(())
+    }
 
-        Ok(AcCommand {
-            span: cursor.span,
-            ac_sweep_type,
-            fstart,
-            fstop,
-        })
+    /// Replace the swept sources of `dc` by their spelling on the source lines. Names that are
+    /// not a V or I source are left for the simulator to reject.
+    fn resolve_dc_sources(dc: &mut DcCommand, devices: &Devices, name_case: NameCase) {
+        let sources = devices
+            .voltage_sources
+            .iter()
+            .chain(&devices.current_sources);
+        for name in std::iter::once(&mut dc.srcnam).chain(dc.src2.as_mut().map(|s| &mut s.srcnam)) {
+            if let Some(source) = sources.clone().find(|s| name_case.matches(&s.name, name)) {
+                *name = source.name.clone();
+            }
+        }
     }
 
-    fn parse_trans_command(
-        &self,
-        cursor: &mut StmtCursor,
-        scope: &Scope,
-    ) -> Result<TranCommand, SpicyError> {
-        let tstep = self.parse_value(cursor, scope)?;
-        let tstop = self.parse_value(cursor, scope)?;
+    /// Replace every name of `spec` by the deck's spelling, failing on unknown nodes and on
+    /// devices without a branch current.
+    /// Check that the inductors of a `K` statement exist, taking their names as written on
+    /// the inductors.
+    fn resolve_coupled_inductors(
+        coupling: &mut MutualInductanceSpec,
+        inductors: &[InductorSpec],
+        name_case: NameCase,
+    ) -> Result<(), SpicyError> {
+        for name in [&mut coupling.inductor1, &mut coupling.inductor2] {
+            let Some(inductor) = inductors.iter().find(|l| name_case.matches(&l.name, name)) else {
+                return Err(ParserError::UnknownInductor {
+                    name: name.clone(),
+                    span: coupling.span,
+                }
+                .into());
+            };
+            *name = inductor.name.clone();
+        }
+        Ok(())
+    }
 
-        #[allow(unused_assignments)]
-        let mut uic = false;
-        match cursor.peek_non_whitespace() {
-            // .tran tstep tstop
-            None => {}
-            Some(t) if t.kind == TokenKind::Ident => {
-                let input = self.source_map.get_content(t.span.source_index);
-                let ident = parse_ident(cursor, input)?;
-                if ident.text.to_uppercase() == "UIC" {
-                    uic = true;
-                } else {
-                    return Err(ParserError::UnexpectedToken {
-                        expected: "UIC".to_string(),
-                        found: t.kind,
-                        span: t.span,
+    /// Resolve the `I(device)` of a B source, which may be defined further down the deck.
+    fn resolve_behavioral_currents(
+        source: &mut BehavioralSourceSpec,
+        node_mapping: &NodeMapping,
+    ) -> Result<(), SpicyError> {
+        let mut result = Ok(());
+        source
+            .expr
+            .currents_mut(&mut |name, branch| match node_mapping.get_branch(name) {
+                Some(index) => *branch = index,
+                None if result.is_ok() => {
+                    result = Err(ParserError::UnknownBranch {
+                        name: name.to_string(),
+                        span: source.span,
                     }
                     .into());
                 }
+                None => {}
+            });
+        result
+    }
+
+    fn resolve_switch_control(
+        switch: &mut SwitchSpec,
+        node_mapping: &NodeMapping,
+    ) -> Result<(), SpicyError> {
+        if let SwitchControl::Current { name, branch, .. } = &mut switch.control {
+            *branch = node_mapping
+                .get_branch(name)
+                .ok_or_else(|| ParserError::UnknownBranch {
+                    name: name.clone(),
+                    span: switch.span,
+                })?;
+        }
+        Ok(())
+    }
+
+    fn resolve_output_names(
+        spec: &mut OutputSpec,
+        node_mapping: &NodeMapping,
+    ) -> Result<(), SpicyError> {
+        for vector in &mut spec.vectors {
+            Self::resolve_output_vector(vector, spec.span, node_mapping)?;
+        }
+        Ok(())
+    }
+
+    /// Replace the node or device of `vector` by the deck's spelling, failing on unknown ones.
+    fn resolve_output_vector(
+        vector: &mut OutputVector,
+        span: Span,
+        node_mapping: &NodeMapping,
+    ) -> Result<(), SpicyError> {
+        let (name, known) = match vector {
+            OutputVector::Voltage(name) => (name, node_mapping.node_names_mna_order()),
+            OutputVector::Current(name) => (name, node_mapping.branch_names_mna_order()),
+        };
+        let Some(resolved) = known
+            .iter()
+            .find(|k| node_mapping.name_case().matches(k, name))
+        else {
+            return Err(ParserError::UnknownOutputVector {
+                name: name.clone(),
+                span,
             }
-            // TODO: .tran tstep tstop tstart [tmax] ... (not yet supported)
-            Some(_) => {
-                unimplemented!("tstart and tmax are not yet implemented");
+            .into());
+        };
+        *name = resolved.clone();
+        Ok(())
+    }
+
+    /// Resolve every vector a `.meas` refers to.
+    fn resolve_measure_names(
+        measure: &mut MeasureCommand,
+        node_mapping: &NodeMapping,
+    ) -> Result<(), SpicyError> {
+        let span = measure.span;
+        let resolve_event = |event: &mut MeasureEvent| match event {
+            MeasureEvent::At(_) => Ok(()),
+            MeasureEvent::Crossing { vector, .. } => {
+                Self::resolve_output_vector(vector, span, node_mapping)
+            }
+        };
+        match &mut measure.kind {
+            MeasureKind::TrigTarg { trig, targ } => {
+                resolve_event(trig)?;
+                resolve_event(targ)
+            }
+            MeasureKind::When(event) => resolve_event(event),
+            MeasureKind::FindAt { vector, .. } | MeasureKind::Function { vector, .. } => {
+                Self::resolve_output_vector(vector, span, node_mapping)
+            }
+            MeasureKind::Response { output, input, .. } => {
+                Self::resolve_output_vector(output, span, node_mapping)?;
+                match input {
+                    Some(input) => Self::resolve_output_vector(input, span, node_mapping),
+                    None => Ok(()),
+                }
             }
         }
-
-        Ok(TranCommand {
-            span: cursor.span,
-            tstep,
-            tstop,
-            uic,
-        })
     }
 
-    fn parse_command(&self, statement: &ScopedStmt) -> Result<Command, SpicyError> {
+    /// Parse a dot command. `.print`/`.plot`/`.ic`/`.nodeset`/`.step`/`.temp`/`.meas`/`.four`
+    /// are collected into `cards` and give `None`.
+    fn parse_command(
+        &self,
+        statement: &ScopedStmt,
+        cards: &mut ControlCards,
+    ) -> Result<Option<Command>, SpicyError> {
         let mut cursor = statement.stmt.as_cursor();
         cursor.expect(TokenKind::Dot)?;
         let ident = cursor.expect(TokenKind::Ident)?;
@@ -1163,7 +2672,70 @@ impl<'s> InstanceParser<'s> {
             CommandType::Op => Command::Op(OpCommand { span: cursor.span }),
             CommandType::AC => Command::Ac(self.parse_ac_command(&mut cursor, scope)?),
             CommandType::Tran => Command::Tran(self.parse_trans_command(&mut cursor, scope)?),
+            CommandType::Noise => Command::Noise(self.parse_noise_command(&mut cursor, scope)?),
+            CommandType::Sp => Command::Sp(self.parse_sp_command(&mut cursor, scope)?),
             CommandType::End => Command::End,
+            CommandType::Print | CommandType::Plot => {
+                let kind = if command_type == CommandType::Print {
+                    OutputKind::Print
+                } else {
+                    OutputKind::Plot
+                };
+                let spec = self.parse_output_command(&mut cursor, kind, scope)?;
+                cards.outputs.push(spec);
+                return Ok(None);
+            }
+            CommandType::Ic => {
+                let values = self.parse_node_values(&mut cursor, scope)?;
+                cards.initial_conditions.extend(values);
+                return Ok(None);
+            }
+            CommandType::Nodeset => {
+                let values = self.parse_node_values(&mut cursor, scope)?;
+                cards.nodesets.extend(values);
+                return Ok(None);
+            }
+            CommandType::Step => {
+                let step = self.parse_step_command(&mut cursor, scope)?;
+                cards.steps.push(step);
+                return Ok(None);
+            }
+            CommandType::Meas => {
+                let measure = self.parse_measure_command(&mut cursor, scope)?;
+                cards.measures.push(measure);
+                return Ok(None);
+            }
+            CommandType::Four => {
+                let fourier = self.parse_fourier_command(&mut cursor, scope)?;
+                cards.fourier.push(fourier);
+                return Ok(None);
+            }
+            CommandType::Save => {
+                let save = self.parse_save_command(&mut cursor, scope)?;
+                cards.saves.push(save);
+                return Ok(None);
+            }
+            // .temp value ...
+            CommandType::Temp => {
+                while cursor.peek_non_whitespace().is_some() {
+                    let temperature = self.parse_value(&mut cursor, scope)?;
+                    cards.temperatures.push(temperature);
+                }
+                return Ok(None);
+            }
+            // .options name[=value] ..., the ones spicy does not know are ignored
+            CommandType::Options => {
+                let input = self.source_map.get_content(cursor.span.source_index);
+                for option in cursor.split_on_whitespace() {
+                    let Some(name) = option.peek().filter(|t| t.kind == TokenKind::Ident) else {
+                        continue;
+                    };
+                    if token_text(input, name).eq_ignore_ascii_case("savecurrents") {
+                        cards.options.save_currents = true;
+                    }
+                }
+                return Ok(None);
+            }
             _ => {
                 return Err(ParserError::UnexpectedCommandType {
                     s: command_type.to_string(),
@@ -1173,63 +2745,150 @@ impl<'s> InstanceParser<'s> {
             }
         };
 
-        Ok(command)
+        Ok(Some(command))
+    }
+
+    /// Resolve each of `items`: an item that fails fails the parse or, when recovering, is
+    /// recorded and dropped.
+    fn resolve_all<T>(
+        items: &mut Vec<T>,
+        recovered: &mut Recovered,
+        mut resolve: impl FnMut(&mut T) -> Result<(), SpicyError>,
+    ) -> Result<(), SpicyError> {
+        let mut failed = Vec::new();
+        items.retain_mut(|item| match resolve(item) {
+            Ok(()) => true,
+            Err(error) => {
+                failed.push(error);
+                false
+            }
+        });
+        for error in failed {
+            recover(recovered, error)?;
+        }
+        Ok(())
     }
 
-    pub(crate) fn parse(&mut self) -> Result<Deck, SpicyError> {
-        // TODO: clone is sadge
-        let mut statements_iter = self.expanded_deck.statements.clone().into_iter();
+    /// Parse the statements into a deck. When recovering, a statement or card with an error
+    /// is recorded in `recovered` and left out of the deck.
+    pub(crate) fn parse(&mut self, mut recovered: Recovered) -> Result<Deck, SpicyError> {
+        let statements = std::mem::take(&mut self.expanded_deck.statements);
+        let mut statements_iter = statements.into_iter();
         // first line should be a title
         let title = self.parse_title(&statements_iter.next().ok_or(ParserError::MissingTitle)?);
 
         let mut commands = vec![];
+        let mut cards = ControlCards::default();
         let mut devices = Devices::new();
-        let mut node_mapping = NodeMapping::new();
+        let mut node_mapping = NodeMapping::with_name_case(self.name_case);
 
         for statement in statements_iter {
             let cursor = statement.stmt.as_cursor();
 
-            let first_token = cursor.peek().ok_or(ParserError::MissingToken {
-                message: "token",
-                span: Some(cursor.span),
-            })?;
+            let Some(first_token) = cursor.peek() else {
+                let error = ParserError::MissingToken {
+                    message: "token",
+                    span: Some(cursor.span),
+                };
+                recover(&mut recovered, error.into())?;
+                continue;
+            };
 
             match first_token.kind {
-                TokenKind::Dot => {
-                    match self.parse_command(&statement)? {
-                        Command::End => {
-                            // once we see an end command we stop
-                            break;
-                        }
-                        command => {
-                            commands.push(command);
-                        }
+                TokenKind::Dot => match self.parse_command(&statement, &mut cards) {
+                    Ok(Some(Command::End)) => {
+                        // once we see an end command we stop
+                        break;
                     }
-                }
+                    Ok(Some(command)) => {
+                        commands.push(command);
+                    }
+                    Ok(None) => {}
+                    Err(error) => recover(&mut recovered, error)?,
+                },
                 // comment
                 TokenKind::Asterisk => {
                     let _ = self.parse_comment(&statement);
                     // TODO: save comments?
                 }
                 TokenKind::Ident => {
-                    self.parse_device(&statement, &mut node_mapping, &mut devices)?;
+                    if let Err(error) =
+                        self.parse_device(&statement, &mut node_mapping, &mut devices)
+                    {
+                        recover(&mut recovered, error)?;
+                    }
                 }
                 _ => {
-                    return Err(ParserError::UnexpectedToken {
+                    let error = ParserError::UnexpectedToken {
                         expected: "command or element".to_string(),
                         found: first_token.kind,
                         span: first_token.span,
-                    }
-                    .into());
+                    };
+                    recover(&mut recovered, error.into())?;
                 }
             }
         }
 
+        // cards may name nodes that appear further down the deck
+        let recovered = &mut recovered;
+        Self::resolve_all(&mut cards.outputs, recovered, |spec| {
+            Self::resolve_output_names(spec, &node_mapping)
+        })?;
+        Self::resolve_all(&mut cards.measures, recovered, |measure| {
+            Self::resolve_measure_names(measure, &node_mapping)
+        })?;
+        Self::resolve_all(&mut cards.fourier, recovered, |fourier| {
+            for vector in &mut fourier.vectors {
+                Self::resolve_output_vector(vector, fourier.span, &node_mapping)?;
+            }
+            Ok(())
+        })?;
+        Self::resolve_all(&mut cards.saves, recovered, |save| {
+            for vector in &mut save.vectors {
+                Self::resolve_output_vector(vector, save.span, &node_mapping)?;
+            }
+            Ok(())
+        })?;
+        for values in [&mut cards.initial_conditions, &mut cards.nodesets] {
+            Self::resolve_all(values, recovered, |value| {
+                Self::resolve_node_values(std::slice::from_mut(value), &node_mapping)
+            })?;
+        }
+        Self::resolve_all(&mut commands, recovered, |command| match command {
+            Command::Noise(noise) => Self::resolve_noise_nodes(noise, &node_mapping),
+            Command::Sp(sp) => Self::resolve_sp_ports(sp, &node_mapping),
+            Command::Dc(dc) => {
+                Self::resolve_dc_sources(dc, &devices, self.name_case);
+                Ok(())
+            }
+            _ => Ok(()),
+        })?;
+        Self::resolve_all(&mut devices.behavioral_sources, recovered, |source| {
+            Self::resolve_behavioral_currents(source, &node_mapping)
+        })?;
+        Self::resolve_all(&mut devices.mutual_inductances, recovered, |coupling| {
+            Self::resolve_coupled_inductors(coupling, &devices.inductors, self.name_case)
+        })?;
+        Self::resolve_all(&mut devices.switches, recovered, |switch| {
+            Self::resolve_switch_control(switch, &node_mapping)
+        })?;
+
         Ok(Deck {
             title,
             node_mapping,
             commands,
+            outputs: cards.outputs,
+            initial_conditions: cards.initial_conditions,
+            nodesets: cards.nodesets,
+            steps: cards.steps,
+            temperatures: cards.temperatures,
+            measures: cards.measures,
+            fourier: cards.fourier,
+            options: cards.options,
+            saves: cards.saves,
             devices,
+            models: std::mem::take(&mut self.expanded_deck.model_table),
+            expansion_stats: self.expanded_deck.stats,
         })
     }
 }
@@ -1241,7 +2900,8 @@ mod tests {
     use super::{ParamParser, ParamSlot, ParsedParam};
     use crate::{
         ParseOptions,
-        error::{ParserError, SpicyError},
+        error::{ExpressionError, IncludeError, ParserError, SpicyError},
+        lexer::TokenKind,
         libs_phase::{SourceFileId, SourceMap},
         parser_utils::{parse_ident, parse_value},
         statement_phase::Statements,
@@ -1260,6 +2920,9 @@ mod tests {
             source_path: PathBuf::from("."),
             source_map,
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let deck = parse(&mut input_options).expect("parse");
 
@@ -1574,4 +3237,379 @@ mod tests {
         assert_eq!(name, "off");
         assert!(params.next().is_none());
     }
+
+    #[test]
+    fn test_param_parser_flag_skips_every_value_before_it() {
+        let input = "off\n";
+        let statements =
+            Statements::new(input, SourceFileId::new(0)).expect("non-empty statement");
+        let cursor = statements.statements[0].as_cursor();
+        let params_order = vec![
+            ParamSlot::other("area"),
+            ParamSlot::other("m"),
+            ParamSlot::flag("off"),
+        ];
+        let mut params = ParamParser::new(input, params_order, &cursor);
+
+        let ParsedParam { name, cursor: _ } = params.next().expect("off").expect("off ok");
+        assert_eq!(name, "off");
+        assert!(params.next().is_none());
+    }
+
+    fn parse_err(netlist: &str) -> ParserError {
+        let mut options = ParseOptions::new_with_source("models.spicy", netlist.to_string());
+        match crate::parse(&mut options) {
+            Err(SpicyError::Parser(err)) => err,
+            other => panic!("expected a parser error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn unknown_model_is_an_error() {
+        let err = parse_err("models\nC1 a 0 1p nosuch\n.end\n");
+        assert!(matches!(&err, ParserError::MissingModel { model, .. } if model == "nosuch"));
+    }
+
+    #[test]
+    fn model_of_another_device_is_an_error() {
+        let err = parse_err("models\n.model dmod D is=1e-14\nC1 a 0 1p dmod\n.end\n");
+        assert_eq!(
+            err.to_string(),
+            "model dmod is a diode model, expected a capacitor model"
+        );
+    }
+
+    #[test]
+    fn print_of_unknown_vector_is_an_error() {
+        let err = parse_err("print\nV1 a 0 1\nR1 a 0 1k\n.print op v(b)\n.end\n");
+        assert_eq!(
+            err.to_string(),
+            "output vector 'b' is not a node or a device with a branch current"
+        );
+
+        // resistors have no branch current to print
+        let err = parse_err("print\nV1 a 0 1\nR1 a 0 1k\n.print op i(r1)\n.end\n");
+        assert!(matches!(&err, ParserError::UnknownOutputVector { name, .. } if name == "r1"));
+    }
+
+    #[test]
+    fn measure_errors() {
+        let netlist = |meas: &str| format!("meas\nV1 a 0 1\nR1 a 0 1k\n{meas}\n.end\n");
+
+        let err = parse_err(&netlist(".meas tran x WHEN v(b)=1"));
+        assert!(matches!(&err, ParserError::UnknownOutputVector { name, .. } if name == "b"));
+
+        let err = parse_err(&netlist(".meas op x AVG v(a)"));
+        assert!(
+            matches!(&err, ParserError::InvalidOperation { operation, .. } if operation == "op")
+        );
+
+        let err = parse_err(&netlist(".meas tran x TRIG v(a) VAL=1 TARG v(a)"));
+        assert!(matches!(
+            &err,
+            ParserError::MissingToken { message: "VAL", .. }
+        ));
+
+        let err = parse_err(&netlist(".meas tran x MEDIAN v(a)"));
+        assert!(matches!(&err, ParserError::InvalidOperation { .. }));
+
+        let err = parse_err(&netlist(".meas tran x FIND v(a) AT=1 v(a)"));
+        assert!(matches!(&err, ParserError::UnexpectedToken { .. }));
+
+        // only an AC sweep has a frequency response
+        let err = parse_err(&netlist(".meas tran x PM v(a)"));
+        assert!(
+            matches!(&err, ParserError::InvalidOperation { operation, .. } if operation == "PM")
+        );
+    }
+
+    #[test]
+    fn sp_errors() {
+        let netlist = |sp: &str| format!("sp\nR1 a b 50\nR2 b 0 50\n{sp}\n.end\n");
+
+        let err = parse_err(&netlist(".sp dec 10 1k 1Meg"));
+        assert!(matches!(
+            &err,
+            ParserError::MissingToken {
+                message: ".sp needs at least one port",
+                ..
+            }
+        ));
+
+        // every port is referenced to ground
+        let err = parse_err(&netlist(".sp dec 10 1k 1Meg a 0"));
+        assert!(matches!(&err, ParserError::UnknownNode { name, .. } if name == "0"));
+
+        let err = parse_err(&netlist(".sp dec 10 1k 1Meg a b r0=50"));
+        assert!(matches!(&err, ParserError::InvalidParam { param, .. } if param == "r0"));
+
+        let err = parse_err(&netlist(".sp dec 10 1k 1Meg a z0=0"));
+        assert!(matches!(&err, ParserError::InvalidParam { .. }));
+    }
+
+    #[test]
+    fn fourier_errors() {
+        let netlist = |four: &str| format!("four\nV1 a 0 1\nR1 a 0 1k\n{four}\n.end\n");
+
+        let err = parse_err(&netlist(".four 1k"));
+        assert!(matches!(
+            &err,
+            ParserError::MissingToken {
+                message: "output vector",
+                ..
+            }
+        ));
+
+        let err = parse_err(&netlist(".four 1k v(b)"));
+        assert!(matches!(&err, ParserError::UnknownOutputVector { name, .. } if name == "b"));
+    }
+
+    #[test]
+    fn save_and_probe_name_the_kept_vectors() {
+        use crate::netlist_types::OutputVector::{Current, Voltage};
+
+        let mut options = ParseOptions::new_with_source(
+            "save.spicy",
+            "save\nV1 a 0 1\nR1 a out 1k\n.save V(OUT) i(v1)\n.probe all\nC1 out 0 1u\n.end\n"
+                .to_string(),
+        );
+        let deck = crate::parse(&mut options).expect("parse");
+        let [save, probe] = deck.saves.as_slice() else {
+            panic!("{:?}", deck.saves);
+        };
+        assert!(!save.all);
+        assert!(matches!(
+            save.vectors.as_slice(),
+            [Voltage(out), Current(v1)] if out == "out" && v1 == "V1"
+        ));
+        assert!(probe.all && probe.vectors.is_empty());
+
+        let err = parse_err("save\nV1 a 0 1\nR1 a 0 1k\n.save v(b)\n.end\n");
+        assert!(matches!(&err, ParserError::UnknownOutputVector { name, .. } if name == "b"));
+    }
+
+    #[test]
+    fn initial_condition_of_unknown_node_is_an_error() {
+        let err = parse_err("ic\nV1 a 0 1\nR1 a 0 1k\n.ic v(b)=1\n.end\n");
+        assert_eq!(err.to_string(), "unknown node 'b'");
+
+        let err = parse_err("ic\nV1 a 0 1\nR1 a 0 1k\n.nodeset i(v1)=1\n.end\n");
+        assert!(
+            matches!(&err, ParserError::InvalidOperation { operation, .. } if operation == "i")
+        );
+    }
+
+    #[test]
+    fn behavioral_source_errors() {
+        let err = parse_err("b\nV1 a 0 1\nB1 b 0 V=2*I(R1)\nR1 a b 1k\n.end\n");
+        assert_eq!(
+            err.to_string(),
+            "'R1' is not a device with a branch current"
+        );
+
+        let err = parse_err("b\nV1 a 0 1\nB1 b 0 Q=V(a)\n.end\n");
+        assert!(matches!(&err, ParserError::InvalidParam { param, .. } if param == "Q"));
+
+        let err = parse_err("b\nV1 a 0 1\nB1 b 0 V=V(2*a)\n.end\n");
+        assert!(matches!(&err, ParserError::ExpectedIdent { .. }));
+    }
+
+    #[test]
+    fn mutual_inductance_errors() {
+        let err = parse_err("k\nL1 a 0 1m\nR1 b 0 1k\nK1 L1 R1 0.5\n.end\n");
+        assert_eq!(err.to_string(), "'R1' is not an inductor");
+
+        let err = parse_err("k\nL1 a 0 1m\nL2 b 0 1m\nK1 L1 L2 1.5\n.end\n");
+        assert!(matches!(&err, ParserError::InvalidParam { .. }));
+    }
+
+    #[test]
+    fn transmission_line_errors() {
+        let err = parse_err("t\nT1 a 0 b 0 Z0=50\n.end\n");
+        assert!(matches!(&err, ParserError::MissingToken { .. }));
+
+        let err = parse_err("t\nT1 a 0 b 0 Z0=50 TD=0\n.end\n");
+        assert!(matches!(&err, ParserError::InvalidParam { .. }));
+
+        let err = parse_err("t\nT1 a 0 b 0 Z0=50 TD=1n F=1g\n.end\n");
+        assert!(matches!(&err, ParserError::InvalidParam { param, .. } if param == "F"));
+    }
+
+    #[test]
+    fn switch_errors() {
+        let err = parse_err("s\n.model sw1 SW vt=1\nW1 a 0 Vx sw1\nVx b 0 0\n.end\n");
+        assert!(matches!(
+            &err,
+            ParserError::InvalidModel {
+                expected: "current switch",
+                ..
+            }
+        ));
+
+        let err = parse_err("s\n.model csw1 CSW it=1m\nW1 a 0 Vx csw1\n.end\n");
+        assert_eq!(
+            err.to_string(),
+            "'Vx' is not a device with a branch current"
+        );
+
+        let err = parse_err("s\n.model sw1 SW vt=1\nS1 a 0 c 0 sw1 closed\n.end\n");
+        assert!(matches!(&err, ParserError::InvalidParam { param, .. } if param == "closed"));
+    }
+
+    #[test]
+    fn expression_functions_evaluate_in_device_values() {
+        let netlist = "functions\n.param x=4\n\
+            R1 a 0 {sqrt(x) + EXP(0)}\n\
+            R2 a 0 {if(x < 1, undefined, pow(x, 2))}\n\
+            R3 a 0 {2 max(x, 1) - log(1)}\n.end\n";
+        let mut options = ParseOptions::new_with_source("functions.spicy", netlist.to_string());
+        let deck = crate::parse(&mut options).expect("parse");
+        let values: Vec<f64> = deck
+            .devices
+            .resistors
+            .iter()
+            .map(|r| r.resistance.as_ref().expect("resistance").get_value())
+            .collect();
+        // if() only evaluates the branch it takes
+        assert_eq!(values, vec![3.0, 16.0, 8.0]);
+    }
+
+    #[test]
+    fn bad_expression_function_calls_are_errors() {
+        let expression_err = |netlist: &str| {
+            let mut options = ParseOptions::new_with_source("functions.spicy", netlist.to_string());
+            match crate::parse(&mut options) {
+                Err(SpicyError::Expression(err)) => err,
+                other => panic!("expected an expression error, got {other:?}"),
+            }
+        };
+
+        let err = expression_err("functions\nR1 a 0 {sinh(1)}\n.end\n");
+        assert!(matches!(&err, ExpressionError::UnknownFunction { name, .. } if name == "sinh"));
+
+        let err = expression_err("functions\nR1 a 0 {max(1)}\n.end\n");
+        assert_eq!(err.to_string(), "function 'max' takes 2 arguments, found 1");
+
+        let err = expression_err("functions\nR1 a 0 {sqrt(4))}\n.end\n");
+        assert!(matches!(
+            &err,
+            ExpressionError::UnexpectedToken {
+                found: TokenKind::RightParen,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn cyclic_params_are_an_error() {
+        let netlist = "cycle\n.param a={b + 1} b={2 * a}\nR1 x 0 {a}\n.end\n";
+        let mut options = ParseOptions::new_with_source("cycle.spicy", netlist.to_string());
+        let err = crate::parse(&mut options).expect_err("cycle");
+        assert_eq!(err.to_string(), "params depend on each other: a -> b -> a");
+
+        // a subcircuit param can't refer to itself either, even with a global of that name
+        let netlist = "cycle\n.param w=1\n.subckt S p\n.param w={w * 2}\nR1 p 0 {w}\n.ends\n\
+            X1 in S\n.end\n";
+        let mut options = ParseOptions::new_with_source("cycle.spicy", netlist.to_string());
+        let err = crate::parse(&mut options).expect_err("self reference");
+        assert_eq!(err.to_string(), "params depend on each other: w -> w");
+    }
+
+    #[test]
+    fn node_after_subckt_params_is_an_error() {
+        let err = parse_err("params\n.subckt S a params: r=1 b\nR1 a 0 {r}\n.ends\nX1 n S\n.end\n");
+        assert!(matches!(&err, ParserError::MissingToken { .. }));
+    }
+
+    #[test]
+    fn step_of_unknown_param_is_an_error() {
+        let err = parse_err("step\n.param r=1k\nR1 a 0 {r}\n.step param c 1 2 1\n.end\n");
+        assert!(matches!(&err, ParserError::UnknownParam { name } if name == "c"));
+
+        let err = parse_err("step\n.param r=1k\nR1 a 0 {r}\n.step r 1 2 1\n.end\n");
+        assert!(
+            matches!(&err, ParserError::InvalidOperation { operation, .. } if operation == "r")
+        );
+    }
+
+    #[test]
+    fn tran_window_is_checked() {
+        let err = parse_err("tran\nR1 a 0 1k\n.tran 1u 1m 1m\n.end\n");
+        assert!(
+            matches!(&err, ParserError::InvalidParam { param, .. } if param.starts_with("tstart"))
+        );
+
+        let err = parse_err("tran\nR1 a 0 1k\n.tran 1u 1m 0 0\n.end\n");
+        assert!(
+            matches!(&err, ParserError::InvalidParam { param, .. } if param.starts_with("tmax"))
+        );
+
+        let err = parse_err("tran\nR1 a 0 1k\n.tran 1u 1m uic 0\n.end\n");
+        assert!(matches!(&err, ParserError::TooManyParameters { .. }));
+    }
+
+    #[test]
+    fn ac_sweep_is_checked() {
+        let err = parse_err("ac\nR1 a 0 1k\n.ac list 1k 10 100\n.end\n");
+        assert!(matches!(&err, ParserError::InvalidParam { .. }));
+
+        let err = parse_err("ac\nR1 a 0 1k\n.ac list\n.end\n");
+        assert!(matches!(&err, ParserError::MissingToken { .. }));
+
+        let err = parse_err("ac\nR1 a 0 1k\n.ac lin 10 1k 10\n.end\n");
+        assert!(matches!(&err, ParserError::InvalidParam { .. }));
+    }
+
+    #[test]
+    fn recovery_collects_every_bad_statement() {
+        let netlist = "recover\nV1 in 0 1\nR1 in out 1k\nC1 out 0 1p nosuch\nR2 out 0 {1 +}\n\
+            .print op v(nowhere)\n.op\n.end\n";
+        let mut options = ParseOptions::new_with_source("recover.spicy", netlist.to_string());
+        let (deck, errors) = crate::parse_recovering(&mut options);
+        let deck = deck.expect("best-effort deck");
+        let resistors = &deck.devices.resistors;
+        assert_eq!(resistors.len(), 1);
+        assert_eq!(resistors[0].name, "R1");
+        assert!(deck.devices.capacitors.is_empty());
+        assert!(deck.outputs.is_empty());
+        assert_eq!(deck.commands.len(), 1);
+
+        // in the order of the phases: expressions, statements, then the cards
+        assert_eq!(errors.len(), 3, "{errors:?}");
+        assert!(matches!(errors[0], SpicyError::Expression(_)));
+        assert!(matches!(
+            &errors[1],
+            SpicyError::Parser(ParserError::MissingModel { model, .. }) if model == "nosuch"
+        ));
+        assert!(matches!(
+            &errors[2],
+            SpicyError::Parser(ParserError::UnknownOutputVector { name, .. }) if name == "nowhere"
+        ));
+
+        // without recovery the first of them fails the parse
+        let mut options = ParseOptions::new_with_source("recover.spicy", netlist.to_string());
+        let err = crate::parse(&mut options).expect_err("first error");
+        assert!(matches!(err, SpicyError::Expression(_)));
+    }
+
+    #[test]
+    fn recovery_of_a_clean_netlist_has_no_errors() {
+        let netlist = "clean\nV1 in 0 1\nR1 in 0 1k\n.op\n.end\n";
+        let mut options = ParseOptions::new_with_source("clean.spicy", netlist.to_string());
+        let (deck, errors) = crate::parse_recovering(&mut options);
+        assert!(errors.is_empty());
+        assert_eq!(deck.expect("deck").devices.resistors.len(), 1);
+    }
+
+    #[test]
+    fn missing_include_stops_recovery() {
+        let netlist = "include\n.include nosuch.lib\nR1 a 0 1k\n.end\n";
+        let mut options = ParseOptions::new_with_source("include.spicy", netlist.to_string());
+        let (deck, errors) = crate::parse_recovering(&mut options);
+        assert!(deck.is_none());
+        assert!(matches!(
+            errors[..],
+            [SpicyError::Include(IncludeError::FileNotFound { .. })]
+        ));
+    }
 }
@@ -1,5 +1,6 @@
 use crate::{error::LexerError, libs_phase::SourceFileId};
 use serde::Serialize;
+use std::ops::Range;
 use unscanny::Scanner;
 
 use crate::expr::PlaceholderId;
@@ -28,6 +29,7 @@ pub enum TokenKind {
     Slash,
     EOF,
     Underscore,
+    Dollar,
 }
 
 impl TokenKind {
@@ -122,6 +124,13 @@ impl<'s> Lexer<'s> {
         }
     }
 
+    /// Lex only `range` of `input`, with the spans still counted from the start of `input`.
+    pub fn new_in(input: &'s str, range: Range<usize>, source_index: SourceFileId) -> Self {
+        let mut s = Scanner::new(&input[..range.end]);
+        s.jump(range.start);
+        Lexer { s, source_index }
+    }
+
     fn whitespace(&mut self, start: usize) -> Token {
         self.s.eat_while(|c: char| c.is_whitespace() && c != '\n');
         Token::new(
@@ -202,6 +211,7 @@ impl<'s> Lexer<'s> {
                 self.source_index,
             )),
             '<' => Ok(Token::single(TokenKind::LessThan, start, self.source_index)),
+            '$' => Ok(Token::single(TokenKind::Dollar, start, self.source_index)),
             '_' => Ok(Token::single(
                 TokenKind::Underscore,
                 start,
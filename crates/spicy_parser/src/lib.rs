@@ -1,30 +1,45 @@
+pub mod asc;
+pub mod compat;
+mod conditional_phase;
 pub mod devices;
+pub mod diagnostic;
 pub mod error;
 mod expr;
 mod expression_phase;
 pub mod instance_parser;
 mod lexer;
 pub mod libs_phase;
-mod netlist_models;
+pub mod lint;
+pub mod netlist_models;
 pub mod netlist_types;
 pub mod netlist_waveform;
+mod netlist_writer;
 pub mod node_mapping;
+pub mod parameters;
+pub mod parse_cache;
 mod parser_utils;
 mod statement_phase;
 mod subcircuit_phase;
+pub mod topology;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
-pub use expr::Value;
+pub use compat::Compatibility;
+pub use expr::{ExprFunction, Value};
 pub use lexer::Span;
 pub use libs_phase::SourceMap;
-pub use netlist_models::BjtPolarity;
+pub use netlist_models::{BjtPolarity, JfetPolarity, MosfetPolarity};
+pub use node_mapping::{NameCase, hierarchical_name};
+pub use parse_cache::ParseCache;
+pub use subcircuit_phase::ExpansionStats;
 
 use crate::{
-    error::{IncludeError, SpicyError},
+    conditional_phase::resolve_conditionals,
+    error::{IncludeError, Recovered, SpicyError},
     expression_phase::substitute_expressions,
     instance_parser::{Deck, InstanceParser},
     libs_phase::{SourceFileId, include_libs},
-    subcircuit_phase::{collect_subckts, expand_subckts},
+    subcircuit_phase::{collect_subckts, expand_subckts, override_params},
 };
 
 #[cfg(test)]
@@ -35,6 +50,12 @@ pub struct ParseOptions {
     pub source_path: PathBuf,
     pub source_map: SourceMap,
     pub max_include_depth: usize,
+    /// Whether node and device names are case-sensitive; SPICE's are not.
+    pub name_case: NameCase,
+    /// The netlist dialect of the sources.
+    pub compatibility: Compatibility,
+    /// Values of the `${NAME}` references in `.include` and `.lib` paths.
+    pub variables: HashMap<String, String>,
 }
 
 impl ParseOptions {
@@ -47,6 +68,9 @@ impl ParseOptions {
             source_path,
             source_map,
             max_include_depth: 10,
+            name_case: NameCase::default(),
+            compatibility: Compatibility::default(),
+            variables: Default::default(),
         }
     }
 
@@ -55,108 +79,145 @@ impl ParseOptions {
         path_str: &str,
         span: Span,
     ) -> Result<(SourceFileId, &str), SpicyError> {
-        let path = Path::new(path_str);
+        let path = self.resolve_path(path_str, span)?;
+        let (canonical_path, content) = load_source(&path, span)?;
+        let content = self.compatibility.translate(content, false);
+        let source_index = self.source_map.push_source(canonical_path, content);
+        Ok((source_index, self.source_map.get_content(source_index)))
+    }
 
-        // Absolute path: read directly
-        if path.is_absolute() {
-            let content = std::fs::read_to_string(path).map_err(|error| {
-                SpicyError::Include(IncludeError::IOError {
-                    path: path.to_path_buf(),
+    /// Replace every `${NAME}` in an include path with its value in `variables`.
+    fn expand_variables(&self, path_str: &str, span: Span) -> Result<String, SpicyError> {
+        let mut expanded = String::with_capacity(path_str.len());
+        let mut rest = path_str;
+        while let Some(start) = rest.find("${") {
+            expanded.push_str(&rest[..start]);
+            let Some(len) = rest[start + 2..].find('}') else {
+                return Err(SpicyError::Include(IncludeError::UnterminatedVariable {
+                    span,
+                }));
+            };
+            let name = &rest[start + 2..start + 2 + len];
+            let value = self.variables.get(name).ok_or_else(|| {
+                SpicyError::Include(IncludeError::UndefinedVariable {
+                    name: name.to_string(),
                     span,
-                    error,
                 })
             })?;
-            // SourceMap::new_source canonicalizes path
-            let path_buf = path.to_path_buf();
-            let (source_index, content) = self
-                .source_map
-                .new_source(path_buf.clone(), content)
-                .map_err(|error| {
-                    SpicyError::Include(IncludeError::IOError {
-                        path: path_buf.clone(),
-                        span,
-                        error,
-                    })
-                })?;
-            return Ok((source_index, content));
+            expanded.push_str(value);
+            rest = &rest[start + 3 + len..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    /// Find the file an include refers to, after expanding its `${NAME}` variables: absolute
+    /// paths are used as is, relative paths are tried against `work_dir` first and then
+    /// against the directory of `source_path`.
+    pub(crate) fn resolve_path(&self, path_str: &str, span: Span) -> Result<PathBuf, SpicyError> {
+        let path_str = self.expand_variables(path_str, span)?;
+        let path = Path::new(&path_str);
+        if path.is_absolute() {
+            return Ok(path.to_path_buf());
         }
 
         let mut checked_paths = vec![];
 
-        // Try joining with work_dir first
         let candidate1 = self.work_dir.join(path);
         if candidate1.exists() {
-            let content = std::fs::read_to_string(&candidate1).map_err(|e| {
-                SpicyError::Include(IncludeError::IOError {
-                    path: candidate1.clone(),
-                    span,
-                    error: e,
-                })
-            })?;
-            let path_buf = candidate1.clone();
-            let (source_index, content) = self
-                .source_map
-                .new_source(path_buf.clone(), content.clone())
-                .map_err(|error| {
-                    SpicyError::Include(IncludeError::IOError {
-                        path: path_buf.clone(),
-                        span,
-                        error,
-                    })
-                })?;
-            return Ok((source_index, content));
+            return Ok(candidate1);
         }
         checked_paths.push(candidate1);
 
-        // Try joining with the parent of source_path
         if let Some(parent) = self.source_path.parent() {
             let candidate2 = parent.join(path);
             if candidate2.exists() {
-                let content = std::fs::read_to_string(&candidate2).map_err(|e| {
-                    SpicyError::Include(IncludeError::IOError {
-                        path: candidate2.clone(),
-                        span,
-                        error: e,
-                    })
-                })?;
-                let path_buf = candidate2.clone();
-                let (source_index, content) = self
-                    .source_map
-                    .new_source(path_buf.clone(), content.clone())
-                    .map_err(|error| {
-                        SpicyError::Include(IncludeError::IOError {
-                            path: path_buf.clone(),
-                            span,
-                            error,
-                        })
-                    })?;
-                return Ok((source_index, content));
+                return Ok(candidate2);
             }
             checked_paths.push(candidate2);
         }
 
         // Not found in either location
-        Err(SpicyError::Include(
-            crate::error::IncludeError::FileNotFound {
-                path: path.to_path_buf(),
-                checked_paths,
-                span,
-            },
-        ))
+        Err(SpicyError::Include(IncludeError::FileNotFound {
+            path: path.to_path_buf(),
+            checked_paths,
+            span,
+        }))
     }
 }
 
+/// Read `path`, returning its canonicalized path along with the content.
+pub(crate) fn load_source(path: &Path, span: Span) -> Result<(PathBuf, String), SpicyError> {
+    let io_error = |error| {
+        SpicyError::Include(IncludeError::IOError {
+            path: path.to_path_buf(),
+            span,
+            error,
+        })
+    };
+    let content = std::fs::read_to_string(path).map_err(io_error)?;
+    let canonical_path = std::fs::canonicalize(path).map_err(io_error)?;
+    Ok((canonical_path, content))
+}
+
 pub fn parse(options: &mut ParseOptions) -> Result<Deck, SpicyError> {
+    parse_with_params(options, &[])
+}
+
+/// Parse with the top-level `.param`s named in `overrides` set to the given values.
+pub fn parse_with_params(
+    options: &mut ParseOptions,
+    overrides: &[(String, f64)],
+) -> Result<Deck, SpicyError> {
+    parse_phases(options, overrides, None)
+}
+
+/// Parse past errors: a statement or card with an error is left out of the deck and its error
+/// collected, so a single pass reports every problem of the netlist. Errors that leave no
+/// statements to go on with (lexing, includes, subcircuits, a missing title) still stop the
+/// parse, giving no deck; they come last in the errors.
+pub fn parse_recovering(options: &mut ParseOptions) -> (Option<Deck>, Vec<SpicyError>) {
+    let mut errors = Vec::new();
+    match parse_phases(options, &[], Some(&mut errors)) {
+        Ok(deck) => (Some(deck), errors),
+        Err(error) => {
+            errors.push(error);
+            (None, errors)
+        }
+    }
+}
+
+fn parse_phases(
+    options: &mut ParseOptions,
+    overrides: &[(String, f64)],
+    mut recovered: Recovered,
+) -> Result<Deck, SpicyError> {
+    options.source_map.translate_main(options.compatibility);
     let stream = statement_phase::Statements::new(
         options.source_map.get_main_content(),
         options.source_map.main_index(),
     )?;
-    let mut stream = include_libs(stream, options)?;
-    let placeholders_map = substitute_expressions(&mut stream, options)?;
-    let unexpanded_deck = collect_subckts(stream, &options.source_map)?;
+    let (mut stream, mut deferred) = include_libs(stream, options)?;
+    let mut placeholders_map =
+        substitute_expressions(&mut stream, options, recovered.as_deref_mut())?;
+    resolve_conditionals(
+        &mut stream,
+        options,
+        &mut deferred,
+        &mut placeholders_map,
+        overrides,
+        recovered.as_deref_mut(),
+    )?;
+    let mut unexpanded_deck = collect_subckts(stream, &options.source_map, &placeholders_map)?;
+    override_params(&mut unexpanded_deck, overrides)?;
     let expanded_deck = expand_subckts(unexpanded_deck, &options.source_map, &placeholders_map)?;
-    let mut parser = InstanceParser::new(expanded_deck, placeholders_map, &options.source_map);
-    let deck = parser.parse()?;
+    let mut parser = InstanceParser::new(
+        expanded_deck,
+        placeholders_map,
+        &options.source_map,
+        options.name_case,
+    );
+    let deck = parser.parse(recovered)?;
 
     Ok(deck)
 }
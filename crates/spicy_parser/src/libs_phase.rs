@@ -1,16 +1,19 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
+    ops::Range,
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
 
 use serde::Serialize;
 
-use crate::parser_utils::parse_ident;
 use crate::{
     ParseOptions, Span,
+    compat::Compatibility,
     error::{IncludeError, SpicyError},
+    load_source,
     netlist_types::CommandType,
-    statement_phase::{Statements, StmtCursor},
+    statement_phase::{Statement, Statements, StmtCursor},
 };
 
 #[derive(Debug)]
@@ -18,9 +21,57 @@ pub struct SourceMap {
     /// canonicalized paths
     paths: Vec<PathBuf>,
     contents: Vec<String>,
+    /// The `.LIB` sections of each source, indexed the first time a `.lib` asks for one.
+    lib_sections: Vec<OnceLock<Vec<LibSection>>>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+/// A `.LIB <name>` ... `.ENDL` section of a library file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LibSection {
+    name: String,
+    /// The lines between the `.LIB` and `.ENDL` lines.
+    body: Range<usize>,
+}
+
+/// Find the sections of a library from the first words of its lines, without lexing it. A
+/// `.LIB` with a single argument opens a section (a `.lib <path> <name>` inside one is a
+/// reference to another library) and an `.ENDL` closes it; a section left open runs to the
+/// end of the file.
+fn index_lib_sections(content: &str) -> Vec<LibSection> {
+    let mut sections = Vec::new();
+    let mut open: Option<(String, usize)> = None;
+    let mut line_start = 0;
+    for line in content.split_inclusive('\n') {
+        let next_line = line_start + line.len();
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some(word) if word.eq_ignore_ascii_case(".lib") && open.is_none() => {
+                if let (Some(name), None) = (words.next(), words.next()) {
+                    open = Some((name.to_string(), next_line));
+                }
+            }
+            Some(word) if word.eq_ignore_ascii_case(".endl") => {
+                if let Some((name, start)) = open.take() {
+                    sections.push(LibSection {
+                        name,
+                        body: start..line_start,
+                    });
+                }
+            }
+            _ => {}
+        }
+        line_start = next_line;
+    }
+    if let Some((name, start)) = open {
+        sections.push(LibSection {
+            name,
+            body: start..content.len(),
+        });
+    }
+    sections
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Hash)]
 pub struct SourceFileId(u16);
 
 /// for tests outside crate
@@ -28,6 +79,11 @@ impl SourceFileId {
     pub fn dummy() -> Self {
         Self(0)
     }
+
+    /// The file the parse started from, rather than one it includes.
+    pub fn is_main(self) -> bool {
+        self.0 == SourceMap::MAIN_INDEX
+    }
 }
 
 #[cfg(test)]
@@ -44,6 +100,7 @@ impl SourceMap {
         Self {
             paths: vec![main_file],
             contents: vec![content],
+            lib_sections: vec![OnceLock::new()],
         }
     }
 
@@ -59,17 +116,40 @@ impl SourceMap {
         path: PathBuf,
         content: String,
     ) -> std::io::Result<(SourceFileId, &str)> {
-        let new_index = SourceFileId(self.paths.len() as u16);
         let canonical_path = std::fs::canonicalize(path)?;
+        let new_index = self.push_source(canonical_path, content);
+        Ok((new_index, &self.contents[new_index.0 as usize]))
+    }
+
+    /// Add a source whose path is already canonicalized.
+    pub(crate) fn push_source(&mut self, canonical_path: PathBuf, content: String) -> SourceFileId {
+        let new_index = SourceFileId(self.paths.len() as u16);
         self.paths.push(canonical_path);
         self.contents.push(content);
-        Ok((new_index, &self.contents[new_index.0 as usize]))
+        self.lib_sections.push(OnceLock::new());
+        new_index
+    }
+
+    /// Rewrite the main source from the `compatibility` dialect into the strict syntax.
+    pub(crate) fn translate_main(&mut self, compatibility: Compatibility) {
+        let main = &mut self.contents[Self::MAIN_INDEX as usize];
+        *main = compatibility.translate(std::mem::take(main), true);
     }
 
     pub const fn main_index(&self) -> SourceFileId {
         SourceFileId(Self::MAIN_INDEX)
     }
 
+    /// The sources added after the main one, in the order they were read.
+    pub(crate) fn included_indices(&self) -> impl Iterator<Item = SourceFileId> + use<> {
+        (Self::MAIN_INDEX + 1..self.paths.len() as u16).map(SourceFileId)
+    }
+
+    /// The path of every source, the main one first.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.paths.iter().map(|path| path.as_path())
+    }
+
     pub fn get_path(&self, index: SourceFileId) -> &Path {
         self.paths
             .get(index.0 as usize)
@@ -77,6 +157,17 @@ impl SourceMap {
             .expect("source file ids have to be valid")
     }
 
+    /// The `.LIB` section `name` of the source, matched case-insensitively. The sections of a
+    /// source are indexed once, so every `.lib` into the same library shares the index.
+    pub(crate) fn lib_section(&self, index: SourceFileId, name: &str) -> Option<Range<usize>> {
+        let sections = self.lib_sections[index.0 as usize]
+            .get_or_init(|| index_lib_sections(self.get_content(index)));
+        sections
+            .iter()
+            .find(|section| section.name.eq_ignore_ascii_case(name))
+            .map(|section| section.body.clone())
+    }
+
     pub fn get_main_content(&self) -> &str {
         self.contents
             .get(Self::MAIN_INDEX as usize)
@@ -94,10 +185,19 @@ fn span_text(src: &str, span: Span) -> &str {
     &src[span.start..=span.end]
 }
 
-fn parse_include<'a>(
-    cursor: &mut StmtCursor<'a>,
-    options: &mut ParseOptions,
-) -> Result<(Statements, SourceFileId), SpicyError> {
+/// An `.include` or `.lib` statement waiting to be loaded.
+struct IncludeDirective {
+    path: String,
+    path_span: Span,
+    /// `.lib <path> <libname>` only pulls in the matching `.LIB`/`.ENDL` section
+    libname: Option<String>,
+    span: Span,
+}
+
+fn parse_include(
+    cursor: &mut StmtCursor,
+    source_map: &SourceMap,
+) -> Result<IncludeDirective, SpicyError> {
     let cursors = cursor.split_on_whitespace();
     let path_cursor = cursors
         .first()
@@ -106,21 +206,24 @@ fn parse_include<'a>(
         }))?;
 
     let path = span_text(
-        options
-            .source_map
-            .get_content(path_cursor.span.source_index),
+        source_map.get_content(path_cursor.span.source_index),
         path_cursor.span,
     )
     .trim()
     .to_string();
 
-    handle_lib_command(options, &path, path_cursor.span, None)
+    Ok(IncludeDirective {
+        path,
+        path_span: path_cursor.span,
+        libname: None,
+        span: cursor.span,
+    })
 }
 
-fn parse_lib_command<'a>(
-    cursor: &mut StmtCursor<'a>,
-    options: &mut ParseOptions,
-) -> Result<(Statements, SourceFileId), SpicyError> {
+fn parse_lib_command(
+    cursor: &mut StmtCursor,
+    source_map: &SourceMap,
+) -> Result<IncludeDirective, SpicyError> {
     let cursors = cursor.split_on_whitespace();
     let path_cursor = cursors
         .first()
@@ -129,40 +232,43 @@ fn parse_lib_command<'a>(
         }))?;
     let lib_cursor_opt = cursors.get(1);
     let path = span_text(
-        options
-            .source_map
-            .get_content(path_cursor.span.source_index),
+        source_map.get_content(path_cursor.span.source_index),
         path_cursor.span,
     )
     .trim()
     .to_string();
 
     // optional target library name (case-insensitive)
-    let libname_opt = lib_cursor_opt.map(|lib_cursor| {
+    let libname = lib_cursor_opt.map(|lib_cursor| {
         span_text(
-            options.source_map.get_content(lib_cursor.span.source_index),
+            source_map.get_content(lib_cursor.span.source_index),
             lib_cursor.span,
         )
         .trim()
         .to_string()
     });
 
-    handle_lib_command(options, &path, path_cursor.span, libname_opt)
+    Ok(IncludeDirective {
+        path,
+        path_span: path_cursor.span,
+        libname,
+        span: cursor.span,
+    })
 }
 
-fn handle_lib_command(
-    options: &mut ParseOptions,
-    path: &str,
-    path_span: Span,
-    libname: Option<String>,
-) -> Result<(Statements, SourceFileId), SpicyError> {
-    let (source_index, file_content) = options.read_file(path, path_span)?;
-    let all = Statements::new(file_content, source_index)?;
-    if libname.is_none() {
+/// Lex an included file and keep the statements the directive asks for. For a `.lib` section
+/// only the lines of that section are lexed.
+fn lex_included(
+    directive: &IncludeDirective,
+    source_map: &SourceMap,
+    source_index: SourceFileId,
+) -> Result<Statements, SpicyError> {
+    let src = source_map.get_content(source_index);
+    let Some(libname) = &directive.libname else {
         // Behave like include: return all statements except .LIB/.ENDL wrappers
+        let all = Statements::new(src, source_index)?;
         let mut filtered = Vec::new();
         for s in all.statements.into_iter() {
-            let src = file_content;
             let mut c = s.as_cursor();
             if c.consume_if_command(src, CommandType::Lib) {
                 continue;
@@ -172,120 +278,267 @@ fn handle_lib_command(
             }
             filtered.push(s);
         }
-        return Ok((
-            Statements {
-                statements: filtered,
-            },
-            source_index,
-        ));
-    }
-    let libname = libname.unwrap();
-
-    // Extract only the statements within .LIB <libname> ... .ENDL
-    let mut in_block = false;
-    let mut out_stmts = Vec::new();
-    for s in all.statements.into_iter() {
-        let src = file_content;
-        let mut c = s.as_cursor();
-        if c.consume_if_command(src, CommandType::Lib) {
-            let name = parse_ident(&mut c, src)?;
-            if !in_block && name.text.eq_ignore_ascii_case(&libname) {
-                in_block = true;
-            }
-            continue;
-        } else if c.consume_if_command(src, CommandType::Endl) {
-            if in_block {
-                break;
-            }
-            continue;
-        }
+        return Ok(Statements {
+            statements: filtered,
+        });
+    };
+
+    // Lex only the statements within .LIB <libname> ... .ENDL
+    let section = source_map
+        .lib_section(source_index, libname)
+        .ok_or_else(|| {
+            SpicyError::Include(IncludeError::LibSectionNotFound {
+                span: directive.path_span,
+                lib: libname.to_string(),
+                path: source_map.get_path(source_index).to_path_buf(),
+            })
+        })?;
+    Statements::new_in(src, section, source_index)
+}
 
-        if in_block {
-            out_stmts.push(s);
-        }
+/// Map `f` over `items` on scoped worker threads, keeping the order of `items`.
+fn par_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if items.len() < 2 || workers < 2 {
+        return items.iter().map(f).collect();
     }
+    let chunk_size = items.len().div_ceil(workers);
+    let f = &f;
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("include loader thread panicked"))
+            .collect()
+    })
+}
+
+enum Item {
+    Statement(Statement),
+    Include,
+}
+
+/// The file of an include directive.
+enum Loaded {
+    /// Read from disk, to be added to the source map.
+    Read(PathBuf, String),
+    /// A library already in the source map, whose sections are taken from the same source.
+    Known(SourceFileId),
+}
 
-    if out_stmts.is_empty() {
-        return Err(SpicyError::Include(IncludeError::LibSectionNotFound {
-            span: path_span,
-            lib: libname.to_string(),
-            path: options.source_map.get_path(source_index).to_path_buf(),
-        }));
+/// The source of the library at `path` when the source map already holds it. Only a `.lib`
+/// reuses a source: an `.include` always adds the file again.
+fn known_library(
+    source_map: &SourceMap,
+    directive: &IncludeDirective,
+    path: &Path,
+) -> Option<SourceFileId> {
+    directive.libname.as_ref()?;
+    source_map
+        .is_in_map(path)
+        .filter(|source_id| !source_id.is_main())
+}
+
+/// What the include stack holds: a file, and the section of it for a `.lib`, so that the
+/// sections of one library can refer to each other.
+type IncludeKey = (PathBuf, Option<String>);
+
+fn include_key(path: PathBuf, directive: &IncludeDirective) -> IncludeKey {
+    let section = directive.libname.as_ref().map(|name| name.to_lowercase());
+    (path, section)
+}
+
+/// The `.include`s and `.lib`s inside an `.if`, left in the statements until the conditionals
+/// reach them in a taken branch, so a file that only an untaken branch names is never read.
+#[derive(Debug, Default)]
+pub(crate) struct DeferredIncludes {
+    /// The depth and the include stack each directive was reached at, by where it starts.
+    directives: HashMap<(SourceFileId, usize), (usize, HashSet<IncludeKey>)>,
+}
+
+impl DeferredIncludes {
+    pub(crate) fn contains(&self, stmt: &Statement) -> bool {
+        self.directives
+            .contains_key(&(stmt.span.source_index, stmt.span.start))
     }
 
-    Ok((
-        Statements {
-            statements: out_stmts,
-        },
-        source_index,
-    ))
+    /// Expand the deferred directive `stmt` as it would have been where it was reached.
+    pub(crate) fn expand(
+        &mut self,
+        stmt: Statement,
+        options: &mut ParseOptions,
+    ) -> Result<Statements, SpicyError> {
+        let (depth, mut stack) = self
+            .directives
+            .remove(&(stmt.span.source_index, stmt.span.start))
+            .expect("the directive was deferred");
+        let stmts = Statements {
+            statements: vec![stmt],
+        };
+        expand_includes(stmts, options, depth, &mut stack, self)
+    }
 }
 
 fn expand_includes(
     stmts: Statements,
     options: &mut ParseOptions,
     depth: usize,
-    stack: &mut HashSet<PathBuf>,
+    stack: &mut HashSet<IncludeKey>,
+    deferred: &mut DeferredIncludes,
 ) -> Result<Statements, SpicyError> {
-    let mut out = Vec::new();
-
+    // Split off the include directives of this file so they can be loaded together.
+    let mut items = Vec::new();
+    let mut directives = Vec::new();
+    // the `.if`s of this file the statement is in
+    let mut conditionals = 0usize;
     for stmt in stmts.statements.into_iter() {
         // TODO: kinda sucky that you have to get the input for each statement
         let input = options.source_map.get_content(stmt.span.source_index);
         let mut cursor = stmt.as_cursor();
 
-        if let Some(command) =
+        match cursor.consume_if_commands(input, &[CommandType::If, CommandType::EndIf]) {
+            Some(CommandType::If) => conditionals += 1,
+            Some(_) => conditionals = conditionals.saturating_sub(1),
+            None => {}
+        }
+        if conditionals > 0 && is_include(&stmt, input) {
+            let key = (stmt.span.source_index, stmt.span.start);
+            deferred.directives.insert(key, (depth, stack.clone()));
+            items.push(Item::Statement(stmt));
+        } else if let Some(command) =
             cursor.consume_if_commands(input, &[CommandType::Include, CommandType::Lib])
         {
-            let (included_stmts, source_id) = match command {
-                CommandType::Include => parse_include(&mut cursor, options)?,
-                CommandType::Lib => parse_lib_command(&mut cursor, options)?,
+            directives.push(match command {
+                CommandType::Include => parse_include(&mut cursor, &options.source_map),
+                CommandType::Lib => parse_lib_command(&mut cursor, &options.source_map),
                 _ => unreachable!(),
+            });
+            items.push(Item::Include);
+        } else {
+            items.push(Item::Statement(stmt));
+        }
+    }
+
+    // Resolve and read every included file concurrently, skipping libraries already read.
+    let loaded = par_map(&directives, |directive| {
+        let directive = directive.as_ref().ok()?;
+        Some(
+            options
+                .resolve_path(&directive.path, directive.path_span)
+                .and_then(|path| {
+                    let canonical = std::fs::canonicalize(&path).ok();
+                    let known = canonical.and_then(|canonical| {
+                        known_library(&options.source_map, directive, &canonical)
+                    });
+                    if let Some(source_id) = known {
+                        return Ok(Loaded::Known(source_id));
+                    }
+                    let (path, content) = load_source(&path, directive.path_span)?;
+                    let content = options.compatibility.translate(content, false);
+                    Ok(Loaded::Read(path, content))
+                }),
+        )
+    });
+    // Source ids are handed out in statement order.
+    let registered: Vec<_> = directives
+        .into_iter()
+        .zip(loaded)
+        .map(|(directive, loaded)| {
+            let directive = directive?;
+            let source_id = match loaded.expect("directive was parsed")? {
+                Loaded::Known(source_id) => source_id,
+                // a library read twice by sibling directives is still added once
+                Loaded::Read(path, content) => {
+                    match known_library(&options.source_map, &directive, &path) {
+                        Some(source_id) => source_id,
+                        None => options.source_map.push_source(path, content),
+                    }
+                }
             };
+            Ok::<_, SpicyError>((directive, source_id))
+        })
+        .collect();
+    // Lex the included files concurrently.
+    let lexed = par_map(&registered, |registered| {
+        let (directive, source_id) = registered.as_ref().ok()?;
+        Some(lex_included(directive, &options.source_map, *source_id))
+    });
+    // Errors are only raised when their statement is reached below, so they come out in the
+    // same order as if the files were loaded one after the other.
+    let mut includes = registered
+        .into_iter()
+        .zip(lexed)
+        .map(|(registered, lexed)| {
+            let (directive, source_id) = registered?;
+            let included_stmts = lexed.expect("registered sources are lexed")?;
+            Ok::<_, SpicyError>((directive, source_id, included_stmts))
+        });
+
+    let mut out = Vec::new();
+    for item in items {
+        let Item::Statement(stmt) = item else {
+            let (directive, source_id, included_stmts) =
+                includes.next().expect("one entry per include")?;
 
-            // cycle detection using canonicalized path
+            // cycle detection using canonicalized path and library section
             let path = options.source_map.get_path(source_id).to_path_buf();
-            if stack.contains(&path) {
+            let key = include_key(path, &directive);
+            if stack.contains(&key) {
                 return Err(SpicyError::Include(IncludeError::CycleDetected {
-                    span: cursor.span,
-                    path,
+                    span: directive.span,
+                    path: key.0,
                 }));
             }
             if depth + 1 > options.max_include_depth {
                 return Err(SpicyError::Include(IncludeError::MaxDepthExceeded {
-                    span: cursor.span,
+                    span: directive.span,
                     depth: depth + 1,
                 }));
             }
-            stack.insert(path.clone());
-            let expanded = expand_includes(included_stmts, options, depth + 1, stack)?;
+            stack.insert(key.clone());
+            let expanded = expand_includes(included_stmts, options, depth + 1, stack, deferred)?;
             // pop stack for this include path
-            let _ = stack.remove(&path);
+            let _ = stack.remove(&key);
             out.extend(expanded.statements);
-        } else {
-            out.push(stmt);
-        }
+            continue;
+        };
+        out.push(stmt);
     }
 
     Ok(Statements { statements: out })
 }
 
+fn is_include(stmt: &Statement, input: &str) -> bool {
+    stmt.as_cursor()
+        .consume_if_commands(input, &[CommandType::Include, CommandType::Lib])
+        .is_some()
+}
+
+/// Expand the `.include`s and `.lib`s of `stmts`, but for those inside an `.if`, which are
+/// returned to expand once their branch is known to be taken.
 pub(crate) fn include_libs(
     stmts: Statements,
     options: &mut ParseOptions,
-) -> Result<Statements, SpicyError> {
+) -> Result<(Statements, DeferredIncludes), SpicyError> {
     let mut stack = HashSet::new();
     let main_path = options
         .source_map
         .get_path(options.source_map.main_index())
         .to_path_buf();
-    stack.insert(main_path);
-    expand_includes(stmts, options, 0, &mut stack)
+    stack.insert((main_path, None));
+    let mut deferred = DeferredIncludes::default();
+    let stmts = expand_includes(stmts, options, 0, &mut stack, &mut deferred)?;
+    Ok((stmts, deferred))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
+    use std::collections::HashMap;
     use std::path::{Path, PathBuf};
 
     fn make_opts(main: &Path, work_dir: &Path, max_depth: usize) -> ParseOptions {
@@ -296,6 +549,9 @@ mod tests {
             source_path: main_path.clone(),
             source_map: SourceMap::new(main_path, content),
             max_include_depth: max_depth,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         }
     }
 
@@ -310,7 +566,7 @@ mod tests {
             opts.source_map.main_index(),
         )
         .unwrap();
-        let expanded = include_libs(stmts, &mut opts).unwrap();
+        let expanded = include_libs(stmts, &mut opts).unwrap().0;
         assert_eq!(expanded.statements.len(), 5);
     }
 
@@ -325,7 +581,7 @@ mod tests {
             opts.source_map.main_index(),
         )
         .unwrap();
-        let expanded = include_libs(stmts, &mut opts).unwrap();
+        let expanded = include_libs(stmts, &mut opts).unwrap().0;
         // Expect: selected mos2 section (Rlib2) + Rmain
         assert_eq!(expanded.statements.len(), 4);
         let mut found = false;
@@ -350,7 +606,7 @@ mod tests {
             opts.source_map.main_index(),
         )
         .unwrap();
-        let expanded = include_libs(stmts, &mut opts).unwrap();
+        let expanded = include_libs(stmts, &mut opts).unwrap().0;
         // Should include both library sections' statements (Rlib1 and Rlib2) plus Rmain
         let src_strings: Vec<String> = expanded
             .statements
@@ -379,7 +635,7 @@ mod tests {
             opts.source_map.main_index(),
         )
         .unwrap();
-        let expanded = include_libs(stmts, &mut opts).unwrap();
+        let expanded = include_libs(stmts, &mut opts).unwrap().0;
         assert_eq!(expanded.statements.len(), 7);
     }
 
@@ -394,7 +650,7 @@ mod tests {
             opts.source_map.main_index(),
         )
         .unwrap();
-        let expanded = include_libs(stmts, &mut opts).unwrap();
+        let expanded = include_libs(stmts, &mut opts).unwrap().0;
         // lib_a twice + local R1
         assert_eq!(expanded.statements.len(), 7);
     }
@@ -454,6 +710,157 @@ mod tests {
         }
     }
 
+    #[test]
+    fn include_path_variables_are_expanded() {
+        let mut opts = dummy_opts(".include ${DIR}/lib_a.spicy\n.lib ${LIBS}.spicy mos1\n");
+        opts.variables = HashMap::from([
+            ("DIR".to_string(), "alt".to_string()),
+            ("LIBS".to_string(), "lib_sections".to_string()),
+        ]);
+        let stmts = Statements::new(
+            opts.source_map.get_main_content(),
+            opts.source_map.main_index(),
+        )
+        .unwrap();
+        let expanded = include_libs(stmts, &mut opts).unwrap().0;
+        assert_eq!(device_names(&expanded, &opts), vec!["Ralt", "Rlib1"]);
+        let first = opts.source_map.included_indices().next().unwrap();
+        assert!(opts.source_map.get_path(first).ends_with("alt/lib_a.spicy"));
+    }
+
+    #[rstest]
+    #[case(
+        ".include ${DIR}/lib_a.spicy\n",
+        "undefined variable 'DIR' in include path"
+    )]
+    #[case(".include ${DIR/lib_a.spicy\n", "unterminated '${' in include path")]
+    fn include_path_variable_errors(#[case] main: &str, #[case] message: &str) {
+        let mut opts = dummy_opts(main);
+        let stmts = Statements::new(
+            opts.source_map.get_main_content(),
+            opts.source_map.main_index(),
+        )
+        .unwrap();
+        let err = include_libs(stmts, &mut opts).unwrap_err();
+        assert_eq!(err.to_string(), message);
+        assert!(err.error_span().is_some());
+    }
+
+    fn dummy_opts(main_content: &str) -> ParseOptions {
+        let crate_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let dummy_main = crate_dir.join("tests/include_inputs/dummy_main.spicy");
+        ParseOptions {
+            work_dir: crate_dir.join("tests/include_inputs"),
+            source_path: dummy_main.clone(),
+            source_map: SourceMap::new(dummy_main, main_content.to_string()),
+            max_include_depth: 8,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
+        }
+    }
+
+    #[test]
+    fn sibling_includes_keep_statement_order() {
+        let mut opts = dummy_opts(
+            ".include lib_a.spicy\n.include lib_b.spicy\nRmid a b 1\n.include lib_a.spicy\n",
+        );
+        let stmts = Statements::new(
+            opts.source_map.get_main_content(),
+            opts.source_map.main_index(),
+        )
+        .unwrap();
+        let expanded = include_libs(stmts, &mut opts).unwrap().0;
+        let devices: Vec<_> = expanded
+            .statements
+            .iter()
+            .filter_map(|s| {
+                let src = opts.source_map.get_content(s.span.source_index);
+                let text = span_text(src, s.span);
+                text.starts_with('R')
+                    .then(|| text.split_whitespace().next().unwrap())
+            })
+            .collect();
+        // lib_b pulls in lib_c before its own R3
+        assert_eq!(devices, vec!["R2", "R4", "R3", "Rmid", "R2"]);
+    }
+
+    fn device_names(expanded: &Statements, opts: &ParseOptions) -> Vec<String> {
+        expanded
+            .statements
+            .iter()
+            .filter_map(|s| {
+                let src = opts.source_map.get_content(s.span.source_index);
+                let text = span_text(src, s.span);
+                text.starts_with('R')
+                    .then(|| text.split_whitespace().next().unwrap().to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lib_sections_are_indexed_by_line() {
+        let library =
+            "* header\n.LIB tt\nR1 a b 1\n.lib other.lib ff\n.ENDL tt\n.lib ff\nR2 a b 2\n";
+        let sections = index_lib_sections(library);
+        let names: Vec<_> = sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["tt", "ff"]);
+        assert_eq!(
+            &library[sections[0].body.clone()],
+            "R1 a b 1\n.lib other.lib ff\n"
+        );
+        // a section without .ENDL runs to the end of the file
+        assert_eq!(&library[sections[1].body.clone()], "R2 a b 2\n");
+    }
+
+    #[test]
+    fn lib_sections_share_one_source() {
+        let mut opts = dummy_opts(".lib lib_corners.spicy tt\n.lib lib_corners.spicy FF\n");
+        let stmts = Statements::new(
+            opts.source_map.get_main_content(),
+            opts.source_map.main_index(),
+        )
+        .unwrap();
+        let expanded = include_libs(stmts, &mut opts).unwrap().0;
+        // sections of a library can pull in other sections of the same file
+        assert_eq!(
+            device_names(&expanded, &opts),
+            vec!["Rcommon", "Rtt", "Rcommon", "Rff"]
+        );
+        assert_eq!(opts.source_map.included_indices().count(), 1);
+    }
+
+    #[test]
+    fn lib_section_cycle_detected() {
+        let mut opts = dummy_opts(".lib lib_cycle.spicy a\n");
+        let stmts = Statements::new(
+            opts.source_map.get_main_content(),
+            opts.source_map.main_index(),
+        )
+        .unwrap();
+        let err = include_libs(stmts, &mut opts).unwrap_err();
+        match err {
+            SpicyError::Include(IncludeError::CycleDetected { .. }) => {}
+            other => panic!("expected CycleDetected, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn include_errors_follow_statement_order() {
+        // the missing file is read alongside the first include, but the cycle comes first
+        let mut opts = dummy_opts(".include root_cycle_a.spicy\n.include does_not_exist.spicy\n");
+        let stmts = Statements::new(
+            opts.source_map.get_main_content(),
+            opts.source_map.main_index(),
+        )
+        .unwrap();
+        let err = include_libs(stmts, &mut opts).unwrap_err();
+        match err {
+            SpicyError::Include(IncludeError::CycleDetected { .. }) => {}
+            other => panic!("expected CycleDetected, got {:?}", other),
+        }
+    }
+
     #[test]
     fn include_absolute_path_ok() {
         let crate_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -466,13 +873,16 @@ mod tests {
             source_path: dummy_main.clone(),
             source_map: SourceMap::new(dummy_main.clone(), main_content),
             max_include_depth: 8,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let stmts = Statements::new(
             opts.source_map.get_main_content(),
             opts.source_map.main_index(),
         )
         .unwrap();
-        let expanded = include_libs(stmts, &mut opts).unwrap();
+        let expanded = include_libs(stmts, &mut opts).unwrap().0;
         assert_eq!(expanded.statements.len(), 3);
     }
 
@@ -487,7 +897,7 @@ mod tests {
             opts.source_map.main_index(),
         )
         .unwrap();
-        let expanded = include_libs(stmts, &mut opts).unwrap();
+        let expanded = include_libs(stmts, &mut opts).unwrap().0;
         // Find a statement from lib_a and assert its path comes from alt/
         let main_idx = opts.source_map.main_index();
         let mut found_alt = false;
@@ -521,7 +931,7 @@ mod tests {
             opts.source_map.main_index(),
         )
         .unwrap();
-        let expanded = include_libs(stmts, &mut opts).unwrap();
+        let expanded = include_libs(stmts, &mut opts).unwrap().0;
         let main_idx = opts.source_map.main_index();
         let mut found_parent = false;
         for st in &expanded.statements {
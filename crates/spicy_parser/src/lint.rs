@@ -0,0 +1,204 @@
+//! Non-fatal checks on a parsed deck, run before any analysis.
+//!
+//! Besides the [`crate::topology`] and [`crate::parameters`] checks, which the simulator may
+//! later turn into errors, the lint pass reports nodes that only one device terminal touches:
+//! almost always a typo in a node name.
+
+use thiserror::Error;
+
+use crate::{
+    Span,
+    devices::SwitchControl,
+    error::{ParameterError, TopologyError},
+    instance_parser::Deck,
+    netlist_types::NodeIndex,
+    parameters::check_parameters,
+    topology::check_topology,
+};
+
+/// A suspicious circuit structure, pointing at the device that shows it.
+#[derive(Debug, Clone, Error)]
+pub enum LintWarning {
+    #[error("node {node} has a single connection (to {device})")]
+    DanglingNode {
+        node: String,
+        device: String,
+        span: Span,
+    },
+
+    #[error(transparent)]
+    Topology(#[from] TopologyError),
+
+    #[error(transparent)]
+    Parameter(#[from] ParameterError),
+}
+
+impl LintWarning {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            LintWarning::DanglingNode { span, .. } => Some(*span),
+            LintWarning::Topology(error) => error.error_span(),
+            LintWarning::Parameter(error) => error.error_span(),
+        }
+    }
+
+    /// Stable code of the kind of lint: `W0001` for a dangling node, the code of the error
+    /// for a topology or parameter problem.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LintWarning::DanglingNode { .. } => "W0001",
+            LintWarning::Topology(error) => error.code(),
+            LintWarning::Parameter(error) => error.code(),
+        }
+    }
+}
+
+/// Every device terminal of `deck` with the name and span of its device. Internal nodes, such
+/// as a BJT's `collector_prime`, are not terminals.
+pub fn terminals<'d>(deck: &'d Deck) -> Vec<(NodeIndex, &'d str, Span)> {
+    let devices = &deck.devices;
+    let mut terminals = Vec::new();
+    let mut add = |nodes: &[NodeIndex], name: &'d str, span: Span| {
+        terminals.extend(nodes.iter().map(|&n| (n, name, span)));
+    };
+
+    for r in &devices.resistors {
+        add(&[r.positive, r.negative], &r.name, r.span);
+    }
+    for c in &devices.capacitors {
+        add(&[c.positive, c.negative], &c.name, c.span);
+    }
+    for l in &devices.inductors {
+        add(&[l.positive, l.negative], &l.name, l.span);
+    }
+    for d in &devices.diodes {
+        add(&[d.positive, d.negative], &d.name, d.span);
+    }
+    for a in &devices.lookup_tables {
+        add(&[a.positive, a.negative], &a.name, a.span);
+    }
+    for s in devices
+        .voltage_sources
+        .iter()
+        .chain(&devices.current_sources)
+    {
+        add(&[s.positive, s.negative], &s.name, s.span);
+    }
+    for q in &devices.bjts {
+        add(&[q.collector, q.base, q.emitter], &q.name, q.span);
+    }
+    for m in &devices.mosfets {
+        add(&[m.drain, m.gate, m.source, m.bulk], &m.name, m.span);
+    }
+    for j in &devices.jfets {
+        add(&[j.drain, j.gate, j.source], &j.name, j.span);
+    }
+    for b in &devices.behavioral_sources {
+        add(&[b.positive, b.negative], &b.name, b.span);
+    }
+    for t in &devices.transmission_lines {
+        let nodes = [t.positive1, t.negative1, t.positive2, t.negative2];
+        add(&nodes, &t.name, t.span);
+    }
+    for s in &devices.switches {
+        add(&[s.positive, s.negative], &s.name, s.span);
+        if let SwitchControl::Voltage {
+            positive, negative, ..
+        } = &s.control
+        {
+            add(&[*positive, *negative], &s.name, s.span);
+        }
+    }
+    terminals
+}
+
+/// Run every lint on `deck`: single-connection nodes, then the [`check_topology`] and
+/// [`check_parameters`] problems.
+pub fn lint_deck(deck: &Deck) -> Vec<LintWarning> {
+    let node_names = deck.node_mapping.node_names_mna_order();
+
+    // per node: the number of terminals on it and the first device touching it
+    let mut connections: Vec<(usize, Option<(&str, Span)>)> = vec![(0, None); node_names.len() + 1];
+    for (node, name, span) in terminals(deck) {
+        let (count, first) = &mut connections[node.0];
+        *count += 1;
+        first.get_or_insert((name, span));
+    }
+
+    let mut warnings: Vec<LintWarning> = connections
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter_map(|(node, (count, first))| match (count, first) {
+            (1, Some((device, span))) => Some(LintWarning::DanglingNode {
+                node: node_names[node - 1].clone(),
+                device: device.to_string(),
+                span: *span,
+            }),
+            _ => None,
+        })
+        .collect();
+    warnings.extend(check_topology(deck).into_iter().map(LintWarning::from));
+    warnings.extend(check_parameters(deck).into_iter().map(LintWarning::from));
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParseOptions, parse};
+
+    fn lint(netlist: &str) -> Vec<LintWarning> {
+        let mut options = ParseOptions::new_with_source("lint.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        lint_deck(&deck)
+    }
+
+    #[test]
+    fn clean_deck_has_no_warnings() {
+        let warnings = lint("rc\nV1 in 0 1\nR1 in out 1k\nC1 out 0 1u\n.op\n.end\n");
+        assert!(warnings.is_empty(), "{warnings:?}");
+    }
+
+    #[test]
+    fn misspelled_node_is_dangling() {
+        let netlist = "typo\nV1 in 0 1\nR1 in out 1k\nR2 ouy 0 1k\n.op\n.end\n";
+        let warnings = lint(netlist);
+        let [
+            LintWarning::DanglingNode { node: out, .. },
+            LintWarning::DanglingNode { node: ouy, .. },
+        ] = warnings.as_slice()
+        else {
+            panic!("{warnings:?}");
+        };
+        assert_eq!((out.as_str(), ouy.as_str()), ("out", "ouy"));
+        assert_eq!(
+            warnings[0].to_string(),
+            "node out has a single connection (to R1)"
+        );
+        let span = warnings[1].span().expect("span");
+        assert_eq!(&netlist[span.start..=span.end], "R2 ouy 0 1k");
+    }
+
+    #[test]
+    fn topology_problems_are_warnings() {
+        let warnings = lint("loop\nV1 a 0 1\nV2 a 0 2\nR1 a 0 1k\n.op\n.end\n");
+        let [LintWarning::Topology(TopologyError::VoltageSourceLoop { devices, .. })] =
+            warnings.as_slice()
+        else {
+            panic!("{warnings:?}");
+        };
+        assert_eq!(devices, &["V1", "V2"]);
+        assert!(warnings[0].span().is_some());
+    }
+
+    #[test]
+    fn parameter_problems_are_warnings() {
+        let warnings = lint("short\nV1 a 0 1\nR1 a 0 0\n.op\n.end\n");
+        let [LintWarning::Parameter(error)] = warnings.as_slice() else {
+            panic!("{warnings:?}");
+        };
+        assert!(error.is_error());
+        assert_eq!(warnings[0].code(), "E0901");
+    }
+}
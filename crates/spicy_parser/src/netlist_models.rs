@@ -1,5 +1,8 @@
+//! `.MODEL` cards: collected while expanding subcircuits, evaluated into typed parameter sets,
+//! then looked up by name when instances are parsed.
+
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[cfg(test)]
 use crate::test_utils::serialize_sorted_map;
@@ -12,16 +15,45 @@ use crate::{
     statement_phase::{Statement, StmtCursor},
 };
 
+/// Every model of a deck, by name.
 #[derive(Debug, Default, Clone, Serialize)]
-pub(crate) struct ModelTable {
-    #[cfg_attr(test, serde(serialize_with = "serialize_sorted_map"))]
-    pub(crate) map: HashMap<String, DeviceModel>,
+pub struct ModelTable {
+    pub(crate) map: BTreeMap<String, DeviceModel>,
 }
 
 impl ModelTable {
-    pub(crate) fn get(&self, model: &str) -> Option<&DeviceModel> {
+    pub fn get(&self, model: &str) -> Option<&DeviceModel> {
         self.map.get(model)
     }
+
+    /// Models sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &DeviceModel)> {
+        self.map.iter().map(|(name, model)| (name.as_str(), model))
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Look up the model an instance references, checking it is a `T` model.
+    pub(crate) fn resolve<T: ModelKind>(&self, name: &Ident) -> Result<&T, ParserError> {
+        let model = self
+            .get(name.text)
+            .ok_or_else(|| ParserError::MissingModel {
+                model: name.text.to_string(),
+                span: name.span,
+            })?;
+        T::from_device_model(model).ok_or_else(|| ParserError::InvalidModel {
+            model: name.text.to_string(),
+            expected: T::KIND,
+            found: model.kind(),
+            span: name.span,
+        })
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize)]
@@ -59,7 +91,7 @@ impl ModelStatementTable {
                 model_statement_to_device_model(model_statement, source_map, placeholder_map, scope)
                     .map(|device_model| (name, device_model))
             })
-            .collect::<Result<HashMap<String, DeviceModel>, SpicyError>>()?;
+            .collect::<Result<BTreeMap<String, DeviceModel>, SpicyError>>()?;
 
         Ok(ModelTable { map })
     }
@@ -84,6 +116,20 @@ impl Default for BjtPolarity {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub enum MosfetPolarity {
+    #[default]
+    Nmos,
+    Pmos,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub enum JfetPolarity {
+    #[default]
+    Njf,
+    Pjf,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub(crate) enum DeviceModelType {
     Resistor,
@@ -91,6 +137,10 @@ pub(crate) enum DeviceModelType {
     Inductor,
     Diode,
     Bjt(BjtPolarity),
+    Mosfet(MosfetPolarity),
+    Jfet(JfetPolarity),
+    Switch,
+    CurrentSwitch,
 }
 
 impl DeviceModelType {
@@ -102,6 +152,12 @@ impl DeviceModelType {
             "D" => Ok(DeviceModelType::Diode),
             "NPN" => Ok(DeviceModelType::Bjt(BjtPolarity::Npn)),
             "PNP" => Ok(DeviceModelType::Bjt(BjtPolarity::Pnp)),
+            "NMOS" => Ok(DeviceModelType::Mosfet(MosfetPolarity::Nmos)),
+            "PMOS" => Ok(DeviceModelType::Mosfet(MosfetPolarity::Pmos)),
+            "NJF" => Ok(DeviceModelType::Jfet(JfetPolarity::Njf)),
+            "PJF" => Ok(DeviceModelType::Jfet(JfetPolarity::Pjf)),
+            "SW" => Ok(DeviceModelType::Switch),
+            "CSW" => Ok(DeviceModelType::CurrentSwitch),
             _ => Err(SubcircuitError::InvalidDeviceModelType {
                 s: s.to_string(),
                 span,
@@ -162,17 +218,33 @@ fn model_statement_to_device_model(
         DeviceModelType::Capacitor => DeviceModel::Capacitor(CapacitorModel::new(params)?),
         DeviceModelType::Inductor => DeviceModel::Inductor(InductorModel::new(params)?),
         DeviceModelType::Diode => DeviceModel::Diode(DiodeModel::new(params)?),
-        DeviceModelType::Bjt(polarity) => DeviceModel::Bjt(BjtModel::new(polarity, params)?),
+        DeviceModelType::Bjt(polarity) => {
+            DeviceModel::Bjt(Box::new(BjtModel::new(polarity, params)?))
+        }
+        DeviceModelType::Mosfet(polarity) => {
+            DeviceModel::Mosfet(MosfetModel::new(polarity, params)?)
+        }
+        DeviceModelType::Jfet(polarity) => {
+            DeviceModel::Jfet(Box::new(JfetModel::new(polarity, params)?))
+        }
+        DeviceModelType::Switch => DeviceModel::Switch(SwitchModel::new(params)?),
+        DeviceModelType::CurrentSwitch => {
+            DeviceModel::CurrentSwitch(CurrentSwitchModel::new(params)?)
+        }
     })
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub struct ResistorModel {
     pub resistance: Option<Value>,
     pub tc1: Option<Value>,
     pub tc2: Option<Value>,
     pub w: Option<Value>,
     pub l: Option<Value>,
+    /// thermal resistance (K/W) of a self-heating resistor
+    pub rth: Option<Value>,
+    /// thermal capacitance (J/K) of a self-heating resistor
+    pub cth: Option<Value>,
 }
 
 impl ResistorModel {
@@ -186,6 +258,8 @@ impl ResistorModel {
                 "tc2" => model.tc2 = Some(value),
                 "w" => model.w = Some(value),
                 "l" => model.l = Some(value),
+                "rth" => model.rth = Some(value),
+                "cth" => model.cth = Some(value),
                 _ => {
                     return Err(ParserError::InvalidParam {
                         param: ident.text.to_string(),
@@ -199,7 +273,7 @@ impl ResistorModel {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub struct CapacitorModel {
     pub cap: Option<Value>,
     pub tc1: Option<Value>,
@@ -228,7 +302,7 @@ impl CapacitorModel {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub struct InductorModel {
     pub inductance: Option<Value>,
     pub tc1: Option<Value>,
@@ -257,11 +331,19 @@ impl InductorModel {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub struct DiodeModel {
     pub is: Option<Value>,
     pub n: Option<Value>,
     pub rs: Option<Value>,
+    /// flicker noise coefficient
+    pub kf: Option<Value>,
+    /// flicker noise exponent
+    pub af: Option<Value>,
+    /// activation energy (eV) of the saturation current
+    pub eg: Option<Value>,
+    /// temperature exponent of the saturation current
+    pub xti: Option<Value>,
 }
 
 impl DiodeModel {
@@ -273,6 +355,10 @@ impl DiodeModel {
                 "is" => model.is = Some(value),
                 "n" => model.n = Some(value),
                 "rs" => model.rs = Some(value),
+                "kf" => model.kf = Some(value),
+                "af" => model.af = Some(value),
+                "eg" => model.eg = Some(value),
+                "xti" => model.xti = Some(value),
                 _ => {
                     return Err(ParserError::InvalidParam {
                         param: ident.text.to_string(),
@@ -286,7 +372,7 @@ impl DiodeModel {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub struct BjtModel {
     pub polarity: BjtPolarity,
     pub is: Option<Value>,
@@ -294,6 +380,60 @@ pub struct BjtModel {
     pub br: Option<Value>,
     pub nf: Option<Value>,
     pub nr: Option<Value>,
+    /// flicker noise coefficient
+    pub kf: Option<Value>,
+    /// flicker noise exponent
+    pub af: Option<Value>,
+    /// activation energy (eV) of the saturation current
+    pub eg: Option<Value>,
+    /// temperature exponent of the saturation current
+    pub xti: Option<Value>,
+    /// temperature exponent of the betas
+    pub xtb: Option<Value>,
+    /// forward Early voltage
+    pub vaf: Option<Value>,
+    /// reverse Early voltage
+    pub var: Option<Value>,
+    /// corner of the forward beta high-current roll-off
+    pub ikf: Option<Value>,
+    /// corner of the reverse beta high-current roll-off
+    pub ikr: Option<Value>,
+    /// base-emitter leakage saturation current
+    pub ise: Option<Value>,
+    /// base-emitter leakage emission coefficient
+    pub ne: Option<Value>,
+    /// base-collector leakage saturation current
+    pub isc: Option<Value>,
+    /// base-collector leakage emission coefficient
+    pub nc: Option<Value>,
+    /// base resistance
+    pub rb: Option<Value>,
+    /// collector resistance
+    pub rc: Option<Value>,
+    /// emitter resistance
+    pub re: Option<Value>,
+    /// forward transit time
+    pub tf: Option<Value>,
+    /// reverse transit time
+    pub tr: Option<Value>,
+    /// base-emitter zero-bias depletion capacitance
+    pub cje: Option<Value>,
+    /// base-emitter built-in potential
+    pub vje: Option<Value>,
+    /// base-emitter junction grading coefficient
+    pub mje: Option<Value>,
+    /// base-collector zero-bias depletion capacitance
+    pub cjc: Option<Value>,
+    /// base-collector built-in potential
+    pub vjc: Option<Value>,
+    /// base-collector junction grading coefficient
+    pub mjc: Option<Value>,
+    /// forward-bias depletion capacitance coefficient
+    pub fc: Option<Value>,
+    /// thermal resistance (K/W) of a self-heating transistor
+    pub rth: Option<Value>,
+    /// thermal capacitance (J/K) of a self-heating transistor
+    pub cth: Option<Value>,
 }
 
 impl BjtModel {
@@ -313,6 +453,33 @@ impl BjtModel {
                 "br" => model.br = Some(value),
                 "nf" => model.nf = Some(value),
                 "nr" => model.nr = Some(value),
+                "kf" => model.kf = Some(value),
+                "af" => model.af = Some(value),
+                "eg" => model.eg = Some(value),
+                "xti" => model.xti = Some(value),
+                "xtb" => model.xtb = Some(value),
+                "vaf" | "va" => model.vaf = Some(value),
+                "var" | "vb" => model.var = Some(value),
+                "ikf" | "ik" => model.ikf = Some(value),
+                "ikr" => model.ikr = Some(value),
+                "ise" => model.ise = Some(value),
+                "ne" => model.ne = Some(value),
+                "isc" => model.isc = Some(value),
+                "nc" => model.nc = Some(value),
+                "rb" => model.rb = Some(value),
+                "rc" => model.rc = Some(value),
+                "re" => model.re = Some(value),
+                "tf" => model.tf = Some(value),
+                "tr" => model.tr = Some(value),
+                "cje" => model.cje = Some(value),
+                "vje" | "pe" => model.vje = Some(value),
+                "mje" | "me" => model.mje = Some(value),
+                "cjc" => model.cjc = Some(value),
+                "vjc" | "pc" => model.vjc = Some(value),
+                "mjc" | "mc" => model.mjc = Some(value),
+                "fc" => model.fc = Some(value),
+                "rth" => model.rth = Some(value),
+                "cth" => model.cth = Some(value),
                 _ => {
                     return Err(ParserError::InvalidParam {
                         param: ident.text.to_string(),
@@ -326,11 +493,248 @@ impl BjtModel {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub(crate) enum DeviceModel {
+/// Level 1 (Shichman-Hodges) MOSFET parameters.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MosfetModel {
+    pub polarity: MosfetPolarity,
+    pub vto: Option<Value>,
+    pub kp: Option<Value>,
+    pub gamma: Option<Value>,
+    pub phi: Option<Value>,
+    pub lambda: Option<Value>,
+}
+
+impl MosfetModel {
+    pub(crate) fn new(
+        polarity: MosfetPolarity,
+        params: Vec<(Ident, Value)>,
+    ) -> Result<Self, SpicyError> {
+        let mut model = Self {
+            polarity,
+            ..Self::default()
+        };
+
+        for (ident, value) in params {
+            match ident.text {
+                // only level 1 is implemented
+                "level" if value.get_value() == 1.0 => {}
+                "vto" => model.vto = Some(value),
+                "kp" => model.kp = Some(value),
+                "gamma" => model.gamma = Some(value),
+                "phi" => model.phi = Some(value),
+                "lambda" => model.lambda = Some(value),
+                _ => {
+                    return Err(ParserError::InvalidParam {
+                        param: ident.text.to_string(),
+                        span: ident.span,
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(model)
+    }
+}
+
+/// Shichman-Hodges JFET parameters.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct JfetModel {
+    pub polarity: JfetPolarity,
+    /// threshold (pinch-off) voltage
+    pub vto: Option<Value>,
+    /// transconductance parameter
+    pub beta: Option<Value>,
+    /// channel-length modulation
+    pub lambda: Option<Value>,
+    /// drain resistance
+    pub rd: Option<Value>,
+    /// source resistance
+    pub rs: Option<Value>,
+    /// zero-bias gate-source junction capacitance
+    pub cgs: Option<Value>,
+    /// zero-bias gate-drain junction capacitance
+    pub cgd: Option<Value>,
+    /// gate junction potential
+    pub pb: Option<Value>,
+    /// gate junction saturation current
+    pub is: Option<Value>,
+    /// gate junction emission coefficient
+    pub n: Option<Value>,
+    /// forward-bias depletion capacitance coefficient
+    pub fc: Option<Value>,
+    /// flicker noise coefficient
+    pub kf: Option<Value>,
+    /// flicker noise exponent
+    pub af: Option<Value>,
+}
+
+impl JfetModel {
+    pub(crate) fn new(
+        polarity: JfetPolarity,
+        params: Vec<(Ident, Value)>,
+    ) -> Result<Self, SpicyError> {
+        let mut model = Self {
+            polarity,
+            ..Self::default()
+        };
+
+        for (ident, value) in params {
+            match ident.text {
+                "vto" => model.vto = Some(value),
+                "beta" => model.beta = Some(value),
+                "lambda" => model.lambda = Some(value),
+                "rd" => model.rd = Some(value),
+                "rs" => model.rs = Some(value),
+                "cgs" => model.cgs = Some(value),
+                "cgd" => model.cgd = Some(value),
+                "pb" => model.pb = Some(value),
+                "is" => model.is = Some(value),
+                "n" => model.n = Some(value),
+                "fc" => model.fc = Some(value),
+                "kf" => model.kf = Some(value),
+                "af" => model.af = Some(value),
+                _ => {
+                    return Err(ParserError::InvalidParam {
+                        param: ident.text.to_string(),
+                        span: ident.span,
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(model)
+    }
+}
+
+/// Voltage-controlled switch: it turns on when the control voltage rises above `vt + vh` and
+/// off when it falls below `vt - vh`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SwitchModel {
+    pub vt: Option<Value>,
+    pub vh: Option<Value>,
+    pub ron: Option<Value>,
+    pub roff: Option<Value>,
+}
+
+impl SwitchModel {
+    pub(crate) fn new(params: Vec<(Ident, Value)>) -> Result<Self, SpicyError> {
+        let mut model = Self::default();
+
+        for (ident, value) in params {
+            match ident.text {
+                "vt" => model.vt = Some(value),
+                "vh" => model.vh = Some(value),
+                "ron" => model.ron = Some(value),
+                "roff" => model.roff = Some(value),
+                _ => {
+                    return Err(ParserError::InvalidParam {
+                        param: ident.text.to_string(),
+                        span: ident.span,
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(model)
+    }
+}
+
+/// Current-controlled switch: like [`SwitchModel`], with the control current thresholds
+/// `it` and `ih`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CurrentSwitchModel {
+    pub it: Option<Value>,
+    pub ih: Option<Value>,
+    pub ron: Option<Value>,
+    pub roff: Option<Value>,
+}
+
+impl CurrentSwitchModel {
+    pub(crate) fn new(params: Vec<(Ident, Value)>) -> Result<Self, SpicyError> {
+        let mut model = Self::default();
+
+        for (ident, value) in params {
+            match ident.text {
+                "it" => model.it = Some(value),
+                "ih" => model.ih = Some(value),
+                "ron" => model.ron = Some(value),
+                "roff" => model.roff = Some(value),
+                _ => {
+                    return Err(ParserError::InvalidParam {
+                        param: ident.text.to_string(),
+                        span: ident.span,
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(model)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum DeviceModel {
     Resistor(ResistorModel),
     Capacitor(CapacitorModel),
     Inductor(InductorModel),
     Diode(DiodeModel),
-    Bjt(BjtModel),
+    Bjt(Box<BjtModel>),
+    Mosfet(MosfetModel),
+    Jfet(Box<JfetModel>),
+    Switch(SwitchModel),
+    CurrentSwitch(CurrentSwitchModel),
+}
+
+impl DeviceModel {
+    /// The device this model is for, as named in error messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DeviceModel::Resistor(_) => ResistorModel::KIND,
+            DeviceModel::Capacitor(_) => CapacitorModel::KIND,
+            DeviceModel::Inductor(_) => InductorModel::KIND,
+            DeviceModel::Diode(_) => DiodeModel::KIND,
+            DeviceModel::Bjt(_) => BjtModel::KIND,
+            DeviceModel::Mosfet(_) => MosfetModel::KIND,
+            DeviceModel::Jfet(_) => JfetModel::KIND,
+            DeviceModel::Switch(_) => SwitchModel::KIND,
+            DeviceModel::CurrentSwitch(_) => CurrentSwitchModel::KIND,
+        }
+    }
+}
+
+/// A model type instances can reference by name.
+pub(crate) trait ModelKind: Sized {
+    const KIND: &'static str;
+
+    fn from_device_model(model: &DeviceModel) -> Option<&Self>;
+}
+
+macro_rules! model_kind {
+    ($($model:ty => $variant:ident, $kind:literal;)*) => {
+        $(
+            impl ModelKind for $model {
+                const KIND: &'static str = $kind;
+
+                fn from_device_model(model: &DeviceModel) -> Option<&Self> {
+                    match model {
+                        // the deref turns a boxed model into a reference to it
+                        DeviceModel::$variant(model) => Some(model as &Self),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+model_kind! {
+    ResistorModel => Resistor, "resistor";
+    CapacitorModel => Capacitor, "capacitor";
+    InductorModel => Inductor, "inductor";
+    DiodeModel => Diode, "diode";
+    BjtModel => Bjt, "bjt";
+    MosfetModel => Mosfet, "mosfet";
+    JfetModel => Jfet, "jfet";
+    SwitchModel => Switch, "switch";
+    CurrentSwitchModel => CurrentSwitch, "current switch";
 }
@@ -12,10 +12,10 @@ use crate::{
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 pub struct NodeName(pub String);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct NodeIndex(pub usize);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct CurrentBranchIndex(pub usize);
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -31,7 +31,24 @@ pub enum CommandType {
     Subcircuit,
     Ends,
     Param,
+    Print,
+    Plot,
+    Ic,
+    Nodeset,
+    Noise,
+    Sp,
+    Step,
+    Temp,
+    Meas,
+    Four,
+    Save,
+    Global,
+    Options,
     End,
+    If,
+    ElseIf,
+    Else,
+    EndIf,
 }
 
 impl fmt::Display for CommandType {
@@ -48,7 +65,24 @@ impl fmt::Display for CommandType {
             CommandType::Subcircuit => "SUBCKT",
             CommandType::Ends => "ENDS",
             CommandType::Param => "PARAM",
+            CommandType::Print => "PRINT",
+            CommandType::Plot => "PLOT",
+            CommandType::Ic => "IC",
+            CommandType::Nodeset => "NODESET",
+            CommandType::Noise => "NOISE",
+            CommandType::Sp => "SP",
+            CommandType::Step => "STEP",
+            CommandType::Temp => "TEMP",
+            CommandType::Meas => "MEAS",
+            CommandType::Four => "FOUR",
+            CommandType::Save => "SAVE",
+            CommandType::Global => "GLOBAL",
+            CommandType::Options => "OPTIONS",
             CommandType::End => "END",
+            CommandType::If => "IF",
+            CommandType::ElseIf => "ELSEIF",
+            CommandType::Else => "ELSE",
+            CommandType::EndIf => "ENDIF",
         };
         f.write_str(command)
     }
@@ -70,42 +104,120 @@ impl FromStr for CommandType {
             "SUBCKT" | "subckt" => Ok(CommandType::Subcircuit),
             "ENDS" | "ends" => Ok(CommandType::Ends),
             "PARAM" | "param" => Ok(CommandType::Param),
+            "PRINT" | "print" => Ok(CommandType::Print),
+            "PLOT" | "plot" => Ok(CommandType::Plot),
+            "IC" | "ic" => Ok(CommandType::Ic),
+            "NODESET" | "nodeset" => Ok(CommandType::Nodeset),
+            "NOISE" | "noise" => Ok(CommandType::Noise),
+            "SP" | "sp" => Ok(CommandType::Sp),
+            "STEP" | "step" => Ok(CommandType::Step),
+            "TEMP" | "temp" => Ok(CommandType::Temp),
+            "MEAS" | "meas" | "MEASURE" | "measure" => Ok(CommandType::Meas),
+            "FOUR" | "four" => Ok(CommandType::Four),
+            "SAVE" | "save" | "PROBE" | "probe" => Ok(CommandType::Save),
+            "GLOBAL" | "global" => Ok(CommandType::Global),
+            "OPTIONS" | "options" | "OPTION" | "option" => Ok(CommandType::Options),
             "END" | "end" => Ok(CommandType::End),
+            "IF" | "if" => Ok(CommandType::If),
+            "ELSEIF" | "elseif" => Ok(CommandType::ElseIf),
+            "ELSE" | "else" => Ok(CommandType::Else),
+            "ENDIF" | "endif" => Ok(CommandType::EndIf),
             _ => Err(()),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct OpCommand {
     pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DcCommand {
     pub span: Span,
     pub srcnam: String,
     pub vstart: Value,
     pub vstop: Value,
     pub vincr: Value,
+    /// Optional outer sweep: the first source is swept once per value of this one.
+    pub src2: Option<DcSweep>,
 }
 
-#[derive(Debug, Clone)]
+/// The second source of a nested `.dc`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DcSweep {
+    pub srcnam: String,
+    pub vstart: Value,
+    pub vstop: Value,
+    pub vincr: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub enum AcSweepType {
     Dec(usize),
     Oct(usize),
     Lin(usize),
+    /// `list f1 f2 ...`: the frequencies as given, increasing
+    List(Vec<Value>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AcCommand {
     pub span: Span,
     pub ac_sweep_type: AcSweepType,
+    /// the first frequency, of a list too
     pub fstart: Value,
+    /// the last frequency, of a list too
     pub fstop: Value,
 }
 
-#[derive(Debug, Clone)]
+/// `.noise v(out[,ref]) src <ac sweep>`
+#[derive(Debug, Clone, Serialize)]
+pub struct NoiseCommand {
+    pub span: Span,
+    pub output: String,
+    pub reference: Option<String>,
+    /// the source the noise is referred to
+    pub input_source: String,
+    pub sweep: AcCommand,
+}
+
+/// `.sp <ac sweep> port1 [port2 ...] [z0=value]`: the S-parameters between the ports, every
+/// port a node referenced to ground.
+#[derive(Debug, Clone, Serialize)]
+pub struct SpCommand {
+    pub span: Span,
+    pub sweep: AcCommand,
+    pub ports: Vec<String>,
+    /// Reference impedance of every port (ohm), 50 by default.
+    pub z0: Value,
+}
+
+/// The values a `.step` gives its parameter.
+#[derive(Debug, Clone, Serialize)]
+pub enum StepSweep {
+    /// `start stop incr`
+    Linear {
+        start: Value,
+        stop: Value,
+        incr: Value,
+    },
+    /// `dec|oct n start stop`, spaced like the frequencies of `.ac`
+    Logarithmic(AcCommand),
+    /// `list value ...`
+    List(Vec<Value>),
+}
+
+/// `.step [lin|dec|oct] param name ...`: run every analysis once per value of a top-level
+/// `.param`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StepCommand {
+    pub span: Span,
+    pub param: String,
+    pub sweep: StepSweep,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct TranCommand {
     pub span: Span,
     /// printing or plotting increment for line-printer output.
@@ -113,16 +225,193 @@ pub struct TranCommand {
     pub tstep: Value,
     /// the final time for the simulation
     pub tstop: Value,
+    /// the time the output starts at; the analysis still starts at 0
+    pub tstart: Option<Value>,
+    /// the largest internal step
+    pub tmax: Option<Value>,
     /// use initial conditions
     pub uic: bool,
 }
 
-#[derive(Debug, Clone)]
+/// The analyses a `.print`/`.plot` can apply to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum AnalysisType {
+    Op,
+    Dc,
+    Ac,
+    Tran,
+}
+
+impl FromStr for AnalysisType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "OP" => Ok(AnalysisType::Op),
+            "DC" => Ok(AnalysisType::Dc),
+            "AC" => Ok(AnalysisType::Ac),
+            "TRAN" => Ok(AnalysisType::Tran),
+            _ => Err(()),
+        }
+    }
+}
+
+/// `.print` writes a table of its vectors to stdout; `.plot` only selects what is saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum OutputKind {
+    Print,
+    Plot,
+}
+
+/// A vector requested by `.print`/`.plot`, with the name as the deck spells it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum OutputVector {
+    /// `V(node)`
+    Voltage(String),
+    /// `I(device)`, for devices with a branch current (voltage sources, inductors)
+    Current(String),
+}
+
+/// `.print tran v(out) i(v1)`
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputSpec {
+    pub span: Span,
+    pub kind: OutputKind,
+    pub analysis: AnalysisType,
+    pub vectors: Vec<OutputVector>,
+}
+
+/// Which crossings of a level a `.meas` event counts, and which one it stops at (from 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum MeasureEdge {
+    /// `RISE=n`: the n-th time the vector rises through the level
+    Rise(usize),
+    /// `FALL=n`: the n-th time it falls through it
+    Fall(usize),
+    /// `CROSS=n`: the n-th time it crosses it either way
+    Cross(usize),
+}
+
+/// A point along the sweep of an analysis picked by a `.meas`.
+#[derive(Debug, Clone, Serialize)]
+pub enum MeasureEvent {
+    /// `AT=x`
+    At(Value),
+    /// `v(out) VAL=level RISE=n` in a `TRIG`/`TARG`, or `v(out)=level RISE=n` in a `WHEN`;
+    /// the first crossing when no edge is given
+    Crossing {
+        vector: OutputVector,
+        level: Value,
+        edge: MeasureEdge,
+    },
+}
+
+/// A function of a vector over a range of the sweep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum MeasureFunction {
+    Avg,
+    Rms,
+    Min,
+    Max,
+    /// peak to peak, `MAX - MIN`
+    Pp,
+    /// the integral over the sweep variable
+    Integ,
+}
+
+/// A figure of merit of a frequency response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ResponseMetric {
+    /// `UGF`: the frequency where the gain falls through 1 (0 dB)
+    UnityGainFrequency,
+    /// `PM`: 180 degrees plus the phase at the unity-gain frequency
+    PhaseMargin,
+    /// `GM`: how far the gain is below 0 dB (in dB) where the phase reaches -180 degrees
+    GainMargin,
+    /// `BW`: the frequency where the gain falls 3 dB below its value at the first frequency
+    Bandwidth,
+}
+
+/// What a `.meas` computes.
+#[derive(Debug, Clone, Serialize)]
+pub enum MeasureKind {
+    /// `TRIG <event> TARG <event>`: the distance from the first event to the second, e.g. a
+    /// rise time or a delay
+    TrigTarg {
+        trig: MeasureEvent,
+        targ: MeasureEvent,
+    },
+    /// `WHEN v(out)=level [RISE|FALL|CROSS=n]`: where the event happens
+    When(MeasureEvent),
+    /// `FIND v(out) AT=x`: the value of a vector at a point
+    FindAt { vector: OutputVector, at: Value },
+    /// `AVG|RMS|MIN|MAX|PP|INTEG v(out) [FROM=x] [TO=x]`, over the whole sweep by default
+    Function {
+        function: MeasureFunction,
+        vector: OutputVector,
+        from: Option<Value>,
+        to: Option<Value>,
+    },
+    /// `UGF|PM|GM|BW v(out) [v(in)]`: a figure of the `.ac` response from `v(in)` to `v(out)`,
+    /// or of `v(out)` alone for a unit AC source
+    Response {
+        metric: ResponseMetric,
+        output: OutputVector,
+        input: Option<OutputVector>,
+    },
+}
+
+/// `.meas tran trise TRIG v(out) VAL=0.1 RISE=1 TARG v(out) VAL=0.9 RISE=1`: a number computed
+/// from the vectors of every analysis of the kind once it finished.
+#[derive(Debug, Clone, Serialize)]
+pub struct MeasureCommand {
+    pub span: Span,
+    pub analysis: AnalysisType,
+    pub name: String,
+    pub kind: MeasureKind,
+}
+
+/// `.four 1k v(out) i(v1)`: the harmonics of the vectors over the last period of the
+/// fundamental frequency, after every transient analysis.
+#[derive(Debug, Clone, Serialize)]
+pub struct FourierCommand {
+    pub span: Span,
+    pub fundamental: Value,
+    pub vectors: Vec<OutputVector>,
+}
+
+/// `.save v(out) i(vdd)` (or `.probe`): the only vectors a transient analysis keeps, the others
+/// are discarded after every time step. `.save all` keeps every vector.
+#[derive(Debug, Clone, Serialize)]
+pub struct SaveCommand {
+    pub span: Span,
+    pub all: bool,
+    pub vectors: Vec<OutputVector>,
+}
+
+/// Simulator settings of the `.options` lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SimulatorOptions {
+    /// `savecurrents`: also output the current through every resistor, capacitor and diode.
+    pub save_currents: bool,
+}
+
+/// One `v(node)=value` of a `.ic` or `.nodeset` line.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeValue {
+    pub span: Span,
+    pub node: String,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub enum Command {
     Op(OpCommand),
     Dc(DcCommand),
     Ac(AcCommand),
     Tran(TranCommand),
+    Noise(NoiseCommand),
+    Sp(SpCommand),
     End,
 }
 
@@ -131,10 +420,18 @@ pub enum DeviceType {
     Resistor,
     Capacitor,
     Inductor,
+    MutualInductance,
     Diode,
     Bjt,
+    Mosfet,
+    Jfet,
     VoltageSource,
     CurrentSource,
+    BehavioralSource,
+    TransmissionLine,
+    Switch,
+    CurrentSwitch,
+    LookupTable,
     Subcircuit,
 }
 
@@ -144,10 +441,18 @@ impl DeviceType {
             'R' => Ok(DeviceType::Resistor),
             'C' => Ok(DeviceType::Capacitor),
             'L' => Ok(DeviceType::Inductor),
+            'K' => Ok(DeviceType::MutualInductance),
             'D' => Ok(DeviceType::Diode),
             'Q' => Ok(DeviceType::Bjt),
+            'M' => Ok(DeviceType::Mosfet),
+            'J' => Ok(DeviceType::Jfet),
             'V' => Ok(DeviceType::VoltageSource),
             'I' => Ok(DeviceType::CurrentSource),
+            'B' => Ok(DeviceType::BehavioralSource),
+            'T' => Ok(DeviceType::TransmissionLine),
+            'S' => Ok(DeviceType::Switch),
+            'W' => Ok(DeviceType::CurrentSwitch),
+            'A' => Ok(DeviceType::LookupTable),
             'X' => Ok(DeviceType::Subcircuit),
             _ => Err(ParserError::InvalidDeviceType { s: c.to_string() }.into()),
         }
@@ -158,10 +463,18 @@ impl DeviceType {
             DeviceType::Resistor => 'R',
             DeviceType::Capacitor => 'C',
             DeviceType::Inductor => 'L',
+            DeviceType::MutualInductance => 'K',
             DeviceType::Diode => 'D',
             DeviceType::Bjt => 'Q',
+            DeviceType::Mosfet => 'M',
+            DeviceType::Jfet => 'J',
             DeviceType::VoltageSource => 'V',
             DeviceType::CurrentSource => 'I',
+            DeviceType::BehavioralSource => 'B',
+            DeviceType::TransmissionLine => 'T',
+            DeviceType::Switch => 'S',
+            DeviceType::CurrentSwitch => 'W',
+            DeviceType::LookupTable => 'A',
             DeviceType::Subcircuit => 'X',
         }
     }
@@ -183,7 +496,7 @@ impl FromStr for DeviceType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Phasor {
     pub mag: Value,
     pub phase: Option<Value>,
@@ -232,6 +545,14 @@ impl ValueSuffix {
             ValueSuffix::Radian => 1.0,
         }
     }
+
+    /// The number of letters of the suffix, before any unit letters following it.
+    pub fn written_len(&self) -> usize {
+        match self {
+            ValueSuffix::Mega | ValueSuffix::Degree | ValueSuffix::Radian => 3,
+            _ => 1,
+        }
+    }
 }
 
 impl FromStr for ValueSuffix {
@@ -239,9 +560,10 @@ impl FromStr for ValueSuffix {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            s if s.starts_with("T") => Ok(ValueSuffix::Tera),
-            s if s.starts_with("G") => Ok(ValueSuffix::Giga),
-            s if s.starts_with("Meg") => Ok(ValueSuffix::Mega),
+            s if s.starts_with("T") || s.starts_with("t") => Ok(ValueSuffix::Tera),
+            s if s.starts_with("G") || s.starts_with("g") => Ok(ValueSuffix::Giga),
+            // before milli, which `m` alone is
+            s if s.get(..3).is_some_and(|p| p.eq_ignore_ascii_case("meg")) => Ok(ValueSuffix::Mega),
             s if s.starts_with("K") || s.starts_with("k") => Ok(ValueSuffix::Kilo),
             s if s.starts_with("m") || s.starts_with("M") => Ok(ValueSuffix::Milli),
             s if s.starts_with("u") || s.starts_with("U") => Ok(ValueSuffix::Micro),
@@ -255,3 +577,76 @@ impl FromStr for ValueSuffix {
         }
     }
 }
+
+/// The unit a value is written in, after its suffix: the `F` of `10uF`.
+///
+/// Units only annotate the value, which [`ValueSuffix`] alone scales.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Unit {
+    Volt,
+    Ampere,
+    Farad,
+    Henry,
+    Ohm,
+    Second,
+    Hertz,
+}
+
+impl Unit {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Volt => "V",
+            Unit::Ampere => "A",
+            Unit::Farad => "F",
+            Unit::Henry => "H",
+            Unit::Ohm => "Ohm",
+            Unit::Second => "s",
+            Unit::Hertz => "Hz",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Unit::Volt => "volts",
+            Unit::Ampere => "amperes",
+            Unit::Farad => "farads",
+            Unit::Henry => "henries",
+            Unit::Ohm => "ohms",
+            Unit::Second => "seconds",
+            Unit::Hertz => "hertz",
+        }
+    }
+
+    /// The suffix the unit's letter is on its own for simulators whose suffixes ignore case,
+    /// as ngspice reads `10F` as 10 femto.
+    pub fn suffix_reading(&self) -> Option<ValueSuffix> {
+        match self {
+            Unit::Farad => Some(ValueSuffix::Femto),
+            Unit::Ampere => Some(ValueSuffix::Atto),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+impl FromStr for Unit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "v" | "volt" | "volts" => Ok(Unit::Volt),
+            "a" | "amp" | "amps" | "ampere" | "amperes" => Ok(Unit::Ampere),
+            "f" | "farad" | "farads" => Ok(Unit::Farad),
+            "h" | "henry" | "henries" | "henrys" => Ok(Unit::Henry),
+            "ohm" | "ohms" => Ok(Unit::Ohm),
+            "s" | "sec" | "second" | "seconds" => Ok(Unit::Second),
+            "hz" | "hertz" => Ok(Unit::Hertz),
+            _ => Err(()),
+        }
+    }
+}
@@ -1,8 +1,9 @@
+use serde::Serialize;
 use std::f64::consts::PI;
 
 use crate::expr::Value;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum WaveForm {
     Pulse {
         /// v1 (volts, amps)
@@ -50,9 +51,50 @@ pub enum WaveForm {
         /// TAU2 (seconds)
         fall_time_constant: Option<Value>,
     },
+    PiecewiseLinear {
+        /// (Ti, Vi) corners, in order of time (seconds; volts, amps)
+        points: Vec<(Value, Value)>,
+        /// R: the time the waveform repeats from after the last corner (seconds)
+        repeat: Option<Value>,
+        /// TD (seconds)
+        delay: Option<Value>,
+    },
+    SingleFrequencyFm {
+        /// VO (volts, amps)
+        offset: Value,
+        /// VA (volts, amps)
+        amplitude: Value,
+        /// FC carrier frequency (Hz)
+        carrier_frequency: Option<Value>,
+        /// MDI modulation index
+        modulation_index: Option<Value>,
+        /// FS signal frequency (Hz)
+        signal_frequency: Option<Value>,
+    },
     Constant(Value),
 }
 
+/// Linear interpolation between the PWL corners, holding the first and last values outside them.
+fn interpolate(points: &[(f64, f64)], t: f64) -> f64 {
+    let Some(&(t_first, v_first)) = points.first() else {
+        return 0.0;
+    };
+    if t <= t_first {
+        return v_first;
+    }
+    for window in points.windows(2) {
+        let (t0, v0) = window[0];
+        let (t1, v1) = window[1];
+        if t <= t1 {
+            if t1 > t0 {
+                return v0 + (v1 - v0) * (t - t0) / (t1 - t0);
+            }
+            return v1;
+        }
+    }
+    points[points.len() - 1].1
+}
+
 impl WaveForm {
     pub fn compute(&self, t: f64, dt: f64, time_stop: f64) -> f64 {
         match self {
@@ -189,7 +231,250 @@ impl WaveForm {
                 v1 + v21 * (1. - f64::exp(-(t - td1) / tau1))
                     + v12 * (1. - f64::exp(-(t - td2) / tau2))
             }
+            WaveForm::PiecewiseLinear {
+                points,
+                repeat,
+                delay,
+            } => {
+                let points: Vec<(f64, f64)> = points
+                    .iter()
+                    .map(|(time, value)| (time.get_value(), value.get_value()))
+                    .collect();
+                let td = delay.as_ref().unwrap_or(&Value::zero()).get_value();
+                let mut t = t - td;
+
+                let t_last = points.last().map_or(0.0, |(time, _)| *time);
+                if let Some(repeat) = repeat {
+                    let r = repeat.get_value();
+                    // repeat the corners from R to the last one for as long as the run lasts
+                    if t > t_last && t_last > r {
+                        t = r + (t - r).rem_euclid(t_last - r);
+                    }
+                }
+
+                interpolate(&points, t)
+            }
+            WaveForm::SingleFrequencyFm {
+                offset,
+                amplitude,
+                carrier_frequency,
+                modulation_index,
+                signal_frequency,
+            } => {
+                let v0 = offset.get_value();
+                let va = amplitude.get_value();
+                let fc = carrier_frequency
+                    .as_ref()
+                    .unwrap_or(&Value::new(1.0 / time_stop, None, None))
+                    .get_value();
+                let mdi = modulation_index
+                    .as_ref()
+                    .unwrap_or(&Value::zero())
+                    .get_value();
+                let fs = signal_frequency
+                    .as_ref()
+                    .unwrap_or(&Value::new(1.0 / time_stop, None, None))
+                    .get_value();
+
+                v0 + va * f64::sin(2.0 * PI * fc * t + mdi * f64::sin(2.0 * PI * fs * t))
+            }
             WaveForm::Constant(value) => value.get_value(),
         }
     }
+
+    /// The first corner of the waveform after `t`, where its slope jumps and a transient step
+    /// should land (a PULSE edge, a PWL point, an EXP delay). `None` when there is no corner
+    /// left, or the waveform is smooth. `dt` and `time_stop` fill in the same defaults as
+    /// [`WaveForm::compute`].
+    pub fn next_breakpoint(&self, t: f64, dt: f64, time_stop: f64) -> Option<f64> {
+        let after = |corners: &mut dyn Iterator<Item = f64>| {
+            corners.filter(|&corner| corner > t).reduce(f64::min)
+        };
+        let or =
+            |value: &Option<Value>, default: f64| value.as_ref().map_or(default, Value::get_value);
+        match self {
+            WaveForm::Pulse {
+                delay,
+                rise_time,
+                fall_time,
+                pulse_width,
+                period,
+                number_of_pulses,
+                ..
+            } => {
+                let td = or(delay, 0.0);
+                let tr = or(rise_time, dt);
+                let tf = or(fall_time, dt);
+                let pw = or(pulse_width, time_stop);
+                let per = or(period, time_stop);
+                let np = number_of_pulses.unwrap_or(0);
+                // the corners of this period and the next
+                let first = if t < td || per <= 0.0 {
+                    0
+                } else {
+                    ((t - td) / per).floor() as u64
+                };
+                let offsets = [0.0, tr, tr + pw, tr + pw + tf];
+                after(
+                    &mut (first..first + 2)
+                        .filter(|&k| np == 0 || k < np)
+                        .flat_map(|k| offsets.map(|offset| td + k as f64 * per + offset)),
+                )
+            }
+            WaveForm::PiecewiseLinear {
+                points,
+                repeat,
+                delay,
+            } => {
+                let td = or(delay, 0.0);
+                let times: Vec<f64> = points.iter().map(|(time, _)| time.get_value()).collect();
+                let t_last = times.last().copied()?;
+                if let Some(corner) = after(&mut times.iter().map(|time| td + time)) {
+                    return Some(corner);
+                }
+                // past the last corner the ones from R on come around again
+                let r = repeat.as_ref()?.get_value();
+                let cycle = t_last - r;
+                if cycle <= 0.0 {
+                    return None;
+                }
+                let first = ((t - td - r) / cycle).floor().max(0.0);
+                after(&mut [first, first + 1.0].into_iter().flat_map(|k| {
+                    times
+                        .iter()
+                        .filter(|&&time| time >= r)
+                        .map(move |time| td + time + k * cycle)
+                }))
+            }
+            WaveForm::Exponential {
+                rise_delay_time,
+                fall_delay_time,
+                ..
+            } => {
+                let td1 = or(rise_delay_time, 0.0);
+                let td2 = or(fall_delay_time, td1 + dt);
+                after(&mut [td1, td2].into_iter())
+            }
+            WaveForm::Sinusoidal { .. }
+            | WaveForm::SingleFrequencyFm { .. }
+            | WaveForm::Constant(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(v: f64) -> Value {
+        Value::new(v, None, None)
+    }
+
+    fn pwl(points: &[(f64, f64)], repeat: Option<f64>, delay: Option<f64>) -> WaveForm {
+        WaveForm::PiecewiseLinear {
+            points: points.iter().map(|(t, v)| (value(*t), value(*v))).collect(),
+            repeat: repeat.map(value),
+            delay: delay.map(value),
+        }
+    }
+
+    #[test]
+    fn pwl_interpolates_between_corners_and_holds_outside_them() {
+        let waveform = pwl(&[(1.0, 0.0), (2.0, 4.0), (4.0, 2.0)], None, None);
+        let at = |t: f64| waveform.compute(t, 0.1, 10.0);
+
+        assert_eq!(at(0.0), 0.0);
+        assert_eq!(at(1.5), 2.0);
+        assert_eq!(at(3.0), 3.0);
+        assert_eq!(at(8.0), 2.0);
+    }
+
+    #[test]
+    fn pwl_repeats_from_r_and_starts_after_td() {
+        // a triangle of period 2 once it reaches its first corner at t=1
+        let waveform = pwl(
+            &[(0.0, 0.0), (1.0, 0.0), (2.0, 1.0), (3.0, 0.0)],
+            Some(1.0),
+            None,
+        );
+        let at = |t: f64| waveform.compute(t, 0.1, 10.0);
+        assert_eq!(at(2.0), 1.0);
+        assert_eq!(at(3.5), 0.5);
+        assert_eq!(at(4.0), 1.0);
+        assert_eq!(at(7.0), 0.0);
+
+        let delayed = pwl(&[(0.0, 0.0), (1.0, 1.0)], None, Some(2.0));
+        assert_eq!(delayed.compute(2.5, 0.1, 10.0), 0.5);
+        assert_eq!(delayed.compute(1.0, 0.1, 10.0), 0.0);
+    }
+
+    #[test]
+    fn pulse_breakpoints_are_its_edges_in_every_period() {
+        let pulse = |number_of_pulses| WaveForm::Pulse {
+            voltage1: value(0.0),
+            voltage2: value(1.0),
+            delay: Some(value(1.0)),
+            rise_time: Some(value(0.1)),
+            fall_time: Some(value(0.2)),
+            pulse_width: Some(value(0.5)),
+            period: Some(value(2.0)),
+            number_of_pulses,
+        };
+        let next = |waveform: &WaveForm, t: f64| waveform.next_breakpoint(t, 0.01, 10.0);
+
+        let endless = pulse(None);
+        assert_eq!(next(&endless, 0.0), Some(1.0));
+        assert_eq!(next(&endless, 1.0), Some(1.1));
+        assert_eq!(next(&endless, 1.7), Some(1.8));
+        assert_eq!(next(&endless, 1.8), Some(3.0));
+        assert_eq!(next(&endless, 3.05), Some(3.1));
+
+        // nothing moves after the last pulse
+        assert_eq!(next(&pulse(Some(1)), 1.8), None);
+    }
+
+    #[test]
+    fn pwl_breakpoints_repeat_with_the_waveform() {
+        let waveform = pwl(
+            &[(0.0, 0.0), (1.0, 0.0), (2.0, 1.0), (3.0, 0.0)],
+            Some(1.0),
+            None,
+        );
+        let next = |t: f64| waveform.next_breakpoint(t, 0.1, 10.0);
+        assert_eq!(next(0.5), Some(1.0));
+        assert_eq!(next(3.0), Some(4.0));
+        assert_eq!(next(4.5), Some(5.0));
+
+        let once = pwl(&[(0.0, 0.0), (1.0, 1.0)], None, Some(2.0));
+        assert_eq!(once.next_breakpoint(2.5, 0.1, 10.0), Some(3.0));
+        assert_eq!(once.next_breakpoint(3.0, 0.1, 10.0), None);
+        assert_eq!(
+            WaveForm::Constant(value(1.0)).next_breakpoint(0.0, 0.1, 10.0),
+            None
+        );
+    }
+
+    #[test]
+    fn sffm_modulates_the_carrier_phase() {
+        let waveform = WaveForm::SingleFrequencyFm {
+            offset: value(1.0),
+            amplitude: value(2.0),
+            carrier_frequency: Some(value(1e3)),
+            modulation_index: Some(value(5.0)),
+            signal_frequency: Some(value(100.0)),
+        };
+        let t = 0.3e-3;
+        let expected = 1.0 + 2.0 * (2.0 * PI * 1e3 * t + 5.0 * (2.0 * PI * 100.0 * t).sin()).sin();
+        assert!((waveform.compute(t, 1e-6, 1e-2) - expected).abs() < 1e-12);
+
+        // without a modulation index it is a plain sine at the carrier frequency
+        let unmodulated = WaveForm::SingleFrequencyFm {
+            offset: value(0.0),
+            amplitude: value(1.0),
+            carrier_frequency: Some(value(1e3)),
+            modulation_index: None,
+            signal_frequency: None,
+        };
+        assert!((unmodulated.compute(0.25e-3, 1e-6, 1e-2) - 1.0).abs() < 1e-12);
+    }
 }
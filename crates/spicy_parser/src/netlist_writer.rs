@@ -0,0 +1,1003 @@
+//! Writing a parsed [`Deck`] back out as a netlist, e.g. to export the flattened circuit of a
+//! deck with subcircuits or to generate a netlist from code.
+//!
+//! The netlist is canonical rather than a copy of the input: the devices come grouped by
+//! kind, every value is evaluated, and every `.model` card is written once. A device inside a
+//! subcircuit instance takes its letter in front of its path, as in ngspice: `X1.R1` is
+//! written as `R.X1.R1`. A deck holds no `.param`s, so the `.step` sweeps over them are left
+//! out.
+
+use std::borrow::Cow;
+use std::fmt::Write as _;
+
+use crate::devices::{BehavioralExpr, BehavioralKind, BehavioralOp, SwitchControl};
+use crate::expr::Value;
+use crate::instance_parser::Deck;
+use crate::netlist_models::{
+    BjtPolarity, DeviceModel, JfetPolarity, MosfetPolarity, ResistorModel,
+};
+use crate::netlist_types::{
+    AcCommand, AcSweepType, AnalysisType, Command, MeasureEdge, MeasureEvent, MeasureFunction,
+    MeasureKind, NodeIndex, NodeValue, OutputKind, OutputVector, ResponseMetric, Unit, ValueSuffix,
+};
+use crate::netlist_waveform::WaveForm;
+use crate::node_mapping::HIERARCHY_SEPARATOR;
+
+impl Deck {
+    /// The deck as a netlist, which parses back into an equivalent deck.
+    pub fn to_netlist(&self) -> String {
+        NetlistWriter::new(self).write()
+    }
+}
+
+struct NetlistWriter<'d> {
+    deck: &'d Deck,
+    /// Node names by index, ground first.
+    nodes: Vec<String>,
+    /// The models to write: the deck's, then any model of a device the deck does not hold.
+    models: Vec<(String, DeviceModel)>,
+}
+
+impl<'d> NetlistWriter<'d> {
+    fn new(deck: &'d Deck) -> Self {
+        let mut nodes = vec!["0".to_string()];
+        nodes.extend(deck.node_mapping.node_names_mna_order());
+        let models = deck
+            .models
+            .iter()
+            .map(|(name, model)| (name.to_string(), model.clone()))
+            .collect();
+        Self {
+            deck,
+            nodes,
+            models,
+        }
+    }
+
+    fn write(mut self) -> String {
+        let mut devices = String::new();
+        self.write_devices(&mut devices);
+
+        let mut out = String::new();
+        let _ = writeln!(out, "{}", self.deck.title);
+        out.push_str(&devices);
+        for (name, model) in &self.models {
+            let _ = writeln!(out, "{}", model_card(name, model));
+        }
+        self.write_cards(&mut out);
+        for command in &self.deck.commands {
+            if let Some(line) = self.command(command) {
+                let _ = writeln!(out, "{line}");
+            }
+        }
+        out.push_str(".end\n");
+        out
+    }
+
+    fn node(&self, node: NodeIndex) -> &str {
+        &self.nodes[node.0]
+    }
+
+    /// The name of the `.model` card equal to `model`, adding one if there is none.
+    fn model_name(&mut self, model: DeviceModel) -> String {
+        if let Some((name, _)) = self.models.iter().find(|(_, m)| *m == model) {
+            return name.clone();
+        }
+        let mut n = self.models.len() + 1;
+        let name = loop {
+            let name = format!("model{n}");
+            if self.models.iter().all(|(taken, _)| *taken != name) {
+                break name;
+            }
+            n += 1;
+        };
+        self.models.push((name.clone(), model));
+        name
+    }
+
+    fn write_devices(&mut self, out: &mut String) {
+        let devices = &self.deck.devices;
+
+        for r in &devices.resistors {
+            let mut line = format!(
+                "{} {} {}",
+                device_name(&r.name),
+                self.node(r.positive),
+                self.node(r.negative)
+            );
+            if let Some(resistance) = &r.resistance {
+                let _ = write!(line, " {}", value(resistance));
+            }
+            if let Some(model) = &r.model {
+                let _ = write!(
+                    line,
+                    " {}",
+                    self.model_name(DeviceModel::Resistor(model.clone()))
+                );
+            }
+            param(&mut line, "ac", &r.ac);
+            param(&mut line, "m", &r.m);
+            param(&mut line, "scale", &r.scale);
+            param(&mut line, "temp", &r.temp);
+            param(&mut line, "dtemp", &r.dtemp);
+            param(&mut line, "tc1", &r.tc1);
+            param(&mut line, "tc2", &r.tc2);
+            if let Some(noisy) = r.noisy {
+                let _ = write!(line, " noisy={}", u8::from(noisy));
+            }
+            param(&mut line, "rth", &r.rth);
+            param(&mut line, "cth", &r.cth);
+            let _ = writeln!(out, "{line}");
+        }
+
+        for c in &devices.capacitors {
+            let mut line = format!(
+                "{} {} {}",
+                device_name(&c.name),
+                self.node(c.positive),
+                self.node(c.negative)
+            );
+            if let Some(capacitance) = &c.capacitance {
+                let _ = write!(line, " {}", value(capacitance));
+            }
+            if let Some(model) = &c.model {
+                let model = DeviceModel::Capacitor(model.clone());
+                let _ = write!(line, " {}", self.model_name(model));
+            }
+            param(&mut line, "m", &c.m);
+            param(&mut line, "scale", &c.scale);
+            param(&mut line, "temp", &c.temp);
+            param(&mut line, "dtemp", &c.dtemp);
+            param(&mut line, "tc1", &c.tc1);
+            param(&mut line, "tc2", &c.tc2);
+            param(&mut line, "ic", &c.ic);
+            let _ = writeln!(out, "{line}");
+        }
+
+        for l in &devices.inductors {
+            let mut line = format!(
+                "{} {} {}",
+                device_name(&l.name),
+                self.node(l.positive),
+                self.node(l.negative)
+            );
+            if let Some(inductance) = &l.inductance {
+                let _ = write!(line, " {}", value(inductance));
+            }
+            if let Some(model) = &l.model {
+                let model = DeviceModel::Inductor(model.clone());
+                let _ = write!(line, " {}", self.model_name(model));
+            }
+            param(&mut line, "nt", &l.nt);
+            param(&mut line, "m", &l.m);
+            param(&mut line, "scale", &l.scale);
+            param(&mut line, "temp", &l.temp);
+            param(&mut line, "dtemp", &l.dtemp);
+            param(&mut line, "tc1", &l.tc1);
+            param(&mut line, "tc2", &l.tc2);
+            param(&mut line, "ic", &l.ic);
+            let _ = writeln!(out, "{line}");
+        }
+
+        for k in &devices.mutual_inductances {
+            let _ = writeln!(
+                out,
+                "{} {} {} {}",
+                device_name(&k.name),
+                device_name(&k.inductor1),
+                device_name(&k.inductor2),
+                value(&k.coupling)
+            );
+        }
+
+        for d in &devices.diodes {
+            let model = self.model_name(DeviceModel::Diode(d.model.clone()));
+            let mut line = format!(
+                "{} {} {} {model}",
+                device_name(&d.name),
+                self.node(d.positive),
+                self.node(d.negative)
+            );
+            param(&mut line, "area", &d.area);
+            param(&mut line, "m", &d.m);
+            param(&mut line, "pj", &d.pj);
+            if d.off == Some(true) {
+                line.push_str(" off");
+            }
+            param(&mut line, "ic", &d.ic);
+            param(&mut line, "temp", &d.temp);
+            param(&mut line, "dtemp", &d.dtemp);
+            param(&mut line, "lm", &d.lm);
+            param(&mut line, "wm", &d.wm);
+            param(&mut line, "lp", &d.lp);
+            param(&mut line, "wp", &d.wp);
+            let _ = writeln!(out, "{line}");
+        }
+
+        for source in devices
+            .voltage_sources
+            .iter()
+            .chain(&devices.current_sources)
+        {
+            let mut line = format!(
+                "{} {} {}",
+                device_name(&source.name),
+                self.node(source.positive),
+                self.node(source.negative)
+            );
+            if let Some(dc) = &source.dc {
+                let _ = write!(line, " {}", waveform(dc));
+            }
+            if let Some(ac) = &source.ac {
+                let _ = write!(line, " AC {}", value(&ac.mag));
+                if let Some(phase) = &ac.phase {
+                    let _ = write!(line, " {}", value(phase));
+                }
+            }
+            let _ = writeln!(out, "{line}");
+        }
+
+        for q in &devices.bjts {
+            let model = self.model_name(DeviceModel::Bjt(Box::new(q.model.clone())));
+            let mut line = format!(
+                "{} {} {} {} {model}",
+                device_name(&q.name),
+                self.node(q.collector),
+                self.node(q.base),
+                self.node(q.emitter)
+            );
+            param(&mut line, "area", &q.area);
+            param(&mut line, "m", &q.m);
+            if q.off == Some(true) {
+                line.push_str(" off");
+            }
+            initial_condition(&mut line, &[&q.ic_vbe, &q.ic_vce]);
+            let _ = writeln!(out, "{line}");
+        }
+
+        for m in &devices.mosfets {
+            let model = self.model_name(DeviceModel::Mosfet(m.model.clone()));
+            let mut line = format!(
+                "{} {} {} {} {} {model}",
+                device_name(&m.name),
+                self.node(m.drain),
+                self.node(m.gate),
+                self.node(m.source),
+                self.node(m.bulk)
+            );
+            param(&mut line, "m", &m.m);
+            param(&mut line, "l", &m.l);
+            param(&mut line, "w", &m.w);
+            if m.off == Some(true) {
+                line.push_str(" off");
+            }
+            initial_condition(&mut line, &[&m.ic_vds, &m.ic_vgs, &m.ic_vbs]);
+            let _ = writeln!(out, "{line}");
+        }
+
+        for j in &devices.jfets {
+            let model = self.model_name(DeviceModel::Jfet(Box::new(j.model.clone())));
+            let mut line = format!(
+                "{} {} {} {} {model}",
+                device_name(&j.name),
+                self.node(j.drain),
+                self.node(j.gate),
+                self.node(j.source)
+            );
+            param(&mut line, "area", &j.area);
+            if j.off == Some(true) {
+                line.push_str(" off");
+            }
+            initial_condition(&mut line, &[&j.ic_vds, &j.ic_vgs]);
+            let _ = writeln!(out, "{line}");
+        }
+
+        for b in &devices.behavioral_sources {
+            let kind = match b.kind {
+                BehavioralKind::Voltage => "V",
+                BehavioralKind::Current => "I",
+            };
+            let _ = writeln!(
+                out,
+                "{} {} {} {kind}={}",
+                device_name(&b.name),
+                self.node(b.positive),
+                self.node(b.negative),
+                self.behavioral_expr(&b.expr)
+            );
+        }
+
+        for t in &devices.transmission_lines {
+            let _ = writeln!(
+                out,
+                "{} {} {} {} {} Z0={} TD={}",
+                device_name(&t.name),
+                self.node(t.positive1),
+                self.node(t.negative1),
+                self.node(t.positive2),
+                self.node(t.negative2),
+                value(&t.z0),
+                value(&t.td)
+            );
+        }
+
+        for s in &devices.switches {
+            let control = match &s.control {
+                SwitchControl::Voltage {
+                    positive,
+                    negative,
+                    model,
+                } => {
+                    let model = self.model_name(DeviceModel::Switch(model.clone()));
+                    format!("{} {} {model}", self.node(*positive), self.node(*negative))
+                }
+                SwitchControl::Current { name, model, .. } => {
+                    let model = self.model_name(DeviceModel::CurrentSwitch(model.clone()));
+                    format!("{} {model}", device_name(name))
+                }
+            };
+            let _ = writeln!(
+                out,
+                "{} {} {} {control} {}",
+                device_name(&s.name),
+                self.node(s.positive),
+                self.node(s.negative),
+                if s.on { "ON" } else { "OFF" }
+            );
+        }
+
+        for a in &devices.lookup_tables {
+            let _ = writeln!(
+                out,
+                "{} {} {} {}",
+                device_name(&a.name),
+                self.node(a.positive),
+                self.node(a.negative),
+                a.path.display()
+            );
+        }
+    }
+
+    fn behavioral_expr(&self, expr: &BehavioralExpr) -> String {
+        match expr {
+            BehavioralExpr::Constant(constant) => number(*constant),
+            BehavioralExpr::Voltage(node) => format!("V({})", self.node(*node)),
+            BehavioralExpr::Current { name, .. } => format!("I({})", device_name(name)),
+            BehavioralExpr::Time => "time".to_string(),
+            BehavioralExpr::Negate(operand) => format!("(-{})", self.behavioral_expr(operand)),
+            BehavioralExpr::Binary { op, left, right } => {
+                let op = match op {
+                    BehavioralOp::Add => "+",
+                    BehavioralOp::Sub => "-",
+                    BehavioralOp::Mul => "*",
+                    BehavioralOp::Div => "/",
+                    BehavioralOp::Less => "<",
+                    BehavioralOp::Greater => ">",
+                };
+                format!(
+                    "({}{op}{})",
+                    self.behavioral_expr(left),
+                    self.behavioral_expr(right)
+                )
+            }
+            BehavioralExpr::Call { function, args } => {
+                let args: Vec<_> = args.iter().map(|arg| self.behavioral_expr(arg)).collect();
+                format!("{}({})", function.name(), args.join(", "))
+            }
+        }
+    }
+
+    /// The `.options`, `.temp`, `.ic`, `.nodeset`, `.save`, `.print`/`.plot`, `.meas` and
+    /// `.four` lines.
+    fn write_cards(&self, out: &mut String) {
+        let deck = self.deck;
+        if deck.options.save_currents {
+            out.push_str(".options savecurrents\n");
+        }
+        if !deck.temperatures.is_empty() {
+            let temperatures: Vec<_> = deck.temperatures.iter().map(value).collect();
+            let _ = writeln!(out, ".temp {}", temperatures.join(" "));
+        }
+        for (card, values) in [
+            (".ic", &deck.initial_conditions),
+            (".nodeset", &deck.nodesets),
+        ] {
+            if !values.is_empty() {
+                let _ = writeln!(out, "{card} {}", node_values(values));
+            }
+        }
+        for save in &deck.saves {
+            let mut line = ".save".to_string();
+            if save.all {
+                line.push_str(" all");
+            }
+            for vector in &save.vectors {
+                let _ = write!(line, " {}", output_vector(vector));
+            }
+            let _ = writeln!(out, "{line}");
+        }
+        for output in &deck.outputs {
+            let card = match output.kind {
+                OutputKind::Print => ".print",
+                OutputKind::Plot => ".plot",
+            };
+            let mut line = format!("{card} {}", analysis(output.analysis));
+            for vector in &output.vectors {
+                let _ = write!(line, " {}", output_vector(vector));
+            }
+            let _ = writeln!(out, "{line}");
+        }
+        for measure in &deck.measures {
+            let kind = match &measure.kind {
+                MeasureKind::TrigTarg { trig, targ } => {
+                    let (trig, targ) = (measure_event(trig, false), measure_event(targ, false));
+                    format!("TRIG {trig} TARG {targ}")
+                }
+                MeasureKind::When(event) => format!("WHEN {}", measure_event(event, true)),
+                MeasureKind::FindAt { vector, at } => {
+                    format!("FIND {} AT={}", output_vector(vector), value(at))
+                }
+                MeasureKind::Function {
+                    function,
+                    vector,
+                    from,
+                    to,
+                } => {
+                    let function = match function {
+                        MeasureFunction::Avg => "AVG",
+                        MeasureFunction::Rms => "RMS",
+                        MeasureFunction::Min => "MIN",
+                        MeasureFunction::Max => "MAX",
+                        MeasureFunction::Pp => "PP",
+                        MeasureFunction::Integ => "INTEG",
+                    };
+                    let mut kind = format!("{function} {}", output_vector(vector));
+                    param(&mut kind, "FROM", from);
+                    param(&mut kind, "TO", to);
+                    kind
+                }
+                MeasureKind::Response {
+                    metric,
+                    output,
+                    input,
+                } => {
+                    let metric = match metric {
+                        ResponseMetric::UnityGainFrequency => "UGF",
+                        ResponseMetric::PhaseMargin => "PM",
+                        ResponseMetric::GainMargin => "GM",
+                        ResponseMetric::Bandwidth => "BW",
+                    };
+                    let mut kind = format!("{metric} {}", output_vector(output));
+                    if let Some(input) = input {
+                        let _ = write!(kind, " {}", output_vector(input));
+                    }
+                    kind
+                }
+            };
+            let _ = writeln!(
+                out,
+                ".meas {} {} {kind}",
+                analysis(measure.analysis),
+                measure.name
+            );
+        }
+        for fourier in &deck.fourier {
+            let mut line = format!(".four {}", value(&fourier.fundamental));
+            for vector in &fourier.vectors {
+                let _ = write!(line, " {}", output_vector(vector));
+            }
+            let _ = writeln!(out, "{line}");
+        }
+    }
+
+    fn command(&self, command: &Command) -> Option<String> {
+        let line = match command {
+            Command::Op(_) => ".op".to_string(),
+            Command::Dc(dc) => {
+                let mut line = format!(
+                    ".dc {} {} {} {}",
+                    device_name(&dc.srcnam),
+                    value(&dc.vstart),
+                    value(&dc.vstop),
+                    value(&dc.vincr)
+                );
+                if let Some(src2) = &dc.src2 {
+                    let _ = write!(
+                        line,
+                        " {} {} {} {}",
+                        device_name(&src2.srcnam),
+                        value(&src2.vstart),
+                        value(&src2.vstop),
+                        value(&src2.vincr)
+                    );
+                }
+                line
+            }
+            Command::Ac(ac) => format!(".ac {}", ac_sweep(ac)),
+            Command::Tran(tran) => {
+                let mut line = format!(".tran {} {}", value(&tran.tstep), value(&tran.tstop));
+                match (&tran.tstart, &tran.tmax) {
+                    (tstart, Some(tmax)) => {
+                        let tstart = tstart.as_ref().map_or("0".to_string(), value);
+                        let _ = write!(line, " {tstart} {}", value(tmax));
+                    }
+                    (Some(tstart), None) => {
+                        let _ = write!(line, " {}", value(tstart));
+                    }
+                    (None, None) => {}
+                }
+                if tran.uic {
+                    line.push_str(" uic");
+                }
+                line
+            }
+            Command::Noise(noise) => {
+                let output = match &noise.reference {
+                    Some(reference) => format!("v({},{reference})", noise.output),
+                    None => format!("v({})", noise.output),
+                };
+                format!(
+                    ".noise {output} {} {}",
+                    device_name(&noise.input_source),
+                    ac_sweep(&noise.sweep)
+                )
+            }
+            Command::Sp(sp) => format!(
+                ".sp {} {} z0={}",
+                ac_sweep(&sp.sweep),
+                sp.ports.join(" "),
+                value(&sp.z0)
+            ),
+            // written last in any case
+            Command::End => return None,
+        };
+        Some(line)
+    }
+}
+
+/// `name` as a device line can start with: a device inside a subcircuit instance, whose path
+/// starts with an `X`, takes the letter of its local name in front of the path.
+fn device_name(name: &str) -> Cow<'_, str> {
+    if let Some((_, local)) = name.rsplit_once(HIERARCHY_SEPARATOR)
+        && let Some(letter) = local.chars().next()
+        && !name.starts_with(|c: char| c.eq_ignore_ascii_case(&letter))
+    {
+        return Cow::Owned(format!("{letter}{HIERARCHY_SEPARATOR}{name}"));
+    }
+    Cow::Borrowed(name)
+}
+
+/// A number that reads back exactly: in scientific notation when it is very small or large
+/// and the mantissa scaled by the exponent gives it back, as the parser computes it.
+fn number(x: f64) -> String {
+    if x != 0.0 && !(1e-4..1e12).contains(&x.abs()) {
+        let scientific = format!("{x:e}");
+        if let Some((mantissa, exponent)) = scientific.split_once('e')
+            && let (Ok(mantissa), Ok(exponent)) = (mantissa.parse::<f64>(), exponent.parse())
+            && mantissa * 10f64.powf(exponent) == x
+        {
+            return scientific;
+        }
+    }
+    format!("{x}")
+}
+
+/// A value as written: its mantissa with the exponent or suffix, and the unit, it was written
+/// with.
+fn value(value: &Value) -> String {
+    let suffix = value.suffix.as_ref().map(|suffix| match suffix {
+        ValueSuffix::Tera => "T",
+        ValueSuffix::Giga => "G",
+        ValueSuffix::Mega => "Meg",
+        ValueSuffix::Kilo => "k",
+        ValueSuffix::Milli => "m",
+        ValueSuffix::Micro => "u",
+        ValueSuffix::Nano => "n",
+        ValueSuffix::Pico => "p",
+        ValueSuffix::Femto => "f",
+        ValueSuffix::Atto => "a",
+        ValueSuffix::Degree => "deg",
+        ValueSuffix::Radian => "rad",
+    });
+    let unit = value.unit.as_ref().map_or("", Unit::symbol);
+    let number = match (value.exponent, suffix) {
+        (None, None) => number(value.value),
+        (Some(exponent), None) => format!("{}e{exponent}", value.value),
+        (None, Some(suffix)) => format!("{}{suffix}", value.value),
+        // an exponent and a suffix do not lex together, scale the mantissa by the exponent
+        (Some(exponent), Some(suffix)) => {
+            format!("{}{suffix}", number(value.value * 10f64.powf(exponent)))
+        }
+    };
+    format!("{number}{unit}")
+}
+
+/// ` name=value` when there is a value.
+fn param(line: &mut String, name: &str, param: &Option<Value>) {
+    if let Some(param) = param {
+        let _ = write!(line, " {name}={}", value(param));
+    }
+}
+
+/// ` ic=v1,v2,...` with the initial conditions given, which come first.
+fn initial_condition(line: &mut String, values: &[&Option<Value>]) {
+    let values: Vec<_> = values.iter().map_while(|v| v.as_ref()).map(value).collect();
+    if !values.is_empty() {
+        let _ = write!(line, " ic={}", values.join(","));
+    }
+}
+
+fn waveform(waveform: &WaveForm) -> String {
+    // the optional values are positional, so every given one comes before the missing ones
+    let call = |name: &str, values: &[Option<&Value>]| {
+        let values: Vec<_> = values.iter().map_while(|v| *v).map(value).collect();
+        format!("{name}({})", values.join(" "))
+    };
+    match waveform {
+        WaveForm::Constant(constant) => format!("DC {}", value(constant)),
+        WaveForm::Pulse {
+            voltage1,
+            voltage2,
+            delay,
+            rise_time,
+            fall_time,
+            pulse_width,
+            period,
+            number_of_pulses,
+        } => {
+            let pulses = number_of_pulses.map(|n| Value::new(n as f64, None, None));
+            call(
+                "PULSE",
+                &[
+                    Some(voltage1),
+                    Some(voltage2),
+                    delay.as_ref(),
+                    rise_time.as_ref(),
+                    fall_time.as_ref(),
+                    pulse_width.as_ref(),
+                    period.as_ref(),
+                    pulses.as_ref(),
+                ],
+            )
+        }
+        WaveForm::Sinusoidal {
+            offset,
+            amplitude,
+            frequency,
+            delay,
+            damping_factor,
+            phase,
+        } => call(
+            "SIN",
+            &[
+                Some(offset),
+                Some(amplitude),
+                frequency.as_ref(),
+                delay.as_ref(),
+                damping_factor.as_ref(),
+                phase.as_ref(),
+            ],
+        ),
+        WaveForm::Exponential {
+            initial_value,
+            pulsed_value,
+            rise_delay_time,
+            rise_time_constant,
+            fall_delay_time,
+            fall_time_constant,
+        } => call(
+            "EXP",
+            &[
+                Some(initial_value),
+                Some(pulsed_value),
+                rise_delay_time.as_ref(),
+                rise_time_constant.as_ref(),
+                fall_delay_time.as_ref(),
+                fall_time_constant.as_ref(),
+            ],
+        ),
+        WaveForm::PiecewiseLinear {
+            points,
+            repeat,
+            delay,
+        } => {
+            let points: Vec<_> = points
+                .iter()
+                .map(|(time, level)| format!("{} {}", value(time), value(level)))
+                .collect();
+            let mut pwl = format!("PWL({})", points.join(" "));
+            param(&mut pwl, "r", repeat);
+            param(&mut pwl, "td", delay);
+            pwl
+        }
+        WaveForm::SingleFrequencyFm {
+            offset,
+            amplitude,
+            carrier_frequency,
+            modulation_index,
+            signal_frequency,
+        } => call(
+            "SFFM",
+            &[
+                Some(offset),
+                Some(amplitude),
+                carrier_frequency.as_ref(),
+                modulation_index.as_ref(),
+                signal_frequency.as_ref(),
+            ],
+        ),
+    }
+}
+
+fn model_card(name: &str, model: &DeviceModel) -> String {
+    let (kind, params): (&str, Vec<(&str, &Option<Value>)>) = match model {
+        DeviceModel::Resistor(ResistorModel {
+            resistance,
+            tc1,
+            tc2,
+            w,
+            l,
+            rth,
+            cth,
+        }) => (
+            "R",
+            vec![
+                ("resistance", resistance),
+                ("tc1", tc1),
+                ("tc2", tc2),
+                ("w", w),
+                ("l", l),
+                ("rth", rth),
+                ("cth", cth),
+            ],
+        ),
+        DeviceModel::Capacitor(c) => ("C", vec![("cap", &c.cap), ("tc1", &c.tc1), ("tc2", &c.tc2)]),
+        DeviceModel::Inductor(l) => (
+            "L",
+            vec![("ind", &l.inductance), ("tc1", &l.tc1), ("tc2", &l.tc2)],
+        ),
+        DeviceModel::Diode(d) => (
+            "D",
+            vec![
+                ("is", &d.is),
+                ("n", &d.n),
+                ("rs", &d.rs),
+                ("kf", &d.kf),
+                ("af", &d.af),
+                ("eg", &d.eg),
+                ("xti", &d.xti),
+            ],
+        ),
+        DeviceModel::Bjt(q) => (
+            match q.polarity {
+                BjtPolarity::Npn => "NPN",
+                BjtPolarity::Pnp => "PNP",
+            },
+            vec![
+                ("is", &q.is),
+                ("bf", &q.bf),
+                ("br", &q.br),
+                ("nf", &q.nf),
+                ("nr", &q.nr),
+                ("kf", &q.kf),
+                ("af", &q.af),
+                ("eg", &q.eg),
+                ("xti", &q.xti),
+                ("xtb", &q.xtb),
+                ("vaf", &q.vaf),
+                ("var", &q.var),
+                ("ikf", &q.ikf),
+                ("ikr", &q.ikr),
+                ("ise", &q.ise),
+                ("ne", &q.ne),
+                ("isc", &q.isc),
+                ("nc", &q.nc),
+                ("rb", &q.rb),
+                ("rc", &q.rc),
+                ("re", &q.re),
+                ("tf", &q.tf),
+                ("tr", &q.tr),
+                ("cje", &q.cje),
+                ("vje", &q.vje),
+                ("mje", &q.mje),
+                ("cjc", &q.cjc),
+                ("vjc", &q.vjc),
+                ("mjc", &q.mjc),
+                ("fc", &q.fc),
+                ("rth", &q.rth),
+                ("cth", &q.cth),
+            ],
+        ),
+        DeviceModel::Mosfet(m) => (
+            match m.polarity {
+                MosfetPolarity::Nmos => "NMOS",
+                MosfetPolarity::Pmos => "PMOS",
+            },
+            vec![
+                ("vto", &m.vto),
+                ("kp", &m.kp),
+                ("gamma", &m.gamma),
+                ("phi", &m.phi),
+                ("lambda", &m.lambda),
+            ],
+        ),
+        DeviceModel::Jfet(j) => (
+            match j.polarity {
+                JfetPolarity::Njf => "NJF",
+                JfetPolarity::Pjf => "PJF",
+            },
+            vec![
+                ("vto", &j.vto),
+                ("beta", &j.beta),
+                ("lambda", &j.lambda),
+                ("rd", &j.rd),
+                ("rs", &j.rs),
+                ("cgs", &j.cgs),
+                ("cgd", &j.cgd),
+                ("pb", &j.pb),
+                ("is", &j.is),
+                ("n", &j.n),
+                ("fc", &j.fc),
+                ("kf", &j.kf),
+                ("af", &j.af),
+            ],
+        ),
+        DeviceModel::Switch(s) => (
+            "SW",
+            vec![
+                ("vt", &s.vt),
+                ("vh", &s.vh),
+                ("ron", &s.ron),
+                ("roff", &s.roff),
+            ],
+        ),
+        DeviceModel::CurrentSwitch(w) => (
+            "CSW",
+            vec![
+                ("it", &w.it),
+                ("ih", &w.ih),
+                ("ron", &w.ron),
+                ("roff", &w.roff),
+            ],
+        ),
+    };
+    let mut params_text = String::new();
+    for (param_name, param_value) in params {
+        param(&mut params_text, param_name, param_value);
+    }
+    if params_text.is_empty() {
+        format!(".model {name} {kind}")
+    } else {
+        format!(".model {name} {kind}({})", params_text.trim_start())
+    }
+}
+
+fn analysis(analysis: AnalysisType) -> &'static str {
+    match analysis {
+        AnalysisType::Op => "op",
+        AnalysisType::Dc => "dc",
+        AnalysisType::Ac => "ac",
+        AnalysisType::Tran => "tran",
+    }
+}
+
+fn output_vector(vector: &OutputVector) -> String {
+    match vector {
+        OutputVector::Voltage(node) => format!("v({node})"),
+        OutputVector::Current(device) => format!("i({})", device_name(device)),
+    }
+}
+
+/// An event of a `TRIG`/`TARG`, or of a `WHEN` with its level after an `=`.
+fn measure_event(event: &MeasureEvent, when: bool) -> String {
+    match event {
+        MeasureEvent::At(at) => format!("AT={}", value(at)),
+        MeasureEvent::Crossing {
+            vector,
+            level,
+            edge,
+        } => {
+            let edge = match edge {
+                MeasureEdge::Rise(n) => format!("RISE={n}"),
+                MeasureEdge::Fall(n) => format!("FALL={n}"),
+                MeasureEdge::Cross(n) => format!("CROSS={n}"),
+            };
+            let level = value(level);
+            if when {
+                format!("{}={level} {edge}", output_vector(vector))
+            } else {
+                format!("{} VAL={level} {edge}", output_vector(vector))
+            }
+        }
+    }
+}
+
+fn ac_sweep(ac: &AcCommand) -> String {
+    let (kind, points) = match &ac.ac_sweep_type {
+        AcSweepType::Dec(points) => ("dec", points),
+        AcSweepType::Oct(points) => ("oct", points),
+        AcSweepType::Lin(points) => ("lin", points),
+        AcSweepType::List(frequencies) => {
+            let frequencies: Vec<_> = frequencies.iter().map(value).collect();
+            return format!("list {}", frequencies.join(" "));
+        }
+    };
+    format!("{kind} {points} {} {}", value(&ac.fstart), value(&ac.fstop))
+}
+
+fn node_values(values: &[NodeValue]) -> String {
+    let values: Vec<_> = values
+        .iter()
+        .map(|v| format!("v({})={}", v.node, value(&v.value)))
+        .collect();
+    values.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rstest::rstest;
+
+    use crate::{ParseOptions, netlist_models::ModelTable, parse};
+
+    fn reparse(netlist: &str) -> crate::instance_parser::Deck {
+        let mut options = ParseOptions::new_with_source("written.spicy", netlist.to_string());
+        parse(&mut options).unwrap_or_else(|e| panic!("{e}\n{netlist}"))
+    }
+
+    #[rstest]
+    fn test_written_netlist_parses_back(#[files("tests/parser_inputs/*.spicy")] input: PathBuf) {
+        let content = std::fs::read_to_string(&input).expect("failed to read input file");
+        let mut options = ParseOptions::new_with_source(&input, content);
+        let deck = parse(&mut options).expect("parse");
+
+        let netlist = deck.to_netlist();
+        let name = format!(
+            "writer-{}",
+            input
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "unknown".to_string())
+        );
+        insta::assert_snapshot!(name, netlist);
+
+        // canonical: writing the deck of the written netlist gives it back
+        assert_eq!(reparse(&netlist).to_netlist(), netlist);
+    }
+
+    #[test]
+    fn test_models_missing_from_the_deck_are_written() {
+        let mut deck = reparse("models\nV1 a 0 1\nD1 a 0 dmod\n.model dmod D(is=1e-14)\n.end\n");
+        deck.models = ModelTable::default();
+        assert_eq!(
+            deck.to_netlist(),
+            "models\nD1 a 0 model1\nV1 a 0 DC 1\n.model model1 D(is=1e-14)\n.end\n"
+        );
+    }
+
+    #[test]
+    fn test_units_are_written_back() {
+        let deck = reparse("units\nV1 a 0 5V\nR1 a b 10kOhm\nC1 b 0 1e6F\n.end\n");
+        assert_eq!(
+            deck.to_netlist(),
+            "units\nR1 a b 10kOhm\nC1 b 0 1e6F\nV1 a 0 DC 5V\n.end\n"
+        );
+    }
+
+    #[test]
+    fn test_deck_serializes_with_node_names() {
+        let deck = reparse("json\nV1 in 0 1\nR1 in out 1k\nR2 out 0 1k\n.op\n.end\n");
+        let json = serde_json::to_value(&deck).expect("serialize");
+        assert_eq!(
+            json["node_mapping"]["nodes"],
+            serde_json::json!(["0", "in", "out"])
+        );
+        assert_eq!(json["node_mapping"]["branches"], serde_json::json!(["V1"]));
+        let resistor = &json["devices"]["resistors"][0];
+        assert_eq!(resistor["name"], "R1");
+        assert_eq!(resistor["positive"], 1);
+        assert_eq!(resistor["resistance"]["suffix"], "Kilo");
+        assert!(json["commands"][0]["Op"]["span"].is_object());
+    }
+}
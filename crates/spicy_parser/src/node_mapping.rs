@@ -1,13 +1,73 @@
 use crate::netlist_types::{CurrentBranchIndex, NodeIndex, NodeName};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 use std::collections::HashMap;
 use std::fmt;
 
+/// Joins the instance path and the local name of a node or device inside a subcircuit.
+pub const HIERARCHY_SEPARATOR: char = '.';
+
+/// The deck-wide name of `name` inside the subcircuit instance `path` (outermost first), e.g.
+/// `X1.X2.out`, as it appears in the results.
+pub fn hierarchical_name(path: &[&str], name: &str) -> String {
+    let mut full = String::new();
+    for instance in path {
+        full.push_str(instance);
+        full.push(HIERARCHY_SEPARATOR);
+    }
+    full.push_str(name);
+    full
+}
+
+/// Whether node and device names that differ only in case name the same thing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NameCase {
+    /// `OUT` and `out` are one node, as in SPICE; the first spelling is the one reported.
+    #[default]
+    Insensitive,
+    Sensitive,
+}
+
+impl NameCase {
+    pub fn matches(self, a: &str, b: &str) -> bool {
+        match self {
+            NameCase::Insensitive => a.eq_ignore_ascii_case(b),
+            NameCase::Sensitive => a == b,
+        }
+    }
+
+    /// The key under which `name` is looked up.
+    fn canonical(self, name: &str) -> String {
+        match self {
+            NameCase::Insensitive => name.to_ascii_lowercase(),
+            NameCase::Sensitive => name.to_string(),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct NodeMapping {
     node_mapping: HashMap<NodeName, NodeIndex>,
     node_counter: usize,
     branch_mapping: HashMap<String, CurrentBranchIndex>,
     branch_counter: usize,
+    name_case: NameCase,
+    /// Every node by its canonical name; `node_mapping` keeps the first spelling.
+    canonical_nodes: HashMap<String, NodeIndex>,
+    /// The unknowns left by [`NodeMapping::merge_nodes`], `None` for the mapping of a deck.
+    merged: Option<MergedUnknowns>,
+}
+
+/// The MNA unknowns of a mapping whose shorted nodes share a voltage and whose dropped branches
+/// have no current unknown.
+#[derive(Debug, Clone)]
+struct MergedUnknowns {
+    /// MNA index of every node by [`NodeIndex`], `None` for ground and the nodes shorted to it.
+    nodes: Vec<Option<usize>>,
+    nodes_len: usize,
+    /// Position among the branch unknowns of every branch by [`CurrentBranchIndex`], `None`
+    /// once dropped.
+    branches: Vec<Option<usize>>,
+    branches_len: usize,
 }
 
 // NOTE: We use `assert_debug_snapshot!` on parsed decks. `HashMap`'s iteration order is not
@@ -25,10 +85,25 @@ impl fmt::Debug for NodeMapping {
         ds.field("node_counter", &self.node_counter);
         ds.field("branch_mapping", &SortedDebugMap(&branch_entries));
         ds.field("branch_counter", &self.branch_counter);
+        if let Some(merged) = &self.merged {
+            ds.field("merged", merged);
+        }
         ds.finish()
     }
 }
 
+/// The names of the nodes and branches by index, ground first.
+impl Serialize for NodeMapping {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut nodes = vec!["0".to_string()];
+        nodes.extend(self.node_names_mna_order());
+        let mut state = serializer.serialize_struct("NodeMapping", 2)?;
+        state.serialize_field("nodes", &nodes)?;
+        state.serialize_field("branches", &self.branch_names_mna_order())?;
+        state.end()
+    }
+}
+
 struct SortedDebugMap<'a, K: 'a, V: 'a>(&'a [(&'a K, &'a V)]);
 
 impl<'a, K: fmt::Debug, V: fmt::Debug> fmt::Debug for SortedDebugMap<'a, K, V> {
@@ -43,6 +118,10 @@ impl<'a, K: fmt::Debug, V: fmt::Debug> fmt::Debug for SortedDebugMap<'a, K, V> {
 
 impl NodeMapping {
     pub fn new() -> Self {
+        Self::with_name_case(NameCase::default())
+    }
+
+    pub fn with_name_case(name_case: NameCase) -> Self {
         let mut node_mapping = HashMap::new();
         let branch_mapping = HashMap::new();
         // always insert ground node at index 0
@@ -52,16 +131,26 @@ impl NodeMapping {
             node_counter: 1,
             branch_mapping,
             branch_counter: 1,
+            name_case,
+            canonical_nodes: HashMap::from([("0".to_string(), NodeIndex(0))]),
+            merged: None,
         }
     }
 
+    pub fn name_case(&self) -> NameCase {
+        self.name_case
+    }
+
     pub fn insert_node(&mut self, node_name: NodeName) -> NodeIndex {
-        let node_counter = &mut self.node_counter;
-        *self.node_mapping.entry(node_name).or_insert_with(|| {
-            let node = NodeIndex(*node_counter);
-            *node_counter += 1;
-            node
-        })
+        let key = self.name_case.canonical(&node_name.0);
+        if let Some(&node) = self.canonical_nodes.get(&key) {
+            return node;
+        }
+        let node = NodeIndex(self.node_counter);
+        self.node_counter += 1;
+        self.canonical_nodes.insert(key, node);
+        self.node_mapping.insert(node_name, node);
+        node
     }
     pub fn insert_branch(&mut self, branch_name: String) -> CurrentBranchIndex {
         let branch_counter = &mut self.branch_counter;
@@ -72,17 +161,96 @@ impl NodeMapping {
         })
     }
 
+    /// Look up the index of an existing node by name.
+    pub fn get_node(&self, node_name: &NodeName) -> Option<NodeIndex> {
+        self.canonical_nodes
+            .get(&self.name_case.canonical(&node_name.0))
+            .copied()
+    }
+
+    /// Look up the branch current of a device by name.
+    pub fn get_branch(&self, name: &str) -> Option<CurrentBranchIndex> {
+        self.branch_mapping
+            .iter()
+            .find(|(branch_name, _)| self.name_case.matches(branch_name, name))
+            .map(|(_, branch)| *branch)
+    }
+
+    /// The mapping of the same nodes and branches where the two nodes of every pair of
+    /// `shorts` are one unknown, and the branches `dropped` have none.
+    ///
+    /// A group of shorted nodes takes the name and the place of its lowest [`NodeIndex`], and
+    /// is ground if ground is among them. The other unknowns keep their order.
+    pub fn merge_nodes(
+        &self,
+        shorts: &[(NodeIndex, NodeIndex)],
+        dropped: &[CurrentBranchIndex],
+    ) -> NodeMapping {
+        // union-find over the node indices, the lowest index as the root of a group
+        let mut parent: Vec<usize> = (0..self.node_counter).collect();
+        fn root(parent: &mut [usize], mut node: usize) -> usize {
+            while parent[node] != node {
+                parent[node] = parent[parent[node]];
+                node = parent[node];
+            }
+            node
+        }
+        for &(a, b) in shorts {
+            let (a, b) = (root(&mut parent, a.0), root(&mut parent, b.0));
+            parent[a.max(b)] = a.min(b);
+        }
+
+        let mut nodes = vec![None; self.node_counter];
+        let mut nodes_len = 0;
+        for node in 1..self.node_counter {
+            let group = root(&mut parent, node);
+            nodes[node] = if group == node {
+                nodes_len += 1;
+                Some(nodes_len - 1)
+            } else {
+                nodes[group]
+            };
+        }
+
+        let mut branches = vec![None; self.branch_counter];
+        let mut branches_len = 0;
+        for (branch, slot) in branches.iter_mut().enumerate().skip(1) {
+            if !dropped.contains(&CurrentBranchIndex(branch)) {
+                *slot = Some(branches_len);
+                branches_len += 1;
+            }
+        }
+
+        let mut mapping = self.clone();
+        mapping.merged = Some(MergedUnknowns {
+            nodes,
+            nodes_len,
+            branches,
+            branches_len,
+        });
+        mapping
+    }
+
     pub fn nodes_len(&self) -> usize {
-        self.node_counter - 1 // -1 for the ground node
+        match &self.merged {
+            Some(merged) => merged.nodes_len,
+            None => self.node_counter - 1, // -1 for the ground node
+        }
     }
 
     pub fn branches_len(&self) -> usize {
-        self.branch_counter - 1
+        match &self.merged {
+            Some(merged) => merged.branches_len,
+            None => self.branch_counter - 1,
+        }
     }
 
     /// Convert a node index to an MNA node index.
     /// Returns None for the ground node.
     pub fn mna_node_index(&self, node_index: NodeIndex) -> Option<usize> {
+        if let Some(merged) = &self.merged {
+            return merged.nodes[node_index.0];
+        }
         if node_index.0 == 0 {
             None
         } else {
@@ -92,11 +260,21 @@ impl NodeMapping {
 
     /// Convert a branch index to an MNA branch index.
     pub fn mna_branch_index(&self, branch_index: CurrentBranchIndex) -> usize {
+        self.try_mna_branch_index(branch_index)
+            .expect("the branch was dropped by merge_nodes")
+    }
+
+    /// The MNA index of a branch, `None` if [`NodeMapping::merge_nodes`] dropped it.
+    pub fn try_mna_branch_index(&self, branch_index: CurrentBranchIndex) -> Option<usize> {
         assert!(
             branch_index.0 != 0,
             "branch index 0 is reserved for devices without an MNA current unknown"
         );
-        self.nodes_len() + (branch_index.0 - 1)
+        let position = match &self.merged {
+            Some(merged) => merged.branches[branch_index.0]?,
+            None => branch_index.0 - 1,
+        };
+        Some(self.nodes_len() + position)
     }
 
     pub fn mna_matrix_dim(&self) -> usize {
@@ -107,14 +285,17 @@ impl NodeMapping {
     /// Node names in MNA order (ground excluded).
     ///
     /// Index `i` in the returned vec corresponds to the MNA node voltage unknown at row/col `i`.
+    /// Merged nodes go by the name of the first of them.
     pub fn node_names_mna_order(&self) -> Vec<String> {
-        let mut names = vec![String::new(); self.nodes_len()];
+        let mut names = vec![(usize::MAX, String::new()); self.nodes_len()];
         for (name, node_index) in &self.node_mapping {
-            if let Some(i) = self.mna_node_index(*node_index) {
-                names[i] = name.0.clone();
+            if let Some(i) = self.mna_node_index(*node_index)
+                && node_index.0 < names[i].0
+            {
+                names[i] = (node_index.0, name.0.clone());
             }
         }
-        names
+        names.into_iter().map(|(_, name)| name).collect()
     }
 
     /// Branch names in MNA order (only branches that allocate a current unknown).
@@ -125,11 +306,15 @@ impl NodeMapping {
         let mut names = vec![String::new(); self.branches_len()];
         for (name, branch_index) in &self.branch_mapping {
             // Branch indices start at 1 (0 is reserved for devices without a current unknown).
-            let i = branch_index
-                .0
-                .checked_sub(1)
-                .expect("branch index 0 should not appear in branch_mapping");
-            if i < names.len() {
+            assert!(
+                branch_index.0 != 0,
+                "branch index 0 should not appear in branch_mapping"
+            );
+            if let Some(i) = self
+                .try_mna_branch_index(*branch_index)
+                .map(|i| i - self.nodes_len())
+                && i < names.len()
+            {
                 names[i] = name.clone();
             }
         }
@@ -146,6 +331,7 @@ impl Default for NodeMapping {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::netlist_types::Command;
     use crate::{ParseOptions, SourceMap, parse};
     use std::path::PathBuf;
 
@@ -225,6 +411,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn merge_nodes_shares_the_unknowns_of_shorted_nodes() {
+        let mut m = NodeMapping::new();
+        let [a, b, c, d] = ["a", "b", "c", "d"].map(|n| m.insert_node(NodeName(n.to_string())));
+        let v1 = m.insert_branch("V1".to_string());
+        let l1 = m.insert_branch("L1".to_string());
+        let l2 = m.insert_branch("L2".to_string());
+
+        // c-b and d-0 are shorted by L1 and L2
+        let merged = m.merge_nodes(&[(c, b), (d, NodeIndex(0))], &[l1, l2]);
+        assert_eq!(merged.nodes_len(), 2);
+        assert_eq!(merged.branches_len(), 1);
+        assert_eq!(merged.mna_matrix_dim(), 3);
+        assert_eq!(merged.mna_node_index(a), Some(0));
+        assert_eq!(merged.mna_node_index(b), Some(1));
+        assert_eq!(merged.mna_node_index(c), Some(1));
+        assert_eq!(merged.mna_node_index(d), None);
+        assert_eq!(merged.mna_branch_index(v1), 2);
+        assert_eq!(merged.try_mna_branch_index(l1), None);
+        assert_eq!(merged.node_names_mna_order(), ["a", "b"]);
+        assert_eq!(merged.branch_names_mna_order(), ["V1"]);
+
+        // the mapping it came from is untouched
+        assert_eq!(m.mna_node_index(d), Some(3));
+        assert_eq!(m.try_mna_branch_index(l2), Some(6));
+    }
+
     #[test]
     fn parse_populates_node_and_branch_mapping_consistently() {
         let netlist = r#"mapping test
@@ -240,6 +453,9 @@ R1 in out 1k
             source_path: PathBuf::from("."),
             source_map,
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
 
         let deck = parse(&mut options).expect("parse");
@@ -269,4 +485,33 @@ R1 in out 1k
         assert_eq!(r1.positive, NodeIndex(1));
         assert_eq!(r1.negative, NodeIndex(2));
     }
+
+    fn parse_with_case(netlist: &str, name_case: NameCase) -> crate::instance_parser::Deck {
+        let mut options = ParseOptions::new_with_source("case.spicy", netlist.to_string());
+        options.name_case = name_case;
+        parse(&mut options).expect("parse")
+    }
+
+    #[test]
+    fn names_differing_in_case_are_one_node_by_default() {
+        let netlist = "case\nV1 IN 0 1\nR1 in Out 1k\nR2 OUT 0 1k\n.dc v1 0 1 0.5\n.end\n";
+
+        let deck = parse_with_case(netlist, NameCase::Insensitive);
+        assert_eq!(deck.node_mapping.node_names_mna_order(), ["IN", "Out"]);
+        assert_eq!(
+            deck.node_mapping.get_node(&NodeName("out".to_string())),
+            Some(NodeIndex(2))
+        );
+        let Some(Command::Dc(dc)) = deck.commands.first() else {
+            panic!("{:?}", deck.commands);
+        };
+        assert_eq!(dc.srcnam, "V1");
+
+        let deck = parse_with_case(netlist, NameCase::Sensitive);
+        assert_eq!(
+            deck.node_mapping.node_names_mna_order(),
+            ["IN", "in", "Out", "OUT"]
+        );
+        assert_eq!(deck.node_mapping.get_branch("v1"), None);
+    }
 }
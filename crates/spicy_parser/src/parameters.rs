@@ -0,0 +1,509 @@
+//! Range checks on the device and model parameters of a parsed deck.
+//!
+//! A zero resistance or a negative capacitance parses fine, but becomes an infinite conductance
+//! or an unstable companion model, and the simulation then fails on a singular matrix or a
+//! shrinking timestep far from the card at fault. These checks point at the card instead.
+//!
+//! Where a parameter has a unit, the unit it is written in (the `F` of `10uF`) is checked too.
+//! Units do not scale values, so a wrong one is only a warning, as is a lone `F` or `A` that
+//! ngspice reads as femto or atto.
+//!
+//! The parameters of a `.model` are checked once, and reported at the first device using it.
+
+use crate::{
+    Span, devices::SwitchControl, error::ParameterError, expr::Value, instance_parser::Deck,
+    netlist_models::DeviceModel, netlist_types::Unit, netlist_waveform::WaveForm,
+};
+
+/// The values a parameter may take, and what is said of them otherwise.
+struct Bound {
+    holds: fn(f64) -> bool,
+    expected: &'static str,
+    /// Breaking it fails the analyses, rather than being a warning.
+    fatal: bool,
+}
+
+const POSITIVE: Bound = Bound {
+    holds: |v| v > 0.0,
+    expected: "positive",
+    fatal: true,
+};
+
+const NONZERO: Bound = Bound {
+    holds: |v| v != 0.0 && v.is_finite(),
+    expected: "nonzero",
+    fatal: true,
+};
+
+const NON_NEGATIVE: Bound = Bound {
+    holds: |v| v >= 0.0,
+    expected: "zero or more",
+    fatal: true,
+};
+
+/// A coupling beyond 1 stores more energy than the inductors, and grows without bound.
+const COUPLING: Bound = Bound {
+    holds: |v| v.abs() <= 1.0,
+    expected: "between -1 and 1",
+    fatal: true,
+};
+
+/// Saturation currents of real junctions, from power devices down to the smallest ones.
+const SATURATION_CURRENT: Bound = Bound {
+    holds: |v| (1e-30..=1e-3).contains(&v),
+    expected: "1e-30 to 1e-3 A",
+    fatal: false,
+};
+
+/// The out-of-range parameters of a deck and its models.
+#[derive(Default)]
+struct Checks {
+    found: Vec<ParameterError>,
+}
+
+impl Checks {
+    fn check(
+        &mut self,
+        owner: &str,
+        span: Option<Span>,
+        param: &'static str,
+        value: Option<&Value>,
+        bound: &Bound,
+    ) {
+        let Some(value) = value.map(Value::get_value) else {
+            return;
+        };
+        if (bound.holds)(value) {
+            return;
+        }
+        let (owner, expected) = (owner.to_string(), bound.expected);
+        self.found.push(if bound.fatal {
+            ParameterError::Invalid {
+                owner,
+                param,
+                value,
+                expected,
+                span,
+            }
+        } else {
+            ParameterError::Unusual {
+                owner,
+                param,
+                value,
+                expected,
+                span,
+            }
+        });
+    }
+
+    /// A saturation current: positive, and warned about outside the usual range.
+    fn check_saturation_current(
+        &mut self,
+        owner: &str,
+        span: Option<Span>,
+        param: &'static str,
+        value: Option<&Value>,
+    ) {
+        let before = self.found.len();
+        self.check(owner, span, param, value, &POSITIVE);
+        if self.found.len() == before {
+            self.check(owner, span, param, value, &SATURATION_CURRENT);
+        }
+    }
+
+    /// The units of parameters: each one must be written in its unit, if in any.
+    fn check_units(
+        &mut self,
+        owner: &str,
+        span: Option<Span>,
+        units: &[(&'static str, Option<&Value>, Unit)],
+    ) {
+        for &(param, value, expected) in units {
+            let Some(value) = value else {
+                continue;
+            };
+            let owner = owner.to_string();
+            match value.unit {
+                Some(found) if found != expected => self.found.push(ParameterError::WrongUnit {
+                    owner,
+                    param,
+                    found,
+                    expected,
+                    span,
+                }),
+                Some(unit) if value.suffix.is_none() => {
+                    if let Some(suffix) = unit.suffix_reading() {
+                        self.found.push(ParameterError::AmbiguousUnit {
+                            owner,
+                            param,
+                            value: value.get_value(),
+                            unit,
+                            suffix,
+                            span,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_instances(&mut self, deck: &Deck) {
+        let devices = &deck.devices;
+        for r in &devices.resistors {
+            let span = Some(r.span);
+            self.check(&r.name, span, "r", r.resistance.as_ref(), &NONZERO);
+            self.check(&r.name, span, "ac", r.ac.as_ref(), &NONZERO);
+            self.check(&r.name, span, "m", r.m.as_ref(), &POSITIVE);
+            self.check_units(
+                &r.name,
+                span,
+                &[
+                    ("r", r.resistance.as_ref(), Unit::Ohm),
+                    ("ac", r.ac.as_ref(), Unit::Ohm),
+                ],
+            );
+        }
+        for c in &devices.capacitors {
+            let span = Some(c.span);
+            self.check(&c.name, span, "c", c.capacitance.as_ref(), &NON_NEGATIVE);
+            self.check(&c.name, span, "m", c.m.as_ref(), &POSITIVE);
+            self.check_units(
+                &c.name,
+                span,
+                &[
+                    ("c", c.capacitance.as_ref(), Unit::Farad),
+                    ("ic", c.ic.as_ref(), Unit::Volt),
+                ],
+            );
+        }
+        for l in &devices.inductors {
+            let span = Some(l.span);
+            self.check(&l.name, span, "l", l.inductance.as_ref(), &NON_NEGATIVE);
+            self.check(&l.name, span, "m", l.m.as_ref(), &POSITIVE);
+            self.check_units(
+                &l.name,
+                span,
+                &[
+                    ("l", l.inductance.as_ref(), Unit::Henry),
+                    ("ic", l.ic.as_ref(), Unit::Ampere),
+                ],
+            );
+        }
+        for k in &devices.mutual_inductances {
+            self.check(&k.name, Some(k.span), "k", Some(&k.coupling), &COUPLING);
+        }
+        for (sources, unit) in [
+            (&devices.voltage_sources, Unit::Volt),
+            (&devices.current_sources, Unit::Ampere),
+        ] {
+            for s in sources {
+                let dc = match &s.dc {
+                    Some(WaveForm::Constant(value)) => Some(value),
+                    _ => None,
+                };
+                let ac = s.ac.as_ref().map(|ac| &ac.mag);
+                self.check_units(&s.name, Some(s.span), &[("dc", dc, unit), ("ac", ac, unit)]);
+            }
+        }
+        for d in &devices.diodes {
+            let span = Some(d.span);
+            self.check(&d.name, span, "area", d.area.as_ref(), &POSITIVE);
+            self.check(&d.name, span, "m", d.m.as_ref(), &POSITIVE);
+        }
+        for q in &devices.bjts {
+            self.check(&q.name, Some(q.span), "area", q.area.as_ref(), &POSITIVE);
+        }
+        for m in &devices.mosfets {
+            let span = Some(m.span);
+            self.check(&m.name, span, "l", m.l.as_ref(), &POSITIVE);
+            self.check(&m.name, span, "w", m.w.as_ref(), &POSITIVE);
+            self.check(&m.name, span, "m", m.m.as_ref(), &POSITIVE);
+        }
+        for j in &devices.jfets {
+            self.check(&j.name, Some(j.span), "area", j.area.as_ref(), &POSITIVE);
+        }
+    }
+
+    fn check_model(&mut self, name: &str, model: &DeviceModel, deck: &Deck) {
+        let owner = format!("model {name}");
+        let owner = owner.as_str();
+        let devices = &deck.devices;
+        match model {
+            DeviceModel::Resistor(model) => {
+                let span = first_span(&devices.resistors, |r| r.model.as_ref() == Some(model));
+                self.check(owner, span, "r", model.resistance.as_ref(), &NONZERO);
+                self.check(owner, span, "rth", model.rth.as_ref(), &POSITIVE);
+                self.check(owner, span, "cth", model.cth.as_ref(), &POSITIVE);
+                self.check_units(owner, span, &[("r", model.resistance.as_ref(), Unit::Ohm)]);
+            }
+            DeviceModel::Capacitor(model) => {
+                let span = first_span(&devices.capacitors, |c| c.model.as_ref() == Some(model));
+                self.check(owner, span, "cap", model.cap.as_ref(), &NON_NEGATIVE);
+                self.check_units(owner, span, &[("cap", model.cap.as_ref(), Unit::Farad)]);
+            }
+            DeviceModel::Inductor(model) => {
+                let span = first_span(&devices.inductors, |l| l.model.as_ref() == Some(model));
+                self.check(owner, span, "ind", model.inductance.as_ref(), &NON_NEGATIVE);
+                self.check_units(
+                    owner,
+                    span,
+                    &[("ind", model.inductance.as_ref(), Unit::Henry)],
+                );
+            }
+            DeviceModel::Diode(model) => {
+                let span = first_span(&devices.diodes, |d| d.model == *model);
+                self.check_saturation_current(owner, span, "is", model.is.as_ref());
+                self.check(owner, span, "n", model.n.as_ref(), &POSITIVE);
+                self.check(owner, span, "rs", model.rs.as_ref(), &NON_NEGATIVE);
+                self.check_units(
+                    owner,
+                    span,
+                    &[
+                        ("is", model.is.as_ref(), Unit::Ampere),
+                        ("rs", model.rs.as_ref(), Unit::Ohm),
+                    ],
+                );
+            }
+            DeviceModel::Bjt(model) => {
+                let span = first_span(&devices.bjts, |q| q.model == **model);
+                self.check_saturation_current(owner, span, "is", model.is.as_ref());
+                for (param, value) in [
+                    ("bf", &model.bf),
+                    ("br", &model.br),
+                    ("nf", &model.nf),
+                    ("nr", &model.nr),
+                ] {
+                    self.check(owner, span, param, value.as_ref(), &POSITIVE);
+                }
+                for (param, value) in [
+                    ("rb", &model.rb),
+                    ("rc", &model.rc),
+                    ("re", &model.re),
+                    ("cje", &model.cje),
+                    ("cjc", &model.cjc),
+                    ("tf", &model.tf),
+                    ("tr", &model.tr),
+                ] {
+                    self.check(owner, span, param, value.as_ref(), &NON_NEGATIVE);
+                }
+                self.check_units(
+                    owner,
+                    span,
+                    &[
+                        ("is", model.is.as_ref(), Unit::Ampere),
+                        ("rb", model.rb.as_ref(), Unit::Ohm),
+                        ("rc", model.rc.as_ref(), Unit::Ohm),
+                        ("re", model.re.as_ref(), Unit::Ohm),
+                        ("cje", model.cje.as_ref(), Unit::Farad),
+                        ("cjc", model.cjc.as_ref(), Unit::Farad),
+                        ("tf", model.tf.as_ref(), Unit::Second),
+                        ("tr", model.tr.as_ref(), Unit::Second),
+                    ],
+                );
+            }
+            DeviceModel::Mosfet(model) => {
+                let span = first_span(&devices.mosfets, |m| m.model == *model);
+                self.check(owner, span, "kp", model.kp.as_ref(), &NON_NEGATIVE);
+                self.check(owner, span, "phi", model.phi.as_ref(), &POSITIVE);
+                self.check_units(owner, span, &[("phi", model.phi.as_ref(), Unit::Volt)]);
+            }
+            DeviceModel::Jfet(model) => {
+                let span = first_span(&devices.jfets, |j| j.model == **model);
+                self.check_saturation_current(owner, span, "is", model.is.as_ref());
+                self.check(owner, span, "n", model.n.as_ref(), &POSITIVE);
+                for (param, value) in [
+                    ("beta", &model.beta),
+                    ("rd", &model.rd),
+                    ("rs", &model.rs),
+                    ("cgs", &model.cgs),
+                    ("cgd", &model.cgd),
+                ] {
+                    self.check(owner, span, param, value.as_ref(), &NON_NEGATIVE);
+                }
+                self.check_units(
+                    owner,
+                    span,
+                    &[
+                        ("is", model.is.as_ref(), Unit::Ampere),
+                        ("rd", model.rd.as_ref(), Unit::Ohm),
+                        ("rs", model.rs.as_ref(), Unit::Ohm),
+                        ("cgs", model.cgs.as_ref(), Unit::Farad),
+                        ("cgd", model.cgd.as_ref(), Unit::Farad),
+                    ],
+                );
+            }
+            DeviceModel::Switch(model) => {
+                let span = first_span(
+                    &devices.switches,
+                    |s| matches!(&s.control, SwitchControl::Voltage { model: m, .. } if m == model),
+                );
+                self.check(owner, span, "ron", model.ron.as_ref(), &POSITIVE);
+                self.check(owner, span, "roff", model.roff.as_ref(), &POSITIVE);
+                self.check_units(
+                    owner,
+                    span,
+                    &[
+                        ("vt", model.vt.as_ref(), Unit::Volt),
+                        ("vh", model.vh.as_ref(), Unit::Volt),
+                        ("ron", model.ron.as_ref(), Unit::Ohm),
+                        ("roff", model.roff.as_ref(), Unit::Ohm),
+                    ],
+                );
+            }
+            DeviceModel::CurrentSwitch(model) => {
+                let span = first_span(
+                    &devices.switches,
+                    |s| matches!(&s.control, SwitchControl::Current { model: m, .. } if m == model),
+                );
+                self.check(owner, span, "ron", model.ron.as_ref(), &POSITIVE);
+                self.check(owner, span, "roff", model.roff.as_ref(), &POSITIVE);
+                self.check_units(
+                    owner,
+                    span,
+                    &[
+                        ("it", model.it.as_ref(), Unit::Ampere),
+                        ("ih", model.ih.as_ref(), Unit::Ampere),
+                        ("ron", model.ron.as_ref(), Unit::Ohm),
+                        ("roff", model.roff.as_ref(), Unit::Ohm),
+                    ],
+                );
+            }
+        }
+    }
+}
+
+/// A device spec with the span of its card.
+trait Card {
+    fn span(&self) -> Span;
+}
+
+macro_rules! impl_card {
+    ($($spec:ty),*) => {
+        $(impl Card for $spec {
+            fn span(&self) -> Span {
+                self.span
+            }
+        })*
+    };
+}
+
+impl_card!(
+    crate::devices::ResistorSpec,
+    crate::devices::CapacitorSpec,
+    crate::devices::InductorSpec,
+    crate::devices::DiodeSpec,
+    crate::devices::BjtSpec,
+    crate::devices::MosfetSpec,
+    crate::devices::JfetSpec,
+    crate::devices::SwitchSpec
+);
+
+/// The span of the first of `cards` using a model, if any does.
+fn first_span<T: Card>(cards: &[T], uses: impl Fn(&T) -> bool) -> Option<Span> {
+    cards.iter().find(|card| uses(card)).map(Card::span)
+}
+
+/// Every parameter of `deck` out of its range: the device parameters first, then the models
+/// by name.
+pub fn check_parameters(deck: &Deck) -> Vec<ParameterError> {
+    let mut checks = Checks::default();
+    checks.check_instances(deck);
+    for (name, model) in deck.models.iter() {
+        checks.check_model(name, model, deck);
+    }
+    checks.found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParseOptions, parse};
+
+    fn check(netlist: &str) -> Vec<ParameterError> {
+        let mut options = ParseOptions::new_with_source("params.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        check_parameters(&deck)
+    }
+
+    #[test]
+    fn physical_values_pass() {
+        let found = check(
+            "ok\nV1 in 0 1\nR1 in out 1k\nC1 out 0 1u\nL1 out 0 1m\nD1 out 0 dmod\n\
+             .model dmod d is=1e-14 n=1.5 rs=0\n.op\n.end\n",
+        );
+        assert!(found.is_empty(), "{found:?}");
+    }
+
+    #[test]
+    fn zero_resistance_and_negative_capacitance_are_errors() {
+        let netlist = "bad\nV1 in 0 1\nR1 in out 0\nC1 out 0 -1u\n.op\n.end\n";
+        let found = check(netlist);
+        let [r1, c1] = found.as_slice() else {
+            panic!("{found:?}");
+        };
+        assert!(r1.is_error() && c1.is_error());
+        assert_eq!(r1.to_string(), "R1: r = 0 must be nonzero");
+        assert_eq!(r1.code(), "E0901");
+        let span = c1.error_span().expect("span");
+        assert_eq!(&netlist[span.start..=span.end], "C1 out 0 -1u");
+    }
+
+    #[test]
+    fn model_parameters_point_at_the_first_device_using_them() {
+        let netlist = "models\nV1 in 0 1\nR1 in a 1k\nD1 a 0 big\nD2 a 0 big\nD3 a 0 bad\n\
+                       .model big d is=0.1\n.model bad d is=-1e-14\n.op\n.end\n";
+        let found = check(netlist);
+        let [bad, big] = found.as_slice() else {
+            panic!("{found:?}");
+        };
+        assert!(bad.is_error());
+        assert_eq!(
+            bad.to_string(),
+            "model bad: is = -0.00000000000001 must be positive"
+        );
+        // an unusually large saturation current is only a warning
+        assert!(!big.is_error());
+        assert_eq!(big.code(), "W0002");
+        let span = big.error_span().expect("span");
+        assert_eq!(&netlist[span.start..=span.end], "D1 a 0 big");
+    }
+
+    #[test]
+    fn units_are_checked_where_the_parameter_has_one() {
+        let found = check(
+            "units\nV1 in 0 5V\nI1 0 out 2mA\nR1 in out 10kOhm\nC1 out 0 1uF ic=1V\n\
+             L1 out 0 1uF\n.op\n.end\n",
+        );
+        let [warning] = found.as_slice() else {
+            panic!("{found:?}");
+        };
+        let ParameterError::WrongUnit {
+            owner,
+            param,
+            found,
+            expected,
+            ..
+        } = warning
+        else {
+            panic!("{warning:?}");
+        };
+        assert_eq!((owner.as_str(), *param), ("L1", "l"));
+        assert_eq!((*found, *expected), (Unit::Farad, Unit::Henry));
+        assert!(!warning.is_error());
+        assert_eq!(warning.to_string(), "L1: l is in H, not F");
+    }
+
+    #[test]
+    fn lone_farad_is_ambiguous() {
+        let found = check("farads\nV1 in 0 1\nR1 in out 1k\nC1 out 0 10F\n.op\n.end\n");
+        let [error] = found.as_slice() else {
+            panic!("{found:?}");
+        };
+        assert_eq!(error.code(), "W0004");
+        assert_eq!(
+            error.to_string(),
+            "C1: c is read as 10 farads, but ngspice reads a lone F as femto"
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Reusing a parse while its sources are unchanged, for the TUI or a watch loop that parses the
+//! same netlist over and over.
+//!
+//! The cache keeps the deck of the last parse with the hash of every source it read. The next
+//! parse hashes the main source and reads and hashes the included files again; when none of
+//! them changed, the deck is reused without lexing, expanding includes or expanding
+//! subcircuits, and the included files are put back in the source map under the same ids so
+//! the deck's spans still point into them. A single changed file invalidates the deck.
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::SpicyError;
+use crate::instance_parser::Deck;
+use crate::{Compatibility, NameCase, ParseOptions, parse};
+
+fn content_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// An included file as a parse puts it in the source map. The hashes are of these contents, so
+/// an edit between the parse and the hashing cannot go unnoticed.
+fn read_included(path: &Path, compatibility: Compatibility) -> Option<String> {
+    let content = std::fs::read_to_string(path).ok()?;
+    Some(compatibility.translate(content, false))
+}
+
+/// Everything besides the sources that the deck depends on.
+#[derive(Debug, Clone, PartialEq)]
+struct OptionsKey {
+    work_dir: PathBuf,
+    source_path: PathBuf,
+    max_include_depth: usize,
+    name_case: NameCase,
+    compatibility: Compatibility,
+    variables: HashMap<String, String>,
+}
+
+impl OptionsKey {
+    fn new(options: &ParseOptions) -> Self {
+        Self {
+            work_dir: options.work_dir.clone(),
+            source_path: options.source_path.clone(),
+            max_include_depth: options.max_include_depth,
+            name_case: options.name_case,
+            compatibility: options.compatibility,
+            variables: options.variables.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    options: OptionsKey,
+    /// Path and content hash of every source, in source id order: the main source first, then
+    /// the included files.
+    sources: Vec<(PathBuf, u64)>,
+    deck: Arc<Deck>,
+}
+
+#[derive(Debug, Default)]
+pub struct ParseCache {
+    entry: Option<CacheEntry>,
+    /// Parses answered from the cache.
+    pub hits: usize,
+    /// Parses that had to run.
+    pub misses: usize,
+}
+
+impl ParseCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse like [`parse`], reusing the deck of the last parse when no source changed.
+    /// `options` must hold only the main source, as [`ParseOptions::new_with_source`] makes
+    /// it; on a hit the included files are added to its source map as a parse would.
+    pub fn parse(&mut self, options: &mut ParseOptions) -> Result<Arc<Deck>, SpicyError> {
+        if let Some(deck) = self.lookup(options) {
+            self.hits += 1;
+            return Ok(deck);
+        }
+        self.misses += 1;
+        self.entry = None;
+        let main_hash = content_hash(options.source_map.get_main_content());
+        let key = OptionsKey::new(options);
+        let deck = Arc::new(parse(options)?);
+
+        let source_map = &options.source_map;
+        let mut sources = vec![(
+            source_map.get_path(source_map.main_index()).into(),
+            main_hash,
+        )];
+        for index in source_map.included_indices() {
+            let path = source_map.get_path(index).to_path_buf();
+            sources.push((path, content_hash(source_map.get_content(index))));
+        }
+        self.entry = Some(CacheEntry {
+            options: key,
+            sources,
+            deck: Arc::clone(&deck),
+        });
+        Ok(deck)
+    }
+
+    /// The sources of the cached deck that changed, main source included; empty when the
+    /// cached deck is still good or there is none.
+    pub fn changed_sources(&self, options: &ParseOptions) -> Vec<PathBuf> {
+        let Some(entry) = &self.entry else {
+            return Vec::new();
+        };
+        let main = content_hash(options.source_map.get_main_content());
+        let (main_path, main_hash) = &entry.sources[0];
+        let main_changed = (main != *main_hash).then(|| main_path.clone());
+        let included = entry.sources[1..].iter().filter_map(|(path, hash)| {
+            let content = read_included(path, options.compatibility);
+            (content.map(|content| content_hash(&content)) != Some(*hash)).then(|| path.clone())
+        });
+        main_changed.into_iter().chain(included).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.entry = None;
+    }
+
+    fn lookup(&self, options: &mut ParseOptions) -> Option<Arc<Deck>> {
+        let entry = self.entry.as_ref()?;
+        if entry.options != OptionsKey::new(options)
+            || options.source_map.included_indices().next().is_some()
+            || content_hash(options.source_map.get_main_content()) != entry.sources[0].1
+        {
+            return None;
+        }
+        let mut included = Vec::with_capacity(entry.sources.len() - 1);
+        for (path, hash) in &entry.sources[1..] {
+            let content = read_included(path, options.compatibility)?;
+            if content_hash(&content) != *hash {
+                return None;
+            }
+            included.push((path.clone(), content));
+        }
+
+        options.source_map.translate_main(options.compatibility);
+        for (path, content) in included {
+            options.source_map.push_source(path, content);
+        }
+        Some(Arc::clone(&entry.deck))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempNetlist {
+        dir: PathBuf,
+    }
+
+    impl TempNetlist {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("spicy-parse-cache-{name}-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self { dir }
+        }
+
+        fn write(&self, name: &str, content: &str) -> PathBuf {
+            let path = self.dir.join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        }
+
+        fn options(&self, main: &str) -> ParseOptions {
+            let path = self.dir.join("main.spicy");
+            ParseOptions::new_with_source(path, main.to_string())
+        }
+    }
+
+    impl Drop for TempNetlist {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.dir).ok();
+        }
+    }
+
+    const MAIN: &str = "cached\nV1 in 0 1\n.include parts.spicy\nX1 in 0 divider\n.op\n.end\n";
+    const PARTS: &str = ".subckt divider a b\nR1 a mid 1k\nR2 mid b 1k\n.ends\n";
+
+    #[test]
+    fn unchanged_sources_reuse_the_deck() {
+        let files = TempNetlist::new("hit");
+        files.write("parts.spicy", PARTS);
+        let mut cache = ParseCache::new();
+
+        let first = cache.parse(&mut files.options(MAIN)).unwrap();
+        let mut options = files.options(MAIN);
+        let second = cache.parse(&mut options).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!((cache.hits, cache.misses), (1, 1));
+
+        // the spans of the deck point into the sources put back in the map
+        let resistor = &second.devices.resistors[0];
+        let src = options.source_map.get_content(resistor.span.source_index);
+        assert!(src[resistor.span.start..=resistor.span.end].starts_with("R1"));
+    }
+
+    #[test]
+    fn a_changed_file_invalidates_the_deck() {
+        let files = TempNetlist::new("miss");
+        let parts = files.write("parts.spicy", PARTS);
+        let mut cache = ParseCache::new();
+        cache.parse(&mut files.options(MAIN)).unwrap();
+
+        files.write("parts.spicy", &PARTS.replace("R2 mid b 1k", "R2 mid b 3k"));
+        let options = files.options(MAIN);
+        let changed = cache.changed_sources(&options);
+        assert_eq!(changed, vec![std::fs::canonicalize(parts).unwrap()]);
+        let deck = cache.parse(&mut files.options(MAIN)).unwrap();
+        let resistance = deck.devices.resistors[1].resistance.as_ref().unwrap();
+        assert_eq!(resistance.get_value(), 3e3);
+        assert_eq!((cache.hits, cache.misses), (0, 2));
+
+        let edited = MAIN.replace("V1 in 0 1", "V1 in 0 2");
+        assert_eq!(cache.changed_sources(&files.options(&edited)).len(), 1);
+        cache.parse(&mut files.options(&edited)).unwrap();
+        cache.parse(&mut files.options(&edited)).unwrap();
+        assert_eq!((cache.hits, cache.misses), (1, 3));
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        let files = TempNetlist::new("error");
+        let mut cache = ParseCache::new();
+        assert!(cache.parse(&mut files.options(MAIN)).is_err());
+        files.write("parts.spicy", PARTS);
+        assert!(cache.parse(&mut files.options(MAIN)).is_ok());
+        assert_eq!(cache.misses, 2);
+    }
+}
@@ -4,7 +4,8 @@ use crate::expr::{Expr, Params};
 use crate::expr::{PlaceholderMap, Scope, Value};
 use crate::lexer::{TokenKind, token_text};
 use crate::netlist_types::NodeName;
-use crate::netlist_types::ValueSuffix;
+use crate::netlist_types::{Unit, ValueSuffix};
+use crate::node_mapping::HIERARCHY_SEPARATOR;
 use crate::statement_phase::StmtCursor;
 
 pub(crate) struct Ident<'a> {
@@ -28,14 +29,35 @@ pub(crate) fn parse_node(cursor: &mut StmtCursor, src: &str) -> Result<NodeName,
         }
         .into());
     }
-    let node_string = token_text(src, node).to_string();
+    let mut node_string = token_text(src, node).to_string();
+    consume_hierarchy(cursor, src, &mut node_string);
     Ok(NodeName(node_string))
 }
 
+/// Append the `.name` segments written right after `name`: the path of a node or device
+/// inside a subcircuit instance, e.g. `X1.out`.
+pub(crate) fn consume_hierarchy(cursor: &mut StmtCursor, src: &str, name: &mut String) {
+    loop {
+        let mark = cursor.checkpoint();
+        if cursor.consume(TokenKind::Dot).is_some()
+            && let Some(segment) = cursor.peek()
+            && matches!(segment.kind, TokenKind::Ident | TokenKind::Number)
+        {
+            cursor.next();
+            name.push(HIERARCHY_SEPARATOR);
+            name.push_str(token_text(src, segment));
+            continue;
+        }
+        cursor.rewind(mark);
+        return;
+    }
+}
+
 pub(crate) fn parse_value(cursor: &mut StmtCursor, src: &str) -> Result<Value, SpicyError> {
     let mut number_str = String::new();
     let mut exponent: Option<f64> = None;
     let mut suffix: Option<ValueSuffix> = None;
+    let mut unit: Option<Unit> = None;
 
     let mut t = cursor
         .next_non_whitespace()
@@ -148,8 +170,24 @@ pub(crate) fn parse_value(cursor: &mut StmtCursor, src: &str) -> Result<Value, S
                 );
         } else if ident_text.starts_with("e") || ident_text.starts_with("E") {
             cursor.next().expect("just peeked");
-            // Split ident after 'e' or 'E' and assume it is the exponent digits
+            // Split ident after 'e' or 'E' into the exponent digits and any unit letters
             let (_e_char, exp_digits_str) = ident_text.split_at(1);
+            let digits = exp_digits_str
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(exp_digits_str.len());
+            let (exp_digits_str, letters) = exp_digits_str.split_at(digits);
+            if !letters.is_empty() {
+                let fraction;
+                (suffix, fraction, unit) = split_suffix(letters);
+                // a `1k5` fraction has no exponent to go with
+                if !fraction.is_empty() {
+                    return Err(ParserError::InvalidNumericLiteral {
+                        span: Some(peek.span),
+                        lexeme: format!("{number_str}{ident_text}"),
+                    }
+                    .into());
+                }
+            }
             if exp_digits_str.is_empty() {
                 return Err(ParserError::MissingToken {
                     message: "Expected digits after exponent",
@@ -166,13 +204,26 @@ pub(crate) fn parse_value(cursor: &mut StmtCursor, src: &str) -> Result<Value, S
         }
     }
 
-    // Optional suffix as trailing identifier without whitespace
+    // Optional suffix and unit as trailing identifier without whitespace
     if let Some(peek) = cursor.peek()
         && matches!(peek.kind, TokenKind::Ident)
     {
         let ident = cursor.next().expect("just peeked");
         let ident_text = token_text(src, ident);
-        suffix = ident_text.parse::<ValueSuffix>().ok();
+        let fraction;
+        (suffix, fraction, unit) = split_suffix(ident_text);
+        // `4k7` is 4.7k, the suffix standing for the decimal point
+        if !fraction.is_empty() {
+            if number_str.contains('.') || exponent.is_some() {
+                return Err(ParserError::InvalidNumericLiteral {
+                    span: Some(ident.span),
+                    lexeme: format!("{number_str}{ident_text}"),
+                }
+                .into());
+            }
+            number_str.push('.');
+            number_str.push_str(fraction);
+        }
     }
 
     let value: f64 = number_str
@@ -186,9 +237,36 @@ pub(crate) fn parse_value(cursor: &mut StmtCursor, src: &str) -> Result<Value, S
         value,
         exponent,
         suffix,
+        unit,
     })
 }
 
+/// The suffix, the digits of a `4k7` style fraction and the unit of the letters after a
+/// number: `uF` is micro farads and `k7Ohm` 0.7 kilo ohms.
+///
+/// Letters spelling a whole unit name are the unit before any suffix, so `10amp` is 10
+/// amperes rather than 10 atto. A unit letter on its own is ambiguous: `10f` is 10 femto,
+/// while `10F` is read as 10 farads, which ngspice would take as femto and the parameter
+/// checks warn about (W0004).
+fn split_suffix(text: &str) -> (Option<ValueSuffix>, &str, Option<Unit>) {
+    if text.len() > 1
+        && let Ok(unit) = text.parse::<Unit>()
+    {
+        return (None, "", Some(unit));
+    }
+    match text.parse::<ValueSuffix>() {
+        Ok(suffix) => {
+            let rest = &text[suffix.written_len()..];
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let (fraction, unit) = rest.split_at(digits);
+            (Some(suffix), fraction, unit.parse().ok())
+        }
+        Err(()) => (None, "", text.parse().ok()),
+    }
+}
+
 pub(crate) fn parse_usize(cursor: &mut StmtCursor, src: &str) -> Result<usize, SpicyError> {
     let usize_token = cursor.expect_non_whitespace(TokenKind::Number)?;
     let usize_text = token_text(src, usize_token);
@@ -222,6 +300,14 @@ pub(crate) fn parse_ident<'a>(
     })
 }
 
+/// A device name, with the path of its subcircuit instance if written out, e.g. `R.X1.R1` in
+/// a flattened netlist.
+pub(crate) fn parse_device_name(cursor: &mut StmtCursor, src: &str) -> Result<String, SpicyError> {
+    let mut name = parse_ident(cursor, src)?.text.to_string();
+    consume_hierarchy(cursor, src, &mut name);
+    Ok(name)
+}
+
 pub(crate) fn parse_expr_into_value(
     cursor: &mut StmtCursor,
     src: &str,
@@ -231,20 +317,20 @@ pub(crate) fn parse_expr_into_value(
     cursor.skip_ws();
     if let Some(token) = cursor.consume(TokenKind::Placeholder) {
         let id = token.id.expect("must have a placeholder id");
-        // TODO: maybe we can change the expression to only evaluate once
-        let expr = placeholder_map.get(id).clone();
-        let evaluated = expr.evaluate(scope)?;
-        return Ok(evaluated);
+        return placeholder_map.evaluate(id, scope);
     }
     parse_value(cursor, src)
 }
 
+/// A param value: a plain value, or the expression a `{...}` placeholder stands for.
 pub(crate) fn parse_value_or_placeholder(
     cursor: &mut StmtCursor,
     src: &str,
+    placeholder_map: &PlaceholderMap,
 ) -> Result<Expr, SpicyError> {
     if let Some(placeholder) = cursor.consume(TokenKind::Placeholder) {
-        return Ok(Expr::placeholder(placeholder.id.unwrap(), placeholder.span));
+        let id = placeholder.id.expect("must have a placeholder id");
+        return Ok(placeholder_map.get(id).clone());
     }
     // TODO: i think value should just have a span
     let cursor_span = cursor
@@ -259,10 +345,11 @@ pub(crate) fn parse_value_or_placeholder(
 pub(crate) fn parse_equal_expr<'a>(
     cursor: &mut StmtCursor,
     src: &'a str,
+    placeholder_map: &PlaceholderMap,
 ) -> Result<(Ident<'a>, Expr), SpicyError> {
     let ident = parse_ident(cursor, src)?;
     cursor.expect(TokenKind::Equal)?;
-    let value = parse_value_or_placeholder(cursor, src)?;
+    let value = parse_value_or_placeholder(cursor, src, placeholder_map)?;
     Ok((ident, value))
 }
 
@@ -270,15 +357,211 @@ pub(crate) fn parse_equal_expr<'a>(
 pub(crate) fn parse_dot_param(
     cursor: &mut StmtCursor,
     src: &str,
+    placeholder_map: &PlaceholderMap,
     env: &mut Params,
 ) -> Result<(), SpicyError> {
     while let Some(token) = cursor.next() {
-        if token.kind != TokenKind::WhiteSpace {
+        // trailing whitespace ends the statement too
+        if token.kind != TokenKind::WhiteSpace || cursor.done() {
             break;
         }
-        let (ident, value) = parse_equal_expr(cursor, src)?;
+        let (ident, value) = parse_equal_expr(cursor, src, placeholder_map)?;
         env.set_param(ident.text.to_string(), value);
     }
     assert!(cursor.done(), "Expected end of statement");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::libs_phase::SourceFileId;
+    use crate::statement_phase::Statements;
+
+    fn value(literal: &str) -> Result<Value, SpicyError> {
+        let statements = Statements::new(literal, SourceFileId::new(0)).expect("statements");
+        let mut cursor = statements.statements[0].as_cursor();
+        let value = parse_value(&mut cursor, literal)?;
+        assert!(cursor.done(), "{literal} left tokens behind");
+        Ok(value)
+    }
+
+    #[rstest]
+    #[case("1k5", 1.5e3, None)]
+    #[case("4k7Ohm", 4.7e3, Some(Unit::Ohm))]
+    #[case("2u2F", 2.2e-6, Some(Unit::Farad))]
+    #[case("10kOhm", 1e4, Some(Unit::Ohm))]
+    #[case("1meg", 1e6, None)]
+    #[case("1MEG", 1e6, None)]
+    #[case("2.2Megohm", 2.2e6, Some(Unit::Ohm))]
+    #[case("1m", 1e-3, None)]
+    #[case("1M", 1e-3, None)]
+    #[case("3g", 3e9, None)]
+    #[case("1t", 1e12, None)]
+    #[case("1e6F", 1e6, Some(Unit::Farad))]
+    #[case("1e3k", 1e6, None)]
+    #[case("5ms", 5e-3, Some(Unit::Second))]
+    #[case("10F", 10.0, Some(Unit::Farad))]
+    #[case("10f", 10e-15, None)]
+    #[case("10amp", 10.0, Some(Unit::Ampere))]
+    #[case("2Amps", 2.0, Some(Unit::Ampere))]
+    #[case("1farad", 1.0, Some(Unit::Farad))]
+    #[case("3mamp", 3e-3, Some(Unit::Ampere))]
+    #[case("1a", 1e-18, None)]
+    fn suffix_edge_cases(#[case] literal: &str, #[case] expected: f64, #[case] unit: Option<Unit>) {
+        let value = value(literal).unwrap_or_else(|e| panic!("{literal}: {e}"));
+        assert!(
+            (value.get_value() - expected).abs() <= 1e-12 * expected.abs(),
+            "{literal} = {}",
+            value.get_value()
+        );
+        assert_eq!(value.unit, unit, "{literal}");
+    }
+
+    #[rstest]
+    #[case("1.5k5")]
+    #[case("1e3k5")]
+    fn fraction_after_a_suffix_needs_an_integer(#[case] literal: &str) {
+        assert!(value(literal).is_err(), "{literal}");
+    }
+
+    struct XorShift64 {
+        state: u64,
+    }
+
+    impl XorShift64 {
+        fn next_u64(&mut self) -> u64 {
+            // xorshift64*
+            let mut x = self.state;
+            x ^= x >> 12;
+            x ^= x << 25;
+            x ^= x >> 27;
+            self.state = x;
+            x.wrapping_mul(0x2545f4914f6cdd1d)
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next_u64() % n as u64) as usize
+        }
+
+        fn digits(&mut self) -> String {
+            let len = 1 + self.below(5);
+            (0..len)
+                .map(|_| char::from(b'0' + self.below(10) as u8))
+                .collect()
+        }
+    }
+
+    /// Suffix spellings with their scale, the way SPICE reads them.
+    const SUFFIXES: &[(&str, f64)] = &[
+        ("", 1.0),
+        ("T", 1e12),
+        ("t", 1e12),
+        ("G", 1e9),
+        ("g", 1e9),
+        ("Meg", 1e6),
+        ("MEG", 1e6),
+        ("meg", 1e6),
+        ("k", 1e3),
+        ("K", 1e3),
+        ("m", 1e-3),
+        ("M", 1e-3),
+        ("u", 1e-6),
+        ("U", 1e-6),
+        ("n", 1e-9),
+        ("p", 1e-12),
+        ("f", 1e-15),
+        ("a", 1e-18),
+    ];
+
+    const UNITS: &[(&str, Option<Unit>)] = &[
+        ("", None),
+        ("V", Some(Unit::Volt)),
+        ("A", Some(Unit::Ampere)),
+        ("F", Some(Unit::Farad)),
+        ("H", Some(Unit::Henry)),
+        ("Ohm", Some(Unit::Ohm)),
+        ("ohm", Some(Unit::Ohm)),
+        ("s", Some(Unit::Second)),
+        ("Hz", Some(Unit::Hertz)),
+    ];
+
+    /// Random literals against a reference reading of them: the mantissa by the standard
+    /// library, scaled by the exponent and by the suffix from the table above.
+    #[test]
+    fn values_match_a_reference_reading() {
+        let mut rng = XorShift64 {
+            state: 0x9e3779b97f4a7c15,
+        };
+        for _ in 0..5000 {
+            let negative = rng.below(4) == 0;
+            let integer = if rng.below(8) == 0 {
+                String::new()
+            } else {
+                rng.digits()
+            };
+            let fraction = if integer.is_empty() || rng.below(3) == 0 {
+                Some(rng.digits())
+            } else {
+                None
+            };
+            let exponent = (rng.below(3) == 0).then(|| rng.below(41) as i32 - 20);
+            let (suffix, scale) = SUFFIXES[rng.below(SUFFIXES.len())];
+            // `4k7` style, which only an integer with a suffix and no exponent can have
+            let suffix_fraction = (!suffix.is_empty()
+                && fraction.is_none()
+                && exponent.is_none()
+                && rng.below(3) == 0)
+                .then(|| rng.digits());
+            let (unit_text, unit) = UNITS[rng.below(UNITS.len())];
+
+            let mut literal = String::new();
+            if negative {
+                literal.push('-');
+            }
+            literal.push_str(&integer);
+            if let Some(fraction) = &fraction {
+                literal.push('.');
+                literal.push_str(fraction);
+            }
+            if let Some(exponent) = exponent {
+                let e = if rng.below(2) == 0 { "e" } else { "E" };
+                let sign = match (exponent < 0, rng.below(2)) {
+                    (true, _) => "-",
+                    (false, 0) => "+",
+                    (false, _) => "",
+                };
+                literal.push_str(&format!("{e}{sign}{}", exponent.abs()));
+            }
+            literal.push_str(suffix);
+            if let Some(digits) = &suffix_fraction {
+                literal.push_str(digits);
+            }
+            literal.push_str(unit_text);
+
+            let mantissa = format!(
+                "{}{}.{}",
+                if negative { "-" } else { "" },
+                if integer.is_empty() { "0" } else { &integer },
+                fraction
+                    .as_ref()
+                    .or(suffix_fraction.as_ref())
+                    .map_or("0", |f| f)
+            );
+            let expected = mantissa.parse::<f64>().expect("mantissa")
+                * 10f64.powi(exponent.unwrap_or(0))
+                * scale;
+
+            let value = value(&literal).unwrap_or_else(|e| panic!("{literal}: {e}"));
+            let error = (value.get_value() - expected).abs();
+            assert!(
+                error <= 1e-12 * expected.abs(),
+                "{literal}: {} against {expected}",
+                value.get_value()
+            );
+            assert_eq!(value.unit, unit, "{literal}");
+        }
+    }
+}
@@ -5,6 +5,7 @@ use crate::{
     netlist_types::{CommandType, DeviceType},
 };
 use serde::Serialize;
+use std::ops::Range;
 
 #[derive(Debug, Clone, Serialize)]
 pub(crate) struct Statement {
@@ -136,9 +137,11 @@ impl<'a> StmtCursor<'a> {
             }
             .into());
         }
+        // at the end of the statement, point at its last character
+        let end = Span::new(self.span.end, self.span.end, self.span.source_index);
         Err(ParserError::MissingToken {
             message: "token",
-            span: self.peek_span(),
+            span: Some(end),
         }
         .into())
     }
@@ -213,6 +216,13 @@ impl<'a> StmtCursor<'a> {
         None
     }
 
+    /// Consume and return every token left in the statement.
+    pub(crate) fn rest(&mut self) -> &'a [Token] {
+        let rest = &self.toks[self.i..];
+        self.i = self.toks.len();
+        rest
+    }
+
     pub(crate) fn contains(&self, kind: TokenKind) -> bool {
         self.toks.iter().any(|t| t.kind == kind)
     }
@@ -320,7 +330,19 @@ impl Statements {
     }
 
     pub(crate) fn new(input: &str, source_index: SourceFileId) -> Result<Self, SpicyError> {
-        let mut lexer = Lexer::new(input, source_index);
+        Self::from_lexer(Lexer::new(input, source_index))
+    }
+
+    /// The statements of `range` of `input` only, such as one `.LIB` section of a library.
+    pub(crate) fn new_in(
+        input: &str,
+        range: Range<usize>,
+        source_index: SourceFileId,
+    ) -> Result<Self, SpicyError> {
+        Self::from_lexer(Lexer::new_in(input, range, source_index))
+    }
+
+    fn from_lexer(mut lexer: Lexer<'_>) -> Result<Self, SpicyError> {
         let mut statements = Vec::new();
         let mut token = lexer.next()?;
 
@@ -1,10 +1,14 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::rc::Rc;
 
 use serde::Serialize;
 
 use crate::SourceMap;
-use crate::error::{SpicyError, SubcircuitError};
-use crate::expr::{Params, Scope, ScopeId};
+use crate::Value;
+use crate::error::{ParserError, SpicyError, SubcircuitError};
+use crate::expr::{Expr, ParamValues, Params, Scope, ScopeId};
 use crate::expr::{PlaceholderMap, ScopeArena};
 use crate::netlist_models::partial_parse_model_command;
 use crate::netlist_models::{ModelStatementTable, ModelTable};
@@ -14,7 +18,10 @@ use crate::parser_utils::{
     parse_dot_param, parse_equal_expr, parse_ident, parse_node, parse_value_or_placeholder,
 };
 use crate::statement_phase::{Statements, StmtCursor};
-use crate::{lexer::TokenKind, statement_phase::Statement};
+use crate::{
+    lexer::{TokenKind, token_text},
+    statement_phase::Statement,
+};
 
 #[cfg(test)]
 use crate::test_utils::serialize_sorted_map;
@@ -26,6 +33,8 @@ pub(crate) struct UnexpandedDeck {
     pub model_table: ModelStatementTable,
     pub subckt_table: SubcktTable,
     pub statements: Vec<Statement>,
+    /// Nodes named by `.global`, shared by every subcircuit instance.
+    pub global_nodes: Vec<NodeName>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -34,7 +43,7 @@ pub(crate) struct SubcktDecl {
     pub nodes: Vec<NodeName>,
     pub default_params: Params,
     pub local_params: Params,
-    pub body: Vec<Statement>, // statements between .subckt and .ends (already placeholderized)
+    pub body: Vec<Rc<Statement>>, // statements between .subckt and .ends (already placeholderized)
 }
 
 #[derive(Debug, Default, Clone, Serialize)]
@@ -45,19 +54,22 @@ pub(crate) struct SubcktTable {
 
 #[derive(Debug, Clone, Serialize)]
 pub(crate) struct ScopedStmt {
-    pub stmt: Statement,
+    /// shared between every instance of a subcircuit
+    pub stmt: Rc<Statement>,
     pub scope: ScopeId,
 }
 
 pub(crate) fn collect_subckts(
     stmts: Statements,
     source_map: &SourceMap,
+    placeholder_map: &PlaceholderMap,
 ) -> Result<UnexpandedDeck, SpicyError> {
     let mut out = Vec::new();
     let mut table = SubcktTable::default();
     let mut model_table = ModelStatementTable::default();
     let mut scope_arena = ScopeArena::new();
     let (root_env, root_env_id) = scope_arena.new_root();
+    let mut global_nodes = Vec::new();
     let mut it = stmts.statements.into_iter();
 
     while let Some(s) = it.next() {
@@ -66,7 +78,12 @@ pub(crate) fn collect_subckts(
         let input = source_map.get_content(s.span.source_index);
 
         if cursor.consume_if_command(input, CommandType::Param) {
-            parse_dot_param(&mut cursor, input, &mut root_env.param_map)?;
+            parse_dot_param(
+                &mut cursor,
+                input,
+                placeholder_map,
+                Rc::make_mut(&mut root_env.param_map),
+            )?;
             continue;
         }
 
@@ -76,14 +93,24 @@ pub(crate) fn collect_subckts(
             continue;
         }
 
+        if cursor.consume_if_command(input, CommandType::Global) {
+            parse_global_command(&mut cursor, input, &mut global_nodes)?;
+            continue;
+        }
+
         if cursor.consume_if_command(input, CommandType::Subcircuit) {
-            let mut subckt = parse_subckt_command(&mut cursor, input)?;
+            let mut subckt = parse_subckt_command(&mut cursor, input, placeholder_map)?;
             let mut body = Vec::new();
             // TODO: this doesn't support nested subcircuits
             for next in it.by_ref() {
                 let mut inner_cursor = next.as_cursor();
                 if inner_cursor.consume_if_command(input, CommandType::Param) {
-                    parse_dot_param(&mut inner_cursor, input, &mut subckt.local_params)?;
+                    parse_dot_param(
+                        &mut inner_cursor,
+                        input,
+                        placeholder_map,
+                        &mut subckt.local_params,
+                    )?;
                     continue;
                 }
                 if inner_cursor.consume_if_command(input, CommandType::Model) {
@@ -91,12 +118,16 @@ pub(crate) fn collect_subckts(
                     model_table.insert(model_statement)?;
                     continue;
                 }
+                if inner_cursor.consume_if_command(input, CommandType::Global) {
+                    parse_global_command(&mut inner_cursor, input, &mut global_nodes)?;
+                    continue;
+                }
                 // collect body until .ends
                 if inner_cursor.consume_if_command(input, CommandType::Ends) {
                     // TODO: the .ends command also has the subcircuit name, add assert here
                     break;
                 }
-                body.push(next);
+                body.push(Rc::new(next));
             }
             subckt.body = body;
             table.map.insert(subckt.name.clone(), subckt);
@@ -111,27 +142,83 @@ pub(crate) fn collect_subckts(
         model_table,
         subckt_table: table,
         statements: out,
+        global_nodes,
     })
 }
 
-// SUBCKT subnam N1 <N2 N3 ...>
-fn parse_subckt_command(cursor: &mut StmtCursor, src: &str) -> Result<SubcktDecl, SpicyError> {
+// .global n1 <n2 ...>
+fn parse_global_command(
+    cursor: &mut StmtCursor,
+    src: &str,
+    global_nodes: &mut Vec<NodeName>,
+) -> Result<(), SpicyError> {
+    global_nodes.push(parse_node(cursor, src)?);
+    while cursor.peek_non_whitespace().is_some() {
+        global_nodes.push(parse_node(cursor, src)?);
+    }
+    Ok(())
+}
+
+/// Replace top-level `.param`s with fixed values, e.g. the variables of an optimization run.
+pub(crate) fn override_params(
+    deck: &mut UnexpandedDeck,
+    overrides: &[(String, f64)],
+) -> Result<(), SpicyError> {
+    let root = deck.scope_arena.get_mut(deck.global_params);
+    let params = Rc::make_mut(&mut root.param_map);
+    for (name, value) in overrides {
+        let Some(expr) = params.get_param(name) else {
+            return Err(ParserError::UnknownParam { name: name.clone() }.into());
+        };
+        let value = Expr::value(Value::new(*value, None, None), expr.span);
+        params.set_param(name.clone(), value);
+    }
+    Ok(())
+}
+
+/// Consume the optional `params:` keyword that introduces the params of a `.subckt` or `X` line.
+fn consume_params_keyword(cursor: &mut StmtCursor, src: &str) -> bool {
+    let mark = cursor.checkpoint();
+    cursor.skip_ws();
+    if let Some(ident) = cursor.consume(TokenKind::Ident)
+        && token_text(src, ident).eq_ignore_ascii_case("params")
+        && cursor.consume(TokenKind::Colon).is_some()
+    {
+        return true;
+    }
+    cursor.rewind(mark);
+    false
+}
+
+// SUBCKT subnam N1 <N2 N3 ...> <params:> <P1=V1 P2=V2 ...>
+fn parse_subckt_command(
+    cursor: &mut StmtCursor,
+    src: &str,
+    placeholder_map: &PlaceholderMap,
+) -> Result<SubcktDecl, SpicyError> {
     let name = parse_ident(cursor, src)?;
 
     let first_node = parse_node(cursor, src)?;
 
     let mut nodes = vec![first_node];
     let mut default_params = Params::new();
+    let mut in_params = false;
 
     loop {
+        if consume_params_keyword(cursor, src) {
+            in_params = true;
+            continue;
+        }
         cursor.skip_ws();
         let Some(_) = cursor.peek() else {
             break;
         };
         let node = parse_node(cursor, src)?;
-        if cursor.consume(TokenKind::Equal).is_some() {
+        // after `params:` every entry must be a param
+        if in_params || cursor.peek().is_some_and(|t| t.kind == TokenKind::Equal) {
+            cursor.expect(TokenKind::Equal)?;
             let param_name = node.0;
-            let value = parse_value_or_placeholder(cursor, src)?;
+            let value = parse_value_or_placeholder(cursor, src, placeholder_map)?;
             default_params.set_param(param_name, value);
         } else {
             // TODO: technically we can't parse nodes after we saw parameters
@@ -151,6 +238,7 @@ fn parse_subckt_command(cursor: &mut StmtCursor, src: &str) -> Result<SubcktDecl
 fn parse_x_device(
     cursor: &mut StmtCursor,
     src: &str,
+    placeholder_map: &PlaceholderMap,
 ) -> Result<(Vec<NodeName>, String, Params), SpicyError> {
     // Phase 1: parse only nodes (last one is the subcircuit name)
     let first_node = parse_node(cursor, src)?;
@@ -158,6 +246,9 @@ fn parse_x_device(
     let mut nodes = vec![first_node];
 
     loop {
+        if consume_params_keyword(cursor, src) {
+            break;
+        }
         let mark = cursor.checkpoint();
         cursor.skip_ws();
         let is_param_start = if cursor.consume(TokenKind::Ident).is_some() {
@@ -199,13 +290,23 @@ fn parse_x_device(
             break;
         }
 
-        let (param_name, value) = parse_equal_expr(cursor, src)?;
+        let (param_name, value) = parse_equal_expr(cursor, src, placeholder_map)?;
         param_overrides.set_param(param_name.text.to_string(), value);
     }
 
     Ok((nodes, subcircuit_name, param_overrides))
 }
 
+/// Replace the `X` instance param expressions by their values in `scope`.
+fn evaluate_overrides(overrides: Params, scope: &Scope) -> Result<Params, SpicyError> {
+    let mut evaluated = Params::new();
+    for (name, expr) in overrides.into_iter() {
+        let value = expr.evaluate(scope)?;
+        evaluated.set_param(name, Expr::value(value, expr.span));
+    }
+    Ok(evaluated)
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub(crate) struct ExpandedDeck {
     pub scope_arena: ScopeArena,
@@ -213,24 +314,81 @@ pub(crate) struct ExpandedDeck {
     pub global_params: ScopeId,
     pub subckt_table: SubcktTable,
     pub statements: Vec<ScopedStmt>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub global_nodes: Vec<NodeName>,
+    #[serde(skip)]
+    pub stats: ExpansionStats,
+}
+
+/// How often `expand_subckts` could reuse the parameters of an earlier instance.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ExpansionStats {
+    /// number of `X` instances expanded
+    pub instances: usize,
+    /// instances whose subcircuit and parameter overrides matched an earlier instance
+    pub cache_hits: usize,
 }
 
-/// Expand `X...` instances. For now assume: Xname n1 n2 subcktName [param=value ...]
+impl ExpansionStats {
+    /// Fraction of instances served from the cache, `None` when there were no instances.
+    pub fn hit_rate(&self) -> Option<f64> {
+        (self.instances > 0).then(|| self.cache_hits as f64 / self.instances as f64)
+    }
+}
+
+impl std::fmt::Display for ExpansionStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} subcircuit instances, {} cache hits",
+            self.instances, self.cache_hits
+        )?;
+        if let Some(rate) = self.hit_rate() {
+            write!(f, " ({:.1}%)", rate * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// The parameters of one subcircuit instantiated with one set of overrides.
+struct ExpansionTemplate {
+    params: Rc<Params>,
+    values: ParamValues,
+}
+
+/// Expand `X...` instances. For now assume: Xname n1 n2 subcktName [params:] [param=value ...]
 pub(crate) fn expand_subckts(
     mut unexpanded_deck: UnexpandedDeck,
     source_map: &SourceMap,
     placeholder_map: &PlaceholderMap,
 ) -> Result<ExpandedDeck, SpicyError> {
     let mut out = Vec::new();
+    let mut templates: HashMap<(String, String), ExpansionTemplate> = HashMap::new();
+    let mut stats = ExpansionStats::default();
 
     let root_scope_id = unexpanded_deck.global_params;
+    let root_scope = unexpanded_deck.scope_arena.get(root_scope_id);
+    root_scope.evaluate_params()?;
+    let global_values = root_scope.evaluated_values();
+
     for s in unexpanded_deck.statements.into_iter() {
         let mut cursor = s.as_cursor();
 
         let src = source_map.get_content(s.span.source_index);
-        if let Some(instance_name) = cursor.consume_if_device(src, DeviceType::Subcircuit) {
-            let instance_name = instance_name.to_string();
-            let (nodes, instance_subckt, param_overrides) = parse_x_device(&mut cursor, src)?;
+        let instance_token = cursor.peek_non_whitespace();
+        if cursor
+            .consume_if_device(src, DeviceType::Subcircuit)
+            .is_some()
+        {
+            // the instance is named with its `X`, like in ngspice's `X1.out`
+            let instance_name = token_text(src, instance_token.expect("device name")).to_string();
+            let (nodes, instance_subckt, param_overrides) =
+                parse_x_device(&mut cursor, src, placeholder_map)?;
+            // overrides are written in the instantiating scope, so they see its params
+            let param_overrides = evaluate_overrides(
+                param_overrides,
+                unexpanded_deck.scope_arena.get(root_scope_id),
+            )?;
 
             let Some(subckt_def) = unexpanded_deck.subckt_table.map.get(&instance_subckt) else {
                 return Err(SubcircuitError::NotFound {
@@ -249,32 +407,62 @@ pub(crate) fn expand_subckts(
                 .into());
             }
 
-            let mut instance_params = subckt_def.default_params.clone();
-            // will override any default params
-            instance_params.merge(param_overrides);
-            // will override any instance params
-            instance_params.merge(subckt_def.local_params.clone());
+            stats.instances += 1;
+            let key = (instance_subckt, param_overrides.fingerprint());
+            let template = match templates.entry(key) {
+                Entry::Occupied(entry) => {
+                    stats.cache_hits += 1;
+                    entry.into_mut()
+                }
+                Entry::Vacant(entry) => {
+                    let mut instance_params = subckt_def.default_params.clone();
+                    // will override any default params
+                    instance_params.merge(param_overrides);
+                    // will override any instance params
+                    instance_params.merge(subckt_def.local_params.clone());
+                    // the global params are visible unless the subcircuit shadows them
+                    let mut visible = global_values.clone();
+                    visible.retain(|name, _| !instance_params.contains(name));
+                    let template = ExpansionTemplate {
+                        params: Rc::new(instance_params),
+                        values: Rc::new(RefCell::new(visible)),
+                    };
+                    Scope::new(
+                        None,
+                        Rc::clone(&template.params),
+                        Rc::clone(&template.values),
+                        HashMap::new(),
+                    )
+                    .evaluate_params()?;
+                    entry.insert(template)
+                }
+            };
             // pin map
             let mut node_mapping = HashMap::new();
             for (f, a) in subckt_def.nodes.iter().cloned().zip(nodes.into_iter()) {
                 node_mapping.insert(f, a);
             }
 
-            let child_scope = Scope::new(Some(instance_name), instance_params, node_mapping);
+            let child_scope = Scope::new(
+                Some(instance_name),
+                Rc::clone(&template.params),
+                Rc::clone(&template.values),
+                node_mapping,
+            );
             let child_scope_id = unexpanded_deck
                 .scope_arena
                 .new_child(root_scope_id, child_scope);
 
             for stmt in subckt_def.body.iter() {
                 out.push(ScopedStmt {
-                    stmt: stmt.clone(),
+                    stmt: Rc::clone(stmt),
                     scope: child_scope_id,
                 });
             }
             continue;
         }
         out.push(ScopedStmt {
-            stmt: s,
+            stmt: Rc::new(s),
             scope: root_scope_id,
         });
     }
@@ -293,6 +481,8 @@ pub(crate) fn expand_subckts(
         subckt_table: unexpanded_deck.subckt_table,
         model_table: models,
         statements: out,
+        global_nodes: unexpanded_deck.global_nodes,
+        stats,
     })
 }
 
@@ -314,13 +504,17 @@ mod tests {
             work_dir: PathBuf::from("."),
             source_path: PathBuf::from("."),
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let mut statements = Statements::new(&input_content, input_options.source_map.main_index())
             .expect("statements");
-        let placeholders_map = substitute_expressions(&mut statements, &input_options)
+        let placeholders_map = substitute_expressions(&mut statements, &input_options, None)
             .expect("substitute expressions");
         let unexpanded_deck =
-            collect_subckts(statements, &input_options.source_map).expect("collect subckts");
+            collect_subckts(statements, &input_options.source_map, &placeholders_map)
+                .expect("collect subckts");
         let expanded_deck = expand_subckts(
             unexpanded_deck,
             &input_options.source_map,
@@ -351,14 +545,18 @@ mod tests {
             work_dir: PathBuf::from("."),
             source_path: PathBuf::from("."),
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
 
         let mut statements = Statements::new(&input_content, input_options.source_map.main_index())
             .expect("statements");
-        let placeholders_map = substitute_expressions(&mut statements, &input_options)
+        let placeholders_map = substitute_expressions(&mut statements, &input_options, None)
             .expect("substitute expressions");
-        let unexpanded_deck = collect_subckts(statements, &input_options.source_map)
-            .expect("collect subckts and models");
+        let unexpanded_deck =
+            collect_subckts(statements, &input_options.source_map, &placeholders_map)
+                .expect("collect subckts and models");
 
         let global_scope = unexpanded_deck
             .scope_arena
@@ -397,14 +595,17 @@ mod tests {
             work_dir: PathBuf::from("."),
             source_path: PathBuf::from("."),
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
 
         let mut statements = Statements::new(input_content, input_options.source_map.main_index())
             .expect("statements");
-        let _ = substitute_expressions(&mut statements, &input_options)
+        let placeholders_map = substitute_expressions(&mut statements, &input_options, None)
             .expect("substitute expressions");
 
-        let err = collect_subckts(statements, &input_options.source_map)
+        let err = collect_subckts(statements, &input_options.source_map, &placeholders_map)
             .expect_err("expected duplicate model error");
 
         match err {
@@ -430,13 +631,16 @@ mod tests {
             work_dir: PathBuf::from("."),
             source_path: PathBuf::from("."),
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
 
         let mut statements = Statements::new(input_content, input_options.source_map.main_index())
             .expect("statements");
-        let _ = substitute_expressions(&mut statements, &input_options)
+        let placeholders_map = substitute_expressions(&mut statements, &input_options, None)
             .expect("substitute expressions");
-        let err = collect_subckts(statements, &input_options.source_map)
+        let err = collect_subckts(statements, &input_options.source_map, &placeholders_map)
             .expect_err("expected invalid model type error");
 
         match err {
@@ -446,4 +650,100 @@ mod tests {
             other => panic!("unexpected error: {:?}", other),
         }
     }
+
+    #[test]
+    fn repeated_instances_share_expansion() {
+        let netlist = "\
+repeated cells
+.subckt cell a b r=1k
+R1 a b {r*2}
+.ends
+X1 n1 0 cell r=1k
+X2 n2 0 cell r=1k
+X3 n3 0 cell r=2k
+X4 n4 0 cell
+X5 n5 0 cell r=1k
+.end
+";
+        let mut options = ParseOptions::new_with_source("inline.spicy", netlist.to_string());
+        let deck = crate::parse(&mut options).expect("parse");
+
+        assert_eq!(
+            deck.expansion_stats,
+            ExpansionStats {
+                instances: 5,
+                cache_hits: 2,
+            }
+        );
+        assert_eq!(
+            deck.expansion_stats.to_string(),
+            "5 subcircuit instances, 2 cache hits (40.0%)"
+        );
+        let resistances: Vec<_> = deck
+            .devices
+            .resistors
+            .iter()
+            .map(|r| (r.name.as_str(), r.resistance.as_ref().unwrap().get_value()))
+            .collect();
+        assert_eq!(
+            resistances,
+            vec![
+                ("X1.R1", 2000.0),
+                ("X2.R1", 2000.0),
+                ("X3.R1", 4000.0),
+                ("X4.R1", 2000.0),
+                ("X5.R1", 2000.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn param_overrides_replace_top_level_params() {
+        let netlist = "\
+divider
+.param rtop=1k rbot=2k
+V1 in 0 1
+R1 in out {rtop}
+R2 out 0 {rbot+rtop}
+.end
+";
+        let mut options = ParseOptions::new_with_source("inline.spicy", netlist.to_string());
+        let deck =
+            crate::parse_with_params(&mut options, &[("rtop".to_string(), 3e3)]).expect("parse");
+        let resistances: Vec<_> = deck
+            .devices
+            .resistors
+            .iter()
+            .map(|r| r.resistance.as_ref().unwrap().get_value())
+            .collect();
+        assert_eq!(resistances, vec![3e3, 5e3]);
+
+        let mut options = ParseOptions::new_with_source("inline.spicy", netlist.to_string());
+        let err = crate::parse_with_params(&mut options, &[("rmid".to_string(), 1.0)])
+            .expect_err("rmid is not a param");
+        assert_eq!(err.to_string(), "no top-level .param named 'rmid'");
+    }
+
+    #[test]
+    fn global_nodes_are_shared_by_every_instance() {
+        let netlist = "\
+cells
+.global VDD
+.subckt cell a
+R1 a mid 1k
+R2 mid vdd 1k
+.ends
+Vdd vdd 0 1
+X1 in1 cell
+X2 in2 cell
+.op
+.end
+";
+        let mut options = ParseOptions::new_with_source("inline.spicy", netlist.to_string());
+        let deck = crate::parse(&mut options).expect("parse");
+        assert_eq!(
+            deck.node_mapping.node_names_mna_order(),
+            ["vdd", "in1", "X1.mid", "in2", "X2.mid"]
+        );
+    }
 }
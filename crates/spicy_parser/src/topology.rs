@@ -0,0 +1,415 @@
+//! Structural checks on a parsed deck that would otherwise surface as a singular MNA matrix.
+//!
+//! All checks use the DC view of the circuit: capacitors and (behavioral) current sources are
+//! open, inductors and (behavioral) voltage sources are shorts.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    Span, devices::BehavioralKind, error::TopologyError, instance_parser::Deck,
+    netlist_types::NodeIndex,
+};
+
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        Self {
+            parent: (0..n).collect(),
+        }
+    }
+
+    fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    /// Returns false if `a` and `b` were already connected.
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (ra, rb) = (self.find(a), self.find(b));
+        if ra == rb {
+            return false;
+        }
+        self.parent[ra] = rb;
+        true
+    }
+}
+
+/// A two-terminal branch of the DC graph.
+struct Edge<'d> {
+    name: &'d str,
+    span: Span,
+    a: NodeIndex,
+    b: NodeIndex,
+}
+
+/// Spanning forest of the short-circuit elements, used to recover the devices closing a loop.
+struct Forest<'d> {
+    uf: UnionFind,
+    adjacency: HashMap<usize, Vec<(usize, &'d str)>>,
+}
+
+impl<'d> Forest<'d> {
+    fn new(n: usize) -> Self {
+        Self {
+            uf: UnionFind::new(n),
+            adjacency: HashMap::new(),
+        }
+    }
+
+    /// Add `edge`; if it closes a loop, return the names of the devices on that loop.
+    fn add(&mut self, edge: &Edge<'d>) -> Option<Vec<String>> {
+        let (a, b) = (edge.a.0, edge.b.0);
+        if self.uf.union(a, b) {
+            self.adjacency.entry(a).or_default().push((b, edge.name));
+            self.adjacency.entry(b).or_default().push((a, edge.name));
+            return None;
+        }
+        let mut devices = self.path(a, b);
+        devices.push(edge.name.to_string());
+        Some(devices)
+    }
+
+    /// Device names on the unique forest path from `from` to `to`.
+    fn path(&self, from: usize, to: usize) -> Vec<String> {
+        let mut previous: HashMap<usize, (usize, &str)> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(node) = queue.pop_front() {
+            if node == to {
+                break;
+            }
+            for &(next, name) in self.adjacency.get(&node).into_iter().flatten() {
+                if next != from && !previous.contains_key(&next) {
+                    previous.insert(next, (node, name));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut devices = Vec::new();
+        let mut node = to;
+        while let Some(&(prev, name)) = previous.get(&node) {
+            devices.push(name.to_string());
+            node = prev;
+        }
+        devices.reverse();
+        devices
+    }
+}
+
+/// Run every topology check on `deck`, returning all problems found.
+pub fn check_topology(deck: &Deck) -> Vec<TopologyError> {
+    let devices = &deck.devices;
+    let node_count = deck.node_mapping.nodes_len() + 1;
+    let node_names = deck.node_mapping.node_names_mna_order();
+    let node_name = |n: usize| node_names[n - 1].clone();
+
+    let mut errors = Vec::new();
+
+    // Loops of voltage sources, then loops that also contain inductors.
+    let mut forest = Forest::new(node_count);
+    for v in &devices.voltage_sources {
+        let edge = Edge {
+            name: &v.name,
+            span: v.span,
+            a: v.positive,
+            b: v.negative,
+        };
+        if let Some(devices) = forest.add(&edge) {
+            errors.push(TopologyError::VoltageSourceLoop {
+                devices,
+                span: edge.span,
+            });
+        }
+    }
+    for b in devices
+        .behavioral_sources
+        .iter()
+        .filter(|b| b.kind == BehavioralKind::Voltage)
+    {
+        let edge = Edge {
+            name: &b.name,
+            span: b.span,
+            a: b.positive,
+            b: b.negative,
+        };
+        if let Some(devices) = forest.add(&edge) {
+            errors.push(TopologyError::VoltageSourceLoop {
+                devices,
+                span: edge.span,
+            });
+        }
+    }
+    for l in &devices.inductors {
+        let edge = Edge {
+            name: &l.name,
+            span: l.span,
+            a: l.positive,
+            b: l.negative,
+        };
+        if let Some(devices) = forest.add(&edge) {
+            errors.push(TopologyError::InductorLoop {
+                devices,
+                span: edge.span,
+            });
+        }
+    }
+
+    // Nodes without a DC path to ground.
+    let mut dc = UnionFind::new(node_count);
+    let mut conducting: Vec<(NodeIndex, NodeIndex)> = Vec::new();
+    conducting.extend(devices.resistors.iter().map(|r| (r.positive, r.negative)));
+    conducting.extend(devices.inductors.iter().map(|l| (l.positive, l.negative)));
+    conducting.extend(devices.diodes.iter().map(|d| (d.positive, d.negative)));
+    conducting.extend(
+        devices
+            .lookup_tables
+            .iter()
+            .map(|a| (a.positive, a.negative)),
+    );
+    conducting.extend(
+        devices
+            .voltage_sources
+            .iter()
+            .map(|v| (v.positive, v.negative)),
+    );
+    conducting.extend(
+        devices
+            .behavioral_sources
+            .iter()
+            .filter(|b| b.kind == BehavioralKind::Voltage)
+            .map(|b| (b.positive, b.negative)),
+    );
+    for q in &devices.bjts {
+        conducting.push((q.collector, q.base));
+        conducting.push((q.base, q.emitter));
+        conducting.push((q.collector, q.collector_prime));
+        conducting.push((q.base, q.base_prime));
+        conducting.push((q.emitter, q.emitter_prime));
+    }
+    // the gate is insulated and the bulk junctions are not modeled
+    conducting.extend(devices.mosfets.iter().map(|m| (m.drain, m.source)));
+    // the gate junctions conduct like diodes
+    for j in &devices.jfets {
+        conducting.push((j.drain, j.source));
+        conducting.push((j.gate, j.source));
+        conducting.push((j.drain, j.drain_prime));
+        conducting.push((j.source, j.source_prime));
+    }
+    // a switch is a resistor in either state, its control nodes draw no current
+    conducting.extend(devices.switches.iter().map(|s| (s.positive, s.negative)));
+    // at DC a transmission line ties the voltages of its two ports, each port across itself
+    for t in &devices.transmission_lines {
+        conducting.push((t.positive1, t.negative1));
+        conducting.push((t.positive2, t.negative2));
+    }
+    for (a, b) in conducting {
+        dc.union(a.0, b.0);
+    }
+
+    let roots: Vec<usize> = (0..node_count).map(|n| dc.find(n)).collect();
+    let mut floating: Vec<Vec<usize>> = Vec::new();
+    let mut component_index: HashMap<usize, usize> = HashMap::new();
+    for node in 1..node_count {
+        if roots[node] == roots[0] {
+            continue;
+        }
+        let index = *component_index.entry(roots[node]).or_insert_with(|| {
+            floating.push(Vec::new());
+            floating.len() - 1
+        });
+        floating[index].push(node);
+    }
+
+    for component in floating {
+        let root = roots[component[0]];
+        let inside = |n: NodeIndex| roots[n.0] == root;
+        let nodes: Vec<String> = component.iter().map(|&n| node_name(n)).collect();
+
+        // a behavioral current source may well depend on its own voltage, but like a
+        // current source it gives no DC path by itself
+        let sources: Vec<(&String, Span)> = devices
+            .current_sources
+            .iter()
+            .filter(|i| inside(i.positive) != inside(i.negative))
+            .map(|i| (&i.name, i.span))
+            .chain(
+                devices
+                    .behavioral_sources
+                    .iter()
+                    .filter(|b| b.kind == BehavioralKind::Current)
+                    .filter(|b| inside(b.positive) != inside(b.negative))
+                    .map(|b| (&b.name, b.span)),
+            )
+            .collect();
+        let capacitors: Vec<_> = devices
+            .capacitors
+            .iter()
+            .filter(|c| inside(c.positive) || inside(c.negative))
+            .collect();
+
+        if let Some((_, span)) = sources.first() {
+            errors.push(TopologyError::CurrentSourceCutset {
+                devices: sources.iter().map(|(name, _)| (*name).clone()).collect(),
+                nodes,
+                span: *span,
+            });
+        } else if let Some(first) = capacitors.first() {
+            errors.push(TopologyError::CapacitorOnlyNodes {
+                devices: capacitors.iter().map(|c| c.name.clone()).collect(),
+                nodes,
+                span: first.span,
+            });
+        } else {
+            // an isolated sub-circuit: point at any device inside it
+            let span = devices
+                .resistors
+                .iter()
+                .filter(|r| inside(r.positive))
+                .map(|r| r.span)
+                .chain(
+                    devices
+                        .diodes
+                        .iter()
+                        .filter(|d| inside(d.positive))
+                        .map(|d| d.span),
+                )
+                .chain(
+                    devices
+                        .inductors
+                        .iter()
+                        .filter(|l| inside(l.positive))
+                        .map(|l| l.span),
+                )
+                .chain(
+                    devices
+                        .voltage_sources
+                        .iter()
+                        .filter(|v| inside(v.positive))
+                        .map(|v| v.span),
+                )
+                .chain(
+                    devices
+                        .bjts
+                        .iter()
+                        .filter(|q| inside(q.base))
+                        .map(|q| q.span),
+                )
+                .chain(
+                    devices
+                        .mosfets
+                        .iter()
+                        .filter(|m| inside(m.drain))
+                        .map(|m| m.span),
+                )
+                .chain(
+                    devices
+                        .jfets
+                        .iter()
+                        .filter(|j| inside(j.drain))
+                        .map(|j| j.span),
+                )
+                .chain(
+                    devices
+                        .current_sources
+                        .iter()
+                        .filter(|i| inside(i.positive))
+                        .map(|i| i.span),
+                )
+                .chain(
+                    devices
+                        .behavioral_sources
+                        .iter()
+                        .filter(|b| inside(b.positive))
+                        .map(|b| b.span),
+                )
+                .next();
+            errors.push(TopologyError::FloatingNodes { nodes, span });
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParseOptions, parse};
+
+    fn check(netlist: &str) -> Vec<TopologyError> {
+        let mut options = ParseOptions::new_with_source("topology.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        check_topology(&deck)
+    }
+
+    #[test]
+    fn clean_deck_has_no_errors() {
+        let errors = check("rc\nV1 in 0 1\nR1 in out 1k\nC1 out 0 1u\nL1 out 0 1m\n.op\n.end\n");
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+
+    #[test]
+    fn voltage_source_loop() {
+        let errors = check("loop\nV1 a 0 1\nV2 a b 1\nV3 b 0 1\nR1 a 0 1k\n.op\n.end\n");
+        let [TopologyError::VoltageSourceLoop { devices, .. }] = errors.as_slice() else {
+            panic!("{errors:?}");
+        };
+        assert_eq!(devices, &["V2", "V1", "V3"]);
+        assert_eq!(
+            errors[0].to_string(),
+            "voltage sources form a loop: V2, V1, V3"
+        );
+    }
+
+    #[test]
+    fn inductor_loop_with_source() {
+        let errors = check("loop\nV1 a 0 1\nL1 a 0 1m\nR1 a 0 1k\n.op\n.end\n");
+        let [TopologyError::InductorLoop { devices, .. }] = errors.as_slice() else {
+            panic!("{errors:?}");
+        };
+        assert_eq!(devices, &["V1", "L1"]);
+        assert!(errors[0].error_span().is_some());
+    }
+
+    #[test]
+    fn current_source_cutset() {
+        let errors = check("cutset\nI1 0 a 1m\nC1 a 0 1u\n.op\n.end\n");
+        let [TopologyError::CurrentSourceCutset { devices, nodes, .. }] = errors.as_slice() else {
+            panic!("{errors:?}");
+        };
+        assert_eq!(devices, &["I1"]);
+        assert_eq!(nodes, &["a"]);
+    }
+
+    #[test]
+    fn mosfet_gate_and_bulk_need_a_dc_path() {
+        let netlist = "nmos\n.model nmod NMOS vto=0.7 kp=110u\nV1 d 0 5\nM1 d g 0 b nmod\nC1 g 0 1p\n.op\n.end\n";
+        let errors = check(netlist);
+        let [
+            TopologyError::CapacitorOnlyNodes { nodes: gate, .. },
+            TopologyError::FloatingNodes { nodes: bulk, .. },
+        ] = errors.as_slice()
+        else {
+            panic!("{errors:?}");
+        };
+        assert_eq!(gate, &["g"]);
+        assert_eq!(bulk, &["b"]);
+    }
+
+    #[test]
+    fn capacitor_only_nodes() {
+        let errors = check(
+            "caps\nV1 in 0 1\nR1 in 0 1k\nC1 in mid 1u\nC2 mid out 1u\nR2 out mid 1k\n.op\n.end\n",
+        );
+        let [TopologyError::CapacitorOnlyNodes { devices, nodes, .. }] = errors.as_slice() else {
+            panic!("{errors:?}");
+        };
+        assert_eq!(devices, &["C1", "C2"]);
+        assert_eq!(nodes, &["mid", "out"]);
+    }
+}
@@ -0,0 +1,433 @@
+//! Traces of instrumented algorithms: the lines an algorithm runs and the values its variables
+//! take, for the visualizations to replay.
+//!
+//! An instrumented function takes a [`Recorder`] and reports every step to it. A
+//! [`VecRecorder`] keeps the trace in memory, to serialize as one JSON object of the initial
+//! values and the steps; a [`JsonLinesRecorder`] writes every value and step as a line of JSON
+//! as it happens. A [`NullRecorder`] records nothing, for the instrumented code to run at full
+//! speed outside the visualizations.
+//!
+//! A trace read back, from either format, plays through a [`Replay`].
+//!
+//! [`recorded`] writes the instrumentation: `#[recorded]` on a function taking a `recorder`
+//! reports its variables, arms, loops and control flow, and
+//! `#[recorded(enabled_if = cfg!(feature = "trace"))]` leaves the function as written in the
+//! builds without the feature.
+
+mod replay;
+
+pub use replay::Replay;
+pub use spicy_record_macros::recorded;
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+/// A step of a trace, at a line of the instrumented source.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Step {
+    /// The line ran.
+    Step { line: u32 },
+    /// A variable took a value.
+    Number {
+        line: u32,
+        name: String,
+        value: Value,
+    },
+    /// A floating point variable took a value, kept as is (a value that is not finite
+    /// serializes as null).
+    Float {
+        line: u32,
+        name: String,
+        #[serde(deserialize_with = "float_or_nan")]
+        value: f64,
+    },
+    /// A string variable took a value.
+    Str {
+        line: u32,
+        name: String,
+        value: String,
+    },
+    /// An element of an array took a value.
+    Array {
+        line: u32,
+        name: String,
+        index: usize,
+        value: Value,
+    },
+    /// The arm `index` of a match, counted from 0, was taken.
+    Arm { line: u32, index: usize },
+    /// Control left the loop or the function.
+    Flow { line: u32, flow: ControlFlow },
+}
+
+/// Where a [`Step::Flow`] takes control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ControlFlow {
+    Return,
+    Break,
+    Continue,
+}
+
+/// A float step's value, NaN for the null a value that is not finite was written as.
+fn float_or_nan<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    Ok(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::NAN))
+}
+
+/// What an instrumented algorithm reports its steps to.
+///
+/// A value that does not serialize is left out of the trace.
+pub trait Recorder {
+    /// The value of a variable before the first step, e.g. an array the algorithm fills.
+    fn set_initial<V: Serialize>(&mut self, name: &str, value: &V);
+
+    /// Add `step` to the trace; the other steps go through it.
+    fn push(&mut self, step: Step);
+
+    fn push_step(&mut self, line: u32) {
+        self.push(Step::Step { line });
+    }
+
+    fn push_number_step<V: Serialize>(&mut self, line: u32, name: &str, value: &V) {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.push(Step::Number {
+                line,
+                name: name.to_string(),
+                value,
+            });
+        }
+    }
+
+    fn push_float_step(&mut self, line: u32, name: &str, value: f64) {
+        self.push(Step::Float {
+            line,
+            name: name.to_string(),
+            value,
+        });
+    }
+
+    fn push_str_step(&mut self, line: u32, name: &str, value: &str) {
+        self.push(Step::Str {
+            line,
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+    }
+
+    fn push_array_step<V: Serialize>(&mut self, line: u32, name: &str, index: usize, value: &V) {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.push(Step::Array {
+                line,
+                name: name.to_string(),
+                index,
+                value,
+            });
+        }
+    }
+
+    fn push_arm_step(&mut self, line: u32, index: usize) {
+        self.push(Step::Arm { line, index });
+    }
+
+    /// Report the `return`, `break` or `continue` at `line` before it runs.
+    fn push_flow_step(&mut self, line: u32, flow: ControlFlow) {
+        self.push(Step::Flow { line, flow });
+    }
+}
+
+/// No trace: every step is dropped before its value is built, so an instrumented function
+/// called with it compiles to its uninstrumented body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NullRecorder;
+
+impl Recorder for NullRecorder {
+    #[inline(always)]
+    fn set_initial<V: Serialize>(&mut self, _name: &str, _value: &V) {}
+
+    #[inline(always)]
+    fn push(&mut self, _step: Step) {}
+
+    #[inline(always)]
+    fn push_step(&mut self, _line: u32) {}
+
+    #[inline(always)]
+    fn push_number_step<V: Serialize>(&mut self, _line: u32, _name: &str, _value: &V) {}
+
+    #[inline(always)]
+    fn push_float_step(&mut self, _line: u32, _name: &str, _value: f64) {}
+
+    #[inline(always)]
+    fn push_str_step(&mut self, _line: u32, _name: &str, _value: &str) {}
+
+    #[inline(always)]
+    fn push_array_step<V: Serialize>(
+        &mut self,
+        _line: u32,
+        _name: &str,
+        _index: usize,
+        _value: &V,
+    ) {
+    }
+
+    #[inline(always)]
+    fn push_arm_step(&mut self, _line: u32, _index: usize) {}
+
+    #[inline(always)]
+    fn push_flow_step(&mut self, _line: u32, _flow: ControlFlow) {}
+}
+
+/// A trace in memory. Serializes to `{"initial": {name: value}, "steps": [step]}`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VecRecorder {
+    pub initial: BTreeMap<String, Value>,
+    pub steps: Vec<Step>,
+}
+
+impl VecRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read back the trace a [`JsonLinesRecorder`] wrote.
+    pub fn from_json_lines<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut trace = Self::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let value: Value = serde_json::from_str(&line)?;
+            if value["type"] == "initial" {
+                let initial: Initial<String> = serde_json::from_value(value)?;
+                trace.initial.insert(initial.name, initial.value);
+            } else {
+                trace.steps.push(serde_json::from_value(value)?);
+            }
+        }
+        Ok(trace)
+    }
+}
+
+impl Recorder for VecRecorder {
+    fn set_initial<V: Serialize>(&mut self, name: &str, value: &V) {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.initial.insert(name.to_string(), value);
+        }
+    }
+
+    fn push(&mut self, step: Step) {
+        self.steps.push(step);
+    }
+}
+
+/// A trace written to `writer` as it is recorded, a JSON object per line: the initial values
+/// as `{"type": "initial", "name": .., "value": ..}` and the steps as [`Step`]s.
+///
+/// The first write error stops the trace; [`JsonLinesRecorder::finish`] returns it.
+#[derive(Debug)]
+pub struct JsonLinesRecorder<W: Write> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename = "initial")]
+struct Initial<S> {
+    name: S,
+    value: Value,
+}
+
+impl<W: Write> JsonLinesRecorder<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            error: None,
+        }
+    }
+
+    fn write_line<T: Serialize>(&mut self, line: &T) {
+        if self.error.is_some() {
+            return;
+        }
+        let written = serde_json::to_writer(&mut self.writer, line)
+            .map_err(io::Error::from)
+            .and_then(|()| self.writer.write_all(b"\n"));
+        if let Err(e) = written {
+            self.error = Some(e);
+        }
+    }
+
+    /// Flush the trace and give back the writer, or the first error writing it.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Recorder for JsonLinesRecorder<W> {
+    fn set_initial<V: Serialize>(&mut self, name: &str, value: &V) {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.write_line(&Initial { name, value });
+        }
+    }
+
+    fn push(&mut self, step: Step) {
+        self.write_line(&step);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Sum of `values`, instrumented.
+    fn sum<R: Recorder>(values: &[i64], recorder: &mut R) -> i64 {
+        recorder.set_initial("values", &values);
+        let mut total = 0;
+        recorder.push_number_step(line!() - 1, "total", &total);
+        for (i, value) in values.iter().enumerate() {
+            recorder.push_step(line!() - 1);
+            total += value;
+            recorder.push_array_step(line!() - 1, "values", i, value);
+            recorder.push_number_step(line!() - 2, "total", &total);
+        }
+        total
+    }
+
+    #[test]
+    fn vec_recorder_keeps_the_trace() {
+        let mut recorder = VecRecorder::new();
+        assert_eq!(sum(&[1, 2], &mut recorder), 3);
+
+        let trace = serde_json::to_value(&recorder).unwrap();
+        assert_eq!(trace["initial"], json!({ "values": [1, 2] }));
+        let steps = trace["steps"].as_array().unwrap();
+        assert_eq!(steps.len(), 7);
+        assert_eq!(steps[1]["type"], "step");
+        assert_eq!(steps[2]["type"], "array");
+        assert_eq!(steps[2]["index"], 0);
+        assert_eq!(steps[6]["name"], "total");
+        assert_eq!(steps[6]["value"], 3);
+        // both point at the line of the addition
+        assert_eq!(steps[6]["line"], steps[5]["line"]);
+    }
+
+    #[test]
+    fn float_and_str_steps_keep_their_type() {
+        let mut recorder = VecRecorder::new();
+        let pivot: f64 = 0.1 + 0.2;
+        recorder.push_float_step(10, "pivot", pivot);
+        recorder.push_float_step(11, "pivot", f64::NAN);
+        recorder.push_str_step(12, "state", "factor");
+
+        let steps = serde_json::to_value(&recorder.steps).unwrap();
+        assert_eq!(
+            steps,
+            json!([
+                { "type": "float", "line": 10, "name": "pivot", "value": pivot },
+                { "type": "float", "line": 11, "name": "pivot", "value": null },
+                { "type": "str", "line": 12, "name": "state", "value": "factor" },
+            ])
+        );
+        assert_eq!(steps[0]["value"].as_f64(), Some(0.30000000000000004));
+    }
+
+    /// The sign of `value`, instrumented.
+    fn sign<R: Recorder>(value: i64, recorder: &mut R) -> i64 {
+        if value == 0 {
+            recorder.push_flow_step(line!() + 1, ControlFlow::Return);
+            return 0;
+        }
+        match value > 0 {
+            true => {
+                recorder.push_arm_step(line!() - 1, 0);
+                1
+            }
+            false => {
+                recorder.push_arm_step(line!() - 1, 1);
+                -1
+            }
+        }
+    }
+
+    #[test]
+    fn arms_and_control_flow_are_recorded() {
+        let mut recorder = VecRecorder::new();
+        assert_eq!(sign(0, &mut recorder), 0);
+        assert_eq!(sign(-3, &mut recorder), -1);
+
+        let steps = serde_json::to_value(&recorder.steps).unwrap();
+        assert_eq!(steps[0]["type"], "flow");
+        assert_eq!(steps[0]["flow"], "return");
+        assert_eq!(steps[1]["type"], "arm");
+        assert_eq!(steps[1]["index"], 1);
+        assert_eq!(
+            steps[0]["line"].as_u64().unwrap() + 7,
+            steps[1]["line"].as_u64().unwrap()
+        );
+    }
+
+    #[test]
+    fn null_recorder_runs_the_algorithm_alone() {
+        /// Fails the test if it is ever serialized.
+        struct Unserializable;
+        impl Serialize for Unserializable {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                panic!("a null recorder serialized a value");
+            }
+        }
+
+        let mut recorder = NullRecorder;
+        assert_eq!(sum(&[1, 2, 3], &mut recorder), 6);
+        recorder.set_initial("x", &Unserializable);
+        recorder.push_number_step(1, "x", &Unserializable);
+        recorder.push_array_step(1, "x", 0, &Unserializable);
+    }
+
+    #[test]
+    fn json_lines_recorder_writes_a_line_per_step() {
+        let mut recorder = JsonLinesRecorder::new(Vec::new());
+        sum(&[5], &mut recorder);
+        let output = String::from_utf8(recorder.finish().unwrap()).unwrap();
+        let lines: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[0],
+            json!({ "type": "initial", "name": "values", "value": [5] })
+        );
+        assert_eq!(lines[4]["type"], "number");
+        assert_eq!(lines[4]["value"], 5);
+    }
+
+    #[test]
+    fn json_lines_recorder_returns_the_write_error() {
+        #[derive(Debug)]
+        struct Full;
+        impl Write for Full {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::StorageFull, "full"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut recorder = JsonLinesRecorder::new(Full);
+        recorder.push_step(1);
+        recorder.push_step(2);
+        let error = recorder.finish().expect_err("write error");
+        assert_eq!(error.kind(), io::ErrorKind::StorageFull);
+    }
+}
@@ -0,0 +1,175 @@
+//! The replay API: the values of the variables after every step of a trace, to step and seek
+//! through. The btf_viz `replay_trace` viewer drives its play, pause and step controls with it.
+
+use std::collections::BTreeMap;
+
+use serde_json::{Number, Value};
+
+use crate::{Step, VecRecorder};
+
+/// A trace and a position in it: the steps before the position have been applied to the
+/// initial values.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    trace: VecRecorder,
+    position: usize,
+    values: BTreeMap<String, Value>,
+}
+
+impl Replay {
+    pub fn new(trace: VecRecorder) -> Self {
+        let values = trace.initial.clone();
+        Self {
+            trace,
+            position: 0,
+            values,
+        }
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.trace.steps
+    }
+
+    /// How many steps have been applied.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position == self.trace.steps.len()
+    }
+
+    /// The last step applied; none at the start.
+    pub fn current(&self) -> Option<&Step> {
+        self.position
+            .checked_sub(1)
+            .map(|index| &self.trace.steps[index])
+    }
+
+    /// The value of a variable at the position, as of its last step.
+    pub fn value(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    pub fn values(&self) -> &BTreeMap<String, Value> {
+        &self.values
+    }
+
+    /// Apply the next step and return it; none once the trace is finished.
+    pub fn step(&mut self) -> Option<&Step> {
+        let step = self.trace.steps.get(self.position)?;
+        apply(&mut self.values, step);
+        self.position += 1;
+        Some(step)
+    }
+
+    /// Move to `position`, clamped to the end of the trace. Moving back replays from the start.
+    pub fn seek(&mut self, position: usize) {
+        let position = position.min(self.trace.steps.len());
+        if position < self.position {
+            self.values = self.trace.initial.clone();
+            self.position = 0;
+        }
+        while self.position < position {
+            self.step();
+        }
+    }
+}
+
+fn apply(values: &mut BTreeMap<String, Value>, step: &Step) {
+    match step {
+        Step::Number { name, value, .. } => {
+            values.insert(name.clone(), value.clone());
+        }
+        Step::Float { name, value, .. } => {
+            let value = Number::from_f64(*value).map_or(Value::Null, Value::Number);
+            values.insert(name.clone(), value);
+        }
+        Step::Str { name, value, .. } => {
+            values.insert(name.clone(), Value::String(value.clone()));
+        }
+        Step::Array {
+            name, index, value, ..
+        } => {
+            // an element of an array the trace never gave is left out
+            if let Some(element) = values
+                .get_mut(name)
+                .and_then(Value::as_array_mut)
+                .and_then(|array| array.get_mut(*index))
+            {
+                *element = value.clone();
+            }
+        }
+        Step::Step { .. } | Step::Arm { .. } | Step::Flow { .. } => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JsonLinesRecorder, Recorder};
+    use serde_json::json;
+
+    fn trace() -> VecRecorder {
+        let mut recorder = VecRecorder::new();
+        recorder.set_initial("visited", &[0, 0]);
+        recorder.push_step(1);
+        recorder.push_number_step(2, "head", &0);
+        recorder.push_array_step(3, "visited", 1, &7);
+        recorder.push_float_step(4, "pivot", 0.5);
+        recorder.push_number_step(5, "head", &1);
+        recorder
+    }
+
+    #[test]
+    fn steps_and_seeks_through_the_values() {
+        let mut replay = Replay::new(trace());
+        assert_eq!(replay.current(), None);
+        assert_eq!(replay.value("visited"), Some(&json!([0, 0])));
+
+        assert_eq!(replay.step(), Some(&Step::Step { line: 1 }));
+        replay.seek(4);
+        assert_eq!(replay.position(), 4);
+        assert_eq!(replay.value("visited"), Some(&json!([0, 7])));
+        assert_eq!(replay.value("head"), Some(&json!(0)));
+        assert_eq!(replay.value("pivot"), Some(&json!(0.5)));
+
+        replay.seek(100);
+        assert!(replay.is_finished());
+        assert_eq!(replay.value("head"), Some(&json!(1)));
+        assert_eq!(replay.step(), None);
+
+        replay.seek(2);
+        assert_eq!(replay.value("visited"), Some(&json!([0, 0])));
+        assert_eq!(replay.value("pivot"), None);
+    }
+
+    #[test]
+    fn json_lines_read_back_as_the_trace() {
+        let mut recorder = JsonLinesRecorder::new(Vec::new());
+        let expected = trace();
+        for (name, value) in &expected.initial {
+            recorder.set_initial(name, value);
+        }
+        for step in &expected.steps {
+            recorder.push(step.clone());
+        }
+        recorder.push_float_step(6, "pivot", f64::INFINITY);
+        let output = recorder.finish().unwrap();
+
+        let read = VecRecorder::from_json_lines(output.as_slice()).unwrap();
+        assert_eq!(read.initial, expected.initial);
+        assert_eq!(read.steps[..expected.steps.len()], expected.steps);
+        let Some(Step::Float { value, .. }) = read.steps.last() else {
+            panic!("expected a float step");
+        };
+        assert!(value.is_nan());
+    }
+
+    #[test]
+    fn bad_line_is_invalid_data() {
+        let error =
+            VecRecorder::from_json_lines(&b"{\"type\": \"jump\"}\n"[..]).expect_err("unknown step");
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
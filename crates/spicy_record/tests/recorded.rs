@@ -0,0 +1,290 @@
+//! The instrumentation `#[recorded]` writes, run against a `VecRecorder`.
+
+use serde_json::json;
+use spicy_record::{ControlFlow, Recorder, Replay, Step, VecRecorder, recorded};
+
+#[recorded]
+fn sum<R: Recorder>(values: &[i64], recorder: &mut R) -> i64 {
+    let mut total = 0;
+    for value in values {
+        total += value;
+    }
+    total
+}
+
+#[test]
+fn variables_and_loops_are_recorded() {
+    let mut recorder = VecRecorder::new();
+    assert_eq!(sum(&[1, 2], &mut recorder), 3);
+
+    assert_eq!(recorder.initial["values"], json!([1, 2]));
+    let steps = serde_json::to_value(&recorder.steps).unwrap();
+    let types: Vec<_> = steps
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| &s["type"])
+        .collect();
+    assert_eq!(
+        types,
+        [
+            "number", "step", "array", "number", "step", "array", "number"
+        ]
+    );
+    assert_eq!(steps[5]["name"], "values");
+    assert_eq!(steps[5]["index"], 1);
+    assert_eq!(steps[5]["value"], 2);
+    assert_eq!(steps[3]["name"], "total");
+    assert_eq!(steps[6]["value"], 3);
+
+    let first = steps[0]["line"].as_u64().unwrap();
+    assert_eq!(steps[1]["line"].as_u64().unwrap(), first + 1);
+    assert_eq!(steps[3]["line"].as_u64().unwrap(), first + 2);
+}
+
+#[recorded]
+fn clamp_all<R: Recorder>(values: &mut [i64], limit: i64, recorder: &mut R) -> usize {
+    let mut clamped = 0;
+    for (i, value) in values.iter_mut().enumerate() {
+        if *value > limit {
+            *value = limit;
+            clamped += 1;
+        }
+    }
+    values[0] -= 1;
+    clamped
+}
+
+#[test]
+fn array_elements_are_recorded_at_their_index() {
+    let mut values = [5, 1];
+    let mut recorder = VecRecorder::new();
+    assert_eq!(clamp_all(&mut values, 3, &mut recorder), 1);
+    assert_eq!(values, [2, 1]);
+
+    assert_eq!(
+        array_steps(&recorder),
+        [
+            // the loop visits values[0], clamps it, then visits values[1]
+            ("values", 0, json!(5)),
+            ("values", 0, json!(3)),
+            ("values", 1, json!(1)),
+            ("values", 0, json!(2)),
+        ]
+    );
+    assert_eq!(recorder.initial["limit"], json!(3));
+}
+
+/// The `(name, index, value)` of the array steps.
+fn array_steps(recorder: &VecRecorder) -> Vec<(&str, usize, serde_json::Value)> {
+    recorder
+        .steps
+        .iter()
+        .filter_map(|step| match step {
+            Step::Array {
+                name, index, value, ..
+            } => Some((name.as_str(), *index, value.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+#[recorded(skip(scale))]
+fn scale_rows<R: Recorder>(rows: &mut [f64], width: usize, scale: &[f64], recorder: &mut R) {
+    for (row, chunk) in rows.chunks_mut(width).enumerate() {
+        for &factor in scale {
+            chunk[row] *= factor;
+        }
+    }
+}
+
+#[test]
+fn chunk_elements_are_recorded_at_their_array_index() {
+    let mut rows = [1.0, 2.0, 3.0, 4.0];
+    let mut recorder = VecRecorder::new();
+    scale_rows(&mut rows, 2, &[10.0], &mut recorder);
+    assert_eq!(rows, [10.0, 2.0, 3.0, 40.0]);
+
+    assert_eq!(
+        array_steps(&recorder),
+        [
+            // the chunks as they are taken, the diagonal as it is scaled
+            ("rows", 0, json!(1.0)),
+            ("rows", 1, json!(2.0)),
+            ("rows", 0, json!(10.0)),
+            ("rows", 2, json!(3.0)),
+            ("rows", 3, json!(4.0)),
+            ("rows", 3, json!(40.0)),
+        ]
+    );
+    assert!(!recorder.initial.contains_key("scale"));
+    assert!(recorder.steps.iter().any(
+        |step| matches!(step, Step::Number { name, value, .. } if name == "row" && value == 1)
+    ));
+}
+
+#[recorded]
+fn count_above<R: Recorder>(values: &[i64], limits: &[i64], recorder: &mut R) -> usize {
+    let mut count = 0;
+    for &limit in limits.iter() {
+        for (i, value) in values.iter().enumerate() {
+            if *value > limit && i > 0 {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+#[test]
+fn nested_loops_keep_their_own_index() {
+    let mut recorder = VecRecorder::new();
+    assert_eq!(count_above(&[5, 1, 7], &[0, 6], &mut recorder), 3);
+
+    let steps = array_steps(&recorder);
+    let limits: Vec<_> = steps.iter().filter(|s| s.0 == "limits").collect();
+    assert_eq!(limits, [&("limits", 0, json!(0)), &("limits", 1, json!(6))]);
+    let values: Vec<_> = steps
+        .iter()
+        .filter(|s| s.0 == "values")
+        .map(|s| s.1)
+        .collect();
+    assert_eq!(values, [0, 1, 2, 0, 1, 2]);
+}
+
+#[recorded]
+fn pivot_label<R: Recorder>(pivot: f64, recorder: &mut R) -> String {
+    let mut ratio = 1.0;
+    ratio /= pivot;
+    let small: f32 = 0.5;
+    let state = "factor";
+    let mut label: String = String::new();
+    label += state;
+    for count in [1, 2] {
+        label = format!("{label} {count} {small}");
+    }
+    label
+}
+
+#[test]
+fn floats_and_strings_are_recorded_as_themselves() {
+    let mut recorder = VecRecorder::new();
+    assert_eq!(pivot_label(0.0, &mut recorder), "factor 1 0.5 2 0.5");
+
+    let steps: Vec<_> = recorder
+        .steps
+        .iter()
+        .filter_map(|step| match step {
+            Step::Float { name, value, .. } => Some((name.as_str(), format!("float {value}"))),
+            Step::Str { name, value, .. } => Some((name.as_str(), format!("str {value}"))),
+            Step::Number { name, value, .. } => Some((name.as_str(), format!("number {value}"))),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        steps,
+        [
+            ("ratio", "float 1".to_string()),
+            // kept, where serde would have dropped it
+            ("ratio", "float inf".to_string()),
+            ("small", "float 0.5".to_string()),
+            ("state", "str factor".to_string()),
+            ("label", "str ".to_string()),
+            ("label", "str factor".to_string()),
+            ("count", "number 1".to_string()),
+            ("label", "str factor 1 0.5".to_string()),
+            ("count", "number 2".to_string()),
+            ("label", "str factor 1 0.5 2 0.5".to_string()),
+        ]
+    );
+}
+
+#[recorded(nested)]
+fn mark<R: Recorder>(marks: &mut [u8], at: usize, recorder: &mut R) {
+    marks[at] = 1;
+}
+
+#[recorded]
+fn marks<R: Recorder>(marks: &mut Vec<u8>, recorder: &mut R) {
+    marks.fill(0);
+    mark(marks, 1, recorder);
+}
+
+#[test]
+fn nested_functions_and_rewritten_arrays_replay_from_the_caller() {
+    let mut values = vec![7, 7, 7];
+    let mut recorder = VecRecorder::new();
+    marks(&mut values, &mut recorder);
+
+    // the callee does not report its arguments over the caller's
+    assert_eq!(recorder.initial.len(), 1);
+    assert_eq!(recorder.initial["marks"], json!([7, 7, 7]));
+    let mut replay = Replay::new(recorder);
+    replay.seek(usize::MAX);
+    assert_eq!(replay.value("marks"), Some(&json!([0, 1, 0])));
+}
+
+const SIGN: u32 = line!();
+#[recorded]
+fn sign<R: Recorder>(value: i64, recorder: &mut R) -> i64 {
+    if value == 0 {
+        return 0;
+    }
+    match value > 0 {
+        true => 1,
+        false => -1,
+    }
+}
+
+#[test]
+fn arms_and_control_flow_are_recorded() {
+    let mut recorder = VecRecorder::new();
+    assert_eq!(sign(0, &mut recorder), 0);
+    assert_eq!(sign(-3, &mut recorder), -1);
+
+    let if_line = SIGN + 3;
+    assert_eq!(
+        recorder.steps,
+        [
+            Step::Step { line: if_line },
+            Step::Flow {
+                line: if_line + 1,
+                flow: ControlFlow::Return,
+            },
+            Step::Arm {
+                line: if_line + 5,
+                index: 1,
+            },
+        ]
+    );
+}
+
+#[derive(Debug, PartialEq)]
+struct Unserializable(i64);
+
+#[recorded(enabled_if = cfg!(any()))]
+fn disabled<R: Recorder>(value: i64, recorder: &mut R) -> i64 {
+    // would not compile instrumented: the value does not serialize
+    let doubled = Unserializable(value * 2);
+    doubled.0
+}
+
+#[recorded(enabled_if = cfg!(all()))]
+fn enabled<R: Recorder>(value: i64, recorder: &mut R) -> i64 {
+    let doubled = value * 2;
+    doubled
+}
+
+#[test]
+fn enabled_if_leaves_the_function_as_written_when_off() {
+    let mut recorder = VecRecorder::new();
+    assert_eq!(disabled(2, &mut recorder), 4);
+    assert_eq!(recorder, VecRecorder::new());
+
+    assert_eq!(enabled(2, &mut recorder), 4);
+    assert_eq!(recorder.initial["value"], json!(2));
+    assert!(matches!(
+        &recorder.steps[..],
+        [Step::Number { name, .. }] if name == "doubled"
+    ));
+}
@@ -0,0 +1,846 @@
+//! The `#[recorded]` attribute, which instruments a function to report its steps to the
+//! `spicy_record::Recorder` it takes as its `recorder` argument.
+//!
+//! The instrumented function reports:
+//! - its arguments, as the initial values,
+//! - every `let` and every assignment to a variable, with the value it took: as a float when
+//!   the variable is an `f64` or `f32`, as a string when it is a `&str` or a `String`, going
+//!   by the type it is written with, `let x: f64`, or the value it starts from, `let x = 0.5`,
+//! - every assignment to an element of an array, `visited[col] = ..`, with its index,
+//! - a variable a method rewrites whole, `nv.fill(1)`, with its new value,
+//! - the arm a `match` took, the branch of an `if` and every iteration of a loop,
+//! - the elements a `for` loop takes from an array variable, whole or in `chunks(n)`, with
+//!   their index in the array, and the assignments through them,
+//! - a `return`, `break` or `continue`, before it runs,
+//! - any other statement as a plain step of its line.
+//!
+//! The values reported must serialize; `skip(..)` leaves out the variables that do not. A
+//! function a recorded one calls with its own variables is `nested`: its arguments are already
+//! in the trace, and are not reported again as initial values. With `enabled_if = cfg!(..)`
+//! the function is only instrumented when the cfg holds, and is left as written otherwise.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{ToTokens, quote};
+use syn::spanned::Spanned;
+use syn::{
+    Arm, BinOp, Block, Expr, ExprForLoop, ExprIf, ExprMatch, FnArg, Ident, ItemFn, Lit, Pat, Stmt,
+    Type, UnOp, parse_macro_input, parse_quote,
+};
+
+/// Instrument the function to report its steps to its `recorder` argument.
+///
+/// ```ignore
+/// #[recorded(skip(m), enabled_if = cfg!(feature = "trace"))]
+/// fn btf_max_transversal<R: Recorder>(m: &CscMatrix, recorder: &mut R) -> usize { .. }
+///
+/// #[recorded(nested, enabled_if = cfg!(feature = "trace"))]
+/// fn try_augmenting_path<R: Recorder>(m: &CscMatrix, cheap: &mut [usize], recorder: &mut R) { .. }
+/// ```
+#[proc_macro_attribute]
+pub fn recorded(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = Args::default();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("enabled_if") {
+            match meta.value()?.parse::<Expr>()? {
+                Expr::Macro(cfg) if cfg.mac.path.is_ident("cfg") => {
+                    args.enabled_if = Some(cfg.mac.tokens);
+                    Ok(())
+                }
+                other => Err(syn::Error::new(
+                    other.span(),
+                    "`enabled_if` takes a `cfg!(..)`",
+                )),
+            }
+        } else if meta.path.is_ident("nested") {
+            args.nested = true;
+            Ok(())
+        } else if meta.path.is_ident("skip") {
+            meta.parse_nested_meta(|name| {
+                args.skip.push(name.path.require_ident()?.clone());
+                Ok(())
+            })
+        } else {
+            Err(meta.error("expected `skip(..)`, `nested` or `enabled_if = cfg!(..)`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let function = parse_macro_input!(item as ItemFn);
+
+    match instrument(&function, &args) {
+        Ok(instrumented) => match args.enabled_if {
+            Some(predicate) => quote! {
+                #[cfg(#predicate)]
+                #instrumented
+                #[cfg(not(#predicate))]
+                #[allow(unused_variables)]
+                #function
+            },
+            None => instrumented.into_token_stream(),
+        },
+        Err(e) => e.into_compile_error(),
+    }
+    .into()
+}
+
+#[derive(Default)]
+struct Args {
+    /// The cfg predicate of `enabled_if`.
+    enabled_if: Option<TokenStream2>,
+    skip: Vec<Ident>,
+    nested: bool,
+}
+
+fn instrument(function: &ItemFn, args: &Args) -> syn::Result<ItemFn> {
+    let mut arguments = Vec::new();
+    let mut kinds = Vec::new();
+    let mut recorder = None;
+    for input in &function.sig.inputs {
+        if let FnArg::Typed(typed) = input {
+            if let Some(name) = single_binding(&typed.pat) {
+                kinds.push((name.clone(), type_kind(&typed.ty)));
+            }
+            for name in bindings(&typed.pat) {
+                if name == "recorder" {
+                    recorder = Some(name);
+                } else {
+                    arguments.push(name);
+                }
+            }
+        }
+    }
+    let Some(recorder) = recorder else {
+        return Err(syn::Error::new(
+            function.sig.span(),
+            "a `#[recorded]` function takes its `recorder` as an argument",
+        ));
+    };
+
+    let mut function = function.clone();
+    let mut instrumenter = Instrumenter {
+        recorder,
+        skip: &args.skip,
+        elements: Vec::new(),
+        kinds,
+        depth: 0,
+    };
+    instrumenter.block(&mut function.block);
+    if !args.nested {
+        let initial = arguments
+            .iter()
+            .filter(|name| instrumenter.recorded(name))
+            .map(|name| instrumenter.set_initial(name));
+        function
+            .block
+            .stmts
+            .splice(0..0, initial.collect::<Vec<_>>());
+    }
+    Ok(function)
+}
+
+/// The line a span starts on.
+fn line_of(span: Span) -> u32 {
+    span.unwrap().line() as u32
+}
+
+/// The variables a pattern binds: `(i, &mut x)` binds `i` and `x`.
+fn bindings(pat: &Pat) -> Vec<Ident> {
+    match pat {
+        Pat::Ident(ident) => {
+            let mut names = vec![ident.ident.clone()];
+            if let Some((_, sub)) = &ident.subpat {
+                names.extend(bindings(sub));
+            }
+            names
+        }
+        Pat::Reference(reference) => bindings(&reference.pat),
+        Pat::Type(typed) => bindings(&typed.pat),
+        Pat::Paren(paren) => bindings(&paren.pat),
+        Pat::Tuple(tuple) => tuple.elems.iter().flat_map(bindings).collect(),
+        Pat::TupleStruct(tuple) => tuple.elems.iter().flat_map(bindings).collect(),
+        Pat::Slice(slice) => slice.elems.iter().flat_map(bindings).collect(),
+        Pat::Struct(strukt) => strukt
+            .fields
+            .iter()
+            .flat_map(|field| bindings(&field.pat))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The variable a pattern binds when it is a single one, through a reference or not.
+fn single_binding(pat: &Pat) -> Option<&Ident> {
+    match pat {
+        Pat::Ident(ident) if ident.subpat.is_none() => Some(&ident.ident),
+        Pat::Reference(reference) => single_binding(&reference.pat),
+        Pat::Type(typed) => single_binding(&typed.pat),
+        Pat::Paren(paren) => single_binding(&paren.pat),
+        _ => None,
+    }
+}
+
+/// The variable an expression is, if it is a bare one.
+fn variable(expr: &Expr) -> Option<&Ident> {
+    match expr {
+        Expr::Path(path) if path.qself.is_none() => path.path.get_ident(),
+        Expr::Paren(paren) => variable(&paren.expr),
+        _ => None,
+    }
+}
+
+/// What a variable holds, for its values to be reported as what they are.
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Number,
+    F64,
+    /// Reported widened to an `f64`.
+    F32,
+    Str,
+}
+
+/// The kind of a variable of type `ty`.
+fn type_kind(ty: &Type) -> Kind {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => match path.path.get_ident() {
+            Some(ident) if ident == "f64" => Kind::F64,
+            Some(ident) if ident == "f32" => Kind::F32,
+            Some(ident) if ident == "String" => Kind::Str,
+            _ => Kind::Number,
+        },
+        Type::Reference(reference) => match &*reference.elem {
+            Type::Path(path) if path.path.is_ident("str") || path.path.is_ident("String") => {
+                Kind::Str
+            }
+            _ => Kind::Number,
+        },
+        Type::Paren(paren) => type_kind(&paren.elem),
+        Type::Group(group) => type_kind(&group.elem),
+        _ => Kind::Number,
+    }
+}
+
+/// The kind of a variable that starts from `init`: a float or string literal, a cast, an
+/// `f64::..` or `String::..`, a `format!` or a `to_string()`.
+fn init_kind(init: &Expr) -> Kind {
+    match init {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Float(float) if float.suffix() == "f32" => Kind::F32,
+            Lit::Float(_) => Kind::F64,
+            Lit::Str(_) => Kind::Str,
+            _ => Kind::Number,
+        },
+        Expr::Unary(unary) if matches!(unary.op, UnOp::Neg(_)) => init_kind(&unary.expr),
+        Expr::Paren(paren) => init_kind(&paren.expr),
+        Expr::Cast(cast) => type_kind(&cast.ty),
+        Expr::Path(path) => path_kind(&path.path),
+        Expr::Call(call) => match &*call.func {
+            Expr::Path(path) => path_kind(&path.path),
+            _ => Kind::Number,
+        },
+        Expr::Macro(mac) if mac.mac.path.is_ident("format") => Kind::Str,
+        Expr::MethodCall(call) if call.method == "to_string" => Kind::Str,
+        _ => Kind::Number,
+    }
+}
+
+/// The kind of the associated items of `f64`, `f32` and `String`: `f64::NAN`,
+/// `String::from(..)`.
+fn path_kind(path: &syn::Path) -> Kind {
+    match path.segments.first() {
+        Some(first) if path.segments.len() == 2 => type_kind(&Type::Path(parse_quote!(#first))),
+        _ => Kind::Number,
+    }
+}
+
+/// A loop variable that is part of an array, for the assignments through it to be reported
+/// as the array's.
+struct Element {
+    binding: Ident,
+    array: Ident,
+    /// The variable holding the index in the array of the element, or of the chunk's first.
+    index: Ident,
+    source: Source,
+}
+
+/// How a loop goes through its array.
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    /// An element at a time: `iter()`, `iter_mut()` or the slice itself.
+    Elements,
+    /// A chunk at a time: `chunks(n)`, `chunks_mut(n)` and their `_exact` forms.
+    Chunks,
+}
+
+/// The array loop of `for (i, x) in array.iter_mut().enumerate()`, `for x in &array` or
+/// `for chunk in array.chunks(n)`.
+struct ArrayLoop {
+    array: Ident,
+    source: Source,
+    /// The index `enumerate()` binds, unless it is `_` or the loop does not enumerate.
+    index: Option<Ident>,
+    element: Ident,
+}
+
+/// The array, index and element of a loop through an array variable, with or without
+/// `enumerate()`: over `array`, `&array`, `&mut array`, `array.iter()`, `array.iter_mut()` or
+/// `array.chunks(n)` and its `chunks_mut` and `_exact` forms.
+fn extract_array_loop(for_loop: &ExprForLoop) -> Option<ArrayLoop> {
+    let (iterable, index, element) = match &*for_loop.pat {
+        Pat::Tuple(tuple) if tuple.elems.len() == 2 => {
+            let Expr::MethodCall(enumerate) = &*for_loop.expr else {
+                return None;
+            };
+            if enumerate.method != "enumerate" || !enumerate.args.is_empty() {
+                return None;
+            }
+            let index = match &tuple.elems[0] {
+                Pat::Wild(_) => None,
+                index => Some(single_binding(index)?.clone()),
+            };
+            (&*enumerate.receiver, index, &tuple.elems[1])
+        }
+        element => (&*for_loop.expr, None, element),
+    };
+
+    let (array, source) = match iterable {
+        Expr::MethodCall(call)
+            if call.args.is_empty() && (call.method == "iter" || call.method == "iter_mut") =>
+        {
+            (variable(&call.receiver)?, Source::Elements)
+        }
+        Expr::MethodCall(call)
+            if call.args.len() == 1
+                && ["chunks", "chunks_mut", "chunks_exact", "chunks_exact_mut"]
+                    .iter()
+                    .any(|method| call.method == method) =>
+        {
+            (variable(&call.receiver)?, Source::Chunks)
+        }
+        // `enumerate()` is called on an iterator, not on the slice itself
+        Expr::Reference(reference) if index.is_none() => {
+            (variable(&reference.expr)?, Source::Elements)
+        }
+        slice if index.is_none() => (variable(slice)?, Source::Elements),
+        _ => return None,
+    };
+    Some(ArrayLoop {
+        array: array.clone(),
+        source,
+        index,
+        element: single_binding(element)?.clone(),
+    })
+}
+
+struct Instrumenter<'a> {
+    recorder: Ident,
+    skip: &'a [Ident],
+    /// The array elements of the loops around the code being instrumented, innermost last.
+    elements: Vec<Element>,
+    /// The kinds of the variables in scope, innermost last.
+    kinds: Vec<(Ident, Kind)>,
+    /// How many `for` loops deep the code being instrumented is, to name their hidden
+    /// variables.
+    depth: usize,
+}
+
+impl Instrumenter<'_> {
+    fn recorded(&self, name: &Ident) -> bool {
+        !self.skip.contains(name)
+    }
+
+    fn set_initial(&self, name: &Ident) -> Stmt {
+        let recorder = &self.recorder;
+        let label = name.to_string();
+        parse_quote!(#recorder.set_initial(#label, &#name);)
+    }
+
+    fn step(&self, line: u32) -> Stmt {
+        let recorder = &self.recorder;
+        parse_quote!(#recorder.push_step(#line);)
+    }
+
+    fn kind(&self, name: &Ident) -> Kind {
+        self.kinds
+            .iter()
+            .rev()
+            .find(|(variable, _)| variable == name)
+            .map_or(Kind::Number, |&(_, kind)| kind)
+    }
+
+    /// Bring the variables a pattern binds in scope, as numbers unless `kind` is given for a
+    /// single one.
+    fn bind(&mut self, pat: &Pat, kind: Option<Kind>) {
+        match (single_binding(pat), kind) {
+            (Some(name), Some(kind)) => self.kinds.push((name.clone(), kind)),
+            _ => {
+                let names = bindings(pat);
+                self.kinds
+                    .extend(names.into_iter().map(|name| (name, Kind::Number)));
+            }
+        }
+    }
+
+    /// The step of the value of a variable, as the kind of value it holds.
+    fn value_step(&self, line: u32, name: &Ident) -> Stmt {
+        let recorder = &self.recorder;
+        let label = name.to_string();
+        match self.kind(name) {
+            Kind::Number => parse_quote!(#recorder.push_number_step(#line, #label, &#name);),
+            Kind::F64 => parse_quote!(#recorder.push_float_step(#line, #label, #name);),
+            Kind::F32 => parse_quote!(#recorder.push_float_step(#line, #label, f64::from(#name));),
+            Kind::Str => parse_quote!(#recorder.push_str_step(#line, #label, &#name);),
+        }
+    }
+
+    fn array_step(
+        &self,
+        line: u32,
+        array: &Ident,
+        index: TokenStream2,
+        value: TokenStream2,
+    ) -> Stmt {
+        let recorder = &self.recorder;
+        let label = array.to_string();
+        parse_quote!(#recorder.push_array_step(#line, #label, #index, #value);)
+    }
+
+    fn arm_step(&self, line: u32, index: usize) -> Stmt {
+        let recorder = &self.recorder;
+        parse_quote!(#recorder.push_arm_step(#line, #index);)
+    }
+
+    /// The innermost loop variable named `name`, if it is part of an array the way `source`
+    /// goes through it.
+    fn element(&self, name: &Ident, source: Source) -> Option<&Element> {
+        self.elements
+            .iter()
+            .rev()
+            .find(|element| &element.binding == name)
+            .filter(|element| element.source == source)
+    }
+
+    /// The steps of the variables a pattern binds, for the start of the code it binds them in.
+    fn binding_steps(&self, line: u32, pat: &Pat) -> Vec<Stmt> {
+        bindings(pat)
+            .iter()
+            .filter(|name| self.recorded(name))
+            .map(|name| self.value_step(line, name))
+            .collect()
+    }
+
+    fn block(&mut self, block: &mut Block) {
+        let kinds = self.kinds.len();
+        for stmt in std::mem::take(&mut block.stmts) {
+            self.stmt(stmt, &mut block.stmts);
+        }
+        self.kinds.truncate(kinds);
+    }
+
+    fn stmt(&mut self, stmt: Stmt, out: &mut Vec<Stmt>) {
+        match stmt {
+            Stmt::Local(mut local) => {
+                let line = line_of(local.let_token.span);
+                let initialized = local.init.is_some();
+                if let Some(init) = &mut local.init {
+                    self.expr(&mut init.expr);
+                    if let Some((_, diverge)) = &mut init.diverge {
+                        self.expr(diverge);
+                    }
+                }
+                let kind = match &local.pat {
+                    Pat::Type(typed) => Some(type_kind(&typed.ty)),
+                    _ => local.init.as_ref().map(|init| init_kind(&init.expr)),
+                };
+                self.bind(&local.pat, kind);
+                let steps = if initialized {
+                    self.binding_steps(line, &local.pat)
+                } else {
+                    Vec::new()
+                };
+                out.push(Stmt::Local(local));
+                out.extend(steps);
+            }
+            Stmt::Expr(mut expr, semi) => {
+                let line = line_of(expr.span());
+                if let Some(steps) = self.assignment(&mut expr, line) {
+                    out.push(Stmt::Expr(expr, semi));
+                    out.extend(steps);
+                    return;
+                }
+                if let Some(name) = self.rewritten(&expr) {
+                    let step = self.value_step(line, &name);
+                    self.expr(&mut expr);
+                    out.push(Stmt::Expr(expr, semi));
+                    out.push(step);
+                    return;
+                }
+                match &expr {
+                    Expr::If(_)
+                    | Expr::Match(_)
+                    | Expr::ForLoop(_)
+                    | Expr::While(_)
+                    | Expr::Loop(_)
+                    | Expr::Block(_)
+                    | Expr::Unsafe(_)
+                    | Expr::Return(_)
+                    | Expr::Break(_)
+                    | Expr::Continue(_)
+                    | Expr::Path(_)
+                    | Expr::Lit(_) => {}
+                    _ => out.push(self.step(line)),
+                }
+                self.expr(&mut expr);
+                out.push(Stmt::Expr(expr, semi));
+            }
+            Stmt::Macro(mac) => {
+                out.push(self.step(line_of(mac.span())));
+                out.push(Stmt::Macro(mac));
+            }
+            Stmt::Item(item) => out.push(Stmt::Item(item)),
+        }
+    }
+
+    /// The variable a method call rewrites whole, `nv.fill(1)` or `order.sort()`.
+    fn rewritten(&self, expr: &Expr) -> Option<Ident> {
+        const REWRITING: [&str; 9] = [
+            "fill",
+            "copy_from_slice",
+            "clone_from_slice",
+            "swap",
+            "reverse",
+            "sort",
+            "sort_unstable",
+            "rotate_left",
+            "rotate_right",
+        ];
+        let Expr::MethodCall(call) = expr else {
+            return None;
+        };
+        let name = variable(&call.receiver)?;
+        (REWRITING.iter().any(|method| call.method == method) && self.recorded(name))
+            .then(|| name.clone())
+    }
+
+    /// Instrument an assignment, `=` or compound, to a variable or an array element, and return
+    /// the steps reporting the value it assigned.
+    fn assignment(&mut self, expr: &mut Expr, line: u32) -> Option<Vec<Stmt>> {
+        let (left, right) = match expr {
+            Expr::Assign(assign) => (&mut *assign.left, &mut *assign.right),
+            Expr::Binary(binary) if is_compound(&binary.op) => {
+                (&mut *binary.left, &mut *binary.right)
+            }
+            _ => return None,
+        };
+
+        if let Some(name) = variable(left) {
+            if !self.recorded(name) {
+                return None;
+            }
+            let name = name.clone();
+            self.expr(right);
+            return Some(vec![self.value_step(line, &name)]);
+        }
+
+        match left {
+            // through a loop's array element
+            Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Deref(_)) => {
+                let name = variable(&unary.expr)?;
+                let element = self.element(name, Source::Elements)?;
+                let index = &element.index;
+                let step = self.array_step(line, &element.array, quote!(#index), quote!(&*#name));
+                self.expr(right);
+                Some(vec![step])
+            }
+            Expr::Index(index) => {
+                let array = variable(&index.expr)?.clone();
+                if !self.recorded(&array) {
+                    return None;
+                }
+                // the index is evaluated once, for the assignment and its step
+                let position = Ident::new("__recorded_index", Span::mixed_site());
+                let at = std::mem::replace(&mut *index.index, parse_quote!(#position));
+                self.expr(right);
+                let assignment = expr.clone();
+                // an element of a chunk is the array's, past the chunk's start
+                let step = match self.element(&array, Source::Chunks) {
+                    Some(chunk) => {
+                        let start = &chunk.index;
+                        self.array_step(
+                            line,
+                            &chunk.array,
+                            quote!(#start + #position),
+                            quote!(&#array[#position]),
+                        )
+                    }
+                    None => {
+                        self.array_step(line, &array, quote!(#position), quote!(&#array[#position]))
+                    }
+                };
+                *expr = parse_quote!({
+                    let #position = #at;
+                    #assignment;
+                    #step
+                });
+                Some(Vec::new())
+            }
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Block(block) => self.block(&mut block.block),
+            Expr::Unsafe(block) => self.block(&mut block.block),
+            Expr::If(if_expr) => self.if_expr(if_expr),
+            Expr::Match(match_expr) => self.match_expr(match_expr),
+            Expr::While(while_expr) => {
+                let line = line_of(while_expr.while_token.span);
+                self.expr(&mut while_expr.cond);
+                let kinds = self.kinds.len();
+                if let Expr::Let(condition) = &*while_expr.cond {
+                    self.bind(&condition.pat, None);
+                }
+                self.block(&mut while_expr.body);
+                let mut steps = vec![self.step(line)];
+                if let Expr::Let(condition) = &*while_expr.cond {
+                    steps.extend(self.binding_steps(line, &condition.pat));
+                }
+                self.kinds.truncate(kinds);
+                while_expr.body.stmts.splice(0..0, steps);
+            }
+            Expr::Loop(loop_expr) => {
+                let line = line_of(loop_expr.loop_token.span);
+                self.block(&mut loop_expr.body);
+                loop_expr.body.stmts.insert(0, self.step(line));
+            }
+            Expr::ForLoop(_) => self.for_loop(expr),
+            Expr::Return(_) | Expr::Break(_) | Expr::Continue(_) => self.flow(expr),
+            Expr::Paren(paren) => self.expr(&mut paren.expr),
+            Expr::Group(group) => self.expr(&mut group.expr),
+            Expr::Reference(reference) => self.expr(&mut reference.expr),
+            Expr::Unary(unary) => self.expr(&mut unary.expr),
+            Expr::Cast(cast) => self.expr(&mut cast.expr),
+            Expr::Field(field) => self.expr(&mut field.base),
+            Expr::Try(try_expr) => self.expr(&mut try_expr.expr),
+            Expr::Let(let_expr) => self.expr(&mut let_expr.expr),
+            Expr::Binary(binary) => {
+                self.expr(&mut binary.left);
+                self.expr(&mut binary.right);
+            }
+            Expr::Assign(assign) => {
+                self.expr(&mut assign.left);
+                self.expr(&mut assign.right);
+            }
+            Expr::Index(index) => {
+                self.expr(&mut index.expr);
+                self.expr(&mut index.index);
+            }
+            Expr::Call(call) => call.args.iter_mut().for_each(|arg| self.expr(arg)),
+            Expr::MethodCall(call) => {
+                self.expr(&mut call.receiver);
+                call.args.iter_mut().for_each(|arg| self.expr(arg));
+            }
+            Expr::Tuple(tuple) => tuple.elems.iter_mut().for_each(|elem| self.expr(elem)),
+            Expr::Array(array) => array.elems.iter_mut().for_each(|elem| self.expr(elem)),
+            // closures and async blocks cannot borrow the recorder, their bodies stay as is
+            _ => {}
+        }
+    }
+
+    /// Report a `return`, `break` or `continue` before it runs.
+    fn flow(&mut self, expr: &mut Expr) {
+        let (line, flow) = match expr {
+            Expr::Return(ret) => {
+                if let Some(value) = &mut ret.expr {
+                    self.expr(value);
+                }
+                (line_of(ret.return_token.span), quote!(Return))
+            }
+            Expr::Break(brk) => {
+                if let Some(value) = &mut brk.expr {
+                    self.expr(value);
+                }
+                (line_of(brk.break_token.span), quote!(Break))
+            }
+            Expr::Continue(cont) => (line_of(cont.continue_token.span), quote!(Continue)),
+            _ => return,
+        };
+        let recorder = &self.recorder;
+        *expr = parse_quote!({
+            #recorder.push_flow_step(#line, ::spicy_record::ControlFlow::#flow);
+            #expr
+        });
+    }
+
+    /// Instrument the branches of an `if`: the branch taken is a step of its `if` or `else`.
+    fn if_expr(&mut self, if_expr: &mut ExprIf) {
+        let line = line_of(if_expr.if_token.span);
+        self.expr(&mut if_expr.cond);
+        let kinds = self.kinds.len();
+        if let Expr::Let(condition) = &*if_expr.cond {
+            self.bind(&condition.pat, None);
+        }
+        self.block(&mut if_expr.then_branch);
+        let mut steps = vec![self.step(line)];
+        if let Expr::Let(condition) = &*if_expr.cond {
+            steps.extend(self.binding_steps(line, &condition.pat));
+        }
+        self.kinds.truncate(kinds);
+        if_expr.then_branch.stmts.splice(0..0, steps);
+
+        if let Some((else_token, else_branch)) = &mut if_expr.else_branch {
+            match &mut **else_branch {
+                Expr::If(else_if) => self.if_expr(else_if),
+                Expr::Block(block) => {
+                    self.block(&mut block.block);
+                    let step = self.step(line_of(else_token.span));
+                    block.block.stmts.insert(0, step);
+                }
+                other => self.expr(other),
+            }
+        }
+    }
+
+    /// Instrument the arms of a `match`: the arm taken is reported by its index.
+    fn match_expr(&mut self, match_expr: &mut ExprMatch) {
+        self.expr(&mut match_expr.expr);
+        for (index, arm) in match_expr.arms.iter_mut().enumerate() {
+            self.arm(arm, index);
+        }
+    }
+
+    fn arm(&mut self, arm: &mut Arm, index: usize) {
+        let line = line_of(arm.pat.span());
+        let kinds = self.kinds.len();
+        self.bind(&arm.pat, None);
+        self.expr(&mut arm.body);
+        let mut steps = vec![self.arm_step(line, index)];
+        steps.extend(self.binding_steps(line, &arm.pat));
+        self.kinds.truncate(kinds);
+        let body = &arm.body;
+        let body: Expr = parse_quote!({
+            #(#steps)*
+            #body
+        });
+        *arm.body = body;
+        if arm.comma.is_none() {
+            arm.comma = Some(Default::default());
+        }
+    }
+
+    /// Instrument a `for` loop: every iteration is a step of its line, with the values of the
+    /// loop variables. An element the loop takes from an array is reported as the array's, at
+    /// its index, and a chunk as its elements.
+    fn for_loop(&mut self, expr: &mut Expr) {
+        let Expr::ForLoop(for_loop) = expr else {
+            return;
+        };
+        let line = line_of(for_loop.for_token.span);
+        self.expr(&mut for_loop.expr);
+
+        let mut steps = vec![self.step(line)];
+        let elements = self.elements.len();
+        let kinds = self.kinds.len();
+        self.bind(&for_loop.pat, None);
+        // sums the lengths of the chunks the loop went through
+        let mut counter = None;
+        let array_loop = extract_array_loop(for_loop).filter(|array_loop| {
+            self.recorded(&array_loop.array) && self.recorded(&array_loop.element)
+        });
+        match array_loop {
+            Some(array_loop) => {
+                let array = &array_loop.array;
+                let element = &array_loop.element;
+                let at = Ident::new(
+                    &format!("__recorded_index_{}", self.depth),
+                    Span::mixed_site(),
+                );
+                match (&array_loop.index, array_loop.source) {
+                    (Some(index), Source::Elements) => steps.push(parse_quote!(let #at = #index;)),
+                    // enumerate the elements, as the loop takes them
+                    (None, Source::Elements) => match &mut *for_loop.pat {
+                        Pat::Tuple(enumerated) => enumerated.elems[0] = parse_quote!(#at),
+                        element => {
+                            let iterable = &for_loop.expr;
+                            *element = parse_quote!((#at, #element));
+                            *for_loop.expr = parse_quote!(
+                                ::core::iter::IntoIterator::into_iter(#iterable).enumerate()
+                            );
+                        }
+                    },
+                    // a chunk starts past the lengths of the chunks before it
+                    (_, Source::Chunks) => {
+                        let start = Ident::new(
+                            &format!("__recorded_start_{}", self.depth),
+                            Span::mixed_site(),
+                        );
+                        steps.push(parse_quote!(let #at = #start;));
+                        steps.push(parse_quote!(#start += #element.len();));
+                        counter = Some(start);
+                    }
+                }
+                if let Some(index) = array_loop
+                    .index
+                    .as_ref()
+                    .filter(|index| self.recorded(index))
+                {
+                    steps.push(self.value_step(line, index));
+                }
+                match array_loop.source {
+                    Source::Elements => {
+                        steps.push(self.array_step(line, array, quote!(#at), quote!(&#element)))
+                    }
+                    Source::Chunks => {
+                        let offset = Ident::new("__recorded_offset", Span::mixed_site());
+                        let value = Ident::new("__recorded_value", Span::mixed_site());
+                        let step =
+                            self.array_step(line, array, quote!(#at + #offset), quote!(#value));
+                        steps.push(parse_quote! {
+                            for (#offset, #value) in #element.iter().enumerate() {
+                                #step
+                            }
+                        });
+                    }
+                }
+                self.elements.push(Element {
+                    binding: element.clone(),
+                    array: array.clone(),
+                    index: at,
+                    source: array_loop.source,
+                });
+            }
+            None => steps.extend(self.binding_steps(line, &for_loop.pat)),
+        }
+
+        self.depth += 1;
+        self.block(&mut for_loop.body);
+        self.depth -= 1;
+        self.elements.truncate(elements);
+        self.kinds.truncate(kinds);
+        for_loop.body.stmts.splice(0..0, steps);
+
+        if let Some(start) = counter {
+            let for_loop = for_loop.clone();
+            *expr = parse_quote!({
+                let mut #start = 0usize;
+                #for_loop
+            });
+        }
+    }
+}
+
+fn is_compound(op: &BinOp) -> bool {
+    matches!(
+        op,
+        BinOp::AddAssign(_)
+            | BinOp::SubAssign(_)
+            | BinOp::MulAssign(_)
+            | BinOp::DivAssign(_)
+            | BinOp::RemAssign(_)
+            | BinOp::BitXorAssign(_)
+            | BinOp::BitAndAssign(_)
+            | BinOp::BitOrAssign(_)
+            | BinOp::ShlAssign(_)
+            | BinOp::ShrAssign(_)
+    )
+}
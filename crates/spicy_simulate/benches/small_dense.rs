@@ -0,0 +1,95 @@
+//! The per-timestep factor and solve of small MNA matrices, dense against KLU, to place the
+//! crossover `SolverBackend::select` uses.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use spicy_simulate::solver::{
+    dense::DenseLu,
+    klu::{self, KluConfig},
+    matrix::{Dim, csc::CscMatrix},
+};
+
+const SIZES: [usize; 8] = [4, 8, 12, 16, 24, 32, 48, 64];
+
+/// The row-major MNA matrix of a resistor ladder of `n - 1` nodes, with a rung from every node
+/// to one further down and a voltage source driving the first node.
+fn ladder(n: usize) -> Vec<f64> {
+    let nodes = n - 1;
+    let mut a = vec![0.0; n * n];
+    let mut conductance = |i: usize, j: usize, g: f64| {
+        a[i * n + i] += g;
+        a[j * n + j] += g;
+        a[i * n + j] -= g;
+        a[j * n + i] -= g;
+    };
+    for i in 0..nodes - 1 {
+        conductance(i, i + 1, 1e-3);
+        conductance(i, (i * 7 + 3) % nodes, 1e-4);
+    }
+    for i in 0..nodes {
+        a[i * n + i] += 1e-5;
+    }
+    a[nodes * n] = 1.0;
+    a[nodes] = 1.0;
+    a
+}
+
+fn to_csc(a: &[f64], n: usize) -> CscMatrix {
+    let mut csc = CscMatrix {
+        dim: Dim { nrows: n, ncols: n },
+        column_pointers: vec![0],
+        row_indices: Vec::new(),
+        values: Vec::new(),
+    };
+    for j in 0..n {
+        for i in 0..n {
+            if a[i * n + j] != 0.0 {
+                csc.row_indices.push(i);
+                csc.values.push(a[i * n + j]);
+            }
+        }
+        csc.column_pointers.push(csc.row_indices.len());
+    }
+    csc
+}
+
+fn bench_factor_solve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("small_dense/factor_solve");
+    for n in SIZES {
+        let a = ladder(n);
+        let rhs: Vec<f64> = (0..n).map(|i| i as f64).collect();
+
+        let mut lu = DenseLu::new(n);
+        let mut b = rhs.clone();
+        group.bench_with_input(BenchmarkId::new("dense", n), &a, |bench, a| {
+            bench.iter(|| {
+                lu.factor(a).expect("dense factor");
+                b.copy_from_slice(&rhs);
+                lu.solve(&mut b);
+                black_box(&b);
+            });
+        });
+
+        // the pivot order is reused from one timestep to the next, and the stability of the
+        // pivots checked, as `SolverMatrix::refactor` does
+        let csc = to_csc(&a, n);
+        let mut config = KluConfig::default();
+        let mut symbolic = klu::analyze(&csc, &config).expect("klu analyze");
+        let mut numeric = klu::factor::<f64>(&csc, &mut symbolic, &mut config).expect("factor");
+        group.bench_with_input(BenchmarkId::new("klu_refactor", n), &csc, |bench, csc| {
+            bench.iter(|| {
+                klu::refactor(csc, &mut symbolic, &mut numeric, &config).expect("refactor");
+                black_box(klu::rcond(&numeric));
+                black_box(klu::rgrowth(csc, &symbolic, &numeric).expect("rgrowth"));
+                b.copy_from_slice(&rhs);
+                klu::solve(&symbolic, &mut numeric, n, 1, &mut b, &config).expect("solve");
+                black_box(&b);
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(small_dense, bench_factor_solve);
+criterion_main!(small_dense);
@@ -5,17 +5,24 @@ use spicy_parser::{
     netlist_types::{AcCommand, AcSweepType},
 };
 
-use crate::SimulationConfig;
+use crate::dc::{NodeConditions, simulate_op_inner};
 use crate::devices::Devices;
+use crate::error::SimulationError;
+use crate::matrix::SolverMatrix;
+use crate::warnings::Warnings;
+use crate::{NewtonMode, NewtonState, SimulationConfig};
 use spicy_parser::node_mapping::NodeMapping;
 use std::f64::consts::PI;
 
-fn ac_frequencies(cmd: &AcCommand) -> Vec<f64> {
+/// Frequency with the real and imaginary parts of the MNA solution, per swept point.
+pub type AcSweep = Vec<(f64, Array1<f64>, Array1<f64>)>;
+
+pub(crate) fn ac_frequencies(cmd: &AcCommand) -> Vec<f64> {
     let fstart = cmd.fstart.get_value();
     let fstop = cmd.fstop.get_value();
     assert!(
-        fstop > fstart,
-        ".AC: fstop {:?} must be > fstart {:?}",
+        fstop >= fstart,
+        ".AC: fstop {:?} must be >= fstart {:?}",
         fstop,
         fstart
     );
@@ -58,6 +65,7 @@ fn ac_frequencies(cmd: &AcCommand) -> Vec<f64> {
             let step = (fstop - fstart) / ((n - 1) as f64);
             (0..n).map(|k| fstart + k as f64 * step).collect()
         }
+        AcSweepType::List(frequencies) => frequencies.iter().map(|f| f.get_value()).collect(),
     }
 }
 
@@ -73,10 +81,12 @@ fn ac_frequencies(cmd: &AcCommand) -> Vec<f64> {
 /// which is the same as the real system:
 /// Assemble the AC small-signal system using a real 2x2 block expansion.
 /// Returns (M, s) where M is 2*(n+k) square and s is length 2*(n+k).
-fn assemble_ac_real_expansion(
+/// The nonlinear devices are linearized at the operating point `op`.
+pub(crate) fn assemble_ac_real_expansion(
     devices: &Devices,
     node_mapping: &NodeMapping,
     w: f64,
+    op: Option<&[f64]>,
 ) -> (Array2<f64>, Array1<f64>) {
     let n = node_mapping.nodes_len();
     let k = node_mapping.branches_len();
@@ -98,12 +108,26 @@ fn assemble_ac_real_expansion(
     for dev in &devices.inductors {
         dev.stamp_ac(&mut ar, &mut ai, node_mapping, w);
     }
+    for dev in &devices.mutual_inductances {
+        dev.stamp_ac(&mut ai, &devices.inductors, node_mapping, w);
+    }
+    for dev in &devices.transmission_lines {
+        dev.stamp_ac(&mut ar, &mut ai, node_mapping, w);
+    }
     for dev in &devices.voltage_sources {
         dev.stamp_ac_voltage_source(&mut ar, &mut br, &mut bi, node_mapping);
     }
     for dev in &devices.current_sources {
         dev.stamp_ac_current_source(&mut br, &mut bi, node_mapping);
     }
+    if let Some(op) = op {
+        for dev in devices.small_signal_models() {
+            dev.stamp_small_signal(&mut ar, &mut ai, node_mapping, op, w);
+        }
+    }
+    for dev in &devices.plugins {
+        dev.load_ac(node_mapping, &mut ar, &mut ai, &mut br, &mut bi, w);
+    }
 
     // Build the 2x2 real system: [ Ar  -Ai ; Ai  Ar ] * [xr; xi] = [br; bi]
     let dim = n + k;
@@ -125,39 +149,76 @@ fn assemble_ac_real_expansion(
 pub fn simulate_ac(
     deck: &Deck,
     cmd: &AcCommand,
-    _sim_config: &SimulationConfig,
-) -> Vec<(f64, Array1<f64>, Array1<f64>)> {
-    let freqs = ac_frequencies(cmd);
-    let devices = Devices::from_spec(&deck.devices);
+    sim_config: &SimulationConfig,
+) -> Result<AcSweep, SimulationError> {
+    let mut devices = Devices::from_deck(deck, sim_config);
     let node_mapping = &deck.node_mapping;
-    let n = node_mapping.nodes_len();
-    let k = node_mapping.branches_len();
 
-    let mut out = Vec::new();
+    // nonlinear devices are linearized at the operating point, so solve it first
+    let op = if devices.is_linear() {
+        None
+    } else {
+        Some(small_signal_op(deck, &mut devices, sim_config)?)
+    };
+    let save_currents = deck.options.save_currents;
+    Ok(run_ac(
+        &devices,
+        node_mapping,
+        cmd,
+        save_currents,
+        op.as_deref(),
+    ))
+}
+
+/// Solve the DC operating point the small-signal analyses linearize around.
+pub(crate) fn small_signal_op(
+    deck: &Deck,
+    devices: &mut Devices,
+    sim_config: &SimulationConfig,
+) -> Result<Vec<f64>, SimulationError> {
+    let mut matrix = SolverMatrix::create_matrix(devices, deck.node_mapping.clone(), sim_config)?;
+    let mut state = NewtonState::new(sim_config.newton, NewtonMode::InitOp)
+        .with_cancel(&sim_config.cancel)
+        .with_dump(&sim_config.dump_matrix);
+    let guess = NodeConditions::from_deck(deck).guess(matrix.rhs().len());
+    simulate_op_inner(
+        &mut matrix,
+        devices,
+        &mut state,
+        guess,
+        &mut Warnings::default(),
+    )?;
+    Ok(matrix.rhs().to_vec())
+}
 
+/// Solve the small-signal system of `devices` at every frequency of `cmd`, with the
+/// nonlinear devices linearized at `op` when given.
+/// Returns the real and imaginary parts of the solution per frequency, followed by the device
+/// currents when `save_currents`.
+pub(crate) fn run_ac(
+    devices: &Devices,
+    node_mapping: &NodeMapping,
+    cmd: &AcCommand,
+    save_currents: bool,
+    op: Option<&[f64]>,
+) -> AcSweep {
+    let freqs = ac_frequencies(cmd);
+    let dim = node_mapping.mna_matrix_dim();
+
+    let mut out = Vec::new();
     for f in freqs {
         let w = 2.0 * PI * f;
-        let (m, s_vec) = assemble_ac_real_expansion(&devices, node_mapping, w);
+        let (m, s_vec) = assemble_ac_real_expansion(devices, node_mapping, w, op);
         let lu = m.factorize_into().expect("Failed to factorize AC matrix");
         let x = lu.solve(&s_vec).expect("Failed to solve AC system");
 
-        let dim = n + k;
-        let xr = x.slice(s![0..dim]).to_owned();
-        let xi = x.slice(s![dim..2 * dim]).to_owned();
-
-        // Optional: print node phasors
-        let node_names = node_mapping.node_names_mna_order();
-        for i in 0..n {
-            let vr = xr[i];
-            let vi = xi[i];
-            let mag = (vr * vr + vi * vi).sqrt();
-            let phase = vi.atan2(vr) * 180.0 / PI;
-            println!(
-                "f={:.6} Hz  {}: {:.6} ∠ {:.3}°",
-                f, node_names[i], mag, phase
-            );
+        let mut xr = x.slice(s![0..dim]).to_owned();
+        let mut xi = x.slice(s![dim..2 * dim]).to_owned();
+        if save_currents {
+            let (ir, ii) = devices.ac_device_currents(node_mapping, w, op, &xr, &xi);
+            xr = xr.into_iter().chain(ir).collect();
+            xi = xi.into_iter().chain(ii).collect();
         }
-
         out.push((f, xr, xi));
     }
 
@@ -0,0 +1,86 @@
+use std::io::Read;
+use std::net::TcpListener;
+
+use clap::Parser;
+use spicy_simulate::ipc::{IpcEndpoint, IpcMessage, IpcReader};
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Listens for a simulator IPC stream and prints every message it receives.",
+    after_help = "Run `spicy_cli --ipc <ENDPOINT> <netlist>` against the same endpoint.",
+    version
+)]
+struct Args {
+    /// Endpoint to listen on (tcp://host:port or unix:///path)
+    #[arg(value_name = "ENDPOINT")]
+    endpoint: IpcEndpoint,
+}
+
+fn print_message(message: &IpcMessage) {
+    match message {
+        IpcMessage::Signals {
+            analysis,
+            x_name,
+            names,
+        } => println!("[{analysis}] {x_name} | {}", names.join(" ")),
+        IpcMessage::Sample { x, values } => {
+            let values: Vec<String> = values.iter().map(|v| format!("{v:.6e}")).collect();
+            println!("{x:.6e} | {}", values.join(" "));
+        }
+        IpcMessage::Matrix { label, matrix } => println!(
+            "matrix {label}: {}x{} nnz={}",
+            matrix.dim.nrows,
+            matrix.dim.ncols,
+            matrix.nnz()
+        ),
+        IpcMessage::Ordering {
+            label,
+            row_permutation,
+            column_permutation,
+        } => println!("ordering {label}: P={row_permutation:?} Q={column_permutation:?}"),
+        IpcMessage::End => println!("end"),
+    }
+}
+
+fn drain<R: Read>(stream: R) {
+    let mut reader = match IpcReader::new(stream) {
+        Ok(reader) => reader,
+        Err(e) => {
+            eprintln!("handshake failed: {e}");
+            return;
+        }
+    };
+    loop {
+        match reader.next_message() {
+            Ok(Some(message)) => print_message(&message),
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("stream error: {e}");
+                break;
+            }
+        }
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let args = Args::parse();
+    match args.endpoint {
+        IpcEndpoint::Tcp(addr) => {
+            let listener = TcpListener::bind(&addr)?;
+            println!("listening on tcp://{addr}");
+            for stream in listener.incoming() {
+                drain(stream?);
+            }
+        }
+        #[cfg(unix)]
+        IpcEndpoint::Unix(path) => {
+            let _ = std::fs::remove_file(&path);
+            let listener = std::os::unix::net::UnixListener::bind(&path)?;
+            println!("listening on unix://{}", path.display());
+            for stream in listener.incoming() {
+                drain(stream?);
+            }
+        }
+    }
+    Ok(())
+}
@@ -3,7 +3,10 @@ use spicy_simulate::solver::{
     klu,
     matrix::{
         csc::CscMatrix,
-        mtx::{load_matrix_market_csc_file, load_matrix_market_csc_file_keep_zeros},
+        mtx::{
+            load_matrix_market_array_file, load_matrix_market_csc_file,
+            load_matrix_market_csc_file_keep_zeros,
+        },
     },
 };
 use std::path::{Path, PathBuf};
@@ -81,6 +84,11 @@ struct Args {
     #[arg(long = "dump-bin", value_name = "PREFIX")]
     dump_bin: Option<PathBuf>,
 
+    /// Solve for this right-hand side (MatrixMarket array, e.g. written by `--dump-matrix`)
+    /// instead of the demo one.
+    #[arg(long, value_name = "PATH")]
+    rhs: Option<PathBuf>,
+
     /// Path to MatrixMarket coordinate matrix (.mtx)
     #[arg(value_name = "PATH")]
     path: PathBuf,
@@ -206,7 +214,20 @@ fn main() {
 
     let n = a.dim.ncols;
     let t = Instant::now();
-    let b = make_demo_rhs(n);
+    let b = match &args.rhs {
+        None => make_demo_rhs(n),
+        Some(rhs) => match load_matrix_market_array_file(rhs) {
+            Ok(b) if b.len() == n => b,
+            Ok(b) => {
+                eprintln!("right-hand side has {} entries, expected {n}", b.len());
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("failed to load right-hand side: {e}");
+                std::process::exit(1);
+            }
+        },
+    };
     let mut x = b.clone();
     stages.push(("make_rhs", t.elapsed()));
 
@@ -224,7 +245,7 @@ fn main() {
     stages.push(("klu_analyze", t.elapsed()));
 
     let t = Instant::now();
-    let mut numeric = match klu::factor(&a, &mut symbolic, &mut config) {
+    let mut numeric = match klu::factor::<f64>(&a, &mut symbolic, &mut config) {
         Ok(numeric) => numeric,
         Err(e) => {
             eprintln!("klu factor failed: {e}");
@@ -0,0 +1,88 @@
+use clap::Parser;
+use spicy_simulate::solver::{
+    klu::{self, KluConfig, KluOrdering},
+    matrix::{
+        csc::CscMatrix,
+        mtx::load_matrix_market_csc_file,
+        spy::{SpyOrdering, write_spy_svg},
+    },
+};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Draws the sparsity pattern of a MatrixMarket matrix (.mtx), e.g. one written by `--dump-matrix`, as SVG spy plots.",
+    after_help = "Writes <PREFIX>.natural.svg (the matrix as stored), <PREFIX>.btf.svg (the block triangular form, blocks in natural order) and <PREFIX>.amd.svg (as KLU factors it, every block ordered by AMD), with the diagonal blocks outlined.",
+    version
+)]
+struct Args {
+    /// Prefix of the SVG files; defaults to the matrix path without its extension.
+    #[arg(long = "out", value_name = "PREFIX")]
+    out: Option<PathBuf>,
+
+    /// Path to MatrixMarket coordinate matrix (.mtx)
+    #[arg(value_name = "PATH")]
+    path: PathBuf,
+}
+
+fn spy_path(prefix: &Path, suffix: &str) -> PathBuf {
+    let mut os = prefix.as_os_str().to_os_string();
+    os.push(suffix);
+    PathBuf::from(os)
+}
+
+fn write_spy(out: &Path, a: &CscMatrix, ordering: Option<SpyOrdering<'_>>) {
+    let written = File::create(out).and_then(|f| {
+        let mut w = BufWriter::new(f);
+        write_spy_svg(&mut w, a, ordering)?;
+        w.flush()
+    });
+    if let Err(e) = written {
+        eprintln!("failed to write spy plot {}: {e}", out.display());
+        std::process::exit(1);
+    }
+    println!("wrote spy plot: {}", out.display());
+}
+
+fn write_ordered_spy(out: &Path, a: &CscMatrix, config: &KluConfig) {
+    let symbolic = match klu::analyze(a, config) {
+        Ok(symbolic) => symbolic,
+        Err(e) => {
+            eprintln!("klu analyze failed: {e}");
+            std::process::exit(1);
+        }
+    };
+    let ordering = SpyOrdering {
+        rows: symbolic.row_permutation(),
+        columns: symbolic.column_permutation(),
+        blocks: symbolic.block_boundaries(),
+    };
+    write_spy(out, a, Some(ordering));
+}
+
+fn main() {
+    let args = Args::parse();
+    let a = match load_matrix_market_csc_file(&args.path) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("failed to load MatrixMarket file: {e}");
+            std::process::exit(1);
+        }
+    };
+    let prefix = args.out.unwrap_or_else(|| args.path.with_extension(""));
+
+    write_spy(&spy_path(&prefix, ".natural.svg"), &a, None);
+    if !a.is_square() {
+        eprintln!("KLU only supports square matrices; skipping the BTF and AMD views.");
+        std::process::exit(2);
+    }
+    let natural = KluOrdering::custom(|block: &CscMatrix| (0..block.dim.ncols).collect());
+    write_ordered_spy(
+        &spy_path(&prefix, ".btf.svg"),
+        &a,
+        &KluConfig::default().with_ordering(natural),
+    );
+    write_ordered_spy(&spy_path(&prefix, ".amd.svg"), &a, &KluConfig::default());
+}
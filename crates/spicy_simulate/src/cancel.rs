@@ -0,0 +1,136 @@
+//! Stopping a running simulation from another thread.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A flag shared between the simulation and whoever may want to stop it.
+///
+/// Clones share the flag, so keep one and pass another in
+/// [`crate::SimulationConfig::cancel`]. The DC sweep and transient loops stop at the next point
+/// and return what they have so far, flagged as cancelled; Newton stops at the next iteration
+/// with [`crate::SimulationError::Cancelled`].
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dc::{OperatingPointResult, simulate_dc};
+    use crate::observer::SimulateObserver;
+    use crate::test_utils::parse_netlist;
+    use crate::trans::simulate_trans;
+    use crate::{SimulationConfig, SimulationError, simulate};
+    use spicy_parser::netlist_types::Command;
+    use std::ops::ControlFlow;
+
+    const NETLIST: &str = "cancelled
+V1 in 0 DC 1
+R1 in out 1k
+C1 out 0 1u
+.dc V1 0 1 0.25
+.tran 100u 1m
+.end
+";
+
+    /// Cancels the run from inside, as another thread would, after `after` points.
+    struct CancelAfter {
+        token: CancellationToken,
+        after: f64,
+    }
+
+    impl SimulateObserver for CancelAfter {
+        fn on_sweep_point(&self, value: f64, _op: &OperatingPointResult) -> ControlFlow<()> {
+            if value >= self.after {
+                self.token.cancel();
+            }
+            ControlFlow::Continue(())
+        }
+
+        fn on_timepoint(&self, time: f64, _solution: &[f64]) -> ControlFlow<()> {
+            if time >= self.after {
+                self.token.cancel();
+            }
+            ControlFlow::Continue(())
+        }
+    }
+
+    fn cancel_after(after: f64) -> SimulationConfig {
+        let token = CancellationToken::new();
+        SimulationConfig {
+            observer: Some(Arc::new(CancelAfter {
+                token: token.clone(),
+                after,
+            })),
+            cancel: token,
+            parallel: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn clones_share_the_flag() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        token.cancel();
+        assert!(clone.is_cancelled());
+    }
+
+    #[test]
+    fn dc_sweep_stops_at_the_last_solved_point() {
+        let deck = parse_netlist(NETLIST);
+        let Some(Command::Dc(dc)) = deck.commands.first() else {
+            panic!("expected .dc");
+        };
+        let result = simulate_dc(&deck, dc, &cancel_after(0.5)).expect("dc");
+        assert!(result.cancelled);
+        let values: Vec<f64> = result.results.iter().map(|(_, v)| *v).collect();
+        assert_eq!(values, [0.0, 0.25, 0.5]);
+    }
+
+    #[test]
+    fn transient_returns_the_accepted_steps() {
+        let deck = parse_netlist(NETLIST);
+        let Some(Command::Tran(tran)) = deck.commands.get(1) else {
+            panic!("expected .tran");
+        };
+        let result = simulate_trans(&deck, tran, &cancel_after(250e-6)).expect("tran");
+        assert!(result.cancelled);
+        assert_eq!(result.times.len(), 4);
+        assert_eq!(result.samples.len(), 4);
+    }
+
+    #[test]
+    fn newton_stops_before_the_first_iteration() {
+        let deck = parse_netlist(NETLIST);
+        let Some(Command::Tran(tran)) = deck.commands.get(1) else {
+            panic!("expected .tran");
+        };
+        let config = SimulationConfig::default();
+        config.cancel.cancel();
+        let result = simulate_trans(&deck, tran, &config);
+        assert!(matches!(result, Err(SimulationError::Cancelled)));
+    }
+
+    #[test]
+    fn simulate_skips_the_analyses_after_a_cancel() {
+        let report = simulate(parse_netlist(NETLIST), cancel_after(0.5)).expect("simulate");
+        assert!(report.cancelled);
+        assert_eq!(report.analyses.len(), 1);
+        assert!(report.dc_sweeps().all(|dc| dc.cancelled));
+    }
+}
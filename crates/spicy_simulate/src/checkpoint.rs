@@ -0,0 +1,737 @@
+//! `--checkpoint`: periodically save the state of a transient analysis to a file, so a run
+//! that crashed or was stopped can carry on from its last accepted time point (`--resume`)
+//! instead of starting over at t=0.
+//!
+//! The files are text, one record per line: a keyword followed by its values. Floats are
+//! written with the shortest representation that reads back to the same value, so a resumed
+//! run continues exactly where the saved one stopped.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::{FromStr, SplitWhitespace};
+
+use crate::error::SimulationError;
+
+const HEADER: &str = "spicy-checkpoint 3";
+
+/// Where a transient analysis saves its state, and whether it starts from it.
+///
+/// A checkpoint is written every `every` accepted time points and when the analysis is
+/// cancelled; `every == 0` only writes the latter. The samples of the points before the
+/// checkpoint are saved with it, appended to `<path>.points` as they are output, so the
+/// resumed result is the whole transient. Warnings and solver statistics only cover the
+/// resumed part.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub path: PathBuf,
+    pub every: usize,
+    /// carry on from the checkpoint at `path` instead of starting at t=0
+    pub resume: bool,
+}
+
+/// The transient analysis a checkpoint belongs to: resuming another one is an error.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Fingerprint {
+    pub tstep: f64,
+    pub tstop: f64,
+    pub uic: bool,
+    pub trapezoidal: bool,
+    pub adaptive: bool,
+    /// size of the MNA solution
+    pub unknowns: usize,
+    /// values per sample, after `.options savecurrents` and `.save`
+    pub width: usize,
+}
+
+/// Everything a transient analysis needs to take its next step from the point at `time`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TransientState {
+    pub fingerprint: Fingerprint,
+    pub time: f64,
+    pub use_device_ic: bool,
+    /// time points accepted up to `time`, output before tstart or not, for the checkpoint
+    /// period
+    pub accepted: usize,
+    /// the next step of the adaptive step control
+    pub step: Option<f64>,
+    /// the solution at `time`
+    pub previous: Vec<f64>,
+    /// the whole sample at `time`, with the saved device currents
+    pub previous_sample: Vec<f64>,
+    /// the trapezoidal rule's capacitor currents, by device name
+    pub capacitor_currents: Vec<(String, f64)>,
+    /// the trapezoidal rule's junction charge currents of BJTs and JFETs, by device name
+    pub junction_currents: Vec<(String, [f64; 2])>,
+    /// the last accepted points of the adaptive step control, oldest first
+    pub history: Vec<(f64, Vec<f64>)>,
+    /// the waves of every transmission line, oldest first
+    pub lines: Vec<Vec<(f64, [f64; 2])>>,
+    /// whether every switch is on
+    pub switches: Vec<bool>,
+    /// the temperature rise of every self-heating device, by device name
+    pub rises: Vec<(String, f64)>,
+}
+
+/// The points of the transient up to a checkpoint: times, Newton iterations and samples.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct SavedPoints {
+    pub times: Vec<f64>,
+    pub newton_iterations: Vec<usize>,
+    pub samples: Vec<Vec<f64>>,
+    /// the length of the points file up to the checkpoint
+    pub length: u64,
+}
+
+/// The file the points of the checkpoint at `path` are appended to.
+fn points_path(path: &Path) -> PathBuf {
+    let mut points = path.as_os_str().to_owned();
+    points.push(".points");
+    PathBuf::from(points)
+}
+
+fn write_values(w: &mut impl Write, values: &[f64]) -> io::Result<()> {
+    for value in values {
+        write!(w, " {value:e}")?;
+    }
+    writeln!(w)
+}
+
+/// Write `state`, which goes with the first `points` bytes of the points file.
+fn write_state(mut w: impl Write, state: &TransientState, points: u64) -> io::Result<()> {
+    let f = &state.fingerprint;
+    writeln!(w, "{HEADER}")?;
+    writeln!(
+        w,
+        "analysis {:e} {:e} {} {} {} {} {}",
+        f.tstep, f.tstop, f.uic, f.trapezoidal, f.adaptive, f.unknowns, f.width
+    )?;
+    writeln!(
+        w,
+        "time {:e} {} {}",
+        state.time, state.use_device_ic, state.accepted
+    )?;
+    if let Some(step) = state.step {
+        writeln!(w, "step {step:e}")?;
+    }
+    write!(w, "previous")?;
+    write_values(&mut w, &state.previous)?;
+    write!(w, "sample")?;
+    write_values(&mut w, &state.previous_sample)?;
+    for (name, current) in &state.capacitor_currents {
+        writeln!(w, "capacitor {name} {current:e}")?;
+    }
+    for (name, [first, second]) in &state.junction_currents {
+        writeln!(w, "junctions {name} {first:e} {second:e}")?;
+    }
+    for (time, x) in &state.history {
+        write!(w, "history {time:e}")?;
+        write_values(&mut w, x)?;
+    }
+    for (line, history) in state.lines.iter().enumerate() {
+        for (time, [first, second]) in history {
+            writeln!(w, "line {line} {time:e} {first:e} {second:e}")?;
+        }
+    }
+    for (switch, on) in state.switches.iter().enumerate() {
+        writeln!(w, "switch {switch} {on}")?;
+    }
+    for (name, rise) in &state.rises {
+        writeln!(w, "rise {name} {rise:e}")?;
+    }
+    writeln!(w, "points {points}")?;
+    w.flush()
+}
+
+fn write_points(
+    mut w: impl Write,
+    times: &[f64],
+    newton_iterations: &[usize],
+    samples: &[Vec<f64>],
+) -> io::Result<()> {
+    for ((time, iterations), sample) in times.iter().zip(newton_iterations).zip(samples) {
+        write!(w, "point {time:e} {iterations}")?;
+        write_values(&mut w, sample)?;
+    }
+    w.flush()
+}
+
+/// Writes the checkpoints of a transient analysis to `path`: the points output since the
+/// last checkpoint are appended to the points file, then the state replaced at once, so a
+/// crash while writing leaves the previous checkpoint.
+pub(crate) struct Writer {
+    path: PathBuf,
+    /// opened at the first checkpoint, so a run that writes none leaves the old one
+    points: Option<File>,
+    /// the points already in the points file
+    written: usize,
+    /// the length of the points file up to the last checkpoint
+    length: u64,
+}
+
+impl Writer {
+    /// The writer of the checkpoints at `path`, carrying on after the `resumed` points.
+    pub(crate) fn new(path: &Path, resumed: Option<&SavedPoints>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            points: None,
+            written: resumed.map_or(0, |points| points.times.len()),
+            length: resumed.map_or(0, |points| points.length),
+        }
+    }
+
+    /// Write `state` and the points output since the last checkpoint.
+    pub(crate) fn write(
+        &mut self,
+        state: &TransientState,
+        times: &[f64],
+        newton_iterations: &[usize],
+        samples: &[Vec<f64>],
+    ) -> Result<(), SimulationError> {
+        self.append_and_replace(state, times, newton_iterations, samples)
+            .map_err(SimulationError::Checkpoint)
+    }
+
+    fn append_and_replace(
+        &mut self,
+        state: &TransientState,
+        times: &[f64],
+        newton_iterations: &[usize],
+        samples: &[Vec<f64>],
+    ) -> io::Result<()> {
+        let points = match &mut self.points {
+            Some(points) => points,
+            None => {
+                let mut points = OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(points_path(&self.path))?;
+                // drops the points of an earlier run, or those a crash appended past its
+                // last checkpoint
+                points.set_len(self.length)?;
+                points.seek(SeekFrom::Start(self.length))?;
+                self.points.insert(points)
+            }
+        };
+        let from = self.written;
+        write_points(
+            BufWriter::new(&mut *points),
+            &times[from..],
+            &newton_iterations[from..],
+            &samples[from..],
+        )?;
+        self.written = times.len();
+        self.length = points.stream_position()?;
+
+        let mut partial = self.path.as_os_str().to_owned();
+        partial.push(".partial");
+        write_state(BufWriter::new(File::create(&partial)?), state, self.length)?;
+        fs::rename(&partial, &self.path)
+    }
+}
+
+fn value<T: FromStr>(words: &mut SplitWhitespace) -> Result<T, String> {
+    let word = words.next().ok_or("missing value")?;
+    word.parse().map_err(|_| format!("invalid value '{word}'"))
+}
+
+fn values(words: &mut SplitWhitespace) -> Result<Vec<f64>, String> {
+    words
+        .map(|word| word.parse().map_err(|_| format!("invalid value '{word}'")))
+        .collect()
+}
+
+/// The entry `index` of `items`, added with the previous ones if the file skipped them.
+fn entry<T: Default>(items: &mut Vec<T>, index: usize) -> &mut T {
+    if items.len() <= index {
+        items.resize_with(index + 1, T::default);
+    }
+    &mut items[index]
+}
+
+/// The state and the length of the points file it goes with.
+fn parse_state(text: &str) -> Result<(TransientState, u64), String> {
+    let mut lines = text.lines().enumerate();
+    if lines.next().map(|(_, line)| line) != Some(HEADER) {
+        return Err("not a spicy checkpoint".to_string());
+    }
+
+    let mut fingerprint = None;
+    let mut time = None;
+    let mut state = TransientState {
+        fingerprint: Fingerprint {
+            tstep: 0.0,
+            tstop: 0.0,
+            uic: false,
+            trapezoidal: false,
+            adaptive: false,
+            unknowns: 0,
+            width: 0,
+        },
+        time: 0.0,
+        use_device_ic: false,
+        accepted: 0,
+        step: None,
+        previous: Vec::new(),
+        previous_sample: Vec::new(),
+        capacitor_currents: Vec::new(),
+        junction_currents: Vec::new(),
+        history: Vec::new(),
+        lines: Vec::new(),
+        switches: Vec::new(),
+        rises: Vec::new(),
+    };
+    let mut points = None;
+    for (number, line) in lines {
+        let mut words = line.split_whitespace();
+        let mut record = || -> Result<(), String> {
+            match words.next() {
+                Some("analysis") => {
+                    fingerprint = Some(Fingerprint {
+                        tstep: value(&mut words)?,
+                        tstop: value(&mut words)?,
+                        uic: value(&mut words)?,
+                        trapezoidal: value(&mut words)?,
+                        adaptive: value(&mut words)?,
+                        unknowns: value(&mut words)?,
+                        width: value(&mut words)?,
+                    });
+                }
+                Some("time") => {
+                    time = Some((value(&mut words)?, value(&mut words)?, value(&mut words)?));
+                }
+                Some("step") => state.step = Some(value(&mut words)?),
+                Some("previous") => state.previous = values(&mut words)?,
+                Some("sample") => state.previous_sample = values(&mut words)?,
+                Some("capacitor") => {
+                    let name = value(&mut words)?;
+                    state.capacitor_currents.push((name, value(&mut words)?));
+                }
+                Some("junctions") => {
+                    let name = value(&mut words)?;
+                    let currents = [value(&mut words)?, value(&mut words)?];
+                    state.junction_currents.push((name, currents));
+                }
+                Some("history") => {
+                    let time = value(&mut words)?;
+                    state.history.push((time, values(&mut words)?));
+                }
+                Some("line") => {
+                    let line = value(&mut words)?;
+                    let time = value(&mut words)?;
+                    let waves = [value(&mut words)?, value(&mut words)?];
+                    entry(&mut state.lines, line).push((time, waves));
+                }
+                Some("switch") => {
+                    let switch = value(&mut words)?;
+                    *entry(&mut state.switches, switch) = value(&mut words)?;
+                }
+                Some("rise") => {
+                    let name = value(&mut words)?;
+                    state.rises.push((name, value(&mut words)?));
+                }
+                Some("points") => points = Some(value(&mut words)?),
+                Some(keyword) => return Err(format!("unknown record '{keyword}'")),
+                None => {}
+            }
+            Ok(())
+        };
+        record().map_err(|reason| format!("line {}: {reason}", number + 1))?;
+    }
+
+    state.fingerprint = fingerprint.ok_or("missing the analysis")?;
+    (state.time, state.use_device_ic, state.accepted) = time.ok_or("missing the time")?;
+    Ok((state, points.ok_or("missing the points")?))
+}
+
+fn parse_points(text: &str) -> Result<SavedPoints, String> {
+    let mut points = SavedPoints {
+        length: text.len() as u64,
+        ..SavedPoints::default()
+    };
+    for (number, line) in text.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        let mut record = || -> Result<(), String> {
+            match words.next() {
+                Some("point") => {
+                    points.times.push(value(&mut words)?);
+                    points.newton_iterations.push(value(&mut words)?);
+                    points.samples.push(values(&mut words)?);
+                }
+                Some(keyword) => return Err(format!("unknown record '{keyword}'")),
+                None => {}
+            }
+            Ok(())
+        };
+        record().map_err(|reason| format!("points line {}: {reason}", number + 1))?;
+    }
+    Ok(points)
+}
+
+/// Read the checkpoint at `path` and its points, which must belong to the analysis of
+/// `fingerprint`.
+pub(crate) fn read(
+    path: &Path,
+    fingerprint: &Fingerprint,
+) -> Result<(TransientState, SavedPoints), SimulationError> {
+    let text = fs::read_to_string(path).map_err(SimulationError::Checkpoint)?;
+    let invalid = |reason| SimulationError::InvalidCheckpoint {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let (state, length) = parse_state(&text).map_err(invalid)?;
+    if state.fingerprint != *fingerprint {
+        return Err(invalid(
+            "it was saved by another transient analysis".to_string(),
+        ));
+    }
+    // a crash may have appended points past the checkpoint
+    let mut points = fs::read(points_path(path)).map_err(SimulationError::Checkpoint)?;
+    if (points.len() as u64) < length {
+        return Err(invalid("its points file is cut short".to_string()));
+    }
+    points.truncate(length as usize);
+    let points = String::from_utf8(points)
+        .map_err(|_| invalid("its points file is not text".to_string()))?;
+    let points = parse_points(&points).map_err(invalid)?;
+    Ok((state, points))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observer::SimulateObserver;
+    use crate::test_utils::parse_netlist;
+    use crate::trans::simulate_trans;
+    use crate::{
+        CancellationToken, SimulationConfig, TimestepConfig, TransientIntegrator, simulate,
+    };
+    use spicy_parser::netlist_types::Command;
+    use std::ops::ControlFlow;
+    use std::sync::Arc;
+
+    const NETLIST: &str = "rectifier
+V1 in 0 SIN(0 5 1k)
+R1 in a 100
+D1 a out DMOD
+.MODEL DMOD D
+C1 out 0 1u
+L1 out b 10m
+R2 b 0 1k
+.options savecurrents
+.tran 10u 3m
+.end
+";
+
+    fn remove(path: &Path) {
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(points_path(path));
+    }
+
+    /// Cancels the run once it passes `after`, as a user stopping it would.
+    struct StopAfter {
+        token: CancellationToken,
+        after: f64,
+    }
+
+    impl SimulateObserver for StopAfter {
+        fn on_timepoint(&self, time: f64, _solution: &[f64]) -> ControlFlow<()> {
+            if time > self.after {
+                self.token.cancel();
+            }
+            ControlFlow::Continue(())
+        }
+    }
+
+    #[test]
+    fn resumed_transient_matches_an_uninterrupted_one() {
+        let deck = parse_netlist(NETLIST);
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+        let path = std::env::temp_dir().join("spicy_checkpoint_resume");
+        for (integrator, adaptive) in [
+            (TransientIntegrator::BackwardEuler, false),
+            (TransientIntegrator::Trapezoidal, true),
+        ] {
+            let config = SimulationConfig {
+                integrator,
+                timestep: TimestepConfig {
+                    adaptive,
+                    ..TimestepConfig::default()
+                },
+                ..SimulationConfig::default()
+            };
+            let reference = simulate_trans(&deck, tran, &config).expect("uninterrupted");
+
+            let token = CancellationToken::new();
+            let stopped = SimulationConfig {
+                checkpoint: Some(Checkpoint {
+                    path: path.clone(),
+                    every: 7,
+                    resume: false,
+                }),
+                observer: Some(Arc::new(StopAfter {
+                    token: token.clone(),
+                    after: 1.2e-3,
+                })),
+                cancel: token,
+                ..config.clone()
+            };
+            let partial = simulate_trans(&deck, tran, &stopped).expect("stopped");
+            assert!(partial.cancelled);
+
+            let resume = SimulationConfig {
+                checkpoint: Some(Checkpoint {
+                    path: path.clone(),
+                    every: 7,
+                    resume: true,
+                }),
+                ..config
+            };
+            let resumed = simulate_trans(&deck, tran, &resume).expect("resumed");
+            assert!(!resumed.cancelled);
+            assert_eq!(resumed.times, reference.times, "{integrator:?}");
+            assert_eq!(resumed.source_names, reference.source_names);
+            for (a, b) in resumed.samples.iter().zip(&reference.samples) {
+                for (x, y) in a.iter().zip(b) {
+                    assert!((x - y).abs() <= 1e-9 * y.abs().max(1.0), "{x} vs {y}");
+                }
+            }
+        }
+        remove(&path);
+    }
+
+    #[test]
+    fn resume_after_tstart_keeps_the_checkpoint_period() {
+        let deck = parse_netlist(&NETLIST.replace(".tran 10u 3m", ".tran 10u 3m 1m"));
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+        let reference_path = std::env::temp_dir().join("spicy_checkpoint_tstart_reference");
+        let path = std::env::temp_dir().join("spicy_checkpoint_tstart");
+        let checkpointed = |path: &Path, resume| SimulationConfig {
+            checkpoint: Some(Checkpoint {
+                path: path.to_path_buf(),
+                every: 7,
+                resume,
+            }),
+            ..SimulationConfig::default()
+        };
+        let reference =
+            simulate_trans(&deck, tran, &checkpointed(&reference_path, false)).expect("reference");
+        let last_checkpoint =
+            |path: &Path| parse_state(&fs::read_to_string(path).unwrap()).unwrap();
+        let (reference_state, _) = last_checkpoint(&reference_path);
+
+        // stopped before tstart, with no point output yet, and after it
+        for after in [0.5e-3, 1.2e-3] {
+            let token = CancellationToken::new();
+            let stopped = SimulationConfig {
+                observer: Some(Arc::new(StopAfter {
+                    token: token.clone(),
+                    after,
+                })),
+                cancel: token,
+                ..checkpointed(&path, false)
+            };
+            assert!(
+                simulate_trans(&deck, tran, &stopped)
+                    .expect("stopped")
+                    .cancelled
+            );
+            let resumed = simulate_trans(&deck, tran, &checkpointed(&path, true)).expect("resumed");
+
+            assert_eq!(resumed.times, reference.times, "stopped after {after}");
+            for (a, b) in resumed.samples.iter().zip(&reference.samples) {
+                for (x, y) in a.iter().zip(b) {
+                    assert!((x - y).abs() <= 1e-9 * y.abs().max(1.0), "{x} vs {y}");
+                }
+            }
+            // the resumed run saves at the same points as the uninterrupted one
+            let (state, _) = last_checkpoint(&path);
+            assert_eq!(
+                state.accepted, reference_state.accepted,
+                "stopped after {after}"
+            );
+            assert_eq!(state.time, reference_state.time);
+        }
+        remove(&path);
+        remove(&reference_path);
+    }
+
+    #[test]
+    fn checkpoint_of_another_analysis_is_rejected() {
+        let path = std::env::temp_dir().join("spicy_checkpoint_other");
+        let checkpoint = |resume| Checkpoint {
+            path: path.clone(),
+            every: 10,
+            resume,
+        };
+        let run = |netlist: &str, resume| {
+            let deck = parse_netlist(netlist);
+            let Some(Command::Tran(tran)) = deck.commands.first() else {
+                panic!("expected .tran");
+            };
+            let config = SimulationConfig {
+                checkpoint: Some(checkpoint(resume)),
+                ..SimulationConfig::default()
+            };
+            simulate_trans(&deck, tran, &config)
+        };
+        run(NETLIST, false).expect("checkpointed");
+        let other = NETLIST.replace(".tran 10u 3m", ".tran 10u 4m");
+        let err = run(&other, true).unwrap_err();
+        assert!(
+            matches!(&err, SimulationError::InvalidCheckpoint { reason, .. }
+                if reason == "it was saved by another transient analysis"),
+            "{err}"
+        );
+        remove(&path);
+
+        let err = run(NETLIST, true).unwrap_err();
+        assert!(matches!(err, SimulationError::Checkpoint(_)), "{err}");
+
+        // several transients would overwrite each other's checkpoints
+        let swept = NETLIST.replace(".end", ".temp 27 50\n.end");
+        let config = SimulationConfig {
+            checkpoint: Some(checkpoint(false)),
+            ..SimulationConfig::default()
+        };
+        let err = simulate(parse_netlist(&swept), config).unwrap_err();
+        assert!(matches!(
+            err,
+            SimulationError::CheckpointNeedsSingleTransient
+        ));
+    }
+
+    #[test]
+    fn state_reads_back_exactly() {
+        let state = TransientState {
+            fingerprint: Fingerprint {
+                tstep: 1e-6,
+                tstop: 1e-3,
+                uic: false,
+                trapezoidal: true,
+                adaptive: true,
+                unknowns: 3,
+                width: 4,
+            },
+            time: 1.0 / 3.0 * 1e-3,
+            use_device_ic: false,
+            accepted: 5,
+            step: Some(2.5e-7),
+            previous: vec![1.0, 0.1 + 0.2, -1e-300],
+            previous_sample: vec![1.0, 0.1 + 0.2, -1e-300, f64::NAN],
+            capacitor_currents: vec![("C1".to_string(), 1e-3 / 7.0)],
+            junction_currents: vec![("Q1".to_string(), [1e-12, -2e-12])],
+            history: vec![(0.0, vec![0.0; 3]), (1e-4, vec![1.0, 2.0, 3.0])],
+            lines: vec![vec![(0.0, [1.0, 2.0])], vec![]],
+            switches: vec![true, false],
+            rises: vec![("R1".to_string(), 12.5)],
+        };
+        let times = [0.0, 1.0 / 3.0 * 1e-3];
+        let newton_iterations = [0, 4];
+        let samples = [vec![0.0; 4], state.previous_sample.clone()];
+
+        let mut text = Vec::new();
+        write_points(&mut text, &times, &newton_iterations, &samples).unwrap();
+        let points = parse_points(&String::from_utf8(text).unwrap()).unwrap();
+        let mut text = Vec::new();
+        write_state(&mut text, &state, points.length).unwrap();
+        let (read, length) = parse_state(&String::from_utf8(text).unwrap()).unwrap();
+        assert_eq!(length, points.length);
+
+        // NaN != NaN: compare the bits of the sample
+        let bits = |x: &[f64]| x.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&read.previous_sample), bits(&state.previous_sample));
+        assert_eq!(
+            TransientState {
+                previous_sample: Vec::new(),
+                // a line without points is not written
+                lines: vec![state.lines[0].clone()],
+                ..state.clone()
+            },
+            TransientState {
+                previous_sample: Vec::new(),
+                ..read
+            }
+        );
+        assert_eq!(points.times, times);
+        assert_eq!(points.newton_iterations, newton_iterations);
+        assert_eq!(bits(&points.samples[1]), bits(&samples[1]));
+    }
+
+    #[test]
+    fn malformed_checkpoints_are_rejected() {
+        assert_eq!(
+            parse_state("spicy-checkpoint 1\n").unwrap_err(),
+            "not a spicy checkpoint"
+        );
+        let err = parse_state(&format!("{HEADER}\nanalysis 1e-6 1e-3 false\n")).unwrap_err();
+        assert_eq!(err, "line 2: missing value");
+        let err = parse_state(&format!("{HEADER}\nstep 1e-6\n")).unwrap_err();
+        assert_eq!(err, "missing the analysis");
+        let err = parse_state(&format!("{HEADER}\nprevious 1 x\n")).unwrap_err();
+        assert_eq!(err, "line 2: invalid value 'x'");
+        let err = parse_points("point 0 0 1\npoint 1e-6\n").unwrap_err();
+        assert_eq!(err, "points line 2: missing value");
+    }
+
+    #[test]
+    fn points_are_appended_once_and_a_torn_append_is_dropped() {
+        let deck = parse_netlist(NETLIST);
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+        let path = std::env::temp_dir().join("spicy_checkpoint_points");
+        let checkpointed = |resume| SimulationConfig {
+            checkpoint: Some(Checkpoint {
+                path: path.clone(),
+                every: 7,
+                resume,
+            }),
+            ..SimulationConfig::default()
+        };
+        let reference =
+            simulate_trans(&deck, tran, &SimulationConfig::default()).expect("reference");
+        let token = CancellationToken::new();
+        let stopped = SimulationConfig {
+            observer: Some(Arc::new(StopAfter {
+                token: token.clone(),
+                after: 1.2e-3,
+            })),
+            cancel: token,
+            ..checkpointed(false)
+        };
+        assert!(
+            simulate_trans(&deck, tran, &stopped)
+                .expect("stopped")
+                .cancelled
+        );
+
+        // every point is in the points file once, up to the checkpoint
+        let points = fs::read_to_string(points_path(&path)).unwrap();
+        let (state, length) = parse_state(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(length, points.len() as u64);
+        let saved = parse_points(&points).unwrap();
+        assert_eq!(saved.times, reference.times[..saved.times.len()]);
+        assert_eq!(saved.times.last(), Some(&state.time));
+
+        // and a crash in the middle of appending the next points
+        let mut torn = OpenOptions::new()
+            .append(true)
+            .open(points_path(&path))
+            .unwrap();
+        write!(torn, "point 1e-3 3 0.5").unwrap();
+        drop(torn);
+        let resumed = simulate_trans(&deck, tran, &checkpointed(true)).expect("resumed");
+        assert_eq!(resumed.times, reference.times);
+        // the resumed run appends past the checkpoint, over the torn point
+        let saved = parse_points(&fs::read_to_string(points_path(&path)).unwrap()).unwrap();
+        assert!(saved.times.len() > reference.times.len() / 2);
+        assert_eq!(saved.times, reference.times[..saved.times.len()]);
+        remove(&path);
+    }
+}
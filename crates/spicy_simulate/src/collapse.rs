@@ -0,0 +1,237 @@
+//! Inductors of a DC analysis collapsed into shorts.
+//!
+//! An inductor is a short at DC, which MNA writes as a branch current unknown and a KVL row
+//! `V(pos) - V(neg) = 0`. Merging its two nodes into one unknown instead drops two rows from
+//! the matrix per inductor. The inductor currents are then recovered after the solve from KCL
+//! at the nodes they connect: the collapsed inductors form trees of shorts, and the current of
+//! each one is what the rest of the circuit sends into the part of its tree beyond it.
+
+use spicy_parser::{
+    devices::BehavioralExpr,
+    netlist_types::{CurrentBranchIndex, NodeIndex},
+    node_mapping::NodeMapping,
+};
+
+use crate::{
+    SimulationConfig,
+    dc::stamp_dc,
+    devices::{Devices, switch::Control},
+    error::SimulationError,
+    matrix::SolverMatrix,
+};
+
+/// The inductors of a deck collapsed into shorts, and the unknowns left without them.
+#[derive(Debug, Clone)]
+pub(crate) struct CollapsedInductors {
+    /// The unknowns the matrix is built on.
+    pub node_mapping: NodeMapping,
+    /// The unknowns of the deck, which the results are reported in.
+    full: NodeMapping,
+    /// Every collapsed inductor (its index in `Devices::inductors`) with its node away from the
+    /// root of its tree, each one after the inductors on the path from the root to it.
+    tree: Vec<(usize, NodeIndex)>,
+}
+
+impl CollapsedInductors {
+    /// Collapse the inductors of `devices` that nothing else needs the branch current of, or
+    /// `None` if there are none.
+    ///
+    /// Coupled inductors and the ones read by a current-controlled switch or a B source keep
+    /// their branch, as does an inductor closing a loop of shorts (whose current is not set by
+    /// KCL), and every inductor of a deck with plugin devices, which may read any branch.
+    pub fn new(devices: &Devices, node_mapping: &NodeMapping) -> Option<Self> {
+        if !devices.plugins.is_empty() {
+            return None;
+        }
+        let mut read = Vec::new();
+        for k in &devices.mutual_inductances {
+            let (l1, l2) = k.pair(&devices.inductors);
+            read.extend([l1.current_branch, l2.current_branch]);
+        }
+        for s in &devices.switches {
+            if let Control::Current { branch } = s.control {
+                read.push(branch);
+            }
+        }
+        for b in &devices.behavioral_sources {
+            read_branches(&b.expr, &mut read);
+        }
+
+        // union-find over the nodes, whose groups are the trees of shorts
+        let nodes = node_mapping.nodes_len() + 1;
+        let mut group: Vec<usize> = (0..nodes).collect();
+        fn root(group: &mut [usize], mut node: usize) -> usize {
+            while group[node] != node {
+                group[node] = group[group[node]];
+                node = group[node];
+            }
+            node
+        }
+        let mut edges = Vec::new();
+        for (k, l) in devices.inductors.iter().enumerate() {
+            if read.contains(&l.current_branch) {
+                continue;
+            }
+            let (a, b) = (
+                root(&mut group, l.positive.0),
+                root(&mut group, l.negative.0),
+            );
+            if a != b {
+                // ground, the lowest node, stays the root of its tree
+                group[a.max(b)] = a.min(b);
+                edges.push(k);
+            }
+        }
+        if edges.is_empty() {
+            return None;
+        }
+
+        // walk every tree down from its root, the node of the group it was merged into
+        let mut adjacent = vec![Vec::new(); nodes];
+        for &k in &edges {
+            let l = &devices.inductors[k];
+            adjacent[l.positive.0].push((k, l.negative));
+            adjacent[l.negative.0].push((k, l.positive));
+        }
+        let mut tree = Vec::with_capacity(edges.len());
+        let mut reached = vec![false; nodes];
+        for start in 0..nodes {
+            if root(&mut group, start) != start {
+                continue;
+            }
+            reached[start] = true;
+            let mut frontier = vec![NodeIndex(start)];
+            while let Some(node) = frontier.pop() {
+                for &(k, next) in &adjacent[node.0] {
+                    if !reached[next.0] {
+                        reached[next.0] = true;
+                        tree.push((k, next));
+                        frontier.push(next);
+                    }
+                }
+            }
+        }
+
+        let shorts: Vec<(NodeIndex, NodeIndex)> = edges
+            .iter()
+            .map(|&k| (devices.inductors[k].positive, devices.inductors[k].negative))
+            .collect();
+        let dropped: Vec<CurrentBranchIndex> = edges
+            .iter()
+            .map(|&k| devices.inductors[k].current_branch)
+            .collect();
+        Some(Self {
+            node_mapping: node_mapping.merge_nodes(&shorts, &dropped),
+            full: node_mapping.clone(),
+            tree,
+        })
+    }
+
+    /// The unknowns `x` of the deck, e.g. a starting guess, as the unknowns of the matrix.
+    pub fn restrict(&self, x: &[f64]) -> Vec<f64> {
+        let mut restricted = vec![0.0; self.node_mapping.mna_matrix_dim()];
+        self.each_unknown(|full, merged| {
+            if let Some(merged) = merged {
+                restricted[merged] = x[full];
+            }
+        });
+        restricted
+    }
+
+    /// The matrix of the deck's own unknowns, which [`CollapsedInductors::expand`] recovers the
+    /// inductor currents with. Built once per analysis; it sets the stamps of `devices` up for
+    /// it, so it comes before the matrix that is solved.
+    pub fn full_matrix(
+        &self,
+        devices: &mut Devices,
+        config: &SimulationConfig,
+    ) -> Result<SolverMatrix, SimulationError> {
+        SolverMatrix::create_matrix(devices, self.full.clone(), config)
+    }
+
+    /// The unknowns of the deck at the solution `x` of the matrix `m`, with the currents of the
+    /// collapsed inductors.
+    ///
+    /// The currents come from the residual of `full`, the [`CollapsedInductors::full_matrix`],
+    /// stamped at the solution, which points the stamps of `devices` at it; they are pointed
+    /// back at `m` afterwards.
+    pub fn expand(
+        &self,
+        m: &SolverMatrix,
+        full: &mut SolverMatrix,
+        devices: &mut Devices,
+        x: &[f64],
+    ) -> Result<Vec<f64>, SimulationError> {
+        let mut expanded = vec![0.0; self.full.mna_matrix_dim()];
+        self.each_unknown(|full, merged| {
+            if let Some(merged) = merged {
+                expanded[full] = x[merged];
+            }
+        });
+
+        full.setup_device_stamps(devices)?;
+        full.clear();
+        stamp_dc(full, devices, &expanded)?;
+        let residual = full.residual(&expanded);
+        m.setup_device_stamps(devices)?;
+
+        // the current each node sends into the circuit but for the collapsed inductors, which
+        // carry it to the root of their tree; ground has no KCL row
+        let mut leaving: Vec<f64> = (0..=self.full.nodes_len())
+            .map(|node| {
+                self.full
+                    .mna_node_index(NodeIndex(node))
+                    .map_or(0.0, |i| residual[i])
+            })
+            .collect();
+        for &(k, node) in self.tree.iter().rev() {
+            let l = &devices.inductors[k];
+            // the inductor current leaves its positive node
+            let (sign, other) = if l.positive == node {
+                (1.0, l.negative)
+            } else {
+                (-1.0, l.positive)
+            };
+            let current = -sign * leaving[node.0];
+            leaving[other.0] -= sign * current;
+            expanded[self.full.mna_branch_index(l.current_branch)] = current;
+        }
+        Ok(expanded)
+    }
+
+    /// Call `f` with the index of every node voltage and kept branch current among the
+    /// unknowns of the deck, and among the unknowns of the matrix.
+    fn each_unknown(&self, mut f: impl FnMut(usize, Option<usize>)) {
+        for node in 1..=self.full.nodes_len() {
+            let node = NodeIndex(node);
+            if let Some(full) = self.full.mna_node_index(node) {
+                f(full, self.node_mapping.mna_node_index(node));
+            }
+        }
+        for branch in 1..=self.full.branches_len() {
+            let branch = CurrentBranchIndex(branch);
+            f(
+                self.full.mna_branch_index(branch),
+                self.node_mapping.try_mna_branch_index(branch),
+            );
+        }
+    }
+}
+
+/// Add the branch of every `I(device)` of `expr` to `read`.
+fn read_branches(expr: &BehavioralExpr, read: &mut Vec<CurrentBranchIndex>) {
+    match expr {
+        BehavioralExpr::Constant(_) | BehavioralExpr::Voltage(_) | BehavioralExpr::Time => {}
+        BehavioralExpr::Current { branch, .. } => read.push(*branch),
+        BehavioralExpr::Negate(operand) => read_branches(operand, read),
+        BehavioralExpr::Binary { left, right, .. } => {
+            read_branches(left, read);
+            read_branches(right, read);
+        }
+        BehavioralExpr::Call { args, .. } => {
+            for arg in args {
+                read_branches(arg, read);
+            }
+        }
+    }
+}
@@ -1,24 +1,44 @@
 use spicy_parser::{
-    Value, instance_parser::Deck, netlist_types::DcCommand, netlist_waveform::WaveForm,
+    Value,
+    instance_parser::Deck,
+    netlist_types::{DcCommand, NodeValue},
+    netlist_waveform::WaveForm,
+    node_mapping::NodeMapping,
 };
 
 use crate::{
-    NewtonMode, NewtonState, SimulationConfig, devices::Devices, error::SimulationError,
-    matrix::SolverMatrix, trans::newton_solve,
+    NewtonMode, NewtonState, SimulationConfig,
+    collapse::CollapsedInductors,
+    devices::{Devices, plugin::Analysis},
+    error::SimulationError,
+    matrix::{SolverMatrix, SolverStats},
+    observer::{self, ProgressClock},
+    output::device_current_names,
+    trans::newton_solve,
+    warnings::{SimulationWarning, Warnings},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OperatingPointResult {
     pub voltages: Vec<(String, f64)>,
     pub currents: Vec<(String, f64)>,
+    /// non-fatal problems hit while solving this point
+    pub warnings: Vec<SimulationWarning>,
+    /// the linear solves of this point
+    pub solver_stats: SolverStats,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DcSweepResult {
+    /// Every point with its value of the first source, curve after curve.
     pub results: Vec<(OperatingPointResult, f64)>,
+    /// Value of the second source for each curve of a nested sweep; empty otherwise.
+    pub outer_values: Vec<f64>,
+    /// The sweep was cancelled and `results` stops at the last solved point.
+    pub cancelled: bool,
 }
 
-fn stamp_dc(
+pub(crate) fn stamp_dc(
     matrix: &mut SolverMatrix,
     devices: &Devices,
     guess: &[f64],
@@ -32,10 +52,22 @@ fn stamp_dc(
         d.stamp_nonlinear(matrix, guess);
     }
 
+    for t in &devices.lookup_tables {
+        t.stamp_nonlinear(matrix, guess);
+    }
+
     for bjt in &devices.bjts {
         bjt.stamp_nonlinear(matrix, guess);
     }
 
+    for mosfet in &devices.mosfets {
+        mosfet.stamp_nonlinear(matrix, guess);
+    }
+
+    for jfet in &devices.jfets {
+        jfet.stamp_nonlinear(matrix, guess);
+    }
+
     for i in &devices.inductors {
         i.stamp_dc(matrix);
     }
@@ -48,6 +80,22 @@ fn stamp_dc(
         c.stamp_current_source_dc(matrix);
     }
 
+    for b in &devices.behavioral_sources {
+        b.stamp_nonlinear(matrix, guess, 0.0);
+    }
+
+    for t in &devices.transmission_lines {
+        t.stamp_dc(matrix);
+    }
+
+    for s in &devices.switches {
+        s.stamp_nonlinear(matrix, guess);
+    }
+
+    for p in &devices.plugins {
+        p.load(matrix, guess, Analysis::Dc);
+    }
+
     Ok(())
 }
 
@@ -86,19 +134,150 @@ fn set_sweep_value(devices: &mut Devices, target: SweepTarget, value: f64) {
     }
 }
 
+/// Conductance tying a node to its `.ic` value while the initial operating point of a
+/// transient is solved.
+const IC_CONDUCTANCE: f64 = 1e10;
+
+/// The `.nodeset` and `.ic` cards of a deck, as MNA node index and volts.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NodeConditions {
+    pub nodesets: Vec<(usize, f64)>,
+    pub initial: Vec<(usize, f64)>,
+}
+
+impl NodeConditions {
+    pub fn from_deck(deck: &Deck) -> Self {
+        let node_names = deck.node_mapping.node_names_mna_order();
+        // the parser only keeps nodes of the deck
+        let indexed = |values: &[NodeValue]| {
+            values
+                .iter()
+                .map(|v| {
+                    let index = node_names.iter().position(|n| *n == v.node);
+                    (index.expect("known node"), v.value.get_value())
+                })
+                .collect()
+        };
+        Self {
+            nodesets: indexed(&deck.nodesets),
+            initial: indexed(&deck.initial_conditions),
+        }
+    }
+
+    /// All zeros but the `.nodeset` values, the starting guess of an operating point.
+    pub fn guess(&self, dim: usize) -> Vec<f64> {
+        let mut guess = vec![0.0; dim];
+        for &(index, value) in &self.nodesets {
+            guess[index] = value;
+        }
+        guess
+    }
+}
+
+/// First gmin shunted from every node to ground when plain Newton fails at an operating point.
+const GMIN_START: f64 = 1e-2;
+/// Last gmin before the final solve without any shunt.
+const GMIN_STOP: f64 = 1e-12;
+
+/// Solve a DC operating point starting from `guess`, falling back to gmin stepping if plain
+/// Newton does not converge. The nodes of `forced` are held at their value (`.ic`), and the
+/// self-heating devices at the steady state of their thermal RC.
+/// Returns the solution and the Newton iterations of the last solve.
+pub(crate) fn solve_dc_point(
+    m: &mut SolverMatrix,
+    devices: &Devices,
+    state: &mut NewtonState,
+    guess: Vec<f64>,
+    forced: &[(usize, f64)],
+    warnings: &mut Warnings,
+) -> Result<(Vec<f64>, usize), SimulationError> {
+    let solved = match newton_solve(m, state, guess.clone(), None, |matrix, guess| {
+        stamp_forced_dc(matrix, devices, guess, forced)
+    }) {
+        Ok(solved) => solved,
+        Err(SimulationError::NonConvergence { unknown, .. }) => {
+            warnings.push(SimulationWarning::NotConverged {
+                unknown,
+                time: None,
+            });
+            let (solved, steps) = gmin_stepping(m, devices, state, guess, forced)?;
+            warnings.push(SimulationWarning::GminStepping {
+                gmin: GMIN_START,
+                steps,
+            });
+            solved
+        }
+        Err(e) => return Err(e),
+    };
+    let solved = devices.solve_heated(m, solved, None, None, |m, guess| {
+        newton_solve(m, state, guess, None, |matrix, guess| {
+            stamp_forced_dc(matrix, devices, guess, forced)
+        })
+    })?;
+    devices.accept_heat();
+    warnings.check_matrix(m, None);
+    for s in &devices.switches {
+        s.update_state(m.node_mapping(), &solved.0);
+    }
+    Ok(solved)
+}
+
+fn stamp_forced_dc(
+    matrix: &mut SolverMatrix,
+    devices: &Devices,
+    guess: &[f64],
+    forced: &[(usize, f64)],
+) -> Result<(), SimulationError> {
+    stamp_dc(matrix, devices, guess)?;
+    for &(node, value) in forced {
+        matrix.add_node_source(node, IC_CONDUCTANCE, value);
+    }
+    Ok(())
+}
+
+/// Walk gmin down a decade at a time from `GMIN_START`, using each solution as the next
+/// initial guess, then solve once more without gmin. Returns the final solution with its
+/// Newton iterations, and the solve count.
+fn gmin_stepping(
+    m: &mut SolverMatrix,
+    devices: &Devices,
+    state: &mut NewtonState,
+    mut guess: Vec<f64>,
+    forced: &[(usize, f64)],
+) -> Result<((Vec<f64>, usize), usize), SimulationError> {
+    let mut steps = 0;
+    let mut gmin = GMIN_START;
+    // only the final solve without the shunts is worth dumping
+    let dump = state.dump.take();
+    while gmin >= GMIN_STOP {
+        // pivots can change a lot between gmin values, so always start with a full factorization
+        state.mode = NewtonMode::InitOp;
+        let (solution, _iters) = newton_solve(m, state, guess, None, |matrix, guess| {
+            stamp_forced_dc(matrix, devices, guess, forced)?;
+            matrix.add_node_conductance(gmin);
+            Ok(())
+        })?;
+        guess = solution;
+        steps += 1;
+        gmin /= 10.0;
+    }
+
+    state.mode = NewtonMode::InitOp;
+    state.dump = dump;
+    let solved = newton_solve(m, state, guess, None, |matrix, guess| {
+        stamp_forced_dc(matrix, devices, guess, forced)
+    })?;
+    Ok((solved, steps + 1))
+}
+
 pub(crate) fn simulate_op_inner(
     m: &mut SolverMatrix,
     devices: &Devices,
     state: &mut NewtonState,
+    guess: Vec<f64>,
+    warnings: &mut Warnings,
 ) -> Result<(), SimulationError> {
-    let initial_guess = vec![0.0; m.rhs().len()];
-    let _ = newton_solve(
-        m,
-        state,
-        initial_guess,
-        None,
-        |matrix, guess| stamp_dc(matrix, devices, guess),
-    )?;
+    solve_dc_point(m, devices, state, guess, &[], warnings)?;
 
     Ok(())
 }
@@ -107,17 +286,62 @@ pub fn simulate_op(
     deck: &Deck,
     sim_config: &SimulationConfig,
 ) -> Result<OperatingPointResult, SimulationError> {
-    let mut devices = Devices::from_spec(&deck.devices);
-
-    let mut matrix =
-        SolverMatrix::create_matrix(&mut devices, deck.node_mapping.clone(), sim_config)?;
+    let mut devices = Devices::from_deck(deck, sim_config);
+    let mut collapsed = collapse_inductors(&mut devices, deck, sim_config)?;
+    let node_mapping = collapsed
+        .as_ref()
+        .map_or(&deck.node_mapping, |(c, _)| &c.node_mapping);
+
+    let mut matrix = SolverMatrix::create_matrix(&mut devices, node_mapping.clone(), sim_config)?;
+
+    let mut state = NewtonState::new(sim_config.newton, NewtonMode::InitOp)
+        .with_cancel(&sim_config.cancel)
+        .with_dump(&sim_config.dump_matrix);
+    let mut warnings = Warnings::default();
+    let guess = NodeConditions::from_deck(deck).guess(deck.node_mapping.mna_matrix_dim());
+    let guess = match &collapsed {
+        Some((collapsed, _)) => collapsed.restrict(&guess),
+        None => guess,
+    };
+    simulate_op_inner(&mut matrix, &devices, &mut state, guess, &mut warnings)?;
+
+    let solver_stats = matrix.take_stats()?;
+    let x = match &mut collapsed {
+        Some((collapsed, full)) => collapsed.expand(&matrix, full, &mut devices, matrix.rhs())?,
+        None => matrix.rhs().to_vec(),
+    };
+    let mut op = operating_point_result(&deck.node_mapping, &x, warnings.into_vec(), solver_stats);
+    let device_names = device_current_names(deck);
+    push_device_currents(&mut op, &device_names, &devices, &deck.node_mapping, &x);
+    Ok(op)
+}
 
-    let mut state = NewtonState::new(sim_config.newton, NewtonMode::InitOp);
-    simulate_op_inner(&mut matrix, &devices, &mut state)?;
+/// The inductors to collapse into shorts when `sim_config` asks for it, with the full matrix
+/// their currents are recovered with.
+fn collapse_inductors(
+    devices: &mut Devices,
+    deck: &Deck,
+    sim_config: &SimulationConfig,
+) -> Result<Option<(CollapsedInductors, SolverMatrix)>, SimulationError> {
+    if !sim_config.collapse_dc_inductors {
+        return Ok(None);
+    }
+    let Some(collapsed) = CollapsedInductors::new(devices, &deck.node_mapping) else {
+        return Ok(None);
+    };
+    let full = collapsed.full_matrix(devices, sim_config)?;
+    Ok(Some((collapsed, full)))
+}
 
-    let x = matrix.rhs();
-    let node_names = deck.node_mapping.node_names_mna_order();
-    let branch_names = deck.node_mapping.branch_names_mna_order();
+/// Name the node voltages and branch currents of the MNA solution `x`.
+pub(crate) fn operating_point_result(
+    node_mapping: &NodeMapping,
+    x: &[f64],
+    warnings: Vec<SimulationWarning>,
+    solver_stats: SolverStats,
+) -> OperatingPointResult {
+    let node_names = node_mapping.node_names_mna_order();
+    let branch_names = node_mapping.branch_names_mna_order();
     let n = node_names.len();
 
     let mut voltages = Vec::with_capacity(n);
@@ -130,10 +354,31 @@ pub fn simulate_op(
         currents.push((name, x[n + i]));
     }
 
-    Ok(OperatingPointResult { voltages, currents })
+    OperatingPointResult {
+        voltages,
+        currents,
+        warnings,
+        solver_stats,
+    }
 }
 
-fn sweep(vstart: f64, vstop: f64, vinc: f64) -> Vec<f64> {
+/// Add the currents of the devices `names` (see [`device_current_names`]) at the DC solution
+/// `x` after the branch currents of `op`. Capacitors are open at DC.
+pub(crate) fn push_device_currents(
+    op: &mut OperatingPointResult,
+    names: &[String],
+    devices: &Devices,
+    node_mapping: &NodeMapping,
+    x: &[f64],
+) {
+    if names.is_empty() {
+        return;
+    }
+    let currents = devices.device_currents(node_mapping, x, |_| 0.0);
+    op.currents.extend(names.iter().cloned().zip(currents));
+}
+
+pub(crate) fn sweep(vstart: f64, vstop: f64, vinc: f64) -> Vec<f64> {
     let nsteps = ((vstop - vstart) / vinc).floor() as usize;
     (0..=nsteps).map(|i| vstart + i as f64 * vinc).collect()
 }
@@ -142,51 +387,236 @@ pub fn simulate_dc(
     deck: &Deck,
     command: &DcCommand,
     sim_config: &SimulationConfig,
-) -> DcSweepResult {
+) -> Result<DcSweepResult, SimulationError> {
     let srcnam = &command.srcnam;
     let vstart = command.vstart.get_value();
     let vstop = command.vstop.get_value();
     let vincr = command.vincr.get_value();
 
-    let mut devices = Devices::from_spec(&deck.devices);
+    let mut devices = Devices::from_deck(deck, sim_config);
+    // the full matrix of collapsed inductors is built once for the whole sweep
+    let mut collapsed = collapse_inductors(&mut devices, deck, sim_config)
+        .expect("Failed to create the full matrix");
+    let node_mapping = collapsed
+        .as_ref()
+        .map_or(&deck.node_mapping, |(c, _)| &c.node_mapping);
 
     // Matrix pattern setup stores nnz indices into the compiled devices.
-    let mut matrix =
-        SolverMatrix::create_matrix(&mut devices, deck.node_mapping.clone(), sim_config)
-            .expect("Failed to create matrix");
+    let mut matrix = SolverMatrix::create_matrix(&mut devices, node_mapping.clone(), sim_config)
+        .expect("Failed to create matrix");
 
     let sweep_target = find_sweep_target(&devices, srcnam);
     let sweep_values = sweep(vstart, vstop, vincr);
+    let outer = command.src2.as_ref().map(|src2| {
+        let values = sweep(
+            src2.vstart.get_value(),
+            src2.vstop.get_value(),
+            src2.vincr.get_value(),
+        );
+        (find_sweep_target(&devices, &src2.srcnam), values)
+    });
     let node_names = deck.node_mapping.node_names_mna_order();
     let branch_names = deck.node_mapping.branch_names_mna_order();
+    let device_names = device_current_names(deck);
     let n = node_names.len();
 
     let mut results = Vec::new();
-    let mut guess = vec![0.0; matrix.rhs().len()];
-    for v in sweep_values {
-        set_sweep_value(&mut devices, sweep_target, v);
-        let mut state = NewtonState::new(sim_config.newton, NewtonMode::InitOp);
-        let (solution, _iters) = newton_solve(
-            &mut matrix,
-            &mut state,
-            guess,
-            None,
-            |matrix, guess| stamp_dc(matrix, &devices, guess),
-        )
-        .expect("simulate_dc newton solve");
-
-        let mut voltages = Vec::with_capacity(node_names.len());
-        let mut currents = Vec::with_capacity(branch_names.len());
-        for (i, name) in node_names.iter().enumerate() {
-            voltages.push((name.clone(), solution[i]));
+    let mut cancelled = false;
+    let mut guess = NodeConditions::from_deck(deck).guess(deck.node_mapping.mna_matrix_dim());
+    if let Some((collapsed, _)) = &collapsed {
+        guess = collapsed.restrict(&guess);
+    }
+    // a single sweep is one curve without an outer value
+    let curves: Vec<Option<f64>> = match &outer {
+        Some((_, values)) => values.iter().copied().map(Some).collect(),
+        None => vec![None],
+    };
+    let progress = ProgressClock::new("dc", curves.len() * sweep_values.len());
+    'sweep: for outer_value in curves {
+        if let (Some((target, _)), Some(value)) = (&outer, outer_value) {
+            set_sweep_value(&mut devices, *target, value);
         }
-        for (i, name) in branch_names.iter().enumerate() {
-            currents.push((name.clone(), solution[n + i]));
+        for &v in &sweep_values {
+            set_sweep_value(&mut devices, sweep_target, v);
+            let mut state = NewtonState::new(sim_config.newton, NewtonMode::InitOp)
+                .with_cancel(&sim_config.cancel)
+                .with_dump(&sim_config.dump_matrix);
+            let mut warnings = Warnings::default();
+            let (solution, _iters) = match solve_dc_point(
+                &mut matrix,
+                &devices,
+                &mut state,
+                guess,
+                &[],
+                &mut warnings,
+            ) {
+                Err(SimulationError::Cancelled) => {
+                    cancelled = true;
+                    break 'sweep;
+                }
+                solved => solved.expect("simulate_dc newton solve"),
+            };
+            let x = match &mut collapsed {
+                Some((collapsed, full)) => collapsed
+                    .expand(&matrix, full, &mut devices, &solution)
+                    .expect("simulate_dc inductor currents"),
+                None => solution.clone(),
+            };
+
+            let mut voltages = Vec::with_capacity(node_names.len());
+            let mut currents = Vec::with_capacity(branch_names.len());
+            for (i, name) in node_names.iter().enumerate() {
+                voltages.push((name.clone(), x[i]));
+            }
+            for (i, name) in branch_names.iter().enumerate() {
+                currents.push((name.clone(), x[n + i]));
+            }
+
+            let mut op = OperatingPointResult {
+                voltages,
+                currents,
+                warnings: warnings.into_vec(),
+                solver_stats: matrix.take_stats().expect("simulate_dc solver stats"),
+            };
+            push_device_currents(&mut op, &device_names, &devices, &deck.node_mapping, &x);
+            if let Some(observer) = &sim_config.observer {
+                observer::check(observer.on_sweep_point(v, &op))?;
+                progress.publish(observer.as_ref(), results.len() + 1);
+            }
+            results.push((op, v));
+            guess = solution;
         }
+    }
 
-        results.push((OperatingPointResult { voltages, currents }, v));
-        guess = solution;
+    Ok(DcSweepResult {
+        results,
+        outer_values: outer.map(|(_, values)| values).unwrap_or_default(),
+        cancelled,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spicy_parser::netlist_types::Command;
+    use spicy_parser::{ParseOptions, parse};
+
+    #[test]
+    fn nested_sweep_produces_a_curve_per_outer_value() {
+        let netlist = "divider\nV1 in 0 1\nV2 ref 0 0\nR1 in out 1k\nR2 out ref 1k\n\
+                       .dc V1 0 2 1 V2 0 1 1\n.end\n";
+        let mut options = ParseOptions::new_with_source("dc.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let Some(Command::Dc(dc)) = deck.commands.first() else {
+            panic!("expected .dc");
+        };
+        assert_eq!(dc.src2.as_ref().map(|s| s.srcnam.as_str()), Some("V2"));
+
+        let result = simulate_dc(&deck, dc, &SimulationConfig::default()).expect("dc");
+        assert_eq!(result.outer_values, vec![0.0, 1.0]);
+        assert_eq!(result.sweep_values(), vec![0.0, 1.0, 2.0, 0.0, 1.0, 2.0]);
+        for (index, expected) in [[0.0, 0.5, 1.0], [0.5, 1.0, 1.5]].iter().enumerate() {
+            let out = result.curve(index).unwrap().voltage("out").unwrap().y;
+            for (v, e) in out.iter().zip(expected) {
+                assert!((v - e).abs() < 1e-9, "{out:?} vs {expected:?}");
+            }
+        }
+    }
+
+    // L1-L3 and L7 are a tree of shorts, one of them to ground; the coupled L5 and L6 keep
+    // their branches
+    const INDUCTORS: &str = "inductors\nV1 in 0 10\nL1 in a 1u\nR1 a 0 100\nL2 a b 1u\n\
+        R2 b 0 50\nL3 b c 1u\nI1 0 c 1m\nD1 c 0 dmod\nL5 d 0 1m\nL6 e 0 1m\nK1 L5 L6 0.5\n\
+        R4 a d 1k\nR5 a e 2k\nL7 f 0 1u\nR6 a f 1k\n.model dmod d\n.dc V1 0 10 5\n.end\n";
+
+    #[test]
+    fn collapsed_inductors_leave_a_smaller_matrix() {
+        let mut options = ParseOptions::new_with_source("dc.spicy", INDUCTORS.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let config = SimulationConfig::default();
+        let devices = Devices::from_deck(&deck, &config);
+        let collapsed = CollapsedInductors::new(&devices, &deck.node_mapping).unwrap();
+        // four nodes merged and four branches dropped
+        assert_eq!(
+            collapsed.node_mapping.mna_matrix_dim(),
+            deck.node_mapping.mna_matrix_dim() - 8
+        );
+        assert_eq!(
+            collapsed.node_mapping.branch_names_mna_order(),
+            ["V1", "L5", "L6"]
+        );
     }
 
-    DcSweepResult { results }
+    #[test]
+    fn collapsed_inductors_give_the_same_operating_point() {
+        let mut options = ParseOptions::new_with_source("dc.spicy", INDUCTORS.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let plain = SimulationConfig::default();
+        let collapsed = SimulationConfig {
+            collapse_dc_inductors: true,
+            ..Default::default()
+        };
+        let close = |a: &[(String, f64)], b: &[(String, f64)]| {
+            assert_eq!(a.len(), b.len());
+            for ((name, a), (other, b)) in a.iter().zip(b) {
+                assert_eq!(name, other);
+                assert!(
+                    (a - b).abs() <= 1e-9 * (1.0 + b.abs()),
+                    "{name}: {a} vs {b}"
+                );
+            }
+        };
+
+        let op = simulate_op(&deck, &collapsed).expect("op");
+        let reference = simulate_op(&deck, &plain).expect("op");
+        close(&op.voltages, &reference.voltages);
+        close(&op.currents, &reference.currents);
+        // all of the current of V1 goes through L1
+        let l1 = op.current("L1").unwrap();
+        assert!(l1 > 0.0 && (l1 + op.current("V1").unwrap()).abs() < 1e-12 * l1);
+        assert!((op.current("L7").unwrap() - op.voltage("a").unwrap() / 1e3).abs() < 1e-12);
+
+        let Some(Command::Dc(dc)) = deck.commands.first() else {
+            panic!("expected .dc");
+        };
+        let sweep = simulate_dc(&deck, dc, &collapsed).expect("dc");
+        let reference = simulate_dc(&deck, dc, &plain).expect("dc");
+        assert_eq!(sweep.results.len(), 3);
+        for ((op, _), (reference, _)) in sweep.results.iter().zip(&reference.results) {
+            close(&op.voltages, &reference.voltages);
+            close(&op.currents, &reference.currents);
+        }
+    }
+
+    #[test]
+    fn savecurrents_names_the_device_currents() {
+        let netlist = "diode\nV1 in 0 1\nR1 in out 1k\nD1 out 0 dmod\nC1 out 0 1u\n\
+                       .model dmod d\n.options savecurrents reltol=1e-4\n.dc V1 0 1 0.5\n.end\n";
+        let mut options = ParseOptions::new_with_source("dc.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        assert!(deck.options.save_currents);
+        let config = SimulationConfig::default();
+
+        let op = simulate_op(&deck, &config).expect("op");
+        let (r1, d1) = (op.current("R1").unwrap(), op.current("d1").unwrap());
+        // the diode current is evaluated at the converged solution, within Newton tolerance
+        assert!(r1 > 1e-5 && (r1 - d1).abs() < 1e-3 * r1, "{r1} vs {d1}");
+        assert_eq!(op.current("C1"), Some(0.0));
+        // the branch current of the source still comes first
+        assert!((op.currents[0].1 + r1).abs() < 1e-12);
+
+        let Some(Command::Dc(dc)) = deck.commands.first() else {
+            panic!("expected .dc");
+        };
+        let sweep = simulate_dc(&deck, dc, &config).expect("dc");
+        let current = sweep.current("R1").unwrap().y;
+        assert_eq!(current.len(), 3);
+        assert_eq!(current[0], 0.0);
+        assert!(current[1] < current[2]);
+
+        let plain = netlist.replace(".options savecurrents reltol=1e-4\n", "");
+        let mut options = ParseOptions::new_with_source("dc.spicy", plain);
+        let deck = parse(&mut options).expect("parse");
+        assert_eq!(simulate_op(&deck, &config).expect("op").current("R1"), None);
+    }
 }
@@ -0,0 +1,441 @@
+use super::small_signal::SmallSignalModel;
+use super::stamp::NodeVoltageSourceStamp;
+use crate::matrix::SolverMatrix;
+use ndarray::Array2;
+use spicy_parser::ExprFunction;
+use spicy_parser::devices::{BehavioralExpr, BehavioralKind, BehavioralOp, BehavioralSourceSpec};
+use spicy_parser::netlist_types::{CurrentBranchIndex, NodeIndex};
+use spicy_parser::node_mapping::NodeMapping;
+
+/// Cached MNA stamp indices for a B source.
+///
+/// The source depends on the unknowns in `columns` (the non-ground node voltages and branch
+/// currents its expression reads), and its linearization is stamped into `rows`: the positive
+/// and negative nodes of an `I=` source, the branch row of a `V=` source.
+#[derive(Debug, Clone)]
+pub struct BehavioralStamp {
+    /// B / B^T entries of a `V=` source.
+    pub incidence: NodeVoltageSourceStamp,
+    pub columns: Vec<usize>,
+    /// MNA row and the sign its current or voltage is stamped with.
+    pub rows: Vec<(usize, f64)>,
+    /// Entry of every (row, column), row-major.
+    pub entries: Vec<usize>,
+}
+
+impl BehavioralStamp {
+    /// Create a stamp with no indices assigned yet.
+    pub fn uninitialized() -> Self {
+        Self {
+            incidence: NodeVoltageSourceStamp::uninitialized(),
+            columns: Vec::new(),
+            rows: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Map temporary indices to their final locations using the provided mapping.
+    pub fn set_final_indices<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize) -> usize,
+    {
+        self.incidence.set_final_indices(&mut f);
+        for entry in &mut self.entries {
+            *entry = f(*entry);
+        }
+    }
+}
+
+/// An expression value with its partial derivatives with respect to the stamp columns.
+#[derive(Debug, Clone)]
+struct Dual {
+    value: f64,
+    gradient: Vec<f64>,
+}
+
+impl Dual {
+    fn constant(value: f64, columns: usize) -> Self {
+        Self {
+            value,
+            gradient: vec![0.0; columns],
+        }
+    }
+
+    /// The unknown of column `column` (none for ground), with value `value`.
+    fn unknown(value: f64, column: Option<usize>, columns: usize) -> Self {
+        let mut dual = Self::constant(value, columns);
+        if let Some(column) = column {
+            dual.gradient[column] = 1.0;
+        }
+        dual
+    }
+
+    /// Apply `f` with derivative `df` at the current value (chain rule).
+    fn map(mut self, f: impl Fn(f64) -> f64, df: impl Fn(f64) -> f64) -> Self {
+        let slope = df(self.value);
+        self.value = f(self.value);
+        for g in &mut self.gradient {
+            *g *= slope;
+        }
+        self
+    }
+
+    /// `a * self + b * other` for the gradients, with `value` as the result value.
+    fn combine(mut self, a: f64, other: &Dual, b: f64, value: f64) -> Self {
+        for (g, o) in self.gradient.iter_mut().zip(&other.gradient) {
+            *g = a * *g + b * o;
+        }
+        self.value = value;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BehavioralSource {
+    pub name: String,
+    pub positive: NodeIndex,
+    pub negative: NodeIndex,
+    pub kind: BehavioralKind,
+    pub current_branch: CurrentBranchIndex,
+    pub expr: BehavioralExpr,
+    pub stamp: BehavioralStamp,
+}
+
+impl BehavioralSource {
+    pub fn from_spec(spec: &BehavioralSourceSpec) -> Self {
+        Self {
+            name: spec.name.clone(),
+            positive: spec.positive,
+            negative: spec.negative,
+            kind: spec.kind,
+            current_branch: spec.current_branch,
+            expr: spec.expr.clone(),
+            stamp: BehavioralStamp::uninitialized(),
+        }
+    }
+
+    /// Reserve the entries of the source, through `entry(row, column)`.
+    pub(crate) fn setup<F, E>(&mut self, node_mapping: &NodeMapping, mut entry: F) -> Result<(), E>
+    where
+        F: FnMut(usize, usize) -> Result<usize, E>,
+    {
+        let mut columns = Vec::new();
+        collect_columns(&self.expr, node_mapping, &mut columns);
+
+        let pos = node_mapping.mna_node_index(self.positive);
+        let neg = node_mapping.mna_node_index(self.negative);
+        let rows: Vec<(usize, f64)> = match self.kind {
+            // the current leaves the positive node and enters the negative one
+            BehavioralKind::Current => [(pos, 1.0), (neg, -1.0)]
+                .into_iter()
+                .filter_map(|(row, sign)| Some((row?, sign)))
+                .collect(),
+            BehavioralKind::Voltage => {
+                let branch = node_mapping.mna_branch_index(self.current_branch);
+                self.stamp.incidence.set_temp_indices_from_nodes(
+                    pos,
+                    neg,
+                    branch,
+                    |col, row| entry(row, col),
+                )?;
+                vec![(branch, 1.0)]
+            }
+        };
+
+        let mut entries = Vec::with_capacity(rows.len() * columns.len());
+        for &(row, _) in &rows {
+            for &column in &columns {
+                entries.push(entry(row, column)?);
+            }
+        }
+
+        self.stamp.columns = columns;
+        self.stamp.rows = rows;
+        self.stamp.entries = entries;
+        Ok(())
+    }
+
+    /// Linearize around the Newton guess: f(x) ~ f(x0) + sum g_k (x_k - x0_k).
+    /// - `I=` source: the current f(x) leaves the positive node, so the g_k go into the
+    ///   matrix and f(x0) - sum g_k x0_k is the equivalent current source.
+    /// - `V=` source: V(pos) - V(neg) - sum g_k x_k = f(x0) - sum g_k x0_k.
+    pub(crate) fn stamp_nonlinear(&self, m: &mut SolverMatrix, guess: &[f64], time: f64) {
+        let f = evaluate(
+            &self.expr,
+            guess,
+            m.node_mapping(),
+            &self.stamp.columns,
+            time,
+        );
+        let i_eq = f.value
+            - f.gradient
+                .iter()
+                .zip(&self.stamp.columns)
+                .map(|(g, &column)| g * guess[column])
+                .sum::<f64>();
+
+        match self.kind {
+            BehavioralKind::Current => {
+                for (r, &(row, sign)) in self.stamp.rows.iter().enumerate() {
+                    for (c, g) in f.gradient.iter().enumerate() {
+                        *m.get_mut_nnz(self.stamp.entries[r * f.gradient.len() + c]) += sign * g;
+                    }
+                    *m.get_mut_rhs(row) -= sign * i_eq;
+                }
+            }
+            BehavioralKind::Voltage => {
+                if let Some((pos_branch, branch_pos)) = self.stamp.incidence.pos_branch {
+                    *m.get_mut_nnz(pos_branch) += 1.0;
+                    *m.get_mut_nnz(branch_pos) += 1.0;
+                }
+                if let Some((neg_branch, branch_neg)) = self.stamp.incidence.neg_branch {
+                    *m.get_mut_nnz(neg_branch) -= 1.0;
+                    *m.get_mut_nnz(branch_neg) -= 1.0;
+                }
+                for (entry, g) in self.stamp.entries.iter().zip(&f.gradient) {
+                    *m.get_mut_nnz(*entry) -= g;
+                }
+                let (branch, _) = self.stamp.rows[0];
+                *m.get_mut_rhs(branch) += i_eq;
+            }
+        }
+    }
+}
+
+impl SmallSignalModel for BehavioralSource {
+    /// Stamp the small-signal linearization at the operating point `op` into the real part
+    /// matrix.
+    fn stamp_small_signal(
+        &self,
+        ar: &mut Array2<f64>,
+        _ai: &mut Array2<f64>,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+        _w: f64,
+    ) {
+        let mut columns = Vec::new();
+        collect_columns(&self.expr, node_mapping, &mut columns);
+        let f = evaluate(&self.expr, op, node_mapping, &columns, 0.0);
+
+        let pos = node_mapping.mna_node_index(self.positive);
+        let neg = node_mapping.mna_node_index(self.negative);
+        match self.kind {
+            BehavioralKind::Current => {
+                for (row, sign) in [(pos, 1.0), (neg, -1.0)] {
+                    let Some(row) = row else { continue };
+                    for (&column, g) in columns.iter().zip(&f.gradient) {
+                        ar[[row, column]] += sign * g;
+                    }
+                }
+            }
+            BehavioralKind::Voltage => {
+                let branch = node_mapping.mna_branch_index(self.current_branch);
+                if let Some(p) = pos {
+                    ar[[p, branch]] += 1.0;
+                    ar[[branch, p]] += 1.0;
+                }
+                if let Some(n) = neg {
+                    ar[[n, branch]] -= 1.0;
+                    ar[[branch, n]] -= 1.0;
+                }
+                for (&column, g) in columns.iter().zip(&f.gradient) {
+                    ar[[branch, column]] -= g;
+                }
+            }
+        }
+    }
+}
+
+/// Add the MNA columns of the unknowns `expr` reads to `columns`, each once.
+fn collect_columns(expr: &BehavioralExpr, node_mapping: &NodeMapping, columns: &mut Vec<usize>) {
+    let column = match expr {
+        BehavioralExpr::Constant(_) | BehavioralExpr::Time => None,
+        BehavioralExpr::Voltage(node) => node_mapping.mna_node_index(*node),
+        BehavioralExpr::Current { branch, .. } => Some(node_mapping.mna_branch_index(*branch)),
+        BehavioralExpr::Negate(operand) => {
+            collect_columns(operand, node_mapping, columns);
+            None
+        }
+        BehavioralExpr::Binary { left, right, .. } => {
+            collect_columns(left, node_mapping, columns);
+            collect_columns(right, node_mapping, columns);
+            None
+        }
+        BehavioralExpr::Call { args, .. } => {
+            for arg in args {
+                collect_columns(arg, node_mapping, columns);
+            }
+            None
+        }
+    };
+    if let Some(column) = column
+        && !columns.contains(&column)
+    {
+        columns.push(column);
+    }
+}
+
+/// Forward-mode evaluation of `expr` at the solution `x`.
+fn evaluate(
+    expr: &BehavioralExpr,
+    x: &[f64],
+    node_mapping: &NodeMapping,
+    columns: &[usize],
+    time: f64,
+) -> Dual {
+    let n = columns.len();
+    let unknown = |column: Option<usize>| {
+        let value = column.map(|c| x[c]).unwrap_or(0.0);
+        let position = column.and_then(|c| columns.iter().position(|&k| k == c));
+        Dual::unknown(value, position, n)
+    };
+    let eval = |expr: &BehavioralExpr| evaluate(expr, x, node_mapping, columns, time);
+
+    match expr {
+        BehavioralExpr::Constant(value) => Dual::constant(*value, n),
+        BehavioralExpr::Time => Dual::constant(time, n),
+        BehavioralExpr::Voltage(node) => unknown(node_mapping.mna_node_index(*node)),
+        BehavioralExpr::Current { branch, .. } => {
+            unknown(Some(node_mapping.mna_branch_index(*branch)))
+        }
+        BehavioralExpr::Negate(operand) => eval(operand).map(|v| -v, |_| -1.0),
+        BehavioralExpr::Binary { op, left, right } => {
+            let a = eval(left);
+            let b = eval(right);
+            let (av, bv) = (a.value, b.value);
+            match op {
+                BehavioralOp::Add => a.combine(1.0, &b, 1.0, av + bv),
+                BehavioralOp::Sub => a.combine(1.0, &b, -1.0, av - bv),
+                BehavioralOp::Mul => a.combine(bv, &b, av, av * bv),
+                BehavioralOp::Div => a.combine(1.0 / bv, &b, -av / (bv * bv), av / bv),
+                BehavioralOp::Less => Dual::constant(f64::from(av < bv), n),
+                BehavioralOp::Greater => Dual::constant(f64::from(av > bv), n),
+            }
+        }
+        BehavioralExpr::Call { function, args } => {
+            if *function == ExprFunction::If {
+                let condition = eval(&args[0]);
+                return if condition.value != 0.0 {
+                    eval(&args[1])
+                } else {
+                    eval(&args[2])
+                };
+            }
+            let mut args = args.iter().map(eval);
+            let a = args.next().expect("every function takes an argument");
+            match function {
+                ExprFunction::Sin => a.map(f64::sin, f64::cos),
+                ExprFunction::Cos => a.map(f64::cos, |v| -v.sin()),
+                ExprFunction::Exp => a.map(f64::exp, f64::exp),
+                ExprFunction::Log => a.map(f64::ln, |v| 1.0 / v),
+                ExprFunction::Log10 => a.map(f64::log10, |v| 1.0 / (v * std::f64::consts::LN_10)),
+                ExprFunction::Sqrt => a.map(f64::sqrt, |v| 0.5 / v.sqrt()),
+                ExprFunction::Abs => a.map(f64::abs, f64::signum),
+                ExprFunction::Min | ExprFunction::Max | ExprFunction::Pow => {
+                    let b = args.next().expect("binary function");
+                    let (av, bv) = (a.value, b.value);
+                    match function {
+                        ExprFunction::Min if av <= bv => a,
+                        ExprFunction::Max if av >= bv => a,
+                        ExprFunction::Min | ExprFunction::Max => b,
+                        _ => {
+                            // d(a^b) = b a^(b-1) da + a^b ln(a) db, the latter only defined for a > 0
+                            let value = av.powf(bv);
+                            let db = if av > 0.0 { value * av.ln() } else { 0.0 };
+                            a.combine(bv * av.powf(bv - 1.0), &b, db, value)
+                        }
+                    }
+                }
+                ExprFunction::If => unreachable!("handled above"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SimulationConfig, dc::simulate_op};
+    use spicy_parser::{ParseOptions, parse};
+
+    fn parse_source(expr: &str) -> (BehavioralSource, NodeMapping) {
+        let netlist = format!("b\nV1 a 0 1\nV2 b 0 1\nB1 out 0 I={expr}\nR1 out 0 1\n.end\n");
+        let mut options = ParseOptions::new_with_source("b.spicy", netlist);
+        let deck = parse(&mut options).expect("parse");
+        let source = BehavioralSource::from_spec(&deck.devices.behavioral_sources[0]);
+        (source, deck.node_mapping)
+    }
+
+    /// Compare the derivatives with central differences at `x`.
+    #[track_caller]
+    fn check_gradient(expr: &str, x: &[f64]) {
+        let (source, node_mapping) = parse_source(expr);
+        let mut columns = Vec::new();
+        collect_columns(&source.expr, &node_mapping, &mut columns);
+        let f = evaluate(&source.expr, x, &node_mapping, &columns, 0.0);
+
+        for (c, &column) in columns.iter().enumerate() {
+            let h = 1e-6;
+            let mut up = x.to_vec();
+            up[column] += h;
+            let mut down = x.to_vec();
+            down[column] -= h;
+            let numeric = (evaluate(&source.expr, &up, &node_mapping, &columns, 0.0).value
+                - evaluate(&source.expr, &down, &node_mapping, &columns, 0.0).value)
+                / (2.0 * h);
+            assert!(
+                (f.gradient[c] - numeric).abs() < 1e-5 * numeric.abs().max(1.0),
+                "{expr}: d/dx{column} = {}, expected {numeric}",
+                f.gradient[c]
+            );
+        }
+    }
+
+    #[test]
+    fn gradients_match_finite_differences() {
+        // unknowns: V(a), V(b), V(out), I(V1), I(V2)
+        let x = [1.5, 0.7, 0.2, 1e-3, -2e-3];
+        for expr in [
+            "{V(a)*V(b) + 1k*I(V2)}",
+            "{V(a, b)/V(b) - -V(a)}",
+            "{sin(V(a))*cos(V(b)) + exp(V(b))}",
+            "{log(V(a)) + log10(V(b)) + sqrt(V(a)) + abs(V(out) - V(a))}",
+            "{pow(V(a), V(b)) + min(V(a), V(b)) * max(V(a), 2*V(b))}",
+            "{if(V(a) > V(b), V(a)*V(a), 1)}",
+        ] {
+            check_gradient(expr, &x);
+        }
+    }
+
+    fn op(netlist: &str) -> crate::dc::OperatingPointResult {
+        let mut options = ParseOptions::new_with_source("b.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        simulate_op(&deck, &SimulationConfig::default()).expect("op")
+    }
+
+    #[test]
+    fn current_source_of_its_own_voltage_is_a_resistor() {
+        let b = op("b\nV1 a 0 2\nB1 a 0 I=V(a)/1k\n.op\n.end\n");
+        let r = op("r\nV1 a 0 2\nR1 a 0 1k\n.op\n.end\n");
+        let (b, r) = (b.current("V1").unwrap(), r.current("V1").unwrap());
+        assert!((b - r).abs() < 1e-12, "{b} vs {r}");
+    }
+
+    #[test]
+    fn voltage_source_of_node_voltages_and_branch_currents() {
+        let result = op("b\nV1 a 0 2\nV2 b 0 3\nR1 b 0 1k\n\
+            B1 out 0 V=V(a)*V(b) + 1k*I(V2)\nR2 out 0 1k\n.op\n.end\n");
+        let expected = 6.0 + 1e3 * result.current("V2").unwrap();
+        let out = result.voltage("out").unwrap();
+        assert!((out - expected).abs() < 1e-9, "{out} vs {expected}");
+    }
+
+    #[test]
+    fn nonlinear_current_converges() {
+        // (1 - v) / 1k = 1m * v^2, so v^2 + v - 1 = 0
+        let result = op("b\nV1 a 0 1\nR1 a out 1k\nB1 out 0 I=1m*V(out)*V(out)\n.op\n.end\n");
+        let out = result.voltage("out").unwrap();
+        let expected = (5f64.sqrt() - 1.0) / 2.0;
+        assert!((out - expected).abs() < 1e-9, "{out} vs {expected}");
+    }
+}
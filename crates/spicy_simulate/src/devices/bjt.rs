@@ -1,17 +1,149 @@
-//! Simple Ebers-Moll BJT model (NPN/PNP).
+//! Gummel-Poon BJT model (NPN/PNP).
 //!
-//! Uses base-emitter/base-collector junctions and alpha gains, then
-//! linearizes around the current Newton guess for MNA stamping.
-use super::stamp::NodeTripletStamp;
+//! The transport current between the base-emitter and base-collector junctions is divided by
+//! the normalized base charge `qb`, which models the Early effect (VAF/VAR) and the beta
+//! roll-off at high injection (IKF/IKR). Leakage diodes (ISE/NE, ISC/NC) lower beta at low
+//! currents. RB/RC/RE sit between the terminals and internal nodes allocated by the parser, and
+//! each junction stores a diffusion (TF/TR) and a depletion (CJE/CJC) charge, used by the
+//! transient and AC analyses. The device is linearized around the current Newton guess for MNA
+//! stamping.
+use super::NOMINAL_TEMPERATURE;
+use super::diode::{saturation_current_at, thermal_voltage};
+use super::multiplicity::Multiplicity;
+use super::small_signal::SmallSignalModel;
+use super::stamp::{NodePairStamp, NodeTripletStamp};
+use super::thermal::ThermalRc;
 use crate::matrix::SolverMatrix;
+use crate::noise::{ELECTRON_CHARGE, NoiseSource, celsius_to_kelvin};
+use crate::op_report::DeviceOperatingPoint;
 use crate::util::get_voltage_diff;
+use ndarray::Array2;
 use spicy_parser::BjtPolarity;
 use spicy_parser::Span;
+use spicy_parser::Value;
 use spicy_parser::devices::BjtSpec;
 use spicy_parser::netlist_types::NodeIndex;
+use spicy_parser::node_mapping::NodeMapping;
 
-const DEFAULT_THERMAL_VOLTAGE: f64 = 0.02585;
 const DEFAULT_EXP_LIMIT: f64 = 40.0;
+/// Silicon bandgap (eV).
+const DEFAULT_ENERGY_GAP: f64 = 1.11;
+const DEFAULT_SATURATION_CURRENT_EXPONENT: f64 = 3.0;
+const DEFAULT_JUNCTION_POTENTIAL: f64 = 0.75;
+const DEFAULT_GRADING_COEFF: f64 = 0.33;
+const DEFAULT_DEPLETION_COEFF: f64 = 0.5;
+
+/// A parasitic series resistance between a terminal and its internal node.
+#[derive(Debug, Clone)]
+pub struct SeriesResistance {
+    /// The external terminal.
+    pub positive: NodeIndex,
+    /// The internal node.
+    pub negative: NodeIndex,
+    pub conductance: f64,
+    pub stamp: NodePairStamp,
+}
+
+impl SeriesResistance {
+    pub(crate) fn stamp(&self, m: &mut SolverMatrix) {
+        let g = self.conductance;
+        if let Some(index) = self.stamp.pos_pos {
+            *m.get_mut_nnz(index) += g;
+        }
+        if let Some(index) = self.stamp.neg_neg {
+            *m.get_mut_nnz(index) += g;
+        }
+        if let Some((pos_neg, neg_pos)) = self.stamp.off_diagonals {
+            *m.get_mut_nnz(pos_neg) -= g;
+            *m.get_mut_nnz(neg_pos) -= g;
+        }
+    }
+}
+
+/// The depletion capacitance of a junction.
+#[derive(Debug, Clone, Copy)]
+pub struct DepletionCapacitance {
+    /// Zero-bias capacitance (F) at the circuit temperature.
+    pub capacitance: f64,
+    /// Built-in potential (V) at the circuit temperature.
+    pub potential: f64,
+    /// Grading coefficient.
+    pub grading: f64,
+}
+
+impl DepletionCapacitance {
+    fn from_model(
+        capacitance: &Option<Value>,
+        potential: &Option<Value>,
+        grading: &Option<Value>,
+        eg: f64,
+        temperature: f64,
+    ) -> Self {
+        let capacitance = capacitance.as_ref().map(|v| v.get_value()).unwrap_or(0.0);
+        let potential = potential
+            .as_ref()
+            .map(|v| v.get_value())
+            .unwrap_or(DEFAULT_JUNCTION_POTENTIAL);
+        let grading = grading
+            .as_ref()
+            .map(|v| v.get_value())
+            .unwrap_or(DEFAULT_GRADING_COEFF);
+        Self::new(capacitance, potential, grading, eg, temperature)
+    }
+
+    /// A junction of zero-bias `capacitance` and built-in `potential` given at the nominal
+    /// temperature, at the circuit `temperature` (°C).
+    pub(crate) fn new(
+        capacitance: f64,
+        potential: f64,
+        grading: f64,
+        eg: f64,
+        temperature: f64,
+    ) -> Self {
+        // SPICE2 temperature scaling with a constant bandgap:
+        // Vj(T) = Vj * T/Tnom - 3 Vt(T) ln(T/Tnom) - eg (T/Tnom - 1)
+        // Cj(T) = Cj * (1 + M (4e-4 (T - Tnom) + 1 - Vj(T)/Vj))
+        let ratio = celsius_to_kelvin(temperature) / celsius_to_kelvin(NOMINAL_TEMPERATURE);
+        let potential_at = potential * ratio
+            - 3.0 * thermal_voltage(temperature) * ratio.ln()
+            - eg * (ratio - 1.0);
+        let capacitance_at = capacitance
+            * (1.0
+                + grading
+                    * (4e-4 * (temperature - NOMINAL_TEMPERATURE) + 1.0
+                        - potential_at / potential));
+        Self {
+            capacitance: capacitance_at,
+            potential: potential_at,
+            grading,
+        }
+    }
+
+    /// Charge and capacitance at the junction voltage `v`. Above `fc * Vj` the capacitance is
+    /// extended linearly instead of going to infinity at `Vj`.
+    pub(crate) fn charge(&self, v: f64, fc: f64) -> (f64, f64) {
+        let Self {
+            capacitance: cj,
+            potential: vj,
+            grading: mj,
+        } = *self;
+        if cj == 0.0 {
+            return (0.0, 0.0);
+        }
+        if v < fc * vj {
+            let arg = 1.0 - v / vj;
+            let q = cj * vj / (1.0 - mj) * (1.0 - arg.powf(1.0 - mj));
+            (q, cj * arg.powf(-mj))
+        } else {
+            let f1 = vj / (1.0 - mj) * (1.0 - (1.0 - fc).powf(1.0 - mj));
+            let f2 = (1.0 - fc).powf(1.0 + mj);
+            let f3 = 1.0 - fc * (1.0 + mj);
+            let v_fc = fc * vj;
+            let q = cj * f1 + cj / f2 * (f3 * (v - v_fc) + mj / (2.0 * vj) * (v * v - v_fc * v_fc));
+            (q, cj / f2 * (f3 + mj * v / vj))
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Bjt {
@@ -20,30 +152,57 @@ pub struct Bjt {
     pub name: String,
     #[allow(dead_code)]
     pub span: Span,
+    // The terminals, only connected through `series_resistances`.
+    #[allow(dead_code)]
     pub collector: NodeIndex,
+    #[allow(dead_code)]
     pub base: NodeIndex,
+    #[allow(dead_code)]
     pub emitter: NodeIndex,
+    /// The intrinsic transistor's terminals, behind the series resistances.
+    pub collector_prime: NodeIndex,
+    pub base_prime: NodeIndex,
+    pub emitter_prime: NodeIndex,
     pub polarity: BjtPolarity,
-    /// Saturation current (A).
+    /// Saturation current (A) at the circuit temperature.
     pub saturation_current: f64,
-    /// Forward beta - approximate relation between I_c and I_e in active region.
-    /// in ebers-moll model beta_forward is usually converted to alpha gains.
-    /// alpha_f = beta_forward / (beta_forward + 1)
-    /// this defines the coupling between the base and collector currents.
+    /// Ideal maximum forward beta, the ratio of the transport current to the base-emitter
+    /// diffusion current.
     pub beta_forward: f64,
-    /// Reverse beta - approximate relation between I_e and I_b in reverse-active region.
-    /// in ebers-moll model beta_reverse is usually converted to alpha gains.
-    /// alpha_r = beta_reverse / (beta_reverse + 1)
-    /// this defines the coupling between the base and emitter currents.
+    /// Ideal maximum reverse beta.
     pub beta_reverse: f64,
     /// Forward emission coefficient (ideality factor), dimensionless.
     pub emission_coeff_forward: f64,
     /// Reverse emission coefficient (ideality factor), dimensionless.
     pub emission_coeff_reverse: f64,
+    /// 1 / VAF, 0 without a forward Early effect.
+    pub inverse_early_forward: f64,
+    /// 1 / VAR, 0 without a reverse Early effect.
+    pub inverse_early_reverse: f64,
+    /// 1 / IKF, 0 without high injection in forward operation.
+    pub inverse_knee_forward: f64,
+    /// 1 / IKR, 0 without high injection in reverse operation.
+    pub inverse_knee_reverse: f64,
+    /// Base-emitter leakage saturation current (A) at the circuit temperature.
+    pub leakage_current_be: f64,
+    pub emission_coeff_leakage_be: f64,
+    /// Base-collector leakage saturation current (A) at the circuit temperature.
+    pub leakage_current_bc: f64,
+    pub emission_coeff_leakage_bc: f64,
+    /// Forward transit time (s): the diffusion charge of the base-emitter junction is TF * Ibe.
+    pub transit_time_forward: f64,
+    /// Reverse transit time (s): the diffusion charge of the base-collector junction is TR * Ibc.
+    pub transit_time_reverse: f64,
+    pub depletion_be: DepletionCapacitance,
+    pub depletion_bc: DepletionCapacitance,
+    /// Fraction of the built-in potential above which the depletion capacitances are linear.
+    pub depletion_coeff: f64,
+    /// RB, RC and RE, when given.
+    pub series_resistances: Vec<SeriesResistance>,
+    /// `m` and `area`, already applied to the currents, the capacitances and the series
+    /// resistances.
     #[allow(dead_code)]
-    pub area: f64,
-    #[allow(dead_code)]
-    pub m: f64,
+    pub multiplicity: Multiplicity,
     /// Thermal voltage (Vt) used in exp(V / (n * Vt)).
     pub thermal_voltage: f64,
     /// Clamp limit for V/Vt to keep exp() bounded.
@@ -54,9 +213,40 @@ pub struct Bjt {
     pub ic_vbe: f64,
     #[allow(dead_code)]
     pub ic_vce: Option<f64>,
+    /// Flicker noise coefficient (base current).
+    pub kf: f64,
+    /// Flicker noise exponent (base current).
+    pub af: f64,
+    /// Thermal RC of a self-heating transistor, see [`BjtHeating`].
+    pub heating: Option<BjtHeating>,
+    /// Stamp of the intrinsic transistor, on the internal nodes.
     pub stamp: NodeTripletStamp,
 }
 
+/// Self-heating of a BJT: the currents follow the temperature of its thermal node, scaled
+/// like the circuit temperature scales the model. The depletion capacitances keep their value
+/// at the circuit temperature.
+#[derive(Debug, Clone)]
+pub struct BjtHeating {
+    pub thermal: ThermalRc,
+    /// Temperature (°C) the values of the BJT are given at, before the rise.
+    temperature: f64,
+    eg: f64,
+    xti: f64,
+    xtb: f64,
+}
+
+/// The values of a BJT that follow the temperature of a self-heating one.
+#[derive(Debug, Clone, Copy)]
+struct Heated {
+    saturation_current: f64,
+    beta_forward: f64,
+    beta_reverse: f64,
+    leakage_current_be: f64,
+    leakage_current_bc: f64,
+    thermal_voltage: f64,
+}
+
 #[derive(Debug, Clone, Copy)]
 struct LinearizedBjt {
     g_bb: f64,
@@ -71,32 +261,85 @@ struct LinearizedBjt {
     i_eq_b: f64,
     i_eq_c: f64,
     i_eq_e: f64,
+    /// Base current at the linearization point.
+    i_b: f64,
+    /// Collector current at the linearization point.
+    i_c: f64,
+    /// Clamped junction voltages (node polarity) the currents were evaluated at.
+    v_be: f64,
+    v_bc: f64,
+    /// Charge and capacitance of the base-emitter junction (polarity-normalized).
+    charge_be: (f64, f64),
+    /// Charge and capacitance of the base-collector junction (polarity-normalized).
+    charge_bc: (f64, f64),
 }
 
-impl Bjt {
-    pub fn from_spec(spec: &BjtSpec) -> Self {
-        let saturation_current = spec
-            .model
-            .is
-            .as_ref()
-            .map(|v| v.get_value())
-            .unwrap_or(1e-14);
+/// `1 / value` of an optional parameter where 0 (or no value) stands for infinity.
+fn inverse_or_zero(value: &Option<Value>) -> f64 {
+    match value.as_ref().map(|v| v.get_value()) {
+        Some(v) if v != 0.0 => 1.0 / v,
+        _ => 0.0,
+    }
+}
 
-        let beta_forward = spec
-            .model
-            .bf
-            .as_ref()
-            .map(|v| v.get_value())
-            .unwrap_or(100.0);
-        let beta_reverse = spec.model.br.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
-        let emission_coeff_forward = spec.model.nf.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
-        let emission_coeff_reverse = spec.model.nr.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
+impl Bjt {
+    /// Compile a parsed BJT at the circuit `temperature` (°C).
+    pub fn from_spec(spec: &BjtSpec, temperature: f64) -> Self {
+        let model = &spec.model;
+        let value_or = |value: &Option<Value>, default: f64| {
+            value.as_ref().map(|v| v.get_value()).unwrap_or(default)
+        };
+        let saturation_current = value_or(&model.is, 1e-14);
+        let beta_forward = value_or(&model.bf, 100.0);
+        let beta_reverse = value_or(&model.br, 1.0);
+        let emission_coeff_forward = value_or(&model.nf, 1.0);
+        let emission_coeff_reverse = value_or(&model.nr, 1.0);
+        let leakage_current_be = value_or(&model.ise, 0.0);
+        let emission_coeff_leakage_be = value_or(&model.ne, 1.5);
+        let leakage_current_bc = value_or(&model.isc, 0.0);
+        let emission_coeff_leakage_bc = value_or(&model.nc, 2.0);
 
-        let area = spec.area.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
-        let m = spec.m.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
+        let multiplicity = Multiplicity::from_values(spec.m.as_ref(), spec.area.as_ref());
         let off = spec.off.unwrap_or(false);
         let ic_vbe = spec.ic_vbe.as_ref().map(|v| v.get_value()).unwrap_or(0.0);
         let ic_vce = spec.ic_vce.as_ref().map(|v| v.get_value());
+        let kf = value_or(&model.kf, 0.0);
+        let af = value_or(&model.af, 1.0);
+
+        let eg = value_or(&model.eg, DEFAULT_ENERGY_GAP);
+        let xti = value_or(&model.xti, DEFAULT_SATURATION_CURRENT_EXPONENT);
+        let xtb = value_or(&model.xtb, 0.0);
+        // the Gummel-Poon temperature scaling: Is with an emission coefficient of 1, both betas
+        // by (T/Tnom)^xtb and the leakage currents by (Is(T)/Is)^(1/n) / (T/Tnom)^xtb
+        let is_scale = saturation_current_at(1.0, 1.0, eg, xti, temperature);
+        let beta_scale =
+            (celsius_to_kelvin(temperature) / celsius_to_kelvin(NOMINAL_TEMPERATURE)).powf(xtb);
+        let leakage_scale = |n: f64| is_scale.powf(1.0 / n) / beta_scale;
+
+        let fc = value_or(&model.fc, DEFAULT_DEPLETION_COEFF);
+        let depletion = |capacitance, potential, grading| {
+            let mut depletion =
+                DepletionCapacitance::from_model(capacitance, potential, grading, eg, temperature);
+            depletion.capacitance = multiplicity.parallel(depletion.capacitance);
+            depletion
+        };
+        let depletion_be = depletion(&model.cje, &model.vje, &model.mje);
+        let depletion_bc = depletion(&model.cjc, &model.vjc, &model.mjc);
+
+        let series_resistances = [
+            (spec.collector, spec.collector_prime, &model.rc),
+            (spec.base, spec.base_prime, &model.rb),
+            (spec.emitter, spec.emitter_prime, &model.re),
+        ]
+        .into_iter()
+        .filter(|(external, internal, _)| external != internal)
+        .map(|(positive, negative, resistance)| SeriesResistance {
+            positive,
+            negative,
+            conductance: multiplicity.parallel(inverse_or_zero(resistance)),
+            stamp: NodePairStamp::uninitialized(),
+        })
+        .collect();
 
         Self {
             name: spec.name.clone(),
@@ -104,19 +347,48 @@ impl Bjt {
             collector: spec.collector,
             base: spec.base,
             emitter: spec.emitter,
-            polarity: spec.model.polarity,
-            saturation_current,
-            beta_forward,
-            beta_reverse,
+            collector_prime: spec.collector_prime,
+            base_prime: spec.base_prime,
+            emitter_prime: spec.emitter_prime,
+            polarity: model.polarity,
+            saturation_current: multiplicity.parallel(saturation_current * is_scale),
+            beta_forward: beta_forward * beta_scale,
+            beta_reverse: beta_reverse * beta_scale,
             emission_coeff_forward,
             emission_coeff_reverse,
-            area,
-            m,
-            thermal_voltage: DEFAULT_THERMAL_VOLTAGE,
+            inverse_early_forward: inverse_or_zero(&model.vaf),
+            inverse_early_reverse: inverse_or_zero(&model.var),
+            inverse_knee_forward: multiplicity.divided(inverse_or_zero(&model.ikf)),
+            inverse_knee_reverse: multiplicity.divided(inverse_or_zero(&model.ikr)),
+            leakage_current_be: multiplicity
+                .parallel(leakage_current_be * leakage_scale(emission_coeff_leakage_be)),
+            emission_coeff_leakage_be,
+            leakage_current_bc: multiplicity
+                .parallel(leakage_current_bc * leakage_scale(emission_coeff_leakage_bc)),
+            emission_coeff_leakage_bc,
+            transit_time_forward: value_or(&model.tf, 0.0),
+            transit_time_reverse: value_or(&model.tr, 0.0),
+            depletion_be,
+            depletion_bc,
+            depletion_coeff: fc,
+            series_resistances,
+            multiplicity,
+            thermal_voltage: thermal_voltage(temperature),
             exp_limit: DEFAULT_EXP_LIMIT,
             off,
             ic_vbe,
             ic_vce,
+            kf,
+            af,
+            heating: ThermalRc::from_values(model.rth.as_ref(), model.cth.as_ref()).map(
+                |thermal| BjtHeating {
+                    thermal,
+                    temperature,
+                    eg,
+                    xti,
+                    xtb,
+                },
+            ),
             stamp: NodeTripletStamp::uninitialized(),
         }
     }
@@ -124,7 +396,7 @@ impl Bjt {
     /// Return +1 for NPN, -1 for PNP.
     ///
     /// This flips the sign of control voltages and resulting currents to
-    /// reuse the same Ebers-Moll equations for both polarities.
+    /// reuse the same Gummel-Poon equations for both polarities.
     fn polarity_sign(&self) -> f64 {
         match self.polarity {
             BjtPolarity::Npn => 1.0,
@@ -132,72 +404,151 @@ impl Bjt {
         }
     }
 
-    /// Compute diode current, conductance, and clamped voltage.
-    fn junction_values(&self, v: f64, emission_coeff: f64) -> (f64, f64, f64) {
-        let nvt = emission_coeff * self.thermal_voltage;
+    /// Whether the junctions store any charge, i.e. the device has a transient companion model.
+    pub(crate) fn stores_charge(&self) -> bool {
+        self.transit_time_forward != 0.0
+            || self.transit_time_reverse != 0.0
+            || self.depletion_be.capacitance != 0.0
+            || self.depletion_bc.capacitance != 0.0
+    }
+
+    /// Clamp a forward-biased junction voltage so exp(v / (n * Vt)) stays bounded. Reverse
+    /// voltages are kept: the exponential vanishes there, and the Early effect and the
+    /// depletion charge depend on them.
+    fn clamp_junction(&self, v: f64, emission_coeff: f64, thermal_voltage: f64) -> f64 {
         // TODO: this is very bad limiting,
         // we need the previous iteration votlage to limit correctly
-        let v_limit = self.exp_limit * nvt;
-        let v_eff = v.clamp(-v_limit, v_limit);
+        let v_limit = self.exp_limit * emission_coeff * thermal_voltage;
+        v.min(v_limit)
+    }
 
-        let x = v_eff / nvt;
-        let exp_v = x.exp();
-        let isat = self.saturation_current;
-        let i = isat * x.exp_m1();
-        let g = isat * exp_v / nvt;
-        (i, g, v_eff)
+    /// Diode current and conductance of `isat * (exp(v / (n * Vt)) - 1)`.
+    fn diode(v: f64, isat: f64, emission_coeff: f64, thermal_voltage: f64) -> (f64, f64) {
+        let nvt = emission_coeff * thermal_voltage;
+        let x = v / nvt;
+        (isat * x.exp_m1(), isat * x.exp() / nvt)
     }
 
-    /// Linearize the Ebers-Moll model at the current node voltages.
+    /// The values that follow the temperature, at the rise of a self-heating transistor.
+    fn heated(&self) -> Heated {
+        let values = Heated {
+            saturation_current: self.saturation_current,
+            beta_forward: self.beta_forward,
+            beta_reverse: self.beta_reverse,
+            leakage_current_be: self.leakage_current_be,
+            leakage_current_bc: self.leakage_current_bc,
+            thermal_voltage: self.thermal_voltage,
+        };
+        let Some(heating) = &self.heating else {
+            return values;
+        };
+        let BjtHeating { eg, xti, xtb, .. } = *heating;
+        let t0 = heating.temperature;
+        let t = t0 + heating.thermal.rise();
+        // the scaling of `from_spec`, from t0 rather than the nominal temperature
+        let is_scale = saturation_current_at(1.0, 1.0, eg, xti, t)
+            / saturation_current_at(1.0, 1.0, eg, xti, t0);
+        let beta_scale = (celsius_to_kelvin(t) / celsius_to_kelvin(t0)).powf(xtb);
+        let leakage_scale = |n: f64| is_scale.powf(1.0 / n) / beta_scale;
+        Heated {
+            saturation_current: values.saturation_current * is_scale,
+            beta_forward: values.beta_forward * beta_scale,
+            beta_reverse: values.beta_reverse * beta_scale,
+            leakage_current_be: values.leakage_current_be
+                * leakage_scale(self.emission_coeff_leakage_be),
+            leakage_current_bc: values.leakage_current_bc
+                * leakage_scale(self.emission_coeff_leakage_bc),
+            thermal_voltage: thermal_voltage(t),
+        }
+    }
+
+    /// Temperature (°C) of a self-heating transistor at the point being solved.
+    pub(crate) fn temperature(&self) -> Option<f64> {
+        let heating = self.heating.as_ref()?;
+        Some(heating.temperature + heating.thermal.rise())
+    }
+
+    /// Junction voltages (base-emitter, base-collector) of the intrinsic transistor at `x`.
+    fn junction_voltages(&self, node_mapping: &NodeMapping, x: &[f64]) -> (f64, f64) {
+        let base = node_mapping.mna_node_index(self.base_prime);
+        let collector = node_mapping.mna_node_index(self.collector_prime);
+        let emitter = node_mapping.mna_node_index(self.emitter_prime);
+        (
+            get_voltage_diff(x, base, emitter),
+            get_voltage_diff(x, base, collector),
+        )
+    }
+
+    /// Linearize the Gummel-Poon model at the given junction voltages.
     fn linearize(&self, v_be_node: f64, v_bc_node: f64) -> LinearizedBjt {
         let polarity = self.polarity_sign();
-        let v_be = polarity * v_be_node;
-        let v_bc = polarity * v_bc_node;
+        let heated = self.heated();
+        let vt = heated.thermal_voltage;
+        let v_be = self.clamp_junction(polarity * v_be_node, self.emission_coeff_forward, vt);
+        let v_bc = self.clamp_junction(polarity * v_bc_node, self.emission_coeff_reverse, vt);
 
-        let (i_f, g_f, vbe_eff) = self.junction_values(v_be, self.emission_coeff_forward);
-        let (i_r, g_r, vbc_eff) = self.junction_values(v_bc, self.emission_coeff_reverse);
+        let vbe_eff_node = v_be * polarity;
+        let vbc_eff_node = v_bc * polarity;
 
-        let vbe_eff_node = vbe_eff * polarity;
-        let vbc_eff_node = vbc_eff * polarity;
+        // In the polarity-normalized domain, with the junction diffusion currents
+        //   i_be = IS*(exp(v_BE/(NF*Vt)) - 1),   i_bc = IS*(exp(v_BC/(NR*Vt)) - 1)
+        // and the leakage currents i_le, i_lc (ISE/NE, ISC/NC), the Gummel-Poon currents are
+        //   i_c0 = (i_be - i_bc)/qb - i_bc/BR - i_lc
+        //   i_b0 = i_be/BF + i_le + i_bc/BR + i_lc
+        //   i_e0 = -(i_c0 + i_b0)
+        // where the normalized base charge qb = q1 * (1 + sqrt(1 + 4 q2)) / 2 with
+        //   q1 = 1 / (1 - v_BC/VAF - v_BE/VAR)    (Early effect)
+        //   q2 = i_be/IKF + i_bc/IKR              (high injection)
+        let is = heated.saturation_current;
+        let (i_be, g_be_diff) = Self::diode(v_be, is, self.emission_coeff_forward, vt);
+        let (i_bc, g_bc_diff) = Self::diode(v_bc, is, self.emission_coeff_reverse, vt);
+        let (i_le, g_le) = Self::diode(
+            v_be,
+            heated.leakage_current_be,
+            self.emission_coeff_leakage_be,
+            vt,
+        );
+        let (i_lc, g_lc) = Self::diode(
+            v_bc,
+            heated.leakage_current_bc,
+            self.emission_coeff_leakage_bc,
+            vt,
+        );
 
-        let alpha_f = self.beta_forward / (self.beta_forward + 1.0);
-        let alpha_r = self.beta_reverse / (self.beta_reverse + 1.0);
+        let q1 =
+            1.0 / (1.0 - v_bc * self.inverse_early_forward - v_be * self.inverse_early_reverse);
+        let dq1_dbe = q1 * q1 * self.inverse_early_reverse;
+        let dq1_dbc = q1 * q1 * self.inverse_early_forward;
+        let q2 = i_be * self.inverse_knee_forward + i_bc * self.inverse_knee_reverse;
+        let dq2_dbe = g_be_diff * self.inverse_knee_forward;
+        let dq2_dbc = g_bc_diff * self.inverse_knee_reverse;
+        let root = (1.0 + 4.0 * q2).sqrt();
+        let qb = q1 * (1.0 + root) / 2.0;
+        let dqb_dbe = dq1_dbe * (1.0 + root) / 2.0 + q1 * dq2_dbe / root;
+        let dqb_dbc = dq1_dbc * (1.0 + root) / 2.0 + q1 * dq2_dbc / root;
 
-        // g_f  = d(i_F)/d(v_BE)  where i_F = IS*(exp(v_BE/(NF*Vt)) - 1)
-        // g_r  = d(i_R)/d(v_BC)  where i_R = IS*(exp(v_BC/(NR*Vt)) - 1)
-        //
-        // Ebers–Moll terminal currents sign convention (in the "polarity-normalized" domain):
-        //   i_c0 =  αF * i_F  -  i_R
-        //   i_b0 = (1-αF)*i_F + (1-αR)*i_R
-        //   i_e0 =  -i_F      +  αR * i_R
-        //
-        // Step 1: partial derivatives of terminal currents w.r.t junction voltages.
-        // These are "how much terminal current changes if v_BE changes" etc.
-        let i_c0 = alpha_f * i_f - i_r;
-        let i_b0 = (1.0 - alpha_f) * i_f + (1.0 - alpha_r) * i_r;
-        let i_e0 = -i_f + alpha_r * i_r;
+        let i_t = (i_be - i_bc) / qb;
+        let g_t_be = (g_be_diff - i_t * dqb_dbe) / qb;
+        let g_t_bc = (-g_bc_diff - i_t * dqb_dbc) / qb;
+
+        let (bf, br) = (heated.beta_forward, heated.beta_reverse);
+        let i_c0 = i_t - i_bc / br - i_lc;
+        let i_b0 = i_be / bf + i_le + i_bc / br + i_lc;
+        let i_e0 = -(i_c0 + i_b0);
 
         let i_c = polarity * i_c0;
         let i_e = polarity * i_e0;
         let i_b = polarity * i_b0;
 
-        // ∂Ic/∂vBE = αF * ∂iF/∂vBE = αF * g_f
-        let g_c_be = alpha_f * g_f;
-
-        // ∂Ic/∂vBC = - ∂iR/∂vBC = - g_r
-        let g_c_bc = -g_r;
-
-        // ∂Ib/∂vBE = (1-αF) * g_f
-        let g_b_be = (1.0 - alpha_f) * g_f;
-
-        // ∂Ib/∂vBC = (1-αR) * g_r
-        let g_b_bc = (1.0 - alpha_r) * g_r;
-
-        // ∂Ie/∂vBE = - g_f     (because i_e0 has -i_F term)
-        let g_e_be = -g_f;
-
-        // ∂Ie/∂vBC = αR * g_r
-        let g_e_bc = alpha_r * g_r;
+        // Step 1: partial derivatives of terminal currents w.r.t junction voltages.
+        // The polarity flips both the current and the voltage, so they are the same in the
+        // node domain.
+        let g_c_be = g_t_be;
+        let g_c_bc = g_t_bc - g_bc_diff / br - g_lc;
+        let g_b_be = g_be_diff / bf + g_le;
+        let g_b_bc = g_bc_diff / br + g_lc;
+        let g_e_be = -(g_c_be + g_b_be);
+        let g_e_bc = -(g_c_bc + g_b_bc);
 
         // Step 2: convert junction-voltage derivatives into node-voltage derivatives.
         //
@@ -228,6 +579,18 @@ impl Bjt {
         let i_eq_b = i_b - g_b_be * vbe_eff_node - g_b_bc * vbc_eff_node;
         let i_eq_e = i_e - g_e_be * vbe_eff_node - g_e_bc * vbc_eff_node;
 
+        // the diffusion charges follow the junction currents, the depletion charges the voltages
+        let (q_be, c_be) = self.depletion_be.charge(v_be, self.depletion_coeff);
+        let (q_bc, c_bc) = self.depletion_bc.charge(v_bc, self.depletion_coeff);
+        let charge_be = (
+            q_be + self.transit_time_forward * i_be,
+            c_be + self.transit_time_forward * g_be_diff,
+        );
+        let charge_bc = (
+            q_bc + self.transit_time_reverse * i_bc,
+            c_bc + self.transit_time_reverse * g_bc_diff,
+        );
+
         LinearizedBjt {
             g_bb,
             g_bc,
@@ -241,18 +604,28 @@ impl Bjt {
             i_eq_b,
             i_eq_c,
             i_eq_e,
+            i_b,
+            i_c,
+            v_be: vbe_eff_node,
+            v_bc: vbc_eff_node,
+            charge_be,
+            charge_bc,
         }
     }
 
-    /// Stamp the linearized BJT conductance matrix and RHS into MNA.
+    /// Stamp the linearized BJT conductance matrix and RHS into MNA, with the series
+    /// resistances.
     pub(crate) fn stamp_nonlinear(&self, m: &mut SolverMatrix, guess: &[f64]) {
-        let base = m.mna_node_index(self.base);
-        let collector = m.mna_node_index(self.collector);
-        let emitter = m.mna_node_index(self.emitter);
+        for r in &self.series_resistances {
+            r.stamp(m);
+        }
+
+        let base = m.mna_node_index(self.base_prime);
+        let collector = m.mna_node_index(self.collector_prime);
+        let emitter = m.mna_node_index(self.emitter_prime);
 
         // compute the junctions voltage diffs
-        let v_be = get_voltage_diff(guess, base, emitter);
-        let v_bc = get_voltage_diff(guess, base, collector);
+        let (v_be, v_bc) = self.junction_voltages(m.node_mapping(), guess);
 
         let linearized = self.linearize(v_be, v_bc);
 
@@ -294,4 +667,423 @@ impl Bjt {
             *m.get_mut_rhs(emitter) -= linearized.i_eq_e;
         }
     }
+
+    /// Junction charge currents (polarity-normalized) of a transient step from `previous` to
+    /// `x`, given the integrator's companion `(k, i_hist)` of each junction: the current is
+    /// `k * (q - q_previous) + i_hist`.
+    fn charge_currents(
+        &self,
+        node_mapping: &NodeMapping,
+        x: &[f64],
+        previous: &[f64],
+        companions: [(f64, f64); 2],
+    ) -> (LinearizedBjt, [f64; 2]) {
+        let (v_be, v_bc) = self.junction_voltages(node_mapping, x);
+        let now = self.linearize(v_be, v_bc);
+        let (v_be, v_bc) = self.junction_voltages(node_mapping, previous);
+        let before = self.linearize(v_be, v_bc);
+        let [(k_be, hist_be), (k_bc, hist_bc)] = companions;
+        let currents = [
+            k_be * (now.charge_be.0 - before.charge_be.0) + hist_be,
+            k_bc * (now.charge_bc.0 - before.charge_bc.0) + hist_bc,
+        ];
+        (now, currents)
+    }
+
+    /// Stamp the companion models of the junction charges for a transient step from the
+    /// solution `previous`; see [`Bjt::charge_currents`] for `companions`.
+    pub(crate) fn stamp_charges(
+        &self,
+        m: &mut SolverMatrix,
+        guess: &[f64],
+        previous: &[f64],
+        companions: [(f64, f64); 2],
+    ) {
+        let base = m.mna_node_index(self.base_prime);
+        let collector = m.mna_node_index(self.collector_prime);
+        let emitter = m.mna_node_index(self.emitter_prime);
+        let polarity = self.polarity_sign();
+        let (l, [i_be, i_bc]) = self.charge_currents(m.node_mapping(), guess, previous, companions);
+
+        // each junction is a nonlinear capacitor from the base: g = k * C and the current,
+        // back in the node polarity, linearized at the guess
+        let g_be = companions[0].0 * l.charge_be.1;
+        let g_bc = companions[1].0 * l.charge_bc.1;
+        let i_eq_be = polarity * i_be - g_be * l.v_be;
+        let i_eq_bc = polarity * i_bc - g_bc * l.v_bc;
+
+        let entries = [
+            (self.stamp.bb, g_be + g_bc),
+            (self.stamp.be, -g_be),
+            (self.stamp.eb, -g_be),
+            (self.stamp.ee, g_be),
+            (self.stamp.bc, -g_bc),
+            (self.stamp.cb, -g_bc),
+            (self.stamp.cc, g_bc),
+        ];
+        for (index, g) in entries {
+            if let Some(index) = index {
+                *m.get_mut_nnz(index) += g;
+            }
+        }
+        if let Some(base) = base {
+            *m.get_mut_rhs(base) -= i_eq_be + i_eq_bc;
+        }
+        if let Some(emitter) = emitter {
+            *m.get_mut_rhs(emitter) += i_eq_be;
+        }
+        if let Some(collector) = collector {
+            *m.get_mut_rhs(collector) += i_eq_bc;
+        }
+    }
+
+    /// The junction charge currents at the accepted solution `x` of a transient step, kept by
+    /// the trapezoidal integrator for the next step.
+    pub(crate) fn junction_currents(
+        &self,
+        node_mapping: &NodeMapping,
+        x: &[f64],
+        previous: &[f64],
+        companions: [(f64, f64); 2],
+    ) -> [f64; 2] {
+        self.charge_currents(node_mapping, x, previous, companions)
+            .1
+    }
+
+    /// Power (W) dissipated at the solution `x`, at the terminals so the series resistances
+    /// are included.
+    pub(crate) fn power(&self, node_mapping: &NodeMapping, x: &[f64]) -> f64 {
+        let (v_be, v_bc) = self.junction_voltages(node_mapping, x);
+        let l = self.linearize(v_be, v_bc);
+        let v = |a: NodeIndex| {
+            get_voltage_diff(
+                x,
+                node_mapping.mna_node_index(a),
+                node_mapping.mna_node_index(self.emitter),
+            )
+        };
+        v(self.base) * l.i_b + v(self.collector) * l.i_c
+    }
+
+    /// Junction voltages, terminal currents (into the device), transconductance and junction
+    /// capacitances at the operating point `op`.
+    pub(crate) fn operating_point(
+        &self,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+    ) -> DeviceOperatingPoint {
+        let (v_be, v_bc) = self.junction_voltages(node_mapping, op);
+        let l = self.linearize(v_be, v_bc);
+        let power = self.power(node_mapping, op);
+        let point = DeviceOperatingPoint::new(
+            &self.name,
+            [
+                ("vbe", v_be),
+                ("vbc", v_bc),
+                ("vce", v_be - v_bc),
+                ("ic", l.i_c),
+                ("ib", l.i_b),
+                ("gm", -l.g_ce),
+                ("gpi", -l.g_be),
+                ("cbe", l.charge_be.1),
+                ("cbc", l.charge_bc.1),
+                ("p", power),
+            ],
+        );
+        match self.temperature() {
+            Some(t) => point.with("t", t),
+            None => point,
+        }
+    }
+
+    /// Collector shot noise (collector-emitter), base shot + flicker noise (base-emitter) at
+    /// the operating point `op`, and the thermal noise of the series resistances.
+    pub(crate) fn noise(&self, node_mapping: &NodeMapping, op: &[f64], f: f64) -> Vec<NoiseSource> {
+        let base = node_mapping.mna_node_index(self.base_prime);
+        let collector = node_mapping.mna_node_index(self.collector_prime);
+        let emitter = node_mapping.mna_node_index(self.emitter_prime);
+
+        let (v_be, v_bc) = self.junction_voltages(node_mapping, op);
+        let l = self.linearize(v_be, v_bc);
+        let i_c = l.i_c.abs();
+        let i_b = l.i_b.abs();
+
+        let mut sources = vec![
+            NoiseSource {
+                positive: collector,
+                negative: emitter,
+                density: 2.0 * ELECTRON_CHARGE * i_c,
+            },
+            NoiseSource {
+                positive: base,
+                negative: emitter,
+                density: 2.0 * ELECTRON_CHARGE * i_b + self.kf * i_b.powf(self.af) / f,
+            },
+        ];
+        // 4kT/R, with kT = q * Vt
+        sources.extend(self.series_resistances.iter().map(|r| NoiseSource {
+            positive: node_mapping.mna_node_index(r.positive),
+            negative: node_mapping.mna_node_index(r.negative),
+            density: 4.0 * ELECTRON_CHARGE * self.heated().thermal_voltage * r.conductance,
+        }));
+        sources
+    }
+}
+
+impl SmallSignalModel for Bjt {
+    /// Stamp the small-signal model at the operating point `op`: the conductances into the real
+    /// part and the junction capacitances (`w * C`) into the imaginary part.
+    fn stamp_small_signal(
+        &self,
+        ar: &mut Array2<f64>,
+        ai: &mut Array2<f64>,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+        w: f64,
+    ) {
+        for r in &self.series_resistances {
+            let pos = node_mapping.mna_node_index(r.positive);
+            let neg = node_mapping.mna_node_index(r.negative);
+            stamp_pair(ar, pos, neg, r.conductance);
+        }
+
+        let base = node_mapping.mna_node_index(self.base_prime);
+        let collector = node_mapping.mna_node_index(self.collector_prime);
+        let emitter = node_mapping.mna_node_index(self.emitter_prime);
+
+        let (v_be, v_bc) = self.junction_voltages(node_mapping, op);
+        let l = self.linearize(v_be, v_bc);
+
+        let entries = [
+            (base, base, l.g_bb),
+            (base, collector, l.g_bc),
+            (base, emitter, l.g_be),
+            (collector, base, l.g_cb),
+            (collector, collector, l.g_cc),
+            (collector, emitter, l.g_ce),
+            (emitter, base, l.g_eb),
+            (emitter, collector, l.g_ec),
+            (emitter, emitter, l.g_ee),
+        ];
+        for (row, col, g) in entries {
+            if let (Some(r), Some(c)) = (row, col) {
+                ar[[r, c]] += g;
+            }
+        }
+
+        stamp_pair(ai, base, emitter, w * l.charge_be.1);
+        stamp_pair(ai, base, collector, w * l.charge_bc.1);
+    }
+}
+
+/// Add a two-terminal admittance `g` between `pos` and `neg`.
+pub(crate) fn stamp_pair(a: &mut Array2<f64>, pos: Option<usize>, neg: Option<usize>, g: f64) {
+    if let Some(p) = pos {
+        a[[p, p]] += g;
+    }
+    if let Some(n) = neg {
+        a[[n, n]] += g;
+    }
+    if let (Some(p), Some(n)) = (pos, neg) {
+        a[[p, n]] -= g;
+        a[[n, p]] -= g;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ac::simulate_ac;
+    use crate::dc::simulate_op;
+    use crate::frequency_response::FrequencyResponse;
+    use crate::op_report::OpReport;
+    use crate::test_utils::parse_netlist;
+    use crate::trans::simulate_trans;
+    use crate::{SimulationConfig, TransientIntegrator};
+    use spicy_parser::netlist_types::Command;
+    use std::f64::consts::PI;
+
+    fn bjt(model: &str) -> Bjt {
+        let deck = parse_netlist(&format!("q\n.model q npn({model})\nQ1 c b 0 q\n.end\n"));
+        Bjt::from_spec(&deck.devices.bjts[0], NOMINAL_TEMPERATURE)
+    }
+
+    fn assert_close(actual: f64, expected: f64, rel: f64) {
+        assert!(
+            (actual - expected).abs() <= rel * expected.abs() + 1e-12,
+            "{actual} != {expected}"
+        );
+    }
+
+    #[test]
+    fn early_effect_gives_the_output_a_slope() {
+        let q = bjt("is=1e-15 bf=100 vaf=50");
+        let (v_be, v_ce) = (0.65, 5.0);
+        let active = q.linearize(v_be, v_be - v_ce);
+        let saturated = q.linearize(v_be, v_be - 1.0);
+        // the transport current grows as 1 - vbc/VAF
+        let early = |v_bc: f64| 1.0 - v_bc / 50.0;
+        assert_close(
+            active.i_c / saturated.i_c,
+            early(v_be - v_ce) / early(v_be - 1.0),
+            1e-6,
+        );
+        // the output conductance dIc/dVc is Ic / (VAF + Vce - Vbe) ...
+        assert_close(active.g_cc, active.i_c / (50.0 + v_ce - v_be), 1e-6);
+        // ... while the base current does not depend on it
+        assert_close(active.i_b, saturated.i_b, 1e-6);
+    }
+
+    #[test]
+    fn high_injection_rolls_off_beta_and_leakage_lowers_it() {
+        let q = bjt("is=1e-15 bf=100 ikf=10m ise=1e-13 ne=2");
+        let beta = |v_be: f64| {
+            let l = q.linearize(v_be, v_be - 5.0);
+            l.i_c / l.i_b
+        };
+        let (low, mid, high) = (beta(0.45), beta(0.65), beta(0.85));
+        assert!(mid > 90.0, "{mid}");
+        assert!(low < 0.5 * mid, "leakage at low current: {low}");
+        assert!(high < 0.5 * mid, "roll-off at high current: {high}");
+    }
+
+    #[test]
+    fn jacobian_matches_finite_differences() {
+        let q = bjt("is=1e-15 bf=100 br=2 vaf=50 var=10 ikf=10m ikr=1m ise=1e-13 isc=1e-13");
+        let h = 1e-7;
+        for (v_be, v_bc) in [(0.7, -3.0), (0.7, 0.6), (-0.2, 0.65)] {
+            let l = q.linearize(v_be, v_bc);
+            let dvbe = |f: fn(&LinearizedBjt) -> f64| {
+                (f(&q.linearize(v_be + h, v_bc)) - f(&q.linearize(v_be - h, v_bc))) / (2.0 * h)
+            };
+            let dvbc = |f: fn(&LinearizedBjt) -> f64| {
+                (f(&q.linearize(v_be, v_bc + h)) - f(&q.linearize(v_be, v_bc - h))) / (2.0 * h)
+            };
+            // the emitter is the reference: dI/dVe = -dI/dvbe, dI/dVc = -dI/dvbc
+            assert_close(-dvbe(|l| l.i_c), l.g_ce, 1e-5);
+            assert_close(-dvbc(|l| l.i_c), l.g_cc, 1e-5);
+            assert_close(-dvbe(|l| l.i_b), l.g_be, 1e-5);
+            assert_close(-dvbc(|l| l.i_b), l.g_bc, 1e-5);
+        }
+    }
+
+    #[test]
+    fn junctions_and_leakage_scale_with_temperature() {
+        let deck = parse_netlist(
+            "q\n.model q npn(is=1e-15 ise=1e-14 ne=2 xtb=1.5 cje=1p vje=0.8)\nQ1 c b 0 q\n.end\n",
+        );
+        let nominal = Bjt::from_spec(&deck.devices.bjts[0], NOMINAL_TEMPERATURE);
+        let hot = Bjt::from_spec(&deck.devices.bjts[0], NOMINAL_TEMPERATURE + 100.0);
+        assert_close(nominal.depletion_be.potential, 0.8, 1e-12);
+        assert_close(nominal.leakage_current_be, 1e-14, 1e-12);
+        // the built-in potential drops and the depletion capacitance grows
+        assert!(
+            hot.depletion_be.potential < 0.7,
+            "{}",
+            hot.depletion_be.potential
+        );
+        assert!(hot.depletion_be.capacitance > 1e-12);
+        // ISE(T) = ISE * (IS(T)/IS)^(1/NE) / (T/Tnom)^XTB
+        let ratio =
+            celsius_to_kelvin(NOMINAL_TEMPERATURE + 100.0) / celsius_to_kelvin(NOMINAL_TEMPERATURE);
+        let is_ratio = hot.saturation_current / nominal.saturation_current;
+        assert_close(
+            hot.leakage_current_be,
+            1e-14 * is_ratio.sqrt() / ratio.powf(1.5),
+            1e-9,
+        );
+    }
+
+    #[test]
+    fn base_resistance_sits_on_an_internal_node() {
+        let deck = parse_netlist(
+            "rb
+VCC vcc 0 DC 5
+VB in 0 DC 1
+RS in b 10k
+RC vcc c 1k
+Q1 c b 0 q
+.model q npn(is=1e-15 bf=100 rb=1k)
+.op
+.end
+",
+        );
+        let config = SimulationConfig::default();
+        let op = simulate_op(&deck, &config).expect("op");
+        let report = OpReport::new(&deck, &op, &config);
+        let i_b = report.device("Q1").unwrap().get("ib").unwrap();
+        let v_rb = op.voltage("b").unwrap() - op.voltage("q1#base").unwrap();
+        assert_close(v_rb, i_b * 1e3, 1e-4);
+        assert_close(i_b, (1.0 - op.voltage("b").unwrap()) / 10e3, 1e-4);
+    }
+
+    #[test]
+    fn junction_charge_sets_the_input_pole() {
+        // the collector is held by a source, so only Cbe loads the base: a single pole at
+        // 1 / (2 pi (RS || r_pi) Cbe)
+        let netlist = "input pole
+VCC c 0 DC 5
+VB in 0 DC 0.65 AC 1
+RS in b 1k
+Q1 c b 0 q
+.model q npn(is=1e-15 bf=100 tf=1n cje=10p)
+.ac dec 50 1k 10G
+.end
+";
+        let deck = parse_netlist(netlist);
+        let config = SimulationConfig::default();
+        let op = simulate_op(&deck, &config).expect("op");
+        let q1 = OpReport::new(&deck, &op, &config);
+        let q1 = q1.device("Q1").unwrap();
+        let r_pi = 1.0 / q1.get("gpi").unwrap();
+        let c_be = q1.get("cbe").unwrap();
+        // the diffusion capacitance is TF * gm
+        assert!(c_be > 1e-9 * q1.get("gm").unwrap(), "{c_be}");
+
+        let Some(Command::Ac(ac)) = deck.commands.first() else {
+            panic!("expected .ac");
+        };
+        let sweep = simulate_ac(&deck, ac, &config).expect("ac");
+        let response = FrequencyResponse::from_nodes(&deck, &sweep, "b", Some("in")).unwrap();
+        let resistance = 1e3 * r_pi / (1e3 + r_pi);
+        assert_close(
+            response.bandwidth().unwrap(),
+            1.0 / (2.0 * PI * resistance * c_be),
+            2e-2,
+        );
+    }
+
+    #[test]
+    fn junction_charge_delays_the_base() {
+        let netlist = "base delay
+VCC vcc 0 DC 5
+VB in 0 PULSE(0 1 0 1n 1n 1 2)
+RS in b 10k
+RC vcc c 1k
+Q1 c b 0 q
+.model q npn(is=1e-15 bf=100 tf=1n cje=100p)
+.tran 10n 20u
+.end
+";
+        for integrator in [
+            TransientIntegrator::BackwardEuler,
+            TransientIntegrator::Trapezoidal,
+        ] {
+            let deck = parse_netlist(netlist);
+            let Some(Command::Tran(tran)) = deck.commands.first() else {
+                panic!("expected .tran");
+            };
+            let config = SimulationConfig {
+                integrator,
+                ..Default::default()
+            };
+            let result = simulate_trans(&deck, tran, &config).expect("tran");
+            let base = result.voltage("b").unwrap();
+            let settled = *base.y.last().unwrap();
+            // 10k and about 100 pF take a microsecond to charge
+            assert!(base.at(100e-9).unwrap() < 0.5 * settled, "{integrator:?}");
+            assert!(base.at(5e-6).unwrap() > 0.95 * settled, "{integrator:?}");
+            assert!((0.6..0.8).contains(&settled), "{settled}");
+        }
+    }
 }
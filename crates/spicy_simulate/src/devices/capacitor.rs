@@ -1,3 +1,4 @@
+use super::multiplicity::Multiplicity;
 use super::stamp::NodePairStamp;
 use crate::matrix::SolverMatrix;
 use ndarray::Array2;
@@ -14,11 +15,10 @@ pub struct Capacitor {
     pub span: Span,
     pub positive: NodeIndex,
     pub negative: NodeIndex,
+    /// Capacitance (F) of the `m` copies scaled by `scale` together.
     pub capacitance: f64,
-    #[allow(dead_code)]
-    pub m: f64,
-    #[allow(dead_code)]
-    pub scale: f64,
+    /// `m` and `scale`, already applied to `capacitance`.
+    pub multiplicity: Multiplicity,
     #[allow(dead_code)]
     pub temp: f64,
     #[allow(dead_code)]
@@ -27,7 +27,8 @@ pub struct Capacitor {
     pub tc1: f64,
     #[allow(dead_code)]
     pub tc2: f64,
-    pub ic: f64,
+    /// initial voltage used with UIC
+    pub ic: Option<f64>,
     pub stamp: NodePairStamp,
 }
 
@@ -44,8 +45,7 @@ impl Capacitor {
             })
             .unwrap_or(0.0);
 
-        let m = spec.m.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
-        let scale = spec.scale.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
+        let multiplicity = Multiplicity::from_values(spec.m.as_ref(), spec.scale.as_ref());
 
         let tc1 = spec
             .tc1
@@ -72,16 +72,15 @@ impl Capacitor {
         let temp = spec.temp.as_ref().map(|v| v.get_value()).unwrap_or(27.0);
         let dtemp = spec.dtemp.as_ref().map(|v| v.get_value()).unwrap_or(0.0);
 
-        let ic = spec.ic.as_ref().map(|v| v.get_value()).unwrap_or(0.0);
+        let ic = spec.ic.as_ref().map(|v| v.get_value());
 
         Self {
             name: spec.name.clone(),
             span: spec.span,
             positive: spec.positive,
             negative: spec.negative,
-            capacitance,
-            m,
-            scale,
+            capacitance: multiplicity.parallel(capacitance),
+            multiplicity,
             temp,
             dtemp,
             tc1,
@@ -1,12 +1,36 @@
+use super::NOMINAL_TEMPERATURE;
+use super::multiplicity::Multiplicity;
+use super::small_signal::SmallSignalModel;
 use super::stamp::NodePairStamp;
 use crate::matrix::SolverMatrix;
+use crate::noise::{ELECTRON_CHARGE, NoiseSource, celsius_to_kelvin};
+use crate::op_report::DeviceOperatingPoint;
 use crate::util::get_voltage_diff;
+use ndarray::Array2;
 use spicy_parser::Span;
 use spicy_parser::devices::DiodeSpec;
 use spicy_parser::netlist_types::NodeIndex;
+use spicy_parser::node_mapping::NodeMapping;
 
 const DEFAULT_THERMAL_VOLTAGE: f64 = 0.02585;
 const DEFAULT_EXP_LIMIT: f64 = 40.0;
+/// Silicon bandgap (eV).
+const DEFAULT_ENERGY_GAP: f64 = 1.11;
+const DEFAULT_SATURATION_CURRENT_EXPONENT: f64 = 3.0;
+
+/// Thermal voltage at `temperature` (°C), `DEFAULT_THERMAL_VOLTAGE` at the nominal temperature.
+pub(crate) fn thermal_voltage(temperature: f64) -> f64 {
+    DEFAULT_THERMAL_VOLTAGE * celsius_to_kelvin(temperature)
+        / celsius_to_kelvin(NOMINAL_TEMPERATURE)
+}
+
+/// Saturation current `is` (given at the nominal temperature) at `temperature` (°C):
+/// Is(T) = Is * (T/Tnom)^(xti/n) * exp((T/Tnom - 1) * eg / (n * Vt(T))).
+pub(crate) fn saturation_current_at(is: f64, n: f64, eg: f64, xti: f64, temperature: f64) -> f64 {
+    let ratio = celsius_to_kelvin(temperature) / celsius_to_kelvin(NOMINAL_TEMPERATURE);
+    let nvt = n * thermal_voltage(temperature);
+    is * ratio.powf(xti / n) * ((ratio - 1.0) * eg / nvt).exp()
+}
 
 #[derive(Debug, Clone)]
 pub struct Diode {
@@ -17,14 +41,13 @@ pub struct Diode {
     pub span: Span,
     pub positive: NodeIndex,
     pub negative: NodeIndex,
-    // saturation current (A)
+    // saturation current (A) at the device temperature, of the `m` copies of `area` together
     pub saturation_current: f64,
     // emission coefficient (dimensionless)
     pub emission_coeff: f64,
+    /// `m` and `area`, already applied to the currents and the series resistance.
     #[allow(dead_code)]
-    pub area: f64,
-    #[allow(dead_code)]
-    pub m: f64,
+    pub multiplicity: Multiplicity,
     /// Thermal voltage (Vt) used in exp(Vd / (n * Vt)).
     pub thermal_voltage: f64,
     /// Clamp limit for Vd/(n*Vt) to keep exp() bounded.
@@ -36,11 +59,17 @@ pub struct Diode {
     /// Series resistance (Ohms) parsed but not used yet.
     #[allow(dead_code)]
     pub series_resistance: f64,
+    /// Flicker noise coefficient.
+    pub kf: f64,
+    /// Flicker noise exponent.
+    pub af: f64,
     pub stamp: NodePairStamp,
 }
 
 impl Diode {
-    pub fn from_spec(spec: &DiodeSpec) -> Self {
+    /// Compile a parsed diode at the circuit `temperature` (°C); the instance `temp` overrides
+    /// it and `dtemp` offsets it.
+    pub fn from_spec(spec: &DiodeSpec, temperature: f64) -> Self {
         let saturation_current = spec
             .model
             .is
@@ -60,10 +89,37 @@ impl Diode {
             .map(|v| v.get_value())
             .unwrap_or(0.0);
 
-        let area = spec.area.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
-        let m = spec.m.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
+        let multiplicity = Multiplicity::from_values(spec.m.as_ref(), spec.area.as_ref());
         let off = spec.off.unwrap_or(false);
         let ic = spec.ic.as_ref().map(|v| v.get_value()).unwrap_or(0.0);
+        let kf = spec.model.kf.as_ref().map(|v| v.get_value()).unwrap_or(0.0);
+        let af = spec.model.af.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
+
+        let dtemp = spec.dtemp.as_ref().map(|v| v.get_value()).unwrap_or(0.0);
+        let temp = spec
+            .temp
+            .as_ref()
+            .map(|v| v.get_value())
+            .unwrap_or(temperature + dtemp);
+        let eg = spec
+            .model
+            .eg
+            .as_ref()
+            .map(|v| v.get_value())
+            .unwrap_or(DEFAULT_ENERGY_GAP);
+        let xti = spec
+            .model
+            .xti
+            .as_ref()
+            .map(|v| v.get_value())
+            .unwrap_or(DEFAULT_SATURATION_CURRENT_EXPONENT);
+        let saturation_current = multiplicity.parallel(saturation_current_at(
+            saturation_current,
+            emission_coeff,
+            eg,
+            xti,
+            temp,
+        ));
 
         Self {
             name: spec.name.clone(),
@@ -72,13 +128,14 @@ impl Diode {
             negative: spec.negative,
             saturation_current,
             emission_coeff,
-            area,
-            m,
-            thermal_voltage: DEFAULT_THERMAL_VOLTAGE,
+            multiplicity,
+            thermal_voltage: thermal_voltage(temp),
             exp_limit: DEFAULT_EXP_LIMIT,
             off,
             ic,
-            series_resistance,
+            series_resistance: multiplicity.divided(series_resistance),
+            kf,
+            af,
             stamp: NodePairStamp::uninitialized(),
         }
     }
@@ -87,14 +144,14 @@ impl Diode {
     // - Vd: diode voltage (pos - neg) from the current Newton guess.
     // - Is: saturation current from the model, scaled by area * m.
     // - n: emission coefficient (ideality factor), dimensionless.
-    // - Vt: thermal voltage (model parameter, defaulted for now).
+    // - Vt: thermal voltage kT/q at the device temperature.
     // For Newton, we linearize around Vd with:
     // - g = dI/dV: small-signal conductance - derivative around the guess.
     // - Ieq = I - g * Vd: equivalent current source for MNA.
     // this converts the non linear equation to the first order Taylor series approximation
     // i(v) ~ i(v_guess) + g * (v - v_guess)
     // Vd is clamped by exp_limit to keep exp() in a safe range.
-    fn linearize(&self, v_d: f64) -> (f64, f64) {
+    pub(crate) fn linearize(&self, v_d: f64) -> (f64, f64) {
         let n = self.emission_coeff;
         let nvt = n * self.thermal_voltage;
         let isat = self.saturation_current;
@@ -143,4 +200,63 @@ impl Diode {
             *m.get_mut_rhs(neg) += i_eq;
         }
     }
+
+    /// Junction voltage, current and small-signal conductance at the operating point `op`.
+    pub(crate) fn operating_point(
+        &self,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+    ) -> DeviceOperatingPoint {
+        let pos = node_mapping.mna_node_index(self.positive);
+        let neg = node_mapping.mna_node_index(self.negative);
+        let v_d = get_voltage_diff(op, pos, neg);
+        let (g, i_eq) = self.linearize(v_d);
+        let i_d = i_eq + g * v_d;
+        DeviceOperatingPoint::new(
+            &self.name,
+            [("vd", v_d), ("id", i_d), ("gd", g), ("p", v_d * i_d)],
+        )
+    }
+
+    /// Shot and flicker noise of the junction current at the operating point `op`:
+    /// 2q|Id| + KF*|Id|^AF/f.
+    pub(crate) fn noise(&self, node_mapping: &NodeMapping, op: &[f64], f: f64) -> NoiseSource {
+        let pos = node_mapping.mna_node_index(self.positive);
+        let neg = node_mapping.mna_node_index(self.negative);
+        let v_d = get_voltage_diff(op, pos, neg);
+        let (g, i_eq) = self.linearize(v_d);
+        let i_d = (i_eq + g * v_d).abs();
+        NoiseSource {
+            positive: pos,
+            negative: neg,
+            density: 2.0 * ELECTRON_CHARGE * i_d + self.kf * i_d.powf(self.af) / f,
+        }
+    }
+}
+
+impl SmallSignalModel for Diode {
+    /// Stamp the small-signal conductance at the operating point `op` into the real part matrix.
+    fn stamp_small_signal(
+        &self,
+        ar: &mut Array2<f64>,
+        _ai: &mut Array2<f64>,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+        _w: f64,
+    ) {
+        let pos = node_mapping.mna_node_index(self.positive);
+        let neg = node_mapping.mna_node_index(self.negative);
+        let (g, _) = self.linearize(get_voltage_diff(op, pos, neg));
+
+        if let Some(p) = pos {
+            ar[[p, p]] += g;
+        }
+        if let Some(n) = neg {
+            ar[[n, n]] += g;
+        }
+        if let (Some(p), Some(n)) = (pos, neg) {
+            ar[[p, n]] -= g;
+            ar[[n, p]] -= g;
+        }
+    }
 }
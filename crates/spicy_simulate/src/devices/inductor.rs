@@ -1,3 +1,4 @@
+use super::multiplicity::Multiplicity;
 use super::stamp::NodeBranchPairStamp;
 use crate::matrix::SolverMatrix;
 use ndarray::Array2;
@@ -16,13 +17,12 @@ pub struct Inductor {
     pub positive: NodeIndex,
     pub negative: NodeIndex,
     pub current_branch: CurrentBranchIndex,
+    /// Inductance (H) of the `m` copies scaled by `scale` together.
     pub inductance: f64,
     #[allow(dead_code)]
     pub nt: f64,
-    #[allow(dead_code)]
-    pub m: f64,
-    #[allow(dead_code)]
-    pub scale: f64,
+    /// `m` and `scale`, already applied to `inductance`.
+    pub multiplicity: Multiplicity,
     #[allow(dead_code)]
     pub temp: f64,
     #[allow(dead_code)]
@@ -32,7 +32,8 @@ pub struct Inductor {
     #[allow(dead_code)]
     pub tc2: f64,
     #[allow(dead_code)]
-    pub ic: f64,
+    /// initial current used with UIC
+    pub ic: Option<f64>,
     pub stamp: NodeBranchPairStamp,
 }
 
@@ -50,8 +51,7 @@ impl Inductor {
             .unwrap_or(0.0);
 
         let nt = spec.nt.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
-        let m = spec.m.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
-        let scale = spec.scale.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
+        let multiplicity = Multiplicity::from_values(spec.m.as_ref(), spec.scale.as_ref());
 
         let tc1 = spec
             .tc1
@@ -78,7 +78,7 @@ impl Inductor {
         let temp = spec.temp.as_ref().map(|v| v.get_value()).unwrap_or(27.0);
         let dtemp = spec.dtemp.as_ref().map(|v| v.get_value()).unwrap_or(0.0);
 
-        let ic = spec.ic.as_ref().map(|v| v.get_value()).unwrap_or(0.0);
+        let ic = spec.ic.as_ref().map(|v| v.get_value());
 
         Self {
             name: spec.name.clone(),
@@ -86,10 +86,9 @@ impl Inductor {
             positive: spec.positive,
             negative: spec.negative,
             current_branch: spec.current_branch,
-            inductance,
+            inductance: multiplicity.series(inductance),
             nt,
-            m,
-            scale,
+            multiplicity,
             temp,
             dtemp,
             tc1,
@@ -102,9 +101,12 @@ impl Inductor {
     /// Stamp DC MNA contributions for an inductor.
     ///
     /// In DC, an ideal inductor is a short circuit enforced via a branch current unknown and a
-    /// KVL equation with zero RHS (similar to a 0V voltage source).
+    /// KVL equation with zero RHS (similar to a 0V voltage source). Nothing is stamped for an
+    /// inductor collapsed into a short of its nodes, which has no branch.
     pub(crate) fn stamp_dc(&self, m: &mut SolverMatrix) {
-        let src_index = m.mna_branch_index(self.current_branch);
+        let Some(src_index) = m.node_mapping().try_mna_branch_index(self.current_branch) else {
+            return;
+        };
 
         if let Some((pos_branch, branch_pos)) = self.stamp.pos_branch {
             // stamp in voltage incidence matrix (B)
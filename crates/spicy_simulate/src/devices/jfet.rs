@@ -0,0 +1,646 @@
+//! Shichman-Hodges JFET model (NJF/PJF).
+//!
+//! Square-law drain current with channel-length modulation (LAMBDA), symmetric in drain and
+//! source. The gate-source and gate-drain junctions are diodes (IS/N) with depletion
+//! capacitances (CGS/CGD, grading 0.5), used by the transient and AC analyses. RD/RS sit
+//! between the terminals and internal nodes allocated by the parser.
+use super::bjt::{DepletionCapacitance, SeriesResistance, stamp_pair};
+use super::diode::{saturation_current_at, thermal_voltage};
+use super::multiplicity::Multiplicity;
+use super::small_signal::SmallSignalModel;
+use super::stamp::{NodePairStamp, NodeTripletStamp};
+use crate::matrix::SolverMatrix;
+use crate::noise::{ELECTRON_CHARGE, NoiseSource};
+use crate::op_report::DeviceOperatingPoint;
+use crate::util::get_voltage_diff;
+use ndarray::Array2;
+use spicy_parser::JfetPolarity;
+use spicy_parser::Span;
+use spicy_parser::Value;
+use spicy_parser::devices::JfetSpec;
+use spicy_parser::netlist_types::NodeIndex;
+use spicy_parser::node_mapping::NodeMapping;
+
+const DEFAULT_EXP_LIMIT: f64 = 40.0;
+/// Silicon bandgap (eV).
+const DEFAULT_ENERGY_GAP: f64 = 1.11;
+const DEFAULT_SATURATION_CURRENT_EXPONENT: f64 = 3.0;
+/// The gate junctions are abrupt.
+const GRADING_COEFF: f64 = 0.5;
+/// Conductance across the channel and the junctions so a device in cutoff does not leave its
+/// nodes floating.
+const GMIN: f64 = 1e-12;
+
+/// Terminal rows and columns of `LinearizedJfet`, in the base, collector and emitter slots of
+/// the `NodeTripletStamp`.
+const GATE: usize = 0;
+const DRAIN: usize = 1;
+const SOURCE: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct Jfet {
+    pub name: String,
+    #[allow(dead_code)]
+    pub span: Span,
+    // The terminals, only connected through `series_resistances`.
+    #[allow(dead_code)]
+    pub drain: NodeIndex,
+    pub gate: NodeIndex,
+    #[allow(dead_code)]
+    pub source: NodeIndex,
+    /// The intrinsic transistor's drain and source, behind the series resistances.
+    pub drain_prime: NodeIndex,
+    pub source_prime: NodeIndex,
+    pub polarity: JfetPolarity,
+    /// Threshold voltage (V), negative for a typical NJF.
+    pub vto: f64,
+    /// Transconductance parameter (A/V^2), times the area.
+    pub beta: f64,
+    /// Channel-length modulation (1/V).
+    pub lambda: f64,
+    /// Gate junction saturation current (A) at the circuit temperature, times the area.
+    pub saturation_current: f64,
+    /// Gate junction emission coefficient.
+    pub emission_coeff: f64,
+    pub depletion_gs: DepletionCapacitance,
+    pub depletion_gd: DepletionCapacitance,
+    /// Fraction of the built-in potential above which the depletion capacitances are linear.
+    pub depletion_coeff: f64,
+    /// RD and RS, when given.
+    pub series_resistances: Vec<SeriesResistance>,
+    /// Thermal voltage (Vt) used in exp(V / (n * Vt)).
+    pub thermal_voltage: f64,
+    /// Clamp limit for V/Vt to keep exp() bounded.
+    pub exp_limit: f64,
+    #[allow(dead_code)]
+    pub off: bool,
+    #[allow(dead_code)]
+    pub ic_vds: f64,
+    #[allow(dead_code)]
+    pub ic_vgs: f64,
+    /// Flicker noise coefficient (drain current).
+    pub kf: f64,
+    /// Flicker noise exponent (drain current).
+    pub af: f64,
+    /// Stamp of the intrinsic transistor with the gate, drain and source in the base,
+    /// collector and emitter slots.
+    pub stamp: NodeTripletStamp,
+}
+
+/// The JFET linearized at a set of junction voltages, in the node polarity.
+#[derive(Debug, Clone, Copy)]
+struct LinearizedJfet {
+    /// `g[row][col]`: derivative of the current into terminal `row` by the voltage of `col`.
+    g: [[f64; 3]; 3],
+    /// Equivalent currents of the Norton companion, per terminal.
+    i_eq: [f64; 3],
+    /// Current into each terminal at the linearization point.
+    i: [f64; 3],
+    /// Clamped junction voltages the currents were evaluated at.
+    v_gs: f64,
+    v_gd: f64,
+    /// Charge and capacitance of the gate-source junction (polarity-normalized).
+    charge_gs: (f64, f64),
+    /// Charge and capacitance of the gate-drain junction (polarity-normalized).
+    charge_gd: (f64, f64),
+}
+
+impl Jfet {
+    /// Compile a parsed JFET at the circuit `temperature` (°C).
+    pub fn from_spec(spec: &JfetSpec, temperature: f64) -> Self {
+        let model = &spec.model;
+        let value_or = |value: &Option<Value>, default: f64| {
+            value.as_ref().map(|v| v.get_value()).unwrap_or(default)
+        };
+        let area = Multiplicity::from_values(None, spec.area.as_ref());
+        let emission_coeff = value_or(&model.n, 1.0);
+        let saturation_current = saturation_current_at(
+            value_or(&model.is, 1e-14),
+            emission_coeff,
+            DEFAULT_ENERGY_GAP,
+            DEFAULT_SATURATION_CURRENT_EXPONENT,
+            temperature,
+        );
+        let potential = value_or(&model.pb, 1.0);
+        let depletion = |capacitance: &Option<Value>| {
+            DepletionCapacitance::new(
+                area.parallel(value_or(capacitance, 0.0)),
+                potential,
+                GRADING_COEFF,
+                DEFAULT_ENERGY_GAP,
+                temperature,
+            )
+        };
+
+        // the series resistances shrink with the area
+        let series_resistances = [
+            (spec.drain, spec.drain_prime, &model.rd),
+            (spec.source, spec.source_prime, &model.rs),
+        ]
+        .into_iter()
+        .filter(|(external, internal, _)| external != internal)
+        .map(|(positive, negative, resistance)| SeriesResistance {
+            positive,
+            negative,
+            conductance: area.parallel(1.0 / value_or(resistance, 0.0)),
+            stamp: NodePairStamp::uninitialized(),
+        })
+        .collect();
+
+        Self {
+            name: spec.name.clone(),
+            span: spec.span,
+            drain: spec.drain,
+            gate: spec.gate,
+            source: spec.source,
+            drain_prime: spec.drain_prime,
+            source_prime: spec.source_prime,
+            polarity: model.polarity,
+            vto: value_or(&model.vto, -2.0),
+            beta: area.parallel(value_or(&model.beta, 1e-4)),
+            lambda: value_or(&model.lambda, 0.0),
+            saturation_current: area.parallel(saturation_current),
+            emission_coeff,
+            depletion_gs: depletion(&model.cgs),
+            depletion_gd: depletion(&model.cgd),
+            depletion_coeff: value_or(&model.fc, 0.5),
+            series_resistances,
+            thermal_voltage: thermal_voltage(temperature),
+            exp_limit: DEFAULT_EXP_LIMIT,
+            off: spec.off.unwrap_or(false),
+            ic_vds: spec.ic_vds.as_ref().map(|v| v.get_value()).unwrap_or(0.0),
+            ic_vgs: spec.ic_vgs.as_ref().map(|v| v.get_value()).unwrap_or(0.0),
+            kf: value_or(&model.kf, 0.0),
+            af: value_or(&model.af, 1.0),
+            stamp: NodeTripletStamp::uninitialized(),
+        }
+    }
+
+    /// Return +1 for NJF, -1 for PJF.
+    fn polarity_sign(&self) -> f64 {
+        match self.polarity {
+            JfetPolarity::Njf => 1.0,
+            JfetPolarity::Pjf => -1.0,
+        }
+    }
+
+    /// Whether the gate junctions store any charge, i.e. the device has a transient companion
+    /// model.
+    pub(crate) fn stores_charge(&self) -> bool {
+        self.depletion_gs.capacitance != 0.0 || self.depletion_gd.capacitance != 0.0
+    }
+
+    /// The intrinsic gate, drain and source in MNA order.
+    fn nodes(&self, node_mapping: &NodeMapping) -> [Option<usize>; 3] {
+        [self.gate, self.drain_prime, self.source_prime].map(|n| node_mapping.mna_node_index(n))
+    }
+
+    /// Junction voltages (gate-source, gate-drain) of the intrinsic transistor at `x`.
+    fn junction_voltages(&self, node_mapping: &NodeMapping, x: &[f64]) -> (f64, f64) {
+        let [gate, drain, source] = self.nodes(node_mapping);
+        (
+            get_voltage_diff(x, gate, source),
+            get_voltage_diff(x, gate, drain),
+        )
+    }
+
+    /// Drain current of a channel in normal mode (`vds >= 0`), with its derivatives `gm`
+    /// (by `vgs`) and `gds` (by `vds`).
+    fn channel(&self, vgs: f64, vds: f64) -> (f64, f64, f64) {
+        let vgst = vgs - self.vto;
+        if vgst <= 0.0 {
+            return (0.0, 0.0, 0.0);
+        }
+        let clm = 1.0 + self.lambda * vds;
+        if vds >= vgst {
+            // saturation
+            let id0 = self.beta * vgst * vgst;
+            (id0 * clm, 2.0 * self.beta * vgst * clm, id0 * self.lambda)
+        } else {
+            // linear region
+            let id0 = self.beta * vds * (2.0 * vgst - vds);
+            (
+                id0 * clm,
+                2.0 * self.beta * vds * clm,
+                2.0 * self.beta * (vgst - vds) * clm + id0 * self.lambda,
+            )
+        }
+    }
+
+    /// Current and conductance of a gate junction, with GMIN across it.
+    fn junction(&self, v: f64) -> (f64, f64) {
+        let nvt = self.emission_coeff * self.thermal_voltage;
+        let x = v / nvt;
+        (
+            self.saturation_current * x.exp_m1() + GMIN * v,
+            self.saturation_current * x.exp() / nvt + GMIN,
+        )
+    }
+
+    /// Linearize the model at the given junction voltages.
+    fn linearize(&self, v_gs_node: f64, v_gd_node: f64) -> LinearizedJfet {
+        let polarity = self.polarity_sign();
+        // clamp forward-biased junctions so exp() stays bounded
+        let v_limit = self.exp_limit * self.emission_coeff * self.thermal_voltage;
+        let v_gs = (polarity * v_gs_node).min(v_limit);
+        let v_gd = (polarity * v_gd_node).min(v_limit);
+        let v_ds = v_gs - v_gd;
+
+        // channel current from drain to source and its derivatives by v_GS and v_GD; with
+        // v_DS < 0 the drain acts as the source
+        let (i_ch, dch_gs, dch_gd) = if v_ds >= 0.0 {
+            let (id, gm, gds) = self.channel(v_gs, v_ds);
+            (id, gm + gds, -gds)
+        } else {
+            let (id, gm, gds) = self.channel(v_gd, -v_ds);
+            (-id, gds, -(gm + gds))
+        };
+        let (i_ch, dch_gs, dch_gd) = (i_ch + GMIN * v_ds, dch_gs + GMIN, dch_gd - GMIN);
+        let (i_gs, g_gs) = self.junction(v_gs);
+        let (i_gd, g_gd) = self.junction(v_gd);
+
+        // currents into the gate, drain and source and their derivatives by (v_GS, v_GD); the
+        // polarity flips both the currents and the voltages, so they hold in the node domain
+        let currents = [i_gs + i_gd, i_ch - i_gd, -i_ch - i_gs];
+        let by_junction = [
+            (g_gs, g_gd),
+            (dch_gs, dch_gd - g_gd),
+            (-dch_gs - g_gs, -dch_gd),
+        ];
+
+        let (v_gs_node, v_gd_node) = (polarity * v_gs, polarity * v_gd);
+        let mut g = [[0.0; 3]; 3];
+        let mut i = [0.0; 3];
+        let mut i_eq = [0.0; 3];
+        for (row, (d_gs, d_gd)) in by_junction.into_iter().enumerate() {
+            // v_GS = Vg - Vs, v_GD = Vg - Vd
+            g[row][GATE] = d_gs + d_gd;
+            g[row][DRAIN] = -d_gd;
+            g[row][SOURCE] = -d_gs;
+            i[row] = polarity * currents[row];
+            i_eq[row] = i[row] - d_gs * v_gs_node - d_gd * v_gd_node;
+        }
+
+        LinearizedJfet {
+            g,
+            i_eq,
+            i,
+            v_gs: v_gs_node,
+            v_gd: v_gd_node,
+            charge_gs: self.depletion_gs.charge(v_gs, self.depletion_coeff),
+            charge_gd: self.depletion_gd.charge(v_gd, self.depletion_coeff),
+        }
+    }
+
+    /// Stamp indices as `[row][col]` in `GATE`, `DRAIN`, `SOURCE` order.
+    fn stamp_indices(&self) -> [[Option<usize>; 3]; 3] {
+        let s = &self.stamp;
+        [[s.bb, s.bc, s.be], [s.cb, s.cc, s.ce], [s.eb, s.ec, s.ee]]
+    }
+
+    /// Stamp the linearized JFET and its series resistances into MNA.
+    pub(crate) fn stamp_nonlinear(&self, m: &mut SolverMatrix, guess: &[f64]) {
+        for r in &self.series_resistances {
+            r.stamp(m);
+        }
+
+        let nodes = self.nodes(m.node_mapping());
+        let (v_gs, v_gd) = self.junction_voltages(m.node_mapping(), guess);
+        let l = self.linearize(v_gs, v_gd);
+
+        for (row, indices) in self.stamp_indices().into_iter().enumerate() {
+            for (col, index) in indices.into_iter().enumerate() {
+                if let Some(index) = index {
+                    *m.get_mut_nnz(index) += l.g[row][col];
+                }
+            }
+            if let Some(node) = nodes[row] {
+                *m.get_mut_rhs(node) -= l.i_eq[row];
+            }
+        }
+    }
+
+    /// Junction charge currents (polarity-normalized) of a transient step from `previous` to
+    /// `x`, given the integrator's companion `(k, i_hist)` of each junction: the current is
+    /// `k * (q - q_previous) + i_hist`.
+    fn charge_currents(
+        &self,
+        node_mapping: &NodeMapping,
+        x: &[f64],
+        previous: &[f64],
+        companions: [(f64, f64); 2],
+    ) -> (LinearizedJfet, [f64; 2]) {
+        let (v_gs, v_gd) = self.junction_voltages(node_mapping, x);
+        let now = self.linearize(v_gs, v_gd);
+        let (v_gs, v_gd) = self.junction_voltages(node_mapping, previous);
+        let before = self.linearize(v_gs, v_gd);
+        let [(k_gs, hist_gs), (k_gd, hist_gd)] = companions;
+        let currents = [
+            k_gs * (now.charge_gs.0 - before.charge_gs.0) + hist_gs,
+            k_gd * (now.charge_gd.0 - before.charge_gd.0) + hist_gd,
+        ];
+        (now, currents)
+    }
+
+    /// Stamp the companion models of the gate charges for a transient step from the solution
+    /// `previous`; see [`Jfet::charge_currents`] for `companions`.
+    pub(crate) fn stamp_charges(
+        &self,
+        m: &mut SolverMatrix,
+        guess: &[f64],
+        previous: &[f64],
+        companions: [(f64, f64); 2],
+    ) {
+        let [gate, drain, source] = self.nodes(m.node_mapping());
+        let polarity = self.polarity_sign();
+        let (l, [i_gs, i_gd]) = self.charge_currents(m.node_mapping(), guess, previous, companions);
+
+        // each junction is a nonlinear capacitor from the gate, linearized at the guess
+        let g_gs = companions[0].0 * l.charge_gs.1;
+        let g_gd = companions[1].0 * l.charge_gd.1;
+        let i_eq_gs = polarity * i_gs - g_gs * l.v_gs;
+        let i_eq_gd = polarity * i_gd - g_gd * l.v_gd;
+
+        let entries = [
+            (self.stamp.bb, g_gs + g_gd),
+            (self.stamp.be, -g_gs),
+            (self.stamp.eb, -g_gs),
+            (self.stamp.ee, g_gs),
+            (self.stamp.bc, -g_gd),
+            (self.stamp.cb, -g_gd),
+            (self.stamp.cc, g_gd),
+        ];
+        for (index, g) in entries {
+            if let Some(index) = index {
+                *m.get_mut_nnz(index) += g;
+            }
+        }
+        if let Some(gate) = gate {
+            *m.get_mut_rhs(gate) -= i_eq_gs + i_eq_gd;
+        }
+        if let Some(source) = source {
+            *m.get_mut_rhs(source) += i_eq_gs;
+        }
+        if let Some(drain) = drain {
+            *m.get_mut_rhs(drain) += i_eq_gd;
+        }
+    }
+
+    /// The gate charge currents at the accepted solution `x` of a transient step, kept by the
+    /// trapezoidal integrator for the next step.
+    pub(crate) fn junction_currents(
+        &self,
+        node_mapping: &NodeMapping,
+        x: &[f64],
+        previous: &[f64],
+        companions: [(f64, f64); 2],
+    ) -> [f64; 2] {
+        self.charge_currents(node_mapping, x, previous, companions)
+            .1
+    }
+
+    /// Terminal voltages and currents (into the device), small-signal conductances and gate
+    /// capacitances at the operating point `op`.
+    pub(crate) fn operating_point(
+        &self,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+    ) -> DeviceOperatingPoint {
+        let (v_gs, v_gd) = self.junction_voltages(node_mapping, op);
+        let l = self.linearize(v_gs, v_gd);
+        // at the terminals, so the series resistances are included
+        let v = |a: NodeIndex| {
+            get_voltage_diff(
+                op,
+                node_mapping.mna_node_index(a),
+                node_mapping.mna_node_index(self.source),
+            )
+        };
+        let power = v(self.drain) * l.i[DRAIN] + v(self.gate) * l.i[GATE];
+        DeviceOperatingPoint::new(
+            &self.name,
+            [
+                ("vgs", v_gs),
+                ("vds", v_gs - v_gd),
+                ("id", l.i[DRAIN]),
+                ("ig", l.i[GATE]),
+                ("gm", l.g[DRAIN][GATE]),
+                ("gds", l.g[DRAIN][DRAIN]),
+                ("cgs", l.charge_gs.1),
+                ("cgd", l.charge_gd.1),
+                ("p", power),
+            ],
+        )
+    }
+
+    /// Channel thermal and flicker noise (drain-source) at the operating point `op`, and the
+    /// thermal noise of the series resistances.
+    pub(crate) fn noise(&self, node_mapping: &NodeMapping, op: &[f64], f: f64) -> Vec<NoiseSource> {
+        let nodes = self.nodes(node_mapping);
+        let (v_gs, v_gd) = self.junction_voltages(node_mapping, op);
+        let l = self.linearize(v_gs, v_gd);
+        let gm = l.g[DRAIN][GATE].abs();
+        let i_d = l.i[DRAIN].abs();
+        // kT = q * Vt
+        let kt = ELECTRON_CHARGE * self.thermal_voltage;
+
+        let mut sources = vec![NoiseSource {
+            positive: nodes[DRAIN],
+            negative: nodes[SOURCE],
+            density: 8.0 / 3.0 * kt * gm + self.kf * i_d.powf(self.af) / f,
+        }];
+        sources.extend(self.series_resistances.iter().map(|r| NoiseSource {
+            positive: node_mapping.mna_node_index(r.positive),
+            negative: node_mapping.mna_node_index(r.negative),
+            density: 4.0 * kt * r.conductance,
+        }));
+        sources
+    }
+}
+
+impl SmallSignalModel for Jfet {
+    /// Stamp the small-signal model at the operating point `op`: the conductances into the real
+    /// part and the gate capacitances (`w * C`) into the imaginary part.
+    fn stamp_small_signal(
+        &self,
+        ar: &mut Array2<f64>,
+        ai: &mut Array2<f64>,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+        w: f64,
+    ) {
+        for r in &self.series_resistances {
+            let pos = node_mapping.mna_node_index(r.positive);
+            let neg = node_mapping.mna_node_index(r.negative);
+            stamp_pair(ar, pos, neg, r.conductance);
+        }
+
+        let nodes = self.nodes(node_mapping);
+        let (v_gs, v_gd) = self.junction_voltages(node_mapping, op);
+        let l = self.linearize(v_gs, v_gd);
+        for (row, row_node) in nodes.into_iter().enumerate() {
+            for (col, col_node) in nodes.into_iter().enumerate() {
+                if let (Some(r), Some(c)) = (row_node, col_node) {
+                    ar[[r, c]] += l.g[row][col];
+                }
+            }
+        }
+
+        stamp_pair(ai, nodes[GATE], nodes[SOURCE], w * l.charge_gs.1);
+        stamp_pair(ai, nodes[GATE], nodes[DRAIN], w * l.charge_gd.1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ac::simulate_ac;
+    use crate::dc::simulate_op;
+    use crate::devices::NOMINAL_TEMPERATURE;
+    use crate::frequency_response::FrequencyResponse;
+    use crate::op_report::OpReport;
+    use crate::test_utils::parse_netlist;
+    use crate::trans::simulate_trans;
+    use crate::{SimulationConfig, TransientIntegrator};
+    use spicy_parser::netlist_types::Command;
+
+    fn jfet(model: &str) -> Jfet {
+        let deck = parse_netlist(&format!("j\n.model j {model}\nJ1 d g 0 j\n.end\n"));
+        Jfet::from_spec(&deck.devices.jfets[0], NOMINAL_TEMPERATURE)
+    }
+
+    fn assert_close(actual: f64, expected: f64, rel: f64) {
+        assert!(
+            (actual - expected).abs() <= rel * expected.abs() + 1e-12,
+            "{actual} != {expected}"
+        );
+    }
+
+    #[test]
+    fn square_law_in_saturation_and_linear_region() {
+        let j = jfet("njf(vto=-2 beta=1m lambda=20m)");
+        // vgs = -1, vds = 5: saturation, id = beta (vgs - vto)^2 (1 + lambda vds)
+        let sat = j.linearize(-1.0, -6.0);
+        assert_close(sat.i[DRAIN], 1e-3 * 1.1, 1e-6);
+        assert_close(sat.g[DRAIN][GATE], 2e-3 * 1.1, 1e-6);
+        assert_close(sat.g[DRAIN][DRAIN], 1e-3 * 20e-3, 1e-3);
+        // vds = 0.5: linear region, id = beta vds (2 (vgs - vto) - vds) (1 + lambda vds)
+        let lin = j.linearize(-1.0, -1.5);
+        assert_close(lin.i[DRAIN], 1e-3 * 0.5 * 1.5 * 1.01, 1e-6);
+        // beyond pinch-off only the leakage of the reverse-biased gate flows
+        let off = j.linearize(-3.0, -8.0);
+        assert!(off.i[DRAIN].abs() < 1e-10, "{}", off.i[DRAIN]);
+    }
+
+    #[test]
+    fn drain_and_source_are_symmetric() {
+        let j = jfet("njf(vto=-2 beta=1m lambda=20m)");
+        // swapping drain and source reverses the channel current
+        let forward = j.linearize(-1.0, -4.0);
+        let reverse = j.linearize(-4.0, -1.0);
+        assert_close(reverse.i[SOURCE], forward.i[DRAIN], 1e-9);
+        assert_close(reverse.i[DRAIN], forward.i[SOURCE], 1e-9);
+    }
+
+    #[test]
+    fn jacobian_matches_finite_differences() {
+        let j = jfet("pjf(vto=-1.5 beta=2m lambda=10m is=1e-12 n=1.5)");
+        let h = 1e-7;
+        for (v_gs, v_gd) in [(1.0, 4.0), (0.5, 0.8), (3.0, -0.2), (0.3, 0.4)] {
+            let l = j.linearize(v_gs, v_gd);
+            for row in [GATE, DRAIN, SOURCE] {
+                let dvgs = (j.linearize(v_gs + h, v_gd).i[row]
+                    - j.linearize(v_gs - h, v_gd).i[row])
+                    / (2.0 * h);
+                let dvgd = (j.linearize(v_gs, v_gd + h).i[row]
+                    - j.linearize(v_gs, v_gd - h).i[row])
+                    / (2.0 * h);
+                // dI/dVs = -dI/dvgs, dI/dVd = -dI/dvgd
+                assert_close(-dvgs, l.g[row][SOURCE], 1e-5);
+                assert_close(-dvgd, l.g[row][DRAIN], 1e-5);
+            }
+        }
+    }
+
+    const COMMON_SOURCE: &str = "common source
+VDD vdd 0 DC 15
+VG in 0 DC -1 AC 1
+RD vdd d 2k
+J1 d in s jn
+RS s 0 100
+.model jn njf(vto=-2 beta=1m lambda=10m rd=20 cgs=5p cgd=2p)
+";
+
+    #[test]
+    fn common_source_bias_and_gain() {
+        let deck = parse_netlist(&format!("{COMMON_SOURCE}.ac dec 10 1k 100k\n.end\n"));
+        let config = SimulationConfig::default();
+        let op = simulate_op(&deck, &config).expect("op");
+        let report = OpReport::new(&deck, &op, &config);
+        let j1 = report.device("J1").expect("J1");
+        let i_d = j1.get("id").unwrap();
+        assert_close(i_d, (15.0 - op.voltage("d").unwrap()) / 2e3, 1e-6);
+        assert_close(i_d, op.voltage("s").unwrap() / 100.0, 1e-6);
+        // saturation: id = beta (vgs - vto)^2 (1 + lambda vds) with vgs = -1 - id RS, and vds
+        // across the intrinsic device, behind the model's RD
+        let v_gs = j1.get("vgs").unwrap();
+        let v_ds = j1.get("vds").unwrap();
+        assert_close(v_gs, -1.0 - 100.0 * i_d, 1e-6);
+        assert_close(
+            v_ds,
+            op.voltage("d").unwrap() - 20.0 * i_d - 100.0 * i_d,
+            1e-6,
+        );
+        assert_close(
+            i_d,
+            1e-3 * (v_gs + 2.0).powi(2) * (1.0 + 10e-3 * v_ds),
+            1e-6,
+        );
+        assert!(j1.get("ig").unwrap().abs() < 1e-10);
+
+        // at low frequency the gain is -gm RD / (1 + gm RS), ignoring gds and RD of the model
+        let Some(Command::Ac(ac)) = deck.commands.first() else {
+            panic!("expected .ac");
+        };
+        let sweep = simulate_ac(&deck, ac, &config).expect("ac");
+        let response = FrequencyResponse::from_nodes(&deck, &sweep, "d", Some("in")).unwrap();
+        let gain = 10f64.powf(response.magnitude_db[0] / 20.0);
+        let gm = j1.get("gm").unwrap();
+        assert_close(gain, gm * 2e3 / (1.0 + gm * 100.0), 2e-2);
+    }
+
+    #[test]
+    fn gate_capacitance_slows_the_gate() {
+        let netlist = "gate delay
+VDD vdd 0 DC 10
+VG in 0 PULSE(0 -1 0 1n 1n 1 2)
+RG in g 100k
+RD vdd d 1k
+J1 d g 0 jn
+.model jn njf(vto=-2 beta=1m cgs=50p cgd=50p)
+.tran 100n 150u
+.end
+";
+        for integrator in [
+            TransientIntegrator::BackwardEuler,
+            TransientIntegrator::Trapezoidal,
+        ] {
+            let deck = parse_netlist(netlist);
+            let Some(Command::Tran(tran)) = deck.commands.first() else {
+                panic!("expected .tran");
+            };
+            let config = SimulationConfig {
+                integrator,
+                ..Default::default()
+            };
+            let result = simulate_trans(&deck, tran, &config).expect("tran");
+            let gate = result.voltage("g").unwrap();
+            // 100k and the gate capacitances, with the Miller effect, take about 10 us
+            assert!(gate.at(1e-6).unwrap() > -0.5, "{integrator:?}");
+            assert_close(*gate.y.last().unwrap(), -1.0, 1e-3);
+            // the drain settles at 10 V - 1k * beta (vgs - vto)^2
+            let drain = result.voltage("d").unwrap();
+            assert_close(*drain.y.last().unwrap(), 9.0, 1e-3);
+        }
+    }
+}
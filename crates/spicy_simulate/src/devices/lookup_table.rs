@@ -0,0 +1,250 @@
+use super::small_signal::SmallSignalModel;
+use super::stamp::NodePairStamp;
+use crate::matrix::SolverMatrix;
+use crate::op_report::DeviceOperatingPoint;
+use crate::util::get_voltage_diff;
+use ndarray::Array2;
+use spicy_parser::Span;
+use spicy_parser::devices::LookupTableSpec;
+use spicy_parser::netlist_types::NodeIndex;
+use spicy_parser::node_mapping::NodeMapping;
+
+/// Piecewise cubic Hermite interpolation (PCHIP) of a table: the slopes at the points keep the
+/// curve monotonic wherever the data is, so a measured I-V curve does not overshoot between
+/// points. Outside of the table the end segments are extended linearly.
+#[derive(Debug, Clone)]
+pub(crate) struct Pchip {
+    xs: Vec<f64>,
+    ys: Vec<f64>,
+    slopes: Vec<f64>,
+}
+
+impl Pchip {
+    /// Interpolate `points`, at least two of them with strictly increasing x.
+    pub fn new(points: &[(f64, f64)]) -> Self {
+        let (xs, ys): (Vec<f64>, Vec<f64>) = points.iter().copied().unzip();
+        let h: Vec<f64> = xs.windows(2).map(|w| w[1] - w[0]).collect();
+        let delta: Vec<f64> = ys
+            .windows(2)
+            .zip(&h)
+            .map(|(w, h)| (w[1] - w[0]) / h)
+            .collect();
+
+        let n = xs.len();
+        let mut slopes = vec![0.0; n];
+        if n == 2 {
+            slopes.fill(delta[0]);
+        } else {
+            // Fritsch-Carlson: a weighted harmonic mean of the neighbouring secants, 0 at an
+            // extremum
+            for k in 1..n - 1 {
+                if delta[k - 1] * delta[k] > 0.0 {
+                    let w1 = 2.0 * h[k] + h[k - 1];
+                    let w2 = h[k] + 2.0 * h[k - 1];
+                    slopes[k] = (w1 + w2) / (w1 / delta[k - 1] + w2 / delta[k]);
+                }
+            }
+            slopes[0] = end_slope(h[0], h[1], delta[0], delta[1]);
+            slopes[n - 1] = end_slope(h[n - 2], h[n - 3], delta[n - 2], delta[n - 3]);
+        }
+        Self { xs, ys, slopes }
+    }
+
+    /// The interpolated value and its derivative at `x`.
+    pub fn eval(&self, x: f64) -> (f64, f64) {
+        let last = self.xs.len() - 1;
+        if x <= self.xs[0] {
+            return (
+                self.ys[0] + self.slopes[0] * (x - self.xs[0]),
+                self.slopes[0],
+            );
+        }
+        if x >= self.xs[last] {
+            let slope = self.slopes[last];
+            return (self.ys[last] + slope * (x - self.xs[last]), slope);
+        }
+
+        let k = self.xs.partition_point(|&xk| xk <= x) - 1;
+        let h = self.xs[k + 1] - self.xs[k];
+        let t = (x - self.xs[k]) / h;
+        let (y0, y1) = (self.ys[k], self.ys[k + 1]);
+        let (d0, d1) = (self.slopes[k] * h, self.slopes[k + 1] * h);
+
+        let (t2, t3) = (t * t, t * t * t);
+        let y = (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * d0
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * d1;
+        let dy = ((6.0 * t2 - 6.0 * t) * y0
+            + (3.0 * t2 - 4.0 * t + 1.0) * d0
+            + (-6.0 * t2 + 6.0 * t) * y1
+            + (3.0 * t2 - 2.0 * t) * d1)
+            / h;
+        (y, dy)
+    }
+}
+
+/// Slope at an end of the table from the secants `delta` of the end segment and of its
+/// neighbour (of widths `h`), limited so the end segment stays monotonic.
+fn end_slope(h0: f64, h1: f64, delta0: f64, delta1: f64) -> f64 {
+    let slope = ((2.0 * h0 + h1) * delta0 - h0 * delta1) / (h0 + h1);
+    if slope.signum() != delta0.signum() {
+        0.0
+    } else if delta0.signum() != delta1.signum() && slope.abs() > 3.0 * delta0.abs() {
+        3.0 * delta0
+    } else {
+        slope
+    }
+}
+
+/// A two-terminal element conducting the current of a measured I-V table.
+#[derive(Debug, Clone)]
+pub struct LookupTable {
+    pub name: String,
+    #[allow(dead_code)]
+    pub span: Span,
+    pub positive: NodeIndex,
+    pub negative: NodeIndex,
+    table: Pchip,
+    pub stamp: NodePairStamp,
+}
+
+impl LookupTable {
+    pub fn from_spec(spec: &LookupTableSpec) -> Self {
+        Self {
+            name: spec.name.clone(),
+            span: spec.span,
+            positive: spec.positive,
+            negative: spec.negative,
+            table: Pchip::new(&spec.points),
+            stamp: NodePairStamp::uninitialized(),
+        }
+    }
+
+    /// The conductance `g = dI/dV` at `v` and the current `Ieq = I - g * v` of the linear
+    /// companion `i(v) ~ i(v_guess) + g * (v - v_guess)`, like a diode's.
+    pub(crate) fn linearize(&self, v: f64) -> (f64, f64) {
+        let (i, g) = self.table.eval(v);
+        (g, i - g * v)
+    }
+
+    pub(crate) fn stamp_nonlinear(&self, m: &mut SolverMatrix, guess: &[f64]) {
+        let pos = m.mna_node_index(self.positive);
+        let neg = m.mna_node_index(self.negative);
+        let (g, i_eq) = self.linearize(get_voltage_diff(guess, pos, neg));
+
+        if let Some(index) = self.stamp.pos_pos {
+            *m.get_mut_nnz(index) += g;
+        }
+        if let Some(index) = self.stamp.neg_neg {
+            *m.get_mut_nnz(index) += g;
+        }
+        if let Some((pos_neg, neg_pos)) = self.stamp.off_diagonals {
+            *m.get_mut_nnz(pos_neg) -= g;
+            *m.get_mut_nnz(neg_pos) -= g;
+        }
+        if let Some(pos) = pos {
+            *m.get_mut_rhs(pos) -= i_eq;
+        }
+        if let Some(neg) = neg {
+            *m.get_mut_rhs(neg) += i_eq;
+        }
+    }
+
+    /// Voltage, current and small-signal conductance at the operating point `op`.
+    pub(crate) fn operating_point(
+        &self,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+    ) -> DeviceOperatingPoint {
+        let pos = node_mapping.mna_node_index(self.positive);
+        let neg = node_mapping.mna_node_index(self.negative);
+        let v = get_voltage_diff(op, pos, neg);
+        let (i, g) = self.table.eval(v);
+        DeviceOperatingPoint::new(&self.name, [("v", v), ("i", i), ("g", g), ("p", v * i)])
+    }
+}
+
+impl SmallSignalModel for LookupTable {
+    /// Stamp the small-signal conductance at the operating point `op` into the real part matrix.
+    fn stamp_small_signal(
+        &self,
+        ar: &mut Array2<f64>,
+        _ai: &mut Array2<f64>,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+        _w: f64,
+    ) {
+        let pos = node_mapping.mna_node_index(self.positive);
+        let neg = node_mapping.mna_node_index(self.negative);
+        let (g, _) = self.linearize(get_voltage_diff(op, pos, neg));
+
+        if let Some(p) = pos {
+            ar[[p, p]] += g;
+        }
+        if let Some(n) = neg {
+            ar[[n, n]] += g;
+        }
+        if let (Some(p), Some(n)) = (pos, neg) {
+            ar[[p, n]] -= g;
+            ar[[n, p]] -= g;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SimulationConfig;
+    use crate::dc::simulate_op;
+    use spicy_parser::{ParseOptions, netlist_types::Command, parse};
+
+    #[test]
+    fn pchip_goes_through_the_points_and_stays_monotonic() {
+        let points = [(0.0, 0.0), (1.0, 0.1), (2.0, 2.0), (3.0, 2.1), (4.0, 2.1)];
+        let pchip = Pchip::new(&points);
+        for (x, y) in points {
+            assert!((pchip.eval(x).0 - y).abs() < 1e-12, "{x}");
+        }
+        let mut previous = pchip.eval(0.0).0;
+        for step in 1..=400 {
+            let (y, dy) = pchip.eval(step as f64 * 0.01);
+            assert!(y >= previous - 1e-12 && y <= 2.1 + 1e-12, "{y}");
+            assert!(dy >= -1e-12, "{dy}");
+            previous = y;
+        }
+        // flat at the plateau, linear outside
+        assert_eq!(pchip.eval(4.0).1, 0.0);
+        assert_eq!(pchip.eval(-1.0), (-pchip.slopes[0], pchip.slopes[0]));
+    }
+
+    #[test]
+    fn pchip_derivative_matches_finite_differences() {
+        let pchip = Pchip::new(&[(-1.0, -2.0), (0.0, 0.0), (0.5, 0.3), (2.0, 5.0)]);
+        for x in [-0.7, -0.2, 0.1, 0.4, 0.9, 1.7] {
+            let eps = 1e-6;
+            let numeric = (pchip.eval(x + eps).0 - pchip.eval(x - eps).0) / (2.0 * eps);
+            assert!((pchip.eval(x).1 - numeric).abs() < 1e-6, "{x}");
+        }
+    }
+
+    #[test]
+    fn table_element_solves_like_its_curve() {
+        let dir = std::env::temp_dir().join(format!("spicy_table_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // a linear 2 mS table, so the divider can be checked by hand
+        std::fs::write(dir.join("iv.csv"), "v,i\n0,0\n1,0.002\n2,0.004\n# end\n").unwrap();
+        let netlist = "table\nV1 in 0 3\nR1 in out 500\nA1 out 0 iv.csv\n.op\n.end\n";
+        let mut options =
+            ParseOptions::new_with_source(dir.join("table.spicy"), netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let Some(Command::Op(_)) = deck.commands.first() else {
+            panic!("expected .op");
+        };
+
+        let op = simulate_op(&deck, &SimulationConfig::default()).expect("simulate_op");
+        // 3 V over 500 ohm + 500 ohm
+        assert!((op.voltage("out").unwrap() - 1.5).abs() < 1e-9);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
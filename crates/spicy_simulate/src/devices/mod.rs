@@ -1,39 +1,106 @@
+pub(crate) mod behavioral;
 pub(crate) mod capacitor;
 pub(crate) mod diode;
 pub(crate) mod inductor;
+pub(crate) mod jfet;
+pub(crate) mod lookup_table;
+pub(crate) mod mosfet;
+pub(crate) mod multiplicity;
+pub(crate) mod mutual_inductance;
+pub mod plugin;
 pub(crate) mod resistor;
+pub(crate) mod small_signal;
 pub(crate) mod sources;
 pub(crate) mod stamp;
+pub(crate) mod switch;
+pub(crate) mod thermal;
+pub(crate) mod transmission_line;
 pub(crate) mod bjt;
 
+use ndarray::Array1;
 use spicy_parser::devices::Devices as DevicesSpec;
+use spicy_parser::instance_parser::Deck;
+use spicy_parser::netlist_types::NodeIndex;
+use spicy_parser::node_mapping::NodeMapping;
 
+use crate::SimulationConfig;
+use crate::util::get_voltage_diff;
+
+pub(crate) use behavioral::BehavioralSource;
 pub(crate) use capacitor::Capacitor;
 pub(crate) use diode::Diode;
 pub(crate) use inductor::Inductor;
+pub(crate) use jfet::Jfet;
+pub(crate) use lookup_table::LookupTable;
+pub(crate) use mosfet::Mosfet;
+pub(crate) use mutual_inductance::MutualInductance;
 pub(crate) use resistor::Resistor;
 pub(crate) use sources::IndependentSource;
+pub(crate) use switch::Switch;
+pub(crate) use transmission_line::TransmissionLine;
 pub(crate) use bjt::Bjt;
+pub(crate) use plugin::PluginDevice;
+
+/// Temperature (°C) the model parameters are given at.
+pub(crate) const NOMINAL_TEMPERATURE: f64 = 27.0;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub(crate) struct Devices {
     pub resistors: Vec<Resistor>,
     pub capacitors: Vec<Capacitor>,
     pub inductors: Vec<Inductor>,
+    pub mutual_inductances: Vec<MutualInductance>,
     pub diodes: Vec<Diode>,
+    pub lookup_tables: Vec<LookupTable>,
     pub bjts: Vec<Bjt>,
+    pub mosfets: Vec<Mosfet>,
+    pub jfets: Vec<Jfet>,
     pub voltage_sources: Vec<IndependentSource>,
     pub current_sources: Vec<IndependentSource>,
+    pub behavioral_sources: Vec<BehavioralSource>,
+    pub transmission_lines: Vec<TransmissionLine>,
+    pub switches: Vec<Switch>,
+    pub plugins: Vec<PluginDevice>,
 }
 
 impl Devices {
-    pub fn from_spec(spec: &DevicesSpec) -> Self {
+    /// Compile the devices of `spec` at the circuit `temperature` (°C).
+    pub fn from_spec(spec: &DevicesSpec, temperature: f64) -> Self {
+        let inductors: Vec<Inductor> = spec.inductors.iter().map(Inductor::from_spec).collect();
         Self {
-            resistors: spec.resistors.iter().map(Resistor::from_spec).collect(),
+            resistors: spec
+                .resistors
+                .iter()
+                .map(|r| Resistor::from_spec(r, temperature))
+                .collect(),
             capacitors: spec.capacitors.iter().map(Capacitor::from_spec).collect(),
-            inductors: spec.inductors.iter().map(Inductor::from_spec).collect(),
-            diodes: spec.diodes.iter().map(Diode::from_spec).collect(),
-            bjts: spec.bjts.iter().map(Bjt::from_spec).collect(),
+            mutual_inductances: spec
+                .mutual_inductances
+                .iter()
+                .map(|k| MutualInductance::from_spec(k, &inductors))
+                .collect(),
+            inductors,
+            diodes: spec
+                .diodes
+                .iter()
+                .map(|d| Diode::from_spec(d, temperature))
+                .collect(),
+            lookup_tables: spec
+                .lookup_tables
+                .iter()
+                .map(LookupTable::from_spec)
+                .collect(),
+            bjts: spec
+                .bjts
+                .iter()
+                .map(|q| Bjt::from_spec(q, temperature))
+                .collect(),
+            mosfets: spec.mosfets.iter().map(Mosfet::from_spec).collect(),
+            jfets: spec
+                .jfets
+                .iter()
+                .map(|j| Jfet::from_spec(j, temperature))
+                .collect(),
             voltage_sources: spec
                 .voltage_sources
                 .iter()
@@ -44,6 +111,153 @@ impl Devices {
                 .iter()
                 .map(IndependentSource::from_spec)
                 .collect(),
+            behavioral_sources: spec
+                .behavioral_sources
+                .iter()
+                .map(BehavioralSource::from_spec)
+                .collect(),
+            transmission_lines: spec
+                .transmission_lines
+                .iter()
+                .map(TransmissionLine::from_spec)
+                .collect(),
+            switches: spec.switches.iter().map(Switch::from_spec).collect(),
+            plugins: Vec::new(),
+        }
+    }
+
+    /// No nonlinear device, so the small-signal system needs no operating point.
+    pub(crate) fn is_linear(&self) -> bool {
+        self.small_signal_models().next().is_none()
+    }
+
+    /// The first corner of an independent source after `t`, see
+    /// [`WaveForm::next_breakpoint`](spicy_parser::netlist_waveform::WaveForm::next_breakpoint).
+    pub(crate) fn next_breakpoint(&self, t: f64, dt: f64, tstop: f64) -> Option<f64> {
+        self.voltage_sources
+            .iter()
+            .chain(&self.current_sources)
+            .filter_map(|source| source.dc.next_breakpoint(t, dt, tstop))
+            .reduce(f64::min)
+    }
+
+    /// Compile the deck devices at the configured temperature and instantiate the registered
+    /// plugin devices.
+    pub fn from_deck(deck: &Deck, sim_config: &SimulationConfig) -> Self {
+        let mut devices = Self::from_spec(&deck.devices, sim_config.temperature);
+        devices.plugins = sim_config.devices.instantiate(deck);
+        devices
+    }
+
+    /// Take the device values of `spec` while keeping the matrix positions set up for `self`.
+    /// Returns false (and leaves `self` untouched) if `spec` has different devices.
+    pub fn update_values(&mut self, spec: &DevicesSpec, temperature: f64) -> bool {
+        let mut next = Self::from_spec(spec, temperature);
+
+        macro_rules! carry_stamps {
+            ($($kind:ident),*) => {
+                $(
+                    if next.$kind.len() != self.$kind.len()
+                        || next.$kind.iter().zip(&self.$kind).any(|(n, o)| n.name != o.name)
+                    {
+                        return false;
+                    }
+                )*
+                $(
+                    for (n, o) in next.$kind.iter_mut().zip(&self.$kind) {
+                        n.stamp = o.stamp.clone();
+                    }
+                )*
+            };
         }
+        carry_stamps!(
+            resistors,
+            capacitors,
+            inductors,
+            mutual_inductances,
+            diodes,
+            lookup_tables,
+            bjts,
+            mosfets,
+            jfets,
+            voltage_sources,
+            current_sources,
+            behavioral_sources,
+            transmission_lines,
+            switches
+        );
+
+        next.plugins = std::mem::take(&mut self.plugins);
+        *self = next;
+        true
+    }
+
+    /// Current through every resistor, capacitor and diode at the solution `x`, from the
+    /// device's first node to its second, in the order of
+    /// [`crate::output::device_current_names`]. A capacitor's current depends on the analysis,
+    /// so `capacitor` gives it.
+    pub(crate) fn device_currents(
+        &self,
+        node_mapping: &NodeMapping,
+        x: &[f64],
+        capacitor: impl Fn(&Capacitor) -> f64,
+    ) -> Vec<f64> {
+        let v = |positive: NodeIndex, negative: NodeIndex| {
+            get_voltage_diff(
+                x,
+                node_mapping.mna_node_index(positive),
+                node_mapping.mna_node_index(negative),
+            )
+        };
+        let resistors = self
+            .resistors
+            .iter()
+            .map(|r| v(r.positive, r.negative) / r.heated_resistance());
+        let capacitors = self.capacitors.iter().map(capacitor);
+        let diodes = self.diodes.iter().map(|d| {
+            let v_d = v(d.positive, d.negative);
+            let (g, i_eq) = d.linearize(v_d);
+            i_eq + g * v_d
+        });
+        resistors.chain(capacitors).chain(diodes).collect()
+    }
+
+    /// The [`Devices::device_currents`] of the AC solution (`xr`, `xi`) at angular frequency
+    /// `w`, as real and imaginary parts. Diodes are linearized at the operating point `op`.
+    pub(crate) fn ac_device_currents(
+        &self,
+        node_mapping: &NodeMapping,
+        w: f64,
+        op: Option<&[f64]>,
+        xr: &Array1<f64>,
+        xi: &Array1<f64>,
+    ) -> (Vec<f64>, Vec<f64>) {
+        let v = |positive: NodeIndex, negative: NodeIndex| {
+            let pos = node_mapping.mna_node_index(positive);
+            let neg = node_mapping.mna_node_index(negative);
+            let part = |x: &Array1<f64>| pos.map_or(0.0, |p| x[p]) - neg.map_or(0.0, |n| x[n]);
+            (part(xr), part(xi))
+        };
+        let resistors = self.resistors.iter().map(|r| {
+            let (re, im) = v(r.positive, r.negative);
+            let ac = r.ac * r.heating_factor();
+            (re / ac, im / ac)
+        });
+        // i = jwC v
+        let capacitors = self.capacitors.iter().map(|c| {
+            let (re, im) = v(c.positive, c.negative);
+            let b = w * c.capacitance;
+            (-b * im, b * re)
+        });
+        let diodes = self.diodes.iter().map(|d| {
+            let (re, im) = v(d.positive, d.negative);
+            // without an operating point the circuit is linear and has no diodes
+            let op = op.expect("diodes are linearized at the operating point");
+            let pos = node_mapping.mna_node_index(d.positive);
+            let neg = node_mapping.mna_node_index(d.negative);
+            let (g, _) = d.linearize(get_voltage_diff(op, pos, neg));
+            (g * re, g * im)
+        });
+        resistors.chain(capacitors).chain(diodes).unzip()
     }
 }
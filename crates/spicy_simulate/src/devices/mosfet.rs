@@ -0,0 +1,367 @@
+//! Level 1 (Shichman-Hodges) MOSFET model (NMOS/PMOS).
+//!
+//! Square-law drain current with channel-length modulation (LAMBDA) and body effect
+//! (GAMMA/PHI). The gate and bulk draw no current: junction diodes and gate charges are not
+//! modeled, so only the drain and source rows are stamped.
+use super::small_signal::SmallSignalModel;
+use super::stamp::MosfetStamp;
+use crate::matrix::SolverMatrix;
+use crate::op_report::DeviceOperatingPoint;
+use crate::util::get_voltage_diff;
+use ndarray::Array2;
+use spicy_parser::MosfetPolarity;
+use spicy_parser::Span;
+use spicy_parser::devices::MosfetSpec;
+use spicy_parser::netlist_types::NodeIndex;
+use spicy_parser::node_mapping::NodeMapping;
+
+/// Default channel length and width (m), as in SPICE (DEFL/DEFW).
+const DEFAULT_LENGTH: f64 = 100e-6;
+const DEFAULT_WIDTH: f64 = 100e-6;
+/// Conductance across the channel so a device in cutoff does not leave its drain floating.
+const GMIN: f64 = 1e-12;
+
+/// `MosfetStamp` columns.
+const DRAIN: usize = 0;
+const GATE: usize = 1;
+const SOURCE: usize = 2;
+const BULK: usize = 3;
+/// Terminal of each `MosfetStamp` row.
+const ROWS: [usize; 2] = [DRAIN, SOURCE];
+
+#[derive(Debug, Clone)]
+pub struct Mosfet {
+    pub name: String,
+    #[allow(dead_code)]
+    pub span: Span,
+    pub drain: NodeIndex,
+    pub gate: NodeIndex,
+    pub source: NodeIndex,
+    pub bulk: NodeIndex,
+    pub polarity: MosfetPolarity,
+    /// Zero-bias threshold voltage (V), negative for a typical PMOS.
+    pub vto: f64,
+    /// Transconductance parameter (A/V^2).
+    pub kp: f64,
+    /// Body-effect coefficient (V^0.5).
+    pub gamma: f64,
+    /// Surface potential (V).
+    pub phi: f64,
+    /// Channel-length modulation (1/V).
+    pub lambda: f64,
+    pub l: f64,
+    pub w: f64,
+    /// Multiplier; replicates the device in parallel.
+    pub m: f64,
+    #[allow(dead_code)]
+    pub off: bool,
+    #[allow(dead_code)]
+    pub ic_vds: f64,
+    #[allow(dead_code)]
+    pub ic_vgs: f64,
+    #[allow(dead_code)]
+    pub ic_vbs: f64,
+    pub stamp: MosfetStamp,
+}
+
+/// Drain current and its derivatives, in the polarity-normalized domain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Channel {
+    id: f64,
+    gm: f64,
+    gds: f64,
+    gmbs: f64,
+}
+
+/// The MOSFET linearized at a set of node voltages.
+#[derive(Debug, Clone, Copy)]
+struct LinearizedMosfet {
+    /// drain and source swapped because `vds` was negative
+    reversed: bool,
+    gm: f64,
+    gds: f64,
+    gmbs: f64,
+    /// current into the (effective) drain minus its linear part
+    i_eq: f64,
+}
+
+impl LinearizedMosfet {
+    /// Stamp columns of the effective drain and source.
+    fn drain_source_columns(&self) -> (usize, usize) {
+        if self.reversed {
+            (SOURCE, DRAIN)
+        } else {
+            (DRAIN, SOURCE)
+        }
+    }
+
+    /// `(row, column, conductance)` entries in `MosfetStamp` layout: the effective drain row
+    /// gets the derivatives of the channel current, the effective source row their negation.
+    fn conductances(&self) -> [(usize, usize, f64); 8] {
+        let (d, s) = self.drain_source_columns();
+        let (d_row, s_row) = if self.reversed { (1, 0) } else { (0, 1) };
+        let g_sum = self.gm + self.gds + self.gmbs;
+        [
+            (d_row, d, self.gds),
+            (d_row, GATE, self.gm),
+            (d_row, BULK, self.gmbs),
+            (d_row, s, -g_sum),
+            (s_row, d, -self.gds),
+            (s_row, GATE, -self.gm),
+            (s_row, BULK, -self.gmbs),
+            (s_row, s, g_sum),
+        ]
+    }
+}
+
+impl Mosfet {
+    pub fn from_spec(spec: &MosfetSpec) -> Self {
+        let value = |v: &Option<spicy_parser::Value>, default: f64| {
+            v.as_ref().map(|v| v.get_value()).unwrap_or(default)
+        };
+        let model = &spec.model;
+
+        Self {
+            name: spec.name.clone(),
+            span: spec.span,
+            drain: spec.drain,
+            gate: spec.gate,
+            source: spec.source,
+            bulk: spec.bulk,
+            polarity: model.polarity,
+            vto: value(&model.vto, 0.0),
+            kp: value(&model.kp, 2e-5),
+            gamma: value(&model.gamma, 0.0),
+            phi: value(&model.phi, 0.6),
+            lambda: value(&model.lambda, 0.0),
+            l: value(&spec.l, DEFAULT_LENGTH),
+            w: value(&spec.w, DEFAULT_WIDTH),
+            m: value(&spec.m, 1.0),
+            off: spec.off.unwrap_or(false),
+            ic_vds: value(&spec.ic_vds, 0.0),
+            ic_vgs: value(&spec.ic_vgs, 0.0),
+            ic_vbs: value(&spec.ic_vbs, 0.0),
+            stamp: MosfetStamp::uninitialized(),
+        }
+    }
+
+    /// Return +1 for NMOS, -1 for PMOS.
+    ///
+    /// Terminal voltages and currents are multiplied by this so both polarities share the
+    /// NMOS equations.
+    fn polarity_sign(&self) -> f64 {
+        match self.polarity {
+            MosfetPolarity::Nmos => 1.0,
+            MosfetPolarity::Pmos => -1.0,
+        }
+    }
+
+    /// Square-law drain current for normalized `vgs`, `vds >= 0` and `vbs`.
+    fn channel(&self, vgs: f64, vds: f64, vbs: f64) -> Channel {
+        let beta = self.m * self.kp * self.w / self.l;
+
+        // body effect raises the threshold as the source-bulk junction is reverse biased
+        let sqrt_phi_vbs = (self.phi - vbs).max(0.0).sqrt();
+        let vth = self.polarity_sign() * self.vto + self.gamma * (sqrt_phi_vbs - self.phi.sqrt());
+        let dvth_dvbs = if sqrt_phi_vbs > 0.0 {
+            -self.gamma / (2.0 * sqrt_phi_vbs)
+        } else {
+            0.0
+        };
+
+        let vov = vgs - vth;
+        let clm = 1.0 + self.lambda * vds;
+        let (id, gm, gds) = if vov <= 0.0 {
+            // cutoff
+            (0.0, 0.0, 0.0)
+        } else if vds < vov {
+            // linear (triode)
+            let id = beta * (vov - vds / 2.0) * vds * clm;
+            let gm = beta * vds * clm;
+            let gds = beta * (vov - vds) * clm + beta * self.lambda * (vov - vds / 2.0) * vds;
+            (id, gm, gds)
+        } else {
+            // saturation
+            let id = beta / 2.0 * vov * vov * clm;
+            let gm = beta * vov * clm;
+            let gds = beta / 2.0 * vov * vov * self.lambda;
+            (id, gm, gds)
+        };
+
+        Channel {
+            id,
+            gm,
+            gds,
+            gmbs: -gm * dvth_dvbs,
+        }
+    }
+
+    /// Drain, gate, source and bulk, the `MosfetStamp` column order.
+    pub(crate) fn terminals(&self) -> [NodeIndex; 4] {
+        [self.drain, self.gate, self.source, self.bulk]
+    }
+
+    /// Linearize around `guess`, given the MNA index of every terminal (`MosfetStamp` order).
+    fn linearize(&self, nodes: [Option<usize>; 4], guess: &[f64]) -> LinearizedMosfet {
+        let p = self.polarity_sign();
+        let v = |a: usize, b: usize| get_voltage_diff(guess, nodes[a], nodes[b]);
+
+        // the channel is symmetric: conduct from whichever of drain/source is higher
+        let reversed = p * v(DRAIN, SOURCE) < 0.0;
+        let (d, s) = if reversed {
+            (SOURCE, DRAIN)
+        } else {
+            (DRAIN, SOURCE)
+        };
+        let (vgs, vds, vbs) = (v(GATE, s), v(d, s), v(BULK, s));
+
+        let channel = self.channel(p * vgs, p * vds, p * vbs);
+        let gds = channel.gds + GMIN;
+        let i = p * channel.id + GMIN * vds;
+        LinearizedMosfet {
+            reversed,
+            gm: channel.gm,
+            gds,
+            gmbs: channel.gmbs,
+            i_eq: i - channel.gm * vgs - gds * vds - channel.gmbs * vbs,
+        }
+    }
+
+    /// Stamp the linearized drain current into MNA.
+    pub(crate) fn stamp_nonlinear(&self, m: &mut SolverMatrix, guess: &[f64]) {
+        let nodes = self.terminals().map(|n| m.mna_node_index(n));
+        let linearized = self.linearize(nodes, guess);
+
+        for (row, col, g) in linearized.conductances() {
+            if let Some(index) = self.stamp.entries[row][col] {
+                *m.get_mut_nnz(index) += g;
+            }
+        }
+
+        let (d, s) = linearized.drain_source_columns();
+        if let Some(drain) = nodes[d] {
+            *m.get_mut_rhs(drain) -= linearized.i_eq;
+        }
+        if let Some(source) = nodes[s] {
+            *m.get_mut_rhs(source) += linearized.i_eq;
+        }
+    }
+
+    /// Terminal voltages, drain current (into the drain) and small-signal conductances at the
+    /// operating point `op`.
+    pub(crate) fn operating_point(
+        &self,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+    ) -> DeviceOperatingPoint {
+        let nodes = self.terminals().map(|n| node_mapping.mna_node_index(n));
+        let p = self.polarity_sign();
+        let v = |a: usize, b: usize| get_voltage_diff(op, nodes[a], nodes[b]);
+
+        let reversed = p * v(DRAIN, SOURCE) < 0.0;
+        let (d, s) = if reversed {
+            (SOURCE, DRAIN)
+        } else {
+            (DRAIN, SOURCE)
+        };
+        let channel = self.channel(p * v(GATE, s), p * v(d, s), p * v(BULK, s));
+        let direction = if reversed { -p } else { p };
+        DeviceOperatingPoint::new(
+            &self.name,
+            [
+                ("vgs", v(GATE, SOURCE)),
+                ("vds", v(DRAIN, SOURCE)),
+                ("id", direction * channel.id),
+                ("gm", channel.gm),
+                ("gds", channel.gds),
+                ("p", v(DRAIN, SOURCE) * direction * channel.id),
+            ],
+        )
+    }
+}
+
+impl SmallSignalModel for Mosfet {
+    /// Stamp the small-signal conductances at the operating point `op` into the real part of
+    /// the AC matrix.
+    fn stamp_small_signal(
+        &self,
+        ar: &mut Array2<f64>,
+        _ai: &mut Array2<f64>,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+        _w: f64,
+    ) {
+        let nodes = self.terminals().map(|n| node_mapping.mna_node_index(n));
+        let linearized = self.linearize(nodes, op);
+        for (row, col, g) in linearized.conductances() {
+            if let (Some(r), Some(c)) = (nodes[ROWS[row]], nodes[col]) {
+                ar[[r, c]] += g;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SimulationConfig;
+    use crate::ac::simulate_ac;
+    use crate::dc::simulate_op;
+    use crate::test_utils::parse_netlist;
+    use spicy_parser::netlist_types::Command;
+
+    fn drain_voltage(netlist: &str) -> f64 {
+        let op = simulate_op(&parse_netlist(netlist), &SimulationConfig::default()).expect("op");
+        op.voltage("d").expect("drain node")
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} vs {expected}");
+    }
+
+    // beta = kp * W/L = 100u, so saturation gives Id = 50u * (Vgs - Vto)^2
+    const NMOS: &str = ".model nmod NMOS vto=1 kp=100u\nM1 d g 0 0 nmod w=10u l=10u\n";
+
+    #[test]
+    fn nmos_saturation() {
+        // Id = 50uA, Vd = 5 - 20k * 50uA
+        let netlist = format!("cs\nVdd vdd 0 5\nVg g 0 2\nRd vdd d 20k\n{NMOS}.op\n.end\n");
+        assert_close(drain_voltage(&netlist), 4.0);
+    }
+
+    #[test]
+    fn nmos_linear_region() {
+        // 100u * (4 - Vd/2) * Vd = (5 - Vd) / 20k  =>  Vd^2 - 9 Vd + 5 = 0
+        let netlist = format!("cs\nVdd vdd 0 5\nVg g 0 5\nRd vdd d 20k\n{NMOS}.op\n.end\n");
+        assert_close(drain_voltage(&netlist), (9.0 - 61f64.sqrt()) / 2.0);
+    }
+
+    #[test]
+    fn pmos_saturation() {
+        // Vsg = 2, so 50uA flows out of the drain into Rd
+        let netlist = "cs\nVdd vdd 0 5\nVg g 0 3\nRd d 0 20k\n\
+                       .model pmod PMOS vto=-1 kp=100u\nM1 d g vdd vdd pmod w=10u l=10u\n.op\n.end\n";
+        assert_close(drain_voltage(netlist), 1.0);
+    }
+
+    #[test]
+    fn common_source_gain() {
+        // gm = beta * (Vgs - Vto) = 100uS, gain = -gm * Rd
+        let netlist = format!(
+            "cs\nVdd vdd 0 5\nVg g 0 DC 2 AC 1\nRd vdd d 20k\n{NMOS}.ac lin 1 1k 2k\n.end\n"
+        );
+        let deck = parse_netlist(&netlist);
+        let Some(Command::Ac(ac)) = deck.commands.first() else {
+            panic!("expected .ac");
+        };
+        let out = simulate_ac(&deck, ac, &SimulationConfig::default()).expect("ac");
+        let d = deck
+            .node_mapping
+            .node_names_mna_order()
+            .iter()
+            .position(|n| n == "d")
+            .unwrap();
+        let (_, re, im) = &out[0];
+        assert!((re[d] + 2.0).abs() < 1e-6, "{}", re[d]);
+        assert!(im[d].abs() < 1e-9);
+    }
+}
@@ -0,0 +1,155 @@
+//! Multiplicity: `m` copies of a device in parallel, each of its values scaled by `scale=` (the
+//! passives) or `area=` (the junctions).
+//!
+//! The devices fold their multiplicity into their values when they are compiled, so every
+//! analysis stamps the effective device.
+
+use spicy_parser::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Multiplicity {
+    /// Copies in parallel.
+    pub m: f64,
+    /// Scale of each copy.
+    pub scale: f64,
+}
+
+impl Default for Multiplicity {
+    fn default() -> Self {
+        Self { m: 1.0, scale: 1.0 }
+    }
+}
+
+impl Multiplicity {
+    /// The multiplicity of an instance's `m` and `scale` (or `area`), 1 when not given.
+    pub fn from_values(m: Option<&Value>, scale: Option<&Value>) -> Self {
+        Self {
+            m: m.map_or(1.0, Value::get_value),
+            scale: scale.map_or(1.0, Value::get_value),
+        }
+    }
+
+    /// The total of a capacitance, conductance or current: it grows with the scale and the
+    /// copies add up.
+    pub fn parallel(self, value: f64) -> f64 {
+        value * self.scale * self.m
+    }
+
+    /// The total of a resistance or inductance: it grows with the scale and the copies divide
+    /// it.
+    pub fn series(self, value: f64) -> f64 {
+        value * self.scale / self.m
+    }
+
+    /// The total of a junction's resistance, or of the inverse of a junction's current: both
+    /// the area and the copies divide it.
+    pub fn divided(self, value: f64) -> f64 {
+        value / (self.scale * self.m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SimulationConfig;
+    use crate::ac::simulate_ac;
+    use crate::dc::simulate_op;
+    use crate::test_utils::parse_netlist;
+    use crate::trans::simulate_trans;
+    use spicy_parser::netlist_types::Command;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= 1e-9 * expected.abs() + 1e-12,
+            "{actual} != {expected}"
+        );
+    }
+
+    /// The operating point of `multiple` and of `copies` agree on every node of `multiple`.
+    fn assert_same_op(multiple: &str, copies: &str) {
+        let config = SimulationConfig::default();
+        let multiple = simulate_op(&parse_netlist(multiple), &config).expect("op");
+        let copies = simulate_op(&parse_netlist(copies), &config).expect("op");
+        for (node, actual) in &multiple.voltages {
+            let expected = copies.voltage(node).expect("node of the copies");
+            assert!(
+                (actual - expected).abs() < 1e-9,
+                "v({node}): {actual} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn resistor_m_and_scale_match_parallel_copies() {
+        assert_same_op(
+            "r\nV1 in 0 1\nR1 in out 1k m=2 scale=3\nR2 out 0 1k\n.end\n",
+            "r\nV1 in 0 1\nR1 in out 3k\nR3 in out 3k\nR2 out 0 1k\n.end\n",
+        );
+    }
+
+    #[test]
+    fn capacitor_m_matches_parallel_copies() {
+        let run = |netlist: &str| {
+            let deck = parse_netlist(netlist);
+            let Some(Command::Tran(tran)) = deck.commands.first() else {
+                panic!("expected .tran");
+            };
+            simulate_trans(&deck, tran, &SimulationConfig::default()).expect("tran")
+        };
+        let multiple =
+            run("c\nV1 in 0 PULSE(0 1 0 1u)\nR1 in out 1k\nC1 out 0 1u m=2\n.tran 10u 5m\n.end\n");
+        let copies = run(
+            "c\nV1 in 0 PULSE(0 1 0 1u)\nR1 in out 1k\nC1 out 0 1u\nC2 out 0 1u\n.tran 10u 5m\n.end\n",
+        );
+        let multiple = multiple.voltage("out").unwrap().y;
+        let copies = copies.voltage("out").unwrap().y;
+        assert_eq!(multiple.len(), copies.len());
+        for (actual, expected) in multiple.iter().zip(&copies) {
+            assert_close(*actual, *expected);
+        }
+    }
+
+    #[test]
+    fn inductor_m_matches_parallel_copies() {
+        let run = |netlist: &str| {
+            let deck = parse_netlist(netlist);
+            let Some(Command::Ac(ac)) = deck.commands.first() else {
+                panic!("expected .ac");
+            };
+            let nodes = deck.node_mapping.nodes_len();
+            let sweep = simulate_ac(&deck, ac, &SimulationConfig::default()).expect("ac");
+            sweep
+                .into_iter()
+                .map(|(_, re, im)| (re[nodes - 1], im[nodes - 1]))
+                .collect::<Vec<_>>()
+        };
+        let multiple =
+            run("l\nV1 in 0 AC 1\nR1 in out 1k\nL1 out 0 10m m=2\n.ac dec 5 1k 1Meg\n.end\n");
+        let copies = run(
+            "l\nV1 in 0 AC 1\nR1 in out 1k\nL1 out 0 10m\nL2 out 0 10m\n.ac dec 5 1k 1Meg\n.end\n",
+        );
+        for ((re, im), (expected_re, expected_im)) in multiple.iter().zip(&copies) {
+            assert_close(*re, *expected_re);
+            assert_close(*im, *expected_im);
+        }
+    }
+
+    #[test]
+    fn diode_m_and_area_match_parallel_copies() {
+        assert_same_op(
+            "d\nV1 in 0 5\nR1 in a 1k\nD1 a 0 dmod 2 m=3\n.model dmod D(is=1e-14 rs=10)\n.end\n",
+            "d\nV1 in 0 5\nR1 in a 1k\nD1 a 0 dmod 2\nD2 a 0 dmod 2\nD3 a 0 dmod 2\n\
+             .model dmod D(is=1e-14 rs=10)\n.end\n",
+        );
+    }
+
+    #[test]
+    fn bjt_m_matches_parallel_copies() {
+        const MODEL: &str = ".model q npn(is=1e-15 bf=100 ikf=10m ise=1e-14 rb=100 rc=10 re=1)\n";
+        assert_same_op(
+            &format!("q\nV1 vcc 0 5\nV2 b 0 0.7\nR1 vcc c 1k\nQ1 c b 0 q m=2\n{MODEL}.end\n"),
+            &format!(
+                "q\nV1 vcc 0 5\nV2 b 0 0.7\nR1 vcc c 1k\nQ1 c b 0 q\nQ2 c b 0 q\n{MODEL}.end\n"
+            ),
+        );
+    }
+}
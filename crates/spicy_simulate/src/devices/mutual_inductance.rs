@@ -0,0 +1,151 @@
+use super::Inductor;
+use super::stamp::BranchCouplingStamp;
+use crate::matrix::SolverMatrix;
+use ndarray::Array2;
+use spicy_parser::devices::MutualInductanceSpec;
+use spicy_parser::node_mapping::NodeMapping;
+
+/// Two coupled inductors: V1 = L1 dI1/dt + M dI2/dt and V2 = M dI1/dt + L2 dI2/dt, with
+/// M = k * sqrt(L1 * L2). The self terms are stamped by the inductors themselves.
+#[derive(Debug, Clone)]
+pub struct MutualInductance {
+    pub name: String,
+    /// Indices of the coupled inductors in `Devices::inductors`.
+    pub inductors: (usize, usize),
+    pub coupling: f64,
+    pub stamp: BranchCouplingStamp,
+}
+
+impl MutualInductance {
+    pub fn from_spec(spec: &MutualInductanceSpec, inductors: &[Inductor]) -> Self {
+        let index = |name: &str| {
+            inductors
+                .iter()
+                .position(|l| l.name == name)
+                .expect("the parser checks that coupled inductors exist")
+        };
+        Self {
+            name: spec.name.clone(),
+            inductors: (index(&spec.inductor1), index(&spec.inductor2)),
+            coupling: spec.coupling.get_value(),
+            stamp: BranchCouplingStamp::uninitialized(),
+        }
+    }
+
+    /// The two coupled inductors.
+    pub(crate) fn pair<'a>(&self, inductors: &'a [Inductor]) -> (&'a Inductor, &'a Inductor) {
+        (&inductors[self.inductors.0], &inductors[self.inductors.1])
+    }
+
+    /// M = k * sqrt(L1 * L2).
+    pub(crate) fn mutual_inductance(&self, inductors: &[Inductor]) -> f64 {
+        let (l1, l2) = self.pair(inductors);
+        self.coupling * (l1.inductance * l2.inductance).sqrt()
+    }
+
+    pub(crate) fn setup<F, E>(
+        &mut self,
+        inductors: &[Inductor],
+        node_mapping: &NodeMapping,
+        entry: F,
+    ) -> Result<(), E>
+    where
+        F: FnMut(usize, usize) -> Result<usize, E>,
+    {
+        let (l1, l2) = self.pair(inductors);
+        let branch1 = node_mapping.mna_branch_index(l1.current_branch);
+        let branch2 = node_mapping.mna_branch_index(l2.current_branch);
+        self.stamp
+            .set_temp_indices_from_branches(branch1, branch2, entry)
+    }
+
+    /// Stamp AC small-signal contributions: -jwM between the two branch rows.
+    pub(crate) fn stamp_ac(
+        &self,
+        ai: &mut Array2<f64>,
+        inductors: &[Inductor],
+        node_mapping: &NodeMapping,
+        w: f64,
+    ) {
+        let (l1, l2) = self.pair(inductors);
+        let branch1 = node_mapping.mna_branch_index(l1.current_branch);
+        let branch2 = node_mapping.mna_branch_index(l2.current_branch);
+        let wm = w * self.mutual_inductance(inductors);
+        ai[[branch1, branch2]] -= wm;
+        ai[[branch2, branch1]] -= wm;
+    }
+
+    /// Stamp the transient companion model of the coupling, after the inductors' own stamps.
+    ///
+    /// KVL rows: ... - r_m * I2 = v_hist1 and ... - r_m * I1 = v_hist2, where the history
+    /// terms are added to the inductors' own.
+    pub(crate) fn stamp_trans(
+        &self,
+        m: &mut SolverMatrix,
+        inductors: &[Inductor],
+        r_m: f64,
+        v_hist: (f64, f64),
+    ) {
+        let (l1, l2) = self.pair(inductors);
+        if let Some((b1_b2, b2_b1)) = self.stamp.off_diagonals {
+            *m.get_mut_nnz(b1_b2) -= r_m;
+            *m.get_mut_nnz(b2_b1) -= r_m;
+        }
+        let branch1 = m.mna_branch_index(l1.current_branch);
+        let branch2 = m.mna_branch_index(l2.current_branch);
+        *m.get_mut_rhs(branch1) += v_hist.0;
+        *m.get_mut_rhs(branch2) += v_hist.1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::parse_netlist;
+    use crate::{SimulationConfig, ac::simulate_ac, trans::simulate_trans};
+    use spicy_parser::netlist_types::{Command, NodeName};
+
+    // with k = 1 the secondary voltage is sqrt(L2 / L1) = 2 times the primary one, whatever
+    // the load
+    const TRANSFORMER: &str = "transformer\nV1 in 0 SIN(0 1 1k) AC 1\nR1 in p 1\n\
+        L1 p 0 1m\nL2 out 0 4m\nK1 L1 l2 1\nR2 out 0 1k\n";
+
+    fn deck(analysis: &str) -> spicy_parser::instance_parser::Deck {
+        parse_netlist(&format!("{TRANSFORMER}{analysis}\n.end\n"))
+    }
+
+    #[test]
+    fn ac_turns_ratio() {
+        let deck = deck(".ac dec 2 100 10k");
+        let Some(Command::Ac(ac)) = deck.commands.first() else {
+            panic!("expected .ac");
+        };
+        let mna = |name: &str| {
+            let node = deck
+                .node_mapping
+                .get_node(&NodeName(name.to_string()))
+                .unwrap();
+            deck.node_mapping.mna_node_index(node).unwrap()
+        };
+        let (p, out) = (mna("p"), mna("out"));
+
+        let sweep = simulate_ac(&deck, ac, &SimulationConfig::default()).expect("ac");
+        for (f, re, im) in &sweep {
+            let ratio = (re[out].hypot(im[out])) / (re[p].hypot(im[p]));
+            assert!((ratio - 2.0).abs() < 1e-9, "{f} Hz: {ratio}");
+        }
+    }
+
+    #[test]
+    fn transient_turns_ratio() {
+        let deck = deck(".tran 10u 2m");
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+        let result = simulate_trans(&deck, tran, &SimulationConfig::default()).expect("tran");
+        let p = result.voltage("p").unwrap().y;
+        let out = result.voltage("out").unwrap().y;
+        for (p, out) in p.iter().zip(&out).skip(1) {
+            assert!((out - 2.0 * p).abs() < 1e-9, "{out} vs 2 * {p}");
+        }
+    }
+}
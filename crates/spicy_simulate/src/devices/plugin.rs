@@ -0,0 +1,445 @@
+//! Extension point for devices defined outside of this crate.
+//!
+//! A plugin device implements [`DeviceModel`] and is created by a factory registered in a
+//! [`DeviceRegistry`] (see `SimulationConfig::devices`). The simulator then drives it through
+//! the same phases as the built-in devices:
+//! 1. `setup`: reserve the matrix entries the device will ever stamp.
+//! 2. `load`: stamp the linearized contributions for the current analysis / Newton guess.
+//! 3. `load_ac`: stamp small-signal admittances (optional).
+//! 4. `update_state`: observe each accepted transient solution (optional).
+
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Arc;
+
+use ndarray::{Array1, Array2};
+use spicy_parser::instance_parser::Deck;
+use spicy_parser::netlist_types::{CurrentBranchIndex, NodeIndex};
+use spicy_parser::node_mapping::NodeMapping;
+
+use crate::error::SimulationError;
+use crate::matrix::SolverMatrix;
+use crate::solver::matrix::builder::MatrixBuilder;
+use crate::util::get_voltage_diff;
+
+/// Handle to a matrix entry reserved during [`DeviceModel::setup`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatrixEntry(usize);
+
+/// The analysis a device is being loaded for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Analysis {
+    /// Operating point / DC sweep point.
+    Dc,
+    /// A transient time point.
+    Transient { time: f64, step: f64 },
+}
+
+/// A user-defined device.
+pub trait DeviceModel: fmt::Debug + Send {
+    /// Instance name, used in diagnostics.
+    fn name(&self) -> &str;
+
+    /// Nodes this device connects to.
+    fn nodes(&self) -> Vec<NodeIndex>;
+
+    /// Branch currents this device owns (must already exist in the deck node mapping).
+    fn branches(&self) -> Vec<CurrentBranchIndex> {
+        Vec::new()
+    }
+
+    /// Reserve every matrix entry the device stamps in `load`.
+    fn setup(&mut self, ctx: &mut SetupContext<'_>) -> Result<(), SimulationError>;
+
+    /// Stamp the device for the current Newton iteration.
+    fn load(&self, ctx: &mut LoadContext<'_>);
+
+    /// Stamp the small-signal admittance at angular frequency `ctx.omega()`.
+    fn load_ac(&self, _ctx: &mut AcLoadContext<'_>) {}
+
+    /// Called with every accepted transient solution (and the initial operating point).
+    fn update_state(&mut self, _solution: &[f64]) {}
+}
+
+enum SetupTarget<'a> {
+    /// KLU: entries are pushed into the sparsity pattern and remapped after the build.
+    Sparse(&'a mut MatrixBuilder),
+    /// BLAS: entries are dense row-major linear indices.
+    Dense { dim: usize },
+}
+
+/// Passed to [`DeviceModel::setup`] to resolve MNA indices and reserve entries.
+pub struct SetupContext<'a> {
+    node_mapping: &'a NodeMapping,
+    target: SetupTarget<'a>,
+    entries: &'a mut Vec<usize>,
+}
+
+impl SetupContext<'_> {
+    /// MNA row/column of a node, `None` for ground.
+    pub fn node(&self, node: NodeIndex) -> Option<usize> {
+        self.node_mapping.mna_node_index(node)
+    }
+
+    /// MNA row/column of a branch current.
+    pub fn branch(&self, branch: CurrentBranchIndex) -> usize {
+        self.node_mapping.mna_branch_index(branch)
+    }
+
+    /// Reserve the entry at (`row`, `col`) of the MNA matrix.
+    pub fn reserve(&mut self, row: usize, col: usize) -> Result<MatrixEntry, SimulationError> {
+        let index = match &mut self.target {
+            SetupTarget::Sparse(builder) => builder.push(col, row, 0.0)?,
+            SetupTarget::Dense { dim } => row * *dim + col,
+        };
+        self.entries.push(index);
+        Ok(MatrixEntry(self.entries.len() - 1))
+    }
+}
+
+/// Passed to [`DeviceModel::load`] to stamp values into the MNA system.
+pub struct LoadContext<'a> {
+    matrix: &'a mut SolverMatrix,
+    entries: &'a [usize],
+    guess: &'a [f64],
+    analysis: Analysis,
+}
+
+impl LoadContext<'_> {
+    pub fn analysis(&self) -> Analysis {
+        self.analysis
+    }
+
+    /// The current Newton guess (node voltages followed by branch currents).
+    pub fn guess(&self) -> &[f64] {
+        self.guess
+    }
+
+    pub fn node(&self, node: NodeIndex) -> Option<usize> {
+        self.matrix.mna_node_index(node)
+    }
+
+    pub fn branch(&self, branch: CurrentBranchIndex) -> usize {
+        self.matrix.mna_branch_index(branch)
+    }
+
+    /// Voltage difference `V(positive) - V(negative)` in the current guess.
+    pub fn voltage(&self, positive: NodeIndex, negative: NodeIndex) -> f64 {
+        get_voltage_diff(self.guess, self.node(positive), self.node(negative))
+    }
+
+    /// Add `value` to a reserved matrix entry.
+    pub fn add(&mut self, entry: MatrixEntry, value: f64) {
+        *self.matrix.get_mut_nnz(self.entries[entry.0]) += value;
+    }
+
+    /// Add `value` to row `row` of the right-hand side.
+    pub fn add_rhs(&mut self, row: usize, value: f64) {
+        *self.matrix.get_mut_rhs(row) += value;
+    }
+}
+
+/// Passed to [`DeviceModel::load_ac`]; stamps into the dense real/imaginary MNA parts.
+pub struct AcLoadContext<'a> {
+    node_mapping: &'a NodeMapping,
+    ar: &'a mut Array2<f64>,
+    ai: &'a mut Array2<f64>,
+    br: &'a mut Array1<f64>,
+    bi: &'a mut Array1<f64>,
+    omega: f64,
+}
+
+impl AcLoadContext<'_> {
+    /// Angular frequency (rad/s).
+    pub fn omega(&self) -> f64 {
+        self.omega
+    }
+
+    pub fn node(&self, node: NodeIndex) -> Option<usize> {
+        self.node_mapping.mna_node_index(node)
+    }
+
+    pub fn branch(&self, branch: CurrentBranchIndex) -> usize {
+        self.node_mapping.mna_branch_index(branch)
+    }
+
+    /// Add `re + j*im` at (`row`, `col`).
+    pub fn add(&mut self, row: usize, col: usize, re: f64, im: f64) {
+        self.ar[[row, col]] += re;
+        self.ai[[row, col]] += im;
+    }
+
+    /// Add `re + j*im` to row `row` of the right-hand side.
+    pub fn add_rhs(&mut self, row: usize, re: f64, im: f64) {
+        self.br[row] += re;
+        self.bi[row] += im;
+    }
+}
+
+type DeviceFactory = dyn Fn(&Deck) -> Vec<Box<dyn DeviceModel>> + Send + Sync;
+
+/// Factories for plugin devices, instantiated for every analysis of a deck.
+#[derive(Clone, Default)]
+pub struct DeviceRegistry {
+    factories: Vec<(String, Arc<DeviceFactory>)>,
+}
+
+impl fmt::Debug for DeviceRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.factories.iter().map(|(name, _)| name))
+            .finish()
+    }
+}
+
+impl DeviceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a factory that creates plugin devices for a parsed deck.
+    pub fn register<F>(&mut self, name: impl Into<String>, factory: F)
+    where
+        F: Fn(&Deck) -> Vec<Box<dyn DeviceModel>> + Send + Sync + 'static,
+    {
+        self.factories.push((name.into(), Arc::new(factory)));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.factories.is_empty()
+    }
+
+    pub(crate) fn instantiate(&self, deck: &Deck) -> Vec<PluginDevice> {
+        self.factories
+            .iter()
+            .flat_map(|(_, factory)| factory(deck))
+            .map(PluginDevice::new)
+            .collect()
+    }
+}
+
+/// A plugin device plus the matrix entries it reserved.
+///
+/// The model sits in a `RefCell` because transient analysis holds the device list by shared
+/// reference while `update_state` needs to mutate it.
+#[derive(Debug)]
+pub(crate) struct PluginDevice {
+    model: RefCell<Box<dyn DeviceModel>>,
+    entries: Vec<usize>,
+}
+
+impl PluginDevice {
+    fn new(model: Box<dyn DeviceModel>) -> Self {
+        Self {
+            model: RefCell::new(model),
+            entries: Vec::new(),
+        }
+    }
+
+    fn validate(&mut self, node_mapping: &NodeMapping) -> Result<(), SimulationError> {
+        let model = self.model.get_mut();
+        let bad_node = model
+            .nodes()
+            .into_iter()
+            .any(|n| n.0 > node_mapping.nodes_len());
+        let bad_branch = model
+            .branches()
+            .into_iter()
+            .any(|b| b.0 == 0 || b.0 > node_mapping.branches_len());
+        if bad_node || bad_branch {
+            return Err(SimulationError::InvalidPluginDevice {
+                name: model.name().to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn setup_sparse(
+        &mut self,
+        node_mapping: &NodeMapping,
+        builder: &mut MatrixBuilder,
+    ) -> Result<(), SimulationError> {
+        self.validate(node_mapping)?;
+        self.entries.clear();
+        let mut ctx = SetupContext {
+            node_mapping,
+            target: SetupTarget::Sparse(builder),
+            entries: &mut self.entries,
+        };
+        self.model.get_mut().setup(&mut ctx)
+    }
+
+    pub fn setup_dense(&mut self, node_mapping: &NodeMapping) -> Result<(), SimulationError> {
+        self.validate(node_mapping)?;
+        self.entries.clear();
+        let mut ctx = SetupContext {
+            node_mapping,
+            target: SetupTarget::Dense {
+                dim: node_mapping.mna_matrix_dim(),
+            },
+            entries: &mut self.entries,
+        };
+        self.model.get_mut().setup(&mut ctx)
+    }
+
+    /// Map temporary builder entries to their final CSC nnz indices.
+    pub fn set_final_indices<F>(&mut self, f: F)
+    where
+        F: Fn(usize) -> usize,
+    {
+        for entry in &mut self.entries {
+            *entry = f(*entry);
+        }
+    }
+
+    pub fn load(&self, matrix: &mut SolverMatrix, guess: &[f64], analysis: Analysis) {
+        let mut ctx = LoadContext {
+            matrix,
+            entries: &self.entries,
+            guess,
+            analysis,
+        };
+        self.model.borrow().load(&mut ctx);
+    }
+
+    pub fn load_ac(
+        &self,
+        node_mapping: &NodeMapping,
+        ar: &mut Array2<f64>,
+        ai: &mut Array2<f64>,
+        br: &mut Array1<f64>,
+        bi: &mut Array1<f64>,
+        omega: f64,
+    ) {
+        let mut ctx = AcLoadContext {
+            node_mapping,
+            ar,
+            ai,
+            br,
+            bi,
+            omega,
+        };
+        self.model.borrow().load_ac(&mut ctx);
+    }
+
+    pub fn update_state(&self, solution: &[f64]) {
+        self.model.borrow_mut().update_state(solution);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dc::simulate_op;
+    use crate::test_utils::parse_netlist;
+    use crate::{LinearSolver, SimulationConfig};
+    use rstest::rstest;
+    use spicy_parser::netlist_types::NodeName;
+
+    /// A plain conductance, implemented only through the plugin API.
+    #[derive(Debug)]
+    struct Conductance {
+        name: String,
+        a: NodeIndex,
+        b: NodeIndex,
+        g: f64,
+        entries: Vec<MatrixEntry>,
+        signs: Vec<f64>,
+    }
+
+    impl DeviceModel for Conductance {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn nodes(&self) -> Vec<NodeIndex> {
+            vec![self.a, self.b]
+        }
+
+        fn setup(&mut self, ctx: &mut SetupContext<'_>) -> Result<(), SimulationError> {
+            let (a, b) = (ctx.node(self.a), ctx.node(self.b));
+            for (row, col, sign) in [(a, a, 1.0), (b, b, 1.0), (a, b, -1.0), (b, a, -1.0)] {
+                if let (Some(row), Some(col)) = (row, col) {
+                    self.entries.push(ctx.reserve(row, col)?);
+                    self.signs.push(sign);
+                }
+            }
+            Ok(())
+        }
+
+        fn load(&self, ctx: &mut LoadContext<'_>) {
+            for (entry, sign) in self.entries.iter().zip(&self.signs) {
+                ctx.add(*entry, sign * self.g);
+            }
+        }
+    }
+
+    fn conductance_registry(g: f64) -> DeviceRegistry {
+        let mut registry = DeviceRegistry::new();
+        registry.register("conductance", move |deck: &Deck| {
+            let out = deck
+                .node_mapping
+                .get_node(&NodeName("out".to_string()))
+                .expect("node out");
+            vec![Box::new(Conductance {
+                name: "Gplugin".to_string(),
+                a: out,
+                b: NodeIndex(0),
+                g,
+                entries: Vec::new(),
+                signs: Vec::new(),
+            }) as Box<dyn DeviceModel>]
+        });
+        registry
+    }
+
+    #[rstest]
+    #[case::klu(LinearSolver::Klu { config: Default::default() })]
+    #[case::blas(LinearSolver::Blas)]
+    fn plugin_conductance_matches_resistor(#[case] solver: LinearSolver) {
+        let builtin =
+            parse_netlist("divider\nV1 in 0 DC 10\nR1 in out 1k\nR2 out 0 1k\n.OP\n.END\n");
+        let plugin = parse_netlist("divider\nV1 in 0 DC 10\nR1 in out 1k\n.OP\n.END\n");
+
+        let builtin_cfg = SimulationConfig {
+            solver: solver.clone(),
+            ..SimulationConfig::default()
+        };
+        let plugin_cfg = SimulationConfig {
+            solver,
+            devices: conductance_registry(1e-3),
+            ..SimulationConfig::default()
+        };
+
+        let expected = simulate_op(&builtin, &builtin_cfg).expect("builtin op");
+        let actual = simulate_op(&plugin, &plugin_cfg).expect("plugin op");
+        for ((name, v_expected), (_, v_actual)) in expected.voltages.iter().zip(&actual.voltages) {
+            assert!(
+                (v_expected - v_actual).abs() < 1e-9,
+                "{name}: expected {v_expected}, got {v_actual}"
+            );
+        }
+    }
+
+    #[test]
+    fn plugin_with_unknown_node_is_rejected() {
+        let deck = parse_netlist("divider\nV1 in 0 DC 10\nR1 in out 1k\n.OP\n.END\n");
+        let mut registry = DeviceRegistry::new();
+        registry.register("broken", |_: &Deck| {
+            vec![Box::new(Conductance {
+                name: "Gbroken".to_string(),
+                a: NodeIndex(42),
+                b: NodeIndex(0),
+                g: 1.0,
+                entries: Vec::new(),
+                signs: Vec::new(),
+            }) as Box<dyn DeviceModel>]
+        });
+        let cfg = SimulationConfig {
+            devices: registry,
+            ..SimulationConfig::default()
+        };
+        let err = simulate_op(&deck, &cfg).expect_err("out of range node");
+        assert!(matches!(err, SimulationError::InvalidPluginDevice { name } if name == "Gbroken"));
+    }
+}
@@ -1,5 +1,11 @@
+use super::NOMINAL_TEMPERATURE;
+use super::multiplicity::Multiplicity;
 use super::stamp::NodePairStamp;
+use super::thermal::ThermalRc;
 use crate::matrix::SolverMatrix;
+use crate::noise::{BOLTZMANN, NoiseSource, celsius_to_kelvin};
+use crate::op_report::DeviceOperatingPoint;
+use crate::util::get_voltage_diff;
 use ndarray::Array2;
 use spicy_parser::Span;
 use spicy_parser::devices::ResistorSpec;
@@ -15,22 +21,16 @@ pub struct Resistor {
     pub span: Span,
     pub positive: NodeIndex,
     pub negative: NodeIndex,
-    /// Resistor value (Ohms) resolved from instance/model/default.
+    /// Resistor value (Ohms) resolved from instance/model/default, of the `m` copies scaled by
+    /// `scale` together.
     pub resistance: f64,
     /// Optional AC override value (Ohms). If not provided, defaults to `resistance`.
     pub ac: f64,
-    /// Multiplier; replicates the resistor in parallel.
-    #[allow(dead_code)]
-    pub m: f64,
-    /// Scaling factor applied to the resistance value.
-    #[allow(dead_code)]
-    pub scale: f64,
-    /// Instance temperature (typically in °C).
-    #[allow(dead_code)]
+    /// `m` and `scale`, already applied to `resistance` and `ac`.
+    pub multiplicity: Multiplicity,
+    /// Operating temperature (°C): the instance `temp`, else the circuit temperature plus
+    /// the instance `dtemp`.
     pub temp: f64,
-    /// Instance temperature delta applied on top of the ambient/circuit temperature.
-    #[allow(dead_code)]
-    pub dtemp: f64,
     /// First-order temperature coefficient.
     #[allow(dead_code)]
     pub tc1: f64,
@@ -38,16 +38,18 @@ pub struct Resistor {
     #[allow(dead_code)]
     pub tc2: f64,
     /// Enable/disable including this resistor in noise analysis.
-    #[allow(dead_code)]
     pub noisy: bool,
+    /// Thermal RC of a self-heating resistor, whose temperature rises above `temp`.
+    pub heating: Option<ThermalRc>,
     pub stamp: NodePairStamp,
 }
 
 impl Resistor {
-    /// Compile a parsed resistor "spec" into a simulation-ready resistor.
+    /// Compile a parsed resistor "spec" into a simulation-ready resistor at the circuit
+    /// `temperature` (°C).
     ///
     /// No validation is performed at this stage; missing parameters are replaced with defaults.
-    pub fn from_spec(spec: &ResistorSpec) -> Self {
+    pub fn from_spec(spec: &ResistorSpec, temperature: f64) -> Self {
         // Resolve resistance (instance overrides model). If absent everywhere, use a small default.
         let resistance = spec
             .resistance
@@ -60,8 +62,7 @@ impl Resistor {
             })
             .unwrap_or(1e-03);
 
-        let m = spec.m.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
-        let scale = spec.scale.as_ref().map(|v| v.get_value()).unwrap_or(1.0);
+        let multiplicity = Multiplicity::from_values(spec.m.as_ref(), spec.scale.as_ref());
 
         let tc1 = spec
             .tc1
@@ -84,15 +85,27 @@ impl Resistor {
             })
             .unwrap_or(0.0);
 
-        // TODO: get this from the config of the deck
-        let temp = spec.temp.as_ref().map(|v| v.get_value()).unwrap_or(27.0);
         let dtemp = spec.dtemp.as_ref().map(|v| v.get_value()).unwrap_or(0.0);
+        let temp = spec
+            .temp
+            .as_ref()
+            .map(|v| v.get_value())
+            .unwrap_or(temperature + dtemp);
         let noisy = spec.noisy.unwrap_or(true);
+        let model = spec.model.as_ref();
+        let heating = ThermalRc::from_values(
+            spec.rth.as_ref().or(model.and_then(|m| m.rth.as_ref())),
+            spec.cth.as_ref().or(model.and_then(|m| m.cth.as_ref())),
+        );
 
+        // R(T) = R * (1 + tc1 * (T - Tnom) + tc2 * (T - Tnom)^2)
+        let dt = temp - NOMINAL_TEMPERATURE;
+        let factor = 1.0 + tc1 * dt + tc2 * dt * dt;
+        let resistance = multiplicity.series(resistance * factor);
         let ac = spec
             .ac
             .as_ref()
-            .map(|v| v.get_value())
+            .map(|v| multiplicity.series(v.get_value() * factor))
             .unwrap_or(resistance);
 
         Self {
@@ -102,20 +115,51 @@ impl Resistor {
             negative: spec.negative,
             resistance,
             ac,
-            m,
-            scale,
+            multiplicity,
             temp,
-            dtemp,
             tc1,
             tc2,
             noisy,
+            heating,
             stamp: NodePairStamp::uninitialized(),
         }
     }
 
+    /// Temperature (°C) of the point being solved, above `temp` for a self-heating resistor.
+    pub(crate) fn temperature(&self) -> f64 {
+        self.temp + self.heating.as_ref().map_or(0.0, ThermalRc::rise)
+    }
+
+    /// Ratio of the resistance at [`Self::temperature`] to the one at `temp`.
+    pub(crate) fn heating_factor(&self) -> f64 {
+        if self.heating.is_none() {
+            return 1.0;
+        }
+        let factor = |t: f64| {
+            let dt = t - NOMINAL_TEMPERATURE;
+            1.0 + self.tc1 * dt + self.tc2 * dt * dt
+        };
+        factor(self.temperature()) / factor(self.temp)
+    }
+
+    /// Resistance (Ohms) at [`Self::temperature`].
+    pub(crate) fn heated_resistance(&self) -> f64 {
+        self.resistance * self.heating_factor()
+    }
+
+    /// Power (W) dissipated at the solution `x`.
+    pub(crate) fn power(&self, node_mapping: &NodeMapping, x: &[f64]) -> f64 {
+        let v = get_voltage_diff(
+            x,
+            node_mapping.mna_node_index(self.positive),
+            node_mapping.mna_node_index(self.negative),
+        );
+        v * v / self.heated_resistance()
+    }
+
     /// Stamp DC MNA contributions for a resistor into the solver matrix.
     pub(crate) fn stamp_dc(&self, m: &mut SolverMatrix) {
-        let conductance = 1.0 / self.resistance;
+        let conductance = 1.0 / self.heated_resistance();
 
         if let Some(index) = self.stamp.pos_pos {
             *m.get_mut_nnz(index) += conductance;
@@ -131,7 +175,7 @@ impl Resistor {
 
     /// Stamp AC small-signal admittance for a resistor into the real part matrix.
     pub(crate) fn stamp_ac(&self, ar: &mut Array2<f64>, node_mapping: &NodeMapping) {
-        let g = 1.0 / self.ac;
+        let g = 1.0 / (self.ac * self.heating_factor());
         let node1 = node_mapping.mna_node_index(self.positive);
         let node2 = node_mapping.mna_node_index(self.negative);
 
@@ -146,4 +190,37 @@ impl Resistor {
             ar[[n2, n1]] -= g;
         }
     }
+
+    /// Voltage across, current through (positive to negative) and power dissipated at the
+    /// operating point `op`.
+    pub(crate) fn operating_point(
+        &self,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+    ) -> DeviceOperatingPoint {
+        let v = get_voltage_diff(
+            op,
+            node_mapping.mna_node_index(self.positive),
+            node_mapping.mna_node_index(self.negative),
+        );
+        let i = v / self.heated_resistance();
+        let point = DeviceOperatingPoint::new(&self.name, [("v", v), ("i", i), ("p", v * i)]);
+        match self.heating {
+            Some(_) => point.with("t", self.temperature()),
+            None => point,
+        }
+    }
+
+    /// Thermal (Johnson) noise current of the resistor: 4kT/R.
+    pub(crate) fn noise(&self, node_mapping: &NodeMapping) -> Option<NoiseSource> {
+        if !self.noisy {
+            return None;
+        }
+        let t = celsius_to_kelvin(self.temperature());
+        Some(NoiseSource {
+            positive: node_mapping.mna_node_index(self.positive),
+            negative: node_mapping.mna_node_index(self.negative),
+            density: 4.0 * BOLTZMANN * t / self.heated_resistance(),
+        })
+    }
 }
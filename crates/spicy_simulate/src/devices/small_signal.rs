@@ -0,0 +1,52 @@
+//! Linearization of the nonlinear devices for the small-signal analyses (AC and noise).
+//!
+//! Every nonlinear device implements [`SmallSignalModel`] and is listed by
+//! [`Devices::small_signal_models`], so the AC system and the decision to solve an operating
+//! point first cover the same devices.
+
+use ndarray::Array2;
+use spicy_parser::node_mapping::NodeMapping;
+
+use super::Devices;
+
+/// A device whose small-signal admittance depends on the operating point.
+pub(crate) trait SmallSignalModel {
+    /// Stamp the admittance of the device linearized at the operating point `op`, at the
+    /// angular frequency `w`: the conductances into `ar` and the susceptances of its charges
+    /// (`w * C`) into `ai`.
+    fn stamp_small_signal(
+        &self,
+        ar: &mut Array2<f64>,
+        ai: &mut Array2<f64>,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+        w: f64,
+    );
+}
+
+impl Devices {
+    /// Every nonlinear device, by kind: diodes, lookup tables, BJTs, MOSFETs, JFETs, B sources,
+    /// then switches.
+    pub(crate) fn small_signal_models(&self) -> impl Iterator<Item = &dyn SmallSignalModel> {
+        let diodes = self.diodes.iter().map(|d| d as &dyn SmallSignalModel);
+        let lookup_tables = self
+            .lookup_tables
+            .iter()
+            .map(|t| t as &dyn SmallSignalModel);
+        let bjts = self.bjts.iter().map(|q| q as &dyn SmallSignalModel);
+        let mosfets = self.mosfets.iter().map(|m| m as &dyn SmallSignalModel);
+        let jfets = self.jfets.iter().map(|j| j as &dyn SmallSignalModel);
+        let behavioral_sources = self
+            .behavioral_sources
+            .iter()
+            .map(|b| b as &dyn SmallSignalModel);
+        let switches = self.switches.iter().map(|s| s as &dyn SmallSignalModel);
+        diodes
+            .chain(lookup_tables)
+            .chain(bjts)
+            .chain(mosfets)
+            .chain(jfets)
+            .chain(behavioral_sources)
+            .chain(switches)
+    }
+}
@@ -1,5 +1,7 @@
 use super::stamp::NodeVoltageSourceStamp;
 use crate::matrix::SolverMatrix;
+use crate::op_report::DeviceOperatingPoint;
+use crate::util::get_voltage_diff;
 use ndarray::{Array1, Array2};
 use spicy_parser::Value;
 use spicy_parser::devices::IndependentSourceSpec;
@@ -37,6 +39,43 @@ impl IndependentSource {
         }
     }
 
+    /// Voltage, current and power absorbed (negative when the source delivers power) at the
+    /// operating point `op`. A voltage source's current is its branch current, into the
+    /// positive terminal.
+    pub(crate) fn operating_point_voltage(
+        &self,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+    ) -> DeviceOperatingPoint {
+        let v = get_voltage_diff(
+            op,
+            node_mapping.mna_node_index(self.positive),
+            node_mapping.mna_node_index(self.negative),
+        );
+        let i = op[node_mapping.mna_branch_index(self.current_branch)];
+        DeviceOperatingPoint::new(&self.name, [("v", v), ("i", i), ("p", v * i)])
+    }
+
+    /// Voltage across, current and power absorbed (negative when the source delivers power)
+    /// at the solution `op` of time `t` of a transient with step `dt` and stop `tstop` (all 0
+    /// for an operating point). The current leaves the source at its positive terminal.
+    pub(crate) fn operating_point_current(
+        &self,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+        t: f64,
+        dt: f64,
+        tstop: f64,
+    ) -> DeviceOperatingPoint {
+        let v = get_voltage_diff(
+            op,
+            node_mapping.mna_node_index(self.positive),
+            node_mapping.mna_node_index(self.negative),
+        );
+        let i = self.dc.compute(t, dt, tstop);
+        DeviceOperatingPoint::new(&self.name, [("v", v), ("i", i), ("p", -v * i)])
+    }
+
     /// Stamp the B / B^T incidence entries for a voltage-defined element.
     pub(crate) fn stamp_voltage_incidence(&self, m: &mut SolverMatrix) {
         if let Some((pos_branch, branch_pos)) = self.stamp.pos_branch {
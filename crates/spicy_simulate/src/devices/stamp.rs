@@ -61,6 +61,46 @@ impl NodePairStamp {
         Ok(())
     }
 
+    /// Like [`Self::set_temp_indices_from_nodes`], with the (column, row) pattern of the stamp
+    /// handed to `entries` at once; it returns the index of the first entry, the rest following
+    /// consecutively.
+    pub fn set_temp_indices_from_pattern<F, E>(
+        &mut self,
+        pos: Option<usize>,
+        neg: Option<usize>,
+        entries: F,
+    ) -> Result<(), E>
+    where
+        F: FnOnce(&[(usize, usize)]) -> Result<usize, E>,
+    {
+        let mut pattern = [(0, 0); 4];
+        let mut len = 0;
+        let mut add = |column: usize, row: usize| {
+            pattern[len] = (column, row);
+            len += 1;
+            len - 1
+        };
+        let pos_pos = pos.map(|p| add(p, p));
+        let neg_neg = neg.map(|n| add(n, n));
+        let off_diagonals = if let (Some(pos), Some(neg)) = (pos, neg) {
+            Some((add(pos, neg), add(neg, pos)))
+        } else {
+            None
+        };
+        if len == 0 {
+            self.set_temp_indices(None, None, None);
+            return Ok(());
+        }
+
+        let first = entries(&pattern[..len])?;
+        self.set_temp_indices(
+            pos_pos.map(|k| first + k),
+            neg_neg.map(|k| first + k),
+            off_diagonals.map(|(a, b)| (first + a, first + b)),
+        );
+        Ok(())
+    }
+
     /// Map temporary indices to their final locations using the provided mapping.
     pub fn set_final_indices<F>(&mut self, mut f: F)
     where
@@ -184,6 +224,56 @@ impl NodeTripletStamp {
     }
 }
 
+/// Cached MNA stamp indices for a MOSFET.
+///
+/// `entries[row][col]`: rows are drain and source, columns drain, gate, source and bulk.
+/// The gate and bulk rows stay empty since they draw no current.
+#[derive(Debug, Clone)]
+pub struct MosfetStamp {
+    pub entries: [[Option<usize>; 4]; 2],
+}
+
+impl MosfetStamp {
+    /// Create a stamp with no indices assigned yet.
+    pub fn uninitialized() -> Self {
+        Self {
+            entries: [[None; 4]; 2],
+        }
+    }
+
+    /// Compute and set temporary indices from the node locations of `[d, g, s, b]`.
+    ///
+    /// The `entry` callback receives (row, column).
+    pub fn set_temp_indices_from_nodes<F, E>(
+        &mut self,
+        nodes: [Option<usize>; 4],
+        mut entry: F,
+    ) -> Result<(), E>
+    where
+        F: FnMut(usize, usize) -> Result<usize, E>,
+    {
+        for (row, row_node) in [nodes[0], nodes[2]].into_iter().enumerate() {
+            for (col, col_node) in nodes.into_iter().enumerate() {
+                self.entries[row][col] = match (row_node, col_node) {
+                    (Some(r), Some(c)) => Some(entry(r, c)?),
+                    _ => None,
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// Map temporary indices to their final locations using the provided mapping.
+    pub fn set_final_indices<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize) -> usize,
+    {
+        for entry in self.entries.iter_mut().flatten() {
+            *entry = entry.map(&mut f);
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct NodeBranchPairStamp {
     // (pos, branch), (branch, pos)
@@ -327,3 +417,44 @@ impl NodeVoltageSourceStamp {
             .map(|(neg_branch, branch_neg)| (f(neg_branch), f(branch_neg)));
     }
 }
+
+/// Cached MNA stamp indices coupling two branch currents: (b1, b2) and (b2, b1).
+#[derive(Debug, Clone)]
+pub struct BranchCouplingStamp {
+    pub off_diagonals: Option<(usize, usize)>,
+}
+
+impl BranchCouplingStamp {
+    /// Create a stamp with no indices assigned yet.
+    pub fn uninitialized() -> Self {
+        Self {
+            off_diagonals: None,
+        }
+    }
+
+    /// Compute and set temporary indices from the two branch locations.
+    ///
+    /// The `entry` callback receives (row, column).
+    pub fn set_temp_indices_from_branches<F, E>(
+        &mut self,
+        branch1: usize,
+        branch2: usize,
+        mut entry: F,
+    ) -> Result<(), E>
+    where
+        F: FnMut(usize, usize) -> Result<usize, E>,
+    {
+        self.off_diagonals = Some((entry(branch1, branch2)?, entry(branch2, branch1)?));
+        Ok(())
+    }
+
+    /// Map temporary indices to their final locations using the provided mapping.
+    pub fn set_final_indices<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize) -> usize,
+    {
+        self.off_diagonals = self
+            .off_diagonals
+            .map(|(b1_b2, b2_b1)| (f(b1_b2), f(b2_b1)));
+    }
+}
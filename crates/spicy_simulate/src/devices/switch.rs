@@ -0,0 +1,366 @@
+use std::cell::Cell;
+
+use super::small_signal::SmallSignalModel;
+use crate::matrix::SolverMatrix;
+use crate::util::get_voltage_diff;
+use ndarray::Array2;
+use spicy_parser::devices::{SwitchControl, SwitchSpec};
+use spicy_parser::netlist_types::{CurrentBranchIndex, NodeIndex};
+use spicy_parser::node_mapping::NodeMapping;
+
+const DEFAULT_ON_RESISTANCE: f64 = 1.0;
+const DEFAULT_OFF_RESISTANCE: f64 = 1e12;
+/// Width of the transition of a switch without hysteresis, relative to its threshold.
+const TRANSITION_FRACTION: f64 = 1e-3;
+/// Width of the transition of a switch without hysteresis or threshold.
+const MIN_TRANSITION_WIDTH: f64 = 1e-6;
+
+/// Cached MNA stamp indices for a switch: the entries of its positive and negative rows in
+/// the positive, negative and two control columns.
+#[derive(Debug, Clone)]
+pub struct SwitchStamp {
+    pub entries: [[Option<usize>; 4]; 2],
+}
+
+impl SwitchStamp {
+    /// Create a stamp with no indices assigned yet.
+    pub fn uninitialized() -> Self {
+        Self {
+            entries: [[None; 4]; 2],
+        }
+    }
+
+    /// Map temporary indices to their final locations using the provided mapping.
+    pub fn set_final_indices<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize) -> usize,
+    {
+        for entry in self.entries.iter_mut().flatten() {
+            *entry = entry.map(&mut f);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Control {
+    Voltage {
+        positive: NodeIndex,
+        negative: NodeIndex,
+    },
+    Current {
+        branch: CurrentBranchIndex,
+    },
+}
+
+/// A resistor between `ron` and `roff`, set by a control voltage or current.
+///
+/// The switch changes state when the control crosses `threshold + hysteresis` while off, or
+/// `threshold - hysteresis` while on. The state is only taken from accepted points; within
+/// the Newton loop the conductance goes smoothly (log-linearly) from off to on over
+/// `width` around the crossing of the current state, so that toggling does not make Newton
+/// jump back and forth between the two resistances.
+#[derive(Debug, Clone)]
+pub struct Switch {
+    pub name: String,
+    pub positive: NodeIndex,
+    pub negative: NodeIndex,
+    pub control: Control,
+    pub threshold: f64,
+    pub hysteresis: f64,
+    pub on_conductance: f64,
+    pub off_conductance: f64,
+    /// Width of the transition, in the unit of the control.
+    pub width: f64,
+    /// The state at the last accepted point. It sits in a `Cell` because the analyses hold
+    /// the device list by shared reference.
+    on: Cell<bool>,
+    pub stamp: SwitchStamp,
+}
+
+impl Switch {
+    pub fn from_spec(spec: &SwitchSpec) -> Self {
+        let (control, threshold, hysteresis, ron, roff) = match &spec.control {
+            SwitchControl::Voltage {
+                positive,
+                negative,
+                model,
+            } => (
+                Control::Voltage {
+                    positive: *positive,
+                    negative: *negative,
+                },
+                &model.vt,
+                &model.vh,
+                &model.ron,
+                &model.roff,
+            ),
+            SwitchControl::Current { branch, model, .. } => (
+                Control::Current { branch: *branch },
+                &model.it,
+                &model.ih,
+                &model.ron,
+                &model.roff,
+            ),
+        };
+        let threshold = threshold.as_ref().map(|v| v.get_value()).unwrap_or(0.0);
+        let hysteresis = hysteresis
+            .as_ref()
+            .map(|v| v.get_value().abs())
+            .unwrap_or(0.0);
+        let ron = ron
+            .as_ref()
+            .map(|v| v.get_value())
+            .unwrap_or(DEFAULT_ON_RESISTANCE);
+        let roff = roff
+            .as_ref()
+            .map(|v| v.get_value())
+            .unwrap_or(DEFAULT_OFF_RESISTANCE);
+        let width = if hysteresis > 0.0 {
+            hysteresis
+        } else {
+            (TRANSITION_FRACTION * threshold.abs()).max(MIN_TRANSITION_WIDTH)
+        };
+
+        Self {
+            name: spec.name.clone(),
+            positive: spec.positive,
+            negative: spec.negative,
+            control,
+            threshold,
+            hysteresis,
+            on_conductance: 1.0 / ron,
+            off_conductance: 1.0 / roff,
+            width,
+            on: Cell::new(spec.on),
+            stamp: SwitchStamp::uninitialized(),
+        }
+    }
+
+    /// MNA columns of the control and the sign they enter it with.
+    fn control_columns(&self, node_mapping: &NodeMapping) -> [(Option<usize>, f64); 2] {
+        match self.control {
+            Control::Voltage { positive, negative } => [
+                (node_mapping.mna_node_index(positive), 1.0),
+                (node_mapping.mna_node_index(negative), -1.0),
+            ],
+            Control::Current { branch } => [
+                (Some(node_mapping.mna_branch_index(branch)), 1.0),
+                (None, 0.0),
+            ],
+        }
+    }
+
+    fn control_value(&self, node_mapping: &NodeMapping, x: &[f64]) -> f64 {
+        self.control_columns(node_mapping)
+            .iter()
+            .filter_map(|&(column, sign)| Some(sign * x[column?]))
+            .sum()
+    }
+
+    /// The control value at which the switch leaves its current state.
+    fn crossing(&self) -> f64 {
+        if self.on.get() {
+            self.threshold - self.hysteresis
+        } else {
+            self.threshold + self.hysteresis
+        }
+    }
+
+    /// Conductance at `control` and its derivative with respect to it.
+    fn conductance(&self, control: f64) -> (f64, f64) {
+        let x = ((control - self.crossing()) / self.width + 0.5).clamp(0.0, 1.0);
+        // smoothstep, with zero slope at both ends of the transition
+        let s = x * x * (3.0 - 2.0 * x);
+        let ds = 6.0 * x * (1.0 - x) / self.width;
+        let log_ratio = (self.on_conductance / self.off_conductance).ln();
+        let g = self.off_conductance * (s * log_ratio).exp();
+        (g, g * log_ratio * ds)
+    }
+
+    /// The state at the last accepted point.
+    pub(crate) fn is_on(&self) -> bool {
+        self.on.get()
+    }
+
+    /// Carry on from the state of an earlier transient, see [`Self::is_on`].
+    pub(crate) fn restore_state(&self, on: bool) {
+        self.on.set(on);
+    }
+
+    /// Take the state of an accepted point.
+    pub(crate) fn update_state(&self, node_mapping: &NodeMapping, solution: &[f64]) {
+        let control = self.control_value(node_mapping, solution);
+        self.on.set(control > self.crossing());
+    }
+
+    /// Reserve the entries of the switch, through `entry(row, column)`.
+    pub(crate) fn setup<F, E>(&mut self, node_mapping: &NodeMapping, mut entry: F) -> Result<(), E>
+    where
+        F: FnMut(usize, usize) -> Result<usize, E>,
+    {
+        let pos = node_mapping.mna_node_index(self.positive);
+        let neg = node_mapping.mna_node_index(self.negative);
+        let [(control1, _), (control2, _)] = self.control_columns(node_mapping);
+        let columns = [pos, neg, control1, control2];
+        for (row, entries) in [pos, neg].into_iter().zip(&mut self.stamp.entries) {
+            let Some(row) = row else {
+                continue;
+            };
+            for (column, slot) in columns.iter().zip(entries.iter_mut()) {
+                *slot = column.map(|column| entry(row, column)).transpose()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Derivatives of the current from the positive to the negative node with respect to the
+    /// positive, negative and control columns, and the current at `x`.
+    fn linearize(&self, node_mapping: &NodeMapping, x: &[f64]) -> ([f64; 4], f64) {
+        let pos = node_mapping.mna_node_index(self.positive);
+        let neg = node_mapping.mna_node_index(self.negative);
+        let v = get_voltage_diff(x, pos, neg);
+        let (g, dg) = self.conductance(self.control_value(node_mapping, x));
+        let [(_, sign1), (_, sign2)] = self.control_columns(node_mapping);
+        ([g, -g, sign1 * dg * v, sign2 * dg * v], g * v)
+    }
+
+    /// Linearize i = g(control) * v around the Newton guess.
+    pub(crate) fn stamp_nonlinear(&self, m: &mut SolverMatrix, guess: &[f64]) {
+        let node_mapping = m.node_mapping();
+        let (jacobian, i) = self.linearize(node_mapping, guess);
+        let pos = node_mapping.mna_node_index(self.positive);
+        let neg = node_mapping.mna_node_index(self.negative);
+        let [(control1, _), (control2, _)] = self.control_columns(node_mapping);
+        let columns = [pos, neg, control1, control2];
+        let i_eq = i - jacobian
+            .iter()
+            .zip(columns)
+            .filter_map(|(d, column)| Some(d * guess[column?]))
+            .sum::<f64>();
+
+        for (row, sign, entries) in [(pos, 1.0, 0), (neg, -1.0, 1)] {
+            let Some(row) = row else {
+                continue;
+            };
+            for (d, entry) in jacobian.iter().zip(self.stamp.entries[entries]) {
+                if let Some(entry) = entry {
+                    *m.get_mut_nnz(entry) += sign * d;
+                }
+            }
+            *m.get_mut_rhs(row) -= sign * i_eq;
+        }
+    }
+}
+
+impl SmallSignalModel for Switch {
+    /// Stamp the small-signal conductances at the operating point `op`.
+    fn stamp_small_signal(
+        &self,
+        ar: &mut Array2<f64>,
+        _ai: &mut Array2<f64>,
+        node_mapping: &NodeMapping,
+        op: &[f64],
+        _w: f64,
+    ) {
+        let (jacobian, _) = self.linearize(node_mapping, op);
+        let pos = node_mapping.mna_node_index(self.positive);
+        let neg = node_mapping.mna_node_index(self.negative);
+        let [(control1, _), (control2, _)] = self.control_columns(node_mapping);
+        let columns = [pos, neg, control1, control2];
+        for (row, sign) in [(pos, 1.0), (neg, -1.0)] {
+            let Some(row) = row else {
+                continue;
+            };
+            for (d, column) in jacobian.iter().zip(columns) {
+                if let Some(column) = column {
+                    ar[[row, column]] += sign * d;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::parse_netlist;
+    use crate::{SimulationConfig, dc::simulate_op, trans::simulate_trans};
+    use spicy_parser::netlist_types::Command;
+
+    const DIVIDER: &str = "s\n.model sw1 SW vt=1 vh=0.5 ron=1 roff=1Meg\n\
+        V1 in 0 1\nR1 in out 1k\nS1 out 0 ctrl 0 sw1\n";
+
+    #[test]
+    fn conductance_is_smooth_across_the_transition() {
+        let deck = parse_netlist(&format!("{DIVIDER}Vc ctrl 0 0\n.end\n"));
+        let switch = Switch::from_spec(&deck.devices.switches[0]);
+        // off: the transition is centered on vt + vh, over vh
+        assert!((switch.conductance(1.0).0 - 1e-6).abs() < 1e-18);
+        assert!((switch.conductance(2.0).0 - 1.0).abs() < 1e-12);
+        let (g, dg) = switch.conductance(1.5);
+        assert!(
+            (g - 1e-3).abs() < 1e-12,
+            "geometric mean at the crossing: {g}"
+        );
+
+        let h = 1e-7;
+        let numeric = (switch.conductance(1.5 + h).0 - switch.conductance(1.5 - h).0) / (2.0 * h);
+        assert!((numeric - dg).abs() < 1e-6 * dg.abs(), "{numeric} vs {dg}");
+    }
+
+    #[test]
+    fn op_follows_the_control() {
+        let off = simulate_op(
+            &parse_netlist(&format!("{DIVIDER}Vc ctrl 0 0\n.end\n")),
+            &SimulationConfig::default(),
+        )
+        .expect("op");
+        assert!(off.voltage("out").unwrap() > 0.99);
+
+        let on = simulate_op(
+            &parse_netlist(&format!("{DIVIDER}Vc ctrl 0 3\n.end\n")),
+            &SimulationConfig::default(),
+        )
+        .expect("op");
+        assert!(on.voltage("out").unwrap() < 1e-2);
+    }
+
+    #[test]
+    fn transient_shows_hysteresis() {
+        // the control goes up to 2 and back: the switch closes near vt + vh = 1.5 and opens
+        // near vt - vh = 0.5
+        let deck = parse_netlist(&format!(
+            "{DIVIDER}Vc ctrl 0 PWL(0 0 1 2 2 0)\n.tran 10m 2\n.end\n"
+        ));
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+        let result = simulate_trans(&deck, tran, &SimulationConfig::default()).expect("tran");
+        let ctrl = result.voltage("ctrl").unwrap().y;
+        let out = result.voltage("out").unwrap().y;
+
+        let closed = (0..=100).find(|&i| out[i] < 0.5).expect("switch closes");
+        assert!(
+            (1.5..1.6).contains(&ctrl[closed]),
+            "closed at {}",
+            ctrl[closed]
+        );
+        let opened = (closed..out.len())
+            .find(|&i| out[i] > 0.5)
+            .expect("switch opens");
+        assert!(
+            (0.4..0.5).contains(&ctrl[opened]),
+            "opened at {}",
+            ctrl[opened]
+        );
+    }
+
+    #[test]
+    fn current_controlled_switch() {
+        let netlist = "w\n.model csw1 CSW it=1m ron=1 roff=1Meg\n\
+            V1 in 0 1\nR1 in out 1k\nW1 out 0 Vsense csw1\n\
+            I1 a 0 2m\nVsense a 0 0\n.end\n";
+        let op = simulate_op(&parse_netlist(netlist), &SimulationConfig::default()).expect("op");
+        assert!(op.voltage("out").unwrap() < 1e-2);
+    }
+}
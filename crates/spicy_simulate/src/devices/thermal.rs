@@ -0,0 +1,266 @@
+//! Self-heating: a device with a thermal RC heats a thermal node of its own with the power it
+//! dissipates, and runs at the temperature of that node.
+//!
+//! The thermal nodes are not part of the MNA system. An analysis point is solved at the rises
+//! of the previous solve, the rises are updated from the power dissipated at the solution and
+//! the point is solved again, until the rises settle.
+
+use std::cell::Cell;
+
+use spicy_parser::Value;
+use spicy_parser::node_mapping::NodeMapping;
+
+use super::Devices;
+use crate::error::SimulationError;
+use crate::matrix::SolverMatrix;
+
+/// Largest change (K) of a rise between two solves of a point for the rises to have settled.
+pub(crate) const RISE_TOLERANCE: f64 = 1e-3;
+/// Solves of an analysis point before giving up on the rises settling.
+pub(crate) const MAX_THERMAL_ITERATIONS: usize = 50;
+
+/// Thermal resistance to the ambient and thermal capacitance of a self-heating device, with
+/// the temperature rise of its thermal node.
+#[derive(Debug, Clone)]
+pub struct ThermalRc {
+    /// Thermal resistance (K/W).
+    pub rth: f64,
+    /// Thermal capacitance (J/K), 0 for a node that follows the power without delay.
+    pub cth: f64,
+    /// Rise (K) over the device temperature at the point being solved. The rises sit in a
+    /// `Cell` because the analyses hold the device list by shared reference.
+    rise: Cell<f64>,
+    /// Rise at the last accepted point.
+    accepted: Cell<f64>,
+}
+
+impl ThermalRc {
+    /// The thermal RC of `rth` and `cth`, none without a positive `rth`.
+    pub fn from_values(rth: Option<&Value>, cth: Option<&Value>) -> Option<Self> {
+        let rth = rth.map(Value::get_value).filter(|rth| *rth > 0.0)?;
+        Some(Self {
+            rth,
+            cth: cth.map_or(0.0, Value::get_value),
+            rise: Cell::new(0.0),
+            accepted: Cell::new(0.0),
+        })
+    }
+
+    /// Temperature rise (K) at the point being solved.
+    pub(crate) fn rise(&self) -> f64 {
+        self.rise.get()
+    }
+
+    /// Heat the node with `power` (W) over a time step of `step` seconds from the accepted
+    /// rise (backward Euler), or to its steady state `power * rth` without a step. Returns how
+    /// much the rise moved.
+    pub(crate) fn heat(&self, power: f64, step: Option<f64>) -> f64 {
+        let rise = match step {
+            // cth * (rise - accepted) / step = power - rise / rth
+            Some(step) if self.cth > 0.0 => {
+                let c = self.cth / step;
+                (c * self.accepted.get() + power) / (c + 1.0 / self.rth)
+            }
+            _ => power * self.rth,
+        };
+        let moved = (rise - self.rise.get()).abs();
+        self.rise.set(rise);
+        moved
+    }
+
+    /// Take the rise of the point just solved as the start of the next step.
+    pub(crate) fn accept(&self) {
+        self.accepted.set(self.rise.get());
+    }
+
+    /// Rise at the last accepted point, see [`Self::accept`].
+    pub(crate) fn accepted(&self) -> f64 {
+        self.accepted.get()
+    }
+
+    /// Carry on from the rise of an earlier transient.
+    pub(crate) fn restore(&self, rise: f64) {
+        self.rise.set(rise);
+        self.accepted.set(rise);
+    }
+}
+
+impl Devices {
+    /// Every self-heating device by name: resistors, then BJTs.
+    pub(crate) fn thermal_rcs(&self) -> impl Iterator<Item = (&str, &ThermalRc)> {
+        let resistors = self
+            .resistors
+            .iter()
+            .filter_map(|r| Some((r.name.as_str(), r.heating.as_ref()?)));
+        let bjts = self
+            .bjts
+            .iter()
+            .filter_map(|q| Some((q.name.as_str(), &q.heating.as_ref()?.thermal)));
+        resistors.chain(bjts)
+    }
+
+    /// Heat every self-heating device with the power it dissipates at the solution `x`, see
+    /// [`ThermalRc::heat`]. Returns the device whose rise moved the most when it moved by more
+    /// than [`RISE_TOLERANCE`].
+    pub(crate) fn heat(
+        &self,
+        node_mapping: &NodeMapping,
+        x: &[f64],
+        step: Option<f64>,
+    ) -> Option<&str> {
+        let resistors = self.resistors.iter().filter_map(|r| {
+            let moved = r.heating.as_ref()?.heat(r.power(node_mapping, x), step);
+            Some((r.name.as_str(), moved))
+        });
+        let bjts = self.bjts.iter().filter_map(|q| {
+            let heating = q.heating.as_ref()?;
+            let moved = heating.thermal.heat(q.power(node_mapping, x), step);
+            Some((q.name.as_str(), moved))
+        });
+        resistors
+            .chain(bjts)
+            .filter(|(_, moved)| *moved > RISE_TOLERANCE)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(name, _)| name)
+    }
+
+    /// Accept the rises of every self-heating device, see [`ThermalRc::accept`].
+    pub(crate) fn accept_heat(&self) {
+        for (_, thermal) in self.thermal_rcs() {
+            thermal.accept();
+        }
+    }
+
+    /// Solve a point at `time` (none for a DC point) `step` seconds after the accepted one
+    /// until the rises settle: `solved` is the first solve, and `solve(m, guess)` solves the
+    /// point again at the rises of the last solution. Returns the solution and the Newton
+    /// iterations of all the solves.
+    pub(crate) fn solve_heated<F>(
+        &self,
+        m: &mut SolverMatrix,
+        solved: (Vec<f64>, usize),
+        time: Option<f64>,
+        step: Option<f64>,
+        mut solve: F,
+    ) -> Result<(Vec<f64>, usize), SimulationError>
+    where
+        F: FnMut(&mut SolverMatrix, Vec<f64>) -> Result<(Vec<f64>, usize), SimulationError>,
+    {
+        let (mut x, mut iters) = solved;
+        for _ in 0..MAX_THERMAL_ITERATIONS {
+            if self.heat(m.node_mapping(), &x, step).is_none() {
+                return Ok((x, iters));
+            }
+            let (next, more) = solve(m, x)?;
+            x = next;
+            iters += more;
+        }
+        match self.heat(m.node_mapping(), &x, step) {
+            None => Ok((x, iters)),
+            Some(hottest) => Err(SimulationError::NonConvergence {
+                time,
+                iters,
+                unknown: format!("T({hottest})"),
+            }),
+        }
+    }
+
+    /// Bring the rises to the solution `x` of a point `step` seconds after the accepted one
+    /// (to their steady state without a step) and accept them, for the reports that evaluate
+    /// the devices at a solution saved by an analysis.
+    pub(crate) fn settle_heat(&self, node_mapping: &NodeMapping, x: &[f64], step: Option<f64>) {
+        for _ in 0..MAX_THERMAL_ITERATIONS {
+            if self.heat(node_mapping, x, step).is_none() {
+                break;
+            }
+        }
+        self.accept_heat();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dc::simulate_op;
+    use crate::test_utils::parse_netlist;
+    use crate::trans::simulate_trans;
+    use crate::{OpReport, SimulationConfig};
+    use spicy_parser::netlist_types::Command;
+
+    fn report(netlist: &str) -> OpReport {
+        let deck = parse_netlist(netlist);
+        let config = SimulationConfig::default();
+        let op = simulate_op(&deck, &config).expect("op");
+        OpReport::new(&deck, &op, &config)
+    }
+
+    fn assert_close(actual: f64, expected: f64, rel: f64) {
+        assert!(
+            (actual - expected).abs() <= rel * expected.abs(),
+            "{actual} != {expected}"
+        );
+    }
+
+    #[test]
+    fn resistor_settles_where_its_power_heats_it() {
+        let report = report("heater\nV1 a 0 10\nR1 a 0 100 tc1=0.01 rth=50\n.op\n.end\n");
+        // rise = rth * V^2 / (R * (1 + tc1 * rise)), so 0.01 rise^2 + rise - 50 = 0
+        let rise = (3.0f64.sqrt() - 1.0) / 0.02;
+        let r1 = report.device("R1").expect("R1");
+        assert_close(r1.get("t").unwrap(), 27.0 + rise, 1e-4);
+        assert_close(r1.get("i").unwrap(), 10.0 / (100.0 + rise), 1e-4);
+        assert_close(r1.get("p").unwrap() * 50.0, rise, 1e-4);
+    }
+
+    #[test]
+    fn resistor_without_rth_keeps_its_value() {
+        let report = report("cold\nV1 a 0 10\nR1 a 0 100 tc1=0.01 cth=1\n.op\n.end\n");
+        let r1 = report.device("R1").expect("R1");
+        assert_eq!(r1.get("t"), None);
+        assert_close(r1.get("i").unwrap(), 0.1, 1e-12);
+    }
+
+    #[test]
+    fn transient_warms_up_to_the_operating_point() {
+        let netlist = "warm up
+V1 in 0 10
+R2 in out 100
+.model hot r(tc1=0.01 rth=50 cth=1m)
+R1 out 0 100 hot
+.tran 1m 400m uic
+.end
+";
+        let deck = parse_netlist(netlist);
+        let config = SimulationConfig::default();
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+        let result = simulate_trans(&deck, tran, &config).expect("tran");
+        let out = result.voltage("out").unwrap().y;
+        // the thermal time constant is rth * cth = 50 ms: cold at the start, heated at the end
+        assert!((out[1] - 5.0).abs() < 0.05, "{}", out[1]);
+        // up to the tolerance the rises settle to
+        assert!(out.windows(2).skip(1).all(|w| w[1] >= w[0] - 1e-6));
+
+        let op = simulate_op(&deck, &config).expect("op");
+        let settled = op.voltage("out").unwrap();
+        assert!(settled > 5.2, "{settled}");
+        assert_close(*out.last().unwrap(), settled, 1e-3);
+    }
+
+    #[test]
+    fn heated_bjt_conducts_more() {
+        let netlist = |rth: &str| {
+            format!(
+                "bjt\nVBE b 0 0.65\nVCE c 0 5\nQ1 c b 0 qn\n\
+                 .model qn npn(is=1e-14 bf=100 {rth})\n.op\n.end\n"
+            )
+        };
+        let cold = report(&netlist(""));
+        let hot = report(&netlist("rth=1k"));
+        let (cold, hot) = (cold.device("Q1").unwrap(), hot.device("Q1").unwrap());
+        assert_eq!(cold.get("t"), None);
+        let t = hot.get("t").unwrap();
+        assert_close(t, 27.0 + 1e3 * hot.get("p").unwrap(), 1e-3);
+        assert!(hot.get("ic").unwrap() > 1.4 * cold.get("ic").unwrap());
+    }
+}
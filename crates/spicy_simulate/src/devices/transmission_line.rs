@@ -0,0 +1,352 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use super::stamp::NodeVoltageSourceStamp;
+use crate::matrix::SolverMatrix;
+use crate::util::get_voltage_diff;
+use ndarray::Array2;
+use spicy_parser::devices::TransmissionLineSpec;
+use spicy_parser::netlist_types::{CurrentBranchIndex, NodeIndex};
+use spicy_parser::node_mapping::NodeMapping;
+
+/// Cached MNA stamp indices for a transmission line.
+#[derive(Debug, Clone)]
+pub struct TransmissionLineStamp {
+    /// B / B^T entries of each port.
+    pub ports: [NodeVoltageSourceStamp; 2],
+    /// (b1, b1) and (b2, b2).
+    pub diagonals: Option<(usize, usize)>,
+    /// Entries of each port's branch row in the columns of the other port: its positive
+    /// node, negative node and branch.
+    pub far_end: [[Option<usize>; 3]; 2],
+}
+
+impl TransmissionLineStamp {
+    /// Create a stamp with no indices assigned yet.
+    pub fn uninitialized() -> Self {
+        Self {
+            ports: [
+                NodeVoltageSourceStamp::uninitialized(),
+                NodeVoltageSourceStamp::uninitialized(),
+            ],
+            diagonals: None,
+            far_end: [[None; 3]; 2],
+        }
+    }
+
+    /// Map temporary indices to their final locations using the provided mapping.
+    pub fn set_final_indices<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize) -> usize,
+    {
+        for port in &mut self.ports {
+            port.set_final_indices(&mut f);
+        }
+        self.diagonals = self.diagonals.map(|(b1_b1, b2_b2)| (f(b1_b1), f(b2_b2)));
+        for entry in self.far_end.iter_mut().flatten() {
+            *entry = entry.map(&mut f);
+        }
+    }
+}
+
+/// An ideal lossless line, as the Branin model: each port is Z0 in series with a source
+/// driven by the wave arriving from the other port.
+///
+/// V1 - Z0 I1 = c (V2 + Z0 I2) and V2 - Z0 I2 = c (V1 + Z0 I1), with the currents flowing
+/// into the line at the positive nodes, where c is a delay of TD: 1 at DC, e^(-jwTD) in AC
+/// and the value of the wave TD ago in a transient.
+#[derive(Debug, Clone)]
+pub struct TransmissionLine {
+    pub name: String,
+    pub positive1: NodeIndex,
+    pub negative1: NodeIndex,
+    pub positive2: NodeIndex,
+    pub negative2: NodeIndex,
+    pub branch1: CurrentBranchIndex,
+    pub branch2: CurrentBranchIndex,
+    pub z0: f64,
+    pub delay: f64,
+    pub stamp: TransmissionLineStamp,
+    /// Accepted transient time points with the wave arriving at each port, oldest first.
+    ///
+    /// Like the plugin devices this sits in a `RefCell`, because transient analysis holds the
+    /// device list by shared reference.
+    history: RefCell<VecDeque<(f64, [f64; 2])>>,
+}
+
+impl TransmissionLine {
+    pub fn from_spec(spec: &TransmissionLineSpec) -> Self {
+        Self {
+            name: spec.name.clone(),
+            positive1: spec.positive1,
+            negative1: spec.negative1,
+            positive2: spec.positive2,
+            negative2: spec.negative2,
+            branch1: spec.branch1,
+            branch2: spec.branch2,
+            z0: spec.z0.get_value(),
+            delay: spec.td.get_value(),
+            stamp: TransmissionLineStamp::uninitialized(),
+            history: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    /// MNA positive node, negative node and branch of each port.
+    fn ports(&self, node_mapping: &NodeMapping) -> [(Option<usize>, Option<usize>, usize); 2] {
+        [
+            (
+                node_mapping.mna_node_index(self.positive1),
+                node_mapping.mna_node_index(self.negative1),
+                node_mapping.mna_branch_index(self.branch1),
+            ),
+            (
+                node_mapping.mna_node_index(self.positive2),
+                node_mapping.mna_node_index(self.negative2),
+                node_mapping.mna_branch_index(self.branch2),
+            ),
+        ]
+    }
+
+    /// Reserve the entries of the line, through `entry(row, column)`.
+    pub(crate) fn setup<F, E>(&mut self, node_mapping: &NodeMapping, mut entry: F) -> Result<(), E>
+    where
+        F: FnMut(usize, usize) -> Result<usize, E>,
+    {
+        let ports = self.ports(node_mapping);
+        for (stamp, &(pos, neg, branch)) in self.stamp.ports.iter_mut().zip(&ports) {
+            stamp.set_temp_indices_from_nodes(pos, neg, branch, |col, row| entry(row, col))?;
+        }
+        let (b1, b2) = (ports[0].2, ports[1].2);
+        self.stamp.diagonals = Some((entry(b1, b1)?, entry(b2, b2)?));
+        for (k, &(_, _, branch)) in ports.iter().enumerate() {
+            let (pos, neg, far_branch) = ports[1 - k];
+            self.stamp.far_end[k] = [
+                pos.map(|p| entry(branch, p)).transpose()?,
+                neg.map(|n| entry(branch, n)).transpose()?,
+                Some(entry(branch, far_branch)?),
+            ];
+        }
+        Ok(())
+    }
+
+    /// Stamp V_k - Z0 I_k - weight (V_far + Z0 I_far) = waves[k] on each port's branch row.
+    fn stamp(&self, m: &mut SolverMatrix, weight: f64, waves: [f64; 2]) {
+        for port in &self.stamp.ports {
+            if let Some((pos_branch, branch_pos)) = port.pos_branch {
+                *m.get_mut_nnz(pos_branch) += 1.0;
+                *m.get_mut_nnz(branch_pos) += 1.0;
+            }
+            if let Some((neg_branch, branch_neg)) = port.neg_branch {
+                *m.get_mut_nnz(neg_branch) -= 1.0;
+                *m.get_mut_nnz(branch_neg) -= 1.0;
+            }
+        }
+        if let Some((b1_b1, b2_b2)) = self.stamp.diagonals {
+            *m.get_mut_nnz(b1_b1) -= self.z0;
+            *m.get_mut_nnz(b2_b2) -= self.z0;
+        }
+        for [pos, neg, branch] in self.stamp.far_end {
+            for (entry, value) in [(pos, -weight), (neg, weight), (branch, -weight * self.z0)] {
+                if let Some(entry) = entry {
+                    *m.get_mut_nnz(entry) += value;
+                }
+            }
+        }
+        let b1 = m.mna_branch_index(self.branch1);
+        let b2 = m.mna_branch_index(self.branch2);
+        *m.get_mut_rhs(b1) += waves[0];
+        *m.get_mut_rhs(b2) += waves[1];
+    }
+
+    /// At DC the waves arrive undelayed, so V1 = V2 and I1 = -I2.
+    pub(crate) fn stamp_dc(&self, m: &mut SolverMatrix) {
+        self.stamp(m, 1.0, [0.0; 2]);
+    }
+
+    /// Stamp the line at `time`, with the waves that left the other port at `time - TD`.
+    ///
+    /// Those are interpolated between the accepted points. When the delay is shorter than
+    /// the step they fall after the last one, and the interpolation with the unknowns of
+    /// `time` goes into the matrix.
+    pub(crate) fn stamp_trans(&self, m: &mut SolverMatrix, time: f64) {
+        let history = self.history.borrow();
+        let delayed = time - self.delay;
+        let &(t_last, last) = history
+            .back()
+            .expect("the history starts at the initial transient point");
+        if delayed >= t_last {
+            let weight = if time > t_last {
+                (delayed - t_last) / (time - t_last)
+            } else {
+                0.0
+            };
+            drop(history);
+            self.stamp(m, weight, last.map(|wave| (1.0 - weight) * wave));
+            return;
+        }
+
+        let after = history.partition_point(|&(t, _)| t <= delayed);
+        let waves = match after.checked_sub(1) {
+            // before the first point the line is still in its initial state
+            None => history[0].1,
+            Some(before) => {
+                let (t0, w0) = history[before];
+                let (t1, w1) = history[after];
+                let s = (delayed - t0) / (t1 - t0);
+                [0, 1].map(|k| w0[k] + s * (w1[k] - w0[k]))
+            }
+        };
+        drop(history);
+        self.stamp(m, 0.0, waves);
+    }
+
+    /// Forget the previous transient and start the history from the initial point.
+    pub(crate) fn start_history(&self, node_mapping: &NodeMapping, solution: &[f64]) {
+        self.history.borrow_mut().clear();
+        self.record(node_mapping, 0.0, solution);
+    }
+
+    /// The recorded points, oldest first.
+    pub(crate) fn history(&self) -> Vec<(f64, [f64; 2])> {
+        self.history.borrow().iter().copied().collect()
+    }
+
+    /// Carry on from the points of an earlier transient, see [`Self::history`].
+    pub(crate) fn restore_history(&self, history: Vec<(f64, [f64; 2])>) {
+        *self.history.borrow_mut() = history.into();
+    }
+
+    /// Record an accepted transient point, dropping the points no later step can reach.
+    pub(crate) fn record(&self, node_mapping: &NodeMapping, time: f64, solution: &[f64]) {
+        let [(pos1, neg1, b1), (pos2, neg2, b2)] = self.ports(node_mapping);
+        let v1 = get_voltage_diff(solution, pos1, neg1);
+        let v2 = get_voltage_diff(solution, pos2, neg2);
+        let waves = [v2 + self.z0 * solution[b2], v1 + self.z0 * solution[b1]];
+
+        let mut history = self.history.borrow_mut();
+        history.push_back((time, waves));
+        while history.len() > 1 && history[1].0 <= time - self.delay {
+            history.pop_front();
+        }
+    }
+
+    /// Stamp the exact two-port: the waves arrive with a phase of -wTD.
+    pub(crate) fn stamp_ac(
+        &self,
+        ar: &mut Array2<f64>,
+        ai: &mut Array2<f64>,
+        node_mapping: &NodeMapping,
+        w: f64,
+    ) {
+        let ports = self.ports(node_mapping);
+        let (sin, cos) = (w * self.delay).sin_cos();
+        for (k, &(pos, neg, branch)) in ports.iter().enumerate() {
+            if let Some(p) = pos {
+                ar[[p, branch]] += 1.0;
+                ar[[branch, p]] += 1.0;
+            }
+            if let Some(n) = neg {
+                ar[[n, branch]] -= 1.0;
+                ar[[branch, n]] -= 1.0;
+            }
+            ar[[branch, branch]] -= self.z0;
+
+            // -e^(-jwTD) (V_far + Z0 I_far)
+            let (far_pos, far_neg, far_branch) = ports[1 - k];
+            for (column, value) in [(far_pos, 1.0), (far_neg, -1.0), (Some(far_branch), self.z0)] {
+                if let Some(column) = column {
+                    ar[[branch, column]] -= cos * value;
+                    ai[[branch, column]] += sin * value;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::parse_netlist;
+    use crate::{SimulationConfig, ac::simulate_ac, dc::simulate_op, trans::simulate_trans};
+    use rstest::rstest;
+    use spicy_parser::netlist_types::{Command, NodeName};
+    use std::f64::consts::FRAC_PI_4;
+
+    fn mna(deck: &spicy_parser::instance_parser::Deck, name: &str) -> usize {
+        let node = deck
+            .node_mapping
+            .get_node(&NodeName(name.to_string()))
+            .unwrap();
+        deck.node_mapping.mna_node_index(node).unwrap()
+    }
+
+    #[test]
+    fn dc_passes_the_voltage_through() {
+        let deck =
+            parse_netlist("t\nV1 in 0 1\nR1 in a 50\nT1 a 0 b 0 Z0=50 TD=1u\nR2 b 0 50\n.end\n");
+        let op = simulate_op(&deck, &SimulationConfig::default()).expect("op");
+        assert!((op.voltage("a").unwrap() - 0.5).abs() < 1e-12);
+        assert!((op.voltage("b").unwrap() - 0.5).abs() < 1e-12);
+    }
+
+    #[rstest]
+    // a matched line only delays the wave: by 1/8 of a period at 125 kHz
+    #[case::matched("R2 b 0 50", 125e3, "b", 0.5, -FRAC_PI_4)]
+    // an open quarter-wave line shorts its input
+    #[case::open_quarter_wave("R2 b 0 1e9", 250e3, "a", 0.0, 0.0)]
+    fn ac_response(
+        #[case] load: &str,
+        #[case] frequency: f64,
+        #[case] node: &str,
+        #[case] magnitude: f64,
+        #[case] phase: f64,
+    ) {
+        let deck = parse_netlist(&format!(
+            "t\nV1 in 0 AC 1\nR1 in a 50\nT1 a 0 b 0 Z0=50 TD=1u\n{load}\n\
+            .ac lin 2 {frequency} {}\n.end\n",
+            2.0 * frequency
+        ));
+        let Some(Command::Ac(ac)) = deck.commands.first() else {
+            panic!("expected .ac");
+        };
+        let node = mna(&deck, node);
+        let sweep = simulate_ac(&deck, ac, &SimulationConfig::default()).expect("ac");
+        let (_, re, im) = &sweep[0];
+        assert!((re[node].hypot(im[node]) - magnitude).abs() < 1e-6);
+        if magnitude > 0.0 {
+            assert!((im[node].atan2(re[node]) - phase).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn transient_matched_line_delays_the_input() {
+        // TD is 10 steps, the far end is the near end 10 samples later
+        let deck = parse_netlist(
+            "t\nV1 in 0 PULSE(0 2 1n 1n 1n 3n 20n)\nR1 in a 50\n\
+            T1 a 0 b 0 Z0=50 TD=1n\nR2 b 0 50\n.tran 0.1n 20n\n.end\n",
+        );
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+        let result = simulate_trans(&deck, tran, &SimulationConfig::default()).expect("tran");
+        let a = result.voltage("a").unwrap().y;
+        let b = result.voltage("b").unwrap().y;
+        assert!(a.iter().any(|&v| (v - 1.0).abs() < 1e-9));
+        for (a, b) in a.iter().zip(b.iter().skip(10)) {
+            assert!((a - b).abs() < 1e-9, "{b} vs {a}");
+        }
+    }
+
+    #[test]
+    fn transient_steps_longer_than_the_delay() {
+        let deck = parse_netlist(
+            "t\nV1 in 0 1\nR1 in a 50\nT1 a 0 b 0 Z0=50 TD=1n\nR2 b 0 50\n.tran 5n 20n\n.end\n",
+        );
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+        let result = simulate_trans(&deck, tran, &SimulationConfig::default()).expect("tran");
+        for v in result.voltage("b").unwrap().y {
+            assert!((v - 0.5).abs() < 1e-9, "{v}");
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! `--dump-matrix`: write the MNA system of one Newton solve to Matrix Market files, to replay
+//! it with the `klu_mtx` tool or SuiteSparse.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::SimulationError;
+use crate::matrix::SolverMatrix;
+use crate::solver::matrix::mtx::{write_matrix_market_array, write_matrix_market_csc};
+
+/// Which solve to dump and where to write it.
+///
+/// The system is linearized at the converged solution of the chosen solve, the way the next
+/// Newton iteration would assemble it. Only the first matching solve of the simulation is
+/// written, even when `.step` points run in parallel.
+#[derive(Debug, Clone)]
+pub struct MatrixDump {
+    /// the matrix goes to `<base>.mtx` and the right-hand side to `<base>_rhs.mtx`
+    pub base: PathBuf,
+    /// dump the first transient point solved at or after this time; `None` dumps the first
+    /// DC solve (an operating point or the first point of a DC sweep)
+    pub time: Option<f64>,
+    written: Arc<AtomicBool>,
+}
+
+impl MatrixDump {
+    pub fn new(base: impl Into<PathBuf>, time: Option<f64>) -> Self {
+        Self {
+            base: base.into(),
+            time,
+            written: Arc::default(),
+        }
+    }
+
+    pub fn matrix_path(&self) -> PathBuf {
+        self.path("")
+    }
+
+    pub fn rhs_path(&self) -> PathBuf {
+        self.path("_rhs")
+    }
+
+    fn path(&self, suffix: &str) -> PathBuf {
+        let mut path = self.base.clone().into_os_string();
+        path.push(format!("{suffix}.mtx"));
+        path.into()
+    }
+
+    /// Whether the solve at `time` (`None` for DC) is the one to dump; true at most once.
+    pub(crate) fn wants(&self, time: Option<f64>) -> bool {
+        let due = match (self.time, time) {
+            (None, None) => true,
+            (Some(at), Some(time)) => time >= at,
+            _ => false,
+        };
+        due && !self.written.swap(true, Ordering::Relaxed)
+    }
+
+    /// Write the matrix and right-hand side currently stamped into `matrix`.
+    pub(crate) fn write(&self, matrix: &SolverMatrix) -> Result<(), SimulationError> {
+        let write = || -> std::io::Result<()> {
+            let mut w = BufWriter::new(File::create(self.matrix_path())?);
+            write_matrix_market_csc(&mut w, &matrix.to_csc())?;
+            w.flush()?;
+
+            let rhs = matrix.rhs();
+            let names: Vec<_> = (0..rhs.len())
+                .map(|i| format!("{} {}", i + 1, matrix.unknown_name(i)))
+                .collect();
+            let mut w = BufWriter::new(File::create(self.rhs_path())?);
+            write_matrix_market_array(&mut w, rhs, &names)?;
+            w.flush()
+        };
+        write().map_err(SimulationError::MatrixDump)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::AnalysisResult;
+    use crate::solver::klu::{self, KluConfig};
+    use crate::solver::matrix::mtx::{
+        load_matrix_market_array_file, load_matrix_market_csc_file_keep_zeros,
+    };
+    use crate::{LinearSolver, SimulationConfig, simulate};
+    use spicy_parser::{ParseOptions, parse};
+
+    /// Solve the dumped system with KLU, returning the solution and the `%` comments of the
+    /// right-hand side.
+    fn replay(dump: &MatrixDump) -> (Vec<f64>, String) {
+        let a = load_matrix_market_csc_file_keep_zeros(dump.matrix_path()).expect("matrix");
+        let mut x = load_matrix_market_array_file(dump.rhs_path()).expect("rhs");
+        let mut config = KluConfig::default();
+        let mut symbolic = klu::analyze(&a, &config).expect("analyze");
+        let mut numeric = klu::factor::<f64>(&a, &mut symbolic, &mut config).expect("factor");
+        klu::solve(&symbolic, &mut numeric, x.len(), 1, &mut x, &config).expect("solve");
+        let rhs = std::fs::read_to_string(dump.rhs_path()).unwrap();
+        (x, rhs)
+    }
+
+    fn run(netlist: &str, solver: LinearSolver, dump: &MatrixDump) -> AnalysisResult {
+        let mut options = ParseOptions::new_with_source("dump.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let config = SimulationConfig {
+            solver,
+            dump_matrix: Some(dump.clone()),
+            ..Default::default()
+        };
+        let mut report = simulate(deck, config).expect("simulate");
+        report.analyses.remove(0).result
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64], rel_tol: f64) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a - e).abs() <= rel_tol * e.abs().max(1.0),
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn operating_point_replays_to_its_solution() {
+        let netlist =
+            "diode\nV1 in 0 DC 5\nR1 in out 1k\nD1 out 0 DMOD\n.MODEL DMOD D\n.OP\n.END\n";
+        for (name, solver) in [
+            ("klu", SimulationConfig::default().solver),
+            ("blas", LinearSolver::Blas),
+        ] {
+            let dump = MatrixDump::new(
+                std::env::temp_dir().join(format!("spicy_dump_op_{name}")),
+                None,
+            );
+            let AnalysisResult::Op(op) = run(netlist, solver, &dump) else {
+                panic!("expected an operating point");
+            };
+            // the system linearized at the solution is one more Newton step, which stays
+            // within the convergence tolerance
+            let (x, rhs) = replay(&dump);
+            let solution: Vec<f64> = op
+                .voltages
+                .iter()
+                .chain(&op.currents)
+                .map(|(_, v)| *v)
+                .collect();
+            assert_close(&x, &solution, 1e-3);
+            assert!(rhs.contains("% 2 V(out)"), "{rhs}");
+        }
+    }
+
+    #[test]
+    fn transient_dumps_the_first_point_at_or_after_the_time() {
+        let netlist = "rc\nV1 in 0 DC 1\nR1 in out 1k\nC1 out 0 1n\n.tran 1u 10u\n.end\n";
+        let dump = MatrixDump::new(std::env::temp_dir().join("spicy_dump_tran"), Some(4.5e-6));
+        let AnalysisResult::Tran(tran) = run(netlist, LinearSolver::Blas, &dump) else {
+            panic!("expected a transient");
+        };
+        let point = tran.times.iter().position(|t| *t >= 4.5e-6).unwrap();
+        let (x, _) = replay(&dump);
+        // a linear circuit solves exactly
+        assert_close(&x, &tran.samples[point], 1e-12);
+        // written once: a later solve does not match again
+        assert!(!dump.wants(Some(8e-6)));
+    }
+}
@@ -0,0 +1,345 @@
+//! A simulation that stays set up between runs.
+//!
+//! `simulate()` compiles the devices, builds the matrix pattern and analyzes it on every call.
+//! `SimulationEngine` does that once and keeps the last operating point, so re-running after a
+//! small value change (tuning a resistor from the TUI, an optimization loop) only refactors the
+//! matrix and starts Newton from the previous solution.
+
+use spicy_parser::{
+    Value,
+    instance_parser::Deck,
+    netlist_types::{AcCommand, TranCommand},
+    netlist_waveform::WaveForm,
+    node_mapping::NodeMapping,
+};
+
+use crate::{
+    NewtonMode, NewtonState, SimulationConfig,
+    ac::{AcSweep, run_ac},
+    check_deck_topology,
+    dc::{
+        NodeConditions, OperatingPointResult, operating_point_result, push_device_currents,
+        solve_dc_point,
+    },
+    devices::Devices,
+    error::SimulationError,
+    matrix::SolverMatrix,
+    output::device_current_names,
+    trans::{TransientResult, run_transient},
+    warnings::Warnings,
+};
+
+pub struct SimulationEngine {
+    node_mapping: NodeMapping,
+    devices: Devices,
+    matrix: SolverMatrix,
+    config: SimulationConfig,
+    conditions: NodeConditions,
+    /// the devices of `.options savecurrents`
+    device_names: Vec<String>,
+    /// last operating point, the starting guess of the next solve
+    solution: Option<Vec<f64>>,
+    /// Newton iterations of the last operating point
+    newton_iterations: usize,
+}
+
+impl SimulationEngine {
+    /// Compile the devices of `deck` and set up (and for KLU analyze) its matrix.
+    pub fn new(deck: &Deck, config: SimulationConfig) -> Result<Self, SimulationError> {
+        check_deck_topology(deck, &config, true)?;
+
+        let mut devices = Devices::from_deck(deck, &config);
+        let mut matrix =
+            SolverMatrix::create_matrix(&mut devices, deck.node_mapping.clone(), &config)?;
+        matrix.ensure_analyzed()?;
+
+        Ok(Self {
+            node_mapping: deck.node_mapping.clone(),
+            devices,
+            matrix,
+            config,
+            conditions: NodeConditions::from_deck(deck),
+            device_names: device_current_names(deck),
+            solution: None,
+            newton_iterations: 0,
+        })
+    }
+
+    /// Change the value of a resistor (ohms), capacitor (farads), inductor (henries) or the DC
+    /// value of an independent source. The `m` and `scale` of the device still apply. The
+    /// matrix pattern is unaffected.
+    pub fn set_value(&mut self, device: &str, value: f64) -> Result<(), SimulationError> {
+        let devices = &mut self.devices;
+        if let Some(r) = devices.resistors.iter_mut().find(|r| r.name == device) {
+            let value = r.multiplicity.series(value);
+            // keep following the DC value unless the deck gave an explicit `ac=`
+            if r.ac == r.resistance {
+                r.ac = value;
+            }
+            r.resistance = value;
+        } else if let Some(c) = devices.capacitors.iter_mut().find(|c| c.name == device) {
+            c.capacitance = c.multiplicity.parallel(value);
+        } else if let Some(l) = devices.inductors.iter_mut().find(|l| l.name == device) {
+            l.inductance = l.multiplicity.series(value);
+        } else if let Some(source) = devices
+            .voltage_sources
+            .iter_mut()
+            .chain(devices.current_sources.iter_mut())
+            .find(|s| s.name == device)
+        {
+            source.dc = WaveForm::Constant(Value::new(value, None, None));
+        } else {
+            return Err(SimulationError::UnknownDevice {
+                name: device.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Take every device value from `deck`, e.g. the same netlist parsed with other `.param`s.
+    /// The deck must have the devices the engine was built from; the previous solution is kept.
+    pub fn update(&mut self, deck: &Deck) -> Result<(), SimulationError> {
+        if deck.node_mapping.mna_matrix_dim() != self.node_mapping.mna_matrix_dim()
+            || !self
+                .devices
+                .update_values(&deck.devices, self.config.temperature)
+        {
+            return Err(SimulationError::DeckMismatch);
+        }
+        Ok(())
+    }
+
+    pub fn node_mapping(&self) -> &NodeMapping {
+        &self.node_mapping
+    }
+
+    /// Forget the previous solution so the next run starts cold.
+    pub fn reset(&mut self) {
+        self.solution = None;
+    }
+
+    /// Newton iterations the last operating point took.
+    pub fn newton_iterations(&self) -> usize {
+        self.newton_iterations
+    }
+
+    /// Solve the operating point, starting from the previous one if there is one.
+    pub fn op(&mut self) -> Result<OperatingPointResult, SimulationError> {
+        let mut warnings = Warnings::default();
+        let solution = match self.solution.take() {
+            Some(previous) => match self.solve(previous, NewtonMode::Iterate, &mut warnings) {
+                Ok(solution) => solution,
+                // the reused pivots may not suit the new values; retry like a first run
+                Err(_) => {
+                    warnings = Warnings::default();
+                    self.solve(self.cold_guess(), NewtonMode::InitOp, &mut warnings)?
+                }
+            },
+            None => self.solve(self.cold_guess(), NewtonMode::InitOp, &mut warnings)?,
+        };
+
+        let solver_stats = self.matrix.take_stats()?;
+        let mut result = operating_point_result(
+            &self.node_mapping,
+            &solution,
+            warnings.into_vec(),
+            solver_stats,
+        );
+        push_device_currents(
+            &mut result,
+            &self.device_names,
+            &self.devices,
+            &self.node_mapping,
+            &solution,
+        );
+        self.solution = Some(solution);
+        Ok(result)
+    }
+
+    /// Run a transient analysis whose initial operating point starts from the previous one.
+    pub fn transient(&mut self, cmd: &TranCommand) -> Result<TransientResult, SimulationError> {
+        let result = run_transient(
+            &mut self.matrix,
+            &self.devices,
+            &self.node_mapping,
+            &self.conditions,
+            &self.device_names,
+            // every vector is kept: the next analyses start from the operating point
+            None,
+            cmd,
+            &self.config,
+            None,
+            self.solution.clone(),
+        )?;
+        // the t=0 sample is the operating point, before any device currents
+        let dim = self.node_mapping.mna_matrix_dim();
+        self.solution = result.samples.first().map(|sample| sample[..dim].to_vec());
+        Ok(result)
+    }
+
+    /// Run an AC sweep with the current device values. The nonlinear devices are linearized at
+    /// the last operating point, which is solved first if there is none yet.
+    pub fn ac(&mut self, cmd: &AcCommand) -> Result<AcSweep, SimulationError> {
+        if !self.devices.is_linear() && self.solution.is_none() {
+            self.op()?;
+        }
+        Ok(run_ac(
+            &self.devices,
+            &self.node_mapping,
+            cmd,
+            !self.device_names.is_empty(),
+            self.solution.as_deref(),
+        ))
+    }
+
+    fn cold_guess(&self) -> Vec<f64> {
+        self.conditions.guess(self.matrix.rhs().len())
+    }
+
+    /// `NewtonMode::Iterate` refactors with the pivot order of the previous factorization
+    /// instead of factorizing from scratch.
+    fn solve(
+        &mut self,
+        guess: Vec<f64>,
+        mode: NewtonMode,
+        warnings: &mut Warnings,
+    ) -> Result<Vec<f64>, SimulationError> {
+        let mut state = NewtonState::new(self.config.newton, mode)
+            .with_cancel(&self.config.cancel)
+            .with_dump(&self.config.dump_matrix);
+        let (solution, iters) = solve_dc_point(
+            &mut self.matrix,
+            &self.devices,
+            &mut state,
+            guess,
+            &[],
+            warnings,
+        )?;
+        self.newton_iterations = iters;
+        Ok(solution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LinearSolver;
+    use crate::dc::simulate_op;
+    use crate::test_utils::parse_netlist;
+    use spicy_parser::netlist_types::Command;
+
+    const DIODE: &str =
+        "diode\nV1 in 0 DC 5\nR1 in out 1k\nD1 out 0 DMOD\n.MODEL DMOD D\n.OP\n.END\n";
+
+    #[test]
+    fn warm_start_matches_cold_solve() {
+        for solver in [LinearSolver::Blas, SimulationConfig::default().solver] {
+            let config = SimulationConfig {
+                solver,
+                ..SimulationConfig::default()
+            };
+            let mut engine =
+                SimulationEngine::new(&parse_netlist(DIODE), config.clone()).expect("engine");
+
+            let first = engine.op().expect("cold op");
+            let cold_iterations = engine.newton_iterations();
+            assert_eq!(
+                first.voltage("out"),
+                simulate_op(&parse_netlist(DIODE), &config)
+                    .unwrap()
+                    .voltage("out")
+            );
+
+            engine.set_value("R1", 1.1e3).expect("R1 exists");
+            let tuned = engine.op().expect("warm op");
+            assert!(engine.newton_iterations() < cold_iterations);
+
+            let reference = simulate_op(&parse_netlist(&DIODE.replace("1k", "1.1k")), &config)
+                .expect("reference op");
+            let (expected, actual) = (
+                reference.voltage("out").unwrap(),
+                tuned.voltage("out").unwrap(),
+            );
+            // both converge within reltol, from different starting points
+            assert!((expected - actual).abs() < 1e-4, "{expected} vs {actual}");
+        }
+    }
+
+    #[test]
+    fn source_values_and_unknown_devices() {
+        let mut engine =
+            SimulationEngine::new(&parse_netlist(DIODE), SimulationConfig::default()).unwrap();
+        engine.set_value("V1", 10.0).expect("V1 exists");
+        let op = engine.op().unwrap();
+        assert_eq!(op.voltage("in"), Some(10.0));
+
+        assert!(matches!(
+            engine.set_value("R9", 1.0),
+            Err(SimulationError::UnknownDevice { name }) if name == "R9"
+        ));
+    }
+
+    #[test]
+    fn transient_after_tuning_capacitor() {
+        let netlist = "rc\nV1 in 0 SIN(0 1 1k)\nR1 in out 1k\nC1 out 0 1u\n.tran 10u 1m\n.END\n";
+        let deck = parse_netlist(netlist);
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+        let mut engine = SimulationEngine::new(&deck, SimulationConfig::default()).unwrap();
+        engine.transient(tran).expect("first run");
+
+        engine.set_value("C1", 2e-6).unwrap();
+        let tuned = engine.transient(tran).expect("second run");
+
+        let reference_deck = parse_netlist(&netlist.replace("1u\n", "2u\n"));
+        let reference =
+            crate::trans::simulate_trans(&reference_deck, tran, &SimulationConfig::default())
+                .unwrap();
+        assert_eq!(tuned.times, reference.times);
+        let (expected, actual) = (
+            reference.voltage("out").unwrap().y,
+            tuned.voltage("out").unwrap().y,
+        );
+        for (e, a) in expected.iter().zip(&actual) {
+            assert!((e - a).abs() < 1e-9, "{e} vs {a}");
+        }
+    }
+
+    #[test]
+    fn ac_linearizes_the_diode_without_a_prior_op() {
+        let netlist = DIODE
+            .replace("DC 5", "DC 5 AC 1")
+            .replace(".OP", ".AC DEC 1 1k 10k");
+        let deck = parse_netlist(&netlist);
+        let Some(Command::Ac(ac)) = deck.commands.first() else {
+            panic!("expected .ac");
+        };
+        let config = SimulationConfig::default();
+        let mut engine = SimulationEngine::new(&deck, config.clone()).unwrap();
+        let sweep = engine.ac(ac).expect("ac");
+        let reference = crate::ac::simulate_ac(&deck, ac, &config).expect("reference");
+
+        // the diode's small-signal resistance divides the input with R1
+        let out = deck.node_mapping.node_names_mna_order();
+        let out = out.iter().position(|n| n == "out").unwrap();
+        let gain = sweep[0].1[out];
+        assert!(gain > 0.0 && gain < 0.1, "{gain}");
+        assert_eq!(gain, reference[0].1[out]);
+    }
+
+    #[test]
+    fn nodeset_seeds_the_operating_point() {
+        let seeded = DIODE.replace(".OP", ".NODESET V(out)=0.65\n.OP");
+        let mut engine =
+            SimulationEngine::new(&parse_netlist(&seeded), SimulationConfig::default()).unwrap();
+        let mut reference =
+            SimulationEngine::new(&parse_netlist(DIODE), SimulationConfig::default()).unwrap();
+        let op = engine.op().unwrap();
+        let cold = reference.op().unwrap();
+
+        assert!(engine.newton_iterations() < reference.newton_iterations());
+        let (expected, actual) = (cold.voltage("out").unwrap(), op.voltage("out").unwrap());
+        assert!((expected - actual).abs() < 1e-4, "{expected} vs {actual}");
+    }
+}
@@ -1,6 +1,9 @@
+use std::path::PathBuf;
+
+use spicy_parser::error::{ParameterError, SpicyError, TopologyError};
 use thiserror::Error;
 
-use crate::solver::{klu, matrix::error::CscError};
+use crate::solver::{dense::DenseLuError, klu, matrix::error::CscError};
 
 #[derive(Debug, Error)]
 pub enum SimulationError {
@@ -13,6 +16,21 @@ pub enum SimulationError {
     #[error(transparent)]
     NdarrayLinalgError(#[from] ndarray_linalg::error::LinalgError),
 
+    #[error(transparent)]
+    DenseLu(#[from] DenseLuError),
+
+    #[error(
+        "structurally singular matrix: nothing determines {} (conflicting equations of {})",
+        .unknowns.join(", "),
+        .equations.join(", ")
+    )]
+    StructurallySingular {
+        /// the unknowns no equation pins down, e.g. `I(V2)`
+        unknowns: Vec<String>,
+        /// the nodes (KCL) and devices whose equations constrain too few unknowns
+        equations: Vec<String>,
+    },
+
     #[error("Klu symbolic not analyzed")]
     KLUSymbolicNotAnalyzed,
 
@@ -22,9 +40,91 @@ pub enum SimulationError {
     #[error("Blas LU not factorized")]
     BlasLUNotFactorized,
 
-    #[error("Newton iteration did not converge (time={time:?}, iters={iters})")]
+    #[error("plugin device '{name}' refers to a node or branch that is not in the deck")]
+    InvalidPluginDevice { name: String },
+
+    #[error(
+        "{}",
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    Topology(Vec<TopologyError>),
+
+    #[error(
+        "{}",
+        .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    Parameters(Vec<ParameterError>),
+
+    #[error("no resistor, capacitor, inductor or independent source named '{name}'")]
+    UnknownDevice { name: String },
+
+    #[error("deck has different devices than the one the engine was built from")]
+    DeckMismatch,
+
+    #[error("optimization variable '{name}' needs min < max")]
+    InvalidBounds { name: String },
+
+    #[error(transparent)]
+    Parse(#[from] SpicyError),
+
+    #[error("IPC connection failed: {0}")]
+    Ipc(std::io::Error),
+
+    #[error("could not write the matrix dump: {0}")]
+    MatrixDump(std::io::Error),
+
+    #[error("checkpoint: {0}")]
+    Checkpoint(std::io::Error),
+
+    #[error("cannot resume from {}: {reason}", .path.display())]
+    InvalidCheckpoint { path: PathBuf, reason: String },
+
+    #[error("checkpoints need a single transient analysis, without .step or several .temp")]
+    CheckpointNeedsSingleTransient,
+
+    #[error("Newton iteration did not converge (time={time:?}, iters={iters}, worst={unknown})")]
     NonConvergence {
         time: Option<f64>,
         iters: usize,
+        /// the unknown furthest from convergence in the last iteration, e.g. `V(out)`
+        unknown: String,
     },
+
+    #[error("simulation aborted by the observer")]
+    Aborted,
+
+    #[error("simulation cancelled")]
+    Cancelled,
+}
+
+impl SimulationError {
+    /// Stable code of the kind of error, in the range after the parser's: a parse error keeps
+    /// its own code, a list of topology or parameter errors has one code for the lot.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SimulationError::Parse(e) => e.code(),
+            SimulationError::Matrix(_) => "E0801",
+            SimulationError::KluError(_) => "E0802",
+            SimulationError::NdarrayLinalgError(_) => "E0803",
+            SimulationError::StructurallySingular { .. } => "E0804",
+            SimulationError::KLUSymbolicNotAnalyzed => "E0805",
+            SimulationError::KluNumericNotFactorized => "E0806",
+            SimulationError::BlasLUNotFactorized => "E0807",
+            SimulationError::InvalidPluginDevice { .. } => "E0808",
+            SimulationError::Topology(_) => "E0809",
+            SimulationError::UnknownDevice { .. } => "E0810",
+            SimulationError::DeckMismatch => "E0811",
+            SimulationError::InvalidBounds { .. } => "E0812",
+            SimulationError::Ipc(_) => "E0813",
+            SimulationError::MatrixDump(_) => "E0814",
+            SimulationError::Checkpoint(_) => "E0815",
+            SimulationError::InvalidCheckpoint { .. } => "E0816",
+            SimulationError::CheckpointNeedsSingleTransient => "E0817",
+            SimulationError::NonConvergence { .. } => "E0818",
+            SimulationError::Aborted => "E0819",
+            SimulationError::Cancelled => "E0820",
+            SimulationError::DenseLu(_) => "E0821",
+            SimulationError::Parameters(_) => "E0822",
+        }
+    }
 }
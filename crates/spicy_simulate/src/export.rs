@@ -0,0 +1,351 @@
+//! Tabular export of analysis results for tools like pandas: CSV, or NDJSON with one object
+//! per point.
+//!
+//! Every point is a row: the sweep variable (`time`, `frequency` or the swept source), then the
+//! vectors the analysis saves (`V(out)`, `I(V1)`, ...). AC values are split into `re(...)` and
+//! `im(...)` columns. With `.step`/`.temp`, every row starts with the `step` label of its point.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use spicy_parser::instance_parser::Deck;
+use spicy_parser::netlist_types::AnalysisType;
+
+use crate::output::{op_solution, saved_traces};
+use crate::raw_writer::output_file;
+use crate::report::{AnalysisReport, AnalysisResult};
+use crate::sparam::SParameters;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "ndjson" | "jsonl" => Ok(ExportFormat::Ndjson),
+            _ => Err(format!(
+                "invalid export format '{s}' (expected csv or ndjson)"
+            )),
+        }
+    }
+}
+
+/// The named columns of one analysis and its rows.
+struct Table {
+    columns: Vec<String>,
+    rows: Vec<Vec<f64>>,
+}
+
+fn table(deck: &Deck, result: &AnalysisResult) -> Table {
+    let names = |analysis| {
+        saved_traces(deck, analysis)
+            .into_iter()
+            .map(|trace| (trace.name, trace.index))
+            .unzip::<_, _, Vec<_>, Vec<_>>()
+    };
+    match result {
+        AnalysisResult::Op(op) => {
+            let (columns, indices) = names(AnalysisType::Op);
+            let solution = op_solution(op);
+            Table {
+                columns,
+                rows: vec![indices.iter().map(|&i| solution[i]).collect()],
+            }
+        }
+        AnalysisResult::Dc(dc, command) => {
+            let (traces, indices) = names(AnalysisType::Dc);
+            let outer = command.src2.as_ref().map(|src2| src2.srcnam.clone());
+            let columns = std::iter::once(command.srcnam.clone())
+                .chain(outer)
+                .chain(traces)
+                .collect();
+            let points_per_curve = dc.results.len() / dc.curve_count();
+            let rows = dc
+                .results
+                .iter()
+                .enumerate()
+                .map(|(index, (op, x))| {
+                    let solution = op_solution(op);
+                    std::iter::once(*x)
+                        .chain(dc.outer_values.get(index / points_per_curve).copied())
+                        .chain(indices.iter().map(|&i| solution[i]))
+                        .collect()
+                })
+                .collect();
+            Table { columns, rows }
+        }
+        AnalysisResult::Ac(ac) => {
+            let (traces, indices) = names(AnalysisType::Ac);
+            let columns = std::iter::once("frequency".to_string())
+                .chain(
+                    traces
+                        .iter()
+                        .flat_map(|name| [format!("re({name})"), format!("im({name})")]),
+                )
+                .collect();
+            let rows = ac
+                .iter()
+                .map(|(f, re, im)| {
+                    std::iter::once(*f)
+                        .chain(indices.iter().flat_map(|&i| [re[i], im[i]]))
+                        .collect()
+                })
+                .collect();
+            Table { columns, rows }
+        }
+        AnalysisResult::Tran(tran) => {
+            let (traces, indices) = names(AnalysisType::Tran);
+            let columns = std::iter::once("time".to_string()).chain(traces).collect();
+            let rows = tran
+                .times
+                .iter()
+                .zip(&tran.samples)
+                .map(|(t, sample)| {
+                    std::iter::once(*t)
+                        .chain(indices.iter().map(|&i| sample[i]))
+                        .collect()
+                })
+                .collect();
+            Table { columns, rows }
+        }
+        AnalysisResult::Noise(noise) => Table {
+            columns: ["frequency", "onoise_spectrum", "inoise_spectrum"]
+                .map(String::from)
+                .to_vec(),
+            rows: noise
+                .frequencies
+                .iter()
+                .zip(&noise.output_density)
+                .zip(&noise.input_density)
+                .map(|((f, onoise), inoise)| vec![*f, *onoise, *inoise])
+                .collect(),
+        },
+        AnalysisResult::Sp(sp) => {
+            let n = sp.ports.len();
+            let columns = std::iter::once("frequency".to_string())
+                .chain((0..n * n).flat_map(|index| {
+                    let name = SParameters::name(index / n, index % n);
+                    [format!("re({name})"), format!("im({name})")]
+                }))
+                .collect();
+            let rows = sp
+                .frequencies
+                .iter()
+                .zip(&sp.s)
+                .map(|(f, s)| {
+                    std::iter::once(*f)
+                        .chain(s.iter().flat_map(|&(re, im)| [re, im]))
+                        .collect()
+                })
+                .collect();
+            Table { columns, rows }
+        }
+    }
+}
+
+/// Quote a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Write the points of one analysis as CSV, with a header line of the column names.
+pub fn write_csv(mut w: impl Write, deck: &Deck, plots: &[AnalysisReport]) -> io::Result<()> {
+    let Some(first) = plots.first() else {
+        return Ok(());
+    };
+    let stepped = first.label.is_some();
+    let header = table(deck, &first.result).columns;
+    let header = stepped
+        .then(|| "step".to_string())
+        .into_iter()
+        .chain(header)
+        .map(|column| csv_field(&column))
+        .collect::<Vec<_>>();
+    writeln!(w, "{}", header.join(","))?;
+
+    for plot in plots {
+        let label = plot.label.as_deref().map(csv_field);
+        for row in table(deck, &plot.result).rows {
+            let values = label
+                .iter()
+                .cloned()
+                .chain(row.iter().map(|v| format!("{v:e}")))
+                .collect::<Vec<_>>();
+            writeln!(w, "{}", values.join(","))?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the points of one analysis as NDJSON: one JSON object per line keyed by column name.
+/// Non-finite values are written as `null`.
+pub fn write_ndjson(mut w: impl Write, deck: &Deck, plots: &[AnalysisReport]) -> io::Result<()> {
+    for plot in plots {
+        let table = table(deck, &plot.result);
+        let keys: Vec<String> = table
+            .columns
+            .iter()
+            .map(|column| serde_json::Value::from(column.as_str()).to_string())
+            .collect();
+        let label = plot
+            .label
+            .as_deref()
+            .map(|label| format!("\"step\":{}", serde_json::Value::from(label)));
+        for row in table.rows {
+            let fields = label
+                .iter()
+                .cloned()
+                .chain(
+                    keys.iter()
+                        .zip(&row)
+                        .map(|(key, v)| format!("{key}:{}", serde_json::Value::from(*v))),
+                )
+                .collect::<Vec<_>>();
+            writeln!(w, "{{{}}}", fields.join(","))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_export(
+    w: impl Write,
+    deck: &Deck,
+    plots: &[AnalysisReport],
+    format: ExportFormat,
+) -> io::Result<()> {
+    match format {
+        ExportFormat::Csv => write_csv(w, deck, plots),
+        ExportFormat::Ndjson => write_ndjson(w, deck, plots),
+    }
+}
+
+/// Write the points of one analysis to `<output_base>.csv` or `<output_base>.ndjson`.
+pub(crate) fn export_file(
+    deck: &Deck,
+    plots: &[AnalysisReport],
+    output_base: &Path,
+    format: ExportFormat,
+) -> io::Result<PathBuf> {
+    let path = output_file(output_base, format.extension());
+    let mut writer = BufWriter::new(File::create(&path)?);
+    write_export(&mut writer, deck, plots, format)?;
+    writer.flush()?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SimulationConfig, simulate_steps};
+    use spicy_parser::{ParseOptions, parse};
+
+    const NETLIST: &str = "exported
+V1 in 0 DC 1
+R1 in out 1k
+R2 out 0 1k
+.dc V1 0 1 0.5
+.tran 1m 2m
+.end
+";
+
+    fn run(netlist: &str) -> (Deck, Vec<AnalysisReport>) {
+        let mut options = ParseOptions::new_with_source("export.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let config = SimulationConfig {
+            write_raw: false,
+            parallel: false,
+            ..Default::default()
+        };
+        let report = simulate_steps(&mut options, deck, config).expect("simulate");
+        (parse(&mut options).expect("parse"), report.analyses)
+    }
+
+    fn csv(deck: &Deck, plots: &[AnalysisReport]) -> String {
+        let mut out = Vec::new();
+        write_csv(&mut out, deck, plots).expect("csv");
+        String::from_utf8(out).expect("utf8")
+    }
+
+    #[test]
+    fn csv_has_named_columns_and_one_row_per_point() {
+        let (deck, analyses) = run(NETLIST);
+        let dc = csv(&deck, &analyses[..1]);
+        let lines: Vec<&str> = dc.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("V1,"), "{}", lines[0]);
+        assert!(lines[0].contains("V(out)"), "{}", lines[0]);
+        let last: Vec<f64> = lines[3].split(',').map(|v| v.parse().unwrap()).collect();
+        assert_eq!(last[0], 1.0);
+
+        let tran = csv(&deck, &analyses[1..]);
+        assert!(tran.starts_with("time,"), "{tran}");
+    }
+
+    #[test]
+    fn ndjson_lines_are_objects_keyed_by_column() {
+        let (deck, analyses) = run(NETLIST);
+        let mut out = Vec::new();
+        write_ndjson(&mut out, &deck, &analyses[..1]).expect("ndjson");
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("json line"))
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2]["V1"], 1.0);
+        assert_eq!(lines[2]["V(out)"], 0.5);
+    }
+
+    #[test]
+    fn stepped_rows_start_with_their_label() {
+        let (deck, analyses) = run("stepped export
+.param r=1k
+V1 in 0 DC 1
+R1 in out {r}
+R2 out 0 1k
+.op
+.step param r list 1k 3k
+.end
+");
+        let out = csv(&deck, &analyses);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("step,"), "{}", lines[0]);
+        assert!(lines[1].starts_with(analyses[0].label.as_deref().unwrap()));
+    }
+
+    #[test]
+    fn parses_the_format_names() {
+        assert_eq!("CSV".parse(), Ok(ExportFormat::Csv));
+        assert_eq!("ndjson".parse(), Ok(ExportFormat::Ndjson));
+        assert!("xlsx".parse::<ExportFormat>().is_err());
+    }
+}
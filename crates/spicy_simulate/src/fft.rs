@@ -0,0 +1,315 @@
+//! `.four` Fourier analysis of transient results.
+//!
+//! The time steps of a transient are not uniform, so the waveform is first resampled on a
+//! uniform grid over the last period of the fundamental, then transformed with an FFT: bin `k`
+//! of the spectrum is the k-th harmonic. Like SPICE, the first nine harmonics are reported with
+//! the total harmonic distortion.
+
+use std::f64::consts::PI;
+use std::fmt;
+
+use spicy_parser::{instance_parser::Deck, netlist_types::AnalysisType};
+
+use crate::measure::interpolate;
+use crate::output::vector_trace;
+use crate::report::AnalysisResult;
+
+/// Harmonics reported besides the DC component, the fundamental included.
+pub const HARMONICS: usize = 9;
+
+/// Points of the resampled period, a power of two for the FFT.
+const POINTS: usize = 512;
+
+/// One harmonic of a waveform, `magnitude * sin(2 pi frequency t + phase)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Harmonic {
+    pub frequency: f64,
+    /// Peak amplitude.
+    pub magnitude: f64,
+    /// Phase in degrees at t = 0.
+    pub phase: f64,
+    /// Magnitude relative to the fundamental.
+    pub normalized_magnitude: f64,
+    /// Phase minus the phase of the fundamental, in degrees.
+    pub normalized_phase: f64,
+}
+
+/// The Fourier analysis of one vector of a `.four` line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FourierAnalysis {
+    /// The analysed vector, e.g. `V(out)`.
+    pub name: String,
+    pub fundamental: f64,
+    /// The average over the period.
+    pub dc: f64,
+    /// The fundamental and the harmonics after it, [`HARMONICS`] in all.
+    pub harmonics: Vec<Harmonic>,
+    /// Total harmonic distortion in percent: the RMS of harmonics 2 to 9 relative to the
+    /// fundamental.
+    pub thd: f64,
+    /// `(frequency, re, im)` of the complex peak amplitude at every multiple of the
+    /// fundamental, from DC up to half the sample rate of the resampled period.
+    pub spectrum: Vec<(f64, f64, f64)>,
+}
+
+impl FourierAnalysis {
+    /// Analyse the waveform `values` at `times` over its last period of `1 / fundamental`;
+    /// `None` when the waveform is shorter than a period.
+    pub fn new(name: &str, times: &[f64], values: &[f64], fundamental: f64) -> Option<Self> {
+        if fundamental <= 0.0 {
+            return None;
+        }
+        let period = 1.0 / fundamental;
+        let start = times.last()? - period;
+        let dt = period / POINTS as f64;
+        let mut re = (0..POINTS)
+            .map(|n| interpolate(times, values, start + n as f64 * dt))
+            .collect::<Option<Vec<_>>>()?;
+        let mut im = vec![0.0; POINTS];
+        fft(&mut re, &mut im);
+
+        let spectrum: Vec<_> = (0..=POINTS / 2)
+            .map(|k| {
+                // peak amplitude, with the phase moved from the start of the period to t = 0
+                let scale = if k == 0 { 1.0 } else { 2.0 } / POINTS as f64;
+                let (sin, cos) = (-2.0 * PI * k as f64 * start / period).sin_cos();
+                let frequency = k as f64 * fundamental;
+                let (x_re, x_im) = (re[k] * scale, im[k] * scale);
+                (frequency, x_re * cos - x_im * sin, x_re * sin + x_im * cos)
+            })
+            .collect();
+
+        // a sine has the phase of its cosine plus 90 degrees
+        let (magnitudes, phases): (Vec<f64>, Vec<f64>) = spectrum[1..=HARMONICS]
+            .iter()
+            .map(|(_, re, im)| {
+                (
+                    re.hypot(*im),
+                    wrap_degrees(im.atan2(*re).to_degrees() + 90.0),
+                )
+            })
+            .unzip();
+        let harmonics = (0..HARMONICS)
+            .map(|i| Harmonic {
+                frequency: spectrum[i + 1].0,
+                magnitude: magnitudes[i],
+                phase: phases[i],
+                normalized_magnitude: magnitudes[i] / magnitudes[0],
+                normalized_phase: phases[i] - phases[0],
+            })
+            .collect();
+        let distortion: f64 = magnitudes[1..].iter().map(|m| m * m).sum();
+
+        Some(Self {
+            name: name.to_string(),
+            fundamental,
+            dc: spectrum[0].1,
+            harmonics,
+            thd: 100.0 * distortion.sqrt() / magnitudes[0],
+            spectrum,
+        })
+    }
+}
+
+impl fmt::Display for FourierAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Fourier analysis for {}:", self.name)?;
+        writeln!(
+            f,
+            "  No. Harmonics: {}, THD: {:.6} %, DC component: {:.6e}",
+            HARMONICS + 1,
+            self.thd,
+            self.dc
+        )?;
+        writeln!(
+            f,
+            "{:>8}{:>14}{:>14}{:>14}{:>14}{:>14}",
+            "Harmonic", "Frequency", "Magnitude", "Phase", "Norm. Mag", "Norm. Phase"
+        )?;
+        writeln!(
+            f,
+            "{:>8}{:>14.6e}{:>14.6e}{:>14.6e}{:>14.6e}{:>14.6e}",
+            0, 0.0, self.dc, 0.0, 0.0, 0.0
+        )?;
+        for (i, h) in self.harmonics.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>8}{:>14.6e}{:>14.6e}{:>14.6e}{:>14.6e}{:>14.6e}",
+                i + 1,
+                h.frequency,
+                h.magnitude,
+                h.phase,
+                h.normalized_magnitude,
+                h.normalized_phase
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// An angle in degrees in [-180, 180).
+fn wrap_degrees(degrees: f64) -> f64 {
+    (degrees + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// In-place radix-2 FFT, `X_k = sum x_n e^(-2 pi i k n / N)`; the length must be a power of
+/// two.
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+
+    // bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_im, w_re) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len *= 2;
+    }
+}
+
+/// Evaluate the `.four` lines of `deck` on a transient result, one analysis per vector in deck
+/// order. Vectors of a `.four` whose period is longer than the transient are left out.
+pub fn fourier(deck: &Deck, result: &AnalysisResult) -> Vec<FourierAnalysis> {
+    let AnalysisResult::Tran(tran) = result else {
+        return Vec::new();
+    };
+    let mut analyses = Vec::new();
+    for command in &deck.fourier {
+        let fundamental = command.fundamental.get_value();
+        for vector in &command.vectors {
+            let trace = vector_trace(deck, AnalysisType::Tran, vector);
+            let values: Vec<f64> = tran.samples.iter().map(|s| s[trace.index]).collect();
+            analyses.extend(FourierAnalysis::new(
+                &trace.name,
+                &tran.times,
+                &values,
+                fundamental,
+            ));
+        }
+    }
+    analyses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw_writer::write_plots;
+    use crate::{RawFormat, SimulationConfig, simulate};
+    use spicy_parser::{ParseOptions, parse};
+
+    fn assert_close(actual: f64, expected: f64, tol: f64) {
+        assert!((actual - expected).abs() <= tol, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn fft_matches_the_dft() {
+        let x: Vec<f64> = (0..16).map(|n| ((n * n) % 7) as f64 - 3.0).collect();
+        let (mut re, mut im) = (x.clone(), vec![0.0; 16]);
+        fft(&mut re, &mut im);
+        for k in 0..16 {
+            let (mut dft_re, mut dft_im) = (0.0, 0.0);
+            for (n, xn) in x.iter().enumerate() {
+                let angle = -2.0 * PI * (k * n) as f64 / 16.0;
+                dft_re += xn * angle.cos();
+                dft_im += xn * angle.sin();
+            }
+            assert_close(re[k], dft_re, 1e-9);
+            assert_close(im[k], dft_im, 1e-9);
+        }
+    }
+
+    #[test]
+    fn harmonics_of_a_non_uniformly_sampled_waveform() {
+        // 0.5 + sin(wt) + 0.1 sin(3wt + 30 deg) over 2.5 periods of 1 kHz, with uneven steps
+        let f = 1e3;
+        let mut times = vec![0.0f64];
+        while *times.last().unwrap() < 2.5e-3 {
+            let step = if times.len() % 2 == 0 { 1e-7 } else { 3e-7 };
+            times.push((times.last().unwrap() + step).min(2.5e-3));
+        }
+        let w = 2.0 * PI * f;
+        let values: Vec<f64> = times
+            .iter()
+            .map(|t| 0.5 + (w * t).sin() + 0.1 * (3.0 * w * t + 30f64.to_radians()).sin())
+            .collect();
+
+        let fourier = FourierAnalysis::new("V(x)", &times, &values, f).expect("a period");
+        assert_close(fourier.dc, 0.5, 1e-4);
+        let [h1, h2, h3, ..] = fourier.harmonics[..] else {
+            panic!("nine harmonics");
+        };
+        assert_close(h1.frequency, f, 1e-9);
+        assert_close(h1.magnitude, 1.0, 1e-4);
+        assert_close(h1.phase, 0.0, 1e-2);
+        assert_close(h2.magnitude, 0.0, 1e-4);
+        assert_close(h3.magnitude, 0.1, 1e-4);
+        assert_close(h3.phase, 30.0, 1e-1);
+        assert_close(fourier.thd, 10.0, 1e-2);
+        assert_eq!(fourier.spectrum.len(), POINTS / 2 + 1);
+
+        assert!(FourierAnalysis::new("V(x)", &times, &values, 100.0).is_none());
+    }
+
+    #[test]
+    fn four_reports_a_square_wave_and_writes_its_spectrum() {
+        let netlist = "square
+V1 in 0 PULSE(0 1 0 1n 1n 0.5m 1m)
+R1 in 0 1k
+.tran 1u 3m
+.four 1k v(in)
+.end
+";
+        let parse_deck = || {
+            let mut options = ParseOptions::new_with_source("four.spicy", netlist.to_string());
+            parse(&mut options).expect("parse")
+        };
+        let config = SimulationConfig {
+            write_raw: false,
+            ..Default::default()
+        };
+        let report = simulate(parse_deck(), config).expect("simulate");
+        let tran = &report.analyses[0];
+        let [square] = &tran.fourier[..] else {
+            panic!("one fourier analysis");
+        };
+        assert_eq!(square.name, "V(in)");
+        // 0.5 + 2/pi (sin wt + sin 3wt / 3 + ...)
+        assert_close(square.dc, 0.5, 1e-2);
+        assert_close(square.harmonics[0].magnitude, 2.0 / PI, 1e-2);
+        assert_close(square.harmonics[1].normalized_magnitude, 0.0, 1e-2);
+        assert_close(square.harmonics[2].normalized_magnitude, 1.0 / 3.0, 1e-2);
+        let odd: f64 = [3.0f64, 5.0, 7.0, 9.0].iter().map(|k| k.powi(-2)).sum();
+        assert_close(square.thd, 100.0 * odd.sqrt(), 1.0);
+        assert!(square.to_string().contains("Fourier analysis for V(in)"));
+
+        let mut raw = Vec::new();
+        write_plots(&mut raw, &parse_deck(), &report.analyses, RawFormat::Ascii).expect("raw");
+        let raw = String::from_utf8(raw).unwrap();
+        assert!(raw.contains("Plotname: Spectrum of V(in)"), "{raw}");
+    }
+}
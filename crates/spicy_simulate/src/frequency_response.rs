@@ -0,0 +1,219 @@
+//! Figures of merit of an AC sweep: unity-gain frequency, phase and gain margins of a loop
+//! gain, and the -3 dB bandwidth of an amplifier or filter.
+//!
+//! Crossings are interpolated linearly in log frequency, the way the sweeps are usually spaced.
+
+use spicy_parser::instance_parser::Deck;
+
+use crate::ac::AcSweep;
+use crate::measure::crossing;
+use spicy_parser::netlist_types::MeasureEdge;
+
+/// The gain from an input to an output at every frequency of an AC sweep.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrequencyResponse {
+    pub frequencies: Vec<f64>,
+    /// `20 log10 |H|`
+    pub magnitude_db: Vec<f64>,
+    /// `arg H` in degrees, unwrapped along the sweep so it does not jump by 360.
+    pub phase_deg: Vec<f64>,
+}
+
+impl FrequencyResponse {
+    /// The response from node `input` to node `output`, or of `output` alone (for a unit AC
+    /// source) when `input` is `None`. Node names are matched by the deck's
+    /// [`NameCase`](spicy_parser::NameCase); `None` if one is not in the deck.
+    pub fn from_nodes(
+        deck: &Deck,
+        ac: &AcSweep,
+        output: &str,
+        input: Option<&str>,
+    ) -> Option<Self> {
+        let nodes = deck.node_mapping.node_names_mna_order();
+        let name_case = deck.node_mapping.name_case();
+        let index = |name: &str| nodes.iter().position(|n| name_case.matches(n, name));
+        let input = match input {
+            Some(input) => Some(index(input)?),
+            None => None,
+        };
+        Some(Self::from_indices(ac, index(output)?, input))
+    }
+
+    /// The response between two entries of the MNA solution.
+    pub(crate) fn from_indices(ac: &AcSweep, output: usize, input: Option<usize>) -> Self {
+        let mut response = Self {
+            frequencies: Vec::with_capacity(ac.len()),
+            magnitude_db: Vec::with_capacity(ac.len()),
+            phase_deg: Vec::with_capacity(ac.len()),
+        };
+        for (f, re, im) in ac {
+            let (mut h_re, mut h_im) = (re[output], im[output]);
+            if let Some(input) = input {
+                // H = out / in
+                let (in_re, in_im) = (re[input], im[input]);
+                let norm = in_re * in_re + in_im * in_im;
+                (h_re, h_im) = (
+                    (h_re * in_re + h_im * in_im) / norm,
+                    (h_im * in_re - h_re * in_im) / norm,
+                );
+            }
+            let mut phase = h_im.atan2(h_re).to_degrees();
+            if let Some(previous) = response.phase_deg.last() {
+                phase -= 360.0 * ((phase - previous) / 360.0).round();
+            }
+            response.frequencies.push(*f);
+            response.magnitude_db.push(20.0 * h_re.hypot(h_im).log10());
+            response.phase_deg.push(phase);
+        }
+        response
+    }
+
+    fn log_frequencies(&self) -> Vec<f64> {
+        self.frequencies.iter().map(|f| f.log10()).collect()
+    }
+
+    /// The first frequency where `values` falls through `level`.
+    fn falls_through(&self, values: &[f64], level: f64) -> Option<f64> {
+        let log_f = crossing(&self.log_frequencies(), values, level, MeasureEdge::Fall(1))?;
+        Some(10f64.powf(log_f))
+    }
+
+    /// The phase at `frequency`, interpolated in log frequency.
+    fn phase_at(&self, frequency: f64) -> f64 {
+        let log_f = frequency.log10();
+        let i = self.frequencies.partition_point(|f| *f < frequency).max(1);
+        let (f0, f1) = (self.frequencies[i - 1].log10(), self.frequencies[i].log10());
+        let t = (log_f - f0) / (f1 - f0);
+        self.phase_deg[i - 1] + t * (self.phase_deg[i] - self.phase_deg[i - 1])
+    }
+
+    /// The frequency where the gain first falls through 0 dB.
+    pub fn unity_gain_frequency(&self) -> Option<f64> {
+        self.falls_through(&self.magnitude_db, 0.0)
+    }
+
+    /// 180 degrees plus the phase at the unity-gain frequency, for a loop gain whose phase
+    /// starts near 0.
+    pub fn phase_margin(&self) -> Option<f64> {
+        let ugf = self.unity_gain_frequency()?;
+        Some(180.0 + self.phase_at(ugf))
+    }
+
+    /// The frequency where the phase first falls through -180 degrees.
+    pub fn phase_crossover_frequency(&self) -> Option<f64> {
+        self.falls_through(&self.phase_deg, -180.0)
+    }
+
+    /// How far the gain is below 0 dB at the phase crossover frequency, in dB.
+    pub fn gain_margin(&self) -> Option<f64> {
+        let crossover = self.phase_crossover_frequency()?.log10();
+        let log_f = self.log_frequencies();
+        let i = log_f.partition_point(|f| *f < crossover).max(1);
+        let t = (crossover - log_f[i - 1]) / (log_f[i] - log_f[i - 1]);
+        let db = self.magnitude_db[i - 1] + t * (self.magnitude_db[i] - self.magnitude_db[i - 1]);
+        Some(-db)
+    }
+
+    /// The frequency where the gain first falls 3 dB below its value at the first frequency.
+    pub fn bandwidth(&self) -> Option<f64> {
+        let reference = *self.magnitude_db.first()?;
+        self.falls_through(&self.magnitude_db, reference - 20.0 * 2f64.sqrt().log10())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ac::simulate_ac;
+    use crate::test_utils::parse_netlist;
+    use crate::{SimulationConfig, simulate};
+    use spicy_parser::netlist_types::Command;
+    use std::f64::consts::PI;
+
+    fn response(netlist: &str, output: &str, input: Option<&str>) -> FrequencyResponse {
+        let deck = parse_netlist(netlist);
+        let Some(Command::Ac(ac)) = deck.commands.first() else {
+            panic!("expected .ac");
+        };
+        let sweep = simulate_ac(&deck, ac, &SimulationConfig::default()).expect("ac");
+        FrequencyResponse::from_nodes(&deck, &sweep, output, input).expect("nodes")
+    }
+
+    fn assert_close(actual: f64, expected: f64, rel: f64) {
+        assert!(
+            (actual - expected).abs() <= rel * expected.abs(),
+            "{actual} != {expected}"
+        );
+    }
+
+    // three RC sections driven by a gain of 100 (a VCVS is built from a behavioral source):
+    // a loop gain with three poles, so it crosses -180 degrees
+    const THREE_POLES: &str = "three poles
+V1 in 0 DC 0 AC 1
+B1 a 0 V=100*V(in)
+R1 a b 1k
+C1 b 0 1u
+R2 b p 100k
+C2 p 0 10n
+R3 p out 10Meg
+C3 out 0 100p
+.ac dec 50 1 1Meg
+.end
+";
+
+    #[test]
+    fn bandwidth_of_a_low_pass() {
+        let netlist = "low pass
+V1 in 0 DC 0 AC 2
+R1 in out 1k
+C1 out 0 1u
+.ac dec 50 1 1Meg
+.end
+";
+        let response = response(netlist, "out", Some("in"));
+        // the 2 V source divides out
+        assert!(response.magnitude_db[0].abs() < 1e-3);
+        assert_close(response.bandwidth().unwrap(), 1.0 / (2.0 * PI * 1e-3), 1e-2);
+    }
+
+    #[test]
+    fn margins_of_a_three_pole_loop() {
+        let response = response(THREE_POLES, "out", None);
+        // the stages barely load each other: all three poles are near 159 Hz
+        let ugf = response.unity_gain_frequency().unwrap();
+        let crossover = response.phase_crossover_frequency().unwrap();
+        assert!(crossover < ugf, "{crossover} {ugf}");
+        // past the phase crossover before unity gain: unstable with negative margins
+        assert!(response.phase_margin().unwrap() < 0.0);
+        assert!(response.gain_margin().unwrap() < 0.0);
+        // |T| = 100 / (1 + x^2)^1.5 at x = f/159 Hz, and -180 degrees is at x = sqrt(3)
+        assert_close(crossover, 159.15 * 3f64.sqrt(), 2e-2);
+        assert_close(
+            response.gain_margin().unwrap(),
+            -(100f64 / 8.0).log10() * 20.0,
+            2e-2,
+        );
+    }
+
+    #[test]
+    fn measured_through_meas_ac() {
+        let netlist = THREE_POLES.replace(
+            ".end",
+            ".meas ac ugf UGF v(out)\n.meas ac gm GM v(out) v(in)\n.end",
+        );
+        let report = simulate(
+            parse_netlist(&netlist),
+            SimulationConfig {
+                write_raw: false,
+                ..Default::default()
+            },
+        )
+        .expect("simulate");
+        let response = response(THREE_POLES, "out", None);
+        let measured: Vec<_> = report.measurements().map(|m| m.value).collect();
+        assert_eq!(
+            measured,
+            [response.unity_gain_frequency(), response.gain_margin()]
+        );
+    }
+}
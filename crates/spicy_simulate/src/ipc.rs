@@ -0,0 +1,560 @@
+//! Live IPC stream from the simulator to an external viewer.
+//!
+//! A simulation can push its MNA matrix, the KLU ordering and waveform samples over a
+//! unix socket or TCP connection while it is running, so a visualizer does not have to
+//! wait for a `.raw` / `.mtx` export.
+//!
+//! Wire format (little-endian):
+//! - handshake: 8 bytes magic = `IPC_MAGIC`, u32 version = `IPC_VERSION`
+//! - then a sequence of frames: u8 kind, u32 payload length, payload
+//!
+//! Payload primitives:
+//! - string: u32 byte length + utf-8 bytes
+//! - f64 / usize vectors: u32 length + entries (`f64` as raw bits, `usize` as u64)
+//!
+//! Transient runs are streamed step by step; OP and DC results are sent once they are
+//! computed. AC results are not streamed yet.
+
+use std::io::{self, BufWriter, Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use spicy_parser::node_mapping::NodeMapping;
+
+use crate::{
+    DcSweepResult, OperatingPointResult,
+    matrix::SolverMatrix,
+    solver::matrix::{Dim, csc::CscMatrix},
+};
+
+/// IPC stream magic (`SPICYIPC`).
+pub const IPC_MAGIC: [u8; 8] = *b"SPICYIPC";
+/// IPC stream format version.
+pub const IPC_VERSION: u32 = 1;
+
+const KIND_SIGNALS: u8 = 1;
+const KIND_SAMPLE: u8 = 2;
+const KIND_MATRIX: u8 = 3;
+const KIND_ORDERING: u8 = 4;
+const KIND_END: u8 = 5;
+
+/// Where the simulator should connect to.
+///
+/// Parsed from `tcp://host:port` or `unix:///path/to/socket`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpcEndpoint {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for IpcEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(addr) = s.strip_prefix("tcp://") {
+            if addr.is_empty() {
+                return Err("tcp endpoint is missing an address".to_string());
+            }
+            return Ok(IpcEndpoint::Tcp(addr.to_string()));
+        }
+        if let Some(path) = s.strip_prefix("unix://") {
+            #[cfg(unix)]
+            {
+                if path.is_empty() {
+                    return Err("unix endpoint is missing a socket path".to_string());
+                }
+                return Ok(IpcEndpoint::Unix(PathBuf::from(path)));
+            }
+            #[cfg(not(unix))]
+            {
+                let _ = path;
+                return Err("unix sockets are not supported on this platform".to_string());
+            }
+        }
+        Err(format!(
+            "invalid IPC endpoint '{s}' (expected tcp://host:port or unix:///path)"
+        ))
+    }
+}
+
+/// A single message on the IPC stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IpcMessage {
+    /// Start of an analysis: the names of the values carried by each following `Sample`.
+    Signals {
+        analysis: String,
+        x_name: String,
+        names: Vec<String>,
+    },
+    /// One point of a waveform (`x` is time, the swept value, ...).
+    Sample { x: f64, values: Vec<f64> },
+    /// A snapshot of the MNA matrix in CSC form.
+    Matrix { label: String, matrix: CscMatrix },
+    /// The fill-reducing ordering chosen for the MNA matrix.
+    Ordering {
+        label: String,
+        row_permutation: Vec<usize>,
+        column_permutation: Vec<usize>,
+    },
+    /// End of the current analysis.
+    End,
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn to_u32(v: usize, what: &'static str) -> io::Result<u32> {
+    u32::try_from(v).map_err(|_| invalid_data(format!("{what} out of range for u32: {v}")))
+}
+
+fn put_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) -> io::Result<()> {
+    put_u32(buf, to_u32(s.len(), "string length")?);
+    buf.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+fn put_f64s(buf: &mut Vec<u8>, values: &[f64]) -> io::Result<()> {
+    put_u32(buf, to_u32(values.len(), "vector length")?);
+    for v in values {
+        buf.extend_from_slice(&v.to_bits().to_le_bytes());
+    }
+    Ok(())
+}
+
+fn put_usizes(buf: &mut Vec<u8>, values: &[usize]) -> io::Result<()> {
+    put_u32(buf, to_u32(values.len(), "vector length")?);
+    for &v in values {
+        buf.extend_from_slice(&(v as u64).to_le_bytes());
+    }
+    Ok(())
+}
+
+impl IpcMessage {
+    fn kind(&self) -> u8 {
+        match self {
+            IpcMessage::Signals { .. } => KIND_SIGNALS,
+            IpcMessage::Sample { .. } => KIND_SAMPLE,
+            IpcMessage::Matrix { .. } => KIND_MATRIX,
+            IpcMessage::Ordering { .. } => KIND_ORDERING,
+            IpcMessage::End => KIND_END,
+        }
+    }
+
+    fn encode_payload(&self) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        match self {
+            IpcMessage::Signals {
+                analysis,
+                x_name,
+                names,
+            } => {
+                put_str(&mut buf, analysis)?;
+                put_str(&mut buf, x_name)?;
+                put_u32(&mut buf, to_u32(names.len(), "signal count")?);
+                for name in names {
+                    put_str(&mut buf, name)?;
+                }
+            }
+            IpcMessage::Sample { x, values } => {
+                buf.extend_from_slice(&x.to_bits().to_le_bytes());
+                put_f64s(&mut buf, values)?;
+            }
+            IpcMessage::Matrix { label, matrix } => {
+                put_str(&mut buf, label)?;
+                put_u32(&mut buf, to_u32(matrix.dim.nrows, "nrows")?);
+                put_u32(&mut buf, to_u32(matrix.dim.ncols, "ncols")?);
+                put_usizes(&mut buf, &matrix.column_pointers)?;
+                put_usizes(&mut buf, &matrix.row_indices)?;
+                put_f64s(&mut buf, &matrix.values)?;
+            }
+            IpcMessage::Ordering {
+                label,
+                row_permutation,
+                column_permutation,
+            } => {
+                put_str(&mut buf, label)?;
+                put_usizes(&mut buf, row_permutation)?;
+                put_usizes(&mut buf, column_permutation)?;
+            }
+            IpcMessage::End => {}
+        }
+        Ok(buf)
+    }
+
+    /// Write this message as a single frame.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let payload = self.encode_payload()?;
+        w.write_all(&[self.kind()])?;
+        w.write_all(&to_u32(payload.len(), "payload length")?.to_le_bytes())?;
+        w.write_all(&payload)
+    }
+}
+
+/// Cursor over a frame payload.
+struct Payload<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Payload<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.bytes.len() < n {
+            return Err(invalid_data("truncated IPC frame"));
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().expect("4 bytes")))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().expect("8 bytes")))
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+
+    fn usize(&mut self) -> io::Result<usize> {
+        let v = self.u64()?;
+        usize::try_from(v).map_err(|_| invalid_data(format!("index out of range: {v}")))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid_data("string is not valid utf-8"))
+    }
+
+    fn f64s(&mut self) -> io::Result<Vec<f64>> {
+        let len = self.u32()? as usize;
+        (0..len).map(|_| self.f64()).collect()
+    }
+
+    fn usizes(&mut self) -> io::Result<Vec<usize>> {
+        let len = self.u32()? as usize;
+        (0..len).map(|_| self.usize()).collect()
+    }
+}
+
+fn decode_payload(kind: u8, bytes: &[u8]) -> io::Result<IpcMessage> {
+    let mut p = Payload { bytes };
+    let message = match kind {
+        KIND_SIGNALS => {
+            let analysis = p.string()?;
+            let x_name = p.string()?;
+            let count = p.u32()? as usize;
+            let names = (0..count).map(|_| p.string()).collect::<io::Result<_>>()?;
+            IpcMessage::Signals {
+                analysis,
+                x_name,
+                names,
+            }
+        }
+        KIND_SAMPLE => {
+            let x = p.f64()?;
+            let values = p.f64s()?;
+            IpcMessage::Sample { x, values }
+        }
+        KIND_MATRIX => {
+            let label = p.string()?;
+            let nrows = p.u32()? as usize;
+            let ncols = p.u32()? as usize;
+            let matrix = CscMatrix {
+                dim: Dim { nrows, ncols },
+                column_pointers: p.usizes()?,
+                row_indices: p.usizes()?,
+                values: p.f64s()?,
+            };
+            matrix
+                .check_invariants()
+                .map_err(|e| invalid_data(format!("invalid matrix in IPC frame: {e}")))?;
+            IpcMessage::Matrix { label, matrix }
+        }
+        KIND_ORDERING => IpcMessage::Ordering {
+            label: p.string()?,
+            row_permutation: p.usizes()?,
+            column_permutation: p.usizes()?,
+        },
+        KIND_END => IpcMessage::End,
+        other => return Err(invalid_data(format!("unknown IPC frame kind {other}"))),
+    };
+    if !p.bytes.is_empty() {
+        return Err(invalid_data("trailing bytes in IPC frame"));
+    }
+    Ok(message)
+}
+
+/// Sending half of the IPC stream, owned by the simulator.
+///
+/// Sends are best-effort: if the viewer disconnects mid-run the first error is kept and
+/// all further messages are dropped, the simulation itself keeps going.
+pub struct IpcSink {
+    writer: BufWriter<Box<dyn Write + Send>>,
+    error: Option<io::Error>,
+}
+
+impl IpcSink {
+    /// Wrap an already connected writer and send the handshake.
+    pub fn from_writer(writer: Box<dyn Write + Send>) -> io::Result<Self> {
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(&IPC_MAGIC)?;
+        writer.write_all(&IPC_VERSION.to_le_bytes())?;
+        writer.flush()?;
+        Ok(Self {
+            writer,
+            error: None,
+        })
+    }
+
+    /// Connect to a listening viewer.
+    pub fn connect(endpoint: &IpcEndpoint) -> io::Result<Self> {
+        let stream: Box<dyn Write + Send> = match endpoint {
+            IpcEndpoint::Tcp(addr) => {
+                let stream = TcpStream::connect(addr)?;
+                stream.set_nodelay(true)?;
+                Box::new(stream)
+            }
+            #[cfg(unix)]
+            IpcEndpoint::Unix(path) => Box::new(std::os::unix::net::UnixStream::connect(path)?),
+        };
+        Self::from_writer(stream)
+    }
+
+    /// Send a message and flush it, so the viewer sees it immediately.
+    pub fn send(&mut self, message: &IpcMessage) -> io::Result<()> {
+        message.write_to(&mut self.writer)?;
+        self.writer.flush()
+    }
+
+    /// Best-effort send; remembers the first failure and drops everything after it.
+    pub fn publish(&mut self, message: &IpcMessage) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.send(message) {
+            self.error = Some(e);
+        }
+    }
+
+    /// The error that disconnected this sink, if any.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+}
+
+/// Trace names in MNA order (`V(node)` then `I(branch)`), matching the raw writer.
+pub(crate) fn signal_names(node_mapping: &NodeMapping) -> Vec<String> {
+    let voltages = node_mapping
+        .node_names_mna_order()
+        .into_iter()
+        .map(|n| format!("V({n})"));
+    let currents = node_mapping
+        .branch_names_mna_order()
+        .into_iter()
+        .map(|b| format!("I({b})"));
+    voltages.chain(currents).collect()
+}
+
+/// Publish the current MNA matrix and, once analyzed, its KLU ordering.
+///
+/// Only the sparse (KLU) solver has a CSC matrix to send; BLAS runs send nothing.
+pub(crate) fn publish_matrix(sink: &mut IpcSink, matrix: &SolverMatrix) {
+    if let Some(csc) = matrix.csc() {
+        sink.publish(&IpcMessage::Matrix {
+            label: "mna".to_string(),
+            matrix: csc.clone(),
+        });
+    }
+    if let Some(symbolic) = matrix.klu_symbolic() {
+        let to_usize = |p: &[isize]| p.iter().map(|&i| i as usize).collect::<Vec<_>>();
+        sink.publish(&IpcMessage::Ordering {
+            label: "klu".to_string(),
+            row_permutation: to_usize(symbolic.row_permutation()),
+            column_permutation: to_usize(symbolic.column_permutation()),
+        });
+    }
+}
+
+fn op_values(op: &OperatingPointResult) -> Vec<f64> {
+    op.voltages
+        .iter()
+        .chain(op.currents.iter())
+        .map(|(_, v)| *v)
+        .collect()
+}
+
+fn op_names(op: &OperatingPointResult) -> Vec<String> {
+    let voltages = op.voltages.iter().map(|(n, _)| format!("V({n})"));
+    let currents = op.currents.iter().map(|(n, _)| format!("I({n})"));
+    voltages.chain(currents).collect()
+}
+
+pub(crate) fn publish_operating_point(sink: &mut IpcSink, op: &OperatingPointResult) {
+    sink.publish(&IpcMessage::Signals {
+        analysis: "op".to_string(),
+        x_name: "point".to_string(),
+        names: op_names(op),
+    });
+    sink.publish(&IpcMessage::Sample {
+        x: 0.0,
+        values: op_values(op),
+    });
+    sink.publish(&IpcMessage::End);
+}
+
+pub(crate) fn publish_dc_sweep(sink: &mut IpcSink, dc: &DcSweepResult, sweep_name: &str) {
+    let Some((first, _)) = dc.results.first() else {
+        return;
+    };
+    sink.publish(&IpcMessage::Signals {
+        analysis: "dc".to_string(),
+        x_name: sweep_name.to_string(),
+        names: op_names(first),
+    });
+    for (op, x) in &dc.results {
+        sink.publish(&IpcMessage::Sample {
+            x: *x,
+            values: op_values(op),
+        });
+    }
+    sink.publish(&IpcMessage::End);
+}
+
+/// Receiving half of the IPC stream, used by viewers.
+pub struct IpcReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> IpcReader<R> {
+    /// Read and validate the handshake.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != IPC_MAGIC {
+            return Err(invalid_data("bad IPC magic"));
+        }
+        let mut version = [0u8; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != IPC_VERSION {
+            return Err(invalid_data(format!(
+                "unsupported IPC version {version} (expected {IPC_VERSION})"
+            )));
+        }
+        Ok(Self { reader })
+    }
+
+    /// Read the next message, `None` once the simulator closed the stream.
+    pub fn next_message(&mut self) -> io::Result<Option<IpcMessage>> {
+        let mut kind = [0u8; 1];
+        match self.reader.read_exact(&mut kind) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let mut len = [0u8; 4];
+        self.reader.read_exact(&mut len)?;
+        let mut payload = vec![0u8; u32::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut payload)?;
+        decode_payload(kind[0], &payload).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::matrix::builder::MatrixBuilder;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn endpoint_from_str() {
+        assert_eq!(
+            "tcp://127.0.0.1:9000".parse::<IpcEndpoint>(),
+            Ok(IpcEndpoint::Tcp("127.0.0.1:9000".to_string()))
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            "unix:///tmp/spicy.sock".parse::<IpcEndpoint>(),
+            Ok(IpcEndpoint::Unix(PathBuf::from("/tmp/spicy.sock")))
+        );
+        assert!("http://localhost".parse::<IpcEndpoint>().is_err());
+        assert!("tcp://".parse::<IpcEndpoint>().is_err());
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let mut b = MatrixBuilder::new(2, 2);
+        b.push(0, 0, 1.0).unwrap();
+        b.push(1, 0, -1.0).unwrap();
+        b.push(1, 1, 2.5).unwrap();
+        let matrix = b.build_csc().unwrap();
+
+        let messages = vec![
+            IpcMessage::Matrix {
+                label: "mna".to_string(),
+                matrix,
+            },
+            IpcMessage::Ordering {
+                label: "klu".to_string(),
+                row_permutation: vec![1, 0],
+                column_permutation: vec![0, 1],
+            },
+            IpcMessage::Signals {
+                analysis: "tran".to_string(),
+                x_name: "time".to_string(),
+                names: vec!["V(out)".to_string(), "I(V1)".to_string()],
+            },
+            IpcMessage::Sample {
+                x: 1e-3,
+                values: vec![0.5, -1e-6],
+            },
+            IpcMessage::End,
+        ];
+
+        let buf = SharedBuf::default();
+        let mut sink = IpcSink::from_writer(Box::new(buf.clone())).unwrap();
+        for m in &messages {
+            sink.publish(m);
+        }
+        assert!(sink.error().is_none());
+
+        let bytes = buf.0.lock().unwrap().clone();
+        let mut reader = IpcReader::new(bytes.as_slice()).unwrap();
+        let mut received = Vec::new();
+        while let Some(m) = reader.next_message().unwrap() {
+            received.push(m);
+        }
+        assert_eq!(received, messages);
+    }
+
+    #[test]
+    fn reader_rejects_bad_magic() {
+        let bytes = b"NOTSPICY\x01\x00\x00\x00".to_vec();
+        assert!(IpcReader::new(bytes.as_slice()).is_err());
+    }
+}
@@ -1,31 +1,121 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use spicy_parser::ParseOptions;
+use spicy_parser::error::{ParameterError, TopologyError};
 use spicy_parser::instance_parser::Deck;
-use spicy_parser::netlist_types::Command;
+use spicy_parser::netlist_types::{AnalysisType, Command};
+use spicy_parser::parameters::check_parameters;
+use spicy_parser::topology::check_topology;
 
 use crate::{
     ac::simulate_ac,
     dc::{simulate_dc, simulate_op},
-    trans::simulate_trans,
+    ipc::{IpcEndpoint, IpcSink},
+    matrix::SolverWorkspace,
+    noise::simulate_noise,
+    output::{op_solution, printed_traces, write_ac_table, write_table},
+    sparam::simulate_sp,
+    trans::simulate_trans_inner,
 };
 
 pub mod ac;
+pub mod cancel;
+pub mod checkpoint;
+mod collapse;
 pub mod dc;
 // mod nodes;
 mod devices;
+pub mod dump;
+pub mod engine;
 mod error;
+pub mod export;
+pub mod fft;
+pub mod frequency_response;
+pub mod ipc;
 mod matrix;
+pub mod measure;
+#[cfg(all(test, feature = "ngspice"))]
+mod ngspice_golden;
+pub mod noise;
+pub mod observer;
+pub mod op_report;
+pub mod optimize;
+mod output;
+pub mod power;
+pub mod report;
 mod util;
+pub mod raw_reader;
 pub(crate) mod raw_writer;
+pub mod results;
 mod setup_pattern;
 pub mod solver;
+pub mod sparam;
+pub mod step;
+#[cfg(test)]
+mod test_utils;
 pub mod trans;
+pub mod warnings;
+pub use cancel::CancellationToken;
+pub use checkpoint::Checkpoint;
 pub use dc::{DcSweepResult, OperatingPointResult};
+pub use devices::plugin;
+pub use dump::MatrixDump;
+pub use engine::SimulationEngine;
+pub use export::ExportFormat;
+pub use fft::FourierAnalysis;
+pub use frequency_response::FrequencyResponse;
+pub use matrix::{BackendSelection, SolverBackend, SolverStats};
+pub use measure::Measurement;
+pub use observer::{Progress, SimulateObserver};
+pub use op_report::OpReport;
+pub use power::TransientPower;
+pub use report::{AnalysisReport, AnalysisResult, SimulationReport};
+pub use results::{Unit, Vector, Waveform};
 pub use trans::TransientResult;
 pub use error::SimulationError;
+pub use warnings::SimulationWarning;
 
 #[derive(Debug, Clone)]
 pub enum LinearSolver {
-    Klu { config: solver::klu::KluConfig },
+    Klu {
+        config: solver::klu::KluConfig,
+    },
     Blas,
+    /// KLU or BLAS depending on the size and density of the MNA matrix, see
+    /// [`SimulationConfig::backend`].
+    Auto {
+        config: solver::klu::KluConfig,
+    },
+}
+
+impl FromStr for LinearSolver {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let config = solver::klu::KluConfig::default();
+        match s.to_ascii_lowercase().as_str() {
+            "klu" => Ok(LinearSolver::Klu { config }),
+            "blas" => Ok(LinearSolver::Blas),
+            "auto" => Ok(LinearSolver::Auto { config }),
+            _ => Err(format!(
+                "invalid linear solver '{s}' (expected klu, blas or auto)"
+            )),
+        }
+    }
+}
+
+/// The floating point type the KLU factors are computed in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Precision {
+    #[default]
+    Double,
+    /// `f32` factors: a faster and less accurate solve, for previews whose results are
+    /// replaced by a `Double` run. The dense BLAS solver always factors in `f64`.
+    Single,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -51,6 +141,39 @@ impl Default for NewtonConfig {
     }
 }
 
+/// Transient timestep control.
+#[derive(Debug, Clone, Copy)]
+pub struct TimestepConfig {
+    /// pick the step from the local truncation error instead of marching with `tstep`
+    pub adaptive: bool,
+    /// relative bound on the truncation error of every unknown
+    pub rel_tol: f64,
+    /// absolute bound on the truncation error of every unknown
+    pub abs_tol: f64,
+    /// largest step the adaptive controller takes, the `.tran` tmax or `(tstop) / 50` if unset
+    pub max_step: Option<f64>,
+}
+
+impl Default for TimestepConfig {
+    fn default() -> Self {
+        Self {
+            adaptive: false,
+            rel_tol: 1e-3,
+            abs_tol: 1e-6,
+            max_step: None,
+        }
+    }
+}
+
+/// Encoding of the values in a raw file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawFormat {
+    /// packed little-endian floats after `Binary:`, as LTspice writes them
+    Binary,
+    /// one value per line after `Values:`
+    Ascii,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum NewtonMode {
     InitOp,
@@ -58,27 +181,85 @@ pub enum NewtonMode {
     Iterate,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct NewtonState {
     pub config: NewtonConfig,
     pub mode: NewtonMode,
+    /// checked before every iteration
+    pub cancel: CancellationToken,
+    /// written once the matching solve converges
+    pub dump: Option<MatrixDump>,
+    /// reused by every solve of the analysis
+    pub(crate) workspace: SolverWorkspace,
 }
 
 impl NewtonState {
     pub fn new(config: NewtonConfig, mode: NewtonMode) -> Self {
-        Self { config, mode }
+        Self {
+            config,
+            mode,
+            cancel: CancellationToken::default(),
+            dump: None,
+            workspace: SolverWorkspace::default(),
+        }
+    }
+
+    pub fn with_cancel(mut self, cancel: &CancellationToken) -> Self {
+        self.cancel = cancel.clone();
+        self
+    }
+
+    pub fn with_dump(mut self, dump: &Option<MatrixDump>) -> Self {
+        self.dump = dump.clone();
+        self
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct SimulationConfig {
     pub solver: LinearSolver,
+    /// how [`LinearSolver::Auto`] picks the backend, or the one it is forced to use
+    pub backend: BackendSelection,
+    /// precision of the KLU factors; the matrix is stamped and the results kept in `f64`
+    pub precision: Precision,
     pub integrator: TransientIntegrator,
     pub newton: NewtonConfig,
+    pub timestep: TimestepConfig,
+    /// circuit temperature (°C), overridden by the deck's `.temp`
+    pub temperature: f64,
     /// if true, write raw files
     pub write_raw: bool,
-    /// optional output base path (without extension). If None, use deck.title in CWD
+    /// how the raw files encode their values
+    pub raw_format: RawFormat,
+    /// if set, also write every analysis as a CSV or NDJSON table next to the raw files
+    pub export: Option<ExportFormat>,
+    /// print an [`OpReport`] of every operating point, along with the `.print` tables
+    pub op_report: bool,
+    /// optional output base name (without extension). If None, use deck.title
     pub output_base: Option<String>,
+    /// directory the output files are written to, created if missing. If None, the CWD
+    pub output_dir: Option<PathBuf>,
+    /// name of the output files of an analysis, where `{deck}` is the output base, `{analysis}`
+    /// the kind of analysis (`op`, `tran`, ...) and `{index}` counts the analyses of that kind
+    /// from 1. If None, the output base when set and `{deck}-{analysis}` otherwise
+    pub output_template: Option<String>,
+    /// if set, stream matrices and waveforms to a viewer listening on this endpoint
+    pub ipc: Option<IpcEndpoint>,
+    /// if set, write the MNA system of one solve to Matrix Market files
+    pub dump_matrix: Option<MatrixDump>,
+    /// if set, save the state of the transient analysis to resume it later
+    pub checkpoint: Option<Checkpoint>,
+    /// plugin devices instantiated alongside the deck devices
+    pub devices: plugin::DeviceRegistry,
+    /// notified of the progress of every analysis, and able to abort it
+    pub observer: Option<Arc<dyn SimulateObserver>>,
+    /// stops the simulation early when cancelled
+    pub cancel: CancellationToken,
+    /// run the analyses of every step and temperature in parallel
+    pub parallel: bool,
+    /// merge the nodes of the inductors of `.op` and `.dc` instead of giving each a branch
+    /// current unknown, and recover their currents after the solve
+    pub collapse_dc_inductors: bool,
 }
 
 impl Default for SimulationConfig {
@@ -87,74 +268,266 @@ impl Default for SimulationConfig {
             solver: LinearSolver::Klu {
                 config: solver::klu::KluConfig::default(),
             },
+            backend: BackendSelection::default(),
+            precision: Precision::Double,
             integrator: TransientIntegrator::BackwardEuler,
             newton: NewtonConfig::default(),
+            timestep: TimestepConfig::default(),
+            temperature: devices::NOMINAL_TEMPERATURE,
             write_raw: false,
+            raw_format: RawFormat::Binary,
+            export: None,
+            op_report: false,
             output_base: None,
+            output_dir: None,
+            output_template: None,
+            ipc: None,
+            dump_matrix: None,
+            checkpoint: None,
+            devices: plugin::DeviceRegistry::default(),
+            observer: None,
+            cancel: CancellationToken::default(),
+            parallel: true,
+            collapse_dc_inductors: false,
         }
     }
 }
 
 impl SimulationConfig {
-    pub fn get_output_base(&self, deck: &Deck, extension: &str) -> String {
-        self.output_base
-            .clone()
-            .unwrap_or_else(|| format!("{}-{}", deck.title.clone(), extension))
+    /// Name of the output files of the `index`th analysis (from 1) of kind `extension`.
+    pub fn get_output_base(&self, deck: &Deck, extension: &str, index: usize) -> String {
+        let template = match (&self.output_template, &self.output_base) {
+            (Some(template), _) => template.as_str(),
+            (None, Some(base)) => return base.clone(),
+            (None, None) => "{deck}-{analysis}",
+        };
+        let deck_name = self.output_base.as_deref().unwrap_or(&deck.title);
+        template
+            .replace("{deck}", deck_name)
+            .replace("{analysis}", extension)
+            .replace("{index}", &index.to_string())
+    }
+
+    /// Path of the output files named `name`, without their extension.
+    fn output_path(&self, name: &str) -> PathBuf {
+        let name = raw_writer::sanitize_filename(name);
+        match &self.output_dir {
+            Some(dir) => dir.join(name),
+            None => PathBuf::from(name),
+        }
+    }
+}
+
+/// Reject decks whose MNA matrix is structurally singular before any analysis runs.
+///
+/// AC analysis has no DC operating point, so without `needs_dc` only voltage-source loops
+/// matter. Skipped when plugin devices are registered, since they add connections the parser
+/// does not know about.
+pub(crate) fn check_deck_topology(
+    deck: &Deck,
+    sim_config: &SimulationConfig,
+    needs_dc: bool,
+) -> Result<(), SimulationError> {
+    if !sim_config.devices.is_empty() {
+        return Ok(());
+    }
+    let errors: Vec<_> = check_topology(deck)
+        .into_iter()
+        .filter(|e| needs_dc || matches!(e, TopologyError::VoltageSourceLoop { .. }))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(SimulationError::Topology(errors))
     }
 }
 
-pub fn simulate(deck: Deck, sim_config: SimulationConfig) -> Result<(), SimulationError> {
-    for command in &deck.commands {
-        match command {
-            Command::Op(_) => {
-                let op = simulate_op(&deck, &sim_config)?;
-                if sim_config.write_raw {
-                    let base = sim_config.get_output_base(&deck, "op");
-                    let _ = raw_writer::write_operating_point_raw(&deck, &op, &base);
-                }
+/// Reject decks whose analyses cannot run, or with parameters out of their physical range, and
+/// connect to the viewer if one is configured.
+fn prepare(deck: &Deck, sim_config: &SimulationConfig) -> Result<Option<IpcSink>, SimulationError> {
+    // AC, noise and S-parameters linearize the nonlinear devices at the operating point
+    let nonlinear = !(deck.devices.diodes.is_empty()
+        && deck.devices.lookup_tables.is_empty()
+        && deck.devices.bjts.is_empty()
+        && deck.devices.mosfets.is_empty()
+        && deck.devices.jfets.is_empty()
+        && deck.devices.behavioral_sources.is_empty()
+        && deck.devices.switches.is_empty());
+    let needs_dc = deck.commands.iter().any(|c| match c {
+        Command::Op(_) | Command::Dc(_) | Command::Tran(_) => true,
+        Command::Ac(_) | Command::Noise(_) | Command::Sp(_) => nonlinear,
+        _ => false,
+    });
+    check_deck_topology(deck, sim_config, needs_dc)?;
+    let errors: Vec<_> = check_parameters(deck)
+        .into_iter()
+        .filter(ParameterError::is_error)
+        .collect();
+    if !errors.is_empty() {
+        return Err(SimulationError::Parameters(errors));
+    }
+
+    // every transient would share the checkpoint file
+    let transients = deck
+        .commands
+        .iter()
+        .filter(|c| matches!(c, Command::Tran(_)))
+        .count();
+    let sweeps = !deck.steps.is_empty() || deck.temperatures.len() > 1;
+    if sim_config.checkpoint.is_some() && (transients > 1 || transients == 1 && sweeps) {
+        return Err(SimulationError::CheckpointNeedsSingleTransient);
+    }
+
+    sim_config
+        .ipc
+        .as_ref()
+        .map(IpcSink::connect)
+        .transpose()
+        .map_err(SimulationError::Ipc)
+}
+
+/// Run one analysis of the deck, streaming it to the viewer and printing its `.print` table.
+fn run_analysis(
+    deck: &Deck,
+    command: &Command,
+    sim_config: &SimulationConfig,
+    ipc: Option<&mut IpcSink>,
+    mut stdout: impl Write,
+) -> Result<Option<AnalysisResult>, SimulationError> {
+    let result = match command {
+        Command::Op(_) => {
+            let op = simulate_op(deck, sim_config)?;
+            if let Some(sink) = ipc {
+                ipc::publish_operating_point(sink, &op);
             }
-            Command::Dc(command_params) => {
-                let dc = simulate_dc(&deck, command_params, &sim_config);
-                if sim_config.write_raw {
-                    let base = sim_config.get_output_base(&deck, "dc");
-                    // detect if sweep is a voltage source by scanning devices
-                    let is_voltage = deck
-                        .devices
-                        .voltage_sources
-                        .iter()
-                        .any(|v| v.name == command_params.srcnam);
-                    let _ = raw_writer::write_dc_raw(
-                        &deck,
-                        &dc,
-                        &base,
-                        &command_params.srcnam,
-                        is_voltage,
-                    );
-                }
+            if let Some(traces) = printed_traces(deck, AnalysisType::Op) {
+                let solution = op_solution(&op);
+                let _ = write_table(&mut stdout, None, &traces, [(None, &solution[..])]);
+            }
+            if sim_config.op_report {
+                let _ = write!(stdout, "{}", OpReport::new(deck, &op, sim_config));
+            }
+            AnalysisResult::Op(op)
+        }
+        Command::Dc(command_params) => {
+            let dc = simulate_dc(deck, command_params, sim_config)?;
+            if let Some(sink) = ipc {
+                ipc::publish_dc_sweep(sink, &dc, &command_params.srcnam);
+            }
+            if let Some(traces) = printed_traces(deck, AnalysisType::Dc) {
+                let solutions: Vec<_> = dc.results.iter().map(|(op, _)| op_solution(op)).collect();
+                let rows = dc
+                    .results
+                    .iter()
+                    .zip(&solutions)
+                    .map(|((_, x), solution)| (Some(*x), &solution[..]));
+                let _ = write_table(&mut stdout, Some(&command_params.srcnam), &traces, rows);
+            }
+            AnalysisResult::Dc(dc, command_params.clone())
+        }
+        Command::Ac(command_params) => {
+            let ac = simulate_ac(deck, command_params, sim_config)?;
+            if let Some(traces) = printed_traces(deck, AnalysisType::Ac) {
+                let _ = write_ac_table(&mut stdout, &traces, &ac);
+            }
+            AnalysisResult::Ac(ac)
+        }
+        Command::Tran(command_params) => {
+            let result = simulate_trans_inner(deck, command_params, sim_config, ipc)?;
+            if let Some(traces) = printed_traces(deck, AnalysisType::Tran) {
+                let rows = result
+                    .times
+                    .iter()
+                    .zip(&result.samples)
+                    .map(|(t, sample)| (Some(*t), &sample[..]));
+                let _ = write_table(&mut stdout, Some("time"), &traces, rows);
             }
-            Command::Ac(command_params) => {
-                let ac = simulate_ac(&deck, command_params, &sim_config);
-                if sim_config.write_raw {
-                    let base = sim_config.get_output_base(&deck, "ac");
-                    let _ = raw_writer::write_ac_raw(&deck, &ac, &base);
-                }
+            AnalysisResult::Tran(result)
+        }
+        Command::Noise(command_params) => {
+            AnalysisResult::Noise(simulate_noise(deck, command_params, sim_config)?)
+        }
+        Command::Sp(command_params) => {
+            AnalysisResult::Sp(simulate_sp(deck, command_params, sim_config)?)
+        }
+        Command::End => return Ok(None),
+    };
+    Ok(Some(result))
+}
+
+/// Run every analysis of the deck, once per `.temp` temperature, returning all of their
+/// results. `.step` needs the netlist parsed again, see [`simulate_steps`].
+pub fn simulate(
+    deck: Deck,
+    sim_config: SimulationConfig,
+) -> Result<SimulationReport, SimulationError> {
+    simulate_sweep(None, &deck, &sim_config)
+}
+
+/// Run every analysis of the deck once per `.step` point and `.temp` temperature, parsing the
+/// netlist of `options` again with the stepped `.param`s set. Every analysis writes a single
+/// raw file with one plot per point.
+pub fn simulate_steps(
+    options: &mut ParseOptions,
+    deck: Deck,
+    sim_config: SimulationConfig,
+) -> Result<SimulationReport, SimulationError> {
+    simulate_sweep(Some(options), &deck, &sim_config)
+}
+
+/// `base`, numbered if an earlier analysis already writes to it (e.g. two `.tran`s).
+fn unique_output_base(base: String, taken: &mut HashSet<String>) -> String {
+    if taken.insert(base.clone()) {
+        return base;
+    }
+    (2..)
+        .map(|n| format!("{base}-{n}"))
+        .find(|numbered| taken.insert(numbered.clone()))
+        .expect("unbounded")
+}
+
+fn simulate_sweep(
+    options: Option<&mut ParseOptions>,
+    deck: &Deck,
+    sim_config: &SimulationConfig,
+) -> Result<SimulationReport, SimulationError> {
+    let mut ipc = prepare(deck, sim_config)?;
+    let mut stdout = std::io::stdout().lock();
+    let plots = step::run_steps(options, deck, sim_config, ipc.as_mut(), &mut stdout)?;
+
+    let mut report = SimulationReport::default();
+    let mut bases = HashSet::new();
+    let mut indices: HashMap<&str, usize> = HashMap::new();
+    let writes = sim_config.write_raw || sim_config.export.is_some();
+    if let (true, Some(dir)) = (writes, &sim_config.output_dir) {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    for plots in plots {
+        if let (true, Some(first)) = (writes, plots.first()) {
+            let extension = first.result.extension();
+            let index = indices.entry(extension).or_default();
+            *index += 1;
+            let base = sim_config.get_output_base(deck, extension, *index);
+            let base = sim_config.output_path(&unique_output_base(base, &mut bases));
+            if sim_config.write_raw {
+                let _ = raw_writer::write_raw(deck, &plots, &base, sim_config.raw_format);
+                let _ = measure::write_measurements_file(&plots, &base);
+                let _ = sparam::write_touchstone_files(deck, &plots, &base);
             }
-            Command::Tran(command_params) => {
-                let result = simulate_trans(&deck, command_params, &sim_config)?;
-                if sim_config.write_raw {
-                    let base = sim_config.get_output_base(&deck, "tran");
-                    let _ = raw_writer::write_transient_raw(&deck, &result, &base);
-                }
+            if let Some(format) = sim_config.export {
+                let _ = export::export_file(deck, &plots, &base, format);
             }
-            Command::End => break,
         }
+        report.analyses.extend(plots);
     }
-    Ok(())
+    report.cancelled = sim_config.cancel.is_cancelled();
+    Ok(report)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::trans::simulate_trans;
     use rstest::rstest;
     use spicy_parser::netlist_types::{NodeIndex, NodeName};
     use spicy_parser::node_mapping::NodeMapping;
@@ -196,6 +569,9 @@ mod tests {
             source_path: PathBuf::from("."),
             source_map,
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let deck = parse(&mut input_options).expect("parse");
         let sim_config = SimulationConfig::default();
@@ -219,13 +595,16 @@ mod tests {
             source_path: PathBuf::from("."),
             source_map,
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let deck = parse(&mut input_options).expect("parse");
         let command = deck.commands[1].clone();
         let output = match command {
             Command::Dc(command) => {
                 let sim_config = SimulationConfig::default();
-                simulate_dc(&deck, &command, &sim_config)
+                simulate_dc(&deck, &command, &sim_config).expect("simulate_dc")
             }
             _ => panic!("Unsupported command: {:?}", command),
         };
@@ -249,6 +628,9 @@ mod tests {
             source_path: PathBuf::from("."),
             source_map,
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let deck = parse(&mut input_options).expect("parse");
         let command = deck
@@ -260,7 +642,7 @@ mod tests {
             })
             .expect("expected .AC command");
         let sim_config = SimulationConfig::default();
-        let output = simulate_ac(&deck, command, &sim_config);
+        let output = simulate_ac(&deck, command, &sim_config).expect("ac");
 
         let name = format!(
             "simulate-ac-{}",
@@ -281,6 +663,9 @@ mod tests {
             source_path: PathBuf::from("."),
             source_map,
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let deck = parse(&mut input_options).expect("parse");
         let command = deck
@@ -303,4 +688,78 @@ mod tests {
         );
         insta::assert_debug_snapshot!(name, output);
     }
+
+    #[test]
+    fn simulate_reports_every_analysis() {
+        let mut options = ParseOptions::new_with_source(
+            "report.spicy",
+            "report
+V1 in 0 DC 1
+R1 in out 1k
+R2 out 0 1k
+C1 out 0 1u
+.op
+.dc V1 0 2 1
+.tran 1u 10u
+.end
+"
+            .to_string(),
+        );
+        let deck = parse(&mut options).expect("parse");
+        let report = simulate(deck, SimulationConfig::default()).expect("simulate");
+
+        assert_eq!(report.analyses.len(), 3);
+        assert!(report.analyses.iter().all(|a| a.label.is_none()));
+        let op = report.operating_points().next().expect("operating point");
+        assert!((op.voltage("out").unwrap() - 0.5).abs() < 1e-9);
+        assert_eq!(report.dc_sweeps().next().unwrap().results.len(), 3);
+        assert_eq!(report.transients().count(), 1);
+        assert_eq!(report.ac_sweeps().count(), 0);
+
+        let stats = report.analyses[1].result.solver_stats().expect("dc stats");
+        assert!(stats.factorizations + stats.refactorizations >= 3);
+        for analysis in &report.analyses {
+            let iterations = analysis.result.max_newton_iterations().expect("iterations");
+            let limit = SimulationConfig::default().newton.max_iters;
+            assert!((1..=limit).contains(&iterations), "{iterations}");
+        }
+        assert_eq!(
+            report.duration(),
+            report.analyses.iter().map(|a| a.duration).sum()
+        );
+        assert!(report.warnings().is_empty());
+    }
+
+    #[test]
+    fn analyses_of_the_same_kind_write_to_numbered_raw_files() {
+        let mut taken = HashSet::new();
+        let bases: Vec<_> = ["out", "out", "deck-tran", "out"]
+            .into_iter()
+            .map(|base| unique_output_base(base.to_string(), &mut taken))
+            .collect();
+        assert_eq!(bases, ["out", "out-2", "deck-tran", "out-3"]);
+    }
+
+    #[test]
+    fn output_files_are_named_by_the_template_in_the_output_dir() {
+        let dir = std::env::temp_dir().join(format!("spicy-output-dir-{}", std::process::id()));
+        let netlist = "named\nV1 in 0 1\nR1 in 0 1k\n.op\n.tran 1m 2m\n.tran 1m 3m\n.end\n";
+        let mut options = ParseOptions::new_with_source("named.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let config = SimulationConfig {
+            write_raw: true,
+            output_dir: Some(dir.join("out")),
+            output_template: Some("{deck}-{analysis}{index}".to_string()),
+            ..Default::default()
+        };
+        simulate(deck, config).expect("simulate");
+
+        let mut files: Vec<_> = std::fs::read_dir(dir.join("out"))
+            .expect("output dir")
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(files, ["named-op1.raw", "named-tran1.raw", "named-tran2.raw"]);
+    }
 }
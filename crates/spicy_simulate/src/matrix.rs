@@ -1,28 +1,332 @@
+use std::fmt;
+use std::str::FromStr;
+
 use ndarray::{Array1, Array2, OwnedRepr};
 use ndarray_linalg::{Factorize, LUFactorized, Solve};
 use spicy_parser::netlist_types::{CurrentBranchIndex, NodeIndex};
 use spicy_parser::node_mapping::NodeMapping;
 
 use crate::{
-    LinearSolver, SimulationConfig,
+    LinearSolver, Precision, SimulationConfig,
     devices::Devices,
     error::SimulationError,
     setup_pattern::{setup_dense_stamps, setup_pattern},
     solver::{
-        klu::{self, KluConfig, KluNumeric, KluSymbolic},
-        matrix::csc::CscMatrix,
+        dense::DenseLu,
+        klu::{self, KluConfig, KluError, KluNumeric, KluResult, KluSymbolic},
+        matrix::{Dim, csc::CscMatrix},
+        scalar::Scalar,
     },
 };
 
+/// Matrices up to this dimension are solved by the small dense LU, which factors and solves
+/// them faster than a refactorization of KLU: `benches/small_dense.rs` puts the crossover of
+/// sparse MNA matrices between 16 and 24 unknowns.
+const SMALL_DENSE_MAX_DIM: usize = 20;
+
+/// Matrices up to this dimension are solved dense: the LU of a handful of unknowns is cheaper
+/// than the symbolic analysis KLU does before it.
+const DENSE_MAX_DIM: usize = 32;
+
+/// Larger matrices are still solved dense when this fraction of their entries is nonzero, up to
+/// [`DENSE_LIMIT_DIM`].
+const DENSE_MIN_DENSITY: f64 = 0.25;
+
+/// Past this dimension the `dim^2` storage of a dense matrix is not worth it at any density.
+const DENSE_LIMIT_DIM: usize = 400;
+
+/// A refactorization whose `klu::rcond` dropped by more than this factor from the last full
+/// factorization is redone with a full factorization.
+const REFACTOR_RCOND_DROP: f64 = 1e-6;
+
+/// How the linear solves of an analysis went.
+///
+/// The conditioning numbers are only known for KLU; they follow the SuiteSparse
+/// `klu_rcond`/`klu_rgrowth`/`klu_condest` diagnostics.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SolverStats {
+    /// Full factorizations, each choosing a new pivot order.
+    pub factorizations: usize,
+    /// Factorizations that reused the pivot order of the previous one.
+    pub refactorizations: usize,
+    /// Smallest min/max |U_kk| over the factorizations.
+    pub rcond: Option<f64>,
+    /// Smallest reciprocal pivot growth over the factorizations.
+    pub rgrowth: Option<f64>,
+    /// 1-norm condition number estimate of the last factorization.
+    pub condest: Option<f64>,
+    /// The solver that factored the matrix.
+    pub backend: Option<SolverBackend>,
+}
+
+/// The linear solver an analysis runs on, resolved from [`LinearSolver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolverBackend {
+    /// KLU on the sparse CSC pattern of the circuit.
+    SparseKlu,
+    /// LAPACK LU on the full matrix.
+    DenseLapack,
+    /// The unrolled LU of [`DenseLu`] on the full matrix, for small matrices.
+    DenseSmall,
+}
+
+impl fmt::Display for SolverBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::SparseKlu => "klu",
+            Self::DenseLapack => "blas",
+            Self::DenseSmall => "dense",
+        })
+    }
+}
+
+impl FromStr for SolverBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "klu" => Ok(Self::SparseKlu),
+            "blas" => Ok(Self::DenseLapack),
+            "dense" => Ok(Self::DenseSmall),
+            _ => Err(format!(
+                "invalid solver backend '{s}' (expected klu, blas or dense)"
+            )),
+        }
+    }
+}
+
+/// How [`LinearSolver::Auto`] picks the [`SolverBackend`] of a matrix from its size and
+/// density.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackendSelection {
+    /// the backend to use whatever the matrix
+    pub force: Option<SolverBackend>,
+    /// matrices up to this dimension use the small dense LU
+    pub small_dense_max_dim: usize,
+    /// matrices up to this dimension use LAPACK
+    pub dense_max_dim: usize,
+    /// larger matrices with at least this fraction of nonzeros still use LAPACK...
+    pub dense_min_density: f64,
+    /// ...up to this dimension
+    pub dense_limit_dim: usize,
+}
+
+impl Default for BackendSelection {
+    fn default() -> Self {
+        Self {
+            force: None,
+            small_dense_max_dim: SMALL_DENSE_MAX_DIM,
+            dense_max_dim: DENSE_MAX_DIM,
+            dense_min_density: DENSE_MIN_DENSITY,
+            dense_limit_dim: DENSE_LIMIT_DIM,
+        }
+    }
+}
+
+impl BackendSelection {
+    /// The backend for a `dim` x `dim` matrix with `nnz` structural nonzeros: the small dense
+    /// LU for the smallest matrices, LAPACK for small or dense ones and KLU otherwise.
+    pub fn select(&self, dim: usize, nnz: usize) -> SolverBackend {
+        if let Some(backend) = self.force {
+            return backend;
+        }
+        let density = nnz as f64 / (dim * dim).max(1) as f64;
+        if dim <= self.small_dense_max_dim {
+            SolverBackend::DenseSmall
+        } else if dim <= self.dense_max_dim
+            || (dim <= self.dense_limit_dim && density >= self.dense_min_density)
+        {
+            SolverBackend::DenseLapack
+        } else {
+            SolverBackend::SparseKlu
+        }
+    }
+}
+
+impl SolverStats {
+    fn record(&mut self, rcond: f64, rgrowth: f64) {
+        self.rcond = Some(self.rcond.map_or(rcond, |r| r.min(rcond)));
+        self.rgrowth = Some(self.rgrowth.map_or(rgrowth, |r| r.min(rgrowth)));
+    }
+
+    /// Combine the statistics of two solves, `other` being the later one.
+    pub(crate) fn merge(self, other: &SolverStats) -> SolverStats {
+        let min = |a: Option<f64>, b: Option<f64>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        SolverStats {
+            factorizations: self.factorizations + other.factorizations,
+            refactorizations: self.refactorizations + other.refactorizations,
+            rcond: min(self.rcond, other.rcond),
+            rgrowth: min(self.rgrowth, other.rgrowth),
+            condest: other.condest.or(self.condest),
+            backend: other.backend.or(self.backend),
+        }
+    }
+}
+
+/// Spare vectors kept by [`SolverWorkspace`]; more are dropped.
+const SPARE_VECTORS: usize = 4;
+
+/// The vectors of the Newton iterations of an analysis, kept across its solves so the hot
+/// loop clears and refills them instead of allocating new ones.
+///
+/// The factorization memory stays in [`SolverMatrix`], which KLU refactors in place.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SolverWorkspace {
+    /// The solution of the current iteration, swapped with the guess it is checked against.
+    solution: Vec<f64>,
+    /// Vectors given back with [`SolverWorkspace::recycle`].
+    spare: Vec<Vec<f64>>,
+}
+
+impl SolverWorkspace {
+    /// Copy the solution in the right-hand side of `matrix` into the solution vector.
+    pub(crate) fn load_solution(&mut self, matrix: &SolverMatrix) -> &mut Vec<f64> {
+        self.solution.clear();
+        self.solution.extend_from_slice(matrix.rhs());
+        &mut self.solution
+    }
+
+    /// A vector holding `values`, reusing a spare one when there is.
+    pub(crate) fn vector_from(&mut self, values: &[f64]) -> Vec<f64> {
+        let mut vector = self.spare.pop().unwrap_or_default();
+        vector.clear();
+        vector.extend_from_slice(values);
+        vector
+    }
+
+    /// Keep `vector` for a later [`SolverWorkspace::vector_from`].
+    pub(crate) fn recycle(&mut self, vector: Vec<f64>) {
+        if self.spare.len() < SPARE_VECTORS {
+            self.spare.push(vector);
+        }
+    }
+}
+
+/// A backend the analyses stamp and solve the MNA system with, one per [`SolverBackend`]:
+/// [`KluMatrix`] for the sparse one and [`BlasMatrix`] for the dense ones.
+pub(crate) trait FactorSolve {
+    /// The backend behind the matrix.
+    fn backend(&self) -> SolverBackend;
+
+    fn node_mapping(&self) -> &NodeMapping;
+
+    /// Set the stamps of `devices` up for the layout of this matrix.
+    fn setup_device_stamps(&self, devices: &mut Devices) -> Result<(), SimulationError>;
+
+    /// The entry at position `nnz` of the stamps.
+    fn get_mut_nnz(&mut self, nnz: usize) -> &mut f64;
+
+    fn get_mut_rhs(&mut self, index: usize) -> &mut f64;
+
+    /// The RHS, overwritten with the solution by [`FactorSolve::solve`].
+    fn rhs(&self) -> &[f64];
+
+    /// Zero out the matrix entries and the RHS, keeping the pattern.
+    fn clear(&mut self);
+
+    /// Work out the pivot order from the pattern, before the first factorization.
+    fn analyze(&mut self) -> Result<(), SimulationError>;
+
+    fn factorize(&mut self) -> Result<(), SimulationError>;
+
+    /// Factorize reusing what the previous factorization allows.
+    fn refactor(&mut self) -> Result<(), SimulationError>;
+
+    fn solve(&mut self) -> Result<(), SimulationError>;
+
+    /// Statistics of the factorizations since the last call.
+    fn take_stats(&mut self) -> Result<SolverStats, SimulationError>;
+}
+
+/// Run `$body` with `$matrix` bound to the backend of a [`SolverMatrix`].
+macro_rules! dispatch {
+    ($solver:expr, $matrix:ident => $body:expr) => {
+        match $solver {
+            SolverMatrix::Klu($matrix) => $body,
+            SolverMatrix::Blas($matrix) => $body,
+        }
+    };
+}
+
+/// The factors of a [`BlasMatrix`].
+enum DenseFactors {
+    Lapack(Option<LUFactorized<OwnedRepr<f64>>>),
+    /// The memory of the factors is kept across factorizations.
+    Small {
+        lu: DenseLu,
+        factored: bool,
+    },
+}
+
+impl DenseFactors {
+    fn factor(&mut self, m: &Array2<f64>) -> Result<(), SimulationError> {
+        match self {
+            Self::Lapack(lu) => *lu = Some(m.factorize()?),
+            Self::Small { lu, factored } => {
+                *factored = false;
+                lu.factor(m.as_slice().expect("dense matrix is in standard layout"))?;
+                *factored = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn solve(&mut self, s: &mut Array1<f64>) -> Result<(), SimulationError> {
+        match self {
+            Self::Lapack(lu) => {
+                let lu = lu.as_mut().ok_or(SimulationError::BlasLUNotFactorized)?;
+                lu.solve_inplace(s)?;
+            }
+            Self::Small { lu, factored } => {
+                if !*factored {
+                    return Err(SimulationError::BlasLUNotFactorized);
+                }
+                lu.solve(s.as_slice_mut().expect("dense RHS is contiguous"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget the factorization, the matrix changed.
+    fn invalidate(&mut self) {
+        match self {
+            Self::Lapack(lu) => *lu = None,
+            Self::Small { factored, .. } => *factored = false,
+        }
+    }
+}
+
 pub struct BlasMatrix {
     node_mapping: NodeMapping,
-    lu: Option<LUFactorized<OwnedRepr<f64>>>,
+    lu: DenseFactors,
     m: ndarray::Array2<f64>,
     s: ndarray::Array1<f64>,
+    stats: SolverStats,
 }
 
 impl BlasMatrix {
+    /// A matrix factored by LAPACK.
     pub fn new(n: usize, node_mapping: NodeMapping) -> Self {
+        Self::with_factors(n, node_mapping, DenseFactors::Lapack(None))
+    }
+
+    /// A matrix factored by the small dense LU.
+    pub fn small(n: usize, node_mapping: NodeMapping) -> Self {
+        let lu = DenseLu::new(n);
+        Self::with_factors(
+            n,
+            node_mapping,
+            DenseFactors::Small {
+                lu,
+                factored: false,
+            },
+        )
+    }
+
+    fn with_factors(n: usize, node_mapping: NodeMapping, lu: DenseFactors) -> Self {
         // Modified nodal analysis matrix
         // [G, B]
         // [B^T, 0]
@@ -34,21 +338,170 @@ impl BlasMatrix {
         let s = Array1::<f64>::zeros(n);
         Self {
             node_mapping,
-            lu: None,
+            lu,
             m,
             s,
+            stats: SolverStats::default(),
         }
     }
 }
 
+impl FactorSolve for BlasMatrix {
+    fn backend(&self) -> SolverBackend {
+        match self.lu {
+            DenseFactors::Lapack(_) => SolverBackend::DenseLapack,
+            DenseFactors::Small { .. } => SolverBackend::DenseSmall,
+        }
+    }
+
+    fn node_mapping(&self) -> &NodeMapping {
+        &self.node_mapping
+    }
+
+    fn setup_device_stamps(&self, devices: &mut Devices) -> Result<(), SimulationError> {
+        setup_dense_stamps(devices, &self.node_mapping)
+    }
+
+    fn get_mut_nnz(&mut self, nnz: usize) -> &mut f64 {
+        let dim = self.m.ncols();
+        let row = nnz / dim;
+        let col = nnz % dim;
+        &mut self.m[[row, col]]
+    }
+
+    fn get_mut_rhs(&mut self, index: usize) -> &mut f64 {
+        &mut self.s[index]
+    }
+
+    fn rhs(&self) -> &[f64] {
+        self.s.as_slice().expect("BLAS RHS should be contiguous")
+    }
+
+    fn clear(&mut self) {
+        self.m.fill(0.0);
+        self.s.fill(0.0);
+        self.lu.invalidate();
+    }
+
+    // no analyze phase for blas
+    fn analyze(&mut self) -> Result<(), SimulationError> {
+        Ok(())
+    }
+
+    fn factorize(&mut self) -> Result<(), SimulationError> {
+        self.lu.factor(&self.m)?;
+        self.stats.factorizations += 1;
+        Ok(())
+    }
+
+    /// The dense LU keeps no pivot order, so this is a full factorization.
+    fn refactor(&mut self) -> Result<(), SimulationError> {
+        self.factorize()
+    }
+
+    fn solve(&mut self) -> Result<(), SimulationError> {
+        self.lu.solve(&mut self.s)
+    }
+
+    fn take_stats(&mut self) -> Result<SolverStats, SimulationError> {
+        let backend = Some(self.backend());
+        Ok(SolverStats {
+            backend,
+            ..std::mem::take(&mut self.stats)
+        })
+    }
+}
+
+/// KLU factors in the [`Precision`] of the simulation.
+enum KluFactors {
+    Double(KluNumeric<f64>),
+    Single(KluNumeric<f32>),
+}
+
+/// Run `$body` with `$numeric` bound to the factors, whatever their precision.
+macro_rules! with_factors {
+    ($factors:expr, $numeric:ident => $body:expr) => {
+        match $factors {
+            KluFactors::Double($numeric) => $body,
+            KluFactors::Single($numeric) => $body,
+        }
+    };
+}
+
+impl KluFactors {
+    fn factor(
+        precision: Precision,
+        a: &CscMatrix,
+        symbolic: &mut KluSymbolic,
+        config: &mut KluConfig,
+    ) -> KluResult<Self> {
+        Ok(match precision {
+            Precision::Double => Self::Double(klu::factor(a, symbolic, config)?),
+            Precision::Single => Self::Single(klu::factor(a, symbolic, config)?),
+        })
+    }
+
+    fn refactor(
+        &mut self,
+        a: &CscMatrix,
+        symbolic: &mut KluSymbolic,
+        config: &KluConfig,
+    ) -> KluResult<()> {
+        with_factors!(self, numeric => klu::refactor(a, symbolic, numeric, config))
+    }
+
+    fn solve(
+        &mut self,
+        symbolic: &KluSymbolic,
+        b: &mut [f64],
+        config: &KluConfig,
+    ) -> KluResult<()> {
+        with_factors!(self, numeric => klu::solve(symbolic, numeric, b.len(), 1, b, config))
+    }
+
+    fn rcond(&self) -> f64 {
+        with_factors!(self, numeric => klu::rcond(numeric))
+    }
+
+    fn rgrowth(&self, a: &CscMatrix, symbolic: &KluSymbolic) -> KluResult<f64> {
+        with_factors!(self, numeric => klu::rgrowth(a, symbolic, numeric))
+    }
+
+    fn condest(
+        &mut self,
+        a: &CscMatrix,
+        symbolic: &KluSymbolic,
+        config: &KluConfig,
+    ) -> KluResult<f64> {
+        with_factors!(self, numeric => klu::condest(a, symbolic, numeric, config))
+    }
+
+    /// Position of the smallest pivot.
+    fn weakest_pivot(&self) -> Option<usize> {
+        fn smallest<T: Scalar>(u_diag: &[T]) -> Option<usize> {
+            let abs = |u: &T| u.abs().to_f64();
+            let min = u_diag
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| abs(a).total_cmp(&abs(b)))?;
+            Some(min.0)
+        }
+        with_factors!(self, numeric => smallest(&numeric.u_diag))
+    }
+}
+
 pub struct KluMatrix {
     config: KluConfig,
+    precision: Precision,
     // TODO: kinda sucks that its an option
     symbolic: Option<KluSymbolic>,
-    numeric: Option<KluNumeric>,
+    numeric: Option<KluFactors>,
+    /// `klu::rcond` of the last full factorization.
+    factored_rcond: f64,
     node_mapping: NodeMapping,
     matrix: CscMatrix,
     s: Vec<f64>,
+    stats: SolverStats,
 }
 
 impl KluMatrix {
@@ -57,15 +510,126 @@ impl KluMatrix {
         s: Vec<f64>,
         node_mapping: NodeMapping,
         config: KluConfig,
+        precision: Precision,
     ) -> Self {
         Self {
             config,
+            precision,
             symbolic: None,
             numeric: None,
+            factored_rcond: 0.0,
             matrix,
             s,
             node_mapping,
+            stats: SolverStats::default(),
+        }
+    }
+}
+
+impl FactorSolve for KluMatrix {
+    fn backend(&self) -> SolverBackend {
+        SolverBackend::SparseKlu
+    }
+
+    fn node_mapping(&self) -> &NodeMapping {
+        &self.node_mapping
+    }
+
+    fn setup_device_stamps(&self, devices: &mut Devices) -> Result<(), SimulationError> {
+        setup_pattern(devices, &self.node_mapping).map(|_| ())
+    }
+
+    fn get_mut_nnz(&mut self, nnz: usize) -> &mut f64 {
+        self.matrix.get_mut_nnz(nnz)
+    }
+
+    fn get_mut_rhs(&mut self, index: usize) -> &mut f64 {
+        &mut self.s[index]
+    }
+
+    fn rhs(&self) -> &[f64] {
+        self.s.as_slice()
+    }
+
+    fn clear(&mut self) {
+        self.matrix.values.fill(0.0);
+        self.s.fill(0.0);
+    }
+
+    fn analyze(&mut self) -> Result<(), SimulationError> {
+        self.symbolic = Some(klu::analyze(&self.matrix, &self.config)?);
+        Ok(())
+    }
+
+    fn factorize(&mut self) -> Result<(), SimulationError> {
+        let symbolic = self
+            .symbolic
+            .as_mut()
+            .ok_or(SimulationError::KLUSymbolicNotAnalyzed)?;
+        let numeric = KluFactors::factor(self.precision, &self.matrix, symbolic, &mut self.config)?;
+        self.factored_rcond = numeric.rcond();
+        let rgrowth = numeric.rgrowth(&self.matrix, symbolic)?;
+        self.stats.factorizations += 1;
+        self.stats.record(self.factored_rcond, rgrowth);
+        self.numeric = Some(numeric);
+        Ok(())
+    }
+
+    /// Factorize again with the pivot order of the previous factorization, keeping its
+    /// memory. Falls back to a full factorization, which picks new pivots, when the old
+    /// pivots became (nearly) singular for the new values.
+    fn refactor(&mut self) -> Result<(), SimulationError> {
+        let symbolic = self
+            .symbolic
+            .as_mut()
+            .ok_or(SimulationError::KLUSymbolicNotAnalyzed)?;
+        let numeric = self
+            .numeric
+            .as_mut()
+            .ok_or(SimulationError::KluNumericNotFactorized)?;
+        let stale = match numeric.refactor(&self.matrix, symbolic, &self.config) {
+            Ok(()) => numeric.rcond() < self.factored_rcond * REFACTOR_RCOND_DROP,
+            Err(KluError::SingularAtBlock { .. }) => true,
+            Err(e) => return Err(e.into()),
+        };
+        if stale {
+            let numeric =
+                KluFactors::factor(self.precision, &self.matrix, symbolic, &mut self.config)?;
+            self.factored_rcond = numeric.rcond();
+            self.stats.factorizations += 1;
+            self.numeric = Some(numeric);
+        } else {
+            self.stats.refactorizations += 1;
+        }
+        let numeric = self.numeric.as_ref().expect("factorized above");
+        let rgrowth = numeric.rgrowth(&self.matrix, symbolic)?;
+        self.stats.record(numeric.rcond(), rgrowth);
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<(), SimulationError> {
+        let symbolic = self
+            .symbolic
+            .as_ref()
+            .ok_or(SimulationError::KLUSymbolicNotAnalyzed)?;
+        let numeric = self
+            .numeric
+            .as_mut()
+            .ok_or(SimulationError::KluNumericNotFactorized)?;
+        numeric.solve(symbolic, &mut self.s, &self.config)?;
+        Ok(())
+    }
+
+    /// The statistics, with the condition estimate of the current factorization.
+    fn take_stats(&mut self) -> Result<SolverStats, SimulationError> {
+        if let (Some(symbolic), Some(numeric)) = (self.symbolic.as_ref(), self.numeric.as_mut()) {
+            let condest = numeric.condest(&self.matrix, symbolic, &self.config)?;
+            self.stats.condest = Some(condest);
         }
+        Ok(SolverStats {
+            backend: Some(self.backend()),
+            ..std::mem::take(&mut self.stats)
+        })
     }
 }
 
@@ -84,91 +648,214 @@ impl SolverMatrix {
     ) -> Result<SolverMatrix, SimulationError> {
         let matrix_dim = node_mapping.mna_matrix_dim();
 
-        let sm = match sim_config.solver {
+        let sm = match &sim_config.solver {
             LinearSolver::Klu { config } => {
                 let matrix = setup_pattern(devices, &node_mapping)?;
-                // KLU solve overwrites RHS in-place, so we allocate it up-front.
-                Self::Klu(KluMatrix::new(
-                    matrix,
-                    vec![0.0; matrix_dim],
-                    node_mapping,
-                    config,
-                ))
+                Self::klu(matrix, node_mapping, config.clone(), sim_config.precision)
             }
             LinearSolver::Blas => {
-                setup_dense_stamps(devices, &node_mapping);
+                setup_dense_stamps(devices, &node_mapping)?;
                 Self::Blas(BlasMatrix::new(matrix_dim, node_mapping))
             }
+            LinearSolver::Auto { config } => {
+                // the pattern is needed to know the density; dense stamps overwrite its indices
+                let matrix = setup_pattern(devices, &node_mapping)?;
+                match sim_config.backend.select(matrix_dim, matrix.nnz()) {
+                    SolverBackend::SparseKlu => {
+                        Self::klu(matrix, node_mapping, config.clone(), sim_config.precision)
+                    }
+                    SolverBackend::DenseLapack => {
+                        setup_dense_stamps(devices, &node_mapping)?;
+                        Self::Blas(BlasMatrix::new(matrix_dim, node_mapping))
+                    }
+                    SolverBackend::DenseSmall => {
+                        setup_dense_stamps(devices, &node_mapping)?;
+                        Self::Blas(BlasMatrix::small(matrix_dim, node_mapping))
+                    }
+                }
+            }
         };
 
         Ok(sm)
     }
 
+    /// Set the stamps of `devices` up for this matrix again, after they were set up for another
+    /// one. The pattern comes out the same as when the matrix was created.
+    pub(crate) fn setup_device_stamps(&self, devices: &mut Devices) -> Result<(), SimulationError> {
+        dispatch!(self, matrix => matrix.setup_device_stamps(devices))
+    }
+
+    fn klu(
+        matrix: CscMatrix,
+        node_mapping: NodeMapping,
+        config: KluConfig,
+        precision: Precision,
+    ) -> Self {
+        // KLU solve overwrites RHS in-place, so we allocate it up-front.
+        let s = vec![0.0; node_mapping.mna_matrix_dim()];
+        Self::Klu(KluMatrix::new(matrix, s, node_mapping, config, precision))
+    }
+
     pub fn get_mut_nnz(&mut self, nnz: usize) -> &mut f64 {
-        match self {
-            Self::Klu(matrix) => matrix.matrix.get_mut_nnz(nnz),
-            Self::Blas(matrix) => {
-                let dim = matrix.m.ncols();
-                let row = nnz / dim;
-                let col = nnz % dim;
-                &mut matrix.m[[row, col]]
-            }
-        }
+        dispatch!(self, matrix => matrix.get_mut_nnz(nnz))
     }
 
     pub fn get_mut_rhs(&mut self, index: usize) -> &mut f64 {
-        match self {
-            Self::Klu(matrix) => &mut matrix.s[index],
-            Self::Blas(matrix) => &mut matrix.s[index],
-        }
+        dispatch!(self, matrix => matrix.get_mut_rhs(index))
     }
 
     /// Zero out matrix entries + RHS (keeps sparsity pattern / mapping).
     pub fn clear(&mut self) {
+        dispatch!(self, matrix => matrix.clear())
+    }
+
+    /// Current RHS vector (overwritten with solution after `solve()`).
+    pub fn rhs(&self) -> &[f64] {
+        dispatch!(self, matrix => matrix.rhs())
+    }
+
+    /// `A*x - b` for the stamped matrix and RHS, before they are solved.
+    pub(crate) fn residual(&self, x: &[f64]) -> Vec<f64> {
+        let mut r: Vec<f64> = self.rhs().iter().map(|b| -b).collect();
         match self {
             Self::Klu(matrix) => {
-                matrix.matrix.values.fill(0.0);
-                matrix.s.fill(0.0);
+                for (j, &xj) in x.iter().enumerate() {
+                    matrix.matrix.axpy_into_dense_col(j, xj, &mut r);
+                }
             }
             Self::Blas(matrix) => {
-                matrix.m.fill(0.0);
-                matrix.s.fill(0.0);
-                matrix.lu = None;
+                for (r, row) in r.iter_mut().zip(matrix.m.rows()) {
+                    *r += row.iter().zip(x).map(|(a, x)| a * x).sum::<f64>();
+                }
             }
         }
+        r
     }
 
-    /// Current RHS vector (overwritten with solution after `solve()`).
-    pub fn rhs(&self) -> &[f64] {
+    /// The sparse MNA matrix (KLU only).
+    pub(crate) fn csc(&self) -> Option<&CscMatrix> {
         match self {
-            Self::Klu(matrix) => matrix.s.as_slice(),
-            Self::Blas(matrix) => matrix.s.as_slice().expect("BLAS RHS should be contiguous"),
+            Self::Klu(matrix) => Some(&matrix.matrix),
+            Self::Blas(_) => None,
         }
     }
 
-    pub fn mna_node_index(&self, node_index: NodeIndex) -> Option<usize> {
+    /// The MNA matrix as CSC, the nonzeros of the dense matrix for BLAS.
+    pub(crate) fn to_csc(&self) -> CscMatrix {
         match self {
-            Self::Klu(matrix) => matrix.node_mapping.mna_node_index(node_index),
-            Self::Blas(matrix) => matrix.node_mapping.mna_node_index(node_index),
+            Self::Klu(matrix) => matrix.matrix.clone(),
+            Self::Blas(matrix) => {
+                let (nrows, ncols) = matrix.m.dim();
+                let mut csc = CscMatrix {
+                    dim: Dim { nrows, ncols },
+                    column_pointers: vec![0],
+                    row_indices: Vec::new(),
+                    values: Vec::new(),
+                };
+                for column in matrix.m.columns() {
+                    for (row, &value) in column.iter().enumerate() {
+                        if value != 0.0 {
+                            csc.row_indices.push(row);
+                            csc.values.push(value);
+                        }
+                    }
+                    csc.column_pointers.push(csc.row_indices.len());
+                }
+                csc
+            }
         }
     }
-    pub fn mna_branch_index(&self, branch_index: CurrentBranchIndex) -> usize {
+
+    /// The symbolic analysis, once `analyze()` ran (KLU only).
+    pub(crate) fn klu_symbolic(&self) -> Option<&KluSymbolic> {
         match self {
-            Self::Klu(matrix) => matrix.node_mapping.mna_branch_index(branch_index),
-            Self::Blas(matrix) => matrix.node_mapping.mna_branch_index(branch_index),
+            Self::Klu(matrix) => matrix.symbolic.as_ref(),
+            Self::Blas(_) => None,
         }
     }
 
-    pub fn analyze(&mut self) -> Result<(), SimulationError> {
+    pub fn mna_node_index(&self, node_index: NodeIndex) -> Option<usize> {
+        self.node_mapping().mna_node_index(node_index)
+    }
+    pub fn mna_branch_index(&self, branch_index: CurrentBranchIndex) -> usize {
+        self.node_mapping().mna_branch_index(branch_index)
+    }
+
+    pub(crate) fn node_mapping(&self) -> &NodeMapping {
+        dispatch!(self, matrix => matrix.node_mapping())
+    }
+
+    /// Human readable name of MNA unknown `index`: `V(node)` or `I(device)`.
+    pub(crate) fn unknown_name(&self, index: usize) -> String {
+        let node_mapping = self.node_mapping();
+        let nodes = node_mapping.nodes_len();
+        if index < nodes {
+            format!("V({})", node_mapping.node_names_mna_order()[index])
+        } else {
+            let branch = &node_mapping.branch_names_mna_order()[index - nodes];
+            format!("I({branch})")
+        }
+    }
+
+    /// The diagonal entry of node `i`.
+    ///
+    /// For KLU this relies on `setup_pattern` reserving every node diagonal.
+    fn node_diagonal_mut(&mut self, i: usize) -> &mut f64 {
         match self {
             Self::Klu(matrix) => {
-                let symbolic = klu::analyze(&matrix.matrix, &matrix.config)?;
-                matrix.symbolic = Some(symbolic);
+                let (rows, _) = matrix.matrix.col(i);
+                let k = rows
+                    .binary_search(&i)
+                    .expect("setup_pattern reserves every node diagonal");
+                let nnz = matrix.matrix.col_start(i) + k;
+                matrix.matrix.get_mut_nnz(nnz)
             }
-            // no analyze phase for blas
-            Self::Blas(_) => {}
+            Self::Blas(matrix) => &mut matrix.m[[i, i]],
         }
-        Ok(())
+    }
+
+    /// Add a conductance `g` from every node to ground (gmin).
+    pub(crate) fn add_node_conductance(&mut self, g: f64) {
+        for i in 0..self.node_mapping().nodes_len() {
+            *self.node_diagonal_mut(i) += g;
+        }
+    }
+
+    /// Tie node `i` to `value` volts through a conductance `g`.
+    pub(crate) fn add_node_source(&mut self, i: usize, g: f64, value: f64) {
+        *self.node_diagonal_mut(i) += g;
+        *self.get_mut_rhs(i) += g * value;
+    }
+
+    /// Statistics of the factorizations since the last call, with the condition estimate of
+    /// the current factorization.
+    pub(crate) fn take_stats(&mut self) -> Result<SolverStats, SimulationError> {
+        dispatch!(self, matrix => matrix.take_stats())
+    }
+
+    /// The unknown whose pivot is the smallest in the last KLU factorization: the node or
+    /// branch most likely responsible for a near-singular matrix.
+    pub(crate) fn weakest_unknown(&self) -> Option<String> {
+        let Self::Klu(matrix) = self else {
+            return None;
+        };
+        let symbolic = matrix.symbolic.as_ref()?;
+        let k = matrix.numeric.as_ref()?.weakest_pivot()?;
+        let column = symbolic.column_permutation()[k] as usize;
+        Some(self.unknown_name(column))
+    }
+
+    /// Ratio of the smallest to the largest pivot magnitude of the last KLU factorization,
+    /// a cheap indicator of ill-conditioning. `None` for BLAS or before factorization.
+    pub(crate) fn pivot_ratio(&self) -> Option<f64> {
+        let Self::Klu(matrix) = self else {
+            return None;
+        };
+        matrix.numeric.as_ref().map(KluFactors::rcond)
+    }
+
+    pub fn analyze(&mut self) -> Result<(), SimulationError> {
+        dispatch!(self, matrix => matrix.analyze())
     }
 
     /// Ensure the symbolic analysis is performed exactly once (KLU only).
@@ -181,75 +868,245 @@ impl SolverMatrix {
     }
 
     pub fn factorize(&mut self) -> Result<(), SimulationError> {
-        match self {
-            Self::Klu(matrix) => {
-                let symbolic = matrix
-                    .symbolic
-                    .as_mut()
-                    .ok_or(SimulationError::KLUSymbolicNotAnalyzed)?;
-                let numeric = klu::factor(&matrix.matrix, symbolic, &mut matrix.config)?;
-                matrix.numeric = Some(numeric);
-            }
-            Self::Blas(matrix) => {
-                let lu = matrix.m.factorize()?;
-                matrix.lu = Some(lu);
-            }
+        dispatch!(self, matrix => matrix.factorize())
+            .map_err(|e| self.structural_error().unwrap_or(e))
+    }
+
+    /// Which unknowns and equations make the matrix structurally singular, if it is.
+    fn structural_error(&self) -> Option<SimulationError> {
+        let structure = klu::btf_structure(&self.to_csc());
+        if structure.structural_rank == self.rhs().len() {
+            return None;
         }
-        Ok(())
+        let node_mapping = self.node_mapping();
+        let (nodes, branches) = (
+            node_mapping.node_names_mna_order(),
+            node_mapping.branch_names_mna_order(),
+        );
+        // row i is the KCL of node i or the branch equation of its device
+        let equation = |i: usize| match nodes.get(i) {
+            Some(node) => format!("node {node}"),
+            None => branches[i - nodes.len()].clone(),
+        };
+        Some(SimulationError::StructurallySingular {
+            unknowns: (structure.underdetermined_columns.iter())
+                .map(|&j| self.unknown_name(j))
+                .collect(),
+            equations: (structure.overdetermined_rows.iter())
+                .map(|&i| equation(i))
+                .collect(),
+        })
     }
 
+    /// Factorize again with the pivot order of the previous factorization, keeping its
+    /// memory. Falls back to a full factorization, which picks new pivots, when the old
+    /// pivots became (nearly) singular for the new values.
     pub fn refactor(&mut self) -> Result<(), SimulationError> {
-        match self {
-            Self::Klu(matrix) => {
-                let symbolic = matrix
-                    .symbolic
-                    .as_mut()
-                    .ok_or(SimulationError::KLUSymbolicNotAnalyzed)?;
-                let numeric = matrix
-                    .numeric
-                    .as_mut()
-                    .ok_or(SimulationError::KluNumericNotFactorized)?;
-                klu::refactor(&matrix.matrix, symbolic, numeric, &matrix.config)?;
-            }
-            Self::Blas(matrix) => {
-                let lu = matrix.m.factorize()?;
-                matrix.lu = Some(lu);
-            }
+        dispatch!(self, matrix => matrix.refactor())
+    }
+
+    pub fn solve(&mut self) -> Result<(), SimulationError> {
+        dispatch!(self, matrix => matrix.solve())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Dense 2x2 KLU matrix, `values` in column-major order.
+    fn klu_2x2(values: [f64; 4]) -> SolverMatrix {
+        let matrix = CscMatrix {
+            dim: Dim { nrows: 2, ncols: 2 },
+            column_pointers: vec![0, 2, 4],
+            row_indices: vec![0, 1, 0, 1],
+            values: values.to_vec(),
+        };
+        SolverMatrix::Klu(KluMatrix::new(
+            matrix,
+            vec![0.0; 2],
+            NodeMapping::new(),
+            KluConfig::default(),
+            Precision::Double,
+        ))
+    }
+
+    fn set_values(m: &mut SolverMatrix, values: [f64; 4], rhs: [f64; 2]) {
+        m.clear();
+        for (nnz, value) in values.into_iter().enumerate() {
+            *m.get_mut_nnz(nnz) = value;
+        }
+        for (i, value) in rhs.into_iter().enumerate() {
+            *m.get_mut_rhs(i) = value;
         }
+    }
 
-        Ok(())
+    #[test]
+    fn refactor_reuses_the_pivot_order() {
+        let mut m = klu_2x2([4.0, 1.0, 1.0, 3.0]);
+        m.analyze().unwrap();
+        m.factorize().unwrap();
+
+        set_values(&mut m, [2.0, 1.0, 1.0, 2.0], [3.0, 3.0]);
+        m.refactor().unwrap();
+        m.solve().unwrap();
+        assert_eq!(m.rhs(), &[1.0, 1.0]);
     }
 
-    pub fn solve(&mut self) -> Result<(), SimulationError> {
-        match self {
-            Self::Klu(matrix) => {
-                let symbolic = matrix
-                    .symbolic
-                    .as_ref()
-                    .ok_or(SimulationError::KLUSymbolicNotAnalyzed)?;
-                let numeric = matrix
-                    .numeric
-                    .as_mut()
-                    .ok_or(SimulationError::KluNumericNotFactorized)?;
-
-                klu::solve(
-                    symbolic,
-                    numeric,
-                    matrix.s.len(),
-                    1,
-                    &mut matrix.s,
-                    &matrix.config,
-                )?;
-            }
-            Self::Blas(matrix) => {
-                let lu = matrix
-                    .lu
-                    .as_mut()
-                    .ok_or(SimulationError::BlasLUNotFactorized)?;
-                let x = lu.solve(&matrix.s)?;
-                matrix.s = x;
+    #[test]
+    fn refactor_picks_new_pivots_when_the_old_ones_vanish() {
+        let mut m = klu_2x2([2.0, 1.0, 1.0, 2.0]);
+        m.analyze().unwrap();
+        m.factorize().unwrap();
+
+        // the diagonal pivots of the first factorization are now zero
+        set_values(&mut m, [0.0, 1.0, 1.0, 0.0], [1.0, 2.0]);
+        m.refactor().unwrap();
+        m.solve().unwrap();
+        assert_eq!(m.rhs(), &[2.0, 1.0]);
+    }
+
+    #[test]
+    fn parallel_voltage_sources_are_reported_as_structurally_singular() {
+        use crate::dc::simulate_op;
+        use spicy_parser::{ParseOptions, parse};
+
+        let netlist = "loop\nV1 a 0 DC 1\nV2 a 0 DC 1\nV3 a 0 DC 1\nR1 a 0 1k\n.op\n.end\n";
+        for solver in [SimulationConfig::default().solver, LinearSolver::Blas] {
+            let mut options = ParseOptions::new_with_source("loop.spicy", netlist.to_string());
+            let deck = parse(&mut options).expect("parse");
+            let config = SimulationConfig {
+                solver,
+                ..Default::default()
+            };
+            let Err(SimulationError::StructurallySingular {
+                unknowns,
+                equations,
+            }) = simulate_op(&deck, &config)
+            else {
+                panic!("expected a structurally singular matrix");
+            };
+            // the source currents only meet in the KCL of node a
+            assert_eq!(unknowns, ["I(V1)", "I(V2)", "I(V3)"]);
+            assert_eq!(equations, ["V1", "V2", "V3"]);
+        }
+    }
+
+    #[test]
+    fn select_prefers_dense_for_small_or_dense_matrices() {
+        let select = BackendSelection::default();
+        assert_eq!(select.select(3, 9), SolverBackend::DenseSmall);
+        assert_eq!(select.select(20, 60), SolverBackend::DenseSmall);
+        assert_eq!(select.select(30, 90), SolverBackend::DenseLapack);
+        assert_eq!(select.select(100, 300), SolverBackend::SparseKlu);
+        assert_eq!(select.select(100, 5000), SolverBackend::DenseLapack);
+        assert_eq!(select.select(1000, 1_000_000), SolverBackend::SparseKlu);
+    }
+
+    #[test]
+    fn select_follows_the_configured_thresholds() {
+        let sparse_early = BackendSelection {
+            small_dense_max_dim: 0,
+            dense_max_dim: 10,
+            dense_min_density: 1.0,
+            ..BackendSelection::default()
+        };
+        assert_eq!(sparse_early.select(3, 9), SolverBackend::DenseLapack);
+        assert_eq!(sparse_early.select(30, 90), SolverBackend::SparseKlu);
+        assert_eq!(sparse_early.select(100, 5000), SolverBackend::SparseKlu);
+
+        let forced = BackendSelection {
+            force: Some(SolverBackend::SparseKlu),
+            ..BackendSelection::default()
+        };
+        assert_eq!(forced.select(3, 9), SolverBackend::SparseKlu);
+        assert_eq!("Dense".parse(), Ok(SolverBackend::DenseSmall));
+    }
+
+    #[test]
+    fn auto_solver_matches_klu_on_either_backend() {
+        use crate::dc::simulate_op;
+        use spicy_parser::{ParseOptions, parse};
+
+        // a divider, a 15-section and a 40-section resistor ladder
+        let ladder = |sections: usize| -> String {
+            (1..=sections)
+                .map(|i| format!("R{i} n{} n{i} 1k\nRG{i} n{i} 0 10k\n", i - 1))
+                .collect()
+        };
+        let cases = [
+            (
+                "divider\nV1 n0 0 DC 1\nR1 n0 n1 1k\nR2 n1 0 1k\n.op\n.end\n".to_string(),
+                SolverBackend::DenseSmall,
+            ),
+            (
+                format!("ladder\nV1 n0 0 DC 1\n{}.op\n.end\n", ladder(15)),
+                SolverBackend::DenseSmall,
+            ),
+            (
+                format!("ladder\nV1 n0 0 DC 1\n{}.op\n.end\n", ladder(40)),
+                SolverBackend::SparseKlu,
+            ),
+        ];
+        for (netlist, backend) in cases {
+            let op = |solver, force| {
+                let mut options = ParseOptions::new_with_source("auto.spicy", netlist.clone());
+                let deck = parse(&mut options).expect("parse");
+                let config = SimulationConfig {
+                    solver,
+                    backend: BackendSelection {
+                        force,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                };
+                simulate_op(&deck, &config).expect("op")
+            };
+            let auto_solver = || LinearSolver::Auto {
+                config: KluConfig::default(),
+            };
+            let auto = op(auto_solver(), None);
+            let klu = op(SimulationConfig::default().solver, None);
+            let forced = op(auto_solver(), Some(SolverBackend::DenseLapack));
+            assert_eq!(auto.solver_stats.backend, Some(backend));
+            assert_eq!(klu.solver_stats.backend, Some(SolverBackend::SparseKlu));
+            assert_eq!(
+                forced.solver_stats.backend,
+                Some(SolverBackend::DenseLapack)
+            );
+            let k = klu.voltage("n1").unwrap();
+            for a in [auto.voltage("n1").unwrap(), forced.voltage("n1").unwrap()] {
+                assert!((a - k).abs() < 1e-12, "{a} vs {k}");
             }
         }
-        Ok(())
+    }
+
+    #[test]
+    fn single_precision_agrees_with_double_to_f32_accuracy() {
+        use crate::dc::simulate_op;
+        use spicy_parser::{ParseOptions, parse};
+
+        let ladder: String = (1..=40)
+            .map(|i| format!("R{i} n{} n{i} 1k\nRG{i} n{i} 0 10k\n", i - 1))
+            .collect();
+        let netlist = format!("ladder\nV1 n0 0 DC 1\n{ladder}.op\n.end\n");
+        let op = |precision| {
+            let mut options = ParseOptions::new_with_source("ladder.spicy", netlist.clone());
+            let deck = parse(&mut options).expect("parse");
+            let config = SimulationConfig {
+                precision,
+                ..Default::default()
+            };
+            simulate_op(&deck, &config).expect("op")
+        };
+        let (double, single) = (op(Precision::Double), op(Precision::Single));
+        for i in [1, 20, 40] {
+            let node = format!("n{i}");
+            let (d, s) = (
+                double.voltage(&node).unwrap(),
+                single.voltage(&node).unwrap(),
+            );
+            assert!((d - s).abs() <= 1e-5 * d.abs(), "{node}: {d} vs {s}");
+        }
     }
 }
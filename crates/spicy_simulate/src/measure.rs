@@ -0,0 +1,370 @@
+//! `.meas` measurements: numbers computed from the vectors of an analysis once it finished,
+//! like a rise time, a delay or the RMS of a current.
+//!
+//! Measurements follow the sweep of their analysis: time for `.tran`, the first source for
+//! `.dc` and frequency for `.ac`, where a vector is measured by its magnitude. Crossings and
+//! values between two points are interpolated linearly.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use spicy_parser::instance_parser::Deck;
+use spicy_parser::netlist_types::{
+    AnalysisType, MeasureEdge, MeasureEvent, MeasureFunction, MeasureKind, OutputVector,
+    ResponseMetric,
+};
+
+use crate::dc::OperatingPointResult;
+use crate::frequency_response::FrequencyResponse;
+use crate::output::vector_trace;
+use crate::raw_writer::output_file;
+use crate::report::{AnalysisReport, AnalysisResult};
+
+/// The result of one `.meas`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub name: String,
+    /// `None` when the measurement failed: the event never happens or the point is outside
+    /// the sweep.
+    pub value: Option<f64>,
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value {
+            Some(value) => write!(f, "{} = {value:.6e}", self.name),
+            None => write!(f, "{} = failed", self.name),
+        }
+    }
+}
+
+/// Evaluate the `.meas` lines of `deck` that apply to `result`, in deck order.
+pub fn measure(deck: &Deck, result: &AnalysisResult) -> Vec<Measurement> {
+    let analysis = match result {
+        AnalysisResult::Dc(..) => AnalysisType::Dc,
+        AnalysisResult::Ac(_) => AnalysisType::Ac,
+        AnalysisResult::Tran(_) => AnalysisType::Tran,
+        AnalysisResult::Op(_) | AnalysisResult::Noise(_) | AnalysisResult::Sp(_) => {
+            return Vec::new();
+        }
+    };
+    deck.measures
+        .iter()
+        .filter(|measure| measure.analysis == analysis)
+        .map(|measure| Measurement {
+            name: measure.name.clone(),
+            value: evaluate(deck, result, analysis, &measure.kind),
+        })
+        .collect()
+}
+
+fn evaluate(
+    deck: &Deck,
+    result: &AnalysisResult,
+    analysis: AnalysisType,
+    kind: &MeasureKind,
+) -> Option<f64> {
+    let waveform =
+        |vector: &OutputVector| waveform(result, vector_trace(deck, analysis, vector).index);
+    let event = |event: &MeasureEvent| match event {
+        MeasureEvent::At(at) => Some(at.get_value()),
+        MeasureEvent::Crossing {
+            vector,
+            level,
+            edge,
+        } => {
+            let (x, y) = waveform(vector);
+            crossing(&x, &y, level.get_value(), *edge)
+        }
+    };
+
+    match kind {
+        MeasureKind::TrigTarg { trig, targ } => Some(event(targ)? - event(trig)?),
+        MeasureKind::When(when) => event(when),
+        MeasureKind::FindAt { vector, at } => {
+            let (x, y) = waveform(vector);
+            interpolate(&x, &y, at.get_value())
+        }
+        MeasureKind::Function {
+            function,
+            vector,
+            from,
+            to,
+        } => {
+            let (x, y) = waveform(vector);
+            let from = from.as_ref().map(|v| v.get_value());
+            let to = to.as_ref().map(|v| v.get_value());
+            let (x, y) = window(&x, &y, from, to)?;
+            Some(apply(*function, &x, &y))
+        }
+        MeasureKind::Response {
+            metric,
+            output,
+            input,
+        } => {
+            let AnalysisResult::Ac(ac) = result else {
+                return None;
+            };
+            let output = vector_trace(deck, analysis, output).index;
+            let input = input
+                .as_ref()
+                .map(|input| vector_trace(deck, analysis, input).index);
+            let response = FrequencyResponse::from_indices(ac, output, input);
+            match metric {
+                ResponseMetric::UnityGainFrequency => response.unity_gain_frequency(),
+                ResponseMetric::PhaseMargin => response.phase_margin(),
+                ResponseMetric::GainMargin => response.gain_margin(),
+                ResponseMetric::Bandwidth => response.bandwidth(),
+            }
+        }
+    }
+}
+
+/// The solution at `index` (MNA order) of an operating point.
+fn op_value(op: &OperatingPointResult, index: usize) -> f64 {
+    match index.checked_sub(op.voltages.len()) {
+        None => op.voltages[index].1,
+        Some(branch) => op.currents[branch].1,
+    }
+}
+
+/// The sweep of `result` and the value of the vector at `index` at every point.
+fn waveform(result: &AnalysisResult, index: usize) -> (Vec<f64>, Vec<f64>) {
+    match result {
+        AnalysisResult::Tran(tran) => (
+            tran.times.clone(),
+            tran.samples.iter().map(|sample| sample[index]).collect(),
+        ),
+        AnalysisResult::Dc(dc, _) => dc
+            .results
+            .iter()
+            .map(|(op, x)| (*x, op_value(op, index)))
+            .unzip(),
+        AnalysisResult::Ac(ac) => ac
+            .iter()
+            .map(|(f, re, im)| (*f, re[index].hypot(im[index])))
+            .unzip(),
+        AnalysisResult::Op(_) | AnalysisResult::Noise(_) | AnalysisResult::Sp(_) => {
+            (Vec::new(), Vec::new())
+        }
+    }
+}
+
+/// Where `y` crosses `level` for the n-th time counted by `edge`.
+pub(crate) fn crossing(x: &[f64], y: &[f64], level: f64, edge: MeasureEdge) -> Option<f64> {
+    let mut count = 0;
+    for i in 1..x.len() {
+        let (y0, y1) = (y[i - 1] - level, y[i] - level);
+        let rising = y0 < 0.0 && y1 >= 0.0;
+        let falling = y0 > 0.0 && y1 <= 0.0;
+        let (counts, n) = match edge {
+            MeasureEdge::Rise(n) => (rising, n),
+            MeasureEdge::Fall(n) => (falling, n),
+            MeasureEdge::Cross(n) => (rising || falling, n),
+        };
+        if counts {
+            count += 1;
+            if count == n {
+                return Some(x[i - 1] + (x[i] - x[i - 1]) * y0 / (y0 - y1));
+            }
+        }
+    }
+    None
+}
+
+/// The value of `y` at `at`, `None` outside the sweep.
+pub(crate) fn interpolate(x: &[f64], y: &[f64], at: f64) -> Option<f64> {
+    let (first, last) = (*x.first()?, *x.last()?);
+    // the last time point may fall a rounding error short of tstop
+    let tolerance = 1e-9 * (last - first).abs();
+    if at < first - tolerance || at > last + tolerance {
+        return None;
+    }
+    let at = at.clamp(first, last);
+    let i = x.partition_point(|&xi| xi < at);
+    if i == 0 || x[i] == at {
+        return Some(y[i]);
+    }
+    let t = (at - x[i - 1]) / (x[i] - x[i - 1]);
+    Some(y[i - 1] + t * (y[i] - y[i - 1]))
+}
+
+/// The points of the waveform from `from` to `to`, with the values at both ends interpolated.
+fn window(
+    x: &[f64],
+    y: &[f64],
+    from: Option<f64>,
+    to: Option<f64>,
+) -> Option<(Vec<f64>, Vec<f64>)> {
+    let from = from.unwrap_or(*x.first()?);
+    let to = to.unwrap_or(*x.last()?);
+    if from > to {
+        return None;
+    }
+    let mut points = vec![(from, interpolate(x, y, from)?)];
+    points.extend(
+        x.iter()
+            .zip(y)
+            .filter(|(xi, _)| **xi > from && **xi < to)
+            .map(|(xi, yi)| (*xi, *yi)),
+    );
+    if to > from {
+        points.push((to, interpolate(x, y, to)?));
+    }
+    Some(points.into_iter().unzip())
+}
+
+/// Trapezoidal integral of `y` over `x`.
+fn integrate(x: &[f64], y: impl Fn(usize) -> f64) -> f64 {
+    (1..x.len())
+        .map(|i| (x[i] - x[i - 1]) * (y(i - 1) + y(i)) / 2.0)
+        .sum()
+}
+
+fn apply(function: MeasureFunction, x: &[f64], y: &[f64]) -> f64 {
+    let span = x[x.len() - 1] - x[0];
+    let min = || y.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = || y.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    match function {
+        // a single point is its own average
+        MeasureFunction::Avg if span == 0.0 => y[0],
+        MeasureFunction::Avg => integrate(x, |i| y[i]) / span,
+        MeasureFunction::Rms if span == 0.0 => y[0].abs(),
+        MeasureFunction::Rms => (integrate(x, |i| y[i] * y[i]) / span).sqrt(),
+        MeasureFunction::Min => min(),
+        MeasureFunction::Max => max(),
+        MeasureFunction::Pp => max() - min(),
+        MeasureFunction::Integ => integrate(x, |i| y[i]),
+    }
+}
+
+/// Write the measurements of every run of an analysis, each `.step` point under its label.
+pub(crate) fn write_measurements(mut w: impl Write, plots: &[AnalysisReport]) -> io::Result<()> {
+    for plot in plots {
+        if let Some(label) = &plot.label {
+            writeln!(w, "step {label}")?;
+        }
+        for measurement in &plot.measurements {
+            writeln!(w, "{measurement}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the measurements of one analysis to `<output_base>.meas`, if it has any.
+pub(crate) fn write_measurements_file(
+    plots: &[AnalysisReport],
+    output_base: &Path,
+) -> io::Result<Option<PathBuf>> {
+    if plots.iter().all(|plot| plot.measurements.is_empty()) {
+        return Ok(None);
+    }
+    let path = output_file(output_base, "meas");
+    let mut writer = BufWriter::new(File::create(&path)?);
+    write_measurements(&mut writer, plots)?;
+    writer.flush()?;
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SimulationConfig, simulate};
+    use spicy_parser::{ParseOptions, parse};
+
+    fn measurements(netlist: &str) -> Vec<Measurement> {
+        let mut options = ParseOptions::new_with_source("measure.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let config = SimulationConfig {
+            write_raw: false,
+            ..Default::default()
+        };
+        let report = simulate(deck, config).expect("simulate");
+        report
+            .analyses
+            .into_iter()
+            .flat_map(|analysis| analysis.measurements)
+            .collect()
+    }
+
+    fn value(measurements: &[Measurement], name: &str) -> Option<f64> {
+        let measurement = measurements.iter().find(|m| m.name == name);
+        measurement.expect("measured").value
+    }
+
+    fn assert_close(actual: f64, expected: f64, rel: f64) {
+        assert!(
+            (actual - expected).abs() <= rel * expected.abs(),
+            "{actual} != {expected}"
+        );
+    }
+
+    #[test]
+    fn rc_step_response() {
+        // tau = 1ms: 10-90% rise time tau*ln(9), 50% delay tau*ln(2)
+        let measured = measurements(
+            "rc step
+V1 in 0 PULSE(0 1 0 1n 1n 20m 40m)
+R1 in out 1k
+C1 out 0 1u
+.tran 5u 5m
+.meas tran trise TRIG v(out) VAL=0.1 RISE=1 TARG v(out) VAL=0.9 RISE=1
+.meas tran delay TRIG AT=0 TARG v(out) VAL=0.5
+.meas tran t50 WHEN v(out)=0.5 RISE=1
+.meas tran vend FIND v(out) AT=5m
+.meas tran never WHEN v(out)=0.5 FALL=1
+.meas tran vmax MAX v(out)
+.meas tran vavg AVG v(in) FROM=1m TO=4m
+.meas tran area INTEG v(in) TO=2m
+.end
+",
+        );
+        assert_close(value(&measured, "trise").unwrap(), 1e-3 * 9f64.ln(), 1e-2);
+        assert_close(value(&measured, "delay").unwrap(), 1e-3 * 2f64.ln(), 1e-2);
+        assert_eq!(value(&measured, "t50"), value(&measured, "delay"));
+        assert_close(value(&measured, "vend").unwrap(), 1.0 - (-5f64).exp(), 1e-2);
+        assert_eq!(value(&measured, "never"), None);
+        assert_eq!(value(&measured, "vmax"), value(&measured, "vend"));
+        assert_close(value(&measured, "vavg").unwrap(), 1.0, 1e-9);
+        assert_close(value(&measured, "area").unwrap(), 2e-3, 1e-2);
+    }
+
+    #[test]
+    fn dc_and_ac_sweeps() {
+        let measured = measurements(
+            "sweeps
+V1 in 0 DC 0 AC 1
+R1 in out 1k
+C1 out 0 1u
+R2 out 0 1k
+.dc V1 0 2 0.5
+.ac dec 20 1 100k
+.meas dc vinhalf WHEN v(out)=0.5
+.meas dc vpp PP v(out)
+.meas ac f3db WHEN v(out)=0.353553 FALL=1
+.end
+",
+        );
+        assert_close(value(&measured, "vinhalf").unwrap(), 1.0, 1e-9);
+        assert_close(value(&measured, "vpp").unwrap(), 1.0, 1e-9);
+        // the divider halves the gain and its pole is 1/(2*pi*500*1u)
+        let pole = 1.0 / (2.0 * std::f64::consts::PI * 500.0 * 1e-6);
+        assert_close(value(&measured, "f3db").unwrap(), pole, 5e-2);
+    }
+
+    #[test]
+    fn functions_over_a_window() {
+        let x = [0.0, 1.0, 2.0, 3.0];
+        let y = [0.0, 2.0, 2.0, -2.0];
+        let (wx, wy) = window(&x, &y, Some(0.5), Some(2.5)).unwrap();
+        assert_eq!(wx, [0.5, 1.0, 2.0, 2.5]);
+        assert_eq!(wy, [1.0, 2.0, 2.0, 0.0]);
+        assert_eq!(apply(MeasureFunction::Pp, &wx, &wy), 2.0);
+        assert_eq!(apply(MeasureFunction::Integ, &x, &y), 3.0);
+        assert_eq!(crossing(&x, &y, 1.0, MeasureEdge::Cross(2)), Some(2.25));
+        assert_eq!(window(&x, &y, Some(2.0), Some(1.0)), None);
+        assert_eq!(interpolate(&x, &y, 4.0), None);
+    }
+}
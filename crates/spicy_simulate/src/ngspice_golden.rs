@@ -0,0 +1,121 @@
+//! Golden-reference tests: the test netlists run through an installed ngspice (`$NGSPICE`, or
+//! `ngspice` on the path) and through spicy, and every node voltage must agree.
+//!
+//! Behind the `ngspice` feature: `cargo test -p spicy_simulate --features ngspice ngspice`.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use rstest::rstest;
+use spicy_parser::instance_parser::Deck;
+use spicy_parser::netlist_types::Command as DeckCommand;
+use spicy_parser::{ParseOptions, parse};
+
+use crate::SimulationConfig;
+use crate::dc::simulate_op;
+use crate::raw_reader::{RawPlot, read_raw_file};
+use crate::trans::simulate_trans;
+
+fn parse_file(input: &Path) -> Deck {
+    let content = std::fs::read_to_string(input).expect("failed to read input file");
+    let mut options = ParseOptions::new_with_source(input, content);
+    parse(&mut options).expect("parse")
+}
+
+/// The plot of ngspice's raw file for `input` whose name starts with `plotname`.
+fn ngspice_plot(input: &Path, plotname: &str) -> RawPlot {
+    let dir = std::env::temp_dir().join(format!("spicy-ngspice-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("temp dir");
+    let stem = input.file_stem().unwrap().to_string_lossy();
+    let raw = dir.join(format!("{}-{stem}.raw", plotname.replace(' ', "_")));
+
+    let ngspice = std::env::var_os("NGSPICE").unwrap_or_else(|| "ngspice".into());
+    let output = Command::new(&ngspice)
+        .arg("-b")
+        .arg("-r")
+        .arg(&raw)
+        .arg(input)
+        .output()
+        .unwrap_or_else(|e| panic!("failed to run {}: {e}", ngspice.to_string_lossy()));
+    assert!(
+        output.status.success(),
+        "ngspice failed on {}:\n{}",
+        input.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let plots = read_raw_file(&raw).expect("ngspice raw file");
+    std::fs::remove_file(&raw).ok();
+    plots
+        .into_iter()
+        .find(|plot| {
+            plot.plotname
+                .to_ascii_lowercase()
+                .starts_with(&plotname.to_ascii_lowercase())
+        })
+        .unwrap_or_else(|| panic!("no '{plotname}' plot for {}", input.display()))
+}
+
+/// `y` at `at`, linear between the points of `x`.
+fn interpolate(x: &[f64], y: &[f64], at: f64) -> f64 {
+    let i = x.partition_point(|&t| t < at).clamp(1, x.len() - 1);
+    let (x0, x1) = (x[i - 1], x[i]);
+    if x1 == x0 {
+        return y[i];
+    }
+    y[i - 1] + (y[i] - y[i - 1]) * (at - x0) / (x1 - x0)
+}
+
+#[rstest]
+fn ngspice_op_agrees(#[files("tests/op_dc/*.spicy")] input: PathBuf) {
+    let deck = parse_file(&input);
+    let op = simulate_op(&deck, &SimulationConfig::default()).expect("simulate_op");
+    let reference = ngspice_plot(&input, "Operating Point");
+    for (node, actual) in &op.voltages {
+        let expected = reference
+            .real(node)
+            .unwrap_or_else(|| panic!("ngspice has no v({node})"))[0];
+        // SPICE's reltol and vntol
+        let tolerance = 1e-3 * expected.abs() + 1e-6;
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{}: v({node}) = {actual}, ngspice {expected}",
+            input.display()
+        );
+    }
+}
+
+#[rstest]
+fn ngspice_tran_agrees(#[files("tests/trans/*.spicy")] input: PathBuf) {
+    let deck = parse_file(&input);
+    let command = deck
+        .commands
+        .iter()
+        .find_map(|cmd| match cmd {
+            DeckCommand::Tran(tran) => Some(tran),
+            _ => None,
+        })
+        .expect("expected .TRAN command");
+    let result =
+        simulate_trans(&deck, command, &SimulationConfig::default()).expect("simulate_trans");
+    let reference = ngspice_plot(&input, "Transient Analysis");
+    let time = reference.real("time").expect("ngspice time");
+    for node in &result.node_names {
+        let expected = reference
+            .real(node)
+            .unwrap_or_else(|| panic!("ngspice has no v({node})"));
+        let swing = expected.iter().copied().fold(f64::MIN, f64::max)
+            - expected.iter().copied().fold(f64::MAX, f64::min);
+        // the time steps differ, so compare against the waveform's swing
+        let tolerance = 1e-2 * swing + 1e-3;
+        let waveform = result.voltage(node).expect("spicy waveform");
+        for (&t, &actual) in waveform.x.iter().zip(&waveform.y) {
+            let expected = interpolate(time, expected, t);
+            assert!(
+                (actual - expected).abs() <= tolerance,
+                "{}: v({node}) at {t} = {actual}, ngspice {expected}",
+                input.display()
+            );
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Small-signal noise analysis (`.noise`).
+//!
+//! Every noisy device is modeled as a current source across two nodes with a one-sided power
+//! spectral density. At each frequency a single solve of the transposed (adjoint) AC system
+//! gives the transfer from a current injected between any two nodes to the output, so the
+//! output noise is the sum of |transfer|^2 * density over all the sources.
+
+use std::f64::consts::PI;
+
+use ndarray::{Array1, Array2, s};
+use ndarray_linalg::{FactorizeInto, Solve};
+use spicy_parser::instance_parser::Deck;
+use spicy_parser::netlist_types::NoiseCommand;
+use spicy_parser::node_mapping::NodeMapping;
+
+use crate::SimulationConfig;
+use crate::ac::{ac_frequencies, assemble_ac_real_expansion, small_signal_op};
+use crate::devices::Devices;
+use crate::error::SimulationError;
+
+/// Boltzmann constant (J/K).
+pub(crate) const BOLTZMANN: f64 = 1.380649e-23;
+/// Elementary charge (C).
+pub(crate) const ELECTRON_CHARGE: f64 = 1.602176634e-19;
+
+pub(crate) fn celsius_to_kelvin(t: f64) -> f64 {
+    t + 273.15
+}
+
+/// A noise current between two MNA nodes (`None` is ground).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NoiseSource {
+    pub positive: Option<usize>,
+    pub negative: Option<usize>,
+    /// One-sided power spectral density (A^2/Hz).
+    pub density: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct NoiseResult {
+    pub frequencies: Vec<f64>,
+    /// Output noise spectral density (V/sqrt(Hz)) per frequency.
+    pub output_density: Vec<f64>,
+    /// Output noise referred to the input source (V/sqrt(Hz) for a voltage source,
+    /// A/sqrt(Hz) for a current source) per frequency.
+    pub input_density: Vec<f64>,
+    /// Output noise integrated over the swept band (V rms).
+    pub total_output: f64,
+    /// Input-referred noise integrated over the swept band.
+    pub total_input: f64,
+}
+
+/// Where the input source drives the circuit, to refer the output noise back to it.
+enum InputSource {
+    /// MNA index of the branch current of a voltage source.
+    Voltage(usize),
+    /// MNA nodes of a current source.
+    Current(Option<usize>, Option<usize>),
+}
+
+impl InputSource {
+    fn find(devices: &Devices, node_mapping: &NodeMapping, name: &str) -> Option<Self> {
+        if let Some(source) = devices
+            .voltage_sources
+            .iter()
+            .find(|v| node_mapping.name_case().matches(&v.name, name))
+        {
+            return Some(Self::Voltage(
+                node_mapping.mna_branch_index(source.current_branch),
+            ));
+        }
+        devices
+            .current_sources
+            .iter()
+            .find(|i| node_mapping.name_case().matches(&i.name, name))
+            .map(|source| {
+                Self::Current(
+                    node_mapping.mna_node_index(source.positive),
+                    node_mapping.mna_node_index(source.negative),
+                )
+            })
+    }
+}
+
+/// Every noise source of `devices` at frequency `f`, with the nonlinear devices biased at `op`.
+fn noise_sources(
+    devices: &Devices,
+    node_mapping: &NodeMapping,
+    op: Option<&[f64]>,
+    f: f64,
+) -> Vec<NoiseSource> {
+    let mut sources: Vec<NoiseSource> = devices
+        .resistors
+        .iter()
+        .filter_map(|r| r.noise(node_mapping))
+        .collect();
+    if let Some(op) = op {
+        sources.extend(devices.diodes.iter().map(|d| d.noise(node_mapping, op, f)));
+        sources.extend(
+            devices
+                .bjts
+                .iter()
+                .flat_map(|q| q.noise(node_mapping, op, f)),
+        );
+        sources.extend(
+            devices
+                .jfets
+                .iter()
+                .flat_map(|j| j.noise(node_mapping, op, f)),
+        );
+    }
+    sources
+}
+
+/// Solve the adjoint system A^T y = e, where A is the complex small-signal matrix given by
+/// its real expansion `m` and `e` selects the output voltage `output - reference`.
+/// Returns the real and imaginary parts of `y`.
+fn solve_adjoint(
+    m: &Array2<f64>,
+    dim: usize,
+    output: usize,
+    reference: Option<usize>,
+) -> (Array1<f64>, Array1<f64>) {
+    // [Ar -Ai; Ai Ar] becomes [Ar^T -Ai^T; Ai^T Ar^T]: every block is transposed in place
+    let mut adjoint = Array2::<f64>::zeros((2 * dim, 2 * dim));
+    for rows in [0..dim, dim..2 * dim] {
+        for cols in [0..dim, dim..2 * dim] {
+            let block = m.slice(s![rows.clone(), cols.clone()]).reversed_axes();
+            adjoint.slice_mut(s![rows.clone(), cols]).assign(&block);
+        }
+    }
+
+    let mut e = Array1::<f64>::zeros(2 * dim);
+    e[output] = 1.0;
+    if let Some(reference) = reference {
+        e[reference] = -1.0;
+    }
+
+    let lu = adjoint
+        .factorize_into()
+        .expect("Failed to factorize adjoint AC matrix");
+    let y = lu.solve(&e).expect("Failed to solve adjoint AC system");
+    (
+        y.slice(s![0..dim]).to_owned(),
+        y.slice(s![dim..2 * dim]).to_owned(),
+    )
+}
+
+/// Integrate a spectral density over frequency with the trapezoid rule.
+fn integrate(frequencies: &[f64], density: &[f64]) -> f64 {
+    frequencies
+        .windows(2)
+        .zip(density.windows(2))
+        .map(|(f, d)| 0.5 * (d[0] + d[1]) * (f[1] - f[0]))
+        .sum()
+}
+
+pub fn simulate_noise(
+    deck: &Deck,
+    cmd: &NoiseCommand,
+    sim_config: &SimulationConfig,
+) -> Result<NoiseResult, SimulationError> {
+    let mut devices = Devices::from_deck(deck, sim_config);
+    let node_mapping = &deck.node_mapping;
+    let dim = node_mapping.mna_matrix_dim();
+
+    let input = InputSource::find(&devices, node_mapping, &cmd.input_source).ok_or_else(|| {
+        SimulationError::UnknownDevice {
+            name: cmd.input_source.clone(),
+        }
+    })?;
+
+    // the parser only keeps nodes of the deck
+    let node_names = node_mapping.node_names_mna_order();
+    let node_index = |name: &str| {
+        node_names
+            .iter()
+            .position(|n| n == name)
+            .expect("known node")
+    };
+    let output = node_index(&cmd.output);
+    let reference = cmd.reference.as_deref().map(node_index);
+
+    let op = if devices.is_linear() {
+        None
+    } else {
+        Some(small_signal_op(deck, &mut devices, sim_config)?)
+    };
+
+    let frequencies = ac_frequencies(&cmd.sweep);
+    let mut output_psd = Vec::with_capacity(frequencies.len());
+    let mut input_psd = Vec::with_capacity(frequencies.len());
+    for &f in &frequencies {
+        let w = 2.0 * PI * f;
+        let (m, _) = assemble_ac_real_expansion(&devices, node_mapping, w, op.as_deref());
+        let (yr, yi) = solve_adjoint(&m, dim, output, reference);
+
+        // |V(out)|^2 per unit current injected into `positive` and out of `negative`
+        let transfer = |positive: Option<usize>, negative: Option<usize>| {
+            let at = |y: &Array1<f64>, i: Option<usize>| i.map_or(0.0, |i| y[i]);
+            let re = at(&yr, positive) - at(&yr, negative);
+            let im = at(&yi, positive) - at(&yi, negative);
+            re * re + im * im
+        };
+
+        let psd: f64 = noise_sources(&devices, node_mapping, op.as_deref(), f)
+            .iter()
+            .map(|source| transfer(source.positive, source.negative) * source.density)
+            .sum();
+        let gain = match input {
+            InputSource::Voltage(branch) => yr[branch] * yr[branch] + yi[branch] * yi[branch],
+            InputSource::Current(positive, negative) => transfer(positive, negative),
+        };
+        output_psd.push(psd);
+        input_psd.push(psd / gain);
+    }
+
+    Ok(NoiseResult {
+        total_output: integrate(&frequencies, &output_psd).sqrt(),
+        total_input: integrate(&frequencies, &input_psd).sqrt(),
+        output_density: output_psd.iter().map(|p| p.sqrt()).collect(),
+        input_density: input_psd.iter().map(|p| p.sqrt()).collect(),
+        frequencies,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spicy_parser::netlist_types::Command;
+    use spicy_parser::{ParseOptions, parse};
+
+    fn run(netlist: &str) -> NoiseResult {
+        let mut options = ParseOptions::new_with_source("noise.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let cmd = deck
+            .commands
+            .iter()
+            .find_map(|c| match c {
+                Command::Noise(noise) => Some(noise),
+                _ => None,
+            })
+            .expect("noise command");
+        simulate_noise(&deck, cmd, &SimulationConfig::default()).expect("noise")
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        let rel = (actual - expected).abs() / expected.abs();
+        assert!(rel < 1e-6, "expected {expected}, got {actual}");
+    }
+
+    #[test]
+    fn divider_output_noise_is_thermal_noise_of_parallel_resistance() {
+        let result = run("noise divider
+V1 in 0 DC 1 AC 1
+R1 in out 1k
+R2 out 0 3k
+.noise v(out) V1 dec 2 1 1k
+.end
+");
+        let kt = BOLTZMANN * celsius_to_kelvin(27.0);
+        let parallel = 1e3 * 3e3 / 4e3;
+        let expected = (4.0 * kt * parallel).sqrt();
+        for (&onoise, &inoise) in result.output_density.iter().zip(&result.input_density) {
+            assert_close(onoise, expected);
+            // gain from V1 to out is 3/4
+            assert_close(inoise, expected / 0.75);
+        }
+        let band = result.frequencies.last().unwrap() - result.frequencies[0];
+        assert_close(result.total_output, expected * band.sqrt());
+    }
+
+    #[test]
+    fn noiseless_resistor_does_not_contribute() {
+        let result = run("noiseless
+I1 out 0 DC 1m
+R1 out 0 1k noisy=0
+R2 out 0 1k
+.noise v(out) I1 lin 2 1k 2k
+.end
+");
+        let kt = BOLTZMANN * celsius_to_kelvin(27.0);
+        // only R2's noise current flows into R1 || R2
+        assert_close(result.output_density[0], (4.0 * kt / 1e3).sqrt() * 500.0);
+        // and referred to I1 it is R2's noise current
+        assert_close(result.input_density[0], (4.0 * kt / 1e3).sqrt());
+    }
+
+    #[test]
+    fn diode_shot_noise_adds_to_resistor_noise() {
+        let result = run("diode shot noise
+I1 a 0 DC 1m
+D1 a 0 dmod
+R1 a 0 1000k noisy=0
+.model dmod D(is=1e-14)
+.noise v(a) I1 lin 2 1k 2k
+.end
+");
+        // the 1 mA bias flows almost entirely through the diode
+        let i_d = 1e-3;
+        let r_d = 0.02585 / i_d;
+        let r = r_d * 1e6 / (r_d + 1e6);
+        let expected = (2.0 * ELECTRON_CHARGE * i_d).sqrt() * r;
+        let rel = (result.output_density[0] - expected).abs() / expected;
+        assert!(
+            rel < 1e-3,
+            "expected {expected}, got {}",
+            result.output_density[0]
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Progress callbacks for long simulations.
+//!
+//! A [`SimulateObserver`] set in [`crate::SimulationConfig::observer`] sees every analysis as
+//! it runs, so a viewer can plot the waveforms live. Returning [`ControlFlow::Break`] from a
+//! callback stops the simulation with [`crate::SimulationError::Aborted`].
+//!
+//! DC sweeps and transients also publish their [`Progress`], for a progress bar with an ETA.
+
+use std::fmt;
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+
+use spicy_parser::netlist_types::Command;
+
+use crate::dc::OperatingPointResult;
+use crate::error::SimulationError;
+use crate::report::AnalysisReport;
+
+/// Callbacks invoked while a simulation runs. Every method does nothing by default.
+///
+/// With [`crate::SimulationConfig::parallel`] the analyses run on several threads at once, so
+/// the callbacks of different analyses interleave.
+pub trait SimulateObserver: Send + Sync {
+    /// An analysis is about to run, at the `.step`/`.temp` point of `label` if any.
+    fn on_analysis_start(&self, _command: &Command, _label: Option<&str>) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// An analysis finished.
+    fn on_analysis_end(&self, _report: &AnalysisReport) {}
+
+    /// A DC sweep solved the point where its first source is `value`.
+    fn on_sweep_point(&self, _value: f64, _op: &OperatingPointResult) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// A transient analysis accepted the time point `time`. The solution is in MNA order, the
+    /// node voltages followed by the branch currents, like [`crate::TransientResult::samples`].
+    fn on_timepoint(&self, _time: f64, _solution: &[f64]) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// A DC sweep or a transient analysis moved forward.
+    fn on_progress(&self, _progress: &Progress) {}
+}
+
+/// How far a DC sweep or a transient analysis got.
+///
+/// A transient counts its steps in `tstep`s, so an adaptive one advances by the simulated time
+/// whatever step sizes it takes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    /// `"dc"` or `"tran"`.
+    pub analysis: &'static str,
+    /// Steps done, at most `total_steps`.
+    pub step: usize,
+    pub total_steps: usize,
+    /// Time since the analysis started.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// The part of the analysis done, between 0 and 1.
+    pub fn fraction(&self) -> f64 {
+        if self.total_steps == 0 {
+            return 1.0;
+        }
+        self.step as f64 / self.total_steps as f64
+    }
+
+    /// The time left at the pace so far, unknown before the first step.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.step == 0 {
+            return None;
+        }
+        let left = self.total_steps.saturating_sub(self.step) as f64;
+        Some(self.elapsed.mul_f64(left / self.step as f64))
+    }
+}
+
+/// Publishes the [`Progress`] of one analysis, timed from its creation.
+pub(crate) struct ProgressClock {
+    analysis: &'static str,
+    total_steps: usize,
+    start: Instant,
+}
+
+impl ProgressClock {
+    pub(crate) fn new(analysis: &'static str, total_steps: usize) -> Self {
+        Self {
+            analysis,
+            total_steps,
+            start: Instant::now(),
+        }
+    }
+
+    pub(crate) fn publish(&self, observer: &dyn SimulateObserver, step: usize) {
+        observer.on_progress(&Progress {
+            analysis: self.analysis,
+            step: step.min(self.total_steps),
+            total_steps: self.total_steps,
+            elapsed: self.start.elapsed(),
+        });
+    }
+}
+
+impl fmt::Debug for dyn SimulateObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SimulateObserver")
+    }
+}
+
+/// Turn a callback's decision into an error that stops the simulation.
+pub(crate) fn check(flow: ControlFlow<()>) -> Result<(), SimulationError> {
+    match flow {
+        ControlFlow::Continue(()) => Ok(()),
+        ControlFlow::Break(()) => Err(SimulationError::Aborted),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trans::simulate_trans;
+    use crate::{SimulationConfig, simulate};
+    use spicy_parser::{ParseOptions, parse};
+    use std::sync::{Arc, Mutex};
+
+    const NETLIST: &str = "observed
+V1 in 0 DC 1
+R1 in out 1k
+C1 out 0 1u
+.dc V1 0 1 0.5
+.tran 100u 1m
+.end
+";
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+        progress: Mutex<Vec<Progress>>,
+        /// abort the transient after this many time points
+        max_timepoints: Option<usize>,
+    }
+
+    impl Recorder {
+        fn timepoints(&self) -> usize {
+            let events = self.events.lock().unwrap();
+            events.iter().filter(|e| e.starts_with("t=")).count()
+        }
+    }
+
+    impl SimulateObserver for Recorder {
+        fn on_analysis_start(&self, command: &Command, _label: Option<&str>) -> ControlFlow<()> {
+            let kind = match command {
+                Command::Dc(_) => "dc",
+                Command::Tran(_) => "tran",
+                _ => "other",
+            };
+            self.events.lock().unwrap().push(format!("start {kind}"));
+            ControlFlow::Continue(())
+        }
+
+        fn on_analysis_end(&self, report: &AnalysisReport) {
+            let name = report.result.extension();
+            self.events.lock().unwrap().push(format!("end {name}"));
+        }
+
+        fn on_sweep_point(&self, value: f64, _op: &OperatingPointResult) -> ControlFlow<()> {
+            self.events.lock().unwrap().push(format!("v={value}"));
+            ControlFlow::Continue(())
+        }
+
+        fn on_timepoint(&self, time: f64, _solution: &[f64]) -> ControlFlow<()> {
+            self.events.lock().unwrap().push(format!("t={time}"));
+            match self.max_timepoints {
+                Some(max) if self.timepoints() >= max => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            }
+        }
+
+        fn on_progress(&self, progress: &Progress) {
+            self.progress.lock().unwrap().push(*progress);
+        }
+    }
+
+    fn config(recorder: &Arc<Recorder>) -> SimulationConfig {
+        SimulationConfig {
+            observer: Some(recorder.clone()),
+            // one analysis at a time, for the order of the events
+            parallel: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn simulate_notifies_every_point() {
+        let mut options = ParseOptions::new_with_source("observed.spicy", NETLIST.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let recorder = Arc::new(Recorder::default());
+        simulate(deck, config(&recorder)).expect("simulate");
+
+        assert_eq!(recorder.timepoints(), 11);
+        let events = recorder.events.lock().unwrap();
+        assert_eq!(events[..5], ["start dc", "v=0", "v=0.5", "v=1", "end dc"]);
+        assert_eq!(events[5], "start tran");
+        assert_eq!(events[6], "t=0");
+        assert_eq!(events.last().unwrap(), "end tran");
+    }
+
+    #[test]
+    fn sweeps_and_transients_publish_their_progress() {
+        let mut options = ParseOptions::new_with_source("observed.spicy", NETLIST.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let recorder = Arc::new(Recorder::default());
+        simulate(deck, config(&recorder)).expect("simulate");
+
+        let progress = recorder.progress.lock().unwrap();
+        let dc: Vec<_> = progress.iter().filter(|p| p.analysis == "dc").collect();
+        assert_eq!(
+            dc.iter()
+                .map(|p| (p.step, p.total_steps))
+                .collect::<Vec<_>>(),
+            [(1, 3), (2, 3), (3, 3)]
+        );
+        let tran: Vec<_> = progress.iter().filter(|p| p.analysis == "tran").collect();
+        assert_eq!(tran.len(), 11);
+        assert!(tran.iter().all(|p| p.total_steps == 10));
+        assert!(tran.windows(2).all(|w| w[0].step <= w[1].step));
+        assert_eq!((tran[0].step, tran[10].step), (0, 10));
+        assert_eq!(tran[10].fraction(), 1.0);
+        assert_eq!(tran[10].eta(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn eta_extrapolates_the_pace_so_far() {
+        let progress = Progress {
+            analysis: "tran",
+            step: 25,
+            total_steps: 100,
+            elapsed: Duration::from_secs(1),
+        };
+        assert_eq!(progress.fraction(), 0.25);
+        assert_eq!(progress.eta(), Some(Duration::from_secs(3)));
+        assert_eq!(
+            Progress {
+                step: 0,
+                ..progress
+            }
+            .eta(),
+            None
+        );
+    }
+
+    #[test]
+    fn observer_aborts_a_transient() {
+        let mut options = ParseOptions::new_with_source("observed.spicy", NETLIST.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let tran = deck
+            .commands
+            .iter()
+            .find_map(|c| match c {
+                Command::Tran(tran) => Some(tran),
+                _ => None,
+            })
+            .expect("tran");
+        let recorder = Arc::new(Recorder {
+            max_timepoints: Some(3),
+            ..Default::default()
+        });
+
+        let result = simulate_trans(&deck, tran, &config(&recorder));
+        assert!(matches!(result, Err(SimulationError::Aborted)));
+        assert_eq!(recorder.timepoints(), 3);
+    }
+}
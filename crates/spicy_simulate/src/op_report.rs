@@ -0,0 +1,256 @@
+//! A readable report of an operating point: every node voltage, every branch current and what
+//! each device is doing there (diode current, BJT bias, power dissipated in a resistor, ...).
+
+use std::fmt;
+
+use spicy_parser::instance_parser::Deck;
+use spicy_parser::node_mapping::NodeMapping;
+
+use crate::SimulationConfig;
+use crate::dc::OperatingPointResult;
+use crate::devices::Devices;
+use crate::output::op_solution;
+use crate::results::position;
+
+/// The quantities one device computes at the operating point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceOperatingPoint {
+    pub name: String,
+    /// Quantity name and value, e.g. `("id", 1e-3)`, in the SPICE units (V, A, W, S).
+    pub values: Vec<(&'static str, f64)>,
+}
+
+impl DeviceOperatingPoint {
+    pub(crate) fn new<const N: usize>(name: &str, values: [(&'static str, f64); N]) -> Self {
+        Self {
+            name: name.to_string(),
+            values: values.to_vec(),
+        }
+    }
+
+    /// Add the value of another `quantity`.
+    pub(crate) fn with(mut self, quantity: &'static str, value: f64) -> Self {
+        self.values.push((quantity, value));
+        self
+    }
+
+    /// The value of `quantity`, e.g. `"ic"` of a BJT.
+    pub fn get(&self, quantity: &str) -> Option<f64> {
+        self.values
+            .iter()
+            .find(|(name, _)| *name == quantity)
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Every device of `devices` at the solution `x`, in the order of [`OpReport::devices`]. The
+/// sources take their value at time `t` of a transient with step `dt` and stop `tstop`, all 0
+/// for an operating point.
+pub(crate) fn device_points(
+    devices: &Devices,
+    map: &NodeMapping,
+    x: &[f64],
+    t: f64,
+    dt: f64,
+    tstop: f64,
+) -> Vec<DeviceOperatingPoint> {
+    let mut device_points = Vec::new();
+    device_points.extend(devices.resistors.iter().map(|r| r.operating_point(map, x)));
+    device_points.extend(devices.diodes.iter().map(|d| d.operating_point(map, x)));
+    device_points.extend(
+        devices
+            .lookup_tables
+            .iter()
+            .map(|t| t.operating_point(map, x)),
+    );
+    device_points.extend(devices.bjts.iter().map(|q| q.operating_point(map, x)));
+    device_points.extend(devices.mosfets.iter().map(|m| m.operating_point(map, x)));
+    device_points.extend(devices.jfets.iter().map(|j| j.operating_point(map, x)));
+    device_points.extend(
+        devices
+            .voltage_sources
+            .iter()
+            .map(|v| v.operating_point_voltage(map, x)),
+    );
+    device_points.extend(
+        devices
+            .current_sources
+            .iter()
+            .map(|i| i.operating_point_current(map, x, t, dt, tstop)),
+    );
+    device_points
+}
+
+/// Number of [`device_points`] that are sources, the last ones.
+pub(crate) fn source_count(devices: &Devices) -> usize {
+    devices.voltage_sources.len() + devices.current_sources.len()
+}
+
+/// An operating point with the quantities of every device.
+///
+/// The devices are listed by kind: resistors, diodes, lookup tables, BJTs, MOSFETs, JFETs, then
+/// voltage and current sources. Every device reports the power it absorbs as `p`, negative for a source
+/// that delivers power.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpReport {
+    pub voltages: Vec<(String, f64)>,
+    pub currents: Vec<(String, f64)>,
+    pub devices: Vec<DeviceOperatingPoint>,
+    /// Power dissipated by the devices other than the sources (W), which is what the sources
+    /// deliver.
+    pub total_power: f64,
+}
+
+impl OpReport {
+    /// Evaluate the devices of `deck` at the solution `op`, at the temperature of `sim_config`.
+    pub fn new(deck: &Deck, op: &OperatingPointResult, sim_config: &SimulationConfig) -> Self {
+        let devices = Devices::from_deck(deck, sim_config);
+        let x = op_solution(op);
+        devices.settle_heat(&deck.node_mapping, &x, None);
+        let device_points = device_points(&devices, &deck.node_mapping, &x, 0.0, 0.0, 0.0);
+        let loads = device_points.len() - source_count(&devices);
+        let total_power = device_points[..loads]
+            .iter()
+            .filter_map(|device| device.get("p"))
+            .sum();
+
+        Self {
+            voltages: op.voltages.clone(),
+            currents: op.currents.clone(),
+            devices: device_points,
+            total_power,
+        }
+    }
+
+    /// The quantities of the device `name`.
+    pub fn device(&self, name: &str) -> Option<&DeviceOperatingPoint> {
+        let index = position(self.devices.iter().map(|device| &device.name), name)?;
+        Some(&self.devices[index])
+    }
+
+    /// Power absorbed by the device `name` (W), `P(name)`.
+    pub fn power(&self, name: &str) -> Option<f64> {
+        self.device(name)?.get("p")
+    }
+}
+
+impl fmt::Display for OpReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Node voltages:")?;
+        for (name, value) in &self.voltages {
+            writeln!(f, "{:>14}{value:>14.6e}", format!("V({name})"))?;
+        }
+        if !self.currents.is_empty() {
+            writeln!(f, "Branch currents:")?;
+            for (name, value) in &self.currents {
+                writeln!(f, "{:>14}{value:>14.6e}", format!("I({name})"))?;
+            }
+        }
+        if !self.devices.is_empty() {
+            writeln!(f, "Devices:")?;
+            for device in &self.devices {
+                write!(f, "{:>14}", device.name)?;
+                for (quantity, value) in &device.values {
+                    write!(f, "{:>6} ={value:>13.6e}", quantity)?;
+                }
+                writeln!(f)?;
+            }
+            writeln!(f, "Total power dissipated: {:.6e} W", self.total_power)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dc::simulate_op;
+    use spicy_parser::{ParseOptions, parse};
+
+    fn report(netlist: &str) -> OpReport {
+        let mut options = ParseOptions::new_with_source("op_report.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let config = SimulationConfig::default();
+        let op = simulate_op(&deck, &config).expect("op");
+        OpReport::new(&deck, &op, &config)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() <= 1e-9 + 1e-6 * expected.abs(),
+            "{actual} != {expected}"
+        );
+    }
+
+    #[test]
+    fn resistor_and_source_powers_balance() {
+        let report = report(
+            "divider
+V1 in 0 DC 2
+R1 in out 1k
+R2 out 0 3k
+I1 out 0 DC 0
+.op
+.end
+",
+        );
+        let r1 = report.device("R1").expect("R1");
+        assert_close(r1.get("v").unwrap(), 0.5);
+        assert_close(r1.get("i").unwrap(), 0.5e-3);
+        assert_close(r1.get("p").unwrap(), 0.25e-3);
+
+        let total: f64 = report
+            .devices
+            .iter()
+            .filter_map(|device| device.get("p"))
+            .sum();
+        assert_close(total, 0.0);
+        assert_close(report.power("V1").unwrap(), -1e-3);
+        assert_close(report.total_power, 1e-3);
+        assert!(report.to_string().contains("Total power dissipated"));
+    }
+
+    #[test]
+    fn diode_and_bjt_currents_match_the_circuit() {
+        let report = report(
+            "biased
+VCC vcc 0 DC 5
+RB vcc b 430k
+RC vcc c 1k
+Q1 c b 0 qn
+RD vcc a 1k
+D1 a 0 dmod
+.model qn npn(bf=100)
+.model dmod d
+.op
+.end
+",
+        );
+        let d1 = report.device("D1").expect("D1");
+        let rd = report.device("RD").expect("RD");
+        assert_close(d1.get("id").unwrap(), rd.get("i").unwrap());
+
+        let q1 = report.device("Q1").expect("Q1");
+        assert_close(
+            q1.get("ib").unwrap(),
+            report.device("RB").unwrap().get("i").unwrap(),
+        );
+        assert_close(
+            q1.get("ic").unwrap(),
+            report.device("RC").unwrap().get("i").unwrap(),
+        );
+        assert!(q1.get("vbe").unwrap() > 0.5);
+        // the supply delivers what the resistors, the diode and the transistor dissipate
+        assert_close(report.total_power, -report.power("VCC").unwrap());
+        let q1_power = q1.get("p").unwrap();
+        assert_close(
+            q1_power,
+            q1.get("vce").unwrap() * q1.get("ic").unwrap()
+                + q1.get("vbe").unwrap() * q1.get("ib").unwrap(),
+        );
+
+        let text = report.to_string();
+        assert!(text.contains("V(c)"), "{text}");
+        assert!(text.contains("Q1"), "{text}");
+    }
+}
@@ -0,0 +1,475 @@
+//! Tune top-level `.param`s to minimize an objective computed from simulation results.
+//!
+//! Every candidate is the netlist parsed again with the variables overridden, loaded into a
+//! `SimulationEngine` so the solve warm-starts from the previous candidate. Candidates that do
+//! not depend on each other (the initial simplex, a shrink, the compass points of a coordinate
+//! search) are evaluated in parallel, one engine per worker.
+//!
+//! The search runs on the variables scaled to `[0, 1]` over their bounds.
+
+use spicy_parser::{ParseOptions, parse_with_params};
+
+use crate::{SimulationConfig, engine::SimulationEngine, error::SimulationError};
+
+/// Size of the initial simplex / compass step, relative to the variable ranges.
+const INITIAL_STEP: f64 = 0.25;
+
+/// A top-level `.param` the optimizer may change within `[min, max]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimizeVariable {
+    pub param: String,
+    pub initial: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OptimizeMethod {
+    #[default]
+    NelderMead,
+    /// Compass search: try a step up and down along every variable, halve the step when none
+    /// of them improves.
+    CoordinateSearch,
+}
+
+#[derive(Debug, Clone)]
+pub struct OptimizeConfig {
+    pub method: OptimizeMethod,
+    /// stop (unconverged) after this many objective evaluations
+    pub max_evaluations: usize,
+    /// converged once the search is smaller than this, relative to the variable ranges
+    pub x_tolerance: f64,
+    /// converged once the objective varies less than this across the simplex (Nelder–Mead)
+    pub f_tolerance: f64,
+    /// candidates evaluated concurrently
+    pub threads: usize,
+}
+
+impl Default for OptimizeConfig {
+    fn default() -> Self {
+        Self {
+            method: OptimizeMethod::default(),
+            max_evaluations: 500,
+            x_tolerance: 1e-6,
+            f_tolerance: 1e-12,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimizeResult {
+    /// the best value found for every variable, in the order they were given
+    pub params: Vec<(String, f64)>,
+    pub objective: f64,
+    pub evaluations: usize,
+    pub converged: bool,
+}
+
+/// Minimize `objective` over `variables`.
+///
+/// The objective runs the analyses it needs on an engine loaded with a candidate and reduces
+/// them to a number, e.g. the squared distance of `V(out)` from a target. A candidate whose
+/// simulation fails scores infinity, except for the starting point whose error is returned.
+pub fn optimize<F>(
+    options: &mut ParseOptions,
+    variables: &[OptimizeVariable],
+    objective: F,
+    sim_config: &SimulationConfig,
+    config: &OptimizeConfig,
+) -> Result<OptimizeResult, SimulationError>
+where
+    F: Fn(&mut SimulationEngine) -> Result<f64, SimulationError> + Sync,
+{
+    if let Some(v) = variables
+        .iter()
+        .find(|v| v.min >= v.max || v.min.is_nan() || v.max.is_nan())
+    {
+        return Err(SimulationError::InvalidBounds {
+            name: v.param.clone(),
+        });
+    }
+
+    let mut evaluator = Evaluator {
+        options,
+        variables,
+        objective: &objective,
+        sim_config,
+        engines: Vec::new(),
+        threads: config.threads.max(1),
+        evaluations: 0,
+    };
+    let start: Vec<f64> = variables
+        .iter()
+        .map(|v| ((v.initial - v.min) / (v.max - v.min)).clamp(0.0, 1.0))
+        .collect();
+
+    let (best, objective, converged) = match config.method {
+        OptimizeMethod::NelderMead => nelder_mead(&mut evaluator, start, config)?,
+        OptimizeMethod::CoordinateSearch => coordinate_search(&mut evaluator, start, config)?,
+    };
+
+    Ok(OptimizeResult {
+        params: evaluator.params(&best),
+        objective,
+        evaluations: evaluator.evaluations,
+        converged,
+    })
+}
+
+struct Evaluator<'a, F> {
+    options: &'a mut ParseOptions,
+    variables: &'a [OptimizeVariable],
+    objective: &'a F,
+    sim_config: &'a SimulationConfig,
+    /// one per worker, each warm-starting from the last candidate it evaluated
+    engines: Vec<SimulationEngine>,
+    threads: usize,
+    evaluations: usize,
+}
+
+impl<F> Evaluator<'_, F>
+where
+    F: Fn(&mut SimulationEngine) -> Result<f64, SimulationError> + Sync,
+{
+    fn params(&self, point: &[f64]) -> Vec<(String, f64)> {
+        self.variables
+            .iter()
+            .zip(point)
+            .map(|(v, &u)| (v.param.clone(), v.min + u * (v.max - v.min)))
+            .collect()
+    }
+
+    fn evaluate(&mut self, points: &[Vec<f64>]) -> Result<Vec<f64>, SimulationError> {
+        let decks = points
+            .iter()
+            .map(|point| parse_with_params(self.options, &self.params(point)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some(first) = decks.first() else {
+            return Ok(Vec::new());
+        };
+
+        let workers = self.threads.min(decks.len());
+        while self.engines.len() < workers {
+            let engine = SimulationEngine::new(first, self.sim_config.clone())?;
+            self.engines.push(engine);
+        }
+
+        let objective = self.objective;
+        let chunk = decks.len().div_ceil(workers);
+        let scores: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .engines
+                .iter_mut()
+                .zip(decks.chunks(chunk))
+                .map(|(engine, decks)| {
+                    scope.spawn(move || {
+                        decks
+                            .iter()
+                            .map(|deck| engine.update(deck).and_then(|()| objective(engine)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("optimization worker panicked"))
+                .collect()
+        });
+
+        let starting = self.evaluations == 0;
+        self.evaluations += scores.len();
+        scores
+            .into_iter()
+            .map(|score| match score {
+                Ok(f) if f.is_nan() => Ok(f64::INFINITY),
+                Ok(f) => Ok(f),
+                Err(e) if starting => Err(e),
+                Err(_) => Ok(f64::INFINITY),
+            })
+            .collect()
+    }
+
+    fn evaluate_one(&mut self, point: &[f64]) -> Result<f64, SimulationError> {
+        Ok(self.evaluate(&[point.to_vec()])?[0])
+    }
+}
+
+fn nelder_mead<F>(
+    evaluator: &mut Evaluator<'_, F>,
+    start: Vec<f64>,
+    config: &OptimizeConfig,
+) -> Result<(Vec<f64>, f64, bool), SimulationError>
+where
+    F: Fn(&mut SimulationEngine) -> Result<f64, SimulationError> + Sync,
+{
+    let n = start.len();
+    let mut simplex = vec![start.clone()];
+    for i in 0..n {
+        let mut vertex = start.clone();
+        vertex[i] += if vertex[i] > 0.5 {
+            -INITIAL_STEP
+        } else {
+            INITIAL_STEP
+        };
+        simplex.push(vertex);
+    }
+    let scores = evaluator.evaluate(&simplex)?;
+    let mut vertices: Vec<(Vec<f64>, f64)> = simplex.into_iter().zip(scores).collect();
+
+    loop {
+        vertices.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let (best, best_score) = vertices[0].clone();
+        let worst_score = vertices[n].1;
+
+        let size = vertices[1..]
+            .iter()
+            .flat_map(|(v, _)| v.iter().zip(&best).map(|(a, b)| (a - b).abs()))
+            .fold(0.0, f64::max);
+        if size < config.x_tolerance || worst_score - best_score <= config.f_tolerance {
+            return Ok((best, best_score, true));
+        }
+        if evaluator.evaluations >= config.max_evaluations {
+            return Ok((best, best_score, false));
+        }
+
+        let centroid: Vec<f64> = (0..n)
+            .map(|j| vertices[..n].iter().map(|(v, _)| v[j]).sum::<f64>() / n as f64)
+            .collect();
+        let worst = vertices[n].0.clone();
+        // the point at `t` times the worst vertex mirrored through the centroid
+        let along = |t: f64| -> Vec<f64> {
+            centroid
+                .iter()
+                .zip(&worst)
+                .map(|(c, w)| (c + t * (c - w)).clamp(0.0, 1.0))
+                .collect()
+        };
+
+        let reflected = along(1.0);
+        let reflected_score = evaluator.evaluate_one(&reflected)?;
+        if reflected_score < best_score {
+            let expanded = along(2.0);
+            let expanded_score = evaluator.evaluate_one(&expanded)?;
+            vertices[n] = if expanded_score < reflected_score {
+                (expanded, expanded_score)
+            } else {
+                (reflected, reflected_score)
+            };
+            continue;
+        }
+        if reflected_score < vertices[n - 1].1 {
+            vertices[n] = (reflected, reflected_score);
+            continue;
+        }
+
+        let t = if reflected_score < worst_score {
+            0.5
+        } else {
+            -0.5
+        };
+        let contracted = along(t);
+        let contracted_score = evaluator.evaluate_one(&contracted)?;
+        if contracted_score < reflected_score.min(worst_score) {
+            vertices[n] = (contracted, contracted_score);
+            continue;
+        }
+
+        // shrink towards the best vertex
+        let shrunk: Vec<Vec<f64>> = vertices[1..]
+            .iter()
+            .map(|(v, _)| {
+                v.iter()
+                    .zip(&best)
+                    .map(|(x, b)| b + 0.5 * (x - b))
+                    .collect()
+            })
+            .collect();
+        let scores = evaluator.evaluate(&shrunk)?;
+        for (vertex, shrunk) in vertices[1..].iter_mut().zip(shrunk.into_iter().zip(scores)) {
+            *vertex = shrunk;
+        }
+    }
+}
+
+fn coordinate_search<F>(
+    evaluator: &mut Evaluator<'_, F>,
+    start: Vec<f64>,
+    config: &OptimizeConfig,
+) -> Result<(Vec<f64>, f64, bool), SimulationError>
+where
+    F: Fn(&mut SimulationEngine) -> Result<f64, SimulationError> + Sync,
+{
+    let mut center = start;
+    let mut center_score = evaluator.evaluate_one(&center)?;
+    let mut step = INITIAL_STEP;
+
+    loop {
+        if step < config.x_tolerance {
+            return Ok((center, center_score, true));
+        }
+        if evaluator.evaluations >= config.max_evaluations {
+            return Ok((center, center_score, false));
+        }
+
+        let candidates: Vec<Vec<f64>> = (0..center.len())
+            .flat_map(|i| {
+                [-step, step].map(|delta| {
+                    let mut point = center.clone();
+                    point[i] = (point[i] + delta).clamp(0.0, 1.0);
+                    point
+                })
+            })
+            .filter(|point| *point != center)
+            .collect();
+        let scores = evaluator.evaluate(&candidates)?;
+
+        match scores.iter().enumerate().min_by(|a, b| a.1.total_cmp(b.1)) {
+            Some((i, &score)) if score < center_score => {
+                center = candidates[i].clone();
+                center_score = score;
+            }
+            _ => step /= 2.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spicy_parser::netlist_types::Command;
+    use std::f64::consts::PI;
+
+    const DIVIDER: &str =
+        "divider\n.param rtop=1k\nV1 in 0 10\nR1 in out {rtop}\nR2 out 0 1k\n.op\n.end\n";
+
+    fn rtop() -> Vec<OptimizeVariable> {
+        vec![OptimizeVariable {
+            param: "rtop".to_string(),
+            initial: 1e3,
+            min: 100.0,
+            max: 10e3,
+        }]
+    }
+
+    /// squared distance of V(out) from 2.5V, reached for rtop = 3k
+    fn bias_error(engine: &mut SimulationEngine) -> Result<f64, SimulationError> {
+        let op = engine.op()?;
+        Ok((op.voltage("out").unwrap() - 2.5).powi(2))
+    }
+
+    #[test]
+    fn tunes_bias_point() {
+        for (method, threads) in [
+            (OptimizeMethod::NelderMead, 1),
+            (OptimizeMethod::CoordinateSearch, 2),
+        ] {
+            let mut options = ParseOptions::new_with_source("divider.spicy", DIVIDER.to_string());
+            let config = OptimizeConfig {
+                method,
+                threads,
+                ..OptimizeConfig::default()
+            };
+            let result = optimize(
+                &mut options,
+                &rtop(),
+                bias_error,
+                &SimulationConfig::default(),
+                &config,
+            )
+            .expect("optimize");
+
+            assert!(result.converged, "{method:?}: {result:?}");
+            let (name, value) = &result.params[0];
+            assert_eq!(name, "rtop");
+            assert!((value - 3e3).abs() < 1.0, "{method:?}: {value}");
+            assert!(result.evaluations <= config.max_evaluations);
+        }
+    }
+
+    #[test]
+    fn tunes_filter_corner() {
+        let netlist = "rc\n.param c=1u\nV1 in 0 DC 0 AC 1\nR1 in out 1k\nC1 out 0 {c}\n.ac lin 1 1k 2k\n.end\n";
+        let mut options = ParseOptions::new_with_source("rc.spicy", netlist.to_string());
+        let deck = spicy_parser::parse(&mut options).unwrap();
+        let Some(Command::Ac(ac)) = deck.commands.first() else {
+            panic!("expected .ac");
+        };
+
+        // -3dB at 1kHz
+        let corner_error = |engine: &mut SimulationEngine| {
+            let out = engine.node_mapping().node_names_mna_order();
+            let out = out.iter().position(|n| n == "out").unwrap();
+            let (_, re, im) = &engine.ac(ac)?[0];
+            Ok((re[out].hypot(im[out]) - 0.5f64.sqrt()).powi(2))
+        };
+        let variables = [OptimizeVariable {
+            param: "c".to_string(),
+            initial: 1e-6,
+            min: 1e-9,
+            max: 1e-6,
+        }];
+        let result = optimize(
+            &mut options,
+            &variables,
+            corner_error,
+            &SimulationConfig::default(),
+            &OptimizeConfig::default(),
+        )
+        .expect("optimize");
+
+        let expected = 1.0 / (2.0 * PI * 1e3 * 1e3);
+        let c = result.params[0].1;
+        assert!((c - expected).abs() / expected < 1e-3, "{c} vs {expected}");
+    }
+
+    #[test]
+    fn stops_at_evaluation_limit() {
+        let mut options = ParseOptions::new_with_source("divider.spicy", DIVIDER.to_string());
+        let config = OptimizeConfig {
+            max_evaluations: 5,
+            ..OptimizeConfig::default()
+        };
+        let result = optimize(
+            &mut options,
+            &rtop(),
+            bias_error,
+            &SimulationConfig::default(),
+            &config,
+        )
+        .unwrap();
+        assert!(!result.converged);
+        assert!(result.evaluations >= 5);
+    }
+
+    #[test]
+    fn rejects_bad_variables() {
+        let mut options = ParseOptions::new_with_source("divider.spicy", DIVIDER.to_string());
+        let mut variables = rtop();
+        variables[0].max = variables[0].min;
+        let err = optimize(
+            &mut options,
+            &variables,
+            bias_error,
+            &SimulationConfig::default(),
+            &OptimizeConfig::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "optimization variable 'rtop' needs min < max"
+        );
+
+        variables[0].param = "rbottom".to_string();
+        variables[0].max = 10e3;
+        let err = optimize(
+            &mut options,
+            &variables,
+            bias_error,
+            &SimulationConfig::default(),
+            &OptimizeConfig::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "no top-level .param named 'rbottom'");
+    }
+}
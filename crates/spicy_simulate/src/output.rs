@@ -0,0 +1,345 @@
+//! Which vectors an analysis outputs, from the deck's `.print`/`.plot` lines, which ones a
+//! transient keeps with `.save`, and the stdout tables of `.print`.
+
+use std::f64::consts::PI;
+use std::io::{self, Write};
+
+use ndarray::Array1;
+use spicy_parser::{
+    instance_parser::Deck,
+    netlist_types::{AnalysisType, MeasureEvent, MeasureKind, OutputKind, OutputVector},
+};
+
+use crate::OperatingPointResult;
+
+/// One output vector: its raw-file name and type, and its index in the samples of an analysis.
+/// That is the solution (node voltages, branch currents, then the device currents of
+/// `.options savecurrents`), or only the vectors of a transient that `.save` keeps.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Trace {
+    pub name: String,
+    pub kind: &'static str,
+    pub index: usize,
+}
+
+/// Traces named by the `.print`/`.plot` lines of `analysis` whose kind is in `kinds`, in
+/// order of appearance. `None` when there are no such lines.
+fn requested_traces(
+    deck: &Deck,
+    analysis: AnalysisType,
+    kinds: &[OutputKind],
+) -> Option<Vec<Trace>> {
+    let mut specs = deck
+        .outputs
+        .iter()
+        .filter(|spec| spec.analysis == analysis && kinds.contains(&spec.kind))
+        .peekable();
+    specs.peek()?;
+
+    let mut traces: Vec<Trace> = Vec::new();
+    for vector in specs.flat_map(|spec| &spec.vectors) {
+        let trace = solution_trace(deck, vector);
+        if !traces.contains(&trace) {
+            traces.push(trace);
+        }
+    }
+    Some(traces)
+}
+
+/// The trace of a vector named in the deck, in the samples of `analysis`.
+pub(crate) fn vector_trace(deck: &Deck, analysis: AnalysisType, vector: &OutputVector) -> Trace {
+    let traces = in_samples(deck, analysis, vec![solution_trace(deck, vector)]);
+    // the vectors a transient reads are always kept
+    traces.into_iter().next().expect("kept vector")
+}
+
+/// The trace of a vector named in the deck, in the solution.
+fn solution_trace(deck: &Deck, vector: &OutputVector) -> Trace {
+    let node_names = deck.node_mapping.node_names_mna_order();
+    // the parser only keeps names of the deck
+    match vector {
+        OutputVector::Voltage(name) => Trace {
+            name: format!("V({name})"),
+            kind: "voltage",
+            index: node_names
+                .iter()
+                .position(|n| n == name)
+                .expect("known node"),
+        },
+        OutputVector::Current(name) => Trace {
+            name: format!("I({name})"),
+            kind: "device_current",
+            index: node_names.len()
+                + deck
+                    .node_mapping
+                    .branch_names_mna_order()
+                    .iter()
+                    .position(|b| b == name)
+                    .expect("known branch"),
+        },
+    }
+}
+
+/// The vectors `.meas` reads.
+fn measure_vectors(kind: &MeasureKind) -> Vec<&OutputVector> {
+    fn event(event: &MeasureEvent) -> Option<&OutputVector> {
+        match event {
+            MeasureEvent::Crossing { vector, .. } => Some(vector),
+            MeasureEvent::At(_) => None,
+        }
+    }
+    match kind {
+        MeasureKind::TrigTarg { trig, targ } => {
+            event(trig).into_iter().chain(event(targ)).collect()
+        }
+        MeasureKind::When(when) => event(when).into_iter().collect(),
+        MeasureKind::FindAt { vector, .. } | MeasureKind::Function { vector, .. } => vec![vector],
+        MeasureKind::Response { output, input, .. } => {
+            std::iter::once(output).chain(input).collect()
+        }
+    }
+}
+
+/// Positions in the solution of the vectors a transient analysis keeps, ascending: those of the
+/// `.save` lines and the ones its `.print`, `.plot`, `.meas` and `.four` lines read. `None`
+/// without `.save`, or with `.save all`, when every vector is kept.
+pub(crate) fn transient_saves(deck: &Deck) -> Option<Vec<usize>> {
+    if deck.saves.is_empty() || deck.saves.iter().any(|save| save.all) {
+        return None;
+    }
+    let saves = deck.saves.iter().flat_map(|save| &save.vectors);
+    let outputs = deck
+        .outputs
+        .iter()
+        .filter(|spec| spec.analysis == AnalysisType::Tran)
+        .flat_map(|spec| &spec.vectors);
+    let measures = deck
+        .measures
+        .iter()
+        .filter(|measure| measure.analysis == AnalysisType::Tran)
+        .flat_map(|measure| measure_vectors(&measure.kind));
+    let fourier = deck.fourier.iter().flat_map(|four| &four.vectors);
+
+    let mut kept: Vec<usize> = saves
+        .chain(outputs)
+        .chain(measures)
+        .chain(fourier)
+        .map(|vector| solution_trace(deck, vector).index)
+        .collect();
+    kept.sort_unstable();
+    kept.dedup();
+    Some(kept)
+}
+
+/// `traces` of the solution as they index the samples of `analysis`. The vectors a transient
+/// does not keep with `.save` are left out.
+fn in_samples(deck: &Deck, analysis: AnalysisType, traces: Vec<Trace>) -> Vec<Trace> {
+    let saves = match analysis {
+        AnalysisType::Tran => transient_saves(deck),
+        _ => None,
+    };
+    let Some(saves) = saves else {
+        return traces;
+    };
+    traces
+        .into_iter()
+        .filter_map(|mut trace| {
+            trace.index = saves.binary_search(&trace.index).ok()?;
+            Some(trace)
+        })
+        .collect()
+}
+
+/// The resistors, capacitors and diodes whose current `.options savecurrents` saves, in the
+/// order their currents follow the branch currents of a solution. Empty without the option.
+pub(crate) fn device_current_names(deck: &Deck) -> Vec<String> {
+    if !deck.options.save_currents {
+        return Vec::new();
+    }
+    let devices = &deck.devices;
+    let resistors = devices.resistors.iter().map(|r| &r.name);
+    let capacitors = devices.capacitors.iter().map(|c| &c.name);
+    let diodes = devices.diodes.iter().map(|d| &d.name);
+    resistors.chain(capacitors).chain(diodes).cloned().collect()
+}
+
+/// Every node voltage and branch current, in MNA order, then the saved device currents.
+fn all_traces(deck: &Deck) -> Vec<Trace> {
+    let node_names = deck.node_mapping.node_names_mna_order();
+    let branch_names = deck.node_mapping.branch_names_mna_order();
+    let device_names = device_current_names(deck);
+    let voltages = node_names.iter().map(|n| (format!("V({n})"), "voltage"));
+    let currents = branch_names
+        .iter()
+        .chain(&device_names)
+        .map(|b| (format!("I({b})"), "device_current"));
+    voltages
+        .chain(currents)
+        .enumerate()
+        .map(|(index, (name, kind))| Trace { name, kind, index })
+        .collect()
+}
+
+/// The vectors `analysis` saves: those of its `.print` and `.plot` lines, or all of them (all
+/// those `.save` keeps for a transient).
+pub(crate) fn saved_traces(deck: &Deck, analysis: AnalysisType) -> Vec<Trace> {
+    let traces = requested_traces(deck, analysis, &[OutputKind::Print, OutputKind::Plot])
+        .unwrap_or_else(|| all_traces(deck));
+    in_samples(deck, analysis, traces)
+}
+
+/// The vectors `analysis` prints to stdout, `None` without a `.print` for it.
+pub(crate) fn printed_traces(deck: &Deck, analysis: AnalysisType) -> Option<Vec<Trace>> {
+    requested_traces(deck, analysis, &[OutputKind::Print])
+        .map(|traces| in_samples(deck, analysis, traces))
+}
+
+/// Node voltages followed by branch (and saved device) currents, the layout `Trace::index`
+/// refers to.
+pub(crate) fn op_solution(op: &OperatingPointResult) -> Vec<f64> {
+    op.voltages
+        .iter()
+        .chain(&op.currents)
+        .map(|(_, value)| *value)
+        .collect()
+}
+
+/// Write a table with an optional sweep column followed by one column per trace.
+/// Each row is the sweep value and the full MNA solution at that point.
+pub(crate) fn write_table<'a>(
+    mut w: impl Write,
+    x_name: Option<&str>,
+    traces: &[Trace],
+    rows: impl IntoIterator<Item = (Option<f64>, &'a [f64])>,
+) -> io::Result<()> {
+    let header = x_name
+        .into_iter()
+        .chain(traces.iter().map(|t| t.name.as_str()));
+    for name in header {
+        write!(w, "{name:>14}")?;
+    }
+    writeln!(w)?;
+
+    for (x, solution) in rows {
+        let values = x
+            .into_iter()
+            .chain(traces.iter().map(|t| solution[t.index]));
+        for value in values {
+            write!(w, "{value:>14.6e}")?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// Write an AC table: the frequency, then magnitude and phase (degrees) of every trace.
+pub(crate) fn write_ac_table(
+    mut w: impl Write,
+    traces: &[Trace],
+    ac: &[(f64, Array1<f64>, Array1<f64>)],
+) -> io::Result<()> {
+    write!(w, "{:>14}", "frequency")?;
+    for t in traces {
+        write!(w, "{:>14}{:>14}", format!("mag({})", t.name), "phase")?;
+    }
+    writeln!(w)?;
+
+    for (f, re, im) in ac {
+        write!(w, "{f:>14.6e}")?;
+        for t in traces {
+            let (re, im) = (re[t.index], im[t.index]);
+            let phase = im.atan2(re) * 180.0 / PI;
+            write!(w, "{:>14.6e}{:>14.3}", re.hypot(im), phase)?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::parse_netlist;
+
+    const DIVIDER: &str = "divider\nV1 in 0 1\nR1 in out 1k\nR2 out 0 1k\nL1 out 0 1m\n";
+
+    #[test]
+    fn print_and_plot_select_traces() {
+        let deck = parse_netlist(&format!(
+            "{DIVIDER}.print tran v(OUT) i(v1)\n.plot tran v(out) v(in)\n.print dc v(in)\n.end\n"
+        ));
+
+        let saved: Vec<_> = saved_traces(&deck, AnalysisType::Tran)
+            .into_iter()
+            .map(|t| (t.name, t.index))
+            .collect();
+        assert_eq!(
+            saved,
+            vec![
+                ("V(out)".to_string(), 1),
+                ("I(V1)".to_string(), 2),
+                ("V(in)".to_string(), 0)
+            ]
+        );
+
+        let printed = printed_traces(&deck, AnalysisType::Tran).expect(".print tran");
+        assert_eq!(printed.len(), 2);
+        // no .print op: nothing printed, everything saved
+        assert!(printed_traces(&deck, AnalysisType::Op).is_none());
+        assert_eq!(saved_traces(&deck, AnalysisType::Op).len(), 4);
+    }
+
+    #[test]
+    fn save_limits_the_transient_traces() {
+        let deck = parse_netlist(&format!(
+            "{DIVIDER}.save v(out)\n.print tran i(v1)\n.four 1k v(in)\n.end\n"
+        ));
+        // the .print and .four vectors are kept with the saved one, I(L1) is not
+        assert_eq!(transient_saves(&deck), Some(vec![0, 1, 2]));
+
+        let printed: Vec<_> = printed_traces(&deck, AnalysisType::Tran)
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.name, t.index))
+            .collect();
+        assert_eq!(printed, [("I(V1)".to_string(), 2)]);
+        let four = vector_trace(
+            &deck,
+            AnalysisType::Tran,
+            &OutputVector::Voltage("out".to_string()),
+        );
+        assert_eq!(four.index, 1);
+        // the other analyses keep everything
+        assert_eq!(saved_traces(&deck, AnalysisType::Op).len(), 4);
+
+        let deck = parse_netlist(&format!("{DIVIDER}.save v(out)\n.end\n"));
+        let saved: Vec<_> = saved_traces(&deck, AnalysisType::Tran)
+            .into_iter()
+            .map(|t| (t.name, t.index))
+            .collect();
+        assert_eq!(saved, [("V(out)".to_string(), 0)]);
+    }
+
+    #[test]
+    fn table_has_a_column_per_trace() {
+        let deck = parse_netlist(&format!("{DIVIDER}.print dc v(out)\n.end\n"));
+        let traces = printed_traces(&deck, AnalysisType::Dc).unwrap();
+        let mut out = Vec::new();
+        let rows = [(1.0, [1.0, 0.5, -5e-4, 0.0]), (2.0, [2.0, 1.0, -1e-3, 0.0])];
+        write_table(
+            &mut out,
+            Some("V1"),
+            &traces,
+            rows.iter().map(|(x, solution)| (Some(*x), &solution[..])),
+        )
+        .unwrap();
+        let table = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = table
+            .lines()
+            .map(str::split_whitespace)
+            .map(Iterator::collect::<Vec<_>>)
+            .collect();
+        assert_eq!(lines[0], ["V1", "V(out)"]);
+        assert_eq!(lines[2], ["2.000000e0", "1.000000e0"]);
+    }
+}
@@ -0,0 +1,141 @@
+//! Power absorbed by every device over a transient analysis: the waveform `P(R1)` of every
+//! device and the total the circuit dissipates, evaluated from the saved solution like the
+//! [`OpReport`] of an operating point.
+//!
+//! [`OpReport`]: crate::OpReport
+
+use spicy_parser::{instance_parser::Deck, netlist_types::TranCommand};
+
+use crate::devices::Devices;
+use crate::op_report::{device_points, source_count};
+use crate::results::{Unit, Waveform, position};
+use crate::{SimulationConfig, TransientResult};
+
+/// Power absorbed (W) by every device at every time point of a transient analysis, negative
+/// for a source that delivers power. Capacitors and inductors only store energy and are not
+/// listed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransientPower {
+    pub times: Vec<f64>,
+    /// The devices, in the order of the [`OpReport`](crate::OpReport) devices.
+    pub devices: Vec<String>,
+    /// Per time point, the power of every device.
+    pub samples: Vec<Vec<f64>>,
+    /// Per time point, the power dissipated by the devices other than the sources.
+    pub total: Vec<f64>,
+}
+
+impl TransientPower {
+    /// Evaluate the devices of `deck` at every time point of `result`, the transient analysis
+    /// of `cmd`, at the temperature of `sim_config`. `result` must keep every vector: the
+    /// deck has no `.save`. The self-heating devices are heated again along the points.
+    pub fn new(
+        deck: &Deck,
+        cmd: &TranCommand,
+        result: &TransientResult,
+        sim_config: &SimulationConfig,
+    ) -> Self {
+        let devices = Devices::from_deck(deck, sim_config);
+        let (tstep, tstop) = (cmd.tstep.get_value(), cmd.tstop.get_value());
+
+        let mut names = Vec::new();
+        let mut samples = Vec::with_capacity(result.times.len());
+        let mut total = Vec::with_capacity(result.times.len());
+        let mut t_prev = None;
+        for (&t, x) in result.times.iter().zip(&result.samples) {
+            // the analysis starts at the operating point, or at ambient with UIC; the output
+            // of one with a tstart starts later
+            if t_prev.is_some() || !cmd.uic || t > 0.0 {
+                let step = t_prev.map(|t_prev| t - t_prev);
+                devices.settle_heat(&deck.node_mapping, x, step);
+            }
+            t_prev = Some(t);
+            let points = device_points(&devices, &deck.node_mapping, x, t, tstep, tstop);
+            if names.is_empty() {
+                names = points.iter().map(|point| point.name.clone()).collect();
+            }
+            let powers: Vec<f64> = points
+                .iter()
+                .map(|point| point.get("p").unwrap_or_default())
+                .collect();
+            let loads = powers.len() - source_count(&devices);
+            total.push(powers[..loads].iter().sum());
+            samples.push(powers);
+        }
+
+        Self {
+            times: result.times.clone(),
+            devices: names,
+            samples,
+            total,
+        }
+    }
+
+    /// Power waveform of the device `name`, `P(name)`.
+    pub fn power(&self, name: &str) -> Option<Waveform<'_>> {
+        let index = position(self.devices.iter(), name)?;
+        Some(Waveform {
+            name: &self.devices[index],
+            unit: Unit::Watt,
+            x: self.times.clone(),
+            y: self.samples.iter().map(|s| s[index]).collect(),
+        })
+    }
+
+    /// Power dissipated by the whole circuit over time.
+    pub fn total_power(&self) -> Waveform<'_> {
+        Waveform {
+            name: "total",
+            unit: Unit::Watt,
+            x: self.times.clone(),
+            y: self.total.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trans::simulate_trans;
+    use spicy_parser::{ParseOptions, netlist_types::Command, parse};
+
+    #[test]
+    fn sources_deliver_what_the_resistors_dissipate() {
+        let netlist = "divider
+V1 in 0 SIN(0 2 1k)
+R1 in out 1k
+R2 out 0 3k
+I1 out 0 DC 1m
+.tran 10u 2m
+.end
+";
+        let mut options = ParseOptions::new_with_source("power.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+        let config = SimulationConfig::default();
+        let result = simulate_trans(&deck, tran, &config).expect("simulate_trans");
+        let power = TransientPower::new(&deck, tran, &result, &config);
+
+        assert_eq!(power.devices, ["R1", "R2", "V1", "I1"]);
+        for (sample, total) in power.samples.iter().zip(&power.total) {
+            let sum: f64 = sample.iter().sum();
+            assert!(sum.abs() < 1e-9, "{sample:?}");
+            assert!((total - sample[0] - sample[1]).abs() < 1e-12);
+        }
+
+        // P(R2) = V(out)^2 / R2
+        let out = result.voltage("out").unwrap();
+        let r2 = power.power("r2").unwrap();
+        assert_eq!(r2.unit, Unit::Watt);
+        for (v, p) in out.y.iter().zip(&r2.y) {
+            assert!((p - v * v / 3e3).abs() < 1e-12, "{p} vs {v}");
+        }
+        // with the sine at its peak both sources deliver power
+        let peak = power.total_power().at(0.25e-3).unwrap();
+        assert!(peak > 1e-3, "{peak}");
+        assert!(power.power("I1").unwrap().at(0.25e-3).unwrap() < 0.0);
+        assert!(power.power("missing").is_none());
+    }
+}
@@ -0,0 +1,419 @@
+//! Reads SPICE raw files back: the ones spicy writes, and ngspice's or LTspice's.
+//!
+//! A file is a list of plots, each a header naming its variables and then the values of every
+//! point, as `Values:` text or as `Binary:` little-endian floats. Binary values are f64 (ngspice),
+//! or f32 after the first variable of a real plot like LTspice and spicy write them; the header
+//! does not say which, so the layout that ends where the plot does wins.
+
+use std::path::Path;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RawReadError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("line {line}: {message}")]
+    Header { line: usize, message: String },
+
+    #[error("plot '{plotname}': {message}")]
+    Data { plotname: String, message: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawVariable {
+    pub name: String,
+    /// e.g. `time`, `voltage`, `current`
+    pub kind: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawPlot {
+    pub title: String,
+    pub plotname: String,
+    pub complex: bool,
+    pub variables: Vec<RawVariable>,
+    /// The real part of every variable, point after point.
+    pub real: Vec<Vec<f64>>,
+    /// The imaginary part of every variable of a complex plot; empty for a real plot.
+    pub imaginary: Vec<Vec<f64>>,
+}
+
+impl RawPlot {
+    /// Index of the variable `name`, ignoring case. A node voltage is found by its node name
+    /// too, whether the file calls it `v(out)` (ngspice) or `V(out)` (spicy) or `out`.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        let find = |name: &str| {
+            self.variables
+                .iter()
+                .position(|v| v.name.eq_ignore_ascii_case(name))
+        };
+        find(name)
+            .or_else(|| find(&format!("v({name})")))
+            .or_else(|| {
+                let node = name
+                    .strip_prefix("v(")
+                    .or_else(|| name.strip_prefix("V("))?
+                    .strip_suffix(')')?;
+                find(node)
+            })
+    }
+
+    pub fn real(&self, name: &str) -> Option<&[f64]> {
+        self.position(name).map(|index| self.real[index].as_slice())
+    }
+
+    pub fn imaginary(&self, name: &str) -> Option<&[f64]> {
+        let index = self.position(name)?;
+        self.imaginary.get(index).map(Vec::as_slice)
+    }
+
+    pub fn points(&self) -> usize {
+        self.real.first().map_or(0, Vec::len)
+    }
+}
+
+pub fn read_raw_file(path: &Path) -> Result<Vec<RawPlot>, RawReadError> {
+    read_raw(&std::fs::read(path)?)
+}
+
+/// Every plot of the raw file `bytes`.
+pub fn read_raw(bytes: &[u8]) -> Result<Vec<RawPlot>, RawReadError> {
+    let mut reader = Reader {
+        bytes,
+        at: 0,
+        line: 0,
+    };
+    let mut plots = Vec::new();
+    while reader.skip_blank_lines() {
+        plots.push(reader.plot()?);
+    }
+    Ok(plots)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+    line: usize,
+}
+
+/// The byte sizes of the variables of a binary point the plot may have: f64 everywhere, or a
+/// f64 scale and f32 values (real plots) or f64 then complex values (complex plots), or f32
+/// everywhere. A complex value is two f64.
+fn binary_layouts(nvars: usize, complex: bool) -> Vec<Vec<usize>> {
+    let scale_then = |size| {
+        std::iter::once(8)
+            .chain(std::iter::repeat_n(size, nvars.saturating_sub(1)))
+            .collect()
+    };
+    if complex {
+        vec![vec![16; nvars], scale_then(16)]
+    } else {
+        vec![vec![8; nvars], scale_then(4), vec![4; nvars]]
+    }
+}
+
+impl<'a> Reader<'a> {
+    fn header_error(&self, message: impl Into<String>) -> RawReadError {
+        RawReadError::Header {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    /// Skip to the next line with text; false at the end of the file.
+    fn skip_blank_lines(&mut self) -> bool {
+        while self.at < self.bytes.len() {
+            let rest = &self.bytes[self.at..];
+            let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            if !rest[..end].iter().all(u8::is_ascii_whitespace) {
+                return true;
+            }
+            self.at += (end + 1).min(rest.len());
+            self.line += 1;
+        }
+        false
+    }
+
+    fn next_line(&mut self) -> Option<&'a str> {
+        if self.at >= self.bytes.len() {
+            return None;
+        }
+        let rest = &self.bytes[self.at..];
+        let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        self.at += (end + 1).min(rest.len());
+        self.line += 1;
+        std::str::from_utf8(&rest[..end])
+            .ok()
+            .map(|line| line.trim_end_matches('\r'))
+    }
+
+    fn plot(&mut self) -> Result<RawPlot, RawReadError> {
+        let mut plot = RawPlot {
+            title: String::new(),
+            plotname: String::new(),
+            complex: false,
+            variables: Vec::new(),
+            real: Vec::new(),
+            imaginary: Vec::new(),
+        };
+        let mut nvars = None;
+        let mut npoints = None;
+        let binary = loop {
+            let Some(line) = self.next_line() else {
+                return Err(self.header_error("the header ends before the values"));
+            };
+            let Some((key, value)) = line.split_once(':') else {
+                return Err(self.header_error(format!("expected 'key: value', found '{line}'")));
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "title" => plot.title = value.trim_start_matches('*').trim().to_string(),
+                "plotname" => plot.plotname = value.to_string(),
+                "flags" => plot.complex = value.split_whitespace().any(|flag| flag == "complex"),
+                "no. variables" => nvars = Some(self.count(value)?),
+                "no. points" => npoints = Some(self.count(value)?),
+                "variables" => {
+                    let nvars = nvars
+                        .ok_or_else(|| self.header_error("'Variables:' before 'No. Variables:'"))?;
+                    for _ in 0..nvars {
+                        let line = self
+                            .next_line()
+                            .ok_or_else(|| self.header_error("missing variable"))?;
+                        let mut fields = line.split_whitespace().skip(1);
+                        let (Some(name), Some(kind)) = (fields.next(), fields.next()) else {
+                            return Err(self.header_error(format!("bad variable '{line}'")));
+                        };
+                        plot.variables.push(RawVariable {
+                            name: name.to_string(),
+                            kind: kind.to_string(),
+                        });
+                    }
+                }
+                "binary" => break true,
+                "values" => break false,
+                _ => {}
+            }
+        };
+        let nvars = plot.variables.len();
+        let npoints = npoints.ok_or_else(|| self.header_error("missing 'No. Points:'"))?;
+        plot.real = vec![Vec::with_capacity(npoints); nvars];
+        if plot.complex {
+            plot.imaginary = vec![Vec::with_capacity(npoints); nvars];
+        }
+        if binary {
+            self.binary_values(&mut plot, npoints)?;
+        } else {
+            self.ascii_values(&mut plot, npoints)?;
+        }
+        Ok(plot)
+    }
+
+    fn count(&self, value: &str) -> Result<usize, RawReadError> {
+        value
+            .parse()
+            .map_err(|_| self.header_error(format!("expected a count, found '{value}'")))
+    }
+
+    fn ascii_values(&mut self, plot: &mut RawPlot, npoints: usize) -> Result<(), RawReadError> {
+        let data_error = |message: String| RawReadError::Data {
+            plotname: plot.plotname.clone(),
+            message,
+        };
+        let nvars = plot.variables.len();
+        let mut values = Vec::with_capacity(npoints * nvars);
+        while values.len() < npoints * nvars {
+            let Some(line) = self.next_line() else {
+                return Err(data_error(format!(
+                    "{} values of {}",
+                    values.len(),
+                    npoints * nvars
+                )));
+            };
+            let mut tokens = line.split_whitespace();
+            // each point starts with its index
+            if values.len() % nvars == 0 && tokens.clone().count() > 1 {
+                tokens.next();
+            }
+            for token in tokens {
+                let (re, im) = token.split_once(',').unwrap_or((token, "0"));
+                match (re.parse::<f64>(), im.parse::<f64>()) {
+                    (Ok(re), Ok(im)) => values.push((re, im)),
+                    _ => {
+                        return Err(data_error(format!(
+                            "line {}: bad value '{token}'",
+                            self.line
+                        )));
+                    }
+                }
+            }
+        }
+        for (i, (re, im)) in values.into_iter().enumerate() {
+            plot.real[i % nvars].push(re);
+            if plot.complex {
+                plot.imaginary[i % nvars].push(im);
+            }
+        }
+        Ok(())
+    }
+
+    fn binary_values(&mut self, plot: &mut RawPlot, npoints: usize) -> Result<(), RawReadError> {
+        let rest = &self.bytes[self.at..];
+        // the plot ends at the end of the file or at the title of the next one
+        let ends_at = |size: usize| {
+            rest.get(size..).is_some_and(|rest| {
+                let next = rest.iter().position(|b| !b.is_ascii_whitespace());
+                next.is_none_or(|next| rest[next..].starts_with(b"Title:"))
+            })
+        };
+        let layout = binary_layouts(plot.variables.len(), plot.complex)
+            .into_iter()
+            .find(|sizes| ends_at(sizes.iter().sum::<usize>() * npoints))
+            .ok_or_else(|| RawReadError::Data {
+                plotname: plot.plotname.clone(),
+                message: format!(
+                    "{} bytes of data fit no layout of {npoints} points of {} values",
+                    rest.len(),
+                    plot.variables.len()
+                ),
+            })?;
+
+        let mut at = 0;
+        for _ in 0..npoints {
+            for (index, &size) in layout.iter().enumerate() {
+                let value = &rest[at..at + size];
+                let (re, im) = match size {
+                    4 => (f32::from_le_bytes(value.try_into().unwrap()) as f64, 0.0),
+                    8 => (f64::from_le_bytes(value.try_into().unwrap()), 0.0),
+                    _ => (
+                        f64::from_le_bytes(value[..8].try_into().unwrap()),
+                        f64::from_le_bytes(value[8..].try_into().unwrap()),
+                    ),
+                };
+                plot.real[index].push(re);
+                if plot.complex {
+                    plot.imaginary[index].push(im);
+                }
+                at += size;
+            }
+        }
+        self.at += at;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw_writer::write_plots;
+    use crate::{RawFormat, SimulationConfig, simulate};
+    use spicy_parser::{ParseOptions, parse};
+
+    const NGSPICE_ASCII: &str = "Title: * divider
+Date: Thu Oct 15 10:00:00  2026
+Plotname: Operating Point
+Flags: real
+No. Variables: 3
+No. Points: 1
+Variables:
+\t0\tv(in)\tvoltage
+\t1\tv(out)\tvoltage
+\t2\ti(v1)\tcurrent
+Values:
+ 0\t1.000000000000000e+00
+\t6.666666666666667e-01
+\t-3.333333333333333e-04
+
+Title: * divider
+Date: Thu Oct 15 10:00:00  2026
+Plotname: AC Analysis
+Flags: complex
+No. Variables: 2
+No. Points: 2
+Variables:
+\t0\tfrequency\tfrequency grid=3
+\t1\tv(out)\tvoltage
+Values:
+ 0\t1.0e+03,0.0e+00
+\t5.0e-01,-2.5e-01
+ 1\t1.0e+04,0.0e+00
+\t1.0e-01,-3.0e-01
+";
+
+    #[test]
+    fn reads_ngspice_ascii_plots() {
+        let plots = read_raw(NGSPICE_ASCII.as_bytes()).unwrap();
+        assert_eq!(plots.len(), 2);
+        let op = &plots[0];
+        assert_eq!(op.title, "divider");
+        assert_eq!(op.plotname, "Operating Point");
+        assert_eq!(op.real("out"), Some(&[6.666666666666667e-1][..]));
+        assert_eq!(op.real("V(in)"), Some(&[1.0][..]));
+        assert_eq!(op.real("i(V1)"), Some(&[-3.333333333333333e-4][..]));
+        assert_eq!(op.real("nope"), None);
+
+        let ac = &plots[1];
+        assert!(ac.complex);
+        assert_eq!(ac.points(), 2);
+        assert_eq!(ac.real("frequency"), Some(&[1e3, 1e4][..]));
+        assert_eq!(ac.imaginary("out"), Some(&[-0.25, -0.3][..]));
+    }
+
+    #[test]
+    fn reads_ngspice_binary_doubles() {
+        let mut bytes = b"Title: t\nPlotname: Transient Analysis\nFlags: real\nNo. Variables: 2\n\
+            No. Points: 2\nVariables:\n\t0\ttime\ttime\n\t1\tv(out)\tvoltage\nBinary:\n"
+            .to_vec();
+        for value in [0.0f64, 1.5, 1e-3, 2.5] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        let plots = read_raw(&bytes).unwrap();
+        assert_eq!(plots[0].real("time"), Some(&[0.0, 1e-3][..]));
+        assert_eq!(plots[0].real("out"), Some(&[1.5, 2.5][..]));
+    }
+
+    #[test]
+    fn truncated_values_are_an_error() {
+        let text = NGSPICE_ASCII.split("\t-3.3").next().unwrap();
+        let error = read_raw(text.as_bytes()).expect_err("truncated");
+        assert!(matches!(error, RawReadError::Data { .. }), "{error}");
+    }
+
+    /// The raw files spicy writes read back to the values it simulated, in both formats.
+    #[test]
+    fn reads_what_spicy_writes() {
+        let netlist = "rc\nV1 in 0 PULSE(0 1 0 1u) AC 1\nR1 in out 1k\nC1 out 0 1u\n\
+                       .op\n.tran 100u 1m\n.ac dec 2 1k 10k\n.end\n";
+        let parse_deck = || {
+            let mut options = ParseOptions::new_with_source("rc.spicy", netlist.to_string());
+            parse(&mut options).expect("parse")
+        };
+        let deck = parse_deck();
+        let report = simulate(parse_deck(), SimulationConfig::default()).expect("simulate");
+        let op = report.operating_points().next().unwrap();
+        let tran = report.transients().next().unwrap();
+        let ac = report.ac_sweeps().next().unwrap();
+
+        for format in [RawFormat::Binary, RawFormat::Ascii] {
+            let mut raw = Vec::new();
+            write_plots(&mut raw, &deck, &report.analyses, format).expect("write");
+            let plots = read_raw(&raw).unwrap();
+            assert_eq!(plots.len(), 3, "{format:?}");
+
+            let out = plots[0].real("out").unwrap()[0];
+            assert_eq!(out as f32, op.voltage("out").unwrap() as f32);
+
+            assert!(!plots[1].complex);
+            assert_eq!(plots[1].real("time").unwrap(), tran.times.as_slice());
+            let out = tran.voltage("out").unwrap().y;
+            for (read, simulated) in plots[1].real("V(out)").unwrap().iter().zip(&out) {
+                assert!((read - simulated).abs() <= 1e-6, "{read} != {simulated}");
+            }
+
+            assert!(plots[2].complex);
+            assert_eq!(plots[2].points(), ac.len());
+            assert_eq!(plots[2].real("frequency").unwrap()[0], ac[0].0);
+        }
+    }
+}
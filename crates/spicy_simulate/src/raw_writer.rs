@@ -1,15 +1,23 @@
 use std::fs::File;
 use std::io::{BufWriter, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use chrono::Local;
 use spicy_parser::instance_parser::Deck;
+use spicy_parser::netlist_types::{AnalysisType, DcCommand};
 
-use crate::{DcSweepResult, OperatingPointResult, TransientResult};
+use crate::ac::AcSweep;
+use crate::fft::FourierAnalysis;
+use crate::noise::NoiseResult;
+use crate::output::{Trace, op_solution, saved_traces};
+use crate::sparam::SParameters;
+use crate::{
+    AnalysisReport, AnalysisResult, DcSweepResult, OperatingPointResult, RawFormat, TransientResult,
+};
 
 // TODO: kinda vibe coded this so it can definitly be improved
 
-fn sanitize_filename(input: &str) -> String {
+pub(crate) fn sanitize_filename(input: &str) -> String {
     let mut out = String::new();
     for c in input.chars() {
         match c {
@@ -25,26 +33,20 @@ fn sanitize_filename(input: &str) -> String {
     }
 }
 
-fn build_trace_variables_from_names(
-    node_names: &[String],
-    source_names: &[String],
-) -> Vec<(String, String)> {
-    let mut vars = Vec::new();
-    // Node voltages
-    for n in node_names {
-        if n.is_empty() {
-            continue;
-        }
-        vars.push((format!("V({})", n), "voltage".to_string()));
-    }
-    // Source currents
-    for s in source_names {
-        if s.is_empty() {
-            continue;
-        }
-        vars.push((format!("I({})", s), "device_current".to_string()));
+/// `<output_base>.<extension>`, keeping any dots already in the name of `output_base`.
+pub(crate) fn output_file(output_base: &Path, extension: &str) -> PathBuf {
+    let mut path = output_base.as_os_str().to_os_string();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// The plot name of an analysis, with the parameter values of its `.step` point if any.
+fn plotname(analysis: &str, step: Option<&str>) -> String {
+    match step {
+        Some(step) => format!("{analysis} (step {step})"),
+        None => analysis.to_string(),
     }
-    vars
 }
 
 fn write_header(
@@ -69,189 +71,216 @@ fn write_header(
 
 fn write_variables_with_offset(
     mut w: impl Write,
-    variables: &[(String, String)],
+    traces: &[Trace],
     start_index: usize,
 ) -> std::io::Result<()> {
-    for (i, (name, kind)) in variables.iter().enumerate() {
-        writeln!(w, "\t{}\t{}\t{}", start_index + i, name, kind)?;
+    for (i, trace) in traces.iter().enumerate() {
+        writeln!(w, "\t{}\t{}\t{}", start_index + i, trace.name, trace.kind)?;
     }
     Ok(())
 }
 
-fn write_binary_series_real_f32(
-    mut w: impl Write,
-    x_values: &[f64],
-    traces_per_point: &[Vec<f64>],
-) -> std::io::Result<()> {
-    writeln!(w, "Binary:")?;
-    for (idx, &x) in x_values.iter().enumerate() {
-        let _ = idx;
-        w.write_all(&x.to_le_bytes())?;
-        for &v in &traces_per_point[idx] {
-            let f = v as f32;
-            w.write_all(&f.to_le_bytes())?;
+/// One value of a point. In binary, the scale (first variable) is a f64 and the other real
+/// variables are f32 like LTspice writes them; complex values are two f64.
+#[derive(Clone, Copy)]
+enum Sample {
+    Double(f64),
+    Single(f64),
+    Complex(f64, f64),
+}
+
+fn trace_samples<'a>(
+    traces: &'a [Trace],
+    solution: &'a [f64],
+) -> impl Iterator<Item = Sample> + 'a {
+    traces
+        .iter()
+        .map(|trace| Sample::Single(solution[trace.index]))
+}
+
+/// Writes the data section of a plot: `Binary:` followed by the packed little-endian values,
+/// or `Values:` followed by one line per value, each point starting with its index.
+struct DataWriter<W> {
+    writer: W,
+    format: RawFormat,
+    /// every value of a complex plot is written as `re,im` in ASCII
+    complex: bool,
+    point: usize,
+}
+
+impl<W: Write> DataWriter<W> {
+    fn new(mut writer: W, format: RawFormat, complex: bool) -> std::io::Result<Self> {
+        match format {
+            RawFormat::Binary => writeln!(writer, "Binary:")?,
+            RawFormat::Ascii => writeln!(writer, "Values:")?,
         }
+        Ok(Self {
+            writer,
+            format,
+            complex,
+            point: 0,
+        })
+    }
+
+    fn point(&mut self, samples: impl IntoIterator<Item = Sample>) -> std::io::Result<()> {
+        for (i, sample) in samples.into_iter().enumerate() {
+            match self.format {
+                RawFormat::Binary => match sample {
+                    Sample::Double(v) => self.writer.write_all(&v.to_le_bytes())?,
+                    Sample::Single(v) => self.writer.write_all(&(v as f32).to_le_bytes())?,
+                    Sample::Complex(re, im) => {
+                        self.writer.write_all(&re.to_le_bytes())?;
+                        self.writer.write_all(&im.to_le_bytes())?;
+                    }
+                },
+                RawFormat::Ascii => {
+                    if i == 0 {
+                        write!(self.writer, " {}", self.point)?;
+                    }
+                    match sample {
+                        Sample::Double(v) | Sample::Single(v) if self.complex => {
+                            writeln!(self.writer, "\t{v:e},0e0")?
+                        }
+                        Sample::Double(v) | Sample::Single(v) => writeln!(self.writer, "\t{v:e}")?,
+                        Sample::Complex(re, im) => writeln!(self.writer, "\t{re:e},{im:e}")?,
+                    }
+                }
+            }
+        }
+        self.point += 1;
+        Ok(())
     }
-    Ok(())
 }
 
-pub(crate) fn write_transient_raw(
+fn write_transient_plot(
+    mut writer: impl Write,
     deck: &Deck,
     result: &TransientResult,
-    output_base: &str,
-) -> std::io::Result<PathBuf> {
-    let filename = format!("{}.raw", sanitize_filename(output_base));
-    let path = PathBuf::from(filename);
-    let file = File::create(&path)?;
-    let mut writer = BufWriter::new(file);
-
-    let traces = build_trace_variables_from_names(&result.node_names, &result.source_names);
+    step: Option<&str>,
+    format: RawFormat,
+) -> std::io::Result<()> {
+    let traces = saved_traces(deck, AnalysisType::Tran);
     let nvars = 1 + traces.len();
     let npoints = result.times.len();
 
     write_header(
         &mut writer,
         &deck.title,
-        "Transient Analysis",
+        &plotname("Transient Analysis", step),
         "real forward",
         nvars,
         npoints,
     )?;
     writeln!(&mut writer, "\t0\ttime\ttime")?;
     write_variables_with_offset(&mut writer, &traces, 1)?;
-    write_binary_series_real_f32(&mut writer, &result.times, &result.samples)?;
-
-    writer.flush()?;
-    Ok(path)
+    let mut data = DataWriter::new(writer, format, false)?;
+    for (&time, solution) in result.times.iter().zip(&result.samples) {
+        data.point(std::iter::once(Sample::Double(time)).chain(trace_samples(&traces, solution)))?;
+    }
+    Ok(())
 }
 
-pub(crate) fn write_operating_point_raw(
+fn write_operating_point_plot(
+    mut writer: impl Write,
     deck: &Deck,
     op: &OperatingPointResult,
-    output_base: &str,
-) -> std::io::Result<PathBuf> {
-    let filename = format!("{}.raw", sanitize_filename(output_base));
-    let path = PathBuf::from(filename);
-    let file = File::create(&path)?;
-    let mut writer = BufWriter::new(file);
-
-    // Build variable names from the provided result ordering
-    let mut variables: Vec<(String, String)> = Vec::new();
-    for (name, _) in &op.voltages {
-        variables.push((format!("V({})", name), "voltage".to_string()));
-    }
-    for (name, _) in &op.currents {
-        variables.push((format!("I({})", name), "device_current".to_string()));
-    }
-    let nvars = variables.len();
+    step: Option<&str>,
+    format: RawFormat,
+) -> std::io::Result<()> {
+    let traces = saved_traces(deck, AnalysisType::Op);
+    let nvars = traces.len();
 
     // Preamble: OP has no forward flag
     write_header(
         &mut writer,
         &deck.title,
-        "Operation Point",
+        &plotname("Operation Point", step),
         "real",
         nvars,
         1,
     )?;
-    write_variables_with_offset(&mut writer, &variables, 0)?;
-    writeln!(&mut writer, "Binary:")?;
+    write_variables_with_offset(&mut writer, &traces, 0)?;
     // Single point: write f32 for each variable in order
-    for (_, v) in &op.voltages {
-        writer.write_all(&(*v as f32).to_le_bytes())?;
-    }
-    for (_, i) in &op.currents {
-        writer.write_all(&(*i as f32).to_le_bytes())?;
-    }
-    writer.flush()?;
-    Ok(path)
+    let mut data = DataWriter::new(writer, format, false)?;
+    data.point(trace_samples(&traces, &op_solution(op)))
 }
 
-pub(crate) fn write_dc_raw(
-    deck: &Deck,
-    dc: &DcSweepResult,
-    output_base: &str,
-    sweep_name: &str,
-    is_voltage_source: bool,
-) -> std::io::Result<PathBuf> {
-    let filename = format!("{}.raw", sanitize_filename(output_base));
-    let path = PathBuf::from(filename);
-    let file = File::create(&path)?;
-    let mut writer = BufWriter::new(file);
-
-    // Assume non-empty results
-    let (first_op, _) = dc.results.first().expect("dc results not empty");
-    let mut variables: Vec<(String, String)> = Vec::new();
-    // var0: swept value
-    let sweep_var_name = sweep_name.to_string();
-    let sweep_type = if is_voltage_source {
+/// Raw variable type of the swept source `name`.
+fn sweep_type(deck: &Deck, name: &str) -> &'static str {
+    let is_voltage = deck.devices.voltage_sources.iter().any(|v| v.name == name);
+    if is_voltage {
         "voltage"
     } else {
         "device_current"
-    };
+    }
+}
+
+/// A nested sweep writes every curve one after the other, with the second source as the
+/// first trace so readers can split the family.
+fn write_dc_plot(
+    mut writer: impl Write,
+    deck: &Deck,
+    dc: &DcSweepResult,
+    command: &DcCommand,
+    step: Option<&str>,
+    format: RawFormat,
+) -> std::io::Result<()> {
+    let traces = saved_traces(deck, AnalysisType::Dc);
+    let outer = command.src2.as_ref().map(|src2| src2.srcnam.as_str());
+    let offset = 1 + usize::from(outer.is_some());
 
     // Preamble
-    let trace_count = first_op.voltages.len() + first_op.currents.len();
+    let trace_count = traces.len();
     write_header(
         &mut writer,
         &deck.title,
-        "DC transfer characteristic",
+        &plotname("DC transfer characteristic", step),
         "real forward",
-        trace_count + 1,
+        trace_count + offset,
         dc.results.len(),
     )?;
     // index 0
-    writeln!(&mut writer, "\t0\t{}\t{}", sweep_var_name, sweep_type)?;
-    // Then traces
-    for (idx, (name, _)) in first_op.voltages.iter().enumerate() {
-        variables.push((format!("V({})", name), "voltage".to_string()));
-        writeln!(&mut writer, "\t{}\tV({})\tvoltage", idx + 1, name)?;
-    }
-    for (iidx, (name, _)) in first_op.currents.iter().enumerate() {
-        writeln!(
-            &mut writer,
-            "\t{}\tI({})\tdevice_current",
-            first_op.voltages.len() + 1 + iidx,
-            name
-        )?;
+    writeln!(
+        &mut writer,
+        "\t0\t{}\t{}",
+        command.srcnam,
+        sweep_type(deck, &command.srcnam)
+    )?;
+    if let Some(name) = outer {
+        writeln!(&mut writer, "\t1\t{}\t{}", name, sweep_type(deck, name))?;
     }
+    // Then traces
+    write_variables_with_offset(&mut writer, &traces, offset)?;
 
-    // Binary
-    writeln!(&mut writer, "Binary:")?;
-    for (op, sweep) in &dc.results {
-        writer.write_all(&sweep.to_le_bytes())?; // swept value as f64
-        for (_, v) in &op.voltages {
-            writer.write_all(&(*v as f32).to_le_bytes())?;
-        }
-        for (_, i) in &op.currents {
-            writer.write_all(&(*i as f32).to_le_bytes())?;
-        }
+    let mut data = DataWriter::new(writer, format, false)?;
+    let points_per_curve = dc.results.len() / dc.curve_count();
+    for (index, (op, sweep)) in dc.results.iter().enumerate() {
+        let outer = dc.outer_values.get(index / points_per_curve);
+        let solution = op_solution(op);
+        data.point(
+            std::iter::once(Sample::Double(*sweep))
+                .chain(outer.map(|value| Sample::Single(*value)))
+                .chain(trace_samples(&traces, &solution)),
+        )?;
     }
-    writer.flush()?;
-    Ok(path)
+    Ok(())
 }
 
-pub(crate) fn write_ac_raw(
+fn write_ac_plot(
+    mut writer: impl Write,
     deck: &Deck,
-    ac: &Vec<(f64, ndarray::Array1<f64>, ndarray::Array1<f64>)>,
-    output_base: &str,
-) -> std::io::Result<PathBuf> {
-    let filename = format!("{}.raw", sanitize_filename(output_base));
-    let path = PathBuf::from(filename);
-    let file = File::create(&path)?;
-    let mut writer = BufWriter::new(file);
-
-    // Rebuild names
-    let node_names = deck.node_mapping.node_names_mna_order();
-    let source_names = deck.node_mapping.branch_names_mna_order();
-    let traces = build_trace_variables_from_names(&node_names, &source_names);
+    ac: &AcSweep,
+    step: Option<&str>,
+    format: RawFormat,
+) -> std::io::Result<()> {
+    let traces = saved_traces(deck, AnalysisType::Ac);
     let trace_count = traces.len();
 
     // Preamble
     write_header(
         &mut writer,
         &deck.title,
-        "AC Analysis",
+        &plotname("AC Analysis", step),
         "complex forward",
         trace_count + 1,
         ac.len(),
@@ -259,27 +288,355 @@ pub(crate) fn write_ac_raw(
     writeln!(&mut writer, "\t0\tfrequency\tfrequency")?;
     write_variables_with_offset(&mut writer, &traces, 1)?;
 
-    // Binary: per point -> f64 frequency, then for each trace: f64 re, f64 im
-    writeln!(&mut writer, "Binary:")?;
-    let n = node_names.len();
-    let k = source_names.len();
+    // per point -> f64 frequency, then for each trace: f64 re, f64 im
+    let mut data = DataWriter::new(writer, format, true)?;
     for (f, xr, xi) in ac {
-        writer.write_all(&f.to_le_bytes())?;
-        // node voltages
-        for i in 0..n {
-            let re = xr[i];
-            let im = xi[i];
-            writer.write_all(&re.to_le_bytes())?;
-            writer.write_all(&im.to_le_bytes())?;
+        let values = traces
+            .iter()
+            .map(|trace| Sample::Complex(xr[trace.index], xi[trace.index]));
+        data.point(std::iter::once(Sample::Double(*f)).chain(values))?;
+    }
+    Ok(())
+}
+
+/// The spectrum of a `.four` vector, as complex peak amplitudes over frequency.
+fn write_spectrum_plot(
+    mut writer: impl Write,
+    deck: &Deck,
+    fourier: &FourierAnalysis,
+    step: Option<&str>,
+    format: RawFormat,
+) -> std::io::Result<()> {
+    write_header(
+        &mut writer,
+        &deck.title,
+        &plotname(&format!("Spectrum of {}", fourier.name), step),
+        "complex forward",
+        2,
+        fourier.spectrum.len(),
+    )?;
+    let kind = if fourier.name.starts_with("I(") {
+        "device_current"
+    } else {
+        "voltage"
+    };
+    writeln!(&mut writer, "\t0\tfrequency\tfrequency")?;
+    writeln!(&mut writer, "\t1\t{}\t{kind}", fourier.name)?;
+    let mut data = DataWriter::new(writer, format, true)?;
+    for (f, re, im) in &fourier.spectrum {
+        data.point([Sample::Double(*f), Sample::Complex(*re, *im)])?;
+    }
+    Ok(())
+}
+
+/// Two plots like ngspice: the spectral densities over frequency, then the integrated noise.
+fn write_noise_plots(
+    mut writer: impl Write,
+    deck: &Deck,
+    noise: &NoiseResult,
+    step: Option<&str>,
+    format: RawFormat,
+) -> std::io::Result<()> {
+    write_header(
+        &mut writer,
+        &deck.title,
+        &plotname("Noise Spectral Density Curves", step),
+        "real forward",
+        3,
+        noise.frequencies.len(),
+    )?;
+    writeln!(&mut writer, "\t0\tfrequency\tfrequency")?;
+    writeln!(&mut writer, "\t1\tonoise_spectrum\tvoltage")?;
+    writeln!(&mut writer, "\t2\tinoise_spectrum\tvoltage")?;
+    let mut data = DataWriter::new(&mut writer, format, false)?;
+    for ((f, onoise), inoise) in noise
+        .frequencies
+        .iter()
+        .zip(&noise.output_density)
+        .zip(&noise.input_density)
+    {
+        data.point([
+            Sample::Double(*f),
+            Sample::Single(*onoise),
+            Sample::Single(*inoise),
+        ])?;
+    }
+
+    write_header(
+        &mut writer,
+        &deck.title,
+        &plotname("Integrated Noise", step),
+        "real",
+        2,
+        1,
+    )?;
+    writeln!(&mut writer, "\t0\tonoise_total\tvoltage")?;
+    writeln!(&mut writer, "\t1\tinoise_total\tvoltage")?;
+    let mut data = DataWriter::new(writer, format, false)?;
+    data.point([
+        Sample::Single(noise.total_output),
+        Sample::Single(noise.total_input),
+    ])
+}
+
+/// The S matrix over frequency, one complex `S_i_j` vector per pair of ports like ngspice.
+fn write_sp_plot(
+    mut writer: impl Write,
+    deck: &Deck,
+    sp: &SParameters,
+    step: Option<&str>,
+    format: RawFormat,
+) -> std::io::Result<()> {
+    let n = sp.ports.len();
+    write_header(
+        &mut writer,
+        &deck.title,
+        &plotname("SP Analysis", step),
+        "complex forward",
+        n * n + 1,
+        sp.frequencies.len(),
+    )?;
+    writeln!(&mut writer, "\t0\tfrequency\tfrequency")?;
+    for i in 0..n {
+        for j in 0..n {
+            writeln!(
+                &mut writer,
+                "\t{}\t{}\tnotype",
+                1 + i * n + j,
+                SParameters::name(i, j)
+            )?;
         }
-        // source currents
-        for i in 0..k {
-            let re = xr[n + i];
-            let im = xi[n + i];
-            writer.write_all(&re.to_le_bytes())?;
-            writer.write_all(&im.to_le_bytes())?;
+    }
+    let mut data = DataWriter::new(writer, format, true)?;
+    for (f, s) in sp.frequencies.iter().zip(&sp.s) {
+        let values = s.iter().map(|&(re, im)| Sample::Complex(re, im));
+        data.point(std::iter::once(Sample::Double(*f)).chain(values))?;
+    }
+    Ok(())
+}
+
+/// Write the plots of one analysis, one after the other for every `.step` point. A transient
+/// is followed by the spectra of its `.four` vectors.
+pub(crate) fn write_plots(
+    mut writer: impl Write,
+    deck: &Deck,
+    plots: &[AnalysisReport],
+    format: RawFormat,
+) -> std::io::Result<()> {
+    for plot in plots {
+        let w = &mut writer;
+        let step = plot.label.as_deref();
+        match &plot.result {
+            AnalysisResult::Op(op) => write_operating_point_plot(w, deck, op, step, format)?,
+            AnalysisResult::Dc(dc, command) => write_dc_plot(w, deck, dc, command, step, format)?,
+            AnalysisResult::Ac(ac) => write_ac_plot(w, deck, ac, step, format)?,
+            AnalysisResult::Tran(tran) => write_transient_plot(w, deck, tran, step, format)?,
+            AnalysisResult::Noise(noise) => write_noise_plots(w, deck, noise, step, format)?,
+            AnalysisResult::Sp(sp) => write_sp_plot(w, deck, sp, step, format)?,
+        }
+        for fourier in &plot.fourier {
+            write_spectrum_plot(&mut writer, deck, fourier, step, format)?;
         }
     }
+    Ok(())
+}
+
+/// Write the plots of one analysis to `<output_base>.raw`.
+pub(crate) fn write_raw(
+    deck: &Deck,
+    plots: &[AnalysisReport],
+    output_base: &Path,
+    format: RawFormat,
+) -> std::io::Result<PathBuf> {
+    let path = output_file(output_base, "raw");
+    let file = File::create(&path)?;
+    let mut writer = BufWriter::new(file);
+    write_plots(&mut writer, deck, plots, format)?;
     writer.flush()?;
     Ok(path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SimulationConfig, simulate};
+    use spicy_parser::{ParseOptions, parse};
+
+    /// The values of the single plot of `raw`, as (re, im) with im = 0 for real values.
+    /// `sizes` is the byte size of every variable of a binary point.
+    fn read_values(raw: &[u8], sizes: &[usize]) -> Vec<Vec<(f64, f64)>> {
+        let find = |marker: &[u8]| {
+            raw.windows(marker.len())
+                .position(|w| w == marker)
+                .map(|at| at + marker.len())
+        };
+        if let Some(start) = find(b"Values:\n") {
+            let text = std::str::from_utf8(&raw[start..]).unwrap();
+            let mut tokens = text.split_whitespace().peekable();
+            let mut points = Vec::new();
+            while tokens.next().is_some() {
+                let values = (0..sizes.len())
+                    .map(|_| {
+                        let token = tokens.next().unwrap();
+                        match token.split_once(',') {
+                            Some((re, im)) => (re.parse().unwrap(), im.parse().unwrap()),
+                            None => (token.parse().unwrap(), 0.0),
+                        }
+                    })
+                    .collect();
+                points.push(values);
+            }
+            return points;
+        }
+
+        let mut data = &raw[find(b"Binary:\n").expect("data section")..];
+        let mut points = Vec::new();
+        while !data.is_empty() {
+            let mut values = Vec::new();
+            for &size in sizes {
+                let (value, rest) = data.split_at(size);
+                values.push(match size {
+                    4 => (f32::from_le_bytes(value.try_into().unwrap()) as f64, 0.0),
+                    8 => (f64::from_le_bytes(value.try_into().unwrap()), 0.0),
+                    _ => (
+                        f64::from_le_bytes(value[..8].try_into().unwrap()),
+                        f64::from_le_bytes(value[8..].try_into().unwrap()),
+                    ),
+                });
+                data = rest;
+            }
+            points.push(values);
+        }
+        points
+    }
+
+    fn write(deck: &Deck, plot: &AnalysisReport, format: RawFormat) -> Vec<u8> {
+        let mut raw = Vec::new();
+        write_plots(&mut raw, deck, std::slice::from_ref(plot), format).expect("write");
+        raw
+    }
+
+    #[test]
+    fn ascii_and_binary_hold_the_same_values() {
+        let netlist = "raw formats
+V1 in 0 DC 1 AC 1 0
+R1 in out 1k
+C1 out 0 1u
+.tran 100u 1m
+.ac dec 5 1 1e5
+.end
+";
+        let parse_deck = || {
+            let mut options = ParseOptions::new_with_source("raw.spicy", netlist.to_string());
+            parse(&mut options).expect("parse")
+        };
+        let deck = parse_deck();
+        let report = simulate(parse_deck(), SimulationConfig::default()).expect("simulate");
+
+        // time, v(in), v(out), i(V1)
+        let tran = &report.analyses[0];
+        let ascii = write(&deck, tran, RawFormat::Ascii);
+        assert!(String::from_utf8_lossy(&ascii).contains("Values:\n 0\t0e0\n"));
+        let from_ascii = read_values(&ascii, &[8; 4]);
+        let from_binary = read_values(&write(&deck, tran, RawFormat::Binary), &[8, 4, 4, 4]);
+        let AnalysisResult::Tran(result) = &tran.result else {
+            panic!("expected a transient");
+        };
+        assert_eq!(from_ascii.len(), result.times.len());
+        assert_eq!(from_binary.len(), result.times.len());
+        for ((ascii, binary), (time, sample)) in from_ascii
+            .iter()
+            .zip(&from_binary)
+            .zip(result.times.iter().zip(&result.samples))
+        {
+            // ASCII round-trips exactly, binary values are f32 past the scale
+            assert_eq!(ascii[0].0, *time);
+            assert_eq!(binary[0].0, *time);
+            for (i, value) in sample.iter().enumerate() {
+                assert_eq!(ascii[i + 1].0, *value);
+                assert!((binary[i + 1].0 - value).abs() <= 1e-6 * value.abs().max(1e-6));
+            }
+        }
+
+        // frequency, then complex v(in), v(out), i(V1) in full precision
+        let ac = &report.analyses[1];
+        let from_ascii = read_values(&write(&deck, ac, RawFormat::Ascii), &[8; 4]);
+        let from_binary = read_values(&write(&deck, ac, RawFormat::Binary), &[8, 16, 16, 16]);
+        assert_eq!(from_ascii.len(), 26);
+        assert_eq!(from_ascii, from_binary);
+    }
+
+    #[test]
+    fn subcircuit_nodes_keep_their_instance_path() {
+        let netlist = "divider
+.subckt half a b
+R1 a mid 1k
+R2 mid b 1k
+.ends
+V1 in 0 2
+X1 in 0 half
+X2 in X1.mid half
+.print op V(X2.mid)
+.op
+.end
+";
+        let parse_deck = || {
+            let mut options = ParseOptions::new_with_source("raw.spicy", netlist.to_string());
+            parse(&mut options).expect("parse")
+        };
+        let deck = parse_deck();
+        let report = simulate(parse_deck(), SimulationConfig::default()).expect("simulate");
+
+        let raw = write(&deck, &report.analyses[0], RawFormat::Ascii);
+        let raw = String::from_utf8_lossy(&raw);
+        // `.print` picks the internal node of the second instance
+        assert!(
+            raw.contains("Variables:\n\t0\tV(X2.mid)\tvoltage\nValues:"),
+            "{raw}"
+        );
+
+        let AnalysisResult::Op(op) = &report.analyses[0].result else {
+            panic!("expected an operating point");
+        };
+        let probe = spicy_parser::hierarchical_name(&["X2"], "mid");
+        assert!((op.voltage(&probe).unwrap() - 1.6).abs() < 1e-9);
+        assert_eq!(op.voltage("x1.MID"), op.voltage("X1.mid"));
+    }
+
+    #[test]
+    fn saved_device_currents_follow_the_branch_currents() {
+        let netlist = "rc
+V1 in 0 DC 1 AC 1 0
+R1 in out 1k
+C1 out 0 1u
+.options savecurrents
+.tran 100u 1m
+.ac dec 5 1 1e5
+.end
+";
+        let parse_deck = || {
+            let mut options = ParseOptions::new_with_source("raw.spicy", netlist.to_string());
+            parse(&mut options).expect("parse")
+        };
+        let deck = parse_deck();
+        let report = simulate(parse_deck(), SimulationConfig::default()).expect("simulate");
+
+        let raw = write(&deck, &report.analyses[0], RawFormat::Ascii);
+        let raw = String::from_utf8_lossy(&raw);
+        assert!(
+            raw.contains("\t3\tI(V1)\tdevice_current\n\t4\tI(R1)\tdevice_current\n\t5\tI(C1)"),
+            "{raw}"
+        );
+
+        // frequency, v(in), v(out), i(V1), i(R1), i(C1): the series current is the same
+        let ac = write(&deck, &report.analyses[1], RawFormat::Ascii);
+        for point in read_values(&ac, &[8; 6]) {
+            let (r, c) = (point[4], point[5]);
+            assert!(
+                (r.0 - c.0).abs() < 1e-12 && (r.1 - c.1).abs() < 1e-12,
+                "{point:?}"
+            );
+            assert!((r.0 + point[3].0).abs() < 1e-12, "{point:?}");
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! The results of a whole [`crate::simulate`] run.
+//!
+//! Every analysis of the deck runs once per `.step` point and `.temp` temperature; each run is
+//! kept as an [`AnalysisReport`] with its label, how long it took and its solver statistics.
+
+use std::time::Duration;
+
+use spicy_parser::netlist_types::DcCommand;
+
+use crate::ac::AcSweep;
+use crate::dc::{DcSweepResult, OperatingPointResult};
+use crate::fft::FourierAnalysis;
+use crate::matrix::SolverStats;
+use crate::measure::Measurement;
+use crate::noise::NoiseResult;
+use crate::sparam::SParameters;
+use crate::trans::TransientResult;
+use crate::warnings::SimulationWarning;
+
+/// The result of one analysis.
+#[derive(Debug, Clone)]
+pub enum AnalysisResult {
+    Op(OperatingPointResult),
+    Dc(DcSweepResult, DcCommand),
+    Ac(AcSweep),
+    Tran(TransientResult),
+    Noise(NoiseResult),
+    Sp(SParameters),
+}
+
+impl AnalysisResult {
+    pub fn warnings(&self) -> Vec<SimulationWarning> {
+        match self {
+            AnalysisResult::Op(op) => op.warnings.clone(),
+            AnalysisResult::Dc(dc, _) => dc.warnings().cloned().collect(),
+            AnalysisResult::Tran(tran) => tran.warnings.clone(),
+            AnalysisResult::Ac(_) | AnalysisResult::Noise(_) | AnalysisResult::Sp(_) => Vec::new(),
+        }
+    }
+
+    /// Solver statistics of the analysis, summed over the points of a DC sweep.
+    /// AC, noise and S-parameter analyses do not record any.
+    pub fn solver_stats(&self) -> Option<SolverStats> {
+        match self {
+            AnalysisResult::Op(op) => Some(op.solver_stats),
+            AnalysisResult::Dc(dc, _) => Some(
+                dc.results
+                    .iter()
+                    .fold(SolverStats::default(), |total, (op, _)| {
+                        total.merge(&op.solver_stats)
+                    }),
+            ),
+            AnalysisResult::Tran(tran) => Some(tran.solver_stats),
+            AnalysisResult::Ac(_) | AnalysisResult::Noise(_) | AnalysisResult::Sp(_) => None,
+        }
+    }
+
+    /// Newton iterations of the hardest point: of the time point that took the most for a
+    /// transient, and counted as the factorizations (one per iteration) of an operating point.
+    /// AC, noise and S-parameter analyses are linear.
+    pub fn max_newton_iterations(&self) -> Option<usize> {
+        let op_iterations = |op: &OperatingPointResult| {
+            op.solver_stats.factorizations + op.solver_stats.refactorizations
+        };
+        match self {
+            AnalysisResult::Op(op) => Some(op_iterations(op)),
+            AnalysisResult::Dc(dc, _) => dc.results.iter().map(|(op, _)| op_iterations(op)).max(),
+            AnalysisResult::Tran(tran) => tran.newton_iterations.iter().copied().max(),
+            AnalysisResult::Ac(_) | AnalysisResult::Noise(_) | AnalysisResult::Sp(_) => None,
+        }
+    }
+
+    /// Suffix of the raw file the analysis is written to.
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            AnalysisResult::Op(_) => "op",
+            AnalysisResult::Dc(..) => "dc",
+            AnalysisResult::Ac(_) => "ac",
+            AnalysisResult::Tran(_) => "tran",
+            AnalysisResult::Noise(_) => "noise",
+            AnalysisResult::Sp(_) => "sp",
+        }
+    }
+}
+
+/// One run of an analysis.
+#[derive(Debug, Clone)]
+pub struct AnalysisReport {
+    /// Parameter values of the `.step` point (and the temperature of a `.temp` sweep);
+    /// `None` for a deck without sweeps.
+    pub label: Option<String>,
+    pub result: AnalysisResult,
+    /// Wall-clock time of the analysis.
+    pub duration: Duration,
+    /// The deck's `.meas` lines for this kind of analysis, evaluated on the result.
+    pub measurements: Vec<Measurement>,
+    /// The deck's `.four` analyses of a transient result.
+    pub fourier: Vec<FourierAnalysis>,
+}
+
+/// Every analysis run by [`crate::simulate`], analysis by analysis in deck order and, within
+/// an analysis, point by point along the sweeps.
+#[derive(Debug, Clone, Default)]
+pub struct SimulationReport {
+    pub analyses: Vec<AnalysisReport>,
+    /// The simulation was cancelled: the last analysis may be partial and the ones after it
+    /// are missing.
+    pub cancelled: bool,
+}
+
+impl SimulationReport {
+    /// The warnings of every analysis.
+    pub fn warnings(&self) -> Vec<SimulationWarning> {
+        self.analyses
+            .iter()
+            .flat_map(|analysis| analysis.result.warnings())
+            .collect()
+    }
+
+    /// Total wall-clock time of the analyses.
+    pub fn duration(&self) -> Duration {
+        self.analyses.iter().map(|analysis| analysis.duration).sum()
+    }
+
+    /// The measurements of every analysis.
+    pub fn measurements(&self) -> impl Iterator<Item = &Measurement> {
+        self.analyses
+            .iter()
+            .flat_map(|analysis| &analysis.measurements)
+    }
+
+    pub fn operating_points(&self) -> impl Iterator<Item = &OperatingPointResult> {
+        self.analyses
+            .iter()
+            .filter_map(|analysis| match &analysis.result {
+                AnalysisResult::Op(op) => Some(op),
+                _ => None,
+            })
+    }
+
+    pub fn dc_sweeps(&self) -> impl Iterator<Item = &DcSweepResult> {
+        self.analyses
+            .iter()
+            .filter_map(|analysis| match &analysis.result {
+                AnalysisResult::Dc(dc, _) => Some(dc),
+                _ => None,
+            })
+    }
+
+    pub fn ac_sweeps(&self) -> impl Iterator<Item = &AcSweep> {
+        self.analyses
+            .iter()
+            .filter_map(|analysis| match &analysis.result {
+                AnalysisResult::Ac(ac) => Some(ac),
+                _ => None,
+            })
+    }
+
+    pub fn transients(&self) -> impl Iterator<Item = &TransientResult> {
+        self.analyses
+            .iter()
+            .filter_map(|analysis| match &analysis.result {
+                AnalysisResult::Tran(tran) => Some(tran),
+                _ => None,
+            })
+    }
+}
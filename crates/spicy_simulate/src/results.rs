@@ -0,0 +1,491 @@
+//! Name-based access to analysis results.
+//!
+//! `OperatingPointResult`, `DcSweepResult` and `TransientResult` all expose the same accessors:
+//! `voltage("out")`, `current("V1")`, `vectors()` and (for swept analyses) `at(x)`. Names are
+//! matched ignoring case, like in the netlist. A swept analysis gives a [`Waveform`] that pairs
+//! every sample with its time or sweep value.
+//!
+//! Nodes and devices inside a subcircuit are named by their instance path, e.g.
+//! `voltage("X1.out")`; see [`spicy_parser::hierarchical_name`].
+
+use std::fmt;
+
+use crate::dc::{DcSweepResult, OperatingPointResult};
+use crate::matrix::SolverStats;
+use crate::trans::TransientResult;
+use crate::warnings::SimulationWarning;
+
+/// Physical unit of a result vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Volt,
+    Ampere,
+    Watt,
+}
+
+impl Unit {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Volt => "V",
+            Unit::Ampere => "A",
+            Unit::Watt => "W",
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+/// A named result vector: a node voltage or a branch current over the analysis points.
+///
+/// For an operating point the data holds a single value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vector<'a> {
+    pub name: &'a str,
+    pub unit: Unit,
+    pub data: Vec<f64>,
+}
+
+/// A node voltage or branch current over the points of a swept analysis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waveform<'a> {
+    /// The node or device, as spelled in the result.
+    pub name: &'a str,
+    pub unit: Unit,
+    /// The time or the swept source value of every point.
+    pub x: Vec<f64>,
+    /// The value at every point.
+    pub y: Vec<f64>,
+}
+
+impl Waveform<'_> {
+    /// `(x, y)` of every point.
+    pub fn points(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.x.iter().copied().zip(self.y.iter().copied())
+    }
+
+    /// The value at `x`, linearly interpolated between points; `None` outside the sweep.
+    pub fn at(&self, x: f64) -> Option<f64> {
+        let (i, frac) = locate(&self.x, x)?;
+        let next = self.y.get(i + 1).unwrap_or(&self.y[i]);
+        Some(lerp(self.y[i], *next, frac))
+    }
+}
+
+/// The position of `name` in `names`: the exact spelling if present, else ignoring case.
+///
+/// The deck's names only differ in case when it was parsed with [`NameCase::Sensitive`], and
+/// then the exact spelling is the one asked for.
+///
+/// [`NameCase::Sensitive`]: spicy_parser::NameCase::Sensitive
+pub(crate) fn position<'a>(
+    names: impl Iterator<Item = &'a String> + Clone,
+    name: &str,
+) -> Option<usize> {
+    names
+        .clone()
+        .position(|n| n == name)
+        .or_else(|| names.clone().position(|n| n.eq_ignore_ascii_case(name)))
+}
+
+/// Find the segment of the monotonic `xs` containing `x`.
+/// Returns the lower index and the fraction towards the next point.
+fn locate(xs: &[f64], x: f64) -> Option<(usize, f64)> {
+    let (&first, &last) = (xs.first()?, xs.last()?);
+    let (lo, hi) = if first <= last {
+        (first, last)
+    } else {
+        (last, first)
+    };
+    if !(lo..=hi).contains(&x) {
+        return None;
+    }
+    if xs.len() == 1 {
+        return Some((0, 0.0));
+    }
+
+    let ascending = first <= last;
+    // first index whose x is past the requested point
+    let upper = xs
+        .partition_point(|&xi| if ascending { xi < x } else { xi > x })
+        .clamp(1, xs.len() - 1);
+    let (x0, x1) = (xs[upper - 1], xs[upper]);
+    let frac = if x1 == x0 { 0.0 } else { (x - x0) / (x1 - x0) };
+    Some((upper - 1, frac))
+}
+
+fn lerp(a: f64, b: f64, frac: f64) -> f64 {
+    a + (b - a) * frac
+}
+
+impl OperatingPointResult {
+    /// Voltage of node `name`.
+    pub fn voltage(&self, name: &str) -> Option<f64> {
+        let index = position(self.voltages.iter().map(|(n, _)| n), name)?;
+        Some(self.voltages[index].1)
+    }
+
+    /// Current through the branch of device `name` (voltage sources, inductors), or through a
+    /// resistor, capacitor or diode with `.options savecurrents`.
+    pub fn current(&self, name: &str) -> Option<f64> {
+        let index = position(self.currents.iter().map(|(n, _)| n), name)?;
+        Some(self.currents[index].1)
+    }
+
+    /// All node voltages followed by all branch currents.
+    pub fn vectors(&self) -> impl Iterator<Item = Vector<'_>> {
+        let voltages = self.voltages.iter().map(|(name, v)| Vector {
+            name,
+            unit: Unit::Volt,
+            data: vec![*v],
+        });
+        let currents = self.currents.iter().map(|(name, i)| Vector {
+            name,
+            unit: Unit::Ampere,
+            data: vec![*i],
+        });
+        voltages.chain(currents)
+    }
+
+    fn lerp(&self, next: &OperatingPointResult, frac: f64) -> OperatingPointResult {
+        let mix = |a: &[(String, f64)], b: &[(String, f64)]| {
+            a.iter()
+                .zip(b)
+                .map(|((name, x), (_, y))| (name.clone(), lerp(*x, *y, frac)))
+                .collect()
+        };
+        OperatingPointResult {
+            voltages: mix(&self.voltages, &next.voltages),
+            currents: mix(&self.currents, &next.currents),
+            warnings: Vec::new(),
+            solver_stats: SolverStats::default(),
+        }
+    }
+}
+
+impl DcSweepResult {
+    /// Warnings from every sweep point, in sweep order.
+    pub fn warnings(&self) -> impl Iterator<Item = &SimulationWarning> {
+        self.results.iter().flat_map(|(op, _)| &op.warnings)
+    }
+
+    /// The swept source values, one per point.
+    pub fn sweep_values(&self) -> Vec<f64> {
+        self.results.iter().map(|(_, x)| *x).collect()
+    }
+
+    /// Voltage of node `name` over the sweep. A nested sweep gives every curve one after the
+    /// other; use `curve(i)` for one of them.
+    pub fn voltage(&self, name: &str) -> Option<Waveform<'_>> {
+        let (op, _) = self.results.first()?;
+        let index = position(op.voltages.iter().map(|(n, _)| n), name)?;
+        Some(Waveform {
+            name: &op.voltages[index].0,
+            unit: Unit::Volt,
+            x: self.sweep_values(),
+            y: self
+                .results
+                .iter()
+                .map(|(op, _)| op.voltages[index].1)
+                .collect(),
+        })
+    }
+
+    /// Branch current of device `name` over the sweep.
+    pub fn current(&self, name: &str) -> Option<Waveform<'_>> {
+        let (op, _) = self.results.first()?;
+        let index = position(op.currents.iter().map(|(n, _)| n), name)?;
+        Some(Waveform {
+            name: &op.currents[index].0,
+            unit: Unit::Ampere,
+            x: self.sweep_values(),
+            y: self
+                .results
+                .iter()
+                .map(|(op, _)| op.currents[index].1)
+                .collect(),
+        })
+    }
+
+    /// All node voltages followed by all branch currents over the sweep.
+    pub fn vectors(&self) -> impl Iterator<Item = Vector<'_>> {
+        let first = self.results.first().map(|(op, _)| op);
+        let voltages = first
+            .into_iter()
+            .flat_map(|op| op.voltages.iter().enumerate())
+            .map(|(i, (name, _))| Vector {
+                name,
+                unit: Unit::Volt,
+                data: self
+                    .results
+                    .iter()
+                    .map(|(op, _)| op.voltages[i].1)
+                    .collect(),
+            });
+        let currents = first
+            .into_iter()
+            .flat_map(|op| op.currents.iter().enumerate())
+            .map(|(i, (name, _))| Vector {
+                name,
+                unit: Unit::Ampere,
+                data: self
+                    .results
+                    .iter()
+                    .map(|(op, _)| op.currents[i].1)
+                    .collect(),
+            });
+        voltages.chain(currents)
+    }
+
+    /// Number of curves: the outer values of a nested sweep, or 1.
+    pub fn curve_count(&self) -> usize {
+        self.outer_values.len().max(1)
+    }
+
+    /// The points of curve `index` as a single sweep.
+    pub fn curve(&self, index: usize) -> Option<DcSweepResult> {
+        if index >= self.curve_count() {
+            return None;
+        }
+        let len = self.results.len() / self.curve_count();
+        Some(DcSweepResult {
+            results: self.results[index * len..(index + 1) * len].to_vec(),
+            outer_values: Vec::new(),
+            cancelled: self.cancelled,
+        })
+    }
+
+    /// The solution at sweep value `x`, linearly interpolated between sweep points.
+    /// Returns `None` when `x` is outside of the sweep. A nested sweep is looked up in its
+    /// first curve; use `curve(i)` for the others.
+    pub fn at(&self, x: f64) -> Option<OperatingPointResult> {
+        let points = &self.results[..self.results.len() / self.curve_count()];
+        let sweep: Vec<f64> = points.iter().map(|(_, x)| *x).collect();
+        let (i, frac) = locate(&sweep, x)?;
+        let (op, _) = &points[i];
+        match points.get(i + 1) {
+            Some((next, _)) => Some(op.lerp(next, frac)),
+            None => Some(op.clone()),
+        }
+    }
+}
+
+impl TransientResult {
+    fn column(&self, index: usize) -> Vec<f64> {
+        self.samples.iter().map(|s| s[index]).collect()
+    }
+
+    /// Voltage waveform of node `name`.
+    pub fn voltage(&self, name: &str) -> Option<Waveform<'_>> {
+        let index = position(self.node_names.iter(), name)?;
+        Some(Waveform {
+            name: &self.node_names[index],
+            unit: Unit::Volt,
+            x: self.times.clone(),
+            y: self.column(index),
+        })
+    }
+
+    /// Current waveform through the branch of device `name`.
+    pub fn current(&self, name: &str) -> Option<Waveform<'_>> {
+        let index = position(self.source_names.iter(), name)?;
+        Some(Waveform {
+            name: &self.source_names[index],
+            unit: Unit::Ampere,
+            x: self.times.clone(),
+            y: self.column(self.node_names.len() + index),
+        })
+    }
+
+    /// All node voltage waveforms followed by all branch current waveforms.
+    pub fn vectors(&self) -> impl Iterator<Item = Vector<'_>> {
+        let voltages = self.node_names.iter().map(|name| (name, Unit::Volt));
+        let currents = self.source_names.iter().map(|name| (name, Unit::Ampere));
+        voltages
+            .chain(currents)
+            .enumerate()
+            .map(|(i, (name, unit))| Vector {
+                name,
+                unit,
+                data: self.column(i),
+            })
+    }
+
+    /// The solution at time `t`, linearly interpolated between time points.
+    /// Returns `None` when `t` is outside of the simulated interval.
+    pub fn at(&self, t: f64) -> Option<OperatingPointResult> {
+        let (i, frac) = locate(&self.times, t)?;
+        let sample = &self.samples[i];
+        let next = self.samples.get(i + 1).unwrap_or(sample);
+        let value = |k: usize| lerp(sample[k], next[k], frac);
+
+        let n = self.node_names.len();
+        Some(OperatingPointResult {
+            voltages: self
+                .node_names
+                .iter()
+                .enumerate()
+                .map(|(k, name)| (name.clone(), value(k)))
+                .collect(),
+            currents: self
+                .source_names
+                .iter()
+                .enumerate()
+                .map(|(k, name)| (name.clone(), value(n + k)))
+                .collect(),
+            warnings: Vec::new(),
+            solver_stats: SolverStats::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(vout: f64, i: f64) -> OperatingPointResult {
+        OperatingPointResult {
+            voltages: vec![("in".to_string(), 1.0), ("out".to_string(), vout)],
+            currents: vec![("V1".to_string(), i)],
+            warnings: Vec::new(),
+            solver_stats: SolverStats::default(),
+        }
+    }
+
+    fn transient() -> TransientResult {
+        TransientResult {
+            times: vec![0.0, 1.0, 2.0],
+            node_names: vec!["in".to_string(), "out".to_string()],
+            source_names: vec!["V1".to_string()],
+            samples: vec![
+                vec![1.0, 0.0, -1.0],
+                vec![1.0, 0.5, -0.5],
+                vec![1.0, 1.0, 0.0],
+            ],
+            newton_iterations: vec![0, 1, 1],
+            warnings: Vec::new(),
+            solver_stats: SolverStats::default(),
+            cancelled: false,
+        }
+    }
+
+    #[test]
+    fn operating_point_lookup() {
+        let op = op(0.25, -1e-3);
+        assert_eq!(op.voltage("out"), Some(0.25));
+        assert_eq!(op.current("V1"), Some(-1e-3));
+        assert_eq!(op.voltage("missing"), None);
+        assert_eq!(op.voltage("OUT"), Some(0.25));
+        assert_eq!(op.current("v1"), Some(-1e-3));
+        // currents are not voltages
+        assert_eq!(op.voltage("V1"), None);
+
+        let vectors: Vec<_> = op.vectors().collect();
+        assert_eq!(vectors.len(), 3);
+        assert_eq!(vectors[1].name, "out");
+        assert_eq!(vectors[1].unit, Unit::Volt);
+        assert_eq!(vectors[2].unit.to_string(), "A");
+        assert_eq!(vectors[2].data, vec![-1e-3]);
+    }
+
+    #[test]
+    fn exact_spelling_wins_for_case_sensitive_decks() {
+        let mut op = op(0.25, -1e-3);
+        op.voltages.push(("OUT".to_string(), 0.75));
+        assert_eq!(op.voltage("out"), Some(0.25));
+        assert_eq!(op.voltage("OUT"), Some(0.75));
+        assert_eq!(op.voltage("Out"), Some(0.25));
+    }
+
+    #[test]
+    fn dc_sweep_lookup_and_interpolation() {
+        let dc = DcSweepResult {
+            results: vec![(op(0.0, 0.0), 0.0), (op(1.0, -2.0), 1.0)],
+            outer_values: Vec::new(),
+            cancelled: false,
+        };
+        let out = dc.voltage("out").expect("out");
+        assert_eq!(out.y, vec![0.0, 1.0]);
+        assert_eq!(out.x, vec![0.0, 1.0]);
+        assert_eq!(dc.current("V1").map(|i| i.y), Some(vec![0.0, -2.0]));
+        assert_eq!(dc.current("V9"), None);
+        assert_eq!(dc.vectors().count(), 3);
+
+        let mid = dc.at(0.25).expect("inside sweep");
+        assert_eq!(mid.voltage("out"), Some(0.25));
+        assert_eq!(mid.current("V1"), Some(-0.5));
+        assert!(dc.at(1.5).is_none());
+    }
+
+    #[test]
+    fn nested_dc_sweep_curves() {
+        let dc = DcSweepResult {
+            results: vec![
+                (op(0.0, 0.0), 0.0),
+                (op(1.0, 0.0), 1.0),
+                (op(2.0, 0.0), 0.0),
+                (op(3.0, 0.0), 1.0),
+            ],
+            outer_values: vec![5.0, 10.0],
+            cancelled: false,
+        };
+        assert_eq!(dc.curve_count(), 2);
+        let second = dc.curve(1).expect("second curve");
+        assert_eq!(second.voltage("out").map(|v| v.y), Some(vec![2.0, 3.0]));
+        assert_eq!(second.sweep_values(), vec![0.0, 1.0]);
+        assert!(dc.curve(2).is_none());
+        assert_eq!(dc.at(0.5).and_then(|op| op.voltage("out")), Some(0.5));
+    }
+
+    #[test]
+    fn transient_lookup_and_interpolation() {
+        let tr = transient();
+        assert_eq!(tr.voltage("out").map(|v| v.y), Some(vec![0.0, 0.5, 1.0]));
+        assert_eq!(tr.current("V1").map(|i| i.y), Some(vec![-1.0, -0.5, 0.0]));
+
+        let names: Vec<_> = tr.vectors().map(|v| (v.name, v.unit)).collect();
+        assert_eq!(
+            names,
+            vec![
+                ("in", Unit::Volt),
+                ("out", Unit::Volt),
+                ("V1", Unit::Ampere)
+            ]
+        );
+
+        let at = tr.at(1.5).expect("inside interval");
+        assert_eq!(at.voltage("out"), Some(0.75));
+        assert_eq!(at.current("V1"), Some(-0.25));
+        assert_eq!(tr.at(2.0).and_then(|op| op.voltage("out")), Some(1.0));
+        assert!(tr.at(-0.1).is_none());
+    }
+
+    #[test]
+    fn waveforms_are_found_ignoring_case() {
+        let tr = transient();
+        let out = tr.voltage("OUT").expect("out");
+        assert_eq!(out.name, "out");
+        assert_eq!(out.unit, Unit::Volt);
+        assert_eq!(
+            out.points().collect::<Vec<_>>(),
+            vec![(0.0, 0.0), (1.0, 0.5), (2.0, 1.0)]
+        );
+        assert_eq!(out.at(0.5), Some(0.25));
+        assert_eq!(out.at(3.0), None);
+
+        let current = tr.current("v1").expect("V1");
+        assert_eq!((current.name, current.unit), ("V1", Unit::Ampere));
+        // a node is not a branch
+        assert!(tr.current("out").is_none());
+    }
+
+    #[test]
+    fn locate_descending() {
+        assert_eq!(locate(&[2.0, 1.0, 0.0], 0.5), Some((1, 0.5)));
+        assert_eq!(locate(&[], 0.5), None);
+        assert_eq!(locate(&[3.0], 3.0), Some((0, 0.0)));
+    }
+}
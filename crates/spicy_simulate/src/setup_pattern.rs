@@ -1,5 +1,9 @@
 use crate::{
-    devices::{Bjt, Capacitor, Devices, Diode, IndependentSource, Inductor, Resistor},
+    devices::{
+        BehavioralSource, Bjt, Capacitor, Devices, Diode, IndependentSource, Inductor, Jfet,
+        LookupTable, Mosfet, MutualInductance, Resistor, Switch, TransmissionLine,
+        stamp::NodeBranchPairStamp,
+    },
     error::SimulationError,
     solver::matrix::csc::CscMatrix,
 };
@@ -21,8 +25,9 @@ fn setup_resistors(
     for r in resistors {
         let pos = node_mapping.mna_node_index(r.positive);
         let neg = node_mapping.mna_node_index(r.negative);
-        r.stamp
-            .set_temp_indices_from_nodes(pos, neg, |col, row| builder.push(col, row, 0.0))?;
+        r.stamp.set_temp_indices_from_pattern(pos, neg, |pattern| {
+            builder.push_pattern(pattern).map(|entries| entries.start)
+        })?;
     }
     Ok(())
 }
@@ -35,8 +40,9 @@ fn setup_capacitors(
     for c in capacitors {
         let pos = node_mapping.mna_node_index(c.positive);
         let neg = node_mapping.mna_node_index(c.negative);
-        c.stamp
-            .set_temp_indices_from_nodes(pos, neg, |col, row| builder.push(col, row, 0.0))?;
+        c.stamp.set_temp_indices_from_pattern(pos, neg, |pattern| {
+            builder.push_pattern(pattern).map(|entries| entries.start)
+        })?;
     }
     Ok(())
 }
@@ -49,8 +55,24 @@ fn setup_diodes(
     for d in diodes {
         let pos = node_mapping.mna_node_index(d.positive);
         let neg = node_mapping.mna_node_index(d.negative);
-        d.stamp
-            .set_temp_indices_from_nodes(pos, neg, |col, row| builder.push(col, row, 0.0))?;
+        d.stamp.set_temp_indices_from_pattern(pos, neg, |pattern| {
+            builder.push_pattern(pattern).map(|entries| entries.start)
+        })?;
+    }
+    Ok(())
+}
+
+fn setup_lookup_tables(
+    tables: &mut [LookupTable],
+    node_mapping: &NodeMapping,
+    builder: &mut MatrixBuilder,
+) -> Result<(), SimulationError> {
+    for t in tables {
+        let pos = node_mapping.mna_node_index(t.positive);
+        let neg = node_mapping.mna_node_index(t.negative);
+        t.stamp.set_temp_indices_from_pattern(pos, neg, |pattern| {
+            builder.push_pattern(pattern).map(|entries| entries.start)
+        })?;
     }
     Ok(())
 }
@@ -61,11 +83,54 @@ fn setup_bjts(
     builder: &mut MatrixBuilder,
 ) -> Result<(), SimulationError> {
     for bjt in bjts {
-        let b = node_mapping.mna_node_index(bjt.base);
-        let c = node_mapping.mna_node_index(bjt.collector);
-        let e = node_mapping.mna_node_index(bjt.emitter);
+        let b = node_mapping.mna_node_index(bjt.base_prime);
+        let c = node_mapping.mna_node_index(bjt.collector_prime);
+        let e = node_mapping.mna_node_index(bjt.emitter_prime);
         bjt.stamp
             .set_temp_indices_from_nodes(b, c, e, |row, col| builder.push(col, row, 0.0))?;
+        for r in &mut bjt.series_resistances {
+            let pos = node_mapping.mna_node_index(r.positive);
+            let neg = node_mapping.mna_node_index(r.negative);
+            r.stamp.set_temp_indices_from_pattern(pos, neg, |pattern| {
+                builder.push_pattern(pattern).map(|entries| entries.start)
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn setup_mosfets(
+    mosfets: &mut [Mosfet],
+    node_mapping: &NodeMapping,
+    builder: &mut MatrixBuilder,
+) -> Result<(), SimulationError> {
+    for mosfet in mosfets {
+        let nodes = mosfet.terminals().map(|n| node_mapping.mna_node_index(n));
+        mosfet
+            .stamp
+            .set_temp_indices_from_nodes(nodes, |row, col| builder.push(col, row, 0.0))?;
+    }
+    Ok(())
+}
+
+fn setup_jfets(
+    jfets: &mut [Jfet],
+    node_mapping: &NodeMapping,
+    builder: &mut MatrixBuilder,
+) -> Result<(), SimulationError> {
+    for jfet in jfets {
+        let g = node_mapping.mna_node_index(jfet.gate);
+        let d = node_mapping.mna_node_index(jfet.drain_prime);
+        let s = node_mapping.mna_node_index(jfet.source_prime);
+        jfet.stamp
+            .set_temp_indices_from_nodes(g, d, s, |row, col| builder.push(col, row, 0.0))?;
+        for r in &mut jfet.series_resistances {
+            let pos = node_mapping.mna_node_index(r.positive);
+            let neg = node_mapping.mna_node_index(r.negative);
+            r.stamp.set_temp_indices_from_pattern(pos, neg, |pattern| {
+                builder.push_pattern(pattern).map(|entries| entries.start)
+            })?;
+        }
     }
     Ok(())
 }
@@ -76,9 +141,13 @@ fn setup_inductors(
     builder: &mut MatrixBuilder,
 ) -> Result<(), SimulationError> {
     for i in inductors {
+        let Some(branch_index) = node_mapping.try_mna_branch_index(i.current_branch) else {
+            // collapsed into a short of its nodes
+            i.stamp = NodeBranchPairStamp::uninitialized();
+            continue;
+        };
         let pos = node_mapping.mna_node_index(i.positive);
         let neg = node_mapping.mna_node_index(i.negative);
-        let branch_index = node_mapping.mna_branch_index(i.current_branch);
         i.stamp.set_temp_indices_from_nodes(pos, neg, branch_index, |col, row| {
             builder.push(col, row, 0.0)
         })?;
@@ -86,6 +155,20 @@ fn setup_inductors(
     Ok(())
 }
 
+fn setup_mutual_inductances(
+    mutual_inductances: &mut [MutualInductance],
+    inductors: &[Inductor],
+    node_mapping: &NodeMapping,
+    builder: &mut MatrixBuilder,
+) -> Result<(), SimulationError> {
+    for k in mutual_inductances {
+        k.setup(inductors, node_mapping, |row, col| {
+            builder.push(col, row, 0.0)
+        })?;
+    }
+    Ok(())
+}
+
 fn setup_voltage_sources(
     voltage_sources: &mut [IndependentSource],
     node_mapping: &NodeMapping,
@@ -102,21 +185,90 @@ fn setup_voltage_sources(
     Ok(())
 }
 
+fn setup_behavioral_sources(
+    behavioral_sources: &mut [BehavioralSource],
+    node_mapping: &NodeMapping,
+    builder: &mut MatrixBuilder,
+) -> Result<(), SimulationError> {
+    for b in behavioral_sources {
+        b.setup(node_mapping, |row, col| builder.push(col, row, 0.0))?;
+    }
+    Ok(())
+}
+
+fn setup_transmission_lines(
+    transmission_lines: &mut [TransmissionLine],
+    node_mapping: &NodeMapping,
+    builder: &mut MatrixBuilder,
+) -> Result<(), SimulationError> {
+    for t in transmission_lines {
+        t.setup(node_mapping, |row, col| builder.push(col, row, 0.0))?;
+    }
+    Ok(())
+}
+
+fn setup_switches(
+    switches: &mut [Switch],
+    node_mapping: &NodeMapping,
+    builder: &mut MatrixBuilder,
+) -> Result<(), SimulationError> {
+    for s in switches {
+        s.setup(node_mapping, |row, col| builder.push(col, row, 0.0))?;
+    }
+    Ok(())
+}
+
+/// Entries the devices with a fixed stamp push, and the node diagonals; the builder grows past it
+/// for the rest.
+fn pattern_size_estimate(devices: &Devices, node_mapping: &NodeMapping) -> usize {
+    let two_terminal = devices.resistors.len()
+        + devices.capacitors.len()
+        + devices.diodes.len()
+        + devices.lookup_tables.len();
+    let branches = devices.inductors.len() + devices.voltage_sources.len();
+    let three_terminal = devices.bjts.len() + devices.jfets.len();
+    4 * two_terminal
+        + 5 * branches
+        + 9 * three_terminal
+        + 8 * devices.mosfets.len()
+        + node_mapping.nodes_len()
+}
+
 pub fn setup_pattern(
     devices: &mut Devices,
     node_mapping: &NodeMapping,
 ) -> Result<CscMatrix, SimulationError> {
     let matrix_dim = node_mapping.mna_matrix_dim();
     let mut builder = MatrixBuilder::new(matrix_dim, matrix_dim);
+    builder.reserve(pattern_size_estimate(devices, node_mapping));
 
     setup_resistors(&mut devices.resistors, node_mapping, &mut builder)?;
     setup_capacitors(&mut devices.capacitors, node_mapping, &mut builder)?;
     setup_inductors(&mut devices.inductors, node_mapping, &mut builder)?;
+    setup_mutual_inductances(
+        &mut devices.mutual_inductances,
+        &devices.inductors,
+        node_mapping,
+        &mut builder,
+    )?;
     setup_diodes(&mut devices.diodes, node_mapping, &mut builder)?;
+    setup_lookup_tables(&mut devices.lookup_tables, node_mapping, &mut builder)?;
     setup_bjts(&mut devices.bjts, node_mapping, &mut builder)?;
+    setup_mosfets(&mut devices.mosfets, node_mapping, &mut builder)?;
+    setup_jfets(&mut devices.jfets, node_mapping, &mut builder)?;
     setup_voltage_sources(&mut devices.voltage_sources, node_mapping, &mut builder)?;
+    setup_behavioral_sources(&mut devices.behavioral_sources, node_mapping, &mut builder)?;
+    setup_transmission_lines(&mut devices.transmission_lines, node_mapping, &mut builder)?;
+    setup_switches(&mut devices.switches, node_mapping, &mut builder)?;
+    for p in &mut devices.plugins {
+        p.setup_sparse(node_mapping, &mut builder)?;
+    }
     // we do not need to setup current sources as they don't effect the matrix structure (only the right hand side)
 
+    // every node diagonal is reserved so gmin can be added without changing the pattern
+    let diagonals: Vec<_> = (0..node_mapping.nodes_len()).map(|i| (i, i)).collect();
+    builder.push_pattern(&diagonals)?;
+
     let (matrix, mapping) = builder.build_csc_pattern()?;
 
     for r in &mut devices.resistors {
@@ -128,15 +280,45 @@ pub fn setup_pattern(
     for ind in &mut devices.inductors {
         ind.stamp.set_final_indices(|i| mapping.get(i));
     }
+    for k in &mut devices.mutual_inductances {
+        k.stamp.set_final_indices(|i| mapping.get(i));
+    }
     for d in &mut devices.diodes {
         d.stamp.set_final_indices(|i| mapping.get(i));
     }
+    for t in &mut devices.lookup_tables {
+        t.stamp.set_final_indices(|i| mapping.get(i));
+    }
     for bjt in &mut devices.bjts {
         bjt.stamp.set_final_indices(|i| mapping.get(i));
+        for r in &mut bjt.series_resistances {
+            r.stamp.set_final_indices(|i| mapping.get(i));
+        }
+    }
+    for mosfet in &mut devices.mosfets {
+        mosfet.stamp.set_final_indices(|i| mapping.get(i));
+    }
+    for jfet in &mut devices.jfets {
+        jfet.stamp.set_final_indices(|i| mapping.get(i));
+        for r in &mut jfet.series_resistances {
+            r.stamp.set_final_indices(|i| mapping.get(i));
+        }
     }
     for v in &mut devices.voltage_sources {
         v.stamp.set_final_indices(|i| mapping.get(i));
     }
+    for b in &mut devices.behavioral_sources {
+        b.stamp.set_final_indices(|i| mapping.get(i));
+    }
+    for t in &mut devices.transmission_lines {
+        t.stamp.set_final_indices(|i| mapping.get(i));
+    }
+    for s in &mut devices.switches {
+        s.stamp.set_final_indices(|i| mapping.get(i));
+    }
+    for p in &mut devices.plugins {
+        p.set_final_indices(|i| mapping.get(i));
+    }
 
     Ok(matrix)
 }
@@ -146,7 +328,10 @@ pub fn setup_pattern(
 /// For BLAS we store a *dense linear index* into the MNA matrix buffer in each stamp field:
 /// `idx = row * dim + col` (row-major). This avoids building a sparse CSC pattern just to
 /// compute per-device stamp locations.
-pub fn setup_dense_stamps(devices: &mut Devices, node_mapping: &NodeMapping) {
+pub fn setup_dense_stamps(
+    devices: &mut Devices,
+    node_mapping: &NodeMapping,
+) -> Result<(), SimulationError> {
     let dim = node_mapping.mna_matrix_dim();
 
     for r in &mut devices.resistors {
@@ -188,10 +373,23 @@ pub fn setup_dense_stamps(devices: &mut Devices, node_mapping: &NodeMapping) {
         d.stamp.set_temp_indices(pos_pos, neg_neg, off);
     }
 
+    for t in &mut devices.lookup_tables {
+        let pos = node_mapping.mna_node_index(t.positive);
+        let neg = node_mapping.mna_node_index(t.negative);
+        let pos_pos = pos.map(|p| dense_index(p, p, dim));
+        let neg_neg = neg.map(|n| dense_index(n, n, dim));
+        let off = if let (Some(p), Some(n)) = (pos, neg) {
+            Some((dense_index(p, n, dim), dense_index(n, p, dim)))
+        } else {
+            None
+        };
+        t.stamp.set_temp_indices(pos_pos, neg_neg, off);
+    }
+
     for bjt in &mut devices.bjts {
-        let b = node_mapping.mna_node_index(bjt.base);
-        let c = node_mapping.mna_node_index(bjt.collector);
-        let e = node_mapping.mna_node_index(bjt.emitter);
+        let b = node_mapping.mna_node_index(bjt.base_prime);
+        let c = node_mapping.mna_node_index(bjt.collector_prime);
+        let e = node_mapping.mna_node_index(bjt.emitter_prime);
 
         let dense_entry = |row: Option<usize>, col: Option<usize>| match (row, col) {
             (Some(r), Some(c)) => Some(dense_index(r, c, dim)),
@@ -209,18 +407,63 @@ pub fn setup_dense_stamps(devices: &mut Devices, node_mapping: &NodeMapping) {
         let ee = dense_entry(e, e);
 
         bjt.stamp.set_temp_indices(bb, bc, be, cb, cc, ce, eb, ec, ee);
+
+        for r in &mut bjt.series_resistances {
+            let pos = node_mapping.mna_node_index(r.positive);
+            let neg = node_mapping.mna_node_index(r.negative);
+            let pos_pos = pos.map(|p| dense_index(p, p, dim));
+            let neg_neg = neg.map(|n| dense_index(n, n, dim));
+            let off = if let (Some(p), Some(n)) = (pos, neg) {
+                Some((dense_index(p, n, dim), dense_index(n, p, dim)))
+            } else {
+                None
+            };
+            r.stamp.set_temp_indices(pos_pos, neg_neg, off);
+        }
+    }
+
+    for mosfet in &mut devices.mosfets {
+        let nodes = mosfet.terminals().map(|n| node_mapping.mna_node_index(n));
+        mosfet
+            .stamp
+            .set_temp_indices_from_nodes(nodes, |row, col| {
+                Ok::<_, SimulationError>(dense_index(row, col, dim))
+            })?;
+    }
+
+    for jfet in &mut devices.jfets {
+        let g = node_mapping.mna_node_index(jfet.gate);
+        let d = node_mapping.mna_node_index(jfet.drain_prime);
+        let s = node_mapping.mna_node_index(jfet.source_prime);
+        let dense_entry = |row, col| Ok::<_, SimulationError>(dense_index(row, col, dim));
+        jfet.stamp
+            .set_temp_indices_from_nodes(g, d, s, dense_entry)?;
+        for r in &mut jfet.series_resistances {
+            let pos = node_mapping.mna_node_index(r.positive);
+            let neg = node_mapping.mna_node_index(r.negative);
+            r.stamp.set_temp_indices_from_nodes(pos, neg, dense_entry)?;
+        }
     }
 
     for ind in &mut devices.inductors {
+        let Some(b) = node_mapping.try_mna_branch_index(ind.current_branch) else {
+            ind.stamp = NodeBranchPairStamp::uninitialized();
+            continue;
+        };
         let pos = node_mapping.mna_node_index(ind.positive);
         let neg = node_mapping.mna_node_index(ind.negative);
-        let b = node_mapping.mna_branch_index(ind.current_branch);
         let pos_branch = pos.map(|p| (dense_index(p, b, dim), dense_index(b, p, dim)));
         let neg_branch = neg.map(|n| (dense_index(n, b, dim), dense_index(b, n, dim)));
         let bb = dense_index(b, b, dim);
         ind.stamp.set_temp_indices(pos_branch, neg_branch, bb);
     }
 
+    for k in &mut devices.mutual_inductances {
+        k.setup(&devices.inductors, node_mapping, |row, col| {
+            Ok::<_, SimulationError>(dense_index(row, col, dim))
+        })?;
+    }
+
     for v in &mut devices.voltage_sources {
         let pos = node_mapping.mna_node_index(v.positive);
         let neg = node_mapping.mna_node_index(v.negative);
@@ -229,12 +472,36 @@ pub fn setup_dense_stamps(devices: &mut Devices, node_mapping: &NodeMapping) {
         let neg_branch = neg.map(|n| (dense_index(n, b, dim), dense_index(b, n, dim)));
         v.stamp.set_temp_indices(pos_branch, neg_branch);
     }
+
+    for b in &mut devices.behavioral_sources {
+        b.setup(node_mapping, |row, col| {
+            Ok::<_, SimulationError>(dense_index(row, col, dim))
+        })?;
+    }
+
+    for t in &mut devices.transmission_lines {
+        t.setup(node_mapping, |row, col| {
+            Ok::<_, SimulationError>(dense_index(row, col, dim))
+        })?;
+    }
+
+    for s in &mut devices.switches {
+        s.setup(node_mapping, |row, col| {
+            Ok::<_, SimulationError>(dense_index(row, col, dim))
+        })?;
+    }
+
+    for p in &mut devices.plugins {
+        p.setup_dense(node_mapping)?;
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::devices::Devices as SimDevices;
+    use crate::devices::{Devices as SimDevices, NOMINAL_TEMPERATURE};
     use spicy_parser::{ParseOptions, SourceMap, parse};
     use std::path::PathBuf;
 
@@ -248,6 +515,9 @@ mod tests {
             source_path: PathBuf::from("."),
             source_map,
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         parse(&mut options).expect("parse")
     }
@@ -276,7 +546,7 @@ L1 out 0 1m
 "#,
         );
 
-        let mut sim_devices = SimDevices::from_spec(&deck.devices);
+        let mut sim_devices = SimDevices::from_spec(&deck.devices, NOMINAL_TEMPERATURE);
         let matrix =
             super::setup_pattern(&mut sim_devices, &deck.node_mapping).expect("setup_pattern");
         debug_assert!(matrix.check_invariants().is_ok());
@@ -19,6 +19,7 @@
 /// the code is extensively documented but is not very easy to understand.
 ///
 use crate::solver::utils::{flip, inverse_permutation};
+use spicy_record::{Recorder, recorded};
 
 pub struct AmdControl {
     /// If true, then aggressive absorption is performed.
@@ -183,7 +184,12 @@ fn add_hash_to_degree_list(
     last[i] = hash as isize;
 }
 
-fn initialize_amd(
+#[recorded(
+    nested,
+    skip(control, head, last, next, degree, w),
+    enabled_if = cfg!(feature = "record")
+)]
+fn initialize_amd<R: Recorder>(
     n: usize,
     control: &AmdControl,
     last: &mut [isize],
@@ -195,6 +201,7 @@ fn initialize_amd(
     degree: &mut [isize],
     len: &mut [usize],
     pe: &mut [isize],
+    recorder: &mut R,
 ) -> (usize, usize) {
     // all lists are empty at the start
     last.fill(EMPTY);
@@ -280,7 +287,12 @@ fn get_pivot_of_minimum_degree(mindeg: &mut usize, n: usize, head: &[isize]) ->
 /// new list, compressing when necessary. this loop is
 /// executed once for each element in the list and once for
 /// all the supervariables in the list.
-fn add_neighboring_supervariables_to_pivot(
+#[recorded(
+    nested,
+    skip(head, last, next, degree, w),
+    enabled_if = cfg!(feature = "record")
+)]
+fn add_neighboring_supervariables_to_pivot<R: Recorder>(
     // element we are searching
     e: usize,
     // start of the current element
@@ -309,6 +321,7 @@ fn add_neighboring_supervariables_to_pivot(
     last: &mut [isize],
     next: &mut [isize],
     degree: &mut [isize],
+    recorder: &mut R,
 ) {
     for knt2 in 1..=ln {
         debug_assert!(iw[*pj] >= 0 && iw[*pj] < n as isize);
@@ -395,7 +408,12 @@ fn add_neighboring_supervariables_to_pivot(
 // at the end Lme (list of supervariables neighboring the **element** me) will be
 // contained in Iw [pme1 .. pme2]
 // also degme holds the external degree |Lme| of new element
-fn construct_new_element(
+#[recorded(
+    nested,
+    skip(head, last, next, degree, w),
+    enabled_if = cfg!(feature = "record")
+)]
+fn construct_new_element<R: Recorder>(
     me: usize,
     nel: &mut usize,
     n: usize,
@@ -414,6 +432,7 @@ fn construct_new_element(
     head: &mut [isize],
     last: &mut [isize],
     next: &mut [isize],
+    recorder: &mut R,
 ) -> (usize, usize, usize, isize) {
     let elenme = elen[me];
     debug_assert!(nv[me] > 0);
@@ -498,6 +517,7 @@ fn construct_new_element(
                 last,
                 next,
                 degree,
+                recorder,
             );
 
             if e != me {
@@ -590,7 +610,12 @@ fn compute_outside_degrees(
     }
 }
 
-fn update_degrees(
+#[recorded(
+    nested,
+    skip(head, last, next, degree, w),
+    enabled_if = cfg!(feature = "record")
+)]
+fn update_degrees<R: Recorder>(
     me: usize,
     pme1: usize,
     pme2: usize,
@@ -611,6 +636,7 @@ fn update_degrees(
     last: &mut [isize],
     next: &mut [isize],
     aggressive: bool,
+    recorder: &mut R,
 ) {
     for pme in pme1..=pme2 {
         debug_assert!(iw[pme] >= 0 && iw[pme] < n as isize);
@@ -738,7 +764,12 @@ fn update_degrees(
     }
 }
 
-fn supervairable_detection(
+#[recorded(
+    nested,
+    skip(head, last, next, degree, w),
+    enabled_if = cfg!(feature = "record")
+)]
+fn supervairable_detection<R: Recorder>(
     pme1: usize,
     pme2: usize,
     n: usize,
@@ -753,6 +784,7 @@ fn supervairable_detection(
     last: &mut [isize],
     w: &mut [isize],
     wflg: &mut isize,
+    recorder: &mut R,
 ) {
     for pme in pme1..=pme2 {
         debug_assert!(iw[pme] >= 0 && iw[pme] < n as isize);
@@ -852,7 +884,12 @@ fn supervairable_detection(
     }
 }
 
-fn restore_degree_list(
+#[recorded(
+    nested,
+    skip(head, last, next, degree, w),
+    enabled_if = cfg!(feature = "record")
+)]
+fn restore_degree_list<R: Recorder>(
     pme1: usize,
     pme2: usize,
     n: usize,
@@ -866,6 +903,7 @@ fn restore_degree_list(
     degree: &mut [isize],
     aggressive: bool,
     mindeg: &mut usize,
+    recorder: &mut R,
 ) -> usize {
     let mut p = pme1;
     let nleft = n - nel;
@@ -900,7 +938,12 @@ fn restore_degree_list(
     p
 }
 
-fn finalize_new_element(
+#[recorded(
+    nested,
+    skip(head, last, next, degree, w),
+    enabled_if = cfg!(feature = "record")
+)]
+fn finalize_new_element<R: Recorder>(
     me: usize,
     nvpiv: usize,
     pme1: usize,
@@ -912,6 +955,7 @@ fn finalize_new_element(
     w: &mut [isize],
     elenme: isize,
     _info: &mut AmdInfo,
+    recorder: &mut R,
 ) {
     nv[me] = nvpiv as isize;
     len[me] = p - pme1;
@@ -1224,7 +1268,14 @@ fn compute_output_permutation(
     inverse_permutation(n, next, last);
 }
 
-pub fn amd(
+/// With the `record` feature, the elimination reports the quotient graph to `recorder`: the
+/// variables and elements of `pe`, `iw`, `len`, `elen` and `nv` as they change, and every
+/// pivot as `me`. The degree lists and the workspace are left out of the trace.
+#[recorded(
+    skip(control, head, last, next, degree, w, info),
+    enabled_if = cfg!(feature = "record")
+)]
+pub fn amd<R: Recorder>(
     n: usize,          // A is n-by-n, where n > 0
     pe: &mut [isize],  // Pe[0..n-1]: index in Iw of row i on input
     iw: &mut [isize],  // workspace of size iwlen. Iw[0..pfree-1] holds the matrix on input
@@ -1242,6 +1293,7 @@ pub fn amd(
     w: &mut [isize],
 
     control: AmdControl,
+    recorder: &mut R,
 ) -> AmdInfo {
     /* Note that this restriction on iwlen is slightly more restrictive than
      * what is actually required in AMD_2.  AMD_2 can operate with no elbow
@@ -1251,8 +1303,9 @@ pub fn amd(
     debug_assert!(n > 0);
     let mut info = AmdInfo::new();
 
-    let (mut nel, ndense) =
-        initialize_amd(n, &control, last, head, next, nv, w, elen, degree, len, pe);
+    let (mut nel, ndense) = initialize_amd(
+        n, &control, last, head, next, nv, w, elen, degree, len, pe, recorder,
+    );
     info.ndense = ndense;
 
     let wbig = isize::MAX - n as isize;
@@ -1270,7 +1323,7 @@ pub fn amd(
         remove_head_from_degree_list(me, n, mindeg, head, last, next);
         let (pme1, pme2, mut nvpiv, elenme) = construct_new_element(
             me, &mut nel, n, &mut pfree, iwlen, elen, &mut degme, nv, pe, iw, len, degree, w, head,
-            last, next,
+            last, next, recorder,
         );
 
         // make sure that wflg is not too large.
@@ -1297,6 +1350,7 @@ pub fn amd(
             last,
             next,
             control.aggressive,
+            recorder,
         );
 
         lemax = usize::max(lemax, degme);
@@ -1305,7 +1359,7 @@ pub fn amd(
         // at this point, W [0..n-1] < wflg holds
 
         supervairable_detection(
-            pme1, pme2, n, iwlen, pe, elen, len, iw, nv, head, next, last, w, &mut wflg,
+            pme1, pme2, n, iwlen, pe, elen, len, iw, nv, head, next, last, w, &mut wflg, recorder,
         );
 
         let p = restore_degree_list(
@@ -1322,9 +1376,10 @@ pub fn amd(
             degree,
             control.aggressive,
             &mut mindeg,
+            recorder,
         );
         finalize_new_element(
-            me, nvpiv, pme1, p, &mut pfree, nv, len, pe, w, elenme, &mut info,
+            me, nvpiv, pme1, p, &mut pfree, nv, len, pe, w, elenme, &mut info, recorder,
         );
 
         let f = nvpiv as f64;
@@ -0,0 +1,196 @@
+//! LU with partial pivoting of small dense matrices, for the circuits whose MNA matrix is too
+//! small for the symbolic analysis of KLU or a LAPACK call to pay off.
+//!
+//! The matrix is row-major. The row updates and the dot products of the solve run over
+//! [`LANES`] independent values at a time, which the compiler turns into SIMD instructions.
+
+use thiserror::Error;
+
+/// Values updated together by the inner loops.
+const LANES: usize = 4;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum DenseLuError {
+    #[error("dense LU: no nonzero pivot in column {column}")]
+    Singular { column: usize },
+    #[error("dense LU: expected {expected} values for the matrix, got {actual}")]
+    DimensionMismatch { expected: usize, actual: usize },
+}
+
+/// The LU factors of an `n` x `n` matrix, `P*A = L*U`, with their memory kept across
+/// factorizations.
+#[derive(Debug, Clone)]
+pub struct DenseLu {
+    n: usize,
+    /// `L` below the diagonal (its unit diagonal not stored) and `U` on and above, row-major.
+    lu: Vec<f64>,
+    /// Row `k` was swapped with row `pivots[k]` at step `k`.
+    pivots: Vec<usize>,
+}
+
+impl DenseLu {
+    pub fn new(n: usize) -> Self {
+        Self {
+            n,
+            lu: vec![0.0; n * n],
+            pivots: vec![0; n],
+        }
+    }
+
+    pub fn dim(&self) -> usize {
+        self.n
+    }
+
+    /// Factor the row-major matrix `a`.
+    pub fn factor(&mut self, a: &[f64]) -> Result<(), DenseLuError> {
+        let n = self.n;
+        if a.len() != n * n {
+            return Err(DenseLuError::DimensionMismatch {
+                expected: n * n,
+                actual: a.len(),
+            });
+        }
+        self.lu.copy_from_slice(a);
+
+        for k in 0..n {
+            let (p, max) =
+                (k..n)
+                    .map(|i| (i, self.lu[i * n + k].abs()))
+                    .fold(
+                        (k, -1.0),
+                        |best, row| if row.1 > best.1 { row } else { best },
+                    );
+            if max == 0.0 || max.is_nan() {
+                return Err(DenseLuError::Singular { column: k });
+            }
+            self.pivots[k] = p;
+            if p != k {
+                let (upper, lower) = self.lu.split_at_mut(p * n);
+                upper[k * n..(k + 1) * n].swap_with_slice(&mut lower[..n]);
+            }
+
+            let (done, rest) = self.lu.split_at_mut((k + 1) * n);
+            let pivot_row = &done[k * n..];
+            let pivot = pivot_row[k];
+            for row in rest.chunks_exact_mut(n) {
+                let l = row[k] / pivot;
+                row[k] = l;
+                if l != 0.0 {
+                    axpy(&mut row[k + 1..], l, &pivot_row[k + 1..]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Overwrite `b` with the solution of `A*x = b`, for the last factored `A`.
+    pub fn solve(&self, b: &mut [f64]) {
+        let n = self.n;
+        debug_assert_eq!(b.len(), n);
+        for (k, &p) in self.pivots.iter().enumerate() {
+            b.swap(k, p);
+        }
+        // L*y = P*b
+        for i in 1..n {
+            let row = &self.lu[i * n..i * n + i];
+            b[i] -= dot(row, &b[..i]);
+        }
+        // U*x = y
+        for i in (0..n).rev() {
+            let row = &self.lu[i * n..(i + 1) * n];
+            b[i] = (b[i] - dot(&row[i + 1..], &b[i + 1..])) / row[i];
+        }
+    }
+}
+
+/// `y -= a * x`.
+#[inline]
+fn axpy(y: &mut [f64], a: f64, x: &[f64]) {
+    let mut ys = y.chunks_exact_mut(LANES);
+    let mut xs = x.chunks_exact(LANES);
+    for (y, x) in (&mut ys).zip(&mut xs) {
+        for lane in 0..LANES {
+            y[lane] -= a * x[lane];
+        }
+    }
+    for (y, x) in ys.into_remainder().iter_mut().zip(xs.remainder()) {
+        *y -= a * x;
+    }
+}
+
+/// `x . y`, summed in [`LANES`] separate accumulators.
+#[inline]
+fn dot(x: &[f64], y: &[f64]) -> f64 {
+    let mut sums = [0.0; LANES];
+    let mut xs = x.chunks_exact(LANES);
+    let mut ys = y.chunks_exact(LANES);
+    for (x, y) in (&mut xs).zip(&mut ys) {
+        for lane in 0..LANES {
+            sums[lane] += x[lane] * y[lane];
+        }
+    }
+    let tail: f64 = xs
+        .remainder()
+        .iter()
+        .zip(ys.remainder())
+        .map(|(x, y)| x * y)
+        .sum();
+    (sums[0] + sums[1]) + (sums[2] + sums[3]) + tail
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `A*x`, `a` row-major.
+    fn multiply(a: &[f64], x: &[f64]) -> Vec<f64> {
+        a.chunks_exact(x.len())
+            .map(|row| row.iter().zip(x).map(|(a, x)| a * x).sum())
+            .collect()
+    }
+
+    #[test]
+    fn solves_a_system_that_needs_pivoting() {
+        // a zero in the top left corner
+        let a = [0.0, 2.0, 1.0, 1.0, 1.0, 0.0, 2.0, 0.0, 3.0];
+        let mut lu = DenseLu::new(3);
+        lu.factor(&a).unwrap();
+        let mut b = multiply(&a, &[1.0, -2.0, 3.0]);
+        lu.solve(&mut b);
+        for (x, expected) in b.iter().zip([1.0, -2.0, 3.0]) {
+            assert!((x - expected).abs() < 1e-14, "{x} vs {expected}");
+        }
+    }
+
+    #[test]
+    fn solves_systems_longer_than_the_lanes() {
+        // a resistor ladder with a voltage source branch, as an MNA matrix
+        for n in [5, 13, 40] {
+            let mut a = vec![0.0; n * n];
+            for i in 0..n - 1 {
+                a[i * n + i] += 2.0 + i as f64 * 0.01;
+                if i + 1 < n - 1 {
+                    a[i * n + i + 1] = -1.0;
+                    a[(i + 1) * n + i] = -1.0;
+                }
+            }
+            a[(n - 1) * n] = 1.0;
+            a[n - 1] = 1.0;
+            let expected: Vec<f64> = (0..n).map(|i| (i as f64).sin()).collect();
+            let mut b = multiply(&a, &expected);
+            let mut lu = DenseLu::new(n);
+            lu.factor(&a).unwrap();
+            lu.solve(&mut b);
+            for (i, (x, e)) in b.iter().zip(&expected).enumerate() {
+                assert!((x - e).abs() < 1e-12, "n={n}: x[{i}] = {x}, expected {e}");
+            }
+        }
+    }
+
+    #[test]
+    fn reports_the_singular_column() {
+        let a = [1.0, 2.0, 2.0, 4.0];
+        let mut lu = DenseLu::new(2);
+        assert_eq!(lu.factor(&a), Err(DenseLuError::Singular { column: 1 }));
+    }
+}
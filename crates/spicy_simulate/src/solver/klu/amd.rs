@@ -15,9 +15,15 @@ use crate::solver::{
     aat::{aat_first_phase, aat_second_phase},
     matrix::csc::CscPointers,
 };
-
-/// Assumes A is square with sorted columns and no duplicates
-pub fn amd(a: CscPointers, permutation: &mut [isize]) -> solver::amd::AmdInfo {
+use spicy_record::Recorder;
+
+/// Assumes A is square with sorted columns and no duplicates. The elimination is reported to
+/// `recorder` with the `record` feature (see [`solver::amd::amd`]).
+pub fn amd<R: Recorder>(
+    a: CscPointers,
+    permutation: &mut [isize],
+    recorder: &mut R,
+) -> solver::amd::AmdInfo {
     debug_assert!(a.check_invariants().is_ok());
 
     let n = a.dim.ncols;
@@ -91,6 +97,7 @@ pub fn amd(a: CscPointers, permutation: &mut [isize]) -> solver::amd::AmdInfo {
         degree,
         w,
         AmdControl::default(),
+        recorder,
     )
 }
 
@@ -99,6 +106,7 @@ mod tests {
     use super::amd;
     use crate::solver::matrix::Dim;
     use crate::solver::matrix::csc::CscPointers;
+    use spicy_record::NullRecorder;
 
     #[test]
     fn amd_regression_matrix_5x5_ap_ai() {
@@ -115,7 +123,7 @@ mod tests {
         a.check_invariants().unwrap();
 
         let mut p = vec![0isize; n];
-        let info = amd(a, &mut p);
+        let info = amd(a, &mut p, &mut NullRecorder);
 
         // For such a small n, the "dense" threshold (>=16) should not trigger.
         assert_eq!(info.ndense, 0);
@@ -11,8 +11,12 @@
 
 use std::cmp::max;
 
+use spicy_record::NullRecorder;
+
 use crate::solver::{
-    klu::{KluConfig, KluOrdering, KluResult, KluSymbolic, amd::amd, btf::btf, klu_valid},
+    klu::{
+        KluConfig, KluError, KluOrdering, KluResult, KluSymbolic, amd::amd, btf::btf, klu_valid,
+    },
     matrix::{
         Dim,
         csc::{CscMatrix, CscPointers},
@@ -73,7 +77,7 @@ fn analyze_worker(
     mut block_row_pointers: Vec<usize>,
     _ci_len: usize,
     mut row_inv_permutations: Vec<isize>,
-) {
+) -> KluResult<()> {
     let n = symbolic.n;
 
     // TODO: this doesn't have to happen in this funciton tbh
@@ -126,7 +130,27 @@ fn analyze_worker(
                 block_row_permutation[k] = k as isize;
             }
             lnz1 = size as f64 * (size as f64 + 1.) / 2.;
-        } else if symbolic.ordering == KluOrdering::Amd {
+        } else if let KluOrdering::Custom(order) = &symbolic.ordering {
+            let submatrix = block_matrix(
+                a,
+                &btf_column_permutation[k1..k2],
+                &row_inv_permutations,
+                k1,
+            );
+            let perm = order(&submatrix);
+            let mut seen = vec![false; size];
+            for (k, &p) in perm.iter().enumerate().take(size) {
+                if p >= size || std::mem::replace(&mut seen[p], true) {
+                    break;
+                }
+                block_row_permutation[k] = p as isize;
+            }
+            if perm.len() != size || seen.contains(&false) {
+                return Err(KluError::InvalidOrdering { block, size });
+            }
+            // like a given ordering in SuiteSparse, the fill is unknown until factorization
+            lnz1 = EMPTY as f64;
+        } else {
             let block_ptrs = CscPointers::new(
                 Dim {
                     nrows: size,
@@ -136,14 +160,20 @@ fn analyze_worker(
                 &block_row_pointers[..pc],
             );
 
-            let info = amd(block_ptrs, &mut block_row_permutation[..size]);
+            let info = amd(
+                block_ptrs,
+                &mut block_row_permutation[..size],
+                &mut NullRecorder,
+            );
             lnz1 = info.lnz + size as f64;
-        } else {
-            todo!()
         }
 
         symbolic.lower_nz[block] = lnz1;
-        lnz += lnz1;
+        lnz = if lnz < 0. || lnz1 < 0. {
+            EMPTY as f64
+        } else {
+            lnz + lnz1
+        };
 
         // combine the preordering with the btf ordering
         for k in 0..size {
@@ -160,16 +190,52 @@ fn analyze_worker(
     symbolic.lnz = lnz;
     symbolic.unz = lnz;
     symbolic.nzoff = nzoff;
+    Ok(())
+}
+
+/// The diagonal block of the BTF form made of the columns `columns` of `a` (rows mapped with
+/// `row_inv_permutations` and shifted by `k1`), with its rows sorted within every column.
+fn block_matrix(
+    a: &CscMatrix,
+    columns: &[isize],
+    row_inv_permutations: &[isize],
+    k1: usize,
+) -> CscMatrix {
+    let size = columns.len();
+    let mut block = CscMatrix {
+        dim: Dim {
+            nrows: size,
+            ncols: size,
+        },
+        column_pointers: vec![0],
+        row_indices: Vec::new(),
+        values: Vec::new(),
+    };
+    let mut entries = Vec::new();
+    for &old_col in columns {
+        entries.clear();
+        for p in a.col_start(old_col as usize)..a.col_end(old_col as usize) {
+            let new_row = row_inv_permutations[a.row_index(p)] as usize;
+            if (k1..k1 + size).contains(&new_row) {
+                entries.push((new_row - k1, a.values[p]));
+            }
+        }
+        entries.sort_unstable_by_key(|&(row, _)| row);
+        block
+            .row_indices
+            .extend(entries.iter().map(|&(row, _)| row));
+        block.values.extend(entries.iter().map(|&(_, value)| value));
+        block.column_pointers.push(block.row_indices.len());
+    }
+    block
 }
 
 // a was already is validated by the caller to be a valid CSC matrix
 pub fn analyze(a: &CscMatrix, config: &KluConfig) -> KluResult<KluSymbolic> {
     let mut symbolic = allocate_symbolic(a);
-    symbolic.ordering = config.ordering;
+    symbolic.ordering = config.ordering.clone();
 
-    let ci_len = match symbolic.ordering {
-        KluOrdering::Amd => symbolic.n + 1,
-    };
+    let ci_len = symbolic.n + 1;
 
     // allocate memory for btf
     let mut btf_row_permutation = vec![0; symbolic.n];
@@ -190,8 +256,8 @@ pub fn analyze(a: &CscMatrix, config: &KluConfig) -> KluResult<KluSymbolic> {
 
         // unflip the column permutation if the matrix is structurally singular
         if symbolic.structural_rank < symbolic.n {
-            for col in 0..symbolic.n {
-                symbolic.column_permutation[col] = unflip(symbolic.column_permutation[col]);
+            for q in &mut btf_column_permutation {
+                *q = unflip(*q);
             }
         }
 
@@ -232,7 +298,7 @@ pub fn analyze(a: &CscMatrix, config: &KluConfig) -> KluResult<KluSymbolic> {
         ci,
         ci_len,
         pinv,
-    );
+    )?;
 
     Ok(symbolic)
 }
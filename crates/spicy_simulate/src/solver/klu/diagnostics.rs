@@ -0,0 +1,268 @@
+// SPDX-License-Identifier: LGPL-2.1-or-later
+//
+// This file is based on the SuiteSparse KLU implementation by Timothy A. Davis
+// and Ekanathan Palamadai.
+//
+// KLU, Copyright (c) 2004-2024, University of Florida.  All Rights Reserved.
+// Authors: Timothy A. Davis and Ekanathan Palamadai.
+//
+// Modifications/porting for this project:
+// Copyright (c) 2025 Ido Ben Amram
+
+use crate::solver::{
+    btf_max_transversal::btf_max_transversal,
+    klu::{
+        KluConfig, KluNumeric, KluResult, KluSymbolic, btf::btf, get_pointers_to_lu, solve, tsolve,
+    },
+    matrix::csc::CscMatrix,
+    scalar::Scalar,
+};
+
+/// Cheap reciprocal condition number estimate (`klu_rcond`):
+/// min(abs(diag(U))) / max(abs(diag(U))).
+///
+/// Zero when a pivot is zero or NaN, i.e. the factorization is singular.
+pub fn rcond<T: Scalar>(numeric: &KluNumeric<T>) -> f64 {
+    if numeric.u_diag.is_empty() {
+        return 0.0;
+    }
+    let mut umin = f64::INFINITY;
+    let mut umax = 0.0_f64;
+    for &u in &numeric.u_diag {
+        let ukk = u.to_f64().abs();
+        if ukk == 0.0 || ukk.is_nan() {
+            return 0.0;
+        }
+        umin = umin.min(ukk);
+        umax = umax.max(ukk);
+    }
+    umin / umax
+}
+
+/// Reciprocal pivot growth (`klu_rgrowth`): the smallest ratio, over the columns of the
+/// diagonal blocks, of the largest entry of the (scaled) `A` to the largest entry of `U`.
+///
+/// Close to one for a stable factorization; a small value means the pivoting let the entries
+/// of `U` grow and the solution may be inaccurate.
+pub fn rgrowth<T: Scalar>(
+    a: &CscMatrix,
+    symbolic: &KluSymbolic,
+    numeric: &KluNumeric<T>,
+) -> KluResult<f64> {
+    let mut rgrowth = 1.0_f64;
+    for block in 0..symbolic.nblocks {
+        let k1 = symbolic.block_boundaries[block];
+        let k2 = symbolic.block_boundaries[block + 1];
+        if k2 - k1 == 1 {
+            // a singleton has no growth
+            continue;
+        }
+        let lu = numeric.lu_bx[block].as_slice();
+        for j in 0..k2 - k1 {
+            let oldcol = symbolic.column_permutation[j + k1] as usize;
+            let mut max_ai = 0.0_f64;
+            for p in a.col_start(oldcol)..a.col_end(oldcol) {
+                let newrow = numeric.pinv[a.row_index(p)] as usize;
+                if newrow < k1 {
+                    // entry in an off-diagonal block
+                    continue;
+                }
+                let aij = match &numeric.rs {
+                    Some(rs) => a.value(p) / rs[newrow],
+                    None => a.value(p),
+                };
+                max_ai = max_ai.max(aij.abs());
+            }
+
+            let (_, ux, len) =
+                get_pointers_to_lu::<T>(lu, &numeric.uip[k1..], &numeric.ulen[k1..], j)?;
+            let max_ui = ux[..len]
+                .iter()
+                .fold(numeric.u_diag[j + k1].to_f64().abs(), |acc, u| {
+                    acc.max(u.to_f64().abs())
+                });
+            if max_ui == 0.0 {
+                continue;
+            }
+            rgrowth = rgrowth.min(max_ai / max_ui);
+        }
+    }
+    Ok(rgrowth)
+}
+
+/// Estimate of the 1-norm condition number of `A` (`klu_condest`), using Hager's method as
+/// refined by Higham. Infinite for a singular factorization.
+pub fn condest<T: Scalar>(
+    a: &CscMatrix,
+    symbolic: &KluSymbolic,
+    numeric: &mut KluNumeric<T>,
+    config: &KluConfig,
+) -> KluResult<f64> {
+    let n = symbolic.n;
+    if n == 0 {
+        return Ok(0.0);
+    }
+    if numeric.u_diag.iter().any(|u| *u == T::ZERO || u.is_nan()) {
+        return Ok(f64::INFINITY);
+    }
+
+    // 1-norm of A: the largest column sum
+    let anorm = (0..n)
+        .map(|j| {
+            (a.col_start(j)..a.col_end(j))
+                .map(|p| a.value(p).abs())
+                .sum::<f64>()
+        })
+        .fold(0.0_f64, f64::max);
+
+    // estimate the 1-norm of inv(A)
+    let mut x = vec![1.0 / n as f64; n];
+    let mut s = vec![0.0_f64; n];
+    let mut jmax = 0;
+    let mut ainv_norm = 0.0_f64;
+    for i in 0..5 {
+        if i > 0 {
+            x.fill(0.0);
+            x[jmax] = 1.0;
+        }
+        solve(symbolic, numeric, n, 1, &mut x, config)?;
+        let ainv_norm_old = ainv_norm;
+        ainv_norm = x.iter().map(|v| v.abs()).sum();
+        if i > 0 && ainv_norm <= ainv_norm_old {
+            break;
+        }
+
+        let mut unchanged = true;
+        for (sj, xj) in s.iter_mut().zip(&x) {
+            let sign = if *xj >= 0.0 { 1.0 } else { -1.0 };
+            if sign != *sj {
+                *sj = sign;
+                unchanged = false;
+            }
+        }
+        if i > 0 && unchanged {
+            break;
+        }
+
+        x.copy_from_slice(&s);
+        tsolve(symbolic, numeric, &mut x)?;
+        let mut jnew = 0;
+        let mut xmax = 0.0;
+        for (j, xj) in x.iter().enumerate() {
+            if xj.abs() > xmax {
+                xmax = xj.abs();
+                jnew = j;
+            }
+        }
+        if i > 0 && jnew == jmax {
+            break;
+        }
+        jmax = jnew;
+    }
+
+    // alternating sign vector, guards against the estimate above being too small
+    for (j, xj) in x.iter_mut().enumerate() {
+        let magnitude = if n > 1 {
+            1.0 + j as f64 / (n - 1) as f64
+        } else {
+            1.0
+        };
+        *xj = if j % 2 == 1 { -magnitude } else { magnitude };
+    }
+    solve(symbolic, numeric, n, 1, &mut x, config)?;
+    let est_new = 2.0 * x.iter().map(|v| v.abs()).sum::<f64>() / (3 * n) as f64;
+    ainv_norm = ainv_norm.max(est_new);
+
+    Ok(ainv_norm * anorm)
+}
+
+/// The block triangular form of a matrix (`btf_order`), and where it is structurally
+/// singular.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BtfStructure {
+    /// Size of a maximum matching of rows to columns; `n` unless structurally singular.
+    pub structural_rank: usize,
+    /// Diagonal block `b` spans positions `block_boundaries[b]..block_boundaries[b + 1]` of
+    /// the permuted matrix.
+    pub block_boundaries: Vec<usize>,
+    /// Columns of the underdetermined part of the Dulmage-Mendelsohn decomposition: more
+    /// unknowns than the rows that mention them. Empty at full structural rank.
+    pub underdetermined_columns: Vec<usize>,
+    /// Rows of the overdetermined part: more equations than the columns they mention.
+    pub overdetermined_rows: Vec<usize>,
+}
+
+/// Find the block triangular structure of the square matrix `a`, and when its structural
+/// rank is short of `n`, the rows and columns that make it singular.
+pub fn btf_structure(a: &CscMatrix) -> BtfStructure {
+    let n = a.dim.ncols;
+    let mut row_permutation = vec![0; n];
+    let mut column_permutation = vec![-1; n];
+    let mut blocks = vec![0; n + 1];
+    let (structural_rank, nblocks) = btf(
+        a,
+        &mut row_permutation,
+        &mut column_permutation,
+        &mut blocks,
+    );
+    blocks.truncate(nblocks + 1);
+
+    let mut structure = BtfStructure {
+        structural_rank,
+        block_boundaries: blocks,
+        underdetermined_columns: Vec::new(),
+        overdetermined_rows: Vec::new(),
+    };
+    if structural_rank == n {
+        return structure;
+    }
+
+    // the matching itself: row i is matched to column row_match[i]
+    let mut row_match = vec![-1; n];
+    btf_max_transversal(a, &mut row_match);
+    let mut column_match = vec![None; n];
+    let mut row_columns = vec![Vec::new(); n];
+    for (i, &j) in row_match.iter().enumerate() {
+        if j >= 0 {
+            column_match[j as usize] = Some(i);
+        }
+    }
+    for j in 0..n {
+        for p in a.col_start(j)..a.col_end(j) {
+            row_columns[a.row_index(p)].push(j);
+        }
+    }
+
+    // alternating paths from the unmatched columns: column -> its rows -> their columns
+    let mut seen = vec![false; n];
+    let mut stack: Vec<usize> = (0..n).filter(|&j| column_match[j].is_none()).collect();
+    stack.iter().for_each(|&j| seen[j] = true);
+    while let Some(j) = stack.pop() {
+        structure.underdetermined_columns.push(j);
+        for p in a.col_start(j)..a.col_end(j) {
+            let next = row_match[a.row_index(p)];
+            if next >= 0 && !std::mem::replace(&mut seen[next as usize], true) {
+                stack.push(next as usize);
+            }
+        }
+    }
+
+    // alternating paths from the unmatched rows: row -> its columns -> their rows
+    let mut seen = vec![false; n];
+    let mut stack: Vec<usize> = (0..n).filter(|&i| row_match[i] < 0).collect();
+    stack.iter().for_each(|&i| seen[i] = true);
+    while let Some(i) = stack.pop() {
+        structure.overdetermined_rows.push(i);
+        for &j in &row_columns[i] {
+            if let Some(next) = column_match[j]
+                && !std::mem::replace(&mut seen[next], true)
+            {
+                stack.push(next);
+            }
+        }
+    }
+
+    structure.underdetermined_columns.sort_unstable();
+    structure.overdetermined_rows.sort_unstable();
+    structure
+}
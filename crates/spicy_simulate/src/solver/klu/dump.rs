@@ -5,6 +5,7 @@
 use std::io;
 
 use super::{KluNumeric, KluSymbolic};
+use crate::solver::scalar::Scalar;
 
 /// Binary permutation dump file magic (`SPKLPERM`).
 ///
@@ -35,11 +36,11 @@ pub enum KluPermDumpStage {
 /// - i32[nblocks+1]: R (block boundaries; only the used prefix is written)
 /// - i32[n]: Pnum (numeric pivot permutation)
 /// - i32[n]: Pinv (inverse pivot permutation)
-pub fn write_perm_dump<W: io::Write>(
+pub fn write_perm_dump<W: io::Write, T: Scalar>(
     mut w: W,
     stage: KluPermDumpStage,
     symbolic: &KluSymbolic,
-    numeric: &KluNumeric,
+    numeric: &KluNumeric<T>,
 ) -> io::Result<()> {
     fn write_u32_le<W: io::Write>(w: &mut W, v: u32) -> io::Result<()> {
         w.write_all(&v.to_le_bytes())
@@ -20,6 +20,9 @@ pub enum KluError {
         actual: usize,
     },
 
+    #[error("custom ordering of block {block} is not a permutation of 0..{size}")]
+    InvalidOrdering { block: usize, size: usize },
+
     // --- Matrix properties ---
     #[error("KLU only supports square matrices (nrows={nrows}, ncols={ncols})")]
     NonSquareMatrix { nrows: usize, ncols: usize },
@@ -14,11 +14,15 @@ use crate::solver::klu::{
 };
 use crate::solver::klu::{kernel, klu_valid, klu_valid_lu};
 use crate::solver::matrix::csc::CscMatrix;
+use crate::solver::scalar::Scalar;
 use crate::solver::utils::{
-    as_usize_slice_mut, dunits, f64_as_isize_slice_mut, inverse_permutation,
+    as_usize_slice_mut, dunits, f64_as_isize_slice_mut, inverse_permutation, units_as_scalars_mut,
 };
 
-pub fn allocate_klu_numeric(symbolic: &KluSymbolic, config: &KluConfig) -> KluResult<KluNumeric> {
+pub fn allocate_klu_numeric<T: Scalar>(
+    symbolic: &KluSymbolic,
+    config: &KluConfig,
+) -> KluResult<KluNumeric<T>> {
     let n = symbolic.n;
     let nzoff = symbolic.nzoff;
     let nblocks = symbolic.nblocks;
@@ -69,7 +73,7 @@ pub fn allocate_klu_numeric(symbolic: &KluSymbolic, config: &KluConfig) -> KluRe
         pnum: vec![0; n],
         offp: vec![0; n1],
         offi: vec![0; nzoff1],
-        offx: vec![0.0; nzoff1],
+        offx: vec![T::ZERO; nzoff1],
 
         lip: vec![0; n],
         uip: vec![0; n],
@@ -79,7 +83,7 @@ pub fn allocate_klu_numeric(symbolic: &KluSymbolic, config: &KluConfig) -> KluRe
         lu_size: vec![0; nblocks],
         lu_bx,
 
-        u_diag: vec![0.0; n],
+        u_diag: vec![T::ZERO; n],
         rs,
         pinv: vec![0; n],
 
@@ -92,7 +96,7 @@ pub fn allocate_klu_numeric(symbolic: &KluSymbolic, config: &KluConfig) -> KluRe
     Ok(numeric)
 }
 
-pub fn kernel_factor(
+pub fn kernel_factor<T: Scalar>(
     n: usize,
     a: &CscMatrix,
     col_permutation: &[isize],
@@ -104,7 +108,7 @@ pub fn kernel_factor(
 
     // outputs
     lu_block: &mut Vec<f64>,
-    u_diag: &mut [f64],
+    u_diag: &mut [T],
     llen: &mut [usize],
     ulen: &mut [usize],
     lip: &mut [usize],
@@ -116,10 +120,10 @@ pub fn kernel_factor(
     // inputs, modified on output
     offp: &mut [usize],
     offi: &mut [usize],
-    offx: &mut [f64],
+    offx: &mut [T],
 
     // workspace
-    x: &mut [f64],
+    x: &mut [T],
     work: &mut [f64],
     metrics: &mut KluNumericMetrics,
     config: &KluConfig,
@@ -150,9 +154,9 @@ pub fn kernel_factor(
 
     let stack = unsafe { as_usize_slice_mut(stack) };
     let lusize = dunits::<isize>(l_size)?
-        + dunits::<f64>(l_size)?
+        + dunits::<T>(l_size)?
         + dunits::<isize>(u_size)?
-        + dunits::<f64>(u_size)?;
+        + dunits::<T>(u_size)?;
 
     lu_block.resize(lusize as usize, 0.0);
 
@@ -187,11 +191,11 @@ pub fn kernel_factor(
     )
 }
 
-pub fn factor(
+pub fn factor<T: Scalar>(
     a: &CscMatrix,
     symbolic: &mut KluSymbolic,
     config: &mut KluConfig,
-) -> KluResult<KluNumeric> {
+) -> KluResult<KluNumeric<T>> {
     config.validate()?;
     let mut numeric = allocate_klu_numeric(symbolic, config)?;
 
@@ -231,7 +235,7 @@ pub fn factor(
             let oldcol = symbolic.column_permutation[k1] as usize;
             let start = a.col_start(oldcol);
             let end = a.col_end(oldcol);
-            let mut diag_val = 0.;
+            let mut diag_val = T::ZERO;
 
             for p in start..end {
                 let oldrow = a.row_index(p);
@@ -248,7 +252,7 @@ pub fn factor(
                         Some(rs) => a.value(p) / rs[oldrow],
                     };
 
-                    numeric.offx[poff] = val;
+                    numeric.offx[poff] = T::from_f64(val);
                     poff += 1;
                 } else {
                     debug_assert!(newrow == k1);
@@ -256,12 +260,12 @@ pub fn factor(
                         None => a.value(p),
                         Some(rs) => a.value(p) / rs[oldrow],
                     };
-                    diag_val = val;
+                    diag_val = T::from_f64(val);
                 }
             }
 
             numeric.u_diag[k1] = diag_val;
-            if diag_val == 0. {
+            if diag_val == T::ZERO {
                 if numeric.metrics.numerical_rank.is_none() {
                     numeric.metrics.numerical_rank = Some(k1);
                     numeric.metrics.singular_col = Some(oldcol);
@@ -304,7 +308,7 @@ pub fn factor(
                 &mut numeric.offp,
                 &mut numeric.offi,
                 &mut numeric.offx,
-                x,
+                units_as_scalars_mut(x),
                 work,
                 &mut numeric.metrics,
                 config,
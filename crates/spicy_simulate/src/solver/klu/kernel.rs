@@ -15,6 +15,7 @@ use crate::solver::{
         get_pointers_to_lu_mut,
     },
     matrix::csc::CscMatrix,
+    scalar::Scalar,
     utils::{EMPTY, dunits, f64_as_usize_slice, f64_as_usize_slice_mut, flip, unflip},
 };
 
@@ -187,12 +188,12 @@ fn lsolve_symbolic(
 }
 
 #[inline(never)]
-fn construct_column(
+fn construct_column<T: Scalar>(
     k: usize,
     a: &CscMatrix,
     col_permutation: &[isize],
 
-    x: &mut [f64],
+    x: &mut [T],
 
     k1: usize,
     psinv: &[isize],
@@ -200,7 +201,7 @@ fn construct_column(
 
     offp: &mut [usize],
     offi: &mut [usize],
-    offx: &mut [f64],
+    offx: &mut [T],
 ) {
     let kglobal = k + k1;
     let mut poff = offp[kglobal];
@@ -212,13 +213,13 @@ fn construct_column(
         let oldrow = a.row_index(p);
         let i = psinv[oldrow] - k1 as isize;
 
-        let val = match row_scaling {
+        let val = T::from_f64(match row_scaling {
             Some(rs) => {
                 let val = a.value(p);
                 val / rs[oldrow]
             }
             None => a.value(p),
-        };
+        });
 
         if i < 0 {
             // this is an entry in the off-diagonal part
@@ -239,7 +240,7 @@ fn construct_column(
 // may include explicit zeros if numerical cancelation occurs.  L is assumed
 // to be unit-diagonal, with possibly unsorted columns (but the first entry in
 // the column must always be the diagonal entry).
-fn lsolve_numeric(
+fn lsolve_numeric<T: Scalar>(
     inverse_row_permutation: &[isize],
     lu: &[f64],
     stack: &[usize],
@@ -249,7 +250,7 @@ fn lsolve_numeric(
     llen: &[usize],
 
     // on output X [Ui [up1..up-1]] and X [Li [lp1..lp-1]]
-    x: &mut [f64],
+    x: &mut [T],
 ) -> KluResult<()> {
     // solve Lx=b
     for s in top..n {
@@ -259,10 +260,10 @@ fn lsolve_numeric(
         let xj = x[j];
         // Common in practice due to structural pattern being a superset and/or cancellation.
         // Skipping saves a full scatter update (memory-bound).
-        if xj == 0.0 {
+        if xj == T::ZERO {
             continue;
         }
-        let (li, lx, len) = get_pointers_to_lu(lu, lip, llen, jnew)?;
+        let (li, lx, len) = get_pointers_to_lu::<T>(lu, lip, llen, jnew)?;
         debug_assert!(lip[jnew] <= lip[jnew + 1]);
         // This is fundamentally a scatter RMW into `x` (typically memory-bound).
         // We optimize by:
@@ -320,13 +321,13 @@ fn lsolve_numeric(
     Ok(())
 }
 
-fn lpivot(
+fn lpivot<T: Scalar>(
     diag_row: usize,
     p_pivrow: &mut usize,
-    p_pivot: &mut f64,
-    p_abs_pivot: &mut f64,
-    tol: f64,
-    x: &mut [f64],
+    p_pivot: &mut T,
+    p_abs_pivot: &mut T,
+    tol: T,
+    x: &mut [T],
     lu: &mut [f64],
     lip: &[usize],
     llen: &mut [usize],
@@ -355,17 +356,17 @@ fn lpivot(
 
         debug_assert!(piv_row >= 0 && piv_row < n as isize);
         *p_pivrow = piv_row as usize;
-        *p_pivot = 0.0;
-        *p_abs_pivot = 0.0;
+        *p_pivot = T::ZERO;
+        *p_abs_pivot = T::ZERO;
         *p_firstrow = piv_row as usize;
         return Ok(false);
     }
 
     let mut pdiag = EMPTY;
     let mut ppivrow = EMPTY;
-    let mut abs_pivot = -1.0;
+    let mut abs_pivot = -T::ONE;
     let i = llen[k] - 1;
-    let (li, _lx, _) = get_pointers_to_lu(lu, lip, llen, k)?;
+    let (li, _lx, _) = get_pointers_to_lu::<T>(lu, lip, llen, k)?;
     let last_row_index = li[i];
 
     // decrement the length by 1
@@ -377,7 +378,7 @@ fn lpivot(
         // gather the entry from X and store in L
         let i = li[p];
         let val = x[i];
-        x[i] = 0.0;
+        x[i] = T::ZERO;
 
         lx[p] = val;
         let val_abs = val.abs();
@@ -425,14 +426,14 @@ fn lpivot(
         piv_row = last_row_index as isize;
         pivot = x[last_row_index];
     }
-    x[last_row_index] = 0.0;
+    x[last_row_index] = T::ZERO;
 
     debug_assert!(piv_row >= 0 && piv_row < n as isize);
     *p_pivrow = piv_row as usize;
     *p_pivot = pivot;
     *p_abs_pivot = abs_pivot;
 
-    if pivot == 0.0 && config.halt_if_singular {
+    if pivot == T::ZERO && config.halt_if_singular {
         return Err(KluError::StructurallySingular);
     }
 
@@ -444,7 +445,7 @@ fn lpivot(
     Ok(true)
 }
 
-fn prune(
+fn prune<T: Scalar>(
     // lpend[j] marks symmetric pruning point for L(:,j)
     lpend: &mut [isize],
 
@@ -468,13 +469,13 @@ fn prune(
         // Get Ui only for this iteration, so the immutable borrow of `lu`
         // does not overlap with the mutable borrow we take for L below.
         let j = {
-            let (ui, _, _) = get_pointers_to_lu(lu, uip, ulen, k)?;
+            let (ui, _, _) = get_pointers_to_lu::<T>(lu, uip, ulen, k)?;
             ui[p]
         };
         debug_assert!(j < k);
         if lpend[j] == EMPTY {
             // scan column j of L for the pivot row
-            let (li, lx, l_len) = get_pointers_to_lu_mut(lu, lip, llen, j)?;
+            let (li, lx, l_len) = get_pointers_to_lu_mut::<T>(lu, lip, llen, j)?;
             for p2 in 0..l_len {
                 if pivrow == li[p2] {
                     // this column can be pruned
@@ -512,7 +513,7 @@ fn prune(
     Ok(())
 }
 
-pub fn kernel(
+pub fn kernel<T: Scalar>(
     n: usize,
     a: &CscMatrix,
     col_permutation: &[isize],
@@ -525,7 +526,7 @@ pub fn kernel(
     row_permutation: &mut [isize],
 
     lu_block: &mut Vec<f64>,
-    u_diag: &mut [f64],
+    u_diag: &mut [T],
     llen: &mut [usize],
     ulen: &mut [usize],
     lip: &mut [usize],
@@ -533,7 +534,7 @@ pub fn kernel(
     lnz: &mut usize,
     unz: &mut usize,
 
-    x: &mut [f64],
+    x: &mut [T],
 
     stack: &mut [usize],
     flag: &mut [isize],
@@ -549,7 +550,7 @@ pub fn kernel(
     // TODO: this is technically a csc matrix?
     offp: &mut [usize],
     offi: &mut [usize],
-    offx: &mut [f64],
+    offx: &mut [T],
     metrics: &mut KluNumericMetrics,
     config: &KluConfig,
 ) -> KluResult<usize> {
@@ -557,15 +558,15 @@ pub fn kernel(
     *unz = 0;
 
     let mut piv_row = 0;
-    let mut abs_pivot = 0.0;
-    let mut pivot = 0.0;
+    let mut abs_pivot = T::ZERO;
+    let mut pivot = T::ZERO;
     let mut first_row = 0;
 
     // lu_pointer
     let mut lup = 0;
 
     for k in 0..n {
-        x[k] = 0.0;
+        x[k] = T::ZERO;
         flag[k] = EMPTY;
         lpend[k] = EMPTY;
     }
@@ -584,10 +585,8 @@ pub fn kernel(
     for k in 0..n {
         // (n - k) entries for L and k entries for U
         // number of rows in lower triangle goes down, upper triangle goes up.
-        let col_max_size = dunits::<usize>(n - k)?
-            + dunits::<usize>(k)?
-            + dunits::<f64>(n - k)?
-            + dunits::<f64>(k)?;
+        let col_max_size =
+            dunits::<usize>(n - k)? + dunits::<usize>(k)? + dunits::<T>(n - k)? + dunits::<T>(k)?;
 
         let max_matrix_size = lup + col_max_size;
         if max_matrix_size > lusize {
@@ -663,7 +662,7 @@ pub fn kernel(
             &mut piv_row,
             &mut pivot,
             &mut abs_pivot,
-            config.tol,
+            T::from_f64(config.tol),
             x,
             lu_block,
             lip,
@@ -687,7 +686,7 @@ pub fn kernel(
         debug_assert!(piv_row < n);
         debug_assert!(inverse_row_permutation[piv_row] < 0);
 
-        let lower_col_length = dunits::<usize>(llen[k])? + dunits::<f64>(llen[k])?;
+        let lower_col_length = dunits::<usize>(llen[k])? + dunits::<T>(llen[k])?;
 
         // set the Uip pointer
         uip[k] = lip[k] + lower_col_length;
@@ -699,7 +698,7 @@ pub fn kernel(
         ulen[k] = n - top;
 
         // extract Stack [top..n-1] to Ui and the values to Ux and clear X
-        let (ui, ux, _) = get_pointers_to_lu_mut(lu_block, uip, ulen, k)?;
+        let (ui, ux, _) = get_pointers_to_lu_mut::<T>(lu_block, uip, ulen, k)?;
         let mut i = 0;
         for p in top..n {
             let j = stack[p];
@@ -708,11 +707,11 @@ pub fn kernel(
             );
             ui[i] = inverse_row_permutation[j] as usize;
             ux[i] = x[j];
-            x[j] = 0.0;
+            x[j] = T::ZERO;
             i += 1;
         }
 
-        lup += dunits::<usize>(ulen[k])? + dunits::<f64>(ulen[k])?;
+        lup += dunits::<usize>(ulen[k])? + dunits::<T>(ulen[k])?;
 
         // U(k,k) = pivot
         u_diag[k] = pivot;
@@ -742,7 +741,7 @@ pub fn kernel(
         row_permutation[k] = piv_row as isize;
         inverse_row_permutation[piv_row] = k as isize;
 
-        prune(
+        prune::<T>(
             lpend,
             inverse_row_permutation,
             k,
@@ -760,7 +759,7 @@ pub fn kernel(
 
     // finalize column pointers for L and U, and put L in the pivotal order
     for p in 0..n {
-        let (li, _, len) = get_pointers_to_lu_mut(lu_block, lip, llen, p)?;
+        let (li, _, len) = get_pointers_to_lu_mut::<T>(lu_block, lip, llen, p)?;
         for i in 0..len {
             li[i] = inverse_row_permutation[li[i]] as usize;
         }
@@ -12,6 +12,7 @@
 mod amd;
 mod analyze;
 mod btf;
+mod diagnostics;
 mod dump;
 mod error;
 mod factor;
@@ -19,8 +20,16 @@ mod kernel;
 mod refactor;
 mod scale;
 mod solve;
+mod tsolve;
 
-use crate::solver::utils::{dunits, f64_as_usize_slice, f64_as_usize_slice_mut};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::solver::matrix::csc::CscMatrix;
+use crate::solver::scalar::Scalar;
+use crate::solver::utils::{
+    dunits, f64_as_usize_slice, f64_as_usize_slice_mut, units_as_scalars, units_as_scalars_mut,
+};
 pub use dump::{
     KLU_PERM_DUMP_MAGIC, KLU_PERM_DUMP_VERSION, KLU_SOLVE_DUMP_MAGIC, KLU_SOLVE_DUMP_VERSION,
     KluPermDumpStage, write_perm_dump, write_solve_dump,
@@ -28,11 +37,13 @@ pub use dump::{
 pub use error::{KluError, KluResult};
 // TODO: might be more correct to move this outside of klu module
 pub use btf::btf;
+pub use diagnostics::{BtfStructure, btf_structure, condest, rcond, rgrowth};
 pub use analyze::{allocate_symbolic, analyze};
 pub use amd::amd;
 pub use factor::factor;
 pub use refactor::refactor;
 pub use solve::solve;
+pub use tsolve::tsolve;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum KluScale {
@@ -41,12 +52,50 @@ enum KluScale {
     Max,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum KluOrdering {
+/// A fill-reducing ordering of one diagonal block of the BTF form: `perm[k]` is the block
+/// column (and row) placed at position `k`.
+pub type KluOrderingFn = dyn Fn(&CscMatrix) -> Vec<usize> + Send + Sync;
+
+/// How the diagonal blocks of the BTF form are ordered before factorization.
+#[derive(Clone)]
+pub enum KluOrdering {
     Amd,
+    /// Mirrors `klu_common.user_order`: the function gets each diagonal block larger than 3x3,
+    /// rows sorted within every column, and returns its permutation. Shared so the config stays
+    /// cheap to clone across analyses.
+    Custom(Arc<KluOrderingFn>),
+}
+
+impl KluOrdering {
+    pub fn custom(order: impl Fn(&CscMatrix) -> Vec<usize> + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(order))
+    }
+
+    /// AMD, with the elimination of every block it orders recorded and pushed to `traces`, for
+    /// [`crate::solver::quotient_graph`] to draw. Like any custom ordering, the fill of the
+    /// blocks is left to the factorization to find.
+    #[cfg(feature = "record")]
+    pub fn recorded_amd(traces: Arc<std::sync::Mutex<Vec<spicy_record::VecRecorder>>>) -> Self {
+        Self::custom(move |block: &CscMatrix| {
+            let mut recorder = spicy_record::VecRecorder::new();
+            let mut permutation = vec![0; block.dim.ncols];
+            amd(block.as_pointers(), &mut permutation, &mut recorder);
+            traces.lock().unwrap().push(recorder);
+            permutation.into_iter().map(|k| k as usize).collect()
+        })
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+impl fmt::Debug for KluOrdering {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Amd => f.write_str("Amd"),
+            Self::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct KluConfig {
     /* pivot tolerance for diagonal preference */
     tol: f64,
@@ -87,6 +136,11 @@ impl Default for KluConfig {
 }
 
 impl KluConfig {
+    pub fn with_ordering(mut self, ordering: KluOrdering) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
     fn validate(&mut self) -> KluResult<()> {
         self.initmem_amd = self.initmem_amd.max(1.);
         self.initmem = self.initmem.max(10.);
@@ -119,6 +173,29 @@ pub struct KluSymbolic {
     block_boundaries: Vec<usize>,
 }
 
+impl KluSymbolic {
+    /// Row permutation `P` chosen by the ordering (BTF + AMD).
+    pub fn row_permutation(&self) -> &[isize] {
+        &self.row_permutation
+    }
+
+    /// Column permutation `Q` chosen by the ordering (BTF + AMD).
+    pub fn column_permutation(&self) -> &[isize] {
+        &self.column_permutation
+    }
+
+    /// Structural rank found by BTF; zero when the analysis ran without BTF.
+    pub fn structural_rank(&self) -> usize {
+        self.structural_rank
+    }
+
+    /// Diagonal block `b` spans positions `block_boundaries()[b]..block_boundaries()[b + 1]`
+    /// of the permuted matrix.
+    pub fn block_boundaries(&self) -> &[usize] {
+        &self.block_boundaries[..=self.nblocks]
+    }
+}
+
 /// Statistics produced by numeric factorization/refactorization.
 ///
 /// In the original SuiteSparse KLU implementation these live in `KLU_common`.
@@ -136,7 +213,8 @@ pub struct KluNumericMetrics {
     pub singular_col: Option<usize>,
 }
 
-pub struct KluNumeric {
+/// The LU factors of a matrix, with their values in `T`.
+pub struct KluNumeric<T: Scalar = f64> {
     // A is n-by-n
     pub n: usize,
     // number of diagonal blocks
@@ -162,12 +240,12 @@ pub struct KluNumeric {
     pub llen: Vec<usize>,
     // size n. Ulen [k] = # of entries in kth column of U
     pub ulen: Vec<usize>,
-    // L and U indices and entries (excl. diagonal of U)
+    // L and U indices and entries (excl. diagonal of U), packed in f64 units
     pub lu_bx: Vec<Vec<f64>>,
     // size of each LUbx [block], in sizeof (f64)
     pub lu_size: Vec<usize>,
     // diagonal of U
-    pub u_diag: Vec<f64>,
+    pub u_diag: Vec<T>,
 
     // scale factors; can be NULL if no scaling
     // size n. Rs [i] is scale factor for row i
@@ -175,7 +253,7 @@ pub struct KluNumeric {
 
     // permanent workspace for factorization and solve (size in bytes, as in C)
     pub worksize: usize,
-    // single contiguous workspace buffer backing both Xwork and Iwork in C
+    // single contiguous workspace buffer backing both Xwork and Iwork in C, in f64 units
     pub work: Vec<f64>,
 
     // column pointers for off-diagonal entries
@@ -183,14 +261,14 @@ pub struct KluNumeric {
     // row indices for off-diagonal entries
     pub offi: Vec<usize>,
     // numerical values for off-diagonal entries
-    pub offx: Vec<f64>,
+    pub offx: Vec<T>,
     // number of off-diagonal entries
     pub nzoff: usize,
 
     pub metrics: KluNumericMetrics,
 }
 
-impl std::fmt::Debug for KluNumeric {
+impl<T: Scalar> std::fmt::Debug for KluNumeric<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // NOTE: `lu_bx` and `work` are packed buffers (indices stored inside `Vec<f64>`),
         // and dumping them would be both huge and misleading.
@@ -267,30 +345,34 @@ pub(crate) fn klu_valid(n: usize, column_pointers: &[usize], row_indices: &[usiz
     true
 }
 
-pub(crate) fn get_pointers_to_lu_mut<'a>(
+pub(crate) fn get_pointers_to_lu_mut<'a, T: Scalar>(
     lu: &'a mut [f64],
     xip: &[usize],
     xlen: &[usize],
     k: usize,
-) -> KluResult<(&'a mut [usize], &'a mut [f64], usize)> {
+) -> KluResult<(&'a mut [usize], &'a mut [T], usize)> {
     let (_, xp) = lu.split_at_mut(xip[k]);
     let len = dunits::<usize>(xlen[k])?;
     let (xi, xx) = xp.split_at_mut(len);
 
-    Ok((unsafe { f64_as_usize_slice_mut(xi) }, xx, len))
+    Ok((
+        unsafe { f64_as_usize_slice_mut(xi) },
+        units_as_scalars_mut(xx),
+        len,
+    ))
 }
 
-pub(crate) fn get_pointers_to_lu<'a>(
+pub(crate) fn get_pointers_to_lu<'a, T: Scalar>(
     lu: &'a [f64],
     xip: &[usize],
     xlen: &[usize],
     k: usize,
-) -> KluResult<(&'a [usize], &'a [f64], usize)> {
+) -> KluResult<(&'a [usize], &'a [T], usize)> {
     let (_, xp) = lu.split_at(xip[k]);
     let len = dunits::<usize>(xlen[k])?;
     let (xi, xx) = xp.split_at(len);
 
-    Ok((unsafe { f64_as_usize_slice(xi) }, xx, len))
+    Ok((unsafe { f64_as_usize_slice(xi) }, units_as_scalars(xx), len))
 }
 
 pub(crate) fn klu_valid_lu(
@@ -320,7 +402,8 @@ pub(crate) fn klu_valid_lu(
             }
         }
 
-        let (xi, _, len) = get_pointers_to_lu(lu, xip, xlen, j)?;
+        // the indices come before the values, whatever their type
+        let (xi, _, len) = get_pointers_to_lu::<f64>(lu, xip, xlen, j)?;
         for p in 0..len {
             let i = xi[p];
             if i >= n {
@@ -336,7 +419,13 @@ pub(crate) fn klu_valid_lu(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::solver::matrix::{csc::CscMatrix, mtx::load_matrix_market_csc_file};
+    use crate::solver::matrix::{
+        csc::CscMatrix,
+        mtx::{
+            load_matrix_market_array_file, load_matrix_market_csc_file,
+            load_matrix_market_csc_file_keep_zeros,
+        },
+    };
     use rstest::rstest;
     use std::path::PathBuf;
 
@@ -442,6 +531,58 @@ mod tests {
         b
     }
 
+    /// MNA systems written by `--dump-matrix` (`<name>.mtx`, `<name>_rhs.mtx`), each with the
+    /// exact solution rounded to f64 in `<name>_x.mtx`.
+    #[rstest]
+    fn solves_dumped_mna_systems(
+        #[files("src/solver/tests/reference/*_x.mtx")] reference: PathBuf,
+    ) {
+        let base = reference.to_string_lossy().replace("_x.mtx", "");
+        let a = load_matrix_market_csc_file_keep_zeros(format!("{base}.mtx")).expect("matrix");
+        let mut x = load_matrix_market_array_file(format!("{base}_rhs.mtx")).expect("rhs");
+        let expected = load_matrix_market_array_file(&reference).expect("reference");
+
+        let mut config = KluConfig::default();
+        let mut symbolic = analyze::analyze(&a, &config).expect("analyze");
+        let mut numeric = factor::factor::<f64>(&a, &mut symbolic, &mut config).expect("factor");
+        solve::solve(&symbolic, &mut numeric, symbolic.n, 1, &mut x, &config).expect("solve");
+
+        let scale = expected.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+        for (i, (actual, expected)) in x.iter().zip(&expected).enumerate() {
+            assert!(
+                (actual - expected).abs() <= 1e-12 * scale,
+                "{}: x[{i}] = {actual}, expected {expected}",
+                reference.display()
+            );
+        }
+    }
+
+    /// The same systems with `f32` factors, factored then refactored.
+    #[rstest]
+    fn solves_dumped_mna_systems_in_single_precision(
+        #[files("src/solver/tests/reference/*_x.mtx")] reference: PathBuf,
+    ) {
+        let base = reference.to_string_lossy().replace("_x.mtx", "");
+        let a = load_matrix_market_csc_file_keep_zeros(format!("{base}.mtx")).expect("matrix");
+        let mut x = load_matrix_market_array_file(format!("{base}_rhs.mtx")).expect("rhs");
+        let expected = load_matrix_market_array_file(&reference).expect("reference");
+
+        let mut config = KluConfig::default();
+        let mut symbolic = analyze::analyze(&a, &config).expect("analyze");
+        let mut numeric = factor::factor::<f32>(&a, &mut symbolic, &mut config).expect("factor");
+        refactor::refactor(&a, &mut symbolic, &mut numeric, &config).expect("refactor");
+        solve::solve(&symbolic, &mut numeric, symbolic.n, 1, &mut x, &config).expect("solve");
+
+        let scale = expected.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+        for (i, (actual, expected)) in x.iter().zip(&expected).enumerate() {
+            assert!(
+                (actual - expected).abs() <= 1e-4 * scale,
+                "{}: x[{i}] = {actual}, expected {expected}",
+                reference.display()
+            );
+        }
+    }
+
     #[rstest]
     fn snapshot_klu_fixtures(#[files("src/solver/tests/klu/*.mtx")] input: PathBuf) {
         let a = load_matrix_market_csc_file(&input).expect("load matrix market");
@@ -520,4 +661,181 @@ mod tests {
 
         insta::assert_debug_snapshot!(name, (n, nnz, is_square, seed, run, x_preview));
     }
+
+    /// Block lower triangular, so BTF splits it into two blocks with off-diagonal entries.
+    const BLOCKS: [[f64; 4]; 4] = [
+        [4.0, 1.0, 0.0, 0.0],
+        [2.0, 5.0, 0.0, 0.0],
+        [1.0, 0.0, 3.0, 1.0],
+        [0.0, 2.0, 1.0, 6.0],
+    ];
+
+    fn dense_to_csc(rows: &[[f64; 4]; 4]) -> CscMatrix {
+        let mut column_pointers = vec![0];
+        let mut row_indices = Vec::new();
+        let mut values = Vec::new();
+        for j in 0..4 {
+            for (i, row) in rows.iter().enumerate() {
+                if row[j] != 0.0 {
+                    row_indices.push(i);
+                    values.push(row[j]);
+                }
+            }
+            column_pointers.push(row_indices.len());
+        }
+        CscMatrix {
+            dim: crate::solver::matrix::Dim { nrows: 4, ncols: 4 },
+            column_pointers,
+            row_indices,
+            values,
+        }
+    }
+
+    fn factor_blocks() -> (CscMatrix, KluSymbolic, KluNumeric, KluConfig) {
+        let a = dense_to_csc(&BLOCKS);
+        let mut config = KluConfig::default();
+        let mut symbolic = analyze::analyze(&a, &config).expect("analyze");
+        let numeric = factor::factor(&a, &mut symbolic, &mut config).expect("factor");
+        assert!(symbolic.nblocks > 1);
+        (a, symbolic, numeric, config)
+    }
+
+    #[test]
+    fn tsolve_solves_the_transposed_system() {
+        let (_, symbolic, mut numeric, _) = factor_blocks();
+        let b = [1.0, -2.0, 3.0, 0.5];
+        let mut x = b.to_vec();
+        tsolve(&symbolic, &mut numeric, &mut x).expect("tsolve");
+
+        for (j, bj) in b.iter().enumerate() {
+            let atx: f64 = (0..4).map(|i| BLOCKS[i][j] * x[i]).sum();
+            assert!((atx - bj).abs() < 1e-12, "row {j}: {atx} != {bj}");
+        }
+    }
+
+    #[test]
+    fn condest_is_a_close_lower_bound_of_the_condition_number() {
+        use ndarray::{Array1, Array2};
+        use ndarray_linalg::{Factorize, Solve};
+
+        let (a, symbolic, mut numeric, config) = factor_blocks();
+        let estimate = condest(&a, &symbolic, &mut numeric, &config).expect("condest");
+
+        let dense = Array2::from_shape_fn((4, 4), |(i, j)| BLOCKS[i][j]);
+        let norm1 = |m: &Array2<f64>| {
+            m.columns()
+                .into_iter()
+                .map(|c| c.iter().map(|v| v.abs()).sum::<f64>())
+                .fold(0.0, f64::max)
+        };
+        let lu = dense.factorize().expect("dense lu");
+        let mut inverse = Array2::<f64>::zeros((4, 4));
+        for j in 0..4 {
+            let e = Array1::from_shape_fn(4, |i| if i == j { 1.0 } else { 0.0 });
+            inverse
+                .column_mut(j)
+                .assign(&lu.solve(&e).expect("dense solve"));
+        }
+        let exact = norm1(&dense) * norm1(&inverse);
+        // Hager's method never overestimates and is rarely off by more than a small factor
+        assert!(
+            estimate <= exact * (1.0 + 1e-12) && estimate >= exact / 3.0,
+            "{estimate} vs {exact}"
+        );
+    }
+
+    #[test]
+    fn rgrowth_and_rcond_of_a_well_conditioned_matrix() {
+        let (a, symbolic, numeric, _) = factor_blocks();
+        let rgrowth = rgrowth(&a, &symbolic, &numeric).expect("rgrowth");
+        assert!(rgrowth > 0.5 && rgrowth <= 1.0, "rgrowth {rgrowth}");
+        let rcond = rcond(&numeric);
+        assert!(rcond > 0.1 && rcond <= 1.0, "rcond {rcond}");
+    }
+
+    #[test]
+    fn btf_structure_of_full_rank_and_singular_matrices() {
+        let structure = btf_structure(&dense_to_csc(&BLOCKS));
+        assert_eq!(structure.structural_rank, 4);
+        assert_eq!(structure.block_boundaries, [0, 2, 4]);
+        assert!(structure.underdetermined_columns.is_empty());
+        assert!(structure.overdetermined_rows.is_empty());
+        let (_, symbolic, _, _) = factor_blocks();
+        assert_eq!(symbolic.structural_rank(), 4);
+        assert_eq!(symbolic.block_boundaries(), [0, 2, 4]);
+
+        // columns 1 and 2 only have entries in row 0, rows 1 and 2 only in column 0
+        let singular = [
+            [1.0, 1.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let structure = btf_structure(&dense_to_csc(&singular));
+        assert_eq!(structure.structural_rank, 3);
+        assert_eq!(structure.underdetermined_columns, [1, 2]);
+        assert_eq!(structure.overdetermined_rows, [1, 2]);
+    }
+
+    fn solve_with(a: &CscMatrix, mut config: KluConfig, b: &[f64]) -> KluResult<Vec<f64>> {
+        let mut symbolic = analyze::analyze(a, &config)?;
+        let mut numeric = factor::factor::<f64>(a, &mut symbolic, &mut config)?;
+        let mut x = b.to_vec();
+        solve::solve(&symbolic, &mut numeric, symbolic.n, 1, &mut x, &config)?;
+        Ok(x)
+    }
+
+    #[test]
+    fn custom_ordering_orders_each_block() {
+        let a = load_matrix_market_csc_file("src/solver/tests/klu/arrow.mtx").expect("arrow");
+        let n = a.dim.ncols;
+        let b: Vec<f64> = (0..n).map(|i| 1.0 + i as f64 / n as f64).collect();
+
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = Arc::clone(&calls);
+        let reverse = KluOrdering::custom(move |block: &CscMatrix| {
+            block.check_invariants().expect("sorted block");
+            seen.lock().unwrap().push(block.dim.ncols);
+            (0..block.dim.ncols).rev().collect()
+        });
+        let config = KluConfig::default().with_ordering(reverse);
+        let x = solve_with(&a, config, &b).expect("custom ordering");
+
+        let sizes = calls.lock().unwrap();
+        assert!(!sizes.is_empty() && sizes.iter().all(|&size| size > 3));
+        let amd = solve_with(&a, KluConfig::default(), &b).expect("amd");
+        for (x, amd) in x.iter().zip(&amd) {
+            assert!(
+                (x - amd).abs() <= 1e-10 * amd.abs().max(1.0),
+                "{x} vs {amd}"
+            );
+        }
+    }
+
+    #[test]
+    fn custom_ordering_must_be_a_permutation() {
+        let a = load_matrix_market_csc_file("src/solver/tests/klu/arrow.mtx").expect("arrow");
+        let repeated = KluOrdering::custom(|block: &CscMatrix| vec![0; block.dim.ncols]);
+        let config = KluConfig::default().with_ordering(repeated);
+        assert!(matches!(
+            analyze::analyze(&a, &config),
+            Err(KluError::InvalidOrdering { block: 0, .. })
+        ));
+    }
+
+    #[cfg(feature = "record")]
+    #[test]
+    fn recorded_amd_orders_like_amd() {
+        let a = load_matrix_market_csc_file("src/solver/tests/klu/arrow.mtx").expect("arrow");
+        let traces = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let ordering = KluOrdering::recorded_amd(Arc::clone(&traces));
+        let config = KluConfig::default().with_ordering(ordering);
+        let recorded = analyze::analyze(&a, &config).expect("recorded amd");
+        let amd = analyze::analyze(&a, &KluConfig::default()).expect("amd");
+
+        assert_eq!(recorded.row_permutation(), amd.row_permutation());
+        assert_eq!(recorded.column_permutation(), amd.column_permutation());
+        let traces = traces.lock().unwrap();
+        assert!(!traces.is_empty() && traces.iter().all(|trace| !trace.steps.is_empty()));
+    }
 }
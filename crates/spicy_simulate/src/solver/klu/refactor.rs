@@ -15,13 +15,14 @@ use crate::solver::{
         get_pointers_to_lu_mut, klu_valid, scale::scale,
     },
     matrix::csc::CscMatrix,
-    utils::dunits,
+    scalar::Scalar,
+    utils::{dunits, units_as_scalars_mut},
 };
 
-pub fn refactor(
+pub fn refactor<T: Scalar>(
     a: &CscMatrix,
     symbolic: &mut KluSymbolic,
-    numeric: &mut KluNumeric,
+    numeric: &mut KluNumeric<T>,
     config: &KluConfig,
 ) -> KluResult<()> {
     let n = symbolic.n;
@@ -54,9 +55,7 @@ pub fn refactor(
     }
 
     // clear workspace X
-    for k in 0..maxblock {
-        numeric.work[k] = 0.0;
-    }
+    units_as_scalars_mut::<T>(&mut numeric.work)[..maxblock].fill(T::ZERO);
 
     let mut poff = 0;
 
@@ -72,7 +71,7 @@ pub fn refactor(
             let start = a.col_start(oldcol);
             let end = a.col_end(oldcol);
 
-            let mut s = 0.0;
+            let mut s = T::ZERO;
             for p in start..end {
                 let oldrow = a.row_index(p);
                 let newrow = numeric.pinv[oldrow] - k1 as isize;
@@ -88,19 +87,19 @@ pub fn refactor(
                         "off-diagonal entry order mismatch at poff={}",
                         poff
                     );
-                    numeric.offx[poff] = val;
+                    numeric.offx[poff] = T::from_f64(val);
                     poff += 1;
                 } else {
                     // singleton
                     // s = a.value(p) / rs[oldrow]
-                    s = match &numeric.rs {
+                    s = T::from_f64(match &numeric.rs {
                         None => a.value(p),
                         Some(rs) => a.value(p) / rs[oldrow],
-                    };
+                    });
                 }
             }
             numeric.u_diag[k1] = s;
-            if s == 0.0 && numeric.metrics.numerical_rank.is_none() {
+            if s == T::ZERO && numeric.metrics.numerical_rank.is_none() {
                 numeric.metrics.numerical_rank = Some(k1);
                 numeric.metrics.singular_col = Some(oldcol);
             }
@@ -112,7 +111,7 @@ pub fn refactor(
             let (_, uip_after) = numeric.uip.split_at(k1);
             let (_, ulen_after) = numeric.ulen.split_at(k1);
             let lu = numeric.lu_bx[block].as_mut();
-            let x = numeric.work.as_mut_slice();
+            let x = units_as_scalars_mut::<T>(&mut numeric.work);
 
             for k in 0..nk {
                 // scatter kth column of the block into workspace X
@@ -135,7 +134,7 @@ pub fn refactor(
                             "off-diagonal entry order mismatch at poff={}",
                             poff
                         );
-                        numeric.offx[poff] = val;
+                        numeric.offx[poff] = T::from_f64(val);
                         poff += 1;
                     } else {
                         // singleton
@@ -144,7 +143,7 @@ pub fn refactor(
                             None => a.value(p),
                             Some(rs) => a.value(p) / rs[oldrow],
                         };
-                        x[newrow as usize] = val;
+                        x[newrow as usize] = T::from_f64(val);
                     }
                 }
 
@@ -158,14 +157,15 @@ pub fn refactor(
                     // the block scope is so the immutable borrow of `lu`
                     // does not overlap with the mutable borrow we take for L below.
                     let (j, ujk) = {
-                        let (ui, ux, _) = get_pointers_to_lu_mut(lu, uip_after, ulen_after, k)?;
+                        let (ui, ux, _) =
+                            get_pointers_to_lu_mut::<T>(lu, uip_after, ulen_after, k)?;
                         let j = ui[up];
                         let ujk = x[j];
-                        x[j] = 0.0;
+                        x[j] = T::ZERO;
                         ux[up] = ujk;
                         (j, ujk)
                     };
-                    let (li, lx, llen) = get_pointers_to_lu(lu, lip_after, llen_after, j)?;
+                    let (li, lx, llen) = get_pointers_to_lu::<T>(lu, lip_after, llen_after, j)?;
                     for p in 0..llen {
                         let i = li[p];
                         let val = lx[p];
@@ -174,8 +174,8 @@ pub fn refactor(
                 }
                 // get the diagonal entry of u
                 let ukk = x[k];
-                x[k] = 0.0;
-                if ukk == 0.0 {
+                x[k] = T::ZERO;
+                if ukk == T::ZERO {
                     // matrix is numerically singular
                     if numeric.metrics.numerical_rank.is_none() {
                         numeric.metrics.numerical_rank = Some(k + k1);
@@ -187,11 +187,11 @@ pub fn refactor(
                 }
                 numeric.u_diag[k + k1] = ukk;
                 // gather and divide by pibot to get kth column of L
-                let (li, lx, llen) = get_pointers_to_lu_mut(lu, lip_after, llen_after, k)?;
+                let (li, lx, llen) = get_pointers_to_lu_mut::<T>(lu, lip_after, llen_after, k)?;
                 for p in 0..llen {
                     let i = li[p];
                     lx[p] = x[i] / ukk;
-                    x[i] = 0.0;
+                    x[i] = T::ZERO;
                 }
             }
         }
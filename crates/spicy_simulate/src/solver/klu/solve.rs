@@ -12,27 +12,29 @@
 use crate::solver::klu::{
     KluConfig, KluError, KluNumeric, KluResult, KluSymbolic, get_pointers_to_lu, klu_valid,
 };
+use crate::solver::scalar::Scalar;
+use crate::solver::utils::units_as_scalars_mut;
 
 /// solve Lx = b, Assumes L is unit lower triangular and where the unit diagonal
 /// entry is NOT stored.
 /// B is n-by-nrhs and is stored in ROW form with row dimension nrhs.
 /// nrhs must be in the range 1 to 4.
-fn klu_lsolve(
+fn klu_lsolve<T: Scalar>(
     n: usize,
     lip: &[usize],
     llen: &[usize],
     lu: &[f64],
     nrhs: usize,
     // right-hand-side on input, solution to Lx=b on output
-    x: &mut [f64],
+    x: &mut [T],
 ) -> KluResult<()> {
-    let mut temp = [0.0; 4];
+    let mut temp = [T::ZERO; 4];
 
     match nrhs {
         1 => {
             for k in 0..n {
                 temp[0] = x[k];
-                let (li, lx, len) = get_pointers_to_lu(lu, lip, llen, k)?;
+                let (li, lx, len) = get_pointers_to_lu::<T>(lu, lip, llen, k)?;
                 // unit diagonal of L is not stored
                 for p in 0..len {
                     let i = li[p];
@@ -45,7 +47,7 @@ fn klu_lsolve(
             for k in 0..n {
                 temp[0] = x[2 * k];
                 temp[1] = x[2 * k + 1];
-                let (li, lx, len) = get_pointers_to_lu(lu, lip, llen, k)?;
+                let (li, lx, len) = get_pointers_to_lu::<T>(lu, lip, llen, k)?;
                 for p in 0..len {
                     let i = li[p];
                     let val = lx[p];
@@ -59,7 +61,7 @@ fn klu_lsolve(
                 temp[0] = x[3 * k];
                 temp[1] = x[3 * k + 1];
                 temp[2] = x[3 * k + 2];
-                let (li, lx, len) = get_pointers_to_lu(lu, lip, llen, k)?;
+                let (li, lx, len) = get_pointers_to_lu::<T>(lu, lip, llen, k)?;
                 for p in 0..len {
                     let i = li[p];
                     let val = lx[p];
@@ -75,7 +77,7 @@ fn klu_lsolve(
                 temp[1] = x[4 * k + 1];
                 temp[2] = x[4 * k + 2];
                 temp[3] = x[4 * k + 3];
-                let (li, lx, len) = get_pointers_to_lu(lu, lip, llen, k)?;
+                let (li, lx, len) = get_pointers_to_lu::<T>(lu, lip, llen, k)?;
                 for p in 0..len {
                     let i = li[p];
                     let val = lx[p];
@@ -96,22 +98,22 @@ fn klu_lsolve(
 /// entry is NOT stored.
 /// B is n-by-nrhs and is stored in ROW form with row dimension nrhs.
 /// nrhs must be in the range 1 to 4.
-fn klu_usolve(
+fn klu_usolve<T: Scalar>(
     n: usize,
     uip: &[usize],
     ulen: &[usize],
     lu: &[f64],
-    u_diag: &[f64],
+    u_diag: &[T],
     nrhs: usize,
     // right-hand-side on input, solution to Ux=b on output
-    x: &mut [f64],
+    x: &mut [T],
 ) -> KluResult<()> {
-    let mut temp = [0.0; 4];
+    let mut temp = [T::ZERO; 4];
 
     match nrhs {
         1 => {
             for k in (0..n).rev() {
-                let (ui, ux, len) = get_pointers_to_lu(lu, uip, ulen, k)?;
+                let (ui, ux, len) = get_pointers_to_lu::<T>(lu, uip, ulen, k)?;
                 temp[0] = x[k] / u_diag[k];
                 x[k] = temp[0];
                 for p in 0..len {
@@ -123,7 +125,7 @@ fn klu_usolve(
         }
         2 => {
             for k in (0..n).rev() {
-                let (ui, ux, len) = get_pointers_to_lu(lu, uip, ulen, k)?;
+                let (ui, ux, len) = get_pointers_to_lu::<T>(lu, uip, ulen, k)?;
                 temp[0] = x[2 * k] / u_diag[k];
                 temp[1] = x[2 * k + 1] / u_diag[k];
                 x[2 * k] = temp[0];
@@ -138,7 +140,7 @@ fn klu_usolve(
         }
         3 => {
             for k in (0..n).rev() {
-                let (ui, ux, len) = get_pointers_to_lu(lu, uip, ulen, k)?;
+                let (ui, ux, len) = get_pointers_to_lu::<T>(lu, uip, ulen, k)?;
                 temp[0] = x[3 * k] / u_diag[k];
                 temp[1] = x[3 * k + 1] / u_diag[k];
                 temp[2] = x[3 * k + 2] / u_diag[k];
@@ -156,7 +158,7 @@ fn klu_usolve(
         }
         4 => {
             for k in (0..n).rev() {
-                let (ui, ux, len) = get_pointers_to_lu(lu, uip, ulen, k)?;
+                let (ui, ux, len) = get_pointers_to_lu::<T>(lu, uip, ulen, k)?;
                 temp[0] = x[4 * k] / u_diag[k];
                 temp[1] = x[4 * k + 1] / u_diag[k];
                 temp[2] = x[4 * k + 2] / u_diag[k];
@@ -183,9 +185,9 @@ fn klu_usolve(
 
 // solve Ax =b using the symbolic and numeric objects from analyze
 // and factor.
-pub fn solve(
+pub fn solve<T: Scalar>(
     symbolic: &KluSymbolic,
-    numeric: &mut KluNumeric,
+    numeric: &mut KluNumeric<T>,
 
     // leading dimension of B
     d: usize,
@@ -231,8 +233,8 @@ pub fn solve(
     let u_diag = &numeric.u_diag;
 
     let rs = &numeric.rs;
-    let x = numeric.work.as_mut_slice();
-    let mut temp = [0.0; 4];
+    let x = units_as_scalars_mut::<T>(&mut numeric.work);
+    let mut temp = [T::ZERO; 4];
 
     debug_assert!(klu_valid(n, offp, offi));
 
@@ -249,34 +251,34 @@ pub fn solve(
                     for k in 0..n {
                         let i = pnum[k] as usize;
                         let rs = rs[k];
-                        x[k] = b[base + i] / rs;
+                        x[k] = T::from_f64(b[base + i] / rs);
                     }
                 }
                 2 => {
                     for k in 0..n {
                         let i = pnum[k] as usize;
                         let rs = rs[k];
-                        x[2 * k] = b[base + i] / rs;
-                        x[2 * k + 1] = b[base + i + d] / rs;
+                        x[2 * k] = T::from_f64(b[base + i] / rs);
+                        x[2 * k + 1] = T::from_f64(b[base + i + d] / rs);
                     }
                 }
                 3 => {
                     for k in 0..n {
                         let i = pnum[k] as usize;
                         let rs = rs[k];
-                        x[3 * k] = b[base + i] / rs;
-                        x[3 * k + 1] = b[base + i + d] / rs;
-                        x[3 * k + 2] = b[base + i + 2 * d] / rs;
+                        x[3 * k] = T::from_f64(b[base + i] / rs);
+                        x[3 * k + 1] = T::from_f64(b[base + i + d] / rs);
+                        x[3 * k + 2] = T::from_f64(b[base + i + 2 * d] / rs);
                     }
                 }
                 4 => {
                     for k in 0..n {
                         let i = pnum[k] as usize;
                         let rs = rs[k];
-                        x[4 * k] = b[base + i] / rs;
-                        x[4 * k + 1] = b[base + i + d] / rs;
-                        x[4 * k + 2] = b[base + i + 2 * d] / rs;
-                        x[4 * k + 3] = b[base + i + 3 * d] / rs;
+                        x[4 * k] = T::from_f64(b[base + i] / rs);
+                        x[4 * k + 1] = T::from_f64(b[base + i + d] / rs);
+                        x[4 * k + 2] = T::from_f64(b[base + i + 2 * d] / rs);
+                        x[4 * k + 3] = T::from_f64(b[base + i + 3 * d] / rs);
                     }
                 }
                 _ => unreachable!("nr = {}", nr),
@@ -285,31 +287,31 @@ pub fn solve(
                 1 => {
                     for k in 0..n {
                         let i = pnum[k] as usize;
-                        x[k] = b[base + i];
+                        x[k] = T::from_f64(b[base + i]);
                     }
                 }
                 2 => {
                     for k in 0..n {
                         let i = pnum[k] as usize;
-                        x[2 * k] = b[base + i];
-                        x[2 * k + 1] = b[base + i + d];
+                        x[2 * k] = T::from_f64(b[base + i]);
+                        x[2 * k + 1] = T::from_f64(b[base + i + d]);
                     }
                 }
                 3 => {
                     for k in 0..n {
                         let i = pnum[k] as usize;
-                        x[3 * k] = b[base + i];
-                        x[3 * k + 1] = b[base + i + d];
-                        x[3 * k + 2] = b[base + i + 2 * d];
+                        x[3 * k] = T::from_f64(b[base + i]);
+                        x[3 * k + 1] = T::from_f64(b[base + i + d]);
+                        x[3 * k + 2] = T::from_f64(b[base + i + 2 * d]);
                     }
                 }
                 4 => {
                     for k in 0..n {
                         let i = pnum[k] as usize;
-                        x[4 * k] = b[base + i];
-                        x[4 * k + 1] = b[base + i + d];
-                        x[4 * k + 2] = b[base + i + 2 * d];
-                        x[4 * k + 3] = b[base + i + 3 * d];
+                        x[4 * k] = T::from_f64(b[base + i]);
+                        x[4 * k + 1] = T::from_f64(b[base + i + d]);
+                        x[4 * k + 2] = T::from_f64(b[base + i + 2 * d]);
+                        x[4 * k + 3] = T::from_f64(b[base + i + 3 * d]);
                     }
                 }
                 _ => unreachable!("nr = {}", nr),
@@ -431,31 +433,31 @@ pub fn solve(
             1 => {
                 for k in 0..n {
                     let i = q[k] as usize;
-                    b[base + i] = x[k];
+                    b[base + i] = x[k].to_f64();
                 }
             }
             2 => {
                 for k in 0..n {
                     let i = q[k] as usize;
-                    b[base + i] = x[2 * k];
-                    b[base + i + d] = x[2 * k + 1];
+                    b[base + i] = x[2 * k].to_f64();
+                    b[base + i + d] = x[2 * k + 1].to_f64();
                 }
             }
             3 => {
                 for k in 0..n {
                     let i = q[k] as usize;
-                    b[base + i] = x[3 * k];
-                    b[base + i + d] = x[3 * k + 1];
-                    b[base + i + 2 * d] = x[3 * k + 2];
+                    b[base + i] = x[3 * k].to_f64();
+                    b[base + i + d] = x[3 * k + 1].to_f64();
+                    b[base + i + 2 * d] = x[3 * k + 2].to_f64();
                 }
             }
             4 => {
                 for k in 0..n {
                     let i = q[k] as usize;
-                    b[base + i] = x[4 * k];
-                    b[base + i + d] = x[4 * k + 1];
-                    b[base + i + 2 * d] = x[4 * k + 2];
-                    b[base + i + 3 * d] = x[4 * k + 3];
+                    b[base + i] = x[4 * k].to_f64();
+                    b[base + i + d] = x[4 * k + 1].to_f64();
+                    b[base + i + 2 * d] = x[4 * k + 2].to_f64();
+                    b[base + i + 3 * d] = x[4 * k + 3].to_f64();
                 }
             }
             _ => unreachable!("nr = {}", nr),
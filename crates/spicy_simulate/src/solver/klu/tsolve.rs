@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: LGPL-2.1-or-later
+//
+// This file is based on the SuiteSparse KLU implementation by Timothy A. Davis
+// and Ekanathan Palamadai.
+//
+// KLU, Copyright (c) 2004-2024, University of Florida.  All Rights Reserved.
+// Authors: Timothy A. Davis and Ekanathan Palamadai.
+//
+// Modifications/porting for this project:
+// Copyright (c) 2025 Ido Ben Amram
+
+use crate::solver::klu::{KluError, KluNumeric, KluResult, KluSymbolic, get_pointers_to_lu};
+use crate::solver::scalar::Scalar;
+use crate::solver::utils::units_as_scalars_mut;
+
+/// solve L'x = b, Assumes L is unit lower triangular and where the unit diagonal
+/// entry is NOT stored.
+fn klu_ltsolve<T: Scalar>(
+    n: usize,
+    lip: &[usize],
+    llen: &[usize],
+    lu: &[f64],
+    x: &mut [T],
+) -> KluResult<()> {
+    for k in (0..n).rev() {
+        let (li, lx, len) = get_pointers_to_lu::<T>(lu, lip, llen, k)?;
+        let mut temp = x[k];
+        for p in 0..len {
+            temp -= lx[p] * x[li[p]];
+        }
+        x[k] = temp;
+    }
+    Ok(())
+}
+
+/// solve U'x = b, Assumes U is non-unit upper triangular and where the diagonal
+/// entry is NOT stored.
+fn klu_utsolve<T: Scalar>(
+    n: usize,
+    uip: &[usize],
+    ulen: &[usize],
+    lu: &[f64],
+    u_diag: &[T],
+    x: &mut [T],
+) -> KluResult<()> {
+    for k in 0..n {
+        let (ui, ux, len) = get_pointers_to_lu::<T>(lu, uip, ulen, k)?;
+        let mut temp = x[k];
+        for p in 0..len {
+            temp -= ux[p] * x[ui[p]];
+        }
+        x[k] = temp / u_diag[k];
+    }
+    Ok(())
+}
+
+// solve A'x = b using the symbolic and numeric objects from analyze and factor.
+// Only a single right-hand-side is supported.
+pub fn tsolve<T: Scalar>(
+    symbolic: &KluSymbolic,
+    numeric: &mut KluNumeric<T>,
+    b: &mut [f64],
+) -> KluResult<()> {
+    let n = symbolic.n;
+    if b.len() < n {
+        return Err(KluError::RhsTooSmall {
+            required: n,
+            d: n,
+            nrhs: 1,
+            actual: b.len(),
+        });
+    }
+
+    let nblocks = symbolic.nblocks;
+    let q = &symbolic.column_permutation;
+    let r = &symbolic.block_boundaries;
+
+    let pnum = &numeric.pnum;
+    let offp = &numeric.offp;
+    let offi = &numeric.offi;
+    let offx = &numeric.offx;
+    let u_diag = &numeric.u_diag;
+    let x = units_as_scalars_mut::<T>(&mut numeric.work);
+
+    // permute the right hand side, X = Q'*B
+    for k in 0..n {
+        x[k] = T::from_f64(b[q[k] as usize]);
+    }
+
+    // solve X = (L*U + Off)'\X, forward over the blocks
+    for block in 0..nblocks {
+        let k1 = r[block];
+        let k2 = r[block + 1];
+        let nk = k2 - k1;
+
+        // block forward-substitution for the off-diagonal-block entries
+        if block > 0 {
+            for k in k1..k2 {
+                for p in offp[k]..offp[k + 1] {
+                    x[k] -= offx[p] * x[offi[p]];
+                }
+            }
+        }
+
+        // solve the block system
+        if nk == 1 {
+            x[k1] /= u_diag[k1];
+        } else {
+            let lu = numeric.lu_bx[block].as_slice();
+            let x_after = &mut x[k1..];
+            klu_utsolve(
+                nk,
+                &numeric.uip[k1..],
+                &numeric.ulen[k1..],
+                lu,
+                &u_diag[k1..],
+                x_after,
+            )?;
+            klu_ltsolve(nk, &numeric.lip[k1..], &numeric.llen[k1..], lu, x_after)?;
+        }
+    }
+
+    // scale and permute the result, B = P'*R\X
+    for k in 0..n {
+        let i = pnum[k] as usize;
+        b[i] = match &numeric.rs {
+            Some(rs) => x[k].to_f64() / rs[k],
+            None => x[k].to_f64(),
+        };
+    }
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use std::mem::MaybeUninit;
+use std::ops::Range;
 
 use crate::solver::matrix::Dim;
 use crate::solver::matrix::csc::CscMatrix;
@@ -50,8 +51,7 @@ impl MatrixBuilder {
         self.entries.reserve(nnz);
     }
 
-    /// push a COO (column, row, value) tuple
-    pub fn push(&mut self, column: usize, row: usize, value: f64) -> Result<usize, CscError> {
+    fn check_bounds(&self, column: usize, row: usize) -> Result<(), CscError> {
         if column >= self.dim.ncols {
             return Err(CscError::OutOfBoundsIndex {
                 index: column,
@@ -64,6 +64,12 @@ impl MatrixBuilder {
                 max: self.dim.nrows,
             });
         }
+        Ok(())
+    }
+
+    /// push a COO (column, row, value) tuple
+    pub fn push(&mut self, column: usize, row: usize, value: f64) -> Result<usize, CscError> {
+        self.check_bounds(column, row)?;
 
         let entry_index = self.entries.len();
         let entry = CooEntry {
@@ -77,6 +83,57 @@ impl MatrixBuilder {
         Ok(entry_index)
     }
 
+    /// push the (column, row) pattern of a device stamp, with zero values.
+    ///
+    /// The entries are numbered consecutively in the order of `pattern`; nothing is pushed if
+    /// one of them is out of bounds.
+    pub fn push_pattern(&mut self, pattern: &[(usize, usize)]) -> Result<Range<usize>, CscError> {
+        for &(column, row) in pattern {
+            self.check_bounds(column, row)?;
+        }
+        let start = self.entries.len();
+        self.entries.extend(
+            pattern
+                .iter()
+                .enumerate()
+                .map(|(k, &(column, row))| CooEntry {
+                    entry: start + k,
+                    final_index: 0, // will be set later
+                    column,
+                    row,
+                    value: 0.0,
+                }),
+        );
+        Ok(start..self.entries.len())
+    }
+
+    /// Sort the entries by (column, row), duplicates in the order they were pushed: a counting
+    /// sort of the columns, then a sort of the rows of each column.
+    fn sort_entries(&mut self) {
+        let n = self.dim.ncols;
+        let mut column_starts = vec![0usize; n + 1];
+        for e in &self.entries {
+            column_starts[e.column + 1] += 1;
+        }
+        for j in 0..n {
+            column_starts[j + 1] += column_starts[j];
+        }
+
+        let mut next = column_starts.clone();
+        // every slot is overwritten, the columns partitioning the entries
+        let mut sorted = self.entries.clone();
+        for e in &self.entries {
+            sorted[next[e.column]] = *e;
+            next[e.column] += 1;
+        }
+
+        for j in 0..n {
+            sorted[column_starts[j]..column_starts[j + 1]]
+                .sort_unstable_by_key(|e| (e.row, e.entry));
+        }
+        self.entries = sorted;
+    }
+
     pub fn build_csc(mut self) -> Result<CscMatrix, CscError> {
         let n = self.dim.ncols;
 
@@ -141,11 +198,15 @@ impl MatrixBuilder {
         Ok(a)
     }
 
+    /// The canonical CSC pattern of the entries with zero values, and where each entry landed.
+    ///
+    /// Duplicate coordinates share one nonzero, so stamping every entry into its mapped index
+    /// accumulates them. The mapping only depends on the coordinates pushed, not on the order
+    /// they were pushed in.
     pub fn build_csc_pattern(mut self) -> Result<(CscMatrix, EntryMapping), CscError> {
         let n = self.dim.ncols;
 
-        self.entries
-            .sort_by(|a, b| (a.column, a.row).cmp(&(b.column, b.row)));
+        self.sort_entries();
 
         // Combine duplicates; entries are now sorted by (col,row)
         let mut last_col = usize::MAX;
@@ -305,6 +366,59 @@ mod tests {
         debug_assert!(a.check_invariants().is_ok());
     }
 
+    #[test]
+    fn push_pattern_numbers_the_entries_consecutively() {
+        let mut b = MatrixBuilder::new(3, 3);
+        b.push(1, 1, 0.0).unwrap();
+        let entries = b.push_pattern(&[(0, 0), (2, 2), (2, 0), (0, 2)]).unwrap();
+        assert_eq!(entries, 1..5);
+
+        let (pattern, mapping) = b.build_csc_pattern().unwrap();
+        assert_eq!(pattern.column_pointers, vec![0, 2, 3, 5]);
+        assert_eq!(pattern.row_indices, vec![0, 2, 1, 0, 2]);
+        // (0,0)->0, (0,2)->1, (1,1)->2, (2,0)->3, (2,2)->4
+        let mapped: Vec<usize> = (0..5).map(|e| mapping.get(e)).collect();
+        assert_eq!(mapped, vec![2, 0, 4, 3, 1]);
+    }
+
+    #[test]
+    fn push_pattern_out_of_bounds_pushes_nothing() {
+        let mut b = MatrixBuilder::new(2, 2);
+        assert!(matches!(
+            b.push_pattern(&[(0, 0), (0, 2)]),
+            Err(CscError::OutOfBoundsIndex { index: 2, max: 2 })
+        ));
+        assert_eq!(b.push_pattern(&[(1, 1)]).unwrap(), 0..1);
+    }
+
+    #[test]
+    fn build_csc_pattern_does_not_depend_on_push_order() {
+        // a pattern with duplicates in every column, pushed forwards and backwards
+        let coords: Vec<(usize, usize)> = (0..40).map(|k| (k * 7 % 5, k * 3 % 4)).collect();
+        let mut forward = MatrixBuilder::new(4, 5);
+        forward.push_pattern(&coords).unwrap();
+        let mut backward = MatrixBuilder::new(4, 5);
+        for &(column, row) in coords.iter().rev() {
+            backward.push(column, row, 0.0).unwrap();
+        }
+
+        let (forward, forward_mapping) = forward.build_csc_pattern().unwrap();
+        let (backward, backward_mapping) = backward.build_csc_pattern().unwrap();
+        assert_eq!(forward.column_pointers, backward.column_pointers);
+        assert_eq!(forward.row_indices, backward.row_indices);
+        assert_eq!(forward.row_indices.len(), 20);
+        debug_assert!(forward.check_invariants().is_ok());
+        for (k, &(column, row)) in coords.iter().enumerate() {
+            let nnz = forward_mapping.get(k);
+            assert_eq!(nnz, backward_mapping.get(coords.len() - 1 - k));
+            assert_eq!(forward.row_indices[nnz], row);
+            assert!(
+                (forward.column_pointers[column]..forward.column_pointers[column + 1])
+                    .contains(&nnz)
+            );
+        }
+    }
+
     #[test]
     fn build_csc_pattern_entry_mapping_allows_stamping_into_final_nnz() {
         // This test exercises the "pattern + entry mapping" flow used by the simulator:
@@ -6,7 +6,7 @@ use crate::solver::matrix::error::CscError;
 /// - column pointers are the indices of the start and end of each column
 /// - row indices are the indices of the rows of the non zero values
 /// - values are the non zero values
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CscMatrix {
     pub dim: Dim,
     /// Column pointers, len = ncols + 1
@@ -4,6 +4,7 @@ pub mod csr;
 pub mod error;
 pub mod mtx;
 pub mod slice;
+pub mod spy;
 
 /// Compressed Sparse Column
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -2,7 +2,7 @@ use crate::solver::matrix::builder::MatrixBuilder;
 use crate::solver::matrix::csc::CscMatrix;
 use crate::solver::matrix::error::{MatrixError, MatrixMarketError};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Write};
 use std::path::Path;
 
 /// A larger-than-default buffer helps for huge `.mtx` files (tens of millions of lines).
@@ -571,6 +571,113 @@ fn load_matrix_market_csc_from_reader_impl<R: BufRead>(
     Ok(b.build_csc()?)
 }
 
+/// Load a dense column vector from a MatrixMarket `.mtx` file (array format), e.g. a
+/// right-hand side written by [`write_matrix_market_array`].
+///
+/// Supports the banner `%%MatrixMarket matrix array {integer|real} general` with a single
+/// column.
+pub fn load_matrix_market_array_file(path: impl AsRef<Path>) -> Result<Vec<f64>, MatrixError> {
+    let f = File::open(path.as_ref()).map_err(MatrixMarketError::from)?;
+    load_matrix_market_array_from_reader(BufReader::new(f))
+}
+
+/// Same as [`load_matrix_market_array_file`], but reads from any buffered reader.
+pub fn load_matrix_market_array_from_reader<R: BufRead>(
+    reader: R,
+) -> Result<Vec<f64>, MatrixError> {
+    let mut lines = reader.lines().enumerate();
+    let mut next_line = || -> Result<Option<(usize, String)>, MatrixMarketError> {
+        for (i, line) in lines.by_ref() {
+            let line = line?;
+            let trimmed = line.trim();
+            if !trimmed.is_empty() && (trimmed.starts_with("%%") || !trimmed.starts_with('%')) {
+                return Ok(Some((i + 1, trimmed.to_string())));
+            }
+        }
+        Ok(None)
+    };
+
+    let (_, banner) =
+        next_line()?.ok_or_else(|| MatrixMarketError::InvalidBanner("empty input".to_string()))?;
+    let tokens: Vec<_> = banner.split_whitespace().collect();
+    match tokens[..] {
+        ["%%MatrixMarket", object, format, field, symmetry]
+            if object.eq_ignore_ascii_case("matrix")
+                && format.eq_ignore_ascii_case("array")
+                && symmetry.eq_ignore_ascii_case("general")
+                && (field.eq_ignore_ascii_case("real")
+                    || field.eq_ignore_ascii_case("integer")) => {}
+        _ => {
+            return Err(MatrixMarketError::UnsupportedType(format!(
+                "only 'matrix array {{integer|real}} general' is supported, got: {banner}"
+            ))
+            .into());
+        }
+    }
+
+    let (line, size) = next_line()?
+        .ok_or_else(|| MatrixMarketError::InvalidSizeLine("missing size line".to_string()))?;
+    let nrows = match size.split_whitespace().collect::<Vec<_>>()[..] {
+        [nrows, "1"] => nrows.parse::<usize>().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        MatrixMarketError::InvalidSizeLine(format!("expected `<rows> 1` at line {line}: {size}"))
+    })?;
+
+    let mut values = Vec::with_capacity(nrows);
+    while let Some((line, entry)) = next_line()? {
+        let value = entry
+            .parse::<f64>()
+            .map_err(|_| MatrixMarketError::InvalidEntry {
+                line,
+                msg: format!("bad value '{entry}'"),
+            })?;
+        values.push(value);
+    }
+    if values.len() != nrows {
+        return Err(MatrixMarketError::EntryCountMismatch {
+            expected: nrows,
+            actual: values.len(),
+        }
+        .into());
+    }
+    Ok(values)
+}
+
+/// Write `a` as a MatrixMarket `coordinate real general` matrix, one line per stored entry
+/// (explicit zeros included), so it loads back into the same pattern with
+/// [`load_matrix_market_csc_file_keep_zeros`].
+pub fn write_matrix_market_csc<W: Write>(mut w: W, a: &CscMatrix) -> io::Result<()> {
+    writeln!(w, "%%MatrixMarket matrix coordinate real general")?;
+    writeln!(w, "{} {} {}", a.dim.nrows, a.dim.ncols, a.nnz())?;
+    for col in 0..a.dim.ncols {
+        let (rows, values) = a.col(col);
+        for (row, value) in rows.iter().zip(values) {
+            writeln!(w, "{} {} {:e}", row + 1, col + 1, value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write `values` as a single-column MatrixMarket `array real general` matrix, with one `%`
+/// comment line per entry of `comments` after the banner.
+pub fn write_matrix_market_array<W: Write>(
+    mut w: W,
+    values: &[f64],
+    comments: &[String],
+) -> io::Result<()> {
+    writeln!(w, "%%MatrixMarket matrix array real general")?;
+    for comment in comments {
+        writeln!(w, "% {comment}")?;
+    }
+    writeln!(w, "{} 1", values.len())?;
+    for value in values {
+        writeln!(w, "{value:e}")?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -618,4 +725,30 @@ mod tests {
         let s = format!("{err}");
         assert!(s.contains("only 'general' symmetry is supported"));
     }
+
+    #[test]
+    fn written_matrices_and_vectors_load_back() {
+        let mtx =
+            "%%MatrixMarket matrix coordinate real general\n2 2 3\n1 1 0.5\n2 1 0\n2 2 -1e-12\n";
+        let a = load_matrix_market_csc_from_reader_keep_zeros(Cursor::new(mtx)).unwrap();
+        let mut written = Vec::new();
+        write_matrix_market_csc(&mut written, &a).unwrap();
+        let b = load_matrix_market_csc_from_reader_keep_zeros(Cursor::new(&written)).unwrap();
+        assert_eq!(a, b);
+
+        let values = [1.0, -2.5e-7, 0.1 + 0.2];
+        let mut written = Vec::new();
+        write_matrix_market_array(&mut written, &values, &["1 V(in)".to_string()]).unwrap();
+        let text = String::from_utf8(written).unwrap();
+        assert!(text.starts_with("%%MatrixMarket matrix array real general\n% 1 V(in)\n3 1\n"));
+        let loaded = load_matrix_market_array_from_reader(Cursor::new(text)).unwrap();
+        assert_eq!(loaded, values);
+    }
+
+    #[test]
+    fn rejects_array_with_missing_entries() {
+        let mtx = "%%MatrixMarket matrix array real general\n3 1\n1\n2\n";
+        let err = load_matrix_market_array_from_reader(Cursor::new(mtx)).unwrap_err();
+        assert!(format!("{err}").contains("expected 3 entries but found 2"));
+    }
 }
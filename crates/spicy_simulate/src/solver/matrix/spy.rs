@@ -0,0 +1,128 @@
+//! Spy plots: the sparsity pattern of a matrix as an SVG, a square for every stored entry.
+
+use std::io::{self, Write};
+
+use crate::solver::matrix::csc::CscMatrix;
+
+/// Largest side of the drawing, in pixels.
+const MAX_SIDE_PX: usize = 800;
+/// Side of an entry when the matrix is small enough to draw at this size.
+const ENTRY_PX: usize = 8;
+
+/// A permuted view of a matrix, as KLU orders it: position `k` holds row `rows[k]` and column
+/// `columns[k]`, and diagonal block `b` spans positions `blocks[b]..blocks[b + 1]`.
+#[derive(Debug, Clone, Copy)]
+pub struct SpyOrdering<'a> {
+    pub rows: &'a [isize],
+    pub columns: &'a [isize],
+    pub blocks: &'a [usize],
+}
+
+fn inverse(permutation: &[isize]) -> Vec<usize> {
+    let mut inverse = vec![0; permutation.len()];
+    for (k, &i) in permutation.iter().enumerate() {
+        inverse[i as usize] = k;
+    }
+    inverse
+}
+
+/// Write the spy plot of `a` to `w`, in the order of `ordering` with its diagonal blocks
+/// outlined, or in the natural order.
+pub fn write_spy_svg<W: Write>(
+    w: &mut W,
+    a: &CscMatrix,
+    ordering: Option<SpyOrdering<'_>>,
+) -> io::Result<()> {
+    let (nrows, ncols) = (a.dim.nrows, a.dim.ncols);
+    let (row_position, column_position) = match ordering {
+        Some(ordering) => (inverse(ordering.rows), inverse(ordering.columns)),
+        None => ((0..nrows).collect(), (0..ncols).collect()),
+    };
+    let side = nrows.max(ncols).max(1);
+    let px = (side * ENTRY_PX).min(MAX_SIDE_PX) as f64 / side as f64;
+
+    writeln!(
+        w,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{:.0}" height="{:.0}" viewBox="0 0 {ncols} {nrows}" shape-rendering="crispEdges">"#,
+        px * ncols as f64,
+        px * nrows as f64,
+    )?;
+    writeln!(
+        w,
+        r#"<rect width="{ncols}" height="{nrows}" fill="white" stroke="gray" stroke-width="1" vector-effect="non-scaling-stroke"/>"#
+    )?;
+    writeln!(w, r#"<g fill="black">"#)?;
+    for (j, &x) in column_position.iter().enumerate() {
+        let (rows, _) = a.col(j);
+        for &i in rows {
+            writeln!(
+                w,
+                r#"<rect x="{x}" y="{}" width="1" height="1"/>"#,
+                row_position[i]
+            )?;
+        }
+    }
+    writeln!(w, "</g>")?;
+    if let Some(ordering) = ordering {
+        writeln!(
+            w,
+            r#"<g fill="none" stroke="red" stroke-width="1" vector-effect="non-scaling-stroke">"#
+        )?;
+        for block in ordering.blocks.windows(2) {
+            let (start, size) = (block[0], block[1] - block[0]);
+            writeln!(
+                w,
+                r#"<rect x="{start}" y="{start}" width="{size}" height="{size}" vector-effect="non-scaling-stroke"/>"#
+            )?;
+        }
+        writeln!(w, "</g>")?;
+    }
+    writeln!(w, "</svg>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::matrix::builder::MatrixBuilder;
+
+    fn spy(a: &CscMatrix, ordering: Option<SpyOrdering<'_>>) -> String {
+        let mut out = Vec::new();
+        write_spy_svg(&mut out, a, ordering).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn entries_move_with_the_ordering() {
+        let mut builder = MatrixBuilder::new(2, 2);
+        builder.push(0, 0, 1.0).unwrap();
+        builder.push(1, 0, 2.0).unwrap();
+        builder.push(1, 1, 3.0).unwrap();
+        let a = builder.build_csc().unwrap();
+
+        let natural = spy(&a, None);
+        assert!(natural.contains(r#"width="16" height="16""#));
+        assert!(natural.contains(r#"<rect x="1" y="0" width="1" height="1"/>"#));
+        assert!(!natural.contains(r#"stroke="red""#));
+
+        // swapped rows and columns make the matrix lower triangular
+        let swapped = [1, 0];
+        let ordering = SpyOrdering {
+            rows: &swapped,
+            columns: &swapped,
+            blocks: &[0, 1, 2],
+        };
+        let permuted = spy(&a, Some(ordering));
+        assert!(permuted.contains(r#"<rect x="0" y="1" width="1" height="1"/>"#));
+        assert!(!permuted.contains(r#"<rect x="1" y="0" width="1" height="1"/>"#));
+        assert_eq!(
+            permuted.matches(r#"<rect x="1" y="1" width="1""#).count(),
+            2
+        );
+        assert_eq!(
+            permuted
+                .matches(r#"vector-effect="non-scaling-stroke"/>"#)
+                .count(),
+            3
+        );
+    }
+}
@@ -2,7 +2,11 @@ mod aat;
 pub mod amd;
 pub mod btf_max_transversal;
 pub mod btf_scc;
+pub mod dense;
 mod error;
 pub mod klu;
 pub mod matrix;
+#[cfg(feature = "record")]
+pub mod quotient_graph;
+pub mod scalar;
 mod utils;
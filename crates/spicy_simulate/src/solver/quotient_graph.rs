@@ -0,0 +1,235 @@
+//! The quotient graph of an AMD elimination, read back from its trace (see
+//! [`KluOrdering::recorded_amd`](crate::solver::klu::KluOrdering::recorded_amd)): the
+//! supervariables left to eliminate, the elements the eliminated ones formed, and what was
+//! merged into a supervariable or absorbed into an element.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use serde_json::Value;
+use spicy_record::{Replay, Step, VecRecorder};
+
+use crate::solver::utils::{EMPTY, flip};
+
+/// The quotient graph between two pivots of an AMD elimination.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuotientGraph {
+    /// The principal variables, `(variable, size)`: a supervariable of `size` variables.
+    pub variables: Vec<(usize, usize)>,
+    /// The elements not absorbed yet.
+    pub elements: Vec<usize>,
+    /// `(variable, element)`: the variable is in the pattern of the element.
+    pub element_edges: Vec<(usize, usize)>,
+    /// `(i, j)` with `i < j`: the entries left between two variables.
+    pub variable_edges: Vec<(usize, usize)>,
+    /// `(variable, parent)`: the variable was merged into the supervariable `parent`, or
+    /// eliminated with the element `parent`.
+    pub merged: Vec<(usize, usize)>,
+    /// `(element, parent)`: the element was absorbed into the element `parent`.
+    pub absorbed: Vec<(usize, usize)>,
+}
+
+/// The integers of the array `name` of `values`.
+fn integers(values: &BTreeMap<String, Value>, name: &str) -> Option<Vec<isize>> {
+    values
+        .get(name)?
+        .as_array()?
+        .iter()
+        .map(|value| value.as_i64().map(|value| value as isize))
+        .collect()
+}
+
+impl QuotientGraph {
+    /// The graph of the arrays `pe`, `iw`, `len`, `elen` and `nv` of a replay's `values`; none
+    /// if one of them is missing.
+    pub fn from_values(values: &BTreeMap<String, Value>) -> Option<Self> {
+        let pe = integers(values, "pe")?;
+        let iw = integers(values, "iw")?;
+        let len = integers(values, "len")?;
+        let elen = integers(values, "elen")?;
+        let nv = integers(values, "nv")?;
+        let n = nv.len();
+
+        let is_variable = |i: usize| elen[i] >= 0 && nv[i] != 0;
+        // an element's degree is flipped into elen, its parent into pe once absorbed
+        let is_element = |e: usize| elen[e] < EMPTY && pe[e] >= EMPTY;
+
+        let mut graph = QuotientGraph::default();
+        let mut variable_edges = BTreeSet::new();
+        let mut element_edges = BTreeSet::new();
+        for i in 0..n {
+            if is_variable(i) {
+                graph.variables.push((i, nv[i].unsigned_abs()));
+                let start = pe[i] as usize;
+                // the elements of the variable come first, then the variables
+                for (k, &j) in iw[start..start + len[i] as usize].iter().enumerate() {
+                    let j = j as usize;
+                    if k < elen[i] as usize {
+                        if is_element(j) {
+                            element_edges.insert((i, j));
+                        }
+                    } else if j != i && is_variable(j) {
+                        variable_edges.insert((i.min(j), i.max(j)));
+                    }
+                }
+            } else if is_element(i) {
+                graph.elements.push(i);
+            } else if pe[i] < EMPTY {
+                let parent = flip(pe[i]) as usize;
+                match elen[i] < EMPTY {
+                    true => graph.absorbed.push((i, parent)),
+                    false => graph.merged.push((i, parent)),
+                }
+            }
+        }
+        graph.variable_edges = variable_edges.into_iter().collect();
+        graph.element_edges = element_edges.into_iter().collect();
+        Some(graph)
+    }
+
+    /// The graph every time the trace of an elimination picks a pivot, with the pivot.
+    pub fn at_pivots(trace: VecRecorder) -> Vec<(usize, QuotientGraph)> {
+        let mut replay = Replay::new(trace);
+        let mut graphs = Vec::new();
+        while let Some(step) = replay.step() {
+            let pivot = match step {
+                Step::Number { name, value, .. } if name == "me" => value.as_u64(),
+                _ => None,
+            };
+            if let Some(pivot) = pivot
+                && let Some(graph) = Self::from_values(replay.values())
+            {
+                graphs.push((pivot as usize, graph));
+            }
+        }
+        graphs
+    }
+
+    /// Draw the graph as Graphviz DOT: the variables as circles, labelled with their size when
+    /// it is more than one, the elements as boxes, and what was merged or absorbed grayed out
+    /// and dashed to where it went. The pivot is filled.
+    pub fn write_dot<W: Write>(&self, w: &mut W, pivot: Option<usize>) -> io::Result<()> {
+        let elements: BTreeSet<usize> = self
+            .elements
+            .iter()
+            .copied()
+            .chain(self.absorbed.iter().map(|&(e, _)| e))
+            .collect();
+        let node = |i: usize| match elements.contains(&i) {
+            true => format!("e{i}"),
+            false => format!("v{i}"),
+        };
+
+        writeln!(w, "graph quotient {{")?;
+        for &(v, size) in &self.variables {
+            let label = match size {
+                1 => v.to_string(),
+                size => format!("{v} ({size})"),
+            };
+            let fill = match pivot == Some(v) {
+                true => r#", style=filled, fillcolor="gold""#,
+                false => "",
+            };
+            writeln!(w, r#"  v{v} [shape=circle, label="{label}"{fill}];"#)?;
+        }
+        for &e in &self.elements {
+            writeln!(w, "  e{e} [shape=box];")?;
+        }
+        for &(v, e) in &self.element_edges {
+            writeln!(w, "  v{v} -- e{e};")?;
+        }
+        for &(i, j) in &self.variable_edges {
+            writeln!(w, "  v{i} -- v{j};")?;
+        }
+        for &(v, parent) in &self.merged {
+            writeln!(w, "  v{v} [shape=circle, color=gray, fontcolor=gray];")?;
+            writeln!(w, "  v{v} -- {} [style=dashed, color=gray];", node(parent))?;
+        }
+        for &(e, parent) in &self.absorbed {
+            writeln!(w, "  e{e} [shape=box, color=gray, fontcolor=gray];")?;
+            writeln!(w, "  e{e} -- {} [style=dashed, color=gray];", node(parent))?;
+        }
+        writeln!(w, "}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::klu::amd;
+    use crate::solver::matrix::{Dim, csc::CscPointers};
+
+    /// The 5x5 regression matrix of the AMD port, ordered by AMD with its elimination recorded.
+    fn recorded_elimination() -> (Vec<isize>, VecRecorder) {
+        let column_pointers = [0, 2, 6, 10, 12, 14];
+        let row_indices = [0, 1, 0, 1, 2, 4, 1, 2, 3, 4, 2, 3, 1, 4];
+        let a = CscPointers::new(Dim { nrows: 5, ncols: 5 }, &column_pointers, &row_indices);
+        let mut permutation = vec![0; 5];
+        let mut recorder = VecRecorder::new();
+        amd(a, &mut permutation, &mut recorder);
+        (permutation, recorder)
+    }
+
+    #[test]
+    fn the_first_pivot_sees_the_matrix_graph() {
+        let (_, trace) = recorded_elimination();
+        let graphs = QuotientGraph::at_pivots(trace);
+
+        let (pivot, first) = &graphs[0];
+        assert_eq!(*pivot, 3);
+        assert_eq!(first.variables, (0..5).map(|i| (i, 1)).collect::<Vec<_>>());
+        assert_eq!(
+            first.variable_edges,
+            [(0, 1), (1, 2), (1, 4), (2, 3), (2, 4)]
+        );
+        assert!(first.elements.is_empty() && first.element_edges.is_empty());
+
+        // eliminating 3 formed the element 3, its edge to 2 now through it
+        let (pivot, second) = &graphs[1];
+        assert_eq!(*pivot, 0);
+        assert_eq!(second.elements, [3]);
+        assert_eq!(second.element_edges, [(2, 3)]);
+        assert_eq!(second.variable_edges, [(0, 1), (1, 2), (1, 4), (2, 4)]);
+    }
+
+    #[test]
+    fn the_last_pivot_absorbs_the_elements_and_its_supervariable() {
+        let (permutation, trace) = recorded_elimination();
+        let mut replay = Replay::new(trace.clone());
+        replay.seek(usize::MAX);
+        let end = QuotientGraph::from_values(replay.values()).unwrap();
+        let pivots: Vec<_> = QuotientGraph::at_pivots(trace)
+            .into_iter()
+            .map(|(pivot, _)| pivot)
+            .collect();
+
+        assert_eq!(pivots, [3, 0, 1]);
+        assert!(end.variables.is_empty());
+        assert_eq!(end.elements, [1]);
+        assert_eq!(end.absorbed, [(0, 1), (3, 1)]);
+        assert_eq!(end.merged, [(2, 1), (4, 1)]);
+        // and its variables are ordered just before it
+        assert_eq!(permutation[2..], [2, 4, 1]);
+    }
+
+    #[test]
+    fn dot_draws_the_pivot_and_where_the_rest_went() {
+        let (_, trace) = recorded_elimination();
+        let (pivot, graph) = QuotientGraph::at_pivots(trace.clone()).pop().unwrap();
+        let mut dot = Vec::new();
+        graph.write_dot(&mut dot, Some(pivot)).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("graph quotient {\n") && dot.ends_with("}\n"));
+        assert!(dot.contains(r#"  v1 [shape=circle, label="1", style=filled, fillcolor="gold"];"#));
+        assert!(dot.contains("  e0 [shape=box];\n  e3 [shape=box];\n  v1 -- e0;\n  v2 -- e3;\n"));
+
+        let mut replay = Replay::new(trace);
+        replay.seek(usize::MAX);
+        let mut dot = Vec::new();
+        let end = QuotientGraph::from_values(replay.values()).unwrap();
+        end.write_dot(&mut dot, None).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.contains("  v2 -- e1 [style=dashed, color=gray];"));
+        assert!(dot.contains("  e3 -- e1 [style=dashed, color=gray];"));
+    }
+}
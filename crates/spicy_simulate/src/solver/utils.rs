@@ -1,6 +1,8 @@
 use std::{mem, slice};
 use thiserror::Error;
 
+use crate::solver::scalar::Scalar;
+
 pub const EMPTY: isize = -1;
 
 /// negation about -1, used to mark an integer i that is normally non-negative.
@@ -85,6 +87,23 @@ pub(crate) unsafe fn as_usize_slice(s: &[isize]) -> &[usize] {
     unsafe { slice::from_raw_parts(ptr, len) }
 }
 
+/// Reinterpret a slice of `f64` units as the scalars `T` they hold, two `f32` to a unit.
+pub(crate) fn units_as_scalars<T: Scalar>(s: &[f64]) -> &[T] {
+    debug_assert!(mem::align_of::<T>() <= mem::align_of::<f64>());
+    let len = mem::size_of_val(s) / mem::size_of::<T>();
+    // SAFETY: `T` is `f32` or `f64` (the trait is sealed): its size divides that of `f64`,
+    // its alignment is at most that of `f64` and every bit pattern is a valid value.
+    unsafe { slice::from_raw_parts(s.as_ptr() as *const T, len) }
+}
+
+/// Reinterpret a mutable slice of `f64` units as the scalars `T` they hold.
+pub(crate) fn units_as_scalars_mut<T: Scalar>(s: &mut [f64]) -> &mut [T] {
+    debug_assert!(mem::align_of::<T>() <= mem::align_of::<f64>());
+    let len = mem::size_of_val(s) / mem::size_of::<T>();
+    // SAFETY: as for `units_as_scalars`
+    unsafe { slice::from_raw_parts_mut(s.as_mut_ptr() as *mut T, len) }
+}
+
 /// Rust equivalent of the KLU `DUNITS` macro.
 ///
 /// In the C implementation (`klu_version.h`):
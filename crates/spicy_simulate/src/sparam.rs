@@ -0,0 +1,300 @@
+//! S-parameter analysis (`.sp`) and its Touchstone files.
+//!
+//! Every port is a node referenced to ground and terminated in the reference impedance z0.
+//! Port j is driven by a 2 V source behind z0 (as its Norton equivalent, 2 / z0 into the
+//! node), so the incident wave is 1 at port j and 0 at the others: the voltage at port i is
+//! then S_ij, plus the incident wave at port j itself. The independent sources of the circuit
+//! are off. One factorization per frequency serves every port.
+
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use ndarray::Array1;
+use ndarray_linalg::{FactorizeInto, Solve};
+use spicy_parser::instance_parser::Deck;
+use spicy_parser::netlist_types::SpCommand;
+
+use crate::SimulationConfig;
+use crate::ac::{ac_frequencies, assemble_ac_real_expansion, small_signal_op};
+use crate::devices::Devices;
+use crate::error::SimulationError;
+use crate::raw_writer::output_file;
+use crate::report::{AnalysisReport, AnalysisResult};
+
+#[derive(Debug, Clone)]
+pub struct SParameters {
+    /// The port nodes, port 1 first.
+    pub ports: Vec<String>,
+    /// Reference impedance of every port (ohm).
+    pub z0: f64,
+    pub frequencies: Vec<f64>,
+    /// The S matrix per frequency, row-major: S_ij as (re, im) at `s[k][i * ports + j]`.
+    pub s: Vec<Vec<(f64, f64)>>,
+}
+
+impl SParameters {
+    /// S_ij (0-based ports) at the `k`-th frequency.
+    pub fn get(&self, k: usize, i: usize, j: usize) -> (f64, f64) {
+        self.s[k][i * self.ports.len() + j]
+    }
+
+    /// Name of S_ij (0-based ports) in the raw and table outputs, `S_1_1` for S_00.
+    pub(crate) fn name(i: usize, j: usize) -> String {
+        format!("S_{}_{}", i + 1, j + 1)
+    }
+}
+
+pub fn simulate_sp(
+    deck: &Deck,
+    cmd: &SpCommand,
+    sim_config: &SimulationConfig,
+) -> Result<SParameters, SimulationError> {
+    let mut devices = Devices::from_deck(deck, sim_config);
+    let node_mapping = &deck.node_mapping;
+    let dim = node_mapping.mna_matrix_dim();
+
+    // the parser only keeps nodes of the deck
+    let node_names = node_mapping.node_names_mna_order();
+    let ports: Vec<usize> = cmd
+        .ports
+        .iter()
+        .map(|port| {
+            node_names
+                .iter()
+                .position(|n| n == port)
+                .expect("known node")
+        })
+        .collect();
+    let z0 = cmd.z0.get_value();
+
+    let op = if devices.is_linear() {
+        None
+    } else {
+        Some(small_signal_op(deck, &mut devices, sim_config)?)
+    };
+
+    let frequencies = ac_frequencies(&cmd.sweep);
+    let mut s = Vec::with_capacity(frequencies.len());
+    for &f in &frequencies {
+        let w = 2.0 * PI * f;
+        // the right-hand side of the circuit's own sources is dropped
+        let (mut m, _) = assemble_ac_real_expansion(&devices, node_mapping, w, op.as_deref());
+        for &p in &ports {
+            m[[p, p]] += 1.0 / z0;
+            m[[dim + p, dim + p]] += 1.0 / z0;
+        }
+        let lu = m
+            .factorize_into()
+            .expect("Failed to factorize S-parameter matrix");
+
+        let mut columns = Vec::with_capacity(ports.len());
+        for (j, &driven) in ports.iter().enumerate() {
+            let mut rhs = Array1::<f64>::zeros(2 * dim);
+            rhs[driven] = 2.0 / z0;
+            let x = lu.solve(&rhs).expect("Failed to solve S-parameter system");
+            let column: Vec<(f64, f64)> = ports
+                .iter()
+                .enumerate()
+                .map(|(i, &p)| {
+                    let incident = if i == j { 1.0 } else { 0.0 };
+                    (x[p] - incident, x[dim + p])
+                })
+                .collect();
+            columns.push(column);
+        }
+        let n = ports.len();
+        s.push(
+            (0..n * n)
+                .map(|index| columns[index % n][index / n])
+                .collect(),
+        );
+    }
+
+    Ok(SParameters {
+        ports: cmd.ports.clone(),
+        z0,
+        frequencies,
+        s,
+    })
+}
+
+/// Write `sp` as a Touchstone (version 1) file: frequencies in Hz and S-parameters as real and
+/// imaginary parts. A 2-port lists S11 S21 S12 S22 on one line per frequency; larger networks
+/// list the matrix row by row, at most four parameters to a line.
+pub fn write_touchstone(
+    mut w: impl Write,
+    deck: &Deck,
+    sp: &SParameters,
+    step: Option<&str>,
+) -> io::Result<()> {
+    writeln!(w, "! {}", deck.title.trim())?;
+    if let Some(step) = step {
+        writeln!(w, "! step {step}")?;
+    }
+    let ports: Vec<String> = sp
+        .ports
+        .iter()
+        .enumerate()
+        .map(|(i, port)| format!("{}={port}", i + 1))
+        .collect();
+    writeln!(w, "! ports {}", ports.join(" "))?;
+    writeln!(w, "# Hz S RI R {}", sp.z0)?;
+
+    let n = sp.ports.len();
+    for k in 0..sp.frequencies.len() {
+        let rows: Vec<Vec<(usize, usize)>> = if n == 2 {
+            vec![vec![(0, 0), (1, 0), (0, 1), (1, 1)]]
+        } else {
+            (0..n)
+                .flat_map(|i| {
+                    let row: Vec<_> = (0..n).map(|j| (i, j)).collect();
+                    row.chunks(4).map(<[_]>::to_vec).collect::<Vec<_>>()
+                })
+                .collect()
+        };
+        for (line, row) in rows.iter().enumerate() {
+            let values: Vec<String> = row
+                .iter()
+                .map(|&(i, j)| {
+                    let (re, im) = sp.get(k, i, j);
+                    format!("{re:.9e} {im:.9e}")
+                })
+                .collect();
+            let start = if line == 0 {
+                format!("{:.9e}", sp.frequencies[k])
+            } else {
+                " ".repeat(15)
+            };
+            writeln!(w, "{start} {}", values.join(" "))?;
+        }
+    }
+    Ok(())
+}
+
+/// Write every S-parameter result of `plots` to `<output_base>.s<n>p`, numbered after the
+/// base when a `.step` gives more than one.
+pub(crate) fn write_touchstone_files(
+    deck: &Deck,
+    plots: &[AnalysisReport],
+    output_base: &Path,
+) -> io::Result<Vec<PathBuf>> {
+    let results: Vec<_> = plots
+        .iter()
+        .filter_map(|plot| match &plot.result {
+            AnalysisResult::Sp(sp) => Some((plot.label.as_deref(), sp)),
+            _ => None,
+        })
+        .collect();
+    let mut paths = Vec::new();
+    for (index, (step, sp)) in results.iter().enumerate() {
+        let extension = format!("s{}p", sp.ports.len());
+        let path = match results.len() {
+            1 => output_file(output_base, &extension),
+            _ => {
+                let mut base = output_base.as_os_str().to_os_string();
+                base.push(format!("-{}", index + 1));
+                output_file(Path::new(&base), &extension)
+            }
+        };
+        let mut writer = BufWriter::new(File::create(&path)?);
+        write_touchstone(&mut writer, deck, sp, *step)?;
+        writer.flush()?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spicy_parser::netlist_types::Command;
+    use spicy_parser::{ParseOptions, parse};
+
+    fn run(netlist: &str) -> (Deck, SParameters) {
+        let mut options = ParseOptions::new_with_source("sp.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let cmd = deck
+            .commands
+            .iter()
+            .find_map(|c| match c {
+                Command::Sp(sp) => Some(sp),
+                _ => None,
+            })
+            .expect("sp command");
+        let sp = simulate_sp(&deck, cmd, &SimulationConfig::default()).expect("sp");
+        (deck, sp)
+    }
+
+    fn assert_close(actual: (f64, f64), expected: (f64, f64)) {
+        let err = (actual.0 - expected.0).hypot(actual.1 - expected.1);
+        assert!(err < 1e-9, "expected {expected:?}, got {actual:?}");
+    }
+
+    #[test]
+    fn series_resistor_between_matched_ports() {
+        // the circuit's own sources are off: an open current source changes nothing
+        let (_, sp) = run("series
+I1 a 0 DC 1 AC 1
+R1 a b 50
+.sp lin 3 1k 3k a b
+.end
+");
+        assert_eq!(sp.frequencies.len(), 3);
+        for k in 0..3 {
+            // S11 = R / (R + 2 z0), S21 = 2 z0 / (R + 2 z0)
+            assert_close(sp.get(k, 0, 0), (1.0 / 3.0, 0.0));
+            assert_close(sp.get(k, 1, 1), (1.0 / 3.0, 0.0));
+            assert_close(sp.get(k, 1, 0), (2.0 / 3.0, 0.0));
+            assert_close(sp.get(k, 0, 1), (2.0 / 3.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn shunt_capacitor_reflects_all_of_the_wave() {
+        let (_, sp) = run("shunt
+C1 a 0 1n
+.sp dec 1 1Meg 100Meg a z0=75
+.end
+");
+        for (k, f) in sp.frequencies.iter().enumerate() {
+            // S11 = (Z - z0) / (Z + z0) with Z = 1 / (j w C), so (1 - jx) / (1 + jx), x = w C z0
+            let x = 2.0 * PI * f * 1e-9 * 75.0;
+            let d = 1.0 + x * x;
+            let s11 = sp.get(k, 0, 0);
+            assert_close(s11, ((1.0 - x * x) / d, -2.0 * x / d));
+            assert!((s11.0.hypot(s11.1) - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn touchstone_lists_a_two_port_by_column() {
+        let (deck, sp) = run("divider
+R1 a b 50
+R2 b 0 1Meg
+.sp lin 1 1k 2k a b
+.end
+");
+        let mut out = Vec::new();
+        write_touchstone(&mut out, &deck, &sp, None).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[..3],
+            ["! divider", "! ports 1=a 2=b", "# Hz S RI R 50"]
+        );
+
+        let values: Vec<f64> = lines[3]
+            .split_whitespace()
+            .map(|v| v.parse().unwrap())
+            .collect();
+        assert_eq!(values.len(), 9);
+        assert_eq!(values[0], 1e3);
+        let [s11, s21, s12, s22] = [0, 1, 2, 3].map(|p| (values[1 + 2 * p], values[2 + 2 * p]));
+        assert_close(s11, sp.get(0, 0, 0));
+        assert_close(s21, sp.get(0, 1, 0));
+        assert_close(s12, sp.get(0, 0, 1));
+        assert_close(s22, sp.get(0, 1, 1));
+    }
+}
@@ -0,0 +1,413 @@
+//! `.step` parameter sweeps and `.temp` temperature sweeps.
+//!
+//! Every step is the netlist parsed again with the stepped `.param`s overridden, so the
+//! expressions that depend on them are evaluated again, and every analysis of the deck runs on
+//! the result once per `.temp` temperature.
+
+use std::io::Write;
+use std::time::Instant;
+
+use rayon::prelude::*;
+use spicy_parser::instance_parser::Deck;
+use spicy_parser::netlist_types::{Command, OutputKind, StepCommand, StepSweep};
+use spicy_parser::{ParseOptions, parse_with_params};
+
+use crate::ac::ac_frequencies;
+use crate::dc::sweep;
+use crate::error::SimulationError;
+use crate::ipc::IpcSink;
+use crate::{AnalysisReport, SimulationConfig, fft, measure, observer, run_analysis};
+
+/// The values `step` gives its parameter.
+pub fn step_values(step: &StepCommand) -> Vec<f64> {
+    match &step.sweep {
+        StepSweep::Linear { start, stop, incr } => {
+            sweep(start.get_value(), stop.get_value(), incr.get_value())
+        }
+        StepSweep::Logarithmic(points) => ac_frequencies(points),
+        StepSweep::List(values) => values.iter().map(|v| v.get_value()).collect(),
+    }
+}
+
+/// Every combination of the values of `steps`, the first `.step` outermost.
+pub fn step_points(steps: &[StepCommand]) -> Vec<Vec<(String, f64)>> {
+    steps.iter().fold(vec![Vec::new()], |points, step| {
+        let values = step_values(step);
+        points
+            .iter()
+            .flat_map(|point| {
+                values.iter().map(move |&value| {
+                    let mut point = point.clone();
+                    point.push((step.param.clone(), value));
+                    point
+                })
+            })
+            .collect()
+    })
+}
+
+/// `name=value` of every stepped parameter, e.g. `rload=1000, gain=2`.
+fn step_label(point: &[(String, f64)]) -> String {
+    point
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Circuit temperatures (°C) of the deck's `.temp`, the configured one without it.
+pub fn temperatures(deck: &Deck, sim_config: &SimulationConfig) -> Vec<f64> {
+    if deck.temperatures.is_empty() {
+        vec![sim_config.temperature]
+    } else {
+        deck.temperatures.iter().map(|t| t.get_value()).collect()
+    }
+}
+
+/// One analysis of the deck at one step and temperature.
+struct Job<'a> {
+    deck: &'a Deck,
+    command: &'a Command,
+    /// position of the command in the deck
+    index: usize,
+    label: Option<String>,
+    temperature: f64,
+}
+
+impl Job<'_> {
+    /// Run the analysis, printing its `.print` table, measurements and Fourier analyses to
+    /// `stdout`. Returns `None` for `.end` and once the simulation is cancelled.
+    fn run(
+        &self,
+        sim_config: &SimulationConfig,
+        ipc: Option<&mut IpcSink>,
+        stdout: &mut Vec<u8>,
+    ) -> Result<Option<AnalysisReport>, SimulationError> {
+        if sim_config.cancel.is_cancelled() {
+            return Ok(None);
+        }
+        if let Some(observer) = &sim_config.observer {
+            observer::check(observer.on_analysis_start(self.command, self.label.as_deref()))?;
+        }
+        let sim_config = SimulationConfig {
+            temperature: self.temperature,
+            ..sim_config.clone()
+        };
+        let start = Instant::now();
+        let result = match run_analysis(self.deck, self.command, &sim_config, ipc, &mut *stdout) {
+            Ok(Some(result)) => result,
+            // nothing to keep of an operating point cut short
+            Ok(None) | Err(SimulationError::Cancelled) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let duration = start.elapsed();
+        let measurements = measure::measure(self.deck, &result);
+        for measurement in &measurements {
+            let _ = writeln!(stdout, "{measurement}");
+        }
+        let fourier = fft::fourier(self.deck, &result);
+        for analysis in &fourier {
+            let _ = write!(stdout, "{analysis}");
+        }
+        let report = AnalysisReport {
+            label: self.label.clone(),
+            result,
+            duration,
+            measurements,
+            fourier,
+        };
+        if let Some(observer) = &sim_config.observer {
+            observer.on_analysis_end(&report);
+        }
+        Ok(Some(report))
+    }
+}
+
+/// Run every analysis of `deck` at every step and temperature; the `.step`s are only run given
+/// the `options` to parse the netlist again. Returns, per analysis, the result at every point
+/// along with its label, which is `None` for a deck without sweeps, and its run time.
+/// Once `sim_config.cancel` is cancelled no further analysis runs.
+///
+/// With `sim_config.parallel` the analyses run on the rayon thread pool, unless they are
+/// streamed to a viewer. The results and the `.print` tables come out in the same order either
+/// way.
+pub(crate) fn run_steps(
+    mut options: Option<&mut ParseOptions>,
+    deck: &Deck,
+    sim_config: &SimulationConfig,
+    mut ipc: Option<&mut IpcSink>,
+    mut stdout: impl Write,
+) -> Result<Vec<Vec<AnalysisReport>>, SimulationError> {
+    let points = match options {
+        Some(_) => step_points(&deck.steps),
+        None => vec![Vec::new()],
+    };
+    let temperatures = temperatures(deck, sim_config);
+    let prints = sim_config.op_report
+        || !deck.measures.is_empty()
+        || !deck.fourier.is_empty()
+        || deck.outputs.iter().any(|o| o.kind == OutputKind::Print);
+
+    // parsing needs `options`, so every step is parsed before any analysis runs
+    let mut stepped = Vec::with_capacity(points.len());
+    for point in &points {
+        stepped.push(match options.as_deref_mut() {
+            Some(options) if !point.is_empty() => Some(parse_with_params(options, point)?),
+            _ => None,
+        });
+    }
+
+    let mut jobs = Vec::new();
+    for (point, stepped) in points.iter().zip(&stepped) {
+        let stepped = stepped.as_ref().unwrap_or(deck);
+        for &temperature in &temperatures {
+            let mut point = point.clone();
+            if temperatures.len() > 1 {
+                point.push(("temp".to_string(), temperature));
+            }
+            let label = (!point.is_empty()).then(|| step_label(&point));
+            for (index, command) in stepped.commands.iter().enumerate() {
+                jobs.push(Job {
+                    deck: stepped,
+                    command,
+                    index,
+                    label: label.clone(),
+                    temperature,
+                });
+            }
+        }
+    }
+
+    let outputs: Vec<_> = if sim_config.parallel && ipc.is_none() {
+        jobs.par_iter()
+            .map(|job| {
+                let mut printed = Vec::new();
+                (job.run(sim_config, None, &mut printed), printed)
+            })
+            .collect()
+    } else {
+        let mut outputs = Vec::with_capacity(jobs.len());
+        for job in &jobs {
+            let mut printed = Vec::new();
+            let result = job.run(sim_config, ipc.as_deref_mut(), &mut printed);
+            let failed = result.is_err();
+            outputs.push((result, printed));
+            if failed {
+                break;
+            }
+        }
+        outputs
+    };
+
+    let mut plots: Vec<Vec<_>> = deck.commands.iter().map(|_| Vec::new()).collect();
+    for (job, (result, printed)) in jobs.iter().zip(outputs) {
+        if let (true, 0, Some(label)) = (prints, job.index, &job.label) {
+            let _ = writeln!(stdout, "step {label}");
+        }
+        let _ = stdout.write_all(&printed);
+        if let (Some(report), Some(plots)) = (result?, plots.get_mut(job.index)) {
+            plots.push(report);
+        }
+    }
+    Ok(plots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw_writer::write_plots;
+    use crate::test_utils::parse_with_options;
+    use crate::{AnalysisResult, OperatingPointResult, RawFormat};
+
+    #[test]
+    fn steps_are_nested_with_the_first_outermost() {
+        let (_, deck) = parse_with_options(
+            "steps
+.param r=1k c=1n
+R1 a 0 {r}
+C1 a 0 {c}
+.step param r 1k 2k 1k
+.step dec param c 1n 100n 1
+.end
+",
+        );
+        let points = step_points(&deck.steps);
+        let values: Vec<Vec<f64>> = points
+            .iter()
+            .map(|point| point.iter().map(|(_, v)| *v).collect())
+            .collect();
+        assert_eq!(points[0][0].0, "r");
+        assert_eq!(points[0][1].0, "c");
+        assert_eq!(values.len(), 6);
+        for (i, expected) in [[1e3, 1e-9], [1e3, 1e-8], [1e3, 1e-7], [2e3, 1e-9]]
+            .iter()
+            .enumerate()
+        {
+            assert!((values[i][0] - expected[0]).abs() < 1e-9);
+            assert!((values[i][1] - expected[1]).abs() / expected[1] < 1e-9);
+        }
+    }
+
+    #[test]
+    fn every_step_reevaluates_the_params_and_writes_a_plot() {
+        let (mut options, deck) = parse_with_options(
+            "stepped divider
+.param rload=1k
+V1 in 0 DC 1
+R1 in out 1k
+R2 out 0 {rload*2}
+.step param rload list 500 1k 2k
+.op
+.end
+",
+        );
+        let plots = run_steps(
+            Some(&mut options),
+            &deck,
+            &SimulationConfig::default(),
+            None,
+            std::io::sink(),
+        )
+        .expect("steps");
+        assert_eq!(plots.len(), 1);
+        assert_eq!(plots[0].len(), 3);
+        for (plot, rload) in plots[0].iter().zip([500.0, 1e3, 2e3]) {
+            assert_eq!(
+                plot.label.as_deref(),
+                Some(format!("rload={rload}").as_str())
+            );
+            let AnalysisResult::Op(op) = &plot.result else {
+                panic!("expected an operating point");
+            };
+            let (_, out) = op.voltages.iter().find(|(n, _)| n == "out").unwrap();
+            let r2 = 2.0 * rload;
+            assert!((out - r2 / (1e3 + r2)).abs() < 1e-9);
+        }
+
+        let mut raw = Vec::new();
+        write_plots(&mut raw, &deck, &plots[0], RawFormat::Binary).expect("write plots");
+        let raw = String::from_utf8_lossy(&raw);
+        assert_eq!(raw.matches("Plotname:").count(), 3);
+        assert!(raw.contains("Plotname: Operation Point (step rload=2000)"));
+    }
+
+    fn operating_points(netlist: &str) -> Vec<(Option<String>, OperatingPointResult)> {
+        let (_, deck) = parse_with_options(netlist);
+        let mut plots = run_steps(
+            None,
+            &deck,
+            &SimulationConfig::default(),
+            None,
+            std::io::sink(),
+        )
+        .expect("temperature sweep");
+        plots
+            .remove(0)
+            .into_iter()
+            .map(|plot| match plot.result {
+                AnalysisResult::Op(op) => (plot.label, op),
+                _ => panic!("expected an operating point"),
+            })
+            .collect()
+    }
+
+    fn voltage(op: &OperatingPointResult, node: &str) -> f64 {
+        op.voltages.iter().find(|(n, _)| n == node).unwrap().1
+    }
+
+    #[test]
+    fn temperature_sweep_applies_the_resistor_coefficients() {
+        let ops = operating_points(
+            "tc divider
+V1 in 0 DC 1
+R1 in out 1k tc1=4m tc2=10u
+R2 out 0 1k
+.temp 27 77
+.op
+.end
+",
+        );
+        assert_eq!(ops.len(), 2);
+        assert_eq!(ops[0].0.as_deref(), Some("temp=27"));
+        assert_eq!(ops[1].0.as_deref(), Some("temp=77"));
+        assert!((voltage(&ops[0].1, "out") - 0.5).abs() < 1e-9);
+        // 1 + 4m * 50 + 10u * 50^2
+        let r1 = 1e3 * 1.225;
+        assert!((voltage(&ops[1].1, "out") - 1e3 / (r1 + 1e3)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn diode_voltage_drops_as_the_temperature_rises() {
+        let ops = operating_points(
+            "hot diode
+I1 a 0 DC 1m
+D1 a 0 dmod
+.model dmod D(is=1e-14)
+.temp 127
+.op
+.end
+",
+        );
+        // a single temperature is not a sweep
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].0, None);
+
+        let ratio: f64 = (127.0 + 273.15) / (27.0 + 273.15);
+        let vt = 0.02585 * ratio;
+        let is = 1e-14 * ratio.powi(3) * ((ratio - 1.0) * 1.11 / vt).exp();
+        let expected = vt * (1e-3 / is).ln_1p();
+        let v = voltage(&ops[0].1, "a");
+        assert!((v - expected).abs() < 1e-4, "expected {expected}, got {v}");
+        // about -1.8 mV/K from the 0.65 V at 27 °C
+        assert!(v < 0.5);
+    }
+
+    #[test]
+    fn parallel_runs_match_the_sequential_order() {
+        let netlist = "parallel
+.param rload=1k
+V1 in 0 DC 1
+R1 in out 1k
+R2 out 0 {rload}
+C1 out 0 1u
+.step param rload list 500 1k 2k
+.temp 27 77
+.op
+.tran 100u 1m
+.print tran v(out)
+.end
+";
+        let run = |parallel| {
+            let (mut options, deck) = parse_with_options(netlist);
+            let sim_config = SimulationConfig {
+                parallel,
+                ..Default::default()
+            };
+            let mut printed = Vec::new();
+            let plots =
+                run_steps(Some(&mut options), &deck, &sim_config, None, &mut printed).expect("run");
+            (plots, String::from_utf8(printed).unwrap())
+        };
+        let (sequential, sequential_printed) = run(false);
+        let (parallel, parallel_printed) = run(true);
+
+        assert_eq!(parallel_printed, sequential_printed);
+        assert_eq!(sequential_printed.matches("step rload=").count(), 6);
+        assert_eq!(parallel.len(), 2);
+        for (sequential, parallel) in sequential.iter().zip(&parallel) {
+            assert_eq!(parallel.len(), 6);
+            for (s, p) in sequential.iter().zip(parallel) {
+                assert_eq!(p.label, s.label);
+                match (&s.result, &p.result) {
+                    (AnalysisResult::Op(s), AnalysisResult::Op(p)) => {
+                        assert_eq!(p.voltages, s.voltages)
+                    }
+                    (AnalysisResult::Tran(s), AnalysisResult::Tran(p)) => {
+                        assert_eq!(p.samples, s.samples)
+                    }
+                    _ => panic!("analyses out of order"),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,16 @@
+//! Helpers shared by the unit tests of this crate.
+
+use spicy_parser::instance_parser::Deck;
+use spicy_parser::{ParseOptions, parse};
+
+/// Parses `netlist` and keeps the options around, for tests that parse it again (`.step`).
+pub(crate) fn parse_with_options(netlist: &str) -> (ParseOptions, Deck) {
+    let mut options = ParseOptions::new_with_source("test.spicy", netlist.to_string());
+    let deck = parse(&mut options).expect("parse");
+    (options, deck)
+}
+
+/// Parses `netlist`, panicking on any parse error.
+pub(crate) fn parse_netlist(netlist: &str) -> Deck {
+    parse_with_options(netlist).1
+}
@@ -1,14 +1,19 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
-use spicy_parser::{instance_parser::Deck, netlist_types::TranCommand};
+use spicy_parser::{instance_parser::Deck, netlist_types::TranCommand, node_mapping::NodeMapping};
 
 use crate::{
-    NewtonConfig, NewtonMode, NewtonState, SimulationConfig, TransientIntegrator,
-    dc::simulate_op_inner,
-    devices::{Capacitor, Devices, Inductor},
+    NewtonConfig, NewtonMode, NewtonState, SimulationConfig, TimestepConfig, TransientIntegrator,
+    checkpoint::{self, Fingerprint, TransientState},
+    dc::{NodeConditions, solve_dc_point},
+    devices::{Capacitor, Devices, Inductor, MutualInductance, plugin::Analysis},
     error::SimulationError,
-    matrix::SolverMatrix,
+    ipc::{self, IpcMessage, IpcSink},
+    matrix::{SolverMatrix, SolverStats},
+    observer::{self, ProgressClock},
+    output::{device_current_names, transient_saves},
     util::get_voltage_diff,
+    warnings::{SimulationWarning, Warnings},
 };
 
 fn steps(dt: f64, tstop: f64) -> Vec<f64> {
@@ -20,27 +25,25 @@ fn get_previous_voltage(
     previous_voltages: &[f64],
     positive: Option<usize>,
     negative: Option<usize>,
-    ic: f64,
+    ic: Option<f64>,
     use_device_ic: bool,
 ) -> f64 {
-    if use_device_ic {
+    match ic {
         // if we set the uic flag we should just take the initial condition from the device
-        ic
-    } else {
-        get_voltage_diff(previous_voltages, positive, negative)
+        Some(ic) if use_device_ic => ic,
+        _ => get_voltage_diff(previous_voltages, positive, negative),
     }
 }
 
 fn get_previous_current(
     previous_solution: &[f64],
     branch_index: usize,
-    ic: f64,
+    ic: Option<f64>,
     use_device_ic: bool,
 ) -> f64 {
-    if use_device_ic {
-        ic
-    } else {
-        previous_solution[branch_index]
+    match ic {
+        Some(ic) if use_device_ic => ic,
+        _ => previous_solution[branch_index],
     }
 }
 
@@ -59,6 +62,19 @@ fn converged(prev: &[f64], next: &[f64], config: &NewtonConfig) -> bool {
         .all(|(&a, &b)| abs_rel_ok(a, b, config.abs_tol, config.rel_tol))
 }
 
+/// Index of the unknown that is furthest from meeting the tolerances.
+fn worst_unknown(prev: &[f64], next: &[f64], config: &NewtonConfig) -> usize {
+    prev.iter()
+        .zip(next.iter())
+        .map(|(&a, &b)| {
+            let scale = a.abs().max(b.abs());
+            (a - b).abs() / (config.abs_tol + config.rel_tol * scale)
+        })
+        .enumerate()
+        .max_by(|(_, x), (_, y)| x.total_cmp(y))
+        .map_or(0, |(i, _)| i)
+}
+
 pub(crate) fn newton_solve<F>(
     matrix: &mut SolverMatrix,
     state: &mut NewtonState,
@@ -70,7 +86,11 @@ where
     F: FnMut(&mut SolverMatrix, &[f64]) -> Result<(), SimulationError>,
 {
     let max_iters = state.config.max_iters;
+    let mut worst = 0;
     for iter in 0..max_iters {
+        if state.cancel.is_cancelled() {
+            return Err(SimulationError::Cancelled);
+        }
         matrix.clear();
         stamp(matrix, &guess)?;
 
@@ -79,7 +99,6 @@ where
         if is_init && iter == 0 {
             matrix.factorize()?;
         } else {
-            // TODO: for KLU, consider retrying full factorization on refactor failure.
             matrix.refactor()?;
         }
 
@@ -89,17 +108,32 @@ where
         }
 
         matrix.solve()?;
-        let solution = matrix.rhs().to_vec();
-
-        if iter > 0 && converged(&guess, &solution, &state.config) {
-            return Ok((solution, iter + 1));
+        let solution = state.workspace.load_solution(matrix);
+
+        if iter > 0 && converged(&guess, solution, &state.config) {
+            if let Some(dump) = &state.dump
+                && dump.wants(time)
+            {
+                // the system linearized at the solution, with the solution put back after
+                matrix.clear();
+                stamp(matrix, solution)?;
+                dump.write(matrix)?;
+                for (i, value) in solution.iter().enumerate() {
+                    *matrix.get_mut_rhs(i) = *value;
+                }
+            }
+            // the guess vector carries the solution out, the workspace keeps the other one
+            std::mem::swap(&mut guess, solution);
+            return Ok((guess, iter + 1));
         }
-        guess = solution;
+        worst = worst_unknown(&guess, solution, &state.config);
+        std::mem::swap(&mut guess, solution);
     }
 
     Err(SimulationError::NonConvergence {
         time,
         iters: max_iters,
+        unknown: matrix.unknown_name(worst),
     })
 }
 
@@ -111,9 +145,57 @@ pub enum Integrator<'a> {
     Trapezoidal {
         previous_output: Vec<f64>,
         previous_currents: HashMap<&'a str, f64>,
+        /// Charge currents of the two junctions of every BJT and JFET, by name.
+        previous_junction_currents: HashMap<&'a str, [f64; 2]>,
     },
 }
 
+impl Clone for Integrator<'_> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::BackwardEuler { previous } => Self::BackwardEuler {
+                previous: previous.clone(),
+            },
+            Self::Trapezoidal {
+                previous_output,
+                previous_currents,
+                previous_junction_currents,
+            } => Self::Trapezoidal {
+                previous_output: previous_output.clone(),
+                previous_currents: previous_currents.clone(),
+                previous_junction_currents: previous_junction_currents.clone(),
+            },
+        }
+    }
+
+    /// Copy into the vectors and maps already there, so the trial of every adaptive step
+    /// reuses them.
+    fn clone_from(&mut self, source: &Self) {
+        match (self, source) {
+            (Self::BackwardEuler { previous }, Self::BackwardEuler { previous: source }) => {
+                previous.clone_from(source);
+            }
+            (
+                Self::Trapezoidal {
+                    previous_output,
+                    previous_currents,
+                    previous_junction_currents,
+                },
+                Self::Trapezoidal {
+                    previous_output: source_output,
+                    previous_currents: source_currents,
+                    previous_junction_currents: source_junction_currents,
+                },
+            ) => {
+                previous_output.clone_from(source_output);
+                previous_currents.clone_from(source_currents);
+                previous_junction_currents.clone_from(source_junction_currents);
+            }
+            (this, source) => *this = source.clone(),
+        }
+    }
+}
+
 impl<'a> Integrator<'a> {
     fn capacitor_values(
         &self,
@@ -139,6 +221,7 @@ impl<'a> Integrator<'a> {
             Integrator::Trapezoidal {
                 previous_output,
                 previous_currents,
+                ..
             } => {
                 let c = device.capacitance;
                 let g = 2.0 * c / config.step;
@@ -150,12 +233,39 @@ impl<'a> Integrator<'a> {
                     config.use_device_ic,
                 );
                 let previous_current = previous_currents.get(device.name.as_str()).unwrap_or(&0.0);
-                let i = -g * previous_voltage - previous_current;
+                // i_n = g * (v_n - v_prev) - i_prev, the history part is injected like BE's
+                let i = g * previous_voltage + previous_current;
                 (g, i)
             }
         }
     }
 
+    /// Current of `device` at the end of a step of length `h` from `previous` to `x`: the
+    /// trapezoidal rule keeps its own, for backward Euler it is the charge moved over the step.
+    fn capacitor_current(
+        &self,
+        device: &Capacitor,
+        node_mapping: &NodeMapping,
+        previous: &[f64],
+        x: &[f64],
+        h: f64,
+    ) -> f64 {
+        match self {
+            Integrator::BackwardEuler { previous: _ } => {
+                let pos = node_mapping.mna_node_index(device.positive);
+                let neg = node_mapping.mna_node_index(device.negative);
+                let dv = get_voltage_diff(x, pos, neg) - get_voltage_diff(previous, pos, neg);
+                device.capacitance * dv / h
+            }
+            Integrator::Trapezoidal {
+                previous_currents, ..
+            } => previous_currents
+                .get(device.name.as_str())
+                .copied()
+                .unwrap_or_default(),
+        }
+    }
+
     fn save_capacitor_current(&mut self, device: &'a Capacitor, current: f64) {
         match self {
             Integrator::BackwardEuler { previous: _ } => {}
@@ -167,6 +277,37 @@ impl<'a> Integrator<'a> {
         }
     }
 
+    /// Companion model of the two junction charges of the BJT or JFET `name`: `(k, i_hist)` per
+    /// junction, the junction current being `k * (q - q_prev) + i_hist`.
+    fn junction_charge_values(&self, name: &str, config: &TransientConfig) -> [(f64, f64); 2] {
+        match self {
+            Integrator::BackwardEuler { previous: _ } => [(1.0 / config.step, 0.0); 2],
+            Integrator::Trapezoidal {
+                previous_junction_currents,
+                ..
+            } => {
+                let previous = previous_junction_currents
+                    .get(name)
+                    .copied()
+                    .unwrap_or_default();
+                // i_n = 2 (q_n - q_prev) / h - i_prev
+                previous.map(|i| (2.0 / config.step, -i))
+            }
+        }
+    }
+
+    fn save_junction_currents(&mut self, name: &'a str, currents: [f64; 2]) {
+        match self {
+            Integrator::BackwardEuler { previous: _ } => {}
+            Integrator::Trapezoidal {
+                previous_junction_currents,
+                ..
+            } => {
+                previous_junction_currents.insert(name, currents);
+            }
+        }
+    }
+
     fn inductor_values(
         &self,
         device: &Inductor,
@@ -200,19 +341,58 @@ impl<'a> Integrator<'a> {
         }
     }
 
-    fn save_previous_voltage(&mut self, voltage: Vec<f64>) {
-        match self {
-            Integrator::BackwardEuler { previous } => {
-                *previous = voltage;
-            }
+    /// Companion model of a coupling: the resistance `r_m` between the two branches and the
+    /// history terms added to each inductor's KVL row.
+    fn mutual_inductance_values(
+        &self,
+        device: &MutualInductance,
+        inductors: &[Inductor],
+        branches: (usize, usize),
+        config: &TransientConfig,
+    ) -> (f64, (f64, f64)) {
+        let mutual = device.mutual_inductance(inductors);
+        let (r_m, previous) = match self {
+            Integrator::BackwardEuler { previous } => (mutual / config.step, previous),
+            // the previous voltages in the inductors' own history already include the coupling
             Integrator::Trapezoidal {
                 previous_output, ..
-            } => {
-                *previous_output = voltage;
+            } => (2.0 * mutual / config.step, previous_output),
+        };
+        let (l1, l2) = device.pair(inductors);
+        let i1 = get_previous_current(previous, branches.0, l1.ic, config.use_device_ic);
+        let i2 = get_previous_current(previous, branches.1, l2.ic, config.use_device_ic);
+        (r_m, (-r_m * i2, -r_m * i1))
+    }
+
+    /// Take the trapezoidal rule's currents of a checkpoint, by the names of `devices`.
+    fn restore_currents(&mut self, devices: &'a Devices, state: &TransientState) {
+        for (name, current) in &state.capacitor_currents {
+            if let Some(c) = devices.capacitors.iter().find(|c| &c.name == name) {
+                self.save_capacitor_current(c, *current);
+            }
+        }
+        let junctions = devices
+            .bjts
+            .iter()
+            .map(|bjt| &bjt.name)
+            .chain(devices.jfets.iter().map(|jfet| &jfet.name));
+        for (name, currents) in &state.junction_currents {
+            if let Some(device) = junctions.clone().find(|device| *device == name) {
+                self.save_junction_currents(device, *currents);
             }
         }
     }
 
+    /// Replace the previous solution with `voltage`, returning the one it replaces.
+    fn save_previous_voltage(&mut self, voltage: Vec<f64>) -> Vec<f64> {
+        match self {
+            Integrator::BackwardEuler { previous } => std::mem::replace(previous, voltage),
+            Integrator::Trapezoidal {
+                previous_output, ..
+            } => std::mem::replace(previous_output, voltage),
+        }
+    }
+
     fn get_previous_output(&self) -> &[f64] {
         match self {
             Integrator::BackwardEuler { previous } => previous,
@@ -223,7 +403,7 @@ impl<'a> Integrator<'a> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 struct TransientConfig {
     /// the increment time
     step: f64,
@@ -250,8 +430,28 @@ fn stamp_transient<'a>(
         d.stamp_nonlinear(matrix, guess);
     }
 
+    for t in &devices.lookup_tables {
+        t.stamp_nonlinear(matrix, guess);
+    }
+
     for bjt in &devices.bjts {
         bjt.stamp_nonlinear(matrix, guess);
+        if bjt.stores_charge() {
+            let companions = integrator.junction_charge_values(&bjt.name, config);
+            bjt.stamp_charges(matrix, guess, integrator.get_previous_output(), companions);
+        }
+    }
+
+    for mosfet in &devices.mosfets {
+        mosfet.stamp_nonlinear(matrix, guess);
+    }
+
+    for jfet in &devices.jfets {
+        jfet.stamp_nonlinear(matrix, guess);
+        if jfet.stores_charge() {
+            let companions = integrator.junction_charge_values(&jfet.name, config);
+            jfet.stamp_charges(matrix, guess, integrator.get_previous_output(), companions);
+        }
     }
 
     for c in &devices.capacitors {
@@ -271,6 +471,17 @@ fn stamp_transient<'a>(
         l.stamp_trans(matrix, r_eq, v_hist);
     }
 
+    for k in &devices.mutual_inductances {
+        let (l1, l2) = k.pair(&devices.inductors);
+        let branches = (
+            matrix.mna_branch_index(l1.current_branch),
+            matrix.mna_branch_index(l2.current_branch),
+        );
+        let (r_m, v_hist) =
+            integrator.mutual_inductance_values(k, &devices.inductors, branches, config);
+        k.stamp_trans(matrix, &devices.inductors, r_m, v_hist);
+    }
+
     for vsrc in &devices.voltage_sources {
         vsrc.stamp_voltage_source_trans(matrix, config.t, config.step, config.tstop);
     }
@@ -279,9 +490,31 @@ fn stamp_transient<'a>(
         isrc.stamp_current_source_trans(matrix, config.t, config.step, config.tstop);
     }
 
+    for b in &devices.behavioral_sources {
+        b.stamp_nonlinear(matrix, guess, config.t);
+    }
+
+    for t in &devices.transmission_lines {
+        t.stamp_trans(matrix, config.t);
+    }
+
+    for s in &devices.switches {
+        s.stamp_nonlinear(matrix, guess);
+    }
+
+    for p in &devices.plugins {
+        let analysis = Analysis::Transient {
+            time: config.t,
+            step: config.step,
+        };
+        p.load(matrix, guess, analysis);
+    }
+
     Ok(())
 }
 
+/// Solve the point at `time`, heating the self-heating devices from their rise at the
+/// accepted point `t_accepted`.
 fn simulation_step<'a>(
     matrix: &mut SolverMatrix,
     devices: &'a Devices,
@@ -289,12 +522,19 @@ fn simulation_step<'a>(
     integrator: &mut Integrator<'a>,
     newton: &mut NewtonState,
     time: f64,
+    t_accepted: f64,
 ) -> Result<(Vec<f64>, usize), SimulationError> {
-    let initial_guess = integrator.get_previous_output().to_vec();
-    let (solution, iters) =
-        newton_solve(matrix, newton, initial_guess, Some(time), |m, guess| {
+    let initial_guess = newton
+        .workspace
+        .vector_from(integrator.get_previous_output());
+    let mut solve = |m: &mut SolverMatrix, guess| {
+        newton_solve(m, newton, guess, Some(time), |m, guess| {
             stamp_transient(m, devices, config, integrator, guess)
-        })?;
+        })
+    };
+    let solved = solve(matrix, initial_guess)?;
+    let (solution, iters) =
+        devices.solve_heated(matrix, solved, Some(time), Some(time - t_accepted), solve)?;
 
     if matches!(&*integrator, Integrator::Trapezoidal { .. }) {
         for c in &devices.capacitors {
@@ -302,25 +542,321 @@ fn simulation_step<'a>(
             let neg = matrix.mna_node_index(c.negative);
             let (g, i_hist) = integrator.capacitor_values(c, pos, neg, config);
             let v_new = get_voltage_diff(&solution, pos, neg);
-            let i_new = g * v_new + i_hist;
+            let i_new = g * v_new - i_hist;
             integrator.save_capacitor_current(c, i_new);
         }
+        for bjt in devices.bjts.iter().filter(|bjt| bjt.stores_charge()) {
+            let companions = integrator.junction_charge_values(&bjt.name, config);
+            let currents = bjt.junction_currents(
+                matrix.node_mapping(),
+                &solution,
+                integrator.get_previous_output(),
+                companions,
+            );
+            integrator.save_junction_currents(&bjt.name, currents);
+        }
+        for jfet in devices.jfets.iter().filter(|jfet| jfet.stores_charge()) {
+            let companions = integrator.junction_charge_values(&jfet.name, config);
+            let currents = jfet.junction_currents(
+                matrix.node_mapping(),
+                &solution,
+                integrator.get_previous_output(),
+                companions,
+            );
+            integrator.save_junction_currents(&jfet.name, currents);
+        }
     }
 
     Ok((solution, iters))
 }
 
+/// Maximum number of times a failing transient step is halved before giving up.
+const MAX_TIMESTEP_CUTS: usize = 4;
+
+/// Advance the solution from `t_prev` to `config.t`.
+///
+/// If Newton does not converge, the step is retried as 2, 4, ... equal sub-steps (up to
+/// `MAX_TIMESTEP_CUTS` halvings). Only the solution at `config.t` is returned.
+#[allow(clippy::too_many_arguments)]
+fn step_with_cuts<'a>(
+    matrix: &mut SolverMatrix,
+    devices: &'a Devices,
+    config: &TransientConfig,
+    integrator: &mut Integrator<'a>,
+    newton: &mut NewtonState,
+    t_prev: f64,
+    warnings: &mut Warnings,
+) -> Result<(Vec<f64>, usize), SimulationError> {
+    let error = match simulation_step(
+        matrix, devices, config, integrator, newton, config.t, t_prev,
+    ) {
+        Err(SimulationError::NonConvergence {
+            time,
+            iters,
+            unknown,
+        }) => {
+            warnings.push(SimulationWarning::NotConverged {
+                unknown: unknown.clone(),
+                time,
+            });
+            SimulationError::NonConvergence {
+                time,
+                iters,
+                unknown,
+            }
+        }
+        result => return result,
+    };
+
+    for cuts in 1..=MAX_TIMESTEP_CUTS {
+        let substeps = 1usize << cuts;
+        let h = (config.t - t_prev) / substeps as f64;
+        let mut trial = integrator.clone();
+        let mut total_iters = 0;
+        let mut solution = Vec::new();
+        let mut attempt = Ok(());
+        for k in 1..=substeps {
+            let sub = TransientConfig {
+                step: h,
+                t: t_prev + k as f64 * h,
+                use_device_ic: config.use_device_ic && k == 1,
+                ..*config
+            };
+            match simulation_step(matrix, devices, &sub, &mut trial, newton, sub.t, t_prev) {
+                Ok((x, iters)) => {
+                    trial.save_previous_voltage(x.clone());
+                    total_iters += iters;
+                    solution = x;
+                }
+                Err(e) => {
+                    attempt = Err(e);
+                    break;
+                }
+            }
+        }
+
+        match attempt {
+            Ok(()) => {
+                *integrator = trial;
+                warnings.push(SimulationWarning::TimestepCut {
+                    time: config.t,
+                    cuts,
+                });
+                return Ok((solution, total_iters));
+            }
+            Err(SimulationError::NonConvergence { .. }) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(error)
+}
+
+/// Smallest step the adaptive controller takes, as a fraction of `tstop`.
+const MIN_STEP_FRACTION: f64 = 1e-9;
+/// Factor by which an accepted step may grow the next one.
+const MAX_STEP_GROWTH: f64 = 2.0;
+/// Margin on the step the error estimate allows, to avoid rejecting the next one.
+const STEP_SAFETY: f64 = 0.9;
+
+/// Highest order divided difference of `values` sampled at `times`.
+fn divided_difference(times: &[f64], table: &mut [f64]) -> f64 {
+    for order in 1..table.len() {
+        for i in (order..table.len()).rev() {
+            table[i] = (table[i] - table[i - 1]) / (times[i] - times[i - order]);
+        }
+    }
+    table[table.len() - 1]
+}
+
+/// Picks the transient step from the local truncation error (LTE).
+///
+/// The LTE of an order `p` integrator is `C * h^(p+1) * x^(p+1)`, with `C` 1/2 for backward
+/// Euler and 1/12 for trapezoidal. The derivative comes from the divided differences of the
+/// last `p + 1` accepted points and the new one; until there are that many the step is
+/// accepted unchecked, so the controller starts small.
+///
+/// Like the breakpoint table of SPICE, a step never crosses the next corner of a source
+/// (a PULSE edge, a PWL point) but lands on it, and starts small again after it.
+struct TimestepController {
+    config: TimestepConfig,
+    /// order of the integrator
+    order: usize,
+    /// the `.tran` step, for the defaults of the source waveforms
+    tstep: f64,
+    tstop: f64,
+    min_step: f64,
+    max_step: f64,
+    /// the step of the next attempt
+    step: f64,
+    /// last accepted points, oldest first
+    history: VecDeque<(f64, Vec<f64>)>,
+    /// the times of the points of an LTE estimate, kept to not allocate every step
+    times: Vec<f64>,
+    /// the divided differences of one unknown over those points
+    table: Vec<f64>,
+}
+
+impl TimestepController {
+    fn new(
+        config: TimestepConfig,
+        integrator: TransientIntegrator,
+        tstep: f64,
+        tstop: f64,
+        initial: Vec<f64>,
+    ) -> Self {
+        let max_step = config.max_step.unwrap_or(tstop / 50.0);
+        let order = match integrator {
+            TransientIntegrator::BackwardEuler => 1,
+            TransientIntegrator::Trapezoidal => 2,
+        };
+        Self {
+            config,
+            order,
+            tstep,
+            tstop,
+            min_step: tstop * MIN_STEP_FRACTION,
+            max_step,
+            step: tstep.min(max_step) / 100.0,
+            history: VecDeque::from([(0.0, initial)]),
+            times: Vec::with_capacity(order + 2),
+            table: Vec::with_capacity(order + 2),
+        }
+    }
+
+    fn done(&self, t: f64) -> bool {
+        t >= self.tstop
+    }
+
+    /// Largest ratio of estimated LTE to tolerance over all unknowns for a step ending at
+    /// (`t`, `x`), `None` while there are too few points for an estimate.
+    fn error_ratio(&mut self, t: f64, x: &[f64]) -> Option<f64> {
+        let previous = self.order + 1;
+        if self.history.len() < previous {
+            return None;
+        }
+        let points = self.history.range(self.history.len() - previous..);
+        self.times.clear();
+        self.times
+            .extend(points.clone().map(|(t, _)| *t).chain([t]));
+        let (t_prev, x_prev) = &self.history[self.history.len() - 1];
+
+        let coefficient = if self.order == 1 { 0.5 } else { 1.0 / 12.0 };
+        let factorial: f64 = (1..=previous).map(|k| k as f64).product();
+        let h = t - t_prev;
+
+        let mut worst: f64 = 0.0;
+        for k in 0..x.len() {
+            self.table.clear();
+            self.table
+                .extend(points.clone().map(|(_, x)| x[k]).chain([x[k]]));
+            let derivative = factorial * divided_difference(&self.times, &mut self.table);
+            let lte = coefficient * h.powi(previous as i32) * derivative.abs();
+            let tol = self.config.abs_tol + self.config.rel_tol * x[k].abs().max(x_prev[k].abs());
+            worst = worst.max(lte / tol);
+        }
+        Some(worst)
+    }
+
+    /// The step after one of size `h` whose error ratio was `ratio`.
+    fn next_step(&self, h: f64, ratio: Option<f64>) -> f64 {
+        let growth = match ratio {
+            Some(ratio) if ratio > 0.0 => {
+                (STEP_SAFETY * ratio.powf(-1.0 / (self.order + 1) as f64)).min(MAX_STEP_GROWTH)
+            }
+            _ => MAX_STEP_GROWTH,
+        };
+        (h * growth).clamp(self.min_step, self.max_step)
+    }
+
+    /// Take the next step from `config.t`, shrinking it until its LTE is within tolerance.
+    /// Leaves `config` at the accepted step. The attempts run on `trial`, a copy of
+    /// `integrator` swapped in once one is accepted.
+    #[allow(clippy::too_many_arguments)]
+    fn advance<'a>(
+        &mut self,
+        matrix: &mut SolverMatrix,
+        devices: &'a Devices,
+        config: &mut TransientConfig,
+        integrator: &mut Integrator<'a>,
+        trial: &mut Integrator<'a>,
+        newton: &mut NewtonState,
+        warnings: &mut Warnings,
+    ) -> Result<(Vec<f64>, usize), SimulationError> {
+        let t_prev = config.t;
+        // a corner closer than the smallest step has been reached already
+        let breakpoint = devices
+            .next_breakpoint(t_prev + self.min_step, self.tstep, self.tstop)
+            .filter(|&corner| corner < self.tstop);
+        let target = breakpoint.unwrap_or(self.tstop);
+        loop {
+            // land on the breakpoint or tstop instead of leaving a sliver before it
+            let remaining = target - t_prev;
+            let last = remaining - self.step < self.min_step;
+            let h = if last { remaining } else { self.step };
+            config.step = h;
+            config.t = if last { target } else { t_prev + h };
+
+            trial.clone_from(integrator);
+            match step_with_cuts(matrix, devices, config, trial, newton, t_prev, warnings) {
+                Ok((x, iters)) => {
+                    let ratio = self.error_ratio(config.t, &x);
+                    if ratio.is_some_and(|r| r > 1.0) && h > self.min_step {
+                        self.step = self.next_step(h, ratio);
+                        continue;
+                    }
+                    std::mem::swap(integrator, trial);
+                    self.step = self.next_step(h, ratio);
+                    // the oldest point makes room for this one, in its vector
+                    let point = if self.history.len() > self.order {
+                        let (_, mut oldest) = self.history.pop_front().expect("a point");
+                        oldest.clone_from(&x);
+                        oldest
+                    } else {
+                        x.clone()
+                    };
+                    self.history.push_back((config.t, point));
+                    if last && breakpoint.is_some() {
+                        // the slope jumps at the corner, so the points before it say nothing
+                        // about the error after it: start again with a fraction of the step
+                        self.history.drain(..self.history.len() - 1);
+                        let gap = devices
+                            .next_breakpoint(config.t + self.min_step, self.tstep, self.tstop)
+                            .map_or(self.tstop, |corner| corner.min(self.tstop))
+                            - config.t;
+                        self.step = (0.1 * h.min(gap)).max(self.min_step);
+                    }
+                    return Ok((x, iters));
+                }
+                Err(SimulationError::NonConvergence { .. }) if h > self.min_step => {
+                    self.step = (h / 8.0).max(self.min_step);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TransientResult {
     pub times: Vec<f64>,
     /// names for node voltages (index aligned with solution vector 0..n-1)
     pub node_names: Vec<String>,
-    /// names for voltage source currents (index aligned after nodes)
+    /// names for voltage source currents (index aligned after nodes), followed by the devices
+    /// of `.options savecurrents`
     pub source_names: Vec<String>,
-    /// one sample per time with all unknowns (node voltages and source currents)
+    /// one sample per time with all unknowns (node voltages and source currents), followed by
+    /// the saved device currents. With `.save` only the kept vectors, in the same order, and
+    /// the names above only list those.
     pub samples: Vec<Vec<f64>>,
     /// number of Newton iterations per time sample (aligned with `times`)
     pub newton_iterations: Vec<usize>,
+    /// non-fatal problems hit during the operating point and the time steps
+    pub warnings: Vec<SimulationWarning>,
+    /// the linear solves of the operating point and the time steps
+    pub solver_stats: SolverStats,
+    /// the analysis was cancelled and the samples stop at the last accepted time step
+    pub cancelled: bool,
 }
 
 pub fn simulate_trans(
@@ -328,31 +864,186 @@ pub fn simulate_trans(
     cmd: &TranCommand,
     sim_config: &SimulationConfig,
 ) -> Result<TransientResult, SimulationError> {
-    let tstep = cmd.tstep.get_value();
-    let tstop = cmd.tstop.get_value();
+    simulate_trans_inner(deck, cmd, sim_config, None)
+}
 
-    let mut devices = Devices::from_spec(&deck.devices);
+/// Run a transient analysis, streaming every accepted time point to `ipc` if given.
+pub(crate) fn simulate_trans_inner(
+    deck: &Deck,
+    cmd: &TranCommand,
+    sim_config: &SimulationConfig,
+    ipc: Option<&mut IpcSink>,
+) -> Result<TransientResult, SimulationError> {
+    let mut devices = Devices::from_deck(deck, sim_config);
 
     let mut matrix =
         SolverMatrix::create_matrix(&mut devices, deck.node_mapping.clone(), sim_config)?;
 
+    run_transient(
+        &mut matrix,
+        &devices,
+        &deck.node_mapping,
+        &NodeConditions::from_deck(deck),
+        &device_current_names(deck),
+        transient_saves(deck).as_deref(),
+        cmd,
+        sim_config,
+        ipc,
+        None,
+    )
+}
+
+/// The state of a transient analysis at the accepted point `config.t`, for a checkpoint.
+/// `previous` is the full sample at that point, only used for the saved capacitor currents.
+fn transient_state(
+    fingerprint: Fingerprint,
+    config: &TransientConfig,
+    integrator: &Integrator,
+    previous: &[f64],
+    accepted: usize,
+    controller: Option<&TimestepController>,
+    devices: &Devices,
+) -> TransientState {
+    let (mut capacitor_currents, mut junction_currents) = match integrator {
+        Integrator::BackwardEuler { .. } => (Vec::new(), Vec::new()),
+        Integrator::Trapezoidal {
+            previous_currents,
+            previous_junction_currents,
+            ..
+        } => (
+            previous_currents
+                .iter()
+                .map(|(name, i)| (name.to_string(), *i))
+                .collect(),
+            previous_junction_currents
+                .iter()
+                .map(|(name, i)| (name.to_string(), *i))
+                .collect::<Vec<_>>(),
+        ),
+    };
+    capacitor_currents.sort_by(|a, b| a.0.cmp(&b.0));
+    junction_currents.sort_by(|a, b| a.0.cmp(&b.0));
+
+    TransientState {
+        fingerprint,
+        time: config.t,
+        use_device_ic: config.use_device_ic,
+        accepted,
+        step: controller.map(|controller| controller.step),
+        previous: integrator.get_previous_output().to_vec(),
+        previous_sample: previous.to_vec(),
+        capacitor_currents,
+        junction_currents,
+        history: controller
+            .map(|controller| controller.history.iter().cloned().collect())
+            .unwrap_or_default(),
+        lines: devices
+            .transmission_lines
+            .iter()
+            .map(|t| t.history())
+            .collect(),
+        switches: devices.switches.iter().map(|s| s.is_on()).collect(),
+        rises: devices
+            .thermal_rcs()
+            .map(|(name, thermal)| (name.to_string(), thermal.accepted()))
+            .collect(),
+    }
+}
+
+/// Run a transient analysis on an already set up matrix.
+///
+/// Without UIC the operating point starts from `op_guess` when given, otherwise from the
+/// `.nodeset` values, and holds the `.ic` nodes at their value. With UIC there is no operating
+/// point: the `.ic` node voltages and the device `ic=` values are the state at t=0.
+///
+/// Every sample is followed by the currents of the `device_names` devices, see
+/// [`device_current_names`]. With `saves` a sample only keeps the values at those positions,
+/// see [`transient_saves`].
+///
+/// With a [`Checkpoint`](crate::Checkpoint) in `sim_config` the state is saved periodically,
+/// and a resumed analysis starts from the saved state instead of the operating point.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run_transient(
+    matrix: &mut SolverMatrix,
+    devices: &Devices,
+    node_mapping: &NodeMapping,
+    conditions: &NodeConditions,
+    device_names: &[String],
+    saves: Option<&[usize]>,
+    cmd: &TranCommand,
+    sim_config: &SimulationConfig,
+    mut ipc: Option<&mut IpcSink>,
+    op_guess: Option<Vec<f64>>,
+) -> Result<TransientResult, SimulationError> {
+    let tstep = cmd.tstep.get_value();
+    let tstop = cmd.tstop.get_value();
+    let tmax = cmd.tmax.as_ref().map(|tmax| tmax.get_value());
+
     let mut config = TransientConfig {
         // TODO: this is not really correct but ok for now, tstep doesn't have to be the step size
-        step: tstep,
+        step: tmax.map_or(tstep, |tmax| tstep.min(tmax)),
         tstop,
         t: 0.0,
         use_device_ic: cmd.uic,
     };
 
+    let mut warnings = Warnings::default();
+
+    let unknowns = matrix.rhs().len();
+    let fingerprint = Fingerprint {
+        tstep,
+        tstop,
+        uic: cmd.uic,
+        trapezoidal: matches!(sim_config.integrator, TransientIntegrator::Trapezoidal),
+        adaptive: sim_config.timestep.adaptive,
+        unknowns,
+        width: saves.map_or(unknowns + device_names.len(), <[usize]>::len),
+    };
+    let resumed = match &sim_config.checkpoint {
+        Some(checkpoint) if checkpoint.resume => {
+            Some(checkpoint::read(&checkpoint.path, &fingerprint)?)
+        }
+        _ => None,
+    };
+    let mut checkpoint_writer = sim_config.checkpoint.as_ref().map(|checkpoint| {
+        checkpoint::Writer::new(&checkpoint.path, resumed.as_ref().map(|(_, points)| points))
+    });
+
     // Initialize previous solution vector.
-    let initial_condition: Vec<f64> = if cmd.uic {
-        unimplemented!("UIC is not supported yet");
+    let initial_condition: Vec<f64> = if let Some((state, _)) = &resumed {
+        state.previous.clone()
+    } else if cmd.uic {
+        // the self-heating devices start at their ambient temperature too
+        for (_, thermal) in devices.thermal_rcs() {
+            thermal.restore(0.0);
+        }
+        let mut initial = vec![0.0; matrix.rhs().len()];
+        for &(node, value) in &conditions.initial {
+            initial[node] = value;
+        }
+        initial
     } else {
         // When there is no initial conditions we use the operating point as the initial condition.
-        let mut op_state = NewtonState::new(sim_config.newton, NewtonMode::InitOp);
-        simulate_op_inner(&mut matrix, &devices, &mut op_state)?;
-        matrix.rhs().to_vec()
+        let mut op_state = NewtonState::new(sim_config.newton, NewtonMode::InitOp)
+            .with_cancel(&sim_config.cancel)
+            .with_dump(&sim_config.dump_matrix);
+        let guess = op_guess.unwrap_or_else(|| conditions.guess(matrix.rhs().len()));
+        let (solution, _iters) = solve_dc_point(
+            matrix,
+            devices,
+            &mut op_state,
+            guess,
+            &conditions.initial,
+            &mut warnings,
+        )?;
+        solution
     };
+    for p in &devices.plugins {
+        p.update_state(&initial_condition);
+    }
+    for t in &devices.transmission_lines {
+        t.start_history(node_mapping, &initial_condition);
+    }
 
     let mut integrator = match sim_config.integrator {
         TransientIntegrator::BackwardEuler => Integrator::BackwardEuler {
@@ -361,53 +1052,293 @@ pub fn simulate_trans(
         TransientIntegrator::Trapezoidal => Integrator::Trapezoidal {
             previous_output: initial_condition,
             previous_currents: HashMap::new(),
+            previous_junction_currents: HashMap::new(),
         },
     };
 
     let mut times: Vec<f64> = Vec::new();
     let mut samples: Vec<Vec<f64>> = Vec::new();
     let mut newton_iterations: Vec<usize> = Vec::new();
-    let mut newton_state = NewtonState::new(sim_config.newton, NewtonMode::InitTrans);
-
-    // initial sample at t=0 using current state (before any transient step)
-    // note this means that for UIC even the the voltage source nodes will have a value of 0 at t=0
-    times.push(0.0);
-    samples.push(integrator.get_previous_output().to_vec());
-    newton_iterations.push(0);
-
-    let steps = steps(config.step, tstop);
-    for step in steps.into_iter().skip(1) {
-        config.t = step;
-        let (x, iters) = simulation_step(
-            &mut matrix,
-            &devices,
-            &config,
-            &mut integrator,
-            &mut newton_state,
-            step,
-        )?;
+    let mut newton_state = NewtonState::new(sim_config.newton, NewtonMode::InitTrans)
+        .with_cancel(&sim_config.cancel)
+        .with_dump(&sim_config.dump_matrix);
+
+    // the points before tstart are simulated but not output; the fixed steps may land a
+    // rounding error short of it
+    let tstart = cmd.tstart.as_ref().map_or(0.0, |tstart| tstart.get_value());
+    let output_from = tstart - config.step * 1e-6;
+
+    // the full previous sample, for the capacitor currents
+    let mut previous;
+    // accepted time points, output or not, for the checkpoint period
+    let mut accepted;
+    let mut controller_state = None;
+    if let Some((state, points)) = resumed {
+        integrator.restore_currents(devices, &state);
+        for (t, history) in devices.transmission_lines.iter().zip(state.lines) {
+            t.restore_history(history);
+        }
+        for (s, on) in devices.switches.iter().zip(state.switches) {
+            s.restore_state(on);
+        }
+        for (name, thermal) in devices.thermal_rcs() {
+            if let Some((_, rise)) = state.rises.iter().find(|(device, _)| device == name) {
+                thermal.restore(*rise);
+            }
+        }
+        config.t = state.time;
+        config.use_device_ic = state.use_device_ic;
+        previous = state.previous_sample;
+        times = points.times;
+        samples = points.samples;
+        newton_iterations = points.newton_iterations;
+        controller_state = state.step.map(|step| (step, state.history));
+        accepted = state.accepted;
+    } else {
+        // initial sample at t=0 using current state (before any transient step)
+        // note this means that for UIC even the the voltage source nodes will have a value of 0
+        // at t=0
+        previous = integrator.get_previous_output().to_vec();
+        if !device_names.is_empty() {
+            // the capacitors start at rest
+            let currents = devices.device_currents(node_mapping, &previous, |_| 0.0);
+            previous.extend(currents);
+        }
+        if 0.0 >= output_from {
+            times.push(0.0);
+            samples.push(kept_values(previous.clone(), saves));
+            newton_iterations.push(0);
+        }
+        accepted = 1;
+    }
+    let mut t_accepted = config.t;
+    // in whole `tstep`s, with some slack for the rounding of `tstop / tstep`
+    let progress = ProgressClock::new("tran", (tstop / tstep - 1e-9).ceil() as usize);
+    let progress_step = |t: f64| (t / tstep + 1e-9).floor() as usize;
+    if let Some(observer) = &sim_config.observer {
+        observer::check(observer.on_timepoint(config.t, integrator.get_previous_output()))?;
+        progress.publish(observer.as_ref(), progress_step(config.t));
+    }
+
+    if let Some(sink) = ipc.as_deref_mut() {
+        ipc::publish_matrix(sink, matrix);
+        sink.publish(&IpcMessage::Signals {
+            analysis: "tran".to_string(),
+            x_name: "time".to_string(),
+            names: ipc::signal_names(node_mapping),
+        });
+        if config.t >= output_from {
+            sink.publish(&IpcMessage::Sample {
+                x: config.t,
+                values: integrator.get_previous_output().to_vec(),
+            });
+        }
+    }
 
-        integrator.save_previous_voltage(x.clone());
+    // the .tran tmax caps the step of the controller too
+    let timestep = TimestepConfig {
+        max_step: [sim_config.timestep.max_step, tmax]
+            .into_iter()
+            .flatten()
+            .reduce(f64::min),
+        ..sim_config.timestep
+    };
+    let mut controller = timestep.adaptive.then(|| {
+        let mut controller = TimestepController::new(
+            timestep,
+            sim_config.integrator,
+            tstep,
+            tstop,
+            integrator.get_previous_output().to_vec(),
+        );
+        if let Some((step, history)) = controller_state {
+            controller.step = step;
+            controller.history = history.into();
+        }
+        controller
+    });
+    let resumed_at = config.t;
+    let mut fixed_steps = steps(config.step, tstop)
+        .into_iter()
+        .skip_while(move |&step| step <= resumed_at);
+    let mut cancelled = false;
+    // the adaptive steps are tried on a copy of the integrator
+    let mut trial = integrator.clone();
+    loop {
+        let t_prev = config.t;
+        let stepped = match controller.as_mut() {
+            Some(controller) => {
+                if controller.done(t_prev) {
+                    break;
+                }
+                controller.advance(
+                    matrix,
+                    devices,
+                    &mut config,
+                    &mut integrator,
+                    &mut trial,
+                    &mut newton_state,
+                    &mut warnings,
+                )
+            }
+            None => {
+                let Some(step) = fixed_steps.next() else {
+                    break;
+                };
+                config.t = step;
+                step_with_cuts(
+                    matrix,
+                    devices,
+                    &config,
+                    &mut integrator,
+                    &mut newton_state,
+                    t_prev,
+                    &mut warnings,
+                )
+            }
+        };
+        let (x, iters) = match stepped {
+            Err(SimulationError::Cancelled) => {
+                cancelled = true;
+                break;
+            }
+            stepped => stepped?,
+        };
+        let step = config.t;
+        warnings.check_matrix(matrix, Some(step));
+
+        for p in &devices.plugins {
+            p.update_state(&x);
+        }
+        for t in &devices.transmission_lines {
+            t.record(node_mapping, step, &x);
+        }
+        for s in &devices.switches {
+            s.update_state(node_mapping, &x);
+        }
+        devices.accept_heat();
+        let voltage = newton_state.workspace.vector_from(&x);
+        let replaced = integrator.save_previous_voltage(voltage);
+        newton_state.workspace.recycle(replaced);
         config.use_device_ic = false;
+        t_accepted = step;
+        accepted += 1;
+
+        if let Some(sink) = ipc.as_deref_mut()
+            && step >= output_from
+        {
+            sink.publish(&IpcMessage::Sample {
+                x: step,
+                values: x.clone(),
+            });
+        }
+        if let Some(observer) = &sim_config.observer {
+            observer::check(observer.on_timepoint(step, &x))?;
+            progress.publish(observer.as_ref(), progress_step(step));
+        }
+
+        // the solution becomes the sample, the one vector a step keeps
+        let currents = (!device_names.is_empty()).then(|| {
+            devices.device_currents(node_mapping, &x, |c| {
+                integrator.capacitor_current(c, node_mapping, &previous, &x, step - t_prev)
+            })
+        });
+        let mut sample = x;
+        if let Some(currents) = currents {
+            sample.extend(currents);
+            previous.clone_from(&sample);
+        }
+        if step >= output_from {
+            times.push(step);
+            samples.push(kept_values(sample, saves));
+            newton_iterations.push(iters);
+        }
 
-        times.push(step);
-        samples.push(x.to_vec());
-        newton_iterations.push(iters);
+        if let Some(checkpoint) = &sim_config.checkpoint
+            && let Some(writer) = &mut checkpoint_writer
+            && (accepted - 1).is_multiple_of(checkpoint.every)
+        {
+            let state = transient_state(
+                fingerprint,
+                &config,
+                &integrator,
+                &previous,
+                accepted,
+                controller.as_ref(),
+                devices,
+            );
+            writer.write(&state, &times, &newton_iterations, &samples)?;
+        }
+    }
+
+    if let Some(writer) = &mut checkpoint_writer
+        && cancelled
+    {
+        // the step that was cancelled moved the time past the last accepted point
+        let config = TransientConfig {
+            t: t_accepted,
+            ..config
+        };
+        let state = transient_state(
+            fingerprint,
+            &config,
+            &integrator,
+            &previous,
+            accepted,
+            controller.as_ref(),
+            devices,
+        );
+        writer.write(&state, &times, &newton_iterations, &samples)?;
+    }
+
+    if let Some(sink) = ipc {
+        sink.publish(&IpcMessage::End);
+    }
+
+    let mut node_names = node_mapping.node_names_mna_order();
+    let mut source_names: Vec<String> = node_mapping
+        .branch_names_mna_order()
+        .into_iter()
+        .chain(device_names.iter().cloned())
+        .collect();
+    if let Some(saves) = saves {
+        let nodes = node_names.len();
+        let names = |names: &[String], offset: usize| {
+            saves
+                .iter()
+                .filter_map(|&i| names.get(i.checked_sub(offset)?).cloned())
+                .collect()
+        };
+        source_names = names(&source_names, nodes);
+        node_names = names(&node_names, 0);
     }
 
     Ok(TransientResult {
         times,
-        node_names: deck.node_mapping.node_names_mna_order(),
-        source_names: deck.node_mapping.branch_names_mna_order(),
+        node_names,
+        source_names,
         samples,
         newton_iterations,
+        warnings: warnings.into_vec(),
+        solver_stats: matrix.take_stats()?,
+        cancelled,
     })
 }
 
+/// The values of `sample` at the `saves` positions, all of them without `.save`.
+fn kept_values(sample: Vec<f64>, saves: Option<&[usize]>) -> Vec<f64> {
+    match saves {
+        Some(saves) => saves.iter().map(|&i| sample[i]).collect(),
+        None => sample,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::devices::NOMINAL_TEMPERATURE;
     use crate::solver::klu::KluConfig;
+    use crate::test_utils::parse_netlist;
     use crate::{LinearSolver, SimulationConfig};
     use spicy_parser::{ParseOptions, SourceMap, netlist_types::Command, parse};
     use std::path::PathBuf;
@@ -439,6 +1370,9 @@ C1 out 0 1u\n\
             work_dir: PathBuf::from("."),
             source_path,
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let deck = parse(&mut parse_options).expect("parse");
 
@@ -526,6 +1460,9 @@ C1 out 0 1u\n\
             work_dir: PathBuf::from("."),
             source_path,
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let deck = parse(&mut parse_options).expect("parse");
 
@@ -534,7 +1471,7 @@ C1 out 0 1u\n\
             ..SimulationConfig::default()
         };
 
-        let mut devices = Devices::from_spec(&deck.devices);
+        let mut devices = Devices::from_spec(&deck.devices, NOMINAL_TEMPERATURE);
         let mut matrix =
             SolverMatrix::create_matrix(&mut devices, deck.node_mapping.clone(), &sim_cfg)
                 .expect("Failed to create matrix");
@@ -543,6 +1480,7 @@ C1 out 0 1u\n\
         let mut integrator = Integrator::Trapezoidal {
             previous_output,
             previous_currents: HashMap::new(),
+            previous_junction_currents: HashMap::new(),
         };
 
         let config = TransientConfig {
@@ -560,6 +1498,7 @@ C1 out 0 1u\n\
             &mut integrator,
             &mut newton_state,
             config.step,
+            0.0,
         )
         .expect("simulation_step");
 
@@ -569,7 +1508,7 @@ C1 out 0 1u\n\
         let g = 2.0 * cap.capacitance / config.step;
         let v_new = get_voltage_diff(&solution, pos, neg);
         let i_hist = 0.0;
-        let expected_current = g * v_new + i_hist;
+        let expected_current = g * v_new - i_hist;
 
         let stored_current = match integrator {
             Integrator::Trapezoidal {
@@ -609,6 +1548,9 @@ L1 n1 0 1\n\
             work_dir: PathBuf::from("."),
             source_path,
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let deck = parse(&mut parse_options).expect("parse");
 
@@ -617,7 +1559,7 @@ L1 n1 0 1\n\
             ..SimulationConfig::default()
         };
 
-        let mut devices = Devices::from_spec(&deck.devices);
+        let mut devices = Devices::from_spec(&deck.devices, NOMINAL_TEMPERATURE);
         let mut matrix =
             SolverMatrix::create_matrix(&mut devices, deck.node_mapping.clone(), &sim_cfg)
                 .expect("Failed to create matrix");
@@ -640,6 +1582,7 @@ L1 n1 0 1\n\
             &mut integrator,
             &mut newton_state,
             config.step,
+            0.0,
         )
         .expect("simulation_step");
 
@@ -685,6 +1628,9 @@ L1 n1 0 1\n\
             work_dir: PathBuf::from("."),
             source_path,
             max_include_depth: 10,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let deck = parse(&mut parse_options).expect("parse");
 
@@ -693,7 +1639,7 @@ L1 n1 0 1\n\
             ..SimulationConfig::default()
         };
 
-        let mut devices = Devices::from_spec(&deck.devices);
+        let mut devices = Devices::from_spec(&deck.devices, NOMINAL_TEMPERATURE);
         let mut matrix =
             SolverMatrix::create_matrix(&mut devices, deck.node_mapping.clone(), &sim_cfg)
                 .expect("Failed to create matrix");
@@ -702,6 +1648,7 @@ L1 n1 0 1\n\
         let mut integrator = Integrator::Trapezoidal {
             previous_output,
             previous_currents: HashMap::new(),
+            previous_junction_currents: HashMap::new(),
         };
 
         let config = TransientConfig {
@@ -719,6 +1666,7 @@ L1 n1 0 1\n\
             &mut integrator,
             &mut newton_state,
             config.step,
+            0.0,
         )
         .expect("simulation_step");
 
@@ -748,4 +1696,237 @@ L1 n1 0 1\n\
             v
         );
     }
+
+    #[test]
+    fn divided_difference_of_a_quadratic() {
+        let times = [0.0, 1.0, 3.0];
+        let mut values = times.map(|t: f64| 2.0 * t * t + t);
+        assert!((divided_difference(&times, &mut values) - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn adaptive_step_matches_fine_fixed_step() {
+        let netlist = "rc\nV1 in 0 PULSE(0 1 1m 10u 10u 2m 4m)\nR1 in out 1k\nC1 out 0 1u\n.tran 1u 4m\n.END\n";
+        let mut options = ParseOptions::new_with_source("adaptive.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+
+        for integrator in [
+            TransientIntegrator::BackwardEuler,
+            TransientIntegrator::Trapezoidal,
+        ] {
+            let fixed = SimulationConfig {
+                integrator,
+                ..SimulationConfig::default()
+            };
+            let adaptive = SimulationConfig {
+                timestep: TimestepConfig {
+                    adaptive: true,
+                    ..TimestepConfig::default()
+                },
+                ..fixed.clone()
+            };
+            let reference = simulate_trans(&deck, tran, &fixed).expect("fixed step");
+            let result = simulate_trans(&deck, tran, &adaptive).expect("adaptive step");
+
+            assert_eq!(result.times.last(), Some(&4e-3));
+            assert!(
+                result.times.len() * 10 < reference.times.len(),
+                "{integrator:?}: {} points",
+                result.times.len()
+            );
+            // the edge at 1ms forces small steps that grow again afterwards
+            let steps: Vec<f64> = result.times.windows(2).map(|w| w[1] - w[0]).collect();
+            let smallest_after_edge = result
+                .times
+                .iter()
+                .zip(&steps)
+                .filter(|(t, _)| (1e-3..1.1e-3).contains(*t))
+                .map(|(_, h)| *h)
+                .fold(f64::INFINITY, f64::min);
+            assert!(smallest_after_edge < 10e-6, "{integrator:?}");
+
+            for (t, sample) in result.times.iter().zip(&result.samples) {
+                let expected = reference.at(*t).unwrap().voltage("out").unwrap();
+                let out = sample[1];
+                assert!(
+                    (out - expected).abs() < 1e-2,
+                    "{integrator:?} at t={t}: {out} vs {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn adaptive_step_lands_on_a_narrow_pulse() {
+        // a 2us pulse in a 5ms run, far narrower than the steps away from it
+        let netlist = "narrow\nV1 in 0 PULSE(0 1 1m 1n 1n 2u 10m)\nR1 in out 1k\nC1 out 0 1n\n\
+            .tran 10u 5m\n.END\n";
+        let deck = parse_netlist(netlist);
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+
+        for integrator in [
+            TransientIntegrator::BackwardEuler,
+            TransientIntegrator::Trapezoidal,
+        ] {
+            let config = SimulationConfig {
+                integrator,
+                timestep: TimestepConfig {
+                    adaptive: true,
+                    ..TimestepConfig::default()
+                },
+                ..SimulationConfig::default()
+            };
+            let result = simulate_trans(&deck, tran, &config).expect("adaptive step");
+
+            for edge in [1e-3, 1e-3 + 1e-9, 1e-3 + 2.001e-6, 1e-3 + 2.002e-6] {
+                assert!(
+                    result.times.iter().any(|t| (t - edge).abs() < 1e-15),
+                    "{integrator:?}: no point at {edge}"
+                );
+            }
+            // the RC (tau = 1us) charges for the 2us of the pulse
+            let out = result.node_names.iter().position(|n| n == "out").unwrap();
+            let peak = result
+                .samples
+                .iter()
+                .map(|sample| sample[out])
+                .fold(f64::MIN, f64::max);
+            let expected = 1.0 - (-2.0f64).exp();
+            assert!((peak - expected).abs() < 2e-2, "{integrator:?}: {peak}");
+        }
+    }
+
+    #[test]
+    fn tstart_hides_points_and_tmax_caps_the_step() {
+        let run = |tran: &str, adaptive: bool| {
+            let netlist = format!(
+                "rc\nV1 in 0 PULSE(0 1 100u 10u 10u 2m 4m)\nR1 in out 1k\nC1 out 0 1u\n{tran}\n.end\n"
+            );
+            let mut options = ParseOptions::new_with_source("window.spicy", netlist);
+            let deck = parse(&mut options).expect("parse");
+            let Some(Command::Tran(tran)) = deck.commands.first() else {
+                panic!("expected .tran");
+            };
+            let config = SimulationConfig {
+                timestep: TimestepConfig {
+                    adaptive,
+                    ..TimestepConfig::default()
+                },
+                ..SimulationConfig::default()
+            };
+            simulate_trans(&deck, tran, &config).expect("simulate_trans")
+        };
+
+        // the points before tstart are simulated but not output
+        let full = run(".tran 10u 1m", false);
+        let window = run(".tran 10u 1m 0.5m", false);
+        assert_eq!(window.times.len(), 51);
+        assert!((window.times[0] - 0.5e-3).abs() < 1e-12);
+        assert_eq!(window.samples, full.samples[50..]);
+
+        let capped = run(".tran 10u 1m 0 2u", false);
+        assert_eq!(capped.times.len(), 501);
+
+        let adaptive = run(".tran 10u 1m 0 5u", true);
+        let steps = adaptive.times.windows(2).map(|w| w[1] - w[0]);
+        assert!(steps.fold(0.0, f64::max) <= 5e-6 * (1.0 + 1e-9));
+    }
+
+    #[test]
+    fn saved_capacitor_current_matches_the_resistor() {
+        let netlist = "rc\nV1 in 0 DC 1\nR1 in out 1k\nC1 out 0 1u\n.ic v(out)=0\n\
+                       .options savecurrents\n.tran 10u 1m\n.end\n";
+        let mut options = ParseOptions::new_with_source("currents.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+
+        for integrator in [
+            TransientIntegrator::BackwardEuler,
+            TransientIntegrator::Trapezoidal,
+        ] {
+            let config = SimulationConfig {
+                integrator,
+                ..SimulationConfig::default()
+            };
+            let result = simulate_trans(&deck, tran, &config).expect("simulate_trans");
+            assert_eq!(result.source_names, ["V1", "R1", "C1"]);
+            let r1 = result.current("R1").unwrap().y;
+            let c1 = result.current("C1").unwrap().y;
+            // the capacitor is held at rest at t=0, then takes the whole resistor current
+            assert_eq!(c1[0], 0.0);
+            for (r, c) in r1.iter().zip(&c1).skip(1) {
+                assert!((r - c).abs() < 1e-9, "{integrator:?}: {r} vs {c}");
+            }
+            assert!(c1[1] > 0.9e-3 && *c1.last().unwrap() < 0.4e-3);
+        }
+    }
+
+    #[test]
+    fn save_keeps_only_the_listed_vectors() {
+        const RC: &str =
+            "rc\nV1 in 0 DC 1\nR1 in mid 1k\nR2 mid out 1k\nC1 out 0 1u\n.tran 10u 1m\n";
+        let full = rc_out(&format!("{RC}.end\n"));
+        // the vectors of .meas are kept too
+        let saved = rc_out(&format!(
+            "{RC}.save v(out) i(v1)\n.meas tran vmid FIND v(mid) AT=1m\n.end\n"
+        ));
+
+        assert_eq!(saved.node_names, ["mid", "out"]);
+        assert_eq!(saved.source_names, ["V1"]);
+        assert_eq!(saved.times, full.times);
+        for (s, f) in saved.samples.iter().zip(&full.samples) {
+            assert_eq!(s, &[f[1], f[2], f[3]]);
+        }
+        assert_eq!(
+            saved.voltage("out").unwrap().y,
+            full.voltage("out").unwrap().y
+        );
+        assert!(saved.voltage("in").is_none());
+
+        let all = rc_out(&format!("{RC}.save v(out)\n.probe all\n.end\n"));
+        assert_eq!(all.samples, full.samples);
+    }
+
+    fn rc_out(netlist: &str) -> TransientResult {
+        let mut options = ParseOptions::new_with_source("ic.spicy", netlist.to_string());
+        let deck = parse(&mut options).expect("parse");
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+        simulate_trans(&deck, tran, &SimulationConfig::default()).expect("simulate_trans")
+    }
+
+    #[test]
+    fn initial_conditions_set_the_state_at_t0() {
+        const RC: &str = "rc\nV1 in 0 DC 1\nR1 in out 1k\n";
+        // with tau = 1ms, out(1ms) = 1 - (1 - out(0)) / e
+        let cases = [
+            // .ic holds out at 0 while the operating point is solved
+            ("C1 out 0 1u\n.ic v(out)=0\n.tran 1u 1m\n", 0.0),
+            // with UIC .ic is the state itself
+            ("C1 out 0 1u\n.ic v(out)=0.5\n.tran 1u 1m uic\n", 0.5),
+            // and a device ic= wins over it
+            ("C1 out 0 1u ic=0.2\n.ic v(out)=0.5\n.tran 1u 1m uic\n", 0.2),
+        ];
+        for (rest, initial) in cases {
+            let result = rc_out(&format!("{RC}{rest}.END\n"));
+            let out = result.voltage("out").unwrap().y;
+            if !rest.contains("ic=") {
+                assert!((out[0] - initial).abs() < 1e-6, "{rest}: {}", out[0]);
+            }
+            let expected = 1.0 - (1.0 - initial) / std::f64::consts::E;
+            let last = *out.last().unwrap();
+            assert!(
+                (last - expected).abs() < 1e-3,
+                "{rest}: {last} vs {expected}"
+            );
+        }
+    }
 }
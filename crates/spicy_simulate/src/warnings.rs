@@ -0,0 +1,238 @@
+//! Non-fatal diagnostics collected while an analysis runs.
+//!
+//! A warning means the simulator produced a result but had to work around a numerical problem
+//! (or spotted one), so the result may be less accurate than the tolerances suggest.
+
+use std::fmt;
+
+use crate::matrix::SolverMatrix;
+
+/// Pivot ratio (min |U_kk| / max |U_kk|) below which the MNA matrix is reported as near-singular.
+const NEAR_SINGULAR_PIVOT_RATIO: f64 = 1e-13;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulationWarning {
+    /// The operating point only converged after shunting every node to ground with `gmin`,
+    /// stepped down over `steps` solves.
+    GminStepping { gmin: f64, steps: usize },
+    /// The transient step ending at `time` only converged after halving the timestep `cuts` times.
+    TimestepCut { time: f64, cuts: usize },
+    /// Newton did not get the `unknown` (a node voltage or branch current) within tolerance.
+    NotConverged { unknown: String, time: Option<f64> },
+    /// The factorized MNA matrix had a pivot ratio of `pivot_ratio`; `unknown` is the node
+    /// voltage or branch current with the smallest pivot, the most likely culprit.
+    NearSingularMatrix {
+        pivot_ratio: f64,
+        unknown: Option<String>,
+        time: Option<f64>,
+    },
+}
+
+impl fmt::Display for SimulationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::GminStepping { gmin, steps } => write!(
+                f,
+                "operating point needed gmin stepping from {gmin:e} S ({steps} steps) to converge"
+            ),
+            Self::TimestepCut { time, cuts } => {
+                write!(f, "timestep cut {cuts} times to converge at t={time:e}")
+            }
+            Self::NotConverged { unknown, time } => match time {
+                Some(t) => write!(f, "{unknown} did not converge within tolerance at t={t:e}"),
+                None => write!(f, "{unknown} did not converge within tolerance"),
+            },
+            Self::NearSingularMatrix {
+                pivot_ratio,
+                unknown,
+                time,
+            } => {
+                write!(f, "matrix is near-singular (pivot ratio {pivot_ratio:e})")?;
+                if let Some(unknown) = unknown {
+                    write!(f, " around {unknown}")?;
+                }
+                if let Some(t) = time {
+                    write!(f, " at t={t:e}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl SimulationWarning {
+    /// Stable code of the kind of warning, after the lint warnings of the parser.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::GminStepping { .. } => "W0101",
+            Self::TimestepCut { .. } => "W0102",
+            Self::NotConverged { .. } => "W0103",
+            Self::NearSingularMatrix { .. } => "W0104",
+        }
+    }
+}
+
+/// Collects warnings for a single analysis.
+#[derive(Debug, Default)]
+pub(crate) struct Warnings {
+    warnings: Vec<SimulationWarning>,
+    reported_near_singular: bool,
+}
+
+impl Warnings {
+    pub fn push(&mut self, warning: SimulationWarning) {
+        self.warnings.push(warning);
+    }
+
+    /// Inspect the last factorization of `matrix`; only the first near-singular matrix of an
+    /// analysis is reported so a transient does not repeat it for every step.
+    pub fn check_matrix(&mut self, matrix: &SolverMatrix, time: Option<f64>) {
+        if self.reported_near_singular {
+            return;
+        }
+        if let Some(pivot_ratio) = matrix.pivot_ratio()
+            && pivot_ratio < NEAR_SINGULAR_PIVOT_RATIO
+        {
+            self.reported_near_singular = true;
+            self.push(SimulationWarning::NearSingularMatrix {
+                pivot_ratio,
+                unknown: matrix.weakest_unknown(),
+                time,
+            });
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<SimulationWarning> {
+        self.warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dc::simulate_op;
+    use crate::test_utils::parse_netlist;
+    use crate::trans::simulate_trans;
+    use crate::{NewtonConfig, SimulationConfig};
+    use spicy_parser::netlist_types::Command;
+
+    fn with_max_iters(max_iters: usize) -> SimulationConfig {
+        SimulationConfig {
+            newton: NewtonConfig {
+                max_iters,
+                ..NewtonConfig::default()
+            },
+            ..SimulationConfig::default()
+        }
+    }
+
+    #[test]
+    fn gmin_stepping_rescues_operating_point() {
+        let deck = parse_netlist(
+            "diode\nV1 in 0 DC 1\nR1 in out 10\nD1 out 0 DMOD\n.MODEL DMOD D\n.OP\n.END\n",
+        );
+        let reference = simulate_op(&deck, &SimulationConfig::default()).expect("plain newton");
+        assert!(reference.warnings.is_empty());
+
+        // too few iterations for plain Newton from a zero guess, enough for each gmin step
+        let op = simulate_op(&deck, &with_max_iters(12)).expect("gmin stepping");
+        assert!(matches!(
+            op.warnings.as_slice(),
+            [
+                SimulationWarning::NotConverged { time: None, .. },
+                SimulationWarning::GminStepping { .. }
+            ]
+        ));
+        let (expected, actual) = (
+            reference.voltage("out").unwrap(),
+            op.voltage("out").unwrap(),
+        );
+        assert!((expected - actual).abs() < 1e-6, "{expected} vs {actual}");
+    }
+
+    #[test]
+    fn timestep_cut_rescues_transient() {
+        let deck = parse_netlist(
+            "rectifier\nV1 in 0 SIN(0 5 1k)\nR1 in out 10\nD1 out 0 DMOD\nC1 out 0 1u\n.MODEL DMOD D\n.tran 10u 1m\n.END\n",
+        );
+        let Some(Command::Tran(tran)) = deck.commands.first() else {
+            panic!("expected .tran");
+        };
+
+        let reference = simulate_trans(&deck, tran, &SimulationConfig::default()).expect("tran");
+        assert!(reference.warnings.is_empty());
+
+        let result = simulate_trans(&deck, tran, &with_max_iters(6)).expect("timestep cut");
+        assert_eq!(result.times, reference.times);
+        assert!(
+            result
+                .warnings
+                .iter()
+                .any(|w| matches!(w, SimulationWarning::TimestepCut { cuts, .. } if *cuts >= 1))
+        );
+        assert!(
+            result
+                .warnings
+                .iter()
+                .any(|w| matches!(w, SimulationWarning::NotConverged { time: Some(_), .. }))
+        );
+    }
+
+    #[test]
+    fn near_singular_matrix_names_the_weak_node() {
+        // `a` and `b` only leak to ground through huge resistors
+        let deck = parse_netlist(
+            "weak\nV1 in 0 DC 1\nR0 in 0 1k\nR1 a b 1k\nR2 b 0 1e18\nR3 a 0 1e18\n.OP\n.END\n",
+        );
+        let op = simulate_op(&deck, &SimulationConfig::default()).expect("op");
+        assert!(matches!(
+            op.warnings.as_slice(),
+            [SimulationWarning::NearSingularMatrix { unknown: Some(unknown), time: None, .. }]
+                if unknown == "V(b)"
+        ));
+
+        let stats = op.solver_stats;
+        assert_eq!(stats.factorizations, 1);
+        assert!(stats.refactorizations >= 1);
+        assert!(stats.rcond.unwrap() < NEAR_SINGULAR_PIVOT_RATIO);
+        assert!(stats.condest.unwrap() > 1e12);
+    }
+
+    #[test]
+    fn warning_messages() {
+        let cases = [
+            (
+                SimulationWarning::GminStepping {
+                    gmin: 1e-3,
+                    steps: 10,
+                },
+                "operating point needed gmin stepping from 1e-3 S (10 steps) to converge",
+            ),
+            (
+                SimulationWarning::TimestepCut {
+                    time: 1e-3,
+                    cuts: 2,
+                },
+                "timestep cut 2 times to converge at t=1e-3",
+            ),
+            (
+                SimulationWarning::NotConverged {
+                    unknown: "V(out)".to_string(),
+                    time: None,
+                },
+                "V(out) did not converge within tolerance",
+            ),
+            (
+                SimulationWarning::NearSingularMatrix {
+                    pivot_ratio: 1e-15,
+                    unknown: Some("V(float)".to_string()),
+                    time: Some(2e-3),
+                },
+                "matrix is near-singular (pivot ratio 1e-15) around V(float) at t=2e-3",
+            ),
+        ];
+        for (warning, expected) in cases {
+            assert_eq!(warning.to_string(), expected);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Allocations of the transient hot loop, counted by a global allocator. On its own in this
+//! binary so no other test allocates while it counts.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use spicy_parser::netlist_types::Command;
+use spicy_parser::{ParseOptions, parse};
+use spicy_simulate::solver::klu::KluConfig;
+use spicy_simulate::trans::simulate_trans;
+use spicy_simulate::{LinearSolver, SimulationConfig, TimestepConfig};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let result = f();
+    (result, ALLOCATIONS.load(Ordering::Relaxed) - before)
+}
+
+#[test]
+fn a_transient_step_allocates_only_its_sample() {
+    for adaptive in [false, true] {
+        // the extra steps only add their samples, and the growth of the result vectors
+        let (short, short_points) = run(200, adaptive);
+        let (long, long_points) = run(400, adaptive);
+        let (extra, extra_points) = (long - short, long_points - short_points);
+        assert!(
+            extra_points >= 100,
+            "adaptive: {adaptive}, {extra_points} more points"
+        );
+        assert!(
+            extra <= extra_points + 32,
+            "adaptive: {adaptive}, {extra_points} more points made {extra} allocations"
+        );
+    }
+}
+
+/// The allocations of a transient of `points` steps of `.tran`, and the points it output.
+fn run(points: usize, adaptive: bool) -> (usize, usize) {
+    let netlist = format!(
+        "diode\nV1 in 0 SIN(0 1 1k)\nR1 in out 1k\nD1 out 0 dmod\nC1 out 0 1u\n\
+         .model dmod D n=2\n.tran 1u {points}u\n.end\n"
+    );
+    let mut options = ParseOptions::new_with_source("hot_loop.spicy", netlist);
+    let deck = parse(&mut options).expect("parse");
+    let Some(Command::Tran(tran)) = deck.commands.first() else {
+        panic!("expected .tran");
+    };
+    // KLU refactors in place; the dense LU of small circuits is a new one every time
+    let config = SimulationConfig {
+        solver: LinearSolver::Klu {
+            config: KluConfig::default(),
+        },
+        // steps of at most `tstep`, for as many of them per length as the fixed ones
+        timestep: TimestepConfig {
+            adaptive,
+            max_step: Some(1e-6),
+            ..TimestepConfig::default()
+        },
+        ..SimulationConfig::default()
+    };
+    let (result, count) = allocations(|| simulate_trans(&deck, tran, &config));
+    let result = result.expect("simulate_trans");
+    (count, result.times.len())
+}
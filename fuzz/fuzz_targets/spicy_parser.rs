@@ -12,6 +12,9 @@ fuzz_target!(|data: &[u8]| {
             source_path: path,
             source_map,
             max_include_depth: 0,
+            name_case: Default::default(),
+            compatibility: Default::default(),
+            variables: Default::default(),
         };
         let _ = parse(&mut options);
     }
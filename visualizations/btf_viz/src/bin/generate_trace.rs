@@ -1,4 +1,6 @@
+use spicy_record::VecRecorder;
 use spicy_simulate::solver::matrix::builder::MatrixBuilder;
+use std::fs;
 use std::path::PathBuf;
 
 // Bring in the `code` module tree so paths like `crate::code::...` resolve
@@ -6,7 +8,6 @@ use std::path::PathBuf;
 mod code;
 
 use crate::code::btf_max_transversal::btf_max_transversal;
-use crate::code::recorder::Recorder;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Create a test matrix that triggers backtracking in the augmenting-path search.
@@ -40,13 +41,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Create recorder
     let trace_path = PathBuf::from("visualizations/btf_viz/assets/traces/sample_5x5.json");
-    let mut recorder = Recorder::new(&trace_path);
+    let mut recorder = VecRecorder::new();
 
     // Run algorithm with recorder
     let (_matches, _permutations) = btf_max_transversal(&matrix, &mut recorder);
 
     // Write trace file
-    recorder.flush()?;
+    if let Some(parent) = trace_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&trace_path, serde_json::to_string_pretty(&recorder)?)?;
 
     println!("Trace written to: {}", trace_path.display());
 
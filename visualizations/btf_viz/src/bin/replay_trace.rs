@@ -0,0 +1,227 @@
+use serde_json::Value;
+use spicy_record::{Replay, Step, VecRecorder};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+const USAGE: &str = "usage: replay_trace TRACE [SOURCE]
+
+Loads a recorded trace (a VecRecorder as JSON, or JSON lines from a JsonLinesRecorder when
+the file ends in .jsonl) and steps through it, drawing the matrix with its matched entries,
+the source line of the last step and the values of the variables.
+SOURCE is the recorded source file, src/code/btf_max_transversal.rs by default.
+
+controls, each followed by enter:
+  (empty) or s   step forward
+  b              step back
+  g N            go to step N
+  p              play, or pause while playing
+  q              quit";
+
+/// How long a frame stays up while playing.
+const FRAME: Duration = Duration::from_millis(400);
+
+enum Command {
+    Step,
+    Back,
+    Go(usize),
+    Play,
+    Quit,
+}
+
+fn parse_command(line: &str) -> Option<Command> {
+    let mut words = line.split_whitespace();
+    let command = match words.next() {
+        None | Some("s") => Command::Step,
+        Some("b") => Command::Back,
+        Some("g") => Command::Go(words.next()?.parse().ok()?),
+        Some("p") => Command::Play,
+        Some("q") => Command::Quit,
+        Some(_) => return None,
+    };
+    Some(command)
+}
+
+fn load_trace(path: &Path) -> io::Result<VecRecorder> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "jsonl")
+    {
+        VecRecorder::from_json_lines(BufReader::new(File::open(path)?))
+    } else {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+fn step_line(step: &Step) -> u32 {
+    match step {
+        Step::Step { line }
+        | Step::Number { line, .. }
+        | Step::Float { line, .. }
+        | Step::Str { line, .. }
+        | Step::Array { line, .. }
+        | Step::Arm { line, .. }
+        | Step::Flow { line, .. } => *line,
+    }
+}
+
+fn number(replay: &Replay, name: &str) -> Option<usize> {
+    replay
+        .value(name)
+        .and_then(Value::as_u64)
+        .map(|value| value as usize)
+}
+
+/// The matrix as a grid: `.` no entry, `#` an entry, `@` the entry a row is matched on.
+fn draw_matrix(replay: &Replay) {
+    let (Some(nrows), Some(ncols)) = (number(replay, "matrix_rows"), number(replay, "matrix_cols"))
+    else {
+        return;
+    };
+    let mut grid = vec![vec!['.'; ncols]; nrows];
+    let entries = replay.value("matrix_entries").and_then(Value::as_array);
+    for entry in entries.into_iter().flatten() {
+        let col = entry.get(0).and_then(Value::as_u64);
+        let row = entry.get(1).and_then(Value::as_u64);
+        if let (Some(col), Some(row)) = (col, row)
+            && let Some(cell) = grid
+                .get_mut(row as usize)
+                .and_then(|cells| cells.get_mut(col as usize))
+        {
+            *cell = '#';
+        }
+    }
+    let matches = replay
+        .value("column_permutations")
+        .and_then(Value::as_array);
+    for (row, col) in matches.into_iter().flatten().enumerate() {
+        // unmatched rows hold -1
+        if let Some(col) = col.as_u64()
+            && let Some(cell) = grid
+                .get_mut(row)
+                .and_then(|cells| cells.get_mut(col as usize))
+        {
+            *cell = '@';
+        }
+    }
+
+    let header: String = (0..ncols).map(|col| format!(" {}", col % 10)).collect();
+    println!("    {header}");
+    for (row, cells) in grid.iter().enumerate() {
+        let cells: String = cells.iter().map(|cell| format!(" {cell}")).collect();
+        println!("r{row:<3}{cells}");
+    }
+}
+
+fn draw(replay: &Replay, source: &[String]) {
+    // clear the terminal and move to its top left
+    print!("\x1b[2J\x1b[H");
+    println!("step {}/{}", replay.position(), replay.steps().len());
+    match replay.current() {
+        Some(step) => {
+            let line = step_line(step);
+            let text = source
+                .get((line as usize).wrapping_sub(1))
+                .map_or("", |text| text.trim());
+            println!("line {line}: {text}");
+            println!("  {step:?}");
+        }
+        None => println!("start of the trace"),
+    }
+    println!();
+    draw_matrix(replay);
+    println!();
+    for (name, value) in replay.values() {
+        if !name.starts_with("matrix_") {
+            println!("{name:>20} = {value}");
+        }
+    }
+    println!();
+    println!("[enter/s] step  [b] back  [g N] go to  [p] play/pause  [q] quit");
+}
+
+/// The commands typed on stdin, read on their own thread so playing can be paused.
+fn commands() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args_os().skip(1);
+    let Some(trace_path) = args.next().map(PathBuf::from) else {
+        eprintln!("{USAGE}");
+        std::process::exit(2);
+    };
+    let source_path = args
+        .next()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("visualizations/btf_viz/src/code/btf_max_transversal.rs"));
+
+    let trace = load_trace(&trace_path)
+        .map_err(|e| format!("failed to load trace {}: {e}", trace_path.display()))?;
+    // without the source the steps are still shown, only their lines go without text
+    let source: Vec<String> = fs::read_to_string(&source_path)
+        .map(|text| text.lines().map(str::to_string).collect())
+        .unwrap_or_default();
+
+    let mut replay = Replay::new(trace);
+    let input = commands();
+    let mut playing = false;
+    draw(&replay, &source);
+    loop {
+        let line = if playing {
+            match input.recv_timeout(FRAME) {
+                Ok(line) => Some(line),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        } else {
+            match input.recv() {
+                Ok(line) => Some(line),
+                Err(_) => break,
+            }
+        };
+
+        match line {
+            // a frame went by while playing
+            None => {
+                replay.step();
+                playing = !replay.is_finished();
+            }
+            Some(line) => match parse_command(&line) {
+                Some(Command::Step) => {
+                    playing = false;
+                    replay.step();
+                }
+                Some(Command::Back) => {
+                    playing = false;
+                    replay.seek(replay.position().saturating_sub(1));
+                }
+                Some(Command::Go(position)) => {
+                    playing = false;
+                    replay.seek(position);
+                }
+                Some(Command::Play) => playing = !playing && !replay.is_finished(),
+                Some(Command::Quit) => break,
+                None => {
+                    draw(&replay, &source);
+                    println!("unknown command: {line}");
+                    continue;
+                }
+            },
+        }
+        draw(&replay, &source);
+    }
+    Ok(())
+}
@@ -1,7 +1,7 @@
-use crate::code::recorder::Recorder;
+use spicy_record::{ControlFlow, Recorder};
 use spicy_simulate::solver::matrix::csc::CscMatrix;
 
-fn try_augmenting_path(
+fn try_augmenting_path<R: Recorder>(
     m: &CscMatrix,
     current_column: usize,
     column_permutations: &mut [isize],
@@ -10,7 +10,7 @@ fn try_augmenting_path(
     row_stack: &mut [usize],
     column_stack: &mut [usize],
     position_stack: &mut [usize],
-    recorder: &mut Recorder,
+    recorder: &mut R,
 ) -> bool {
     let mut found = false;
     let mut head: i64 = 0;
@@ -54,6 +54,7 @@ fn try_augmenting_path(
                     head as usize,
                     &row_stack[head as usize],
                 );
+                recorder.push_flow_step(line!() + 1, ControlFlow::Break);
                 break;
             }
             position_stack[head as usize] = m.col_start(col);
@@ -98,6 +99,7 @@ fn try_augmenting_path(
                     head as usize,
                     &column_stack[head as usize],
                 );
+                recorder.push_flow_step(line!() + 1, ControlFlow::Break);
                 break;
             }
             row_ptr += 1;
@@ -128,10 +130,11 @@ fn try_augmenting_path(
         }
     }
 
+    recorder.push_flow_step(line!() + 1, ControlFlow::Return);
     return found;
 }
 
-pub fn btf_max_transversal(m: &CscMatrix, recorder: &mut Recorder) -> (usize, Vec<isize>) {
+pub fn btf_max_transversal<R: Recorder>(m: &CscMatrix, recorder: &mut R) -> (usize, Vec<isize>) {
     let n = m.dim.ncols;
     let out_of_bounds = n + 1;
 
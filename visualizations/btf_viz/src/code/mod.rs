@@ -1,2 +1 @@
 pub mod btf_max_transversal;
-pub mod recorder;